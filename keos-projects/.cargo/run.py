@@ -64,7 +64,7 @@ def find_qemu_pids():
 
 def is_ignored(file_path):
     IGNORED_PATTERNS = [
-        'target/', 'build/', 'build_objects/', 'keos_kernel', 'ffs.bin', 'sfs.bin', '.o', 'kelibc.a',
+        'target/', 'build/', 'build_objects/', 'keos_kernel', 'ffs.bin', 'sfs.bin', 'swap.bin', '.o', 'kelibc.a',
         '.cargo/template/', 'Cargo.lock',
     ]
     
@@ -389,6 +389,10 @@ def make_run_command(pj):
             '-device', f'virtio-blk-pci,drive=ffs',
             '-drive', f'format=raw,if=none,file=ffs.bin,id=ffs,cache=none',
         ] if pj == 5 else []),
+        *([
+            '-device', f'virtio-blk-pci,drive=swap',
+            '-drive', f'format=raw,if=none,file=swap.bin,id=swap,cache=none',
+        ] if pj == 3 else []),
         *QEMU_CPU_TYPE.split(),
         '-serial', 'mon:stdio', '-no-reboot'
     ]
@@ -406,8 +410,8 @@ def run(pj, run_command):
     setup_kernel_arguments(' '.join(sys.argv[2:]) if sys.argv[2:] else '')
     os.execvp(run_command[0], run_command)
 
-def grade_single(run_command, target, cpu, mem, timeout, live):
-    setup_kernel_arguments('--quite {}'.format(target))
+def grade_single(run_command, target, cpu, mem, timeout, live, args=''):
+    setup_kernel_arguments('--quite {} {}'.format(target, args).strip())
     try:
         if live:
             p = subprocess.Popen(
@@ -461,6 +465,7 @@ def grade(pj, run_command, rubrics, filt = None):
             timeout = conf.get('timeout', 30)
             mem = conf.get('mem', '512M')
             cpu = conf.get('cpu', 4)
+            args = conf.get('args', '')
 
             if filt is not None and name not in filt and filt != []:
                 continue
@@ -468,7 +473,7 @@ def grade(pj, run_command, rubrics, filt = None):
             if filt is None:
                 print(f'Running test: {name} ... ', end='', flush=True)
 
-            output = grade_single(run_command, name, cpu, mem, timeout, filt is not None)
+            output = grade_single(run_command, name, cpu, mem, timeout, filt is not None, args)
             passed = 'test result: ok. 1 passed; 0 failed' in output
             if filt is not None and 'panic' in output:
                 return
@@ -3,9 +3,13 @@
 #include <syscall.h>
 
 int main(int, char *[]);
-void _start(int argc, char *argv[]);
+void _start(int argc, char *argv[], char *envp[]);
 
-void _start(int argc, char *argv[]) {
+/* The environment of this process, set up by the kernel. */
+char **environ;
+
+void _start(int argc, char *argv[], char *envp[]) {
+  environ = envp;
 #ifdef THREADING
   exit_group(main(argc, argv));
 #else
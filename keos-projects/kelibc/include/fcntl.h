@@ -7,5 +7,20 @@
 #define O_RDONLY 00
 #define O_WRONLY 01
 #define O_RDWR 02
+#define O_NONBLOCK 04000
+#define O_DIRECT 040000
+#define O_CLOEXEC 02000000
+
+#define F_GETFD 1
+#define F_SETFD 2
+#define F_GETFL 3
+#define F_SETFL 4
+
+#define FD_CLOEXEC 1
+
+#define LOCK_SH 1
+#define LOCK_EX 2
+#define LOCK_NB 4
+#define LOCK_UN 8
 
 #endif /* lib/fcntl.h */
\ No newline at end of file
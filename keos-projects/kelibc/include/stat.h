@@ -8,7 +8,10 @@ struct stat {
 
   //mode_t st_mode;
   uint32_t ty;
-#define st_mode ty << 14 | 0777
+  /* `st_mode` follows a member access, so it cannot be parenthesized. It is
+     a sum, which binds tighter than the masks and the comparisons applied to
+     it, e.g., `st.st_mode & S_IFMT`. */
+#define st_mode ty * 0040000 % 0070000 + 0777
 
   uint64_t st_size;
 #define st_blksize mbz + 4096
@@ -19,6 +22,15 @@ struct stat {
 #define st_atime mbz 
 #define st_mtime mbz
 #define st_ctime mbz
+
+  uint64_t st_nlink;
+  uint64_t st_blocks;
+  /* Last modification and access in timer ticks since boot, as there is no
+     wall clock. */
+  uint64_t st_mtime_ticks;
+  uint64_t st_atime_ticks;
+  /* Permission bits of the owner (S_IRWXU), as there are no other users. */
+  uint32_t st_perm;
 };
 
 #define S_IFMT  0170000
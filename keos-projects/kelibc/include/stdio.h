@@ -20,6 +20,8 @@ int vhprintf (int, const char *, va_list) PRINTF_FORMAT (2, 0);
 #define SEEK_SET 0
 #define SEEK_CUR 1
 #define SEEK_END 2
+#define SEEK_DATA 3
+#define SEEK_HOLE 4
 
 /* Standard functions. */
 int printf (const char *, ...) PRINTF_FORMAT (1, 2);
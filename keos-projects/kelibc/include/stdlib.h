@@ -3,8 +3,12 @@
 
 #include <stddef.h>
 
+/* Environment of the current process, as passed by the kernel. */
+extern char **environ;
+
 /* Standard functions. */
 int atoi (const char *);
+char *getenv (const char *);
 void qsort (void *array, size_t cnt, size_t size,
 		int (*compare) (const void *, const void *));
 void *bsearch (const void *key, const void *array, size_t cnt,
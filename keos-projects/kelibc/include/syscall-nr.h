@@ -22,6 +22,30 @@
 #define SYS_READDIR 18
 #define SYS_STAT 19
 #define SYS_FSYNC 20
+#define SYS_IOSTAT 21
+#define SYS_MKFIFO 22
+#define SYS_PIPE2 23
+#define SYS_POLL 24
+#define SYS_GETTID 25
+#define SYS_GETPID 26
+#define SYS_GETPPID 27
+#define SYS_WAITPID 28
+#define SYS_MSYNC 29
+#define SYS_SLEEP 30
+#define SYS_CLOCK_GETTIME 31
+#define SYS_FCNTL 32
+#define SYS_FLOCK 33
+#define SYS_FALLOCATE 34
+#define SYS_SENDFILE 35
+#define SYS_CHMOD 36
+#define SYS_EXECVE 37
+#define SYS_GETRSS 38
+#define SYS_SHMGET 39
+#define SYS_SHMAT 40
+#define SYS_ATOMIC_CAS 41
+#define SYS_SCHED_STATS 42
+#define SYS_VFORK 43
+#define SYS_SPAWN 44
 
 /* Only used for Project 3 CoW grading */
 #define SYS_GETPHYS 0x81
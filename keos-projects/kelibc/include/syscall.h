@@ -8,6 +8,40 @@
 #include <stat.h>
 #include <dirent.h>
 
+/* Per-process file I/O statistics, filled by iostat(). */
+struct iostat {
+  uint64_t bytes_read;
+  uint64_t bytes_written;
+  uint64_t read_calls;
+  uint64_t write_calls;
+  uint64_t cache_hits;
+  uint64_t cache_misses;
+};
+
+/* File descriptor actions of spawn(). */
+#define SPAWN_DUP2 0  /* Duplicate FD onto NEWFD. */
+#define SPAWN_CLOSE 1 /* Close FD. */
+
+struct spawn_action {
+  int action;
+  int fd;
+  int newfd;
+};
+
+/* Monotonic clock since boot, filled by clock_gettime(). */
+struct clock_time {
+  uint64_t ticks;
+  uint64_t ns;
+};
+
+/* Per-thread scheduling statistics, filled by sched_stats(). */
+struct sched_stats {
+  uint64_t run_ticks;
+  uint64_t scheduled;
+  uint64_t voluntary_switches;
+  uint64_t involuntary_switches;
+};
+
 __attribute__((always_inline)) static __inline int64_t
 syscall(uint64_t num_, uint64_t a1_, uint64_t a2_, uint64_t a3_, uint64_t a4_,
         uint64_t a5_, uint64_t a6_) {
@@ -74,9 +108,12 @@ off_t tell(int fd);
 int close(int fd);
 int pipe(int pipefd[2]);
 void *mmap(void *addr, size_t length, int prot, int fd, off_t offset);
-int munmap(void *addr);
+int munmap(void *addr, size_t length);
 ssize_t getrandom(void *buf, size_t buflen, unsigned int flags);
 int fork();
+int vfork(void);
+int spawn(const char *pathname, char *const argv[], char *const envp[],
+          const struct spawn_action *actions, int nactions);
 int thread_create(const char *name, void *stack, int (*fn)(void *), void *arg);
 int thread_join(int thread_id, int *exitcode);
 void exit_group(int exitcode);
@@ -87,5 +124,27 @@ int chdir(char *name);
 int readdir(int fd, struct dirent *dirents, int size);
 int stat(const char* pathname, struct stat *stat);
 int fsync(int fd);
+int iostat(struct iostat *buf);
+int mkfifo(char *name);
+int pipe2(int pipefd[2], int flags, size_t size);
+long poll(int *fds, int nfds, long timeout_ticks);
+int gettid(void);
+int getpid(void);
+int getppid(void);
+int waitpid(int pid);
+int msync(void *addr, size_t len);
+int sleep(unsigned long ticks);
+int clock_gettime(struct clock_time *tp);
+int sched_stats(int tid, struct sched_stats *stats);
+int fcntl(int fd, int cmd, long arg);
+int flock(int fd, int operation);
+int fallocate(int fd, off_t offset, off_t len);
+ssize_t sendfile(int out_fd, int in_fd, size_t count);
+int chmod(const char *pathname, mode_t mode);
+int execve(const char *pathname, char *const argv[], char *const envp[]);
+long getrss(void);
+int shmget(long key, size_t size);
+void *shmat(int shmid, void *addr);
+int atomic_cas(uint64_t *addr, uint64_t expected, uint64_t new);
 
 #endif /* lib/user/syscall.h */
\ No newline at end of file
@@ -2,6 +2,7 @@
 #include <debug.h>
 #include <stdlib.h>
 #include <stdbool.h>
+#include <string.h>
 
 /* Converts a string representation of a signed decimal integer
    in S into an `int', which is returned. */
@@ -39,6 +40,24 @@ atoi (const char *s)
   return value;
 }
 
+/* Returns the value of the environment variable NAME, or a null
+   pointer if there is no such variable. */
+char *
+getenv (const char *name)
+{
+  size_t len;
+  char **env;
+
+  if (name == NULL || environ == NULL)
+    return NULL;
+
+  len = strlen (name);
+  for (env = environ; *env != NULL; env++)
+    if (!memcmp (*env, name, len) && (*env)[len] == '=')
+      return *env + len + 1;
+  return NULL;
+}
+
 /* Compares A and B by calling the AUX function. */
 static int
 compare_thunk (const void *a, const void *b, void *aux) 
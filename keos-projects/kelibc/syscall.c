@@ -35,7 +35,9 @@ void *mmap(void *addr, size_t length, int prot, int fd, off_t offset) {
   return (void *)syscall5(SYS_MMAP, addr, length, prot, fd, offset);
 }
 
-int munmap(void *addr) { return syscall1(SYS_MUNMAP, addr); }
+int munmap(void *addr, size_t length) {
+  return syscall2(SYS_MUNMAP, addr, length);
+}
 int fork() { return syscall0(SYS_FORK); }
 
 int thread_create(const char *name, void *stack, int (*fn)(void *), void *arg) {
@@ -61,6 +63,70 @@ int stat(const char* pathname, struct stat *stat) {
   return syscall2(SYS_STAT, pathname, stat);
 }
 int fsync(int fd) { return syscall1(SYS_FSYNC, fd); }
+int iostat(struct iostat *buf) { return syscall1(SYS_IOSTAT, buf); }
+int mkfifo(char *name) { return syscall1(SYS_MKFIFO, name); }
+int pipe2(int pipefd[2], int flags, size_t size) {
+  return syscall3(SYS_PIPE2, pipefd, flags, size);
+}
+long poll(int *fds, int nfds, long timeout_ticks) {
+  return syscall3(SYS_POLL, fds, nfds, timeout_ticks);
+}
+int gettid(void) { return syscall0(SYS_GETTID); }
+int getpid(void) { return syscall0(SYS_GETPID); }
+int getppid(void) { return syscall0(SYS_GETPPID); }
+int waitpid(int pid) { return syscall1(SYS_WAITPID, pid); }
+int msync(void *addr, size_t len) { return syscall2(SYS_MSYNC, addr, len); }
+int sleep(unsigned long ticks) { return syscall1(SYS_SLEEP, ticks); }
+int clock_gettime(struct clock_time *tp) {
+  return syscall1(SYS_CLOCK_GETTIME, tp);
+}
+int sched_stats(int tid, struct sched_stats *stats) {
+  return syscall2(SYS_SCHED_STATS, tid, stats);
+}
+int fcntl(int fd, int cmd, long arg) {
+  return syscall3(SYS_FCNTL, fd, cmd, arg);
+}
+int flock(int fd, int operation) { return syscall2(SYS_FLOCK, fd, operation); }
+int fallocate(int fd, off_t offset, off_t len) {
+  return syscall3(SYS_FALLOCATE, fd, offset, len);
+}
+ssize_t sendfile(int out_fd, int in_fd, size_t count) {
+  return syscall3(SYS_SENDFILE, out_fd, in_fd, count);
+}
+int chmod(const char *pathname, mode_t mode) {
+  return syscall2(SYS_CHMOD, pathname, mode);
+}
+int execve(const char *pathname, char *const argv[], char *const envp[]) {
+  return syscall3(SYS_EXECVE, pathname, argv, envp);
+}
+int spawn(const char *pathname, char *const argv[], char *const envp[],
+          const struct spawn_action *actions, int nactions) {
+  return syscall5(SYS_SPAWN, pathname, argv, envp, actions, nactions);
+}
+long getrss(void) { return syscall0(SYS_GETRSS); }
+int shmget(long key, size_t size) { return syscall2(SYS_SHMGET, key, size); }
+void *shmat(int shmid, void *addr) {
+  return (void *)syscall2(SYS_SHMAT, shmid, addr);
+}
+int atomic_cas(uint64_t *addr, uint64_t expected, uint64_t new) {
+  return syscall3(SYS_ATOMIC_CAS, addr, expected, new);
+}
+
+/* The child of vfork runs on the stack of the parent, and returns from this
+   function before the parent does. Therefore, the return address is kept in
+   a register across the syscall, instead of on the stack which the child may
+   overwrite. */
+#define __STR(X) #X
+#define STR(X) __STR(X)
+__asm__(".globl vfork\n"
+        ".type vfork, @function\n"
+        "vfork:\n"
+        "  pop %rdi\n"
+        "  mov $" STR(SYS_VFORK) ", %eax\n"
+        "  syscall\n"
+        "  push %rdi\n"
+        "  ret\n"
+        ".size vfork, .-vfork\n");
 
 /* "virtual" system call */
 ssize_t getrandom(void *buf, size_t buflen, unsigned int flags) {
@@ -0,0 +1,17 @@
+use keos::cmdline;
+
+/// Tests parsing the kernel command line.
+///
+/// The grader boots the kernel with `foo=bar baz=1 strace` on the command
+/// line, i.e., `cargo run -- cmdline::get foo=bar baz=1 strace`.
+pub fn get() {
+    assert_eq!(cmdline::get("foo"), Some("bar"));
+    assert_eq!(cmdline::get("baz"), Some("1"));
+    assert_eq!(cmdline::get("cmdline::get"), Some(""));
+    assert_eq!(cmdline::get("qux"), None);
+    assert_eq!(cmdline::get("strace"), Some(""));
+    assert!(
+        cmdline::words().eq(["cmdline::get"]),
+        "Options and flags must not select the tests to run."
+    );
+}
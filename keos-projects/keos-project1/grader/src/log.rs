@@ -0,0 +1,38 @@
+use keos::{LogLevel, capture_output, log_enabled, log_level};
+
+/// Tests the log level threshold set by the grader.
+///
+/// The grader boots the kernel with the threshold at [`LogLevel::Warning`].
+/// Informational and debug messages must be suppressed while warnings and
+/// errors still reach the console.
+pub fn level_warning() {
+    assert_eq!(log_level(), LogLevel::Warning);
+    assert!(
+        !log_enabled(LogLevel::Debug),
+        "Debug messages must be suppressed."
+    );
+    assert!(
+        !log_enabled(LogLevel::Info),
+        "Info messages must be suppressed."
+    );
+    assert!(log_enabled(LogLevel::Warning), "Warnings must be printed.");
+    assert!(log_enabled(LogLevel::Error), "Errors must be printed.");
+
+    let output = capture_output(|| {
+        keos::debug!("This debug message must not be printed.");
+        keos::info!("This message must not be printed.");
+        keos::warning!("This warning is printed by log::level_warning.");
+    });
+    assert!(
+        output.contains("[WARN] This warning is printed by log::level_warning.\n"),
+        "Warnings must be printed."
+    );
+    assert!(
+        !output.contains("[DEBUG]"),
+        "Debug messages must be suppressed."
+    );
+    assert!(
+        !output.contains("[INFO]"),
+        "Info messages must be suppressed."
+    );
+}
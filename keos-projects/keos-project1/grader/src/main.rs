@@ -10,20 +10,24 @@ extern crate keos_project1;
 #[macro_use]
 extern crate grading;
 
+mod cmdline;
+mod log;
+mod stack;
 mod syscall;
 
 use alloc::boxed::Box;
-use keos::SystemConfigurationBuilder;
+use keos::{LogLevel, SystemConfigurationBuilder};
 pub use keos_project1::Process;
 
 use crate::syscall::syscall_abi;
 
 #[unsafe(no_mangle)]
-pub unsafe fn main(_config_builder: SystemConfigurationBuilder) {
+pub unsafe fn main(config_builder: SystemConfigurationBuilder) {
     if let Ok(fs) = simple_fs::FileSystem::load(1) {
         keos::info!("Filesystem: use `SimpleFS`.");
         keos::fs::FileSystem::register(fs)
     }
+    config_builder.set_log_level(LogLevel::Warning);
 
     keos::thread::ThreadBuilder::new("test-prehook")
         .attach_task(Box::new(syscall::SyscallAbiValidator::default()))
@@ -57,8 +61,10 @@ pub unsafe fn main(_config_builder: SystemConfigurationBuilder) {
                 &syscall::tell_error_bad_fd,
                 &syscall::stdio_normal,
                 &syscall::stdio_partial,
+                &syscall::stdio_canonical,
                 &syscall::stdout_normal,
                 &syscall::stdout_empty,
+                &syscall::stdout_ansi_stripped,
                 &syscall::stdout_invalid,
                 &syscall::stderr_normal,
                 &syscall::stderr_empty,
@@ -68,6 +74,20 @@ pub unsafe fn main(_config_builder: SystemConfigurationBuilder) {
                 &syscall::pipe_partial,
                 &syscall::pipe_error_bad_direction,
                 &syscall::pipe_error_bad_address,
+                &syscall::pipe2_blocking,
+                &syscall::channel_nonblocking,
+                &syscall::channel_close,
+                &syscall::channel_mpmc,
+                &syscall::pipe2_nonblocking,
+                &syscall::pipe2_error_invalid,
+                &syscall::poll_two_pipes,
+                // Kernel log.
+                &log::level_warning,
+                // Kernel command line.
+                &cmdline::get,
+                // Kernel stack.
+                &stack::guard,
+                &stack::overflow,
             ]);
         });
 }
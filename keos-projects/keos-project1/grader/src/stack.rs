@@ -0,0 +1,56 @@
+use keos::{
+    cmdline,
+    thread::{self, ThreadBuilder, stack_guard},
+};
+
+/// Tests that the guard page of a kernel stack is unmapped, and that it is
+/// traced back to the thread.
+pub fn guard() {
+    let (tid, guard) = thread::with_current(|th| (th.tid, th.stack_guard()));
+    assert!(!stack_guard::is_mapped(guard.start));
+    assert!(stack_guard::is_mapped(guard.end));
+    assert!(stack_guard::is_mapped(guard.start - 0x1000));
+    assert_eq!(stack_guard::find_owner(guard.start.into_usize()), Some(tid));
+    assert_eq!(
+        stack_guard::find_owner(guard.end.into_usize() - 8),
+        Some(tid)
+    );
+    assert_eq!(stack_guard::find_owner(guard.end.into_usize()), None);
+
+    // Each thread has its own guard page.
+    let handle = ThreadBuilder::new("guarded").spawn(move || {
+        let (tid, child) = thread::with_current(|th| (th.tid, th.stack_guard()));
+        assert_ne!(child.start, guard.start);
+        assert!(!stack_guard::is_mapped(child.start));
+        assert_eq!(stack_guard::find_owner(child.start.into_usize()), Some(tid));
+    });
+    assert_eq!(handle.join(), 0);
+}
+
+#[inline(never)]
+fn recurse(depth: usize) -> usize {
+    if depth == usize::MAX {
+        return 0;
+    }
+    let frame = core::hint::black_box([depth as u8; 0x400]);
+    recurse(depth + 1) + frame[depth % 0x400] as usize
+}
+
+/// Tests that a kernel stack overflow is reported with the overflowing
+/// thread.
+///
+/// As the overflow panics the kernel, this test only runs with the `overflow`
+/// option, i.e., `cargo run -- stack::overflow overflow=1`. The kernel must
+/// panic with `kernel stack overflow in thread stack-overflow (tid <tid>)`,
+/// followed by the backtrace of `recurse`.
+pub fn overflow() {
+    if cmdline::get("overflow").is_none() {
+        return;
+    }
+    let handle = ThreadBuilder::new("stack-overflow").spawn(|| {
+        core::hint::black_box(recurse(0));
+    });
+    keos::println!("Overflowing the stack of thread {}.", handle.tid);
+    handle.join();
+    panic!("The stack overflow is not detected.");
+}
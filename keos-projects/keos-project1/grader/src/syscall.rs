@@ -1,5 +1,17 @@
-use keos::{KernelError, fs::FileSystem, syscall::flags::FileMode};
-use keos_project1::{SyscallNumber, syscall::SyscallAbi};
+use alloc::{sync::Arc, vec::Vec};
+use keos::{
+    KernelError,
+    channel::{RecvError, SendError, TryRecvError, TrySendError, channel},
+    fs::FileSystem,
+    sync::atomic::AtomicUsize,
+    syscall::flags::{FileMode, O_NONBLOCK},
+    thread::{ThreadBuilder, ThreadState},
+};
+use keos_project1::{
+    SyscallNumber,
+    poll::{POLL_INFINITE, PollTarget},
+    syscall::SyscallAbi,
+};
 
 #[derive(Default)]
 pub struct SyscallAbiValidator {}
@@ -940,6 +952,53 @@ pub fn stdio_partial() {
     );
 }
 
+/// Test case for canonical reads from standard input.
+///
+/// This test verifies that in the canonical mode:
+/// - Backspace erases the last typed character, and the line is delivered
+///   only after the newline.
+/// - A line longer than the buffer is delivered over several reads.
+/// - The typed characters, including the erase, are echoed.
+/// - A multi-byte UTF-8 character is echoed as typed, and erased at once.
+#[stdin(b"ab\x08c\nKeOS\n\xc3\xa9\xc3\xbc\x08\n")]
+#[assert_output(b"ab\x08 \x08c\nKeOS\n\xc3\xa9\xc3\xbc\x08 \x08\n")]
+pub fn stdio_canonical() {
+    keos::teletype::set_mode(keos::teletype::TtyMode::Canonical);
+
+    // A read returns a single edited line, even if the buffer is larger.
+    let mut buf = [0u8; 8];
+    assert_eq!(
+        syscall!(SyscallNumber::Read as usize, 0, buf.as_mut_ptr(), 8),
+        3
+    );
+    assert_eq!(&buf[..3], b"ac\n");
+
+    // The next line is delivered over two reads.
+    assert_eq!(
+        syscall!(SyscallNumber::Read as usize, 0, buf.as_mut_ptr(), 2),
+        2
+    );
+    assert_eq!(&buf[..2], b"Ke");
+    assert_eq!(
+        syscall!(SyscallNumber::Read as usize, 0, buf.as_mut_ptr(), 8),
+        3
+    );
+    assert_eq!(&buf[..3], b"OS\n");
+
+    // The erased character leaves no byte behind.
+    assert_eq!(
+        syscall!(SyscallNumber::Read as usize, 0, buf.as_mut_ptr(), 8),
+        3
+    );
+    assert_eq!(&buf[..3], "é\n".as_bytes());
+
+    // All input has been consumed.
+    assert_eq!(
+        syscall!(SyscallNumber::Read as usize, 0, buf.as_mut_ptr(), 8),
+        0
+    );
+}
+
 /// Tests normal writing to standard output.
 ///
 /// This test verifies that writing to `stdout` (file descriptor 1) correctly
@@ -969,6 +1028,28 @@ pub fn stdout_normal() {
     );
 }
 
+/// Tests that escape sequences are stripped from the captured standard output.
+///
+/// This test verifies that the color and cursor escapes written to `stdout`
+/// are removed from the captured output, even when a sequence is split across
+/// writes, while the text around them is kept.
+#[stdin(b"")]
+#[assert_output(b"red, green, plain")]
+pub fn stdout_ansi_stripped() {
+    for s in [
+        "\x1b[31mred\x1b[0m, ",
+        "\x1b[1;3",
+        "2mgreen\x1b[0m\x1b[2K",
+        ", \x1b[Hplain",
+    ] {
+        assert_eq!(
+            syscall!(SyscallNumber::Write as usize, 1, s.as_ptr(), s.len()),
+            s.len() as isize,
+            "Writing to stdout should return the number of bytes written."
+        );
+    }
+}
+
 /// Tests writing empty string to standard output.
 ///
 /// This test verifies that writing an empty string to stdout works correctly.
@@ -1338,3 +1419,398 @@ pub fn pipe_error_bad_address() {
         "Creating a pipe with a null pointer should return BadAddress error."
     );
 }
+
+/// Tests a blocking pipe with a given capacity.
+///
+/// This test verifies that a pipe can be filled up to its capacity without
+/// blocking, and that a blocked sender or receiver of the underlying channel
+/// is woken up by its peer.
+pub fn pipe2_blocking() {
+    let mut fds = [0i32; 2];
+    let mut buf = [0u8; 8];
+
+    assert_eq!(
+        syscall!(SyscallNumber::Pipe2 as usize, fds.as_mut_ptr(), 0, 8),
+        0,
+        "Creating a pipe with capacity 8 should return success."
+    );
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Write as usize,
+            fds[1],
+            c"01234567".as_ptr(),
+            8
+        ),
+        8,
+        "Writing up to the capacity should not block."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Read as usize, fds[0], buf.as_mut_ptr(), 8),
+        8,
+        "Reading from the rx fd should return success."
+    );
+    assert_eq!(
+        &buf, b"01234567",
+        "File content mismatch to what was written to tx fd."
+    );
+    assert_eq!(syscall!(SyscallNumber::Close as usize, fds[0]), 0);
+    assert_eq!(syscall!(SyscallNumber::Close as usize, fds[1]), 0);
+
+    // A sender blocked on a full channel is woken up by a receiver.
+    let (tx, rx) = channel::<u8>(8);
+    let sender = ThreadBuilder::new("pipe2_sender").spawn(move || {
+        for i in 0..9 {
+            assert!(tx.send(i).is_ok(), "Sending must succeed.");
+        }
+    });
+    for i in 0..9 {
+        assert_eq!(rx.recv(), Ok(i), "Received value mismatch.");
+    }
+    sender.join();
+
+    // A receiver blocked on an empty channel is woken up when the last sender
+    // hangs up.
+    let (tx, rx) = channel::<u8>(8);
+    let receiver = ThreadBuilder::new("pipe2_receiver").spawn(move || {
+        assert_eq!(
+            rx.recv(),
+            Err(RecvError),
+            "Receiving from a hung-up channel should fail."
+        );
+    });
+    drop(tx);
+    receiver.join();
+}
+
+/// Tests the non-blocking operations of a channel.
+///
+/// This test verifies that `try_send` hands the value back on a full channel
+/// and `try_recv` fails on an empty one, both without blocking.
+pub fn channel_nonblocking() {
+    let (tx, rx) = channel::<u8>(2);
+    assert_eq!(
+        rx.try_recv(),
+        Err(TryRecvError::Empty),
+        "Receiving from an empty channel should fail with Empty."
+    );
+    assert!(
+        tx.try_send(0).is_ok(),
+        "Sending to a channel with room must succeed."
+    );
+    assert!(
+        tx.try_send(1).is_ok(),
+        "Sending to a channel with room must succeed."
+    );
+    assert!(
+        matches!(tx.try_send(2), Err(TrySendError::Full(2))),
+        "Sending to a full channel should return the value back."
+    );
+    assert_eq!(rx.try_recv(), Ok(0), "Received value mismatch.");
+    assert!(
+        tx.try_send(2).is_ok(),
+        "Sending to a channel with room must succeed."
+    );
+    assert_eq!(rx.try_recv(), Ok(1), "Received value mismatch.");
+    assert_eq!(rx.try_recv(), Ok(2), "Received value mismatch.");
+    assert_eq!(
+        rx.try_recv(),
+        Err(TryRecvError::Empty),
+        "Receiving from a drained channel should fail with Empty."
+    );
+    drop(tx);
+    assert_eq!(
+        rx.try_recv(),
+        Err(TryRecvError::Disconnected),
+        "Receiving from a hung-up channel should fail with Disconnected."
+    );
+}
+
+/// Tests closing a channel.
+///
+/// This test verifies that the values sent before closing a channel are
+/// still received before the end of the stream, and that closing a channel
+/// wakes up a blocked receiver and a blocked sender while the other halves
+/// are still alive.
+pub fn channel_close() {
+    let (tx, rx) = channel::<u8>(4);
+    assert!(tx.send(1).is_ok(), "Sending must succeed.");
+    tx.close();
+    assert!(
+        tx.is_closed() && rx.is_closed(),
+        "Both halves must be closed."
+    );
+    assert!(
+        matches!(tx.try_send(2), Err(TrySendError::Disconnected(2))),
+        "Sending to a closed channel should return the value back."
+    );
+    assert!(
+        matches!(tx.send(2), Err(SendError(2))),
+        "Sending to a closed channel should fail."
+    );
+    assert_eq!(
+        rx.recv(),
+        Ok(1),
+        "A value sent before closing must be received."
+    );
+    assert_eq!(
+        rx.recv(),
+        Err(RecvError),
+        "Receiving from a closed and drained channel should fail."
+    );
+
+    // A receiver blocked on an empty channel is woken up by the closure.
+    let (tx, rx) = channel::<u8>(4);
+    let receiver = ThreadBuilder::new("channel_receiver").spawn(move || {
+        assert_eq!(
+            rx.recv(),
+            Err(RecvError),
+            "Receiving from a closed channel should fail."
+        );
+    });
+    while keos::thread::get_state_by_tid(receiver.tid) != Ok(ThreadState::Parked) {
+        core::hint::spin_loop();
+    }
+    tx.close();
+    receiver.join();
+
+    // A sender blocked on a full channel is woken up by the closure.
+    let (tx, rx) = channel::<u8>(1);
+    assert!(tx.send(0).is_ok(), "Sending must succeed.");
+    let sender = ThreadBuilder::new("channel_sender").spawn(move || {
+        assert!(
+            matches!(tx.send(1), Err(SendError(1))),
+            "Sending to a closed channel should fail."
+        );
+    });
+    while keos::thread::get_state_by_tid(sender.tid) != Ok(ThreadState::Parked) {
+        core::hint::spin_loop();
+    }
+    rx.close();
+    sender.join();
+}
+
+/// Tests a channel shared by multiple senders and receivers.
+///
+/// This test verifies that every value sent by several producers is
+/// received exactly once by one of several consumers, through a small buffer
+/// on which both sides block frequently.
+pub fn channel_mpmc() {
+    const PRODUCERS: usize = 4;
+    const CONSUMERS: usize = 4;
+    const VALUES: usize = 1000;
+
+    let (tx, rx) = channel::<usize>(4);
+    let sum = Arc::new(AtomicUsize::new(0));
+    let count = Arc::new(AtomicUsize::new(0));
+
+    let producers = (0..PRODUCERS)
+        .map(|p| {
+            let tx = tx.clone();
+            ThreadBuilder::new("channel_producer").spawn(move || {
+                for i in 0..VALUES {
+                    assert!(tx.send(p * VALUES + i).is_ok(), "Sending must succeed.");
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    let consumers = (0..CONSUMERS)
+        .map(|_| {
+            let (rx, sum, count) = (rx.clone(), sum.clone(), count.clone());
+            ThreadBuilder::new("channel_consumer").spawn(move || {
+                // Receive until all the producers hang up.
+                while let Ok(v) = rx.recv() {
+                    sum.fetch_add(v);
+                    count.fetch_add(1);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop((tx, rx));
+
+    for producer in producers {
+        producer.join();
+    }
+    for consumer in consumers {
+        consumer.join();
+    }
+    let n = PRODUCERS * VALUES;
+    assert_eq!(count.load(), n, "Each value must be received exactly once.");
+    assert_eq!(sum.load(), n * (n - 1) / 2, "Received values mismatch.");
+}
+
+/// Tests a non-blocking pipe at its capacity boundary.
+///
+/// This test verifies that I/O on a non-blocking pipe transfers as many
+/// bytes as possible without waiting, and returns Busy error when no byte can
+/// be transferred.
+pub fn pipe2_nonblocking() {
+    let mut fds = [0i32; 2];
+    let mut buf = [0u8; 12];
+
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Pipe2 as usize,
+            fds.as_mut_ptr(),
+            O_NONBLOCK,
+            8
+        ),
+        0,
+        "Creating a non-blocking pipe should return success."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Read as usize, fds[0], buf.as_mut_ptr(), 12).try_into(),
+        Ok(KernelError::Busy),
+        "Reading from an empty non-blocking pipe should return Busy error."
+    );
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Write as usize,
+            fds[1],
+            c"Hello, keos!".as_ptr(),
+            12
+        ),
+        8,
+        "Writing beyond the capacity should write up to the capacity."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Write as usize, fds[1], c"!".as_ptr(), 1).try_into(),
+        Ok(KernelError::Busy),
+        "Writing to a full non-blocking pipe should return Busy error."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Read as usize, fds[0], buf.as_mut_ptr(), 3),
+        3,
+        "Reading from the rx fd should return success."
+    );
+    assert_eq!(&buf[..3], b"Hel");
+    assert_eq!(
+        syscall!(SyscallNumber::Write as usize, fds[1], c"keos!".as_ptr(), 5),
+        3,
+        "Writing to a partially full pipe should fill the pipe."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Close as usize, fds[1]),
+        0,
+        "Closing the tx should return success.",
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Read as usize, fds[0], buf.as_mut_ptr(), 12),
+        8,
+        "Reading should return the bytes in the pipe."
+    );
+    assert_eq!(
+        &buf[..8],
+        b"lo, kkeo",
+        "File content mismatch to what was written to tx fd."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Read as usize, fds[0], buf.as_mut_ptr(), 12).try_into(),
+        Ok(KernelError::BrokenPipe),
+        "Reading from an empty pipe after closing the tx should return BrokenPipe Error."
+    );
+}
+
+/// Tests pipe2 error with invalid arguments.
+pub fn pipe2_error_invalid() {
+    let mut fds = [0i32; 2];
+
+    assert_eq!(
+        syscall!(SyscallNumber::Pipe2 as usize, fds.as_mut_ptr(), 0, 0).try_into(),
+        Ok(KernelError::InvalidArgument),
+        "Creating a pipe with zero capacity should return InvalidArgument error."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Pipe2 as usize, fds.as_mut_ptr(), 0x1234, 8).try_into(),
+        Ok(KernelError::InvalidArgument),
+        "Creating a pipe with unknown flags should return InvalidArgument error."
+    );
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Pipe2 as usize,
+            core::ptr::null_mut::<u8>(),
+            0,
+            8
+        )
+        .try_into(),
+        Ok(KernelError::BadAddress),
+        "Creating a pipe with a null pointer should return BadAddress error."
+    );
+}
+
+/// Tests polling two pipes where only one has data.
+///
+/// This test verifies that poll reports only the ready file descriptors,
+/// times out when none is ready, and is woken up by a pipe that becomes ready
+/// while waiting.
+pub fn poll_two_pipes() {
+    let mut a = [0i32; 2];
+    let mut b = [0i32; 2];
+
+    assert_eq!(
+        syscall!(SyscallNumber::Pipe2 as usize, a.as_mut_ptr(), 0, 16),
+        0,
+        "Creating a pipe should return success."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Pipe2 as usize, b.as_mut_ptr(), 0, 16),
+        0,
+        "Creating a pipe should return success."
+    );
+
+    let rx = [a[0], b[0]];
+    assert_eq!(
+        syscall!(SyscallNumber::Poll as usize, rx.as_ptr(), 2, 0),
+        0,
+        "Polling empty pipes without waiting should return 0."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Poll as usize, rx.as_ptr(), 2, 10),
+        0,
+        "Polling empty pipes should time out."
+    );
+
+    assert_eq!(
+        syscall!(SyscallNumber::Write as usize, b[1], c"keos".as_ptr(), 4),
+        4,
+        "Writing to the tx fd should return success."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Poll as usize, rx.as_ptr(), 2, POLL_INFINITE),
+        0b10,
+        "Only the pipe with data should be ready."
+    );
+    let all = [a[0], b[0], a[1], b[1]];
+    assert_eq!(
+        syscall!(SyscallNumber::Poll as usize, all.as_ptr(), 4, 0),
+        0b1110,
+        "The pipe with data and the tx fds with room should be ready."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Poll as usize, [1234i32].as_ptr(), 1, 0).try_into(),
+        Ok(KernelError::BadFileDescriptor),
+        "Polling a closed file descriptor should return BadFileDescriptor error."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Poll as usize, rx.as_ptr(), 0, 0).try_into(),
+        Ok(KernelError::InvalidArgument),
+        "Polling no file descriptor should return InvalidArgument error."
+    );
+
+    // A waiting poller is woken up by a pipe that becomes ready.
+    let (tx1, rx1) = channel::<u8>(4);
+    let (tx2, rx2) = channel::<u8>(4);
+    let sender = ThreadBuilder::new("poll_sender").spawn(move || {
+        assert!(tx2.send(0x42).is_ok(), "Sending must succeed.");
+    });
+    assert_eq!(
+        keos_project1::poll::wait(
+            &[PollTarget::Rx(rx1), PollTarget::Rx(rx2.clone())],
+            POLL_INFINITE
+        ),
+        Ok(0b10),
+        "Only the channel with data should be ready."
+    );
+    sender.join();
+    assert_eq!(rx2.recv(), Ok(0x42));
+    drop(tx1);
+}
@@ -172,12 +172,28 @@
 //! [`alloc::collections`]: <https://doc.rust-lang.org/alloc/collections/index.html>
 
 use crate::syscall::SyscallAbi;
-use alloc::collections::BTreeMap;
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    vec::Vec,
+};
 use keos::{
     KernelError,
-    fs::{Directory, RegularFile},
-    syscall::flags::FileMode,
+    channel::{Receiver, Sender, TryRecvError, TrySendError, channel},
+    fs::{CacheAccounting, Directory, FileLock, RegularFile},
+    syscall::{
+        flags::{
+            F_GETFD, F_GETFL, F_SETFD, F_SETFL, FD_CLOEXEC, FileMode, O_CLOEXEC, O_NONBLOCK,
+            SEEK_DATA, SEEK_HOLE,
+        },
+        uaccess::{UserPtrWO, UserU8SliceRO, UserU8SliceWO},
+    },
 };
+
+/// The maximum buffer capacity of a pipe created with
+/// [`FileStruct::pipe2`], in bytes.
+pub const PIPE_MAX_CAPACITY: usize = 0x10000;
 #[cfg(doc)]
 use keos::{channel, teletype};
 
@@ -219,8 +235,9 @@ pub enum FileKind {
         dir: Directory,
         /// The current position in the directory (offset).
         ///
-        /// This field is internally used in readdir() function to track
-        /// how much entries
+        /// This field is internally used in readdir() function as the cursor
+        /// of [`Directory::read_dir_from`], which tracks how much entries
+        /// are read.
         position: usize,
     },
     /// A special file for standard input/output streams.
@@ -308,6 +325,29 @@ pub struct File {
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct FileDescriptor(pub i32);
 
+/// Per-process file I/O statistics.
+///
+/// [`IoStats`] counts the file I/O issued by a process through the `read` and
+/// `write` system calls, together with the page cache accesses they incurred.
+/// It is reported to the user program as-is by the `iostat` system call, so
+/// the layout must match the `struct iostat` of the user library.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct IoStats {
+    /// Total bytes returned by successful `read` calls.
+    pub bytes_read: u64,
+    /// Total bytes consumed by successful `write` calls.
+    pub bytes_written: u64,
+    /// Number of `read` system calls, including the failed ones.
+    pub read_calls: u64,
+    /// Number of `write` system calls, including the failed ones.
+    pub write_calls: u64,
+    /// Number of file blocks served from the page cache.
+    pub cache_hits: u64,
+    /// Number of file blocks loaded from the disk into the page cache.
+    pub cache_misses: u64,
+}
+
 /// The [`FileStruct`] represents the filesystem state for a specific
 /// process, which corresponding to the Linux kernel's `struct files_struct`.
 ///
@@ -332,9 +372,26 @@ pub struct FileDescriptor(pub i32);
 #[derive(Clone)]
 pub struct FileStruct {
     /// The current working directory of the process.
+    ///
+    /// Relative paths given to the system calls are resolved from this
+    /// directory. `chdir` replaces the handle rather than changing the
+    /// directory it refers to, so a copy of the [`FileStruct`] (e.g., by
+    /// `fork`) keeps its own working directory.
     pub cwd: Directory,
     /// The file descriptor table of the process.
     pub files: BTreeMap<FileDescriptor, File>,
+    /// The file I/O statistics of the process.
+    pub io_stats: IoStats,
+    /// The owners of the advisory locks taken through the file descriptors.
+    ///
+    /// See [`flock`](crate::flock) for details.
+    pub flocks: BTreeMap<FileDescriptor, Arc<FileLock>>,
+    /// The file descriptors marked close-on-exec.
+    ///
+    /// These are closed by `execve` with [`FileStruct::close_on_exec`], while
+    /// the others are kept in the new image. A copy of the [`FileStruct`]
+    /// (e.g., by `fork`) keeps the marks.
+    pub cloexec: BTreeSet<FileDescriptor>,
 }
 
 impl Default for FileStruct {
@@ -358,6 +415,9 @@ impl FileStruct {
         let mut this = Self {
             cwd: keos::fs::FileSystem::root(),
             files: BTreeMap::new(),
+            io_stats: IoStats::default(),
+            flocks: BTreeMap::new(),
+            cloexec: BTreeSet::new(),
         };
         this.install_file(File {
             mode: FileMode::Read,
@@ -483,10 +543,15 @@ impl FileStruct {
     /// - Returns [`KernelError::InvalidArgument`] if the calculated position is
     ///   invalid.
     /// - Returns [`KernelError::InvalidArgument`] if the specified file is not a
-    ///   [`FileKind::RegularFile`].
+    ///   [`FileKind::RegularFile`], except for rewinding a
+    ///   [`FileKind::Directory`].
     /// - Returns [`KernelError::BadFileDescriptor`] if specified file descriptor is
     ///   invalid.
     /// - Propagates any errors from underlying APIs (e.g. [`uaccess`](keos::syscall::uaccess)).
+    ///
+    /// A directory can only be rewound, by seeking to the offset 0 with
+    /// `SEEK_SET`. This resets the cursor of the directory, so that the next
+    /// `readdir()` starts from the first entry.
     /// 
     /// # Syscall API
     /// ```c
@@ -500,6 +565,9 @@ impl FileStruct {
     ///   - `SEEK_CUR` (1): The offset is relative to the current file position.
     ///   - `SEEK_END` (2): The offset is relative to the end of the file.
     ///
+    ///   [`SEEK_DATA`] and [`SEEK_HOLE`] are handled by
+    ///   [`FileStruct::seek_sparse`] before reaching here.
+    ///
     /// Returns the new position of the file descriptor after moving it.
     pub fn seek(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
         todo!()
@@ -567,4 +635,445 @@ impl FileStruct {
     pub fn pipe(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
         todo!()
     }
+
+    /// Reads data from an open file, accounting it into the
+    /// [`FileStruct::io_stats`].
+    ///
+    /// This wraps [`FileStruct::read`] and counts the call, the bytes read,
+    /// and the page cache accesses made on behalf of this call.
+    pub fn read_accounted(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        let (hits, misses) = CacheAccounting::current();
+        let result = match self.files.get(&FileDescriptor(abi.arg1 as i32)) {
+            Some(File {
+                file: FileKind::Rx(rx),
+                ..
+            }) if rx.is_nonblocking() => read_nonblocking(rx, abi),
+            _ => self.read(abi),
+        };
+        let stats = &mut self.io_stats;
+        stats.read_calls += 1;
+        if let Ok(bytes) = result {
+            stats.bytes_read += bytes as u64;
+        }
+        stats.account_cache(hits, misses);
+        result
+    }
+
+    /// Writes data to an open file, accounting it into the
+    /// [`FileStruct::io_stats`].
+    ///
+    /// This wraps [`FileStruct::write`] and counts the call, the bytes
+    /// written, and the page cache accesses made on behalf of this call.
+    pub fn write_accounted(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        let (hits, misses) = CacheAccounting::current();
+        let result = match self.files.get(&FileDescriptor(abi.arg1 as i32)) {
+            Some(File {
+                file: FileKind::Tx(tx),
+                ..
+            }) if tx.is_nonblocking() => write_nonblocking(tx, abi),
+            _ => self.write(abi),
+        };
+        let stats = &mut self.io_stats;
+        stats.write_calls += 1;
+        if let Ok(bytes) = result {
+            stats.bytes_written += bytes as u64;
+        }
+        stats.account_cache(hits, misses);
+        result
+    }
+
+    /// Retrieves the file I/O statistics of the process.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int iostat(struct iostat *buf);
+    /// ```
+    /// - `buf`: Buffer to store the statistics.
+    ///
+    /// Returns 0 if success.
+    pub fn iostat(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        UserPtrWO::<IoStats>::new(abi.arg1).put(self.io_stats)?;
+        Ok(0)
+    }
+
+    /// Creates a pipe with the given buffer capacity and flags.
+    ///
+    /// This is a variant of [`FileStruct::pipe`] that allows to choose the
+    /// capacity of the pipe buffer, and to open both ends in the non-blocking
+    /// mode. On a non-blocking pipe, a `read` from an empty pipe and a `write`
+    /// to a full pipe return [`KernelError::Busy`] instead of waiting for the
+    /// other end; otherwise, they transfer as many bytes as possible without
+    /// waiting.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int pipe2(int pipefd[2], int flags, size_t size);
+    /// ```
+    /// - `pipefd`: An array of two file descriptors, where `pipefd[0]` is for
+    ///   reading and `pipefd[1]` is for writing.
+    /// - `flags`: `0` or [`O_NONBLOCK`].
+    /// - `size`: The capacity of the pipe buffer in bytes, which must be in
+    ///   `1..=`[`PIPE_MAX_CAPACITY`].
+    ///
+    /// Returns 0 if success.
+    pub fn pipe2(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        if abi.arg2 & !O_NONBLOCK != 0 || !(1..=PIPE_MAX_CAPACITY).contains(&abi.arg3) {
+            return Err(KernelError::InvalidArgument);
+        }
+        let (mut tx, mut rx) = channel(abi.arg3);
+        tx.set_nonblocking(abi.arg2 & O_NONBLOCK != 0);
+        rx.set_nonblocking(abi.arg2 & O_NONBLOCK != 0);
+
+        let rfd = self.install_file(File {
+            mode: FileMode::Read,
+            file: FileKind::Rx(rx),
+        })?;
+        let wfd = self
+            .install_file(File {
+                mode: FileMode::Write,
+                file: FileKind::Tx(tx),
+            })
+            .inspect_err(|_| {
+                self.files.remove(&rfd);
+            })?;
+        UserPtrWO::<[i32; 2]>::new(abi.arg1)
+            .put([rfd.0, wfd.0])
+            .inspect_err(|_| {
+                self.files.remove(&rfd);
+                self.files.remove(&wfd);
+            })?;
+        Ok(0)
+    }
+
+    /// Opens a file, honoring the [`O_CLOEXEC`] flag.
+    ///
+    /// The flag is stripped before calling [`FileStruct::open`], and the
+    /// opened file descriptor is then marked close-on-exec.
+    pub fn open_cloexec(&mut self, abi: &mut SyscallAbi) -> Result<usize, KernelError> {
+        let cloexec = abi.arg2 & O_CLOEXEC != 0;
+        abi.arg2 &= !O_CLOEXEC;
+        let fd = self.open(abi)?;
+        self.set_cloexec(FileDescriptor(fd as i32), cloexec);
+        Ok(fd)
+    }
+
+    /// Seeks to a new position in the file, honoring [`SEEK_DATA`] and
+    /// [`SEEK_HOLE`].
+    ///
+    /// With these `whence`, the position moves to the next data or hole of
+    /// a sparse file at or after `offset`, as found by
+    /// [`RegularFile::seek_data`] and [`RegularFile::seek_hole`]. Other
+    /// values of `whence` are handled by [`FileStruct::seek`].
+    ///
+    /// # Errors
+    /// - Returns [`KernelError::NoSuchDeviceOrAddress`] if `offset` is not
+    ///   within the file, or there is no data at or after `offset` for
+    ///   [`SEEK_DATA`].
+    /// - Returns [`KernelError::InvalidArgument`] if `offset` is negative or
+    ///   the file is not a [`FileKind::RegularFile`].
+    /// - Returns [`KernelError::BadFileDescriptor`] if specified file
+    ///   descriptor is invalid.
+    pub fn seek_sparse(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        let hole = match abi.arg3 {
+            SEEK_DATA => false,
+            SEEK_HOLE => true,
+            _ => return self.seek(abi),
+        };
+        let offset =
+            usize::try_from(abi.arg2 as isize).map_err(|_| KernelError::InvalidArgument)?;
+        match self.files.get_mut(&FileDescriptor(abi.arg1 as i32)) {
+            Some(File {
+                file: FileKind::RegularFile { file, position },
+                ..
+            }) => {
+                *position = if hole {
+                    file.seek_hole(offset)?
+                } else {
+                    file.seek_data(offset)?
+                };
+                Ok(*position)
+            }
+            Some(_) => Err(KernelError::InvalidArgument),
+            None => Err(KernelError::BadFileDescriptor),
+        }
+    }
+
+    /// Marks or unmarks the file descriptor `fd` close-on-exec.
+    pub fn set_cloexec(&mut self, fd: FileDescriptor, cloexec: bool) {
+        if cloexec {
+            self.cloexec.insert(fd);
+        } else {
+            self.cloexec.remove(&fd);
+        }
+    }
+
+    /// Closes all file descriptors marked close-on-exec.
+    ///
+    /// This is called by `execve` once the new image is loaded. The advisory
+    /// locks held through the closed file descriptors are released as by
+    /// [`FileStruct::close_unlocking`].
+    pub fn close_on_exec(&mut self) {
+        for fd in core::mem::take(&mut self.cloexec) {
+            self.files.remove(&fd);
+            self.flocks.remove(&fd);
+        }
+    }
+
+    /// Gets or sets the flags of an open file.
+    ///
+    /// With [`F_GETFL`], returns the access mode of the file OR-ed with its
+    /// status flags. With [`F_SETFL`], replaces the status flags with `arg`;
+    /// the access mode is fixed when the file is opened, so the access mode
+    /// bits of `arg` are ignored.
+    ///
+    /// The only status flag is [`O_NONBLOCK`], which takes effect on the pipe
+    /// ends. As the I/O on the other files never waits, the flag is ignored
+    /// on them and never reported.
+    ///
+    /// The file descriptor flags are separate from the status flags, as they
+    /// belong to the file descriptor rather than to the open file. They are
+    /// read with [`F_GETFD`] and replaced with [`F_SETFD`]; the only one is
+    /// [`FD_CLOEXEC`].
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int fcntl(int fd, int cmd, long arg);
+    /// ```
+    /// - `fd`: The file descriptor of the open file.
+    /// - `cmd`: [`F_GETFL`], [`F_SETFL`], [`F_GETFD`], or [`F_SETFD`].
+    /// - `arg`: The new flags for [`F_SETFL`] and [`F_SETFD`]; ignored
+    ///   otherwise.
+    ///
+    /// Returns the flags for [`F_GETFL`] and [`F_GETFD`], and 0 for
+    /// [`F_SETFL`] and [`F_SETFD`].
+    pub fn fcntl(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        let fd = FileDescriptor(abi.arg1 as i32);
+        let file = self
+            .files
+            .get_mut(&fd)
+            .ok_or(KernelError::BadFileDescriptor)?;
+        match abi.arg2 {
+            F_GETFD => Ok(if self.cloexec.contains(&fd) {
+                FD_CLOEXEC
+            } else {
+                0
+            }),
+            F_SETFD => {
+                self.set_cloexec(fd, abi.arg3 & FD_CLOEXEC != 0);
+                Ok(0)
+            }
+            F_GETFL => {
+                let nonblocking = match &file.file {
+                    FileKind::Rx(rx) => rx.is_nonblocking(),
+                    FileKind::Tx(tx) => tx.is_nonblocking(),
+                    _ => false,
+                };
+                Ok(file.mode as usize | if nonblocking { O_NONBLOCK } else { 0 })
+            }
+            F_SETFL => {
+                let nonblocking = abi.arg3 & O_NONBLOCK != 0;
+                match &mut file.file {
+                    FileKind::Rx(rx) => rx.set_nonblocking(nonblocking),
+                    FileKind::Tx(tx) => tx.set_nonblocking(nonblocking),
+                    _ => (),
+                }
+                Ok(0)
+            }
+            _ => Err(KernelError::InvalidArgument),
+        }
+    }
+
+    /// Transfers data from a regular file to another open file within the
+    /// kernel.
+    ///
+    /// Reads up to `count` bytes from the position of `in_fd` and writes them
+    /// to `out_fd`, which is either a regular file or the write end of a
+    /// pipe. The data moves through a single kernel buffer one block at a
+    /// time, so it is never copied to or from the user memory, and the reads
+    /// are served from the page cache when the file system has one. The
+    /// transfer stops at the end of the input file.
+    ///
+    /// The position of `in_fd` advances by the number of bytes transferred,
+    /// as does the position of `out_fd` if it is a regular file. A
+    /// non-blocking pipe takes only the bytes that fit in it; a blocking pipe
+    /// waits until all of them are written. The call is accounted into the
+    /// [`FileStruct::io_stats`] as both a read and a write.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// ssize_t sendfile(int out_fd, int in_fd, size_t count);
+    /// ```
+    /// - `out_fd`: A regular file opened for writing, or the write end of a
+    ///   pipe.
+    /// - `in_fd`: A regular file opened for reading.
+    /// - `count`: The maximum number of bytes to transfer.
+    ///
+    /// Returns the number of bytes transferred.
+    pub fn sendfile(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        let (out_fd, in_fd, count) = (
+            FileDescriptor(abi.arg1 as i32),
+            FileDescriptor(abi.arg2 as i32),
+            abi.arg3,
+        );
+        let (input, mut position) = match self.files.get(&in_fd) {
+            Some(File {
+                mode: FileMode::Read | FileMode::ReadWrite,
+                file: FileKind::RegularFile { file, position },
+            }) => (file.clone(), *position),
+            Some(File {
+                file: FileKind::RegularFile { .. },
+                ..
+            })
+            | None => return Err(KernelError::BadFileDescriptor),
+            Some(_) => return Err(KernelError::InvalidArgument),
+        };
+        match self.files.get(&out_fd) {
+            Some(File {
+                mode: FileMode::Write | FileMode::ReadWrite,
+                file: FileKind::RegularFile { .. } | FileKind::Tx(_),
+            }) => (),
+            Some(File {
+                file: FileKind::RegularFile { .. } | FileKind::Tx(_),
+                ..
+            })
+            | None => return Err(KernelError::BadFileDescriptor),
+            Some(_) => return Err(KernelError::InvalidArgument),
+        }
+
+        let (hits, misses) = CacheAccounting::current();
+        let mut buf = Box::new([0u8; 0x1000]);
+        let mut sent = 0;
+        let result = loop {
+            // Keep each read within a single block of the input.
+            let chunk = (count - sent).min(0x1000 - position % 0x1000);
+            if chunk == 0 {
+                break Ok(());
+            }
+            let n = match input.read(position, &mut buf[..chunk]) {
+                Ok(0) => break Ok(()),
+                Ok(n) => n,
+                Err(e) => break Err(e),
+            };
+            let written = match self.files.get_mut(&out_fd) {
+                Some(File {
+                    file: FileKind::RegularFile { file, position },
+                    ..
+                }) => file.write(*position, &buf[..n]).inspect(|w| *position += w),
+                Some(File {
+                    file: FileKind::Tx(tx),
+                    ..
+                }) => send_to_pipe(tx, &buf[..n]),
+                _ => Err(KernelError::BadFileDescriptor),
+            };
+            match written {
+                Ok(w) => {
+                    position += w;
+                    sent += w;
+                    if w < n {
+                        break Ok(());
+                    }
+                }
+                Err(e) => break Err(e),
+            }
+        };
+        if let Some(File {
+            file: FileKind::RegularFile { position: p, .. },
+            ..
+        }) = self.files.get_mut(&in_fd)
+        {
+            *p = position;
+        }
+
+        let stats = &mut self.io_stats;
+        stats.read_calls += 1;
+        stats.write_calls += 1;
+        stats.bytes_read += sent as u64;
+        stats.bytes_written += sent as u64;
+        stats.account_cache(hits, misses);
+        match result {
+            Err(e) if sent == 0 => Err(e),
+            _ => Ok(sent),
+        }
+    }
+}
+
+// Writes `buf` to a pipe for `sendfile`. A non-blocking pipe takes only the
+// bytes that fit in the pipe.
+fn send_to_pipe(tx: &Sender<u8>, buf: &[u8]) -> Result<usize, KernelError> {
+    let mut written = 0;
+    for &b in buf {
+        let result = if tx.is_nonblocking() {
+            tx.try_send(b).map_err(|e| match e {
+                TrySendError::Full(_) => KernelError::Busy,
+                TrySendError::Disconnected(_) => KernelError::BrokenPipe,
+            })
+        } else {
+            tx.send(b).map_err(|_| KernelError::BrokenPipe)
+        };
+        match result {
+            Ok(()) => written += 1,
+            Err(e) if written == 0 => return Err(e),
+            Err(_) => break,
+        }
+    }
+    Ok(written)
+}
+
+// Reads from a non-blocking pipe, taking only the bytes already in the pipe.
+//
+// The bytes taken off the pipe cannot be put back, so the buffer is checked
+// to be writable, by putting nothing into it, before taking any.
+fn read_nonblocking(rx: &Receiver<u8>, abi: &SyscallAbi) -> Result<usize, KernelError> {
+    if abi.arg3 == 0 {
+        return Ok(0);
+    }
+    UserU8SliceWO::new(abi.arg2, abi.arg3).put(&[])?;
+    let mut buf = Vec::new();
+    let mut disconnected = false;
+    while buf.len() < abi.arg3 {
+        match rx.try_recv() {
+            Ok(b) => buf.push(b),
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Disconnected) => {
+                disconnected = true;
+                break;
+            }
+        }
+    }
+    match buf.len() {
+        0 if disconnected => Err(KernelError::BrokenPipe),
+        0 => Err(KernelError::Busy),
+        _ => UserU8SliceWO::new(abi.arg2, buf.len()).put(&buf),
+    }
+}
+
+// Writes to a non-blocking pipe, taking only the bytes that fit in the pipe.
+fn write_nonblocking(tx: &Sender<u8>, abi: &SyscallAbi) -> Result<usize, KernelError> {
+    let buf = UserU8SliceRO::new(abi.arg2, abi.arg3).get()?;
+    let mut written = 0;
+    for b in buf {
+        match tx.try_send(b) {
+            Ok(()) => written += 1,
+            Err(TrySendError::Full(_)) => break,
+            Err(TrySendError::Disconnected(_)) if written == 0 => {
+                return Err(KernelError::BrokenPipe);
+            }
+            Err(TrySendError::Disconnected(_)) => break,
+        }
+    }
+    if written == 0 && abi.arg3 != 0 {
+        Err(KernelError::Busy)
+    } else {
+        Ok(written)
+    }
+}
+
+impl IoStats {
+    // Adds the page cache accesses of the current thread since the snapshot
+    // of (`hits`, `misses`).
+    fn account_cache(&mut self, hits: u64, misses: u64) {
+        let (now_hits, now_misses) = CacheAccounting::current();
+        self.cache_hits += now_hits - hits;
+        self.cache_misses += now_misses - misses;
+    }
 }
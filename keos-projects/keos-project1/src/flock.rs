@@ -0,0 +1,125 @@
+//! # Advisory file locking.
+//!
+//! Processes that write a shared file concurrently must coordinate, or their
+//! updates interleave. The `flock` system call provides advisory locks for
+//! this: a file is locked either by any number of shared locks (e.g., by the
+//! readers), or by a single exclusive lock (e.g., by a writer). A process that
+//! asks for a conflicting lock sleeps until the lock is released.
+//!
+//! The locks are kept per inode in a kernel table (see [`FileLock`]), and are
+//! owned by the open file: a lock taken through a file descriptor is shared
+//! with its copies made by `fork`, and is released when all of them are
+//! closed, or when the processes holding them exit.
+//!
+//! The file descriptor table keeps the owners in [`FileStruct::flocks`], so
+//! that closing the file with [`FileStruct::close_unlocking`] or dropping the
+//! table releases the lock.
+
+use crate::{
+    file_struct::{File, FileDescriptor, FileKind, FileStruct},
+    syscall::SyscallAbi,
+};
+use alloc::sync::Arc;
+use keos::{
+    KernelError,
+    fs::{FileLock, FlockKind},
+};
+
+/// Takes a shared lock.
+pub const LOCK_SH: usize = 1;
+
+/// Takes an exclusive lock.
+pub const LOCK_EX: usize = 2;
+
+/// Fails with [`KernelError::Busy`] instead of waiting for a conflicting lock.
+///
+/// This can be OR-ed with [`LOCK_SH`] or [`LOCK_EX`].
+pub const LOCK_NB: usize = 4;
+
+/// Releases the held lock.
+pub const LOCK_UN: usize = 8;
+
+impl FileStruct {
+    /// Finds the owner of the advisory lock of the file descriptor given to
+    /// the `flock` system call, creating it on the first lock.
+    ///
+    /// The owner is shared, so that the caller can [`apply`] the operation
+    /// without holding the [`FileStruct`].
+    ///
+    /// # Errors
+    /// - [`KernelError::BadFileDescriptor`] if `fd` is not open.
+    /// - [`KernelError::InvalidArgument`] if `fd` is not a regular file or a
+    ///   directory, or `operation` is invalid.
+    pub fn flock_owner(&mut self, abi: &SyscallAbi) -> Result<Arc<FileLock>, KernelError> {
+        let fd = FileDescriptor(abi.arg1 as i32);
+        let ino = match self.files.get(&fd) {
+            Some(File {
+                file: FileKind::RegularFile { file, .. },
+                ..
+            }) => file.ino(),
+            Some(File {
+                file: FileKind::Directory { dir, .. },
+                ..
+            }) => dir.ino(),
+            Some(_) => return Err(KernelError::InvalidArgument),
+            None => return Err(KernelError::BadFileDescriptor),
+        };
+        if !matches!(abi.arg2 & !LOCK_NB, LOCK_SH | LOCK_EX | LOCK_UN) {
+            return Err(KernelError::InvalidArgument);
+        }
+        Ok(self
+            .flocks
+            .entry(fd)
+            .or_insert_with(|| Arc::new(FileLock::new(ino)))
+            .clone())
+    }
+
+    /// Applies or removes an advisory lock on an open file.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int flock(int fd, int operation);
+    /// ```
+    /// - `fd`: The file descriptor of the open file.
+    /// - `operation`: [`LOCK_SH`], [`LOCK_EX`], or [`LOCK_UN`]. [`LOCK_SH`]
+    ///   and [`LOCK_EX`] can be OR-ed with [`LOCK_NB`].
+    ///
+    /// Calling it on a file that is already locked converts the lock into the
+    /// given kind.
+    ///
+    /// Returns 0 if success.
+    pub fn flock(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        let owner = self.flock_owner(abi)?;
+        apply(&owner, abi.arg2)
+    }
+
+    /// Closes an open file, releasing the advisory lock held through it if
+    /// no other copy of the file descriptor holds it.
+    ///
+    /// This wraps [`FileStruct::close`]. The close-on-exec mark of the file
+    /// descriptor is cleared as well, so that it does not stick to a file
+    /// opened later with the same number.
+    pub fn close_unlocking(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        let result = self.close(abi);
+        if result.is_ok() {
+            let fd = FileDescriptor(abi.arg1 as i32);
+            self.flocks.remove(&fd);
+            self.cloexec.remove(&fd);
+        }
+        result
+    }
+}
+
+/// Applies the `flock` `operation` on the lock `owner`.
+///
+/// See [`FileStruct::flock`] for the operations.
+pub fn apply(owner: &FileLock, operation: usize) -> Result<usize, KernelError> {
+    let nonblocking = operation & LOCK_NB != 0;
+    match operation & !LOCK_NB {
+        LOCK_SH => owner.lock(FlockKind::Shared, nonblocking)?,
+        LOCK_EX => owner.lock(FlockKind::Exclusive, nonblocking)?,
+        LOCK_UN => owner.unlock(),
+        _ => return Err(KernelError::InvalidArgument),
+    }
+    Ok(0)
+}
@@ -0,0 +1,161 @@
+//! # Waiting on multiple file descriptors.
+//!
+//! With pipes, a process often has to wait for data from several sources at
+//! once: reading a pipe that has no data blocks the process, even if another
+//! pipe already has something to read. The `poll` system call solves this by
+//! waiting until *any* of the given file descriptors becomes ready, i.e., a
+//! `read` (or `write`) on it would not block.
+//!
+//! A regular file, a directory, and the standard I/O are always ready. The
+//! read end of a pipe is ready when the pipe has data or all writers have
+//! hung up, and the write end is ready when the pipe has a room or all
+//! readers have hung up.
+//!
+//! To wait, [`wait`] registers a [`Poller`] on every pipe, and is woken up by
+//! the first pipe that changes its state. See [`keos::poll`] for how the
+//! poller avoids lost wakeups.
+
+use crate::{
+    file_struct::{File, FileDescriptor, FileKind, FileStruct},
+    syscall::SyscallAbi,
+};
+use alloc::{sync::Arc, vec::Vec};
+use keos::{
+    KernelError,
+    channel::{Receiver, Sender},
+    poll::Poller,
+    syscall::uaccess::UserPtrRO,
+    time::now_ticks,
+};
+
+/// The maximum number of file descriptors waited by a single `poll`.
+pub const POLL_MAX_FDS: usize = 32;
+
+/// The timeout of `poll` that waits without a time limit.
+pub const POLL_INFINITE: usize = usize::MAX;
+
+/// A file descriptor waited by the `poll` system call.
+pub enum PollTarget {
+    /// A file that is always ready, such as a regular file.
+    Ready,
+    /// The read end of a pipe.
+    Rx(Receiver<u8>),
+    /// The write end of a pipe.
+    Tx(Sender<u8>),
+}
+
+impl PollTarget {
+    fn is_ready(&self) -> bool {
+        match self {
+            PollTarget::Ready => true,
+            PollTarget::Rx(rx) => rx.is_ready(),
+            PollTarget::Tx(tx) => tx.is_ready(),
+        }
+    }
+
+    fn register(&self, poller: &Arc<Poller>) {
+        match self {
+            PollTarget::Ready => (),
+            PollTarget::Rx(rx) => rx.register_poller(poller),
+            PollTarget::Tx(tx) => tx.register_poller(poller),
+        }
+    }
+
+    fn deregister(&self, poller: &Arc<Poller>) {
+        match self {
+            PollTarget::Ready => (),
+            PollTarget::Rx(rx) => rx.deregister_poller(poller),
+            PollTarget::Tx(tx) => tx.deregister_poller(poller),
+        }
+    }
+}
+
+impl FileStruct {
+    /// Collects the [`PollTarget`]s of the file descriptors given to the
+    /// `poll` system call.
+    ///
+    /// The targets hold their own handles to the pipes, so that the caller
+    /// can [`wait`] on them without holding the [`FileStruct`].
+    ///
+    /// # Errors
+    /// - [`KernelError::InvalidArgument`] if `nfds` is not in
+    ///   `1..=`[`POLL_MAX_FDS`].
+    /// - [`KernelError::BadAddress`] if `fds` is not accessible.
+    /// - [`KernelError::BadFileDescriptor`] if any of `fds` is not open.
+    pub fn poll_targets(&self, abi: &SyscallAbi) -> Result<Vec<PollTarget>, KernelError> {
+        if !(1..=POLL_MAX_FDS).contains(&abi.arg2) {
+            return Err(KernelError::InvalidArgument);
+        }
+        (0..abi.arg2)
+            .map(|i| {
+                let fd = UserPtrRO::<i32>::new(abi.arg1 + i * core::mem::size_of::<i32>()).get()?;
+                match self.files.get(&FileDescriptor(fd)) {
+                    Some(File {
+                        file: FileKind::Rx(rx),
+                        ..
+                    }) => Ok(PollTarget::Rx(rx.clone())),
+                    Some(File {
+                        file: FileKind::Tx(tx),
+                        ..
+                    }) => Ok(PollTarget::Tx(tx.clone())),
+                    Some(_) => Ok(PollTarget::Ready),
+                    None => Err(KernelError::BadFileDescriptor),
+                }
+            })
+            .collect()
+    }
+
+    /// Waits until at least one of the file descriptors becomes ready.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// long poll(int *fds, int nfds, long timeout_ticks);
+    /// ```
+    /// - `fds`: An array of file descriptors to wait on.
+    /// - `nfds`: The number of file descriptors in `fds`, up to
+    ///   [`POLL_MAX_FDS`].
+    /// - `timeout_ticks`: The maximum number of timer ticks (1ms) to wait.
+    ///   `0` returns immediately, and `-1` ([`POLL_INFINITE`]) waits without a
+    ///   time limit.
+    ///
+    /// Returns a bitmask of the ready file descriptors, where the bit `i` is
+    /// set if `fds[i]` is ready. Returns 0 if timed out.
+    pub fn poll(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        wait(&self.poll_targets(abi)?, abi.arg3)
+    }
+}
+
+/// Waits until at least one of the `targets` becomes ready, or `timeout`
+/// ticks elapse.
+///
+/// Returns a bitmask of the ready targets, which is 0 if timed out.
+pub fn wait(targets: &[PollTarget], timeout: usize) -> Result<usize, KernelError> {
+    let ready = || {
+        targets
+            .iter()
+            .enumerate()
+            .filter(|(_, target)| target.is_ready())
+            .fold(0, |mask, (i, _)| mask | 1 << i)
+    };
+    let deadline = (timeout != POLL_INFINITE).then(|| now_ticks().saturating_add(timeout as u64));
+
+    // Register the poller before checking the readiness, so that an event
+    // after the check wakes up (or prevents) the wait below.
+    let poller = Poller::new();
+    for target in targets {
+        target.register(&poller);
+    }
+    let mask = loop {
+        let mask = ready();
+        if mask != 0 {
+            break mask;
+        }
+        if !poller.wait(deadline) {
+            break ready();
+        }
+    };
+    for target in targets {
+        target.deregister(&poller);
+    }
+    Ok(mask)
+}
@@ -34,14 +34,17 @@ pub unsafe fn main(_config_builder: SystemConfigurationBuilder) {
         &page_table::complicate,
         &page_table::x86_permission,
         &page_table::x86_permission_advanced,
+        &page_table::iter_mappings,
         // Mmap.
         &mm_struct::do_mmap,
         &mm_struct::access_ok_normal,
         &mm_struct::access_ok_invalid,
         &mm_struct::bad_addr_0,
         &mm_struct::get_user_page,
+        &mm_struct::uaccess,
         // Loader.
         &userprog::arg_parse,
+        &userprog::env_parse,
         &userprog::loader_bss_sanity,
         &userprog::sys_open,
         &userprog::sys_read,
@@ -57,6 +60,8 @@ pub unsafe fn main(_config_builder: SystemConfigurationBuilder) {
         &userprog::sys_stdout,
         &userprog::sys_stderr,
         &userprog::sys_pipe,
+        &userprog::sys_execve,
+        &userprog::sys_execve_cloexec,
         &userprog::mm_mmap,
         &userprog::mm_mmap_error_bad_addr,
         &userprog::mm_mmap_error_bad_fd,
@@ -64,12 +69,25 @@ pub unsafe fn main(_config_builder: SystemConfigurationBuilder) {
         &userprog::mm_mmap_error_protection_exec,
         &userprog::mm_munmap,
         &userprog::mm_munmap2,
+        &userprog::mm_munmap_partial,
         &userprog::mm_munmap_error_bad_addr,
         &userprog::mm_munmap_error_double_free,
         &userprog::mm_munmap_error_unaligned,
         &userprog::mm_exit_cleanup_stress,
         &userprog::bad_addr_1,
         &userprog::bad_code_write,
+        &userprog::bad_addr_backtrace,
+        &userprog::sys_trace,
+        &userprog::loader_noexec_stack,
+        &userprog::loader_interp,
+        &userprog::loader_overlap_stack,
+        &userprog::loader_overlap_segment,
+        &userprog::loader_share_page,
+        &userprog::loader_low_segment,
+        &userprog::loader_wx_segment,
+        &userprog::loader_wx_stack,
+        &userprog::loader_phnum,
+        &userprog::loader_phnum_malformed,
     ]);
 }
 
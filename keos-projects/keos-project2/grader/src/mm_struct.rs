@@ -1,9 +1,12 @@
+use alloc::boxed::Box;
 use keos::{
     KernelError,
     addressing::Va,
     mm::page_table::{Permission, Pml4e},
+    syscall::uaccess::{copy_from_user, copy_to_user, strncpy_from_user},
+    thread::ThreadBuilder,
 };
-use keos_project2::{eager_pager::EagerPager, mm_struct::MmStruct, pager::Pager};
+use keos_project2::{Process, eager_pager::EagerPager, mm_struct::MmStruct, pager::Pager};
 
 pub fn do_mmap() {
     let mut mm: MmStruct<EagerPager> = MmStruct::new();
@@ -184,9 +187,111 @@ pub fn get_user_page() {
     );
 
     assert!(
-        mm.pager.munmap(&mut mm.page_table, va).is_ok(),
+        mm.pager.munmap(&mut mm.page_table, va, 0x1000).is_ok(),
         "munmap() should succeed"
     );
 
     keos::mm::page_table::load_pt(prev_cr3);
 }
+
+pub fn uaccess() {
+    let mut mm: MmStruct<EagerPager> = MmStruct::new();
+    // [0x1000, 0x3000): read-write, [0x3000, 0x4000): hole,
+    // [0x4000, 0x5000): read-only.
+    assert_eq!(
+        mm.do_mmap(
+            Va::new(0x1000).unwrap(),
+            0x2000,
+            Permission::READ | Permission::WRITE,
+            None,
+            0
+        ),
+        Ok(0x1000),
+        "mmap() to valid Virtual Address should succeed"
+    );
+    assert_eq!(
+        mm.do_mmap(Va::new(0x4000).unwrap(), 0x1000, Permission::READ, None, 0),
+        Ok(0x4000),
+        "mmap() to valid Virtual Address should succeed"
+    );
+
+    ThreadBuilder::new("uaccess")
+        .attach_task(Box::new(Process::from_mm_struct(mm)))
+        .spawn(|| {
+            // Valid pointers.
+            assert_eq!(copy_to_user(0x1000, 0x1122_3344_5566_7788u64), Ok(()));
+            assert_eq!(copy_from_user::<u64>(0x1000), Ok(0x1122_3344_5566_7788));
+            assert_eq!(
+                copy_to_user(0x1ffc, 0xcafe_beefu64),
+                Ok(()),
+                "Copying an object across mapped pages should succeed."
+            );
+            assert_eq!(copy_from_user::<u64>(0x1ffc), Ok(0xcafe_beef));
+            assert_eq!(copy_from_user::<u64>(0x4000), Ok(0));
+            assert_eq!(
+                copy_to_user(0x4000, 0u64),
+                Err(KernelError::BadAddress),
+                "Writing to a read-only page should fail."
+            );
+
+            // Partially-mapped pointers.
+            assert_eq!(
+                copy_from_user::<u64>(0x2ffc),
+                Err(KernelError::BadAddress),
+                "Reading an object that spans an unmapped page should fail."
+            );
+            assert_eq!(
+                copy_to_user(0x2ffc, u64::MAX),
+                Err(KernelError::BadAddress),
+                "Writing an object that spans an unmapped page should fail."
+            );
+            assert_eq!(
+                copy_from_user::<u32>(0x2ffc),
+                Ok(0),
+                "A failed write must not modify the mapped part."
+            );
+            assert_eq!(
+                copy_from_user::<u64>(0xffc),
+                Err(KernelError::BadAddress),
+                "Reading an object that spans the 0th page should fail."
+            );
+
+            // Wholly-invalid pointers.
+            for addr in [0, 0xdead_beef, 0xffff_ff00_0090_0000, usize::MAX - 3] {
+                assert_eq!(copy_from_user::<u64>(addr), Err(KernelError::BadAddress));
+                assert_eq!(copy_to_user(addr, 0u64), Err(KernelError::BadAddress));
+                assert_eq!(strncpy_from_user(addr, 16), Err(KernelError::BadAddress));
+            }
+
+            // Strings.
+            for (i, b) in b"keos\0".iter().enumerate() {
+                assert_eq!(copy_to_user(0x1ffe + i, *b), Ok(()));
+            }
+            assert_eq!(
+                strncpy_from_user(0x1ffe, 16),
+                Ok("keos".into()),
+                "Reading a string across mapped pages should succeed."
+            );
+            assert_eq!(strncpy_from_user(0x1ffe, 5), Ok("keos".into()));
+            assert_eq!(
+                strncpy_from_user(0x1ffe, 4),
+                Err(KernelError::NameTooLong),
+                "Reading a string longer than the limit should fail."
+            );
+            for (i, b) in b"abc\0".iter().enumerate() {
+                assert_eq!(copy_to_user(0x2ffc + i, *b), Ok(()));
+            }
+            assert_eq!(
+                strncpy_from_user(0x2ffc, 16),
+                Ok("abc".into()),
+                "Reading a string that ends right before an unmapped page should succeed."
+            );
+            assert_eq!(copy_to_user(0x2fff, b'!'), Ok(()));
+            assert_eq!(
+                strncpy_from_user(0x2ffc, 16),
+                Err(KernelError::BadAddress),
+                "Reading a string that runs into an unmapped page should fail."
+            );
+        })
+        .join();
+}
@@ -370,3 +370,42 @@ pub fn complicate() {
     // No explicit unmap is performed here—`#[validate_alloc]` ensures all pages
     // are freed at drop.
 }
+
+/// A test to verify that `PageTable::iter_mappings()` yields exactly the
+/// mapped pages, in the order of the virtual address, with their permissions.
+pub fn iter_mappings() {
+    let mut pgtbl = PageTable(PageTableRoot::new_boxed());
+    assert_eq!(pgtbl.iter_mappings().count(), 0);
+
+    // Regions across different PT, PD, and PDP tables, in ascending order.
+    let regions = [
+        (
+            0x400000,
+            3,
+            Permission::READ | Permission::EXECUTABLE | Permission::USER,
+        ),
+        (
+            0x601000,
+            2,
+            Permission::READ | Permission::WRITE | Permission::USER,
+        ),
+        (0x7fff_ffff_e000, 2, Permission::READ | Permission::WRITE),
+        (0x8000_0000_0000 - 0x1000 * 513, 1, Permission::READ),
+    ];
+    let mut expected = alloc::vec::Vec::new();
+    for (base, npages, perm) in regions {
+        for i in 0..npages {
+            let va = Va::new(base + i * 0x1000).unwrap();
+            let pg = Page::new();
+            expected.push((va, pg.pa(), perm));
+            assert!(pgtbl.map(va, pg, perm).is_ok());
+        }
+    }
+    expected.sort_by_key(|(va, _, _)| va.into_usize());
+    assert!(pgtbl.iter_mappings().eq(expected.iter().copied()));
+
+    // An unmapped page is no longer yielded.
+    let (va, _, _) = expected.remove(3);
+    assert!(pgtbl.unmap(va).is_ok());
+    assert!(pgtbl.iter_mappings().eq(expected.iter().copied()));
+}
@@ -1,30 +1,51 @@
 use crate::Process;
 use alloc::boxed::Box;
-use keos::thread::ThreadBuilder;
-use keos_project2::{loader::LoadContext, mm_struct::MmStruct};
+use keos::{KernelError, thread::ThreadBuilder};
+use keos_project2::{
+    eager_pager::EagerPager,
+    loader::{
+        LoadContext, WxPolicy,
+        elf::{Elf, PN_XNUM},
+    },
+};
 
 pub fn run_elf(name: &str) -> i32 {
     run_elf_with_arg(name, &[name])
 }
 
 pub fn run_elf_with_arg(name: &str, args: &[&str]) -> i32 {
-    let LoadContext { mm_struct, regs } = LoadContext {
-        mm_struct: MmStruct::new(),
-        regs: keos::syscall::Registers::new(),
-    }
-    .load(
-        &keos::fs::FileSystem::root()
-            .open(name)
-            .unwrap()
-            .into_regular_file()
-            .unwrap(),
-        args,
-    )
-    .unwrap_or_else(|e| panic!("Failed to load elf: {}. reason: {:?}", name, e));
+    run_elf_with_env(name, args, &[])
+}
+
+pub fn run_elf_with_env(name: &str, args: &[&str], env: &[&str]) -> i32 {
+    run_elf_with_policy(name, args, env, WxPolicy::default())
+}
+
+pub fn run_elf_with_policy(name: &str, args: &[&str], env: &[&str], wx_policy: WxPolicy) -> i32 {
+    let LoadContext {
+        mm_struct,
+        regs,
+        symbols,
+        ..
+    } = LoadContext::new()
+        .with_wx_policy(wx_policy)
+        .load_with_env(
+            &keos::fs::FileSystem::root()
+                .open(name)
+                .unwrap()
+                .into_regular_file()
+                .unwrap(),
+            args,
+            env,
+        )
+        .unwrap_or_else(|e| panic!("Failed to load elf: {}. reason: {:?}", name, e));
 
     ThreadBuilder::new(name)
         .attach_task(Box::new(Process::from_mm_struct(mm_struct)))
-        .spawn(move || regs.launch())
+        .spawn(move || {
+            keos::task::set_user_symbols(symbols);
+            regs.launch()
+        })
         .join()
 }
 
@@ -41,6 +62,20 @@ pub fn arg_parse() {
     run_elf_with_arg("arg_parse", &["/bin/ls", "-l", "foo", "bar"]);
 }
 
+#[stdin(b"")]
+#[assert_output(
+    b"envp[0] = HOME=/
+envp[1] = KEOS=is fun
+KEOS=is fun
+"
+)]
+pub fn env_parse() {
+    assert_eq!(
+        run_elf_with_env("env_parse", &["env_parse"], &["HOME=/", "KEOS=is fun"]),
+        0
+    );
+}
+
 #[stdin(b"")]
 #[assert_output(b"success ")]
 pub fn sys_open() {
@@ -168,6 +203,12 @@ pub fn mm_munmap2() {
     run_elf("mm_munmap2");
 }
 
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn mm_munmap_partial() {
+    run_elf("mm_munmap_partial");
+}
+
 #[stdin(b"")]
 #[assert_output(b"success ")]
 pub fn mm_munmap_error_bad_addr() {
@@ -197,12 +238,172 @@ pub fn bad_code_write() {
     assert_eq!(run_elf("bad_code_write"), -1);
 }
 
+#[stdin(b"")]
+pub fn bad_addr_backtrace() {
+    assert_eq!(run_elf("bad_addr_backtrace"), -1);
+    let output = keos::thread::with_current(|th| th.finish_hook()).unwrap();
+    assert!(
+        output.contains("- fault_here+0x"),
+        "The backtrace must name the faulting function:\n{output}"
+    );
+    assert!(
+        output.contains("- main+0x"),
+        "The backtrace must name the caller of the faulting function:\n{output}"
+    );
+}
+
+#[stdin(b"")]
+pub fn sys_trace() {
+    keos::syscall::trace::set_trace(true);
+    let status = run_elf("sys_trace");
+    keos::syscall::trace::set_trace(false);
+    assert_eq!(status, 0);
+
+    let output = keos::thread::with_current(|th| th.finish_hook()).unwrap();
+    let mut rest = output.as_str();
+    for expected in [
+        "open(path=\"hello\", mode=0) = 3\n",
+        "read(fd=3, buf=0x",
+        ", count=7) = 7\n",
+        // The output of a call precedes its trace.
+        "Welcome",
+        "write(fd=1, buf=0x",
+        ", count=7) = 7\n",
+        "close(fd=3) = 0\n",
+        "close(fd=3) = -",
+        "exit(status=0) = ?\n",
+    ] {
+        match rest.find(expected) {
+            Some(pos) => rest = &rest[pos + expected.len()..],
+            None => panic!("The trace must contain `{expected}` in order:\n{output}"),
+        }
+    }
+}
+
 #[stdin(b"")]
 #[assert_output(b"success ")]
 pub fn loader_bss_sanity() {
     run_elf("loader_bss_sanity");
 }
 
+#[stdin(b"")]
+#[assert_output(b"before execve after execve ")]
+pub fn sys_execve() {
+    assert_eq!(run_elf("sys_execve"), 0);
+}
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn sys_execve_cloexec() {
+    assert_eq!(run_elf("sys_execve_cloexec"), 0);
+}
+
+pub fn loader_noexec_stack() {
+    assert_eq!(run_elf("loader_noexec_stack"), -1);
+}
+
+fn load_error(name: &str) -> Option<KernelError> {
+    let file = keos::fs::FileSystem::root()
+        .open(name)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    LoadContext::<EagerPager>::new().load(&file, &[name]).err()
+}
+
+pub fn loader_interp() {
+    assert_eq!(load_error("loader_interp"), Some(KernelError::NoExec));
+}
+
+pub fn loader_overlap_stack() {
+    assert_eq!(
+        load_error("loader_overlap_stack"),
+        Some(KernelError::NoExec)
+    );
+}
+
+pub fn loader_overlap_segment() {
+    assert_eq!(
+        load_error("loader_overlap_segment"),
+        Some(KernelError::NoExec)
+    );
+}
+
+pub fn loader_share_page() {
+    assert_eq!(load_error("loader_share_page"), Some(KernelError::NoExec));
+}
+
+pub fn loader_low_segment() {
+    assert_eq!(load_error("loader_low_segment"), Some(KernelError::NoExec));
+}
+
+pub fn loader_wx_segment() {
+    // By default, a writable and executable segment is refused.
+    assert_eq!(load_error("loader_wx_segment"), Some(KernelError::NoExec));
+    // Otherwise, it is mapped without the execute permission. The program can
+    // still write its code there, but faults on jumping into it.
+    assert_eq!(
+        run_elf_with_policy(
+            "loader_wx_segment",
+            &["loader_wx_segment"],
+            &[],
+            WxPolicy::StripExec
+        ),
+        -1
+    );
+}
+
+pub fn loader_wx_stack() {
+    assert_eq!(load_error("loader_wx_stack"), Some(KernelError::NoExec));
+    assert_eq!(
+        run_elf_with_policy(
+            "loader_wx_stack",
+            &["loader_wx_stack"],
+            &[],
+            WxPolicy::StripExec
+        ),
+        -1
+    );
+}
+
+// Returns `e_phnum` of the ELF header and the number of the program headers
+// read from the binary `name`.
+fn phdrs(name: &str) -> Result<(u16, usize), KernelError> {
+    let file = keos::fs::FileSystem::root()
+        .open(name)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    let elf = Elf::from_file(&file).unwrap();
+    let phnum = elf.phnum()?;
+    assert_eq!(elf.phdrs()?.count(), phnum);
+    Ok((elf.header.e_phnum, phnum))
+}
+
+#[stdin(b"")]
+#[assert_output(b"success success ")]
+pub fn loader_phnum() {
+    // The number of the program headers is in the ELF header.
+    let (e_phnum, phnum) = phdrs("loader_phnum").unwrap();
+    assert_eq!(e_phnum as usize, phnum);
+    assert!(phnum > 0);
+    assert_eq!(run_elf("loader_phnum"), 0);
+
+    // The same binary with the number in the first section header.
+    assert_eq!(phdrs("loader_phnum_xnum"), Ok((PN_XNUM, phnum)));
+    assert_eq!(run_elf("loader_phnum_xnum"), 0);
+}
+
+pub fn loader_phnum_malformed() {
+    // The program headers run past the end of the file.
+    assert_eq!(phdrs("loader_phnum_range"), Err(KernelError::NoExec));
+    assert_eq!(load_error("loader_phnum_range"), Some(KernelError::NoExec));
+
+    // The size of an entry is not the size of a program header.
+    assert_eq!(phdrs("loader_phentsize"), Err(KernelError::NoExec));
+    assert_eq!(load_error("loader_phentsize"), Some(KernelError::NoExec));
+}
+
 pub fn mm_exit_cleanup_stress() {
     for _ in 0..24 {
         assert_eq!(run_elf("mm_exit_cleanup"), 0);
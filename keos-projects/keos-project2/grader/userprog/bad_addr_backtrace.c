@@ -0,0 +1,11 @@
+#include <syscall.h>
+
+__attribute__((noinline)) void fault_here(volatile int *addr) {
+    *addr = 0x42;
+}
+
+int main(int argc, char *argv[]) {
+    fault_here((int*)0);
+
+    return 0x1337; // This should NEVER executed
+}
@@ -0,0 +1,16 @@
+#include <debug.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+int main(int argc, char *argv[], char *envp[]) {
+  ASSERT(environ == envp);
+  for (int i = 0; envp[i]; i++) {
+    printf("envp[%d] = %s\n", i, envp[i]);
+  }
+  ASSERT(argv[argc] == NULL);
+  ASSERT(&argv[argc + 1] == envp);
+  ASSERT(getenv("KEO") == NULL);
+  ASSERT(getenv("MISSING") == NULL);
+  printf("KEOS=%s\n", getenv("KEOS"));
+  return 0;
+}
@@ -0,0 +1,10 @@
+#include <debug.h>
+
+// Requesting an interpreter makes the linker emit a PT_INTERP header, as it
+// does for dynamically linked executables.
+const char interp[] __attribute__((section(".interp"))) =
+    "/lib64/ld-linux-x86-64.so.2";
+
+int main(int argc, char *argv[]) {
+  return 0x1337; // The loader must reject this binary.
+}
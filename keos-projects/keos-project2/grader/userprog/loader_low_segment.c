@@ -0,0 +1,9 @@
+#include <debug.h>
+
+// Linked with `.low` placed on the first page, which is never mapped so that
+// a NULL dereference faults. The loader must reject this binary.
+char low[0x8] __attribute__((section(".low"))) = {1};
+
+int main(int argc, char *argv[]) {
+  return low[0] + 0x1337; // This should be NEVER executed.
+}
@@ -0,0 +1,9 @@
+#include <debug.h>
+
+// Linked with `.overlap` placed on top of `.text`, so the loader must reject
+// this binary.
+char overlap[0x1000] __attribute__((section(".overlap"))) = {1};
+
+int main(int argc, char *argv[]) {
+  return overlap[0] + 0x1337; // This should be NEVER executed.
+}
@@ -0,0 +1,9 @@
+#include <debug.h>
+
+// Linked with `.stack_overlap` placed inside the stack region
+// (0x47470000-0x47480000), so the loader must reject this binary.
+char overlap[0x2000] __attribute__((section(".stack_overlap"))) = {1};
+
+int main(int argc, char *argv[]) {
+  return overlap[0] + 0x1337; // This should be NEVER executed.
+}
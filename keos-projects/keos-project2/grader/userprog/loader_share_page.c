@@ -0,0 +1,9 @@
+#include <debug.h>
+
+// Linked with `.share` placed on the last page of `.text` without overlapping
+// it, so the loader must reject this binary: a page has a single permission.
+char share[0x8] __attribute__((section(".share"))) = {1};
+
+int main(int argc, char *argv[]) {
+  return share[0] + 0x1337; // This should be NEVER executed.
+}
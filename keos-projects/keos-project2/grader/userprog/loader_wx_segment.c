@@ -0,0 +1,17 @@
+#include <debug.h>
+#include <string.h>
+
+// The "awx" flags make the linker place this buffer in a loadable segment that
+// is both writable and executable. The trailing '#' comments out the flags
+// that the compiler appends to the section directive.
+char code[8] __attribute__((section(".wx,\"awx\",@progbits#")));
+
+int main(int argc, char *argv[]) {
+  // 0:  48 31 c0                 xor    rax,rax
+  // 3:  b0 42                    mov    al,0x42
+  // 5:  c3                       ret
+  ASSERT(memcpy(code, "\x48\x31\xC0\xB0\x42\xC3", 6));
+  (*(int (*)())code)();
+
+  return 0x1337; // This should be NEVER executed.
+}
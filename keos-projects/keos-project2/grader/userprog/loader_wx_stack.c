@@ -0,0 +1,17 @@
+#include <debug.h>
+#include <string.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  // The binary is linked with `-z execstack`, so its PT_GNU_STACK header asks
+  // for a stack that is both writable and executable.
+  //
+  // 0:  48 31 c0                 xor    rax,rax
+  // 3:  b0 42                    mov    al,0x42
+  // 5:  c3                       ret
+  char code[8];
+  ASSERT(memcpy(code, "\x48\x31\xC0\xB0\x42\xC3", 6));
+  (*(int (*)())code)();
+
+  return 0x1337; // This should be NEVER executed.
+}
@@ -23,14 +23,14 @@ int main(int argc, char *argv[]) {
   ASSERT(read(fd, (void *)0xCFF8, 24) == 24);
   ASSERT(memcmp((char *)0xD000, "to KeOS Project!", 16) == 0);
 
-  ASSERT(munmap((void *)0xA000) == 0);
+  ASSERT(munmap((void *)0xA000, 0x3000) == 0);
 
   ASSERT(read(fd, (void *)0xAE00, 0x10) < 0);
   ASSERT(read(fd, (void *)0xBE00, 0x10) < 0);
   ASSERT(read(fd, (void *)0xCE00, 0x10) < 0);
 
   ASSERT(read(fd, (void *)0xDE00, 0x10) == 0x10);
-  ASSERT(munmap((void *)0xD000) == 0);
+  ASSERT(munmap((void *)0xD000, 0x1000) == 0);
 
   ASSERT(read(fd, (void *)0xDE00, 0x10) < 0);
 
@@ -6,17 +6,17 @@
 #include <syscall.h>
 
 int main(int argc, char *argv[]) {
-  ASSERT(munmap(NULL) < 0);
+  ASSERT(munmap(NULL, 0x1000) < 0);
 
   ASSERT(mmap((void *)0xDEAD0000, 0x2000, PROT_READ, -1, 0) ==
          (void *)0xDEAD0000);
-  ASSERT(munmap((void *)0xDEAD0000) == 0);
-  ASSERT(munmap((void *)0xDEAD0000) < 0);
+  ASSERT(munmap((void *)0xDEAD0000, 0x2000) == 0);
+  ASSERT(munmap((void *)0xDEAD0000, 0x2000) < 0);
 
-  ASSERT(munmap((void *)0xFFFFFF0000900000) < 0);
+  ASSERT(munmap((void *)0xFFFFFF0000900000, 0x1000) < 0);
 
   ASSERT(mmap((void *)0xA000, 0x2000, PROT_READ, -1, 0) == (void *)0xA000);
-  ASSERT(munmap((void *)0xB000) < 0);
+  ASSERT(munmap((void *)0xB000, 0x2000) < 0);
 
   printf("success ");
   return 0;
@@ -7,10 +7,10 @@
 
 int main(int argc, char *argv[]) {
   // NULL pointer munmap
-  ASSERT(munmap(NULL) < 0);
+  ASSERT(munmap(NULL, 0x1000) < 0);
 
   // Kernel address munmap
-  ASSERT(munmap((void *)0xFFFFFF0000900000) < 0);
+  ASSERT(munmap((void *)0xFFFFFF0000900000, 0x1000) < 0);
 
   printf("success ");
   return 0;
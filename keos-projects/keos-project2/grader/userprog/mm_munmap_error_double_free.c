@@ -9,8 +9,8 @@ int main(int argc, char *argv[]) {
   // Test double munmap (use-after-free)
   ASSERT(mmap((void *)0xDEAD0000, 0x2000, PROT_READ, -1, 0) ==
          (void *)0xDEAD0000);
-  ASSERT(munmap((void *)0xDEAD0000) == 0);
-  ASSERT(munmap((void *)0xDEAD0000) < 0);  // Double free should fail
+  ASSERT(munmap((void *)0xDEAD0000, 0x2000) == 0);
+  ASSERT(munmap((void *)0xDEAD0000, 0x2000) < 0);  // Double free should fail
 
   printf("success ");
   return 0;
@@ -1,14 +1,27 @@
 #include <debug.h>
 #include <fcntl.h>
 #include <mman.h>
+#include <stdint.h>
 #include <stdio.h>
 #include <string.h>
 #include <syscall.h>
 
+#define TRUELY_ERROR(x) ((int64_t)x < 0 && (-(int64_t)x) < 0x100)
+
 int main(int argc, char *argv[]) {
-  // Test unaligned/partial munmap
+  // Test unaligned and zero-length munmap
   ASSERT(mmap((void *)0xA000, 0x2000, PROT_READ, -1, 0) == (void *)0xA000);
-  ASSERT(munmap((void *)0xB000) < 0);  // Unaligned/partial munmap should fail
+  ASSERT(munmap((void *)0xA800, 0x1000) < 0);  // Unaligned address
+  ASSERT(munmap((void *)0xA000, 0x1800) < 0);  // Unaligned length
+  ASSERT(munmap((void *)0xA000, 0) < 0);       // Zero length
+
+  // Unaligned and zero-length mmap
+  ASSERT(TRUELY_ERROR(mmap((void *)0xC800, 0x1000, PROT_READ, -1, 0)));
+  ASSERT(TRUELY_ERROR(mmap((void *)0xC000, 0x1800, PROT_READ, -1, 0)));
+  ASSERT(TRUELY_ERROR(mmap((void *)0xC000, 0, PROT_READ, -1, 0)));
+
+  // The failed calls must not have unmapped anything.
+  ASSERT(munmap((void *)0xA000, 0x2000) == 0);
 
   printf("success ");
   return 0;
@@ -0,0 +1,44 @@
+#include <debug.h>
+#include <fcntl.h>
+#include <mman.h>
+#include <stdio.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  int fd = open("hello", O_RDWR);
+  ASSERT(fd >= 3);
+
+  ASSERT(mmap((void *)0xA000, 0x4000, PROT_READ | PROT_WRITE, -1, 0) == (void *)0xA000);
+  for (int i = 0; i < 4; i++)
+    *(int *)(0xA000UL + i * 0x1000) = i + 1;
+
+  // Unmapping the middle page splits the mapping in two.
+  ASSERT(munmap((void *)0xB000, 0x1000) == 0);
+  ASSERT(read(fd, (void *)0xB000, 0x10) < 0);
+  ASSERT(*(int *)0xA000 == 1);
+  ASSERT(*(int *)0xC000 == 3);
+  ASSERT(*(int *)0xD000 == 4);
+
+  // A range across the hole is not entirely mapped, so nothing is unmapped.
+  ASSERT(munmap((void *)0xA000, 0x4000) < 0);
+  ASSERT(*(int *)0xA000 == 1);
+  ASSERT(*(int *)0xD000 == 4);
+
+  // Unmapping the tail keeps the head of the upper half.
+  ASSERT(munmap((void *)0xD000, 0x1000) == 0);
+  ASSERT(read(fd, (void *)0xD000, 0x10) < 0);
+  ASSERT(*(int *)0xC000 == 3);
+
+  ASSERT(munmap((void *)0xC000, 0x1000) == 0);
+  ASSERT(munmap((void *)0xA000, 0x1000) == 0);
+  ASSERT(read(fd, (void *)0xA000, 0x10) < 0);
+  ASSERT(read(fd, (void *)0xC000, 0x10) < 0);
+
+  // The hole can be mapped again, with fresh contents.
+  ASSERT(mmap((void *)0xB000, 0x1000, PROT_READ | PROT_WRITE, -1, 0) == (void *)0xB000);
+  ASSERT(*(int *)0xB000 == 0);
+  ASSERT(munmap((void *)0xB000, 0x1000) == 0);
+
+  printf("success ");
+  return 0;
+}
@@ -0,0 +1,25 @@
+#include <debug.h>
+#include <stdio.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  int fds[2] = {0};
+  char fd[4] = {0};
+  char *child_argv[] = {"sys_execve_child", fd, NULL};
+  char *child_envp[] = {"KEOS=is fun", NULL};
+  char *bad_argv[] = {"no_such_program", NULL};
+
+  ASSERT(pipe(fds) == 0);
+  ASSERT(write(fds[1], "kept", 4) == 4);
+  snprintf(fd, sizeof(fd), "%d", fds[0]);
+
+  // A failed execve returns to the caller with the image intact.
+  ASSERT(execve("no_such_program", bad_argv, NULL) < 0);
+  ASSERT(execve(NULL, bad_argv, NULL) < 0);
+  ASSERT(execve("sys_execve_child", (void *)0x1234, NULL) < 0);
+
+  printf("before execve ");
+  execve("sys_execve_child", child_argv, child_envp);
+
+  return 0x1337; // This should be NEVER executed.
+}
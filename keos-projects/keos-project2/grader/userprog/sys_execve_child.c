@@ -0,0 +1,20 @@
+#include <debug.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  char buf[5] = {0};
+
+  ASSERT(argc == 2);
+  ASSERT(strcmp(argv[0], "sys_execve_child") == 0);
+  ASSERT(strcmp(getenv("KEOS"), "is fun") == 0);
+
+  // File descriptors survive execve.
+  ASSERT(read(atoi(argv[1]), buf, 4) == 4);
+  ASSERT(strcmp(buf, "kept") == 0);
+
+  printf("after execve ");
+  return 0;
+}
@@ -0,0 +1,39 @@
+#include <debug.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  int kept, closed, marked, reused;
+  char fds[4][4] = {0};
+  char *child_argv[] = {"sys_execve_cloexec_child", fds[0], fds[1], fds[2],
+                        fds[3], NULL};
+
+  kept = open("hello", O_RDONLY);
+  ASSERT(kept >= 3);
+  ASSERT(fcntl(kept, F_GETFD, 0) == 0);
+
+  closed = open("hello2", O_RDONLY | O_CLOEXEC);
+  ASSERT(closed >= 3);
+  ASSERT(fcntl(closed, F_GETFD, 0) == FD_CLOEXEC);
+
+  marked = open("hello3", O_RDONLY);
+  ASSERT(marked >= 3);
+  ASSERT(fcntl(marked, F_SETFD, FD_CLOEXEC) == 0);
+  ASSERT(fcntl(marked, F_GETFD, 0) == FD_CLOEXEC);
+
+  // Closing a file descriptor clears its mark, so the file opened next with
+  // the same number is kept.
+  reused = open("hello", O_RDONLY | O_CLOEXEC);
+  ASSERT(close(reused) == 0);
+  ASSERT(open("hello", O_RDONLY) == reused);
+  ASSERT(fcntl(reused, F_GETFD, 0) == 0);
+
+  snprintf(fds[0], sizeof(fds[0]), "%d", kept);
+  snprintf(fds[1], sizeof(fds[1]), "%d", closed);
+  snprintf(fds[2], sizeof(fds[2]), "%d", marked);
+  snprintf(fds[3], sizeof(fds[3]), "%d", reused);
+  execve("sys_execve_cloexec_child", child_argv, NULL);
+
+  return 0x1337; // This should be NEVER executed.
+}
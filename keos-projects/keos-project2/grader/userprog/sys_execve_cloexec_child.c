@@ -0,0 +1,27 @@
+#include <debug.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  char buf[8] = {0};
+
+  ASSERT(argc == 5);
+
+  // File descriptors without the close-on-exec mark survive execve.
+  ASSERT(read(atoi(argv[1]), buf, 7) == 7);
+  ASSERT(strcmp(buf, "Welcome") == 0);
+  ASSERT(fcntl(atoi(argv[1]), F_GETFD, 0) == 0);
+  ASSERT(read(atoi(argv[4]), buf, 7) == 7);
+  ASSERT(strcmp(buf, "Welcome") == 0);
+
+  // The marked ones are closed.
+  ASSERT(read(atoi(argv[2]), buf, 7) < 0);
+  ASSERT(read(atoi(argv[3]), buf, 7) < 0);
+  ASSERT(fcntl(atoi(argv[2]), F_GETFD, 0) < 0);
+
+  printf("success ");
+  return 0;
+}
@@ -0,0 +1,17 @@
+#include <stdio.h>
+#include <syscall.h>
+#include <fcntl.h>
+#include <debug.h>
+#include <string.h>
+
+int main(int argc, char *argv[]) {
+    char buf[8] = {0};
+    int fd;
+
+    ASSERT ((fd = open("hello", O_RDONLY)) == 3);
+    ASSERT (read(fd, buf, 7) == 7);
+    ASSERT (write(1, buf, 7) == 7);
+    ASSERT (close(fd) == 0);
+    ASSERT (close(fd) < 0);
+    return 0;
+}
@@ -100,9 +100,15 @@ impl Pager for EagerPager {
 
     /// Memory unmap function (`munmap`) for eager paging.
     ///
-    /// This function would unmap a previously mapped memory region, releasing
-    /// any associated resources.
-    fn munmap(&mut self, page_table: &mut PageTable, addr: Va) -> Result<usize, KernelError> {
+    /// This function would unmap a range of previously mapped memory, releasing
+    /// any associated resources. When the range covers only a part of a
+    /// [`Mapping`], the remaining parts are kept as separate mappings.
+    fn munmap(
+        &mut self,
+        page_table: &mut PageTable,
+        addr: Va,
+        size: usize,
+    ) -> Result<usize, KernelError> {
         todo!()
     }
 
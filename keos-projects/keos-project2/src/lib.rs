@@ -44,10 +44,21 @@
 //!
 //! ## Debugging a User Process
 //!
-//! Because KeOS's internal backtrace only support for kernel's virtual
-//! addresses, user program's instruction address may be shown as unknown.
-//! For such cases, you may utilize `addr2line` utility to see which user mode
-//! codes are responsible for the error.
+//! When a user program is killed by a page fault, KeOS prints the backtrace of
+//! the program, naming each function with the symbol table loaded from the
+//! executable:
+//!
+//! ```plaintext
+//! User backtrace:
+//!    0: 0x000000000040100c  - fault_here+0xc
+//!    1: 0x000000000040102d  - main+0x18
+//!    2: 0x0000000000401063  - _start+0x2e
+//! ```
+//!
+//! As KeOS only knows the function symbols, and nothing about the source
+//! lines, the addresses are shown as `?` for a stripped executable.
+//! For such cases, or to find the source line, you may utilize `addr2line`
+//! utility to see which user mode codes are responsible for the error.
 //! For example, if you get the following error message:
 //!
 //! ```plaintext
@@ -127,6 +138,22 @@ pub enum SyscallNumber {
     Mmap = 8,
     /// Unmap the memory.
     Munmap = 9,
+    /// Retrieves the file I/O statistics of the process.
+    IoStat = 21,
+    /// Create a pipe with the given capacity and flags.
+    Pipe2 = 23,
+    /// Wait until one of file descriptors becomes ready.
+    Poll = 24,
+    /// Get or set the flags of an open file.
+    Fcntl = 32,
+    /// Apply or remove an advisory lock on an open file.
+    Flock = 33,
+    /// Transfer data between open files within the kernel.
+    Sendfile = 35,
+    /// Replace the process image with a new program.
+    Execve = 37,
+    /// Get the resident set size of the process, in pages.
+    GetRss = 38,
 }
 
 impl TryFrom<usize> for SyscallNumber {
@@ -143,6 +170,14 @@ impl TryFrom<usize> for SyscallNumber {
             7 => Ok(SyscallNumber::Pipe),
             8 => Ok(SyscallNumber::Mmap),
             9 => Ok(SyscallNumber::Munmap),
+            21 => Ok(SyscallNumber::IoStat),
+            23 => Ok(SyscallNumber::Pipe2),
+            24 => Ok(SyscallNumber::Poll),
+            32 => Ok(SyscallNumber::Fcntl),
+            33 => Ok(SyscallNumber::Flock),
+            35 => Ok(SyscallNumber::Sendfile),
+            37 => Ok(SyscallNumber::Execve),
+            38 => Ok(SyscallNumber::GetRss),
             _ => Err(KernelError::NoSuchSyscall),
         }
     }
@@ -188,19 +223,27 @@ impl Task for Process {
     /// modifies the CPU registers accordingly.
     fn syscall(&mut self, regs: &mut Registers) {
         // ** YOU DON'T NEED TO CHANGE THIS FUNCTION **
-        let abi = SyscallAbi::from_registers(regs); // Extract ABI from the registers.
+        let mut abi = SyscallAbi::from_registers(regs); // Extract ABI from the registers.
         // Lookup the system call handler function based on the system call number.
         let return_val = SyscallNumber::try_from(abi.sysno).and_then(|no| match no {
             SyscallNumber::Exit => self.exit(&abi),
-            SyscallNumber::Open => self.file_struct.open(&abi),
-            SyscallNumber::Read => self.file_struct.read(&abi),
-            SyscallNumber::Write => self.file_struct.write(&abi),
-            SyscallNumber::Seek => self.file_struct.seek(&abi),
+            SyscallNumber::Open => self.file_struct.open_cloexec(&mut abi),
+            SyscallNumber::Read => self.file_struct.read_accounted(&abi),
+            SyscallNumber::Write => self.file_struct.write_accounted(&abi),
+            SyscallNumber::Seek => self.file_struct.seek_sparse(&abi),
             SyscallNumber::Tell => self.file_struct.tell(&abi),
-            SyscallNumber::Close => self.file_struct.close(&abi),
+            SyscallNumber::Close => self.file_struct.close_unlocking(&abi),
             SyscallNumber::Pipe => self.file_struct.pipe(&abi),
+            SyscallNumber::Pipe2 => self.file_struct.pipe2(&abi),
+            SyscallNumber::Poll => self.file_struct.poll(&abi),
             SyscallNumber::Mmap => self.mm_struct.mmap(&mut self.file_struct, &abi),
             SyscallNumber::Munmap => self.mm_struct.munmap(&abi),
+            SyscallNumber::IoStat => self.file_struct.iostat(&abi),
+            SyscallNumber::Fcntl => self.file_struct.fcntl(&abi),
+            SyscallNumber::Flock => self.file_struct.flock(&abi),
+            SyscallNumber::Sendfile => self.file_struct.sendfile(&abi),
+            SyscallNumber::Execve => self.execve(&abi),
+            SyscallNumber::GetRss => Ok(self.mm_struct.rss()),
         });
         // Set the return value of the system call (success or error) back into the
         // registers.
@@ -8,9 +8,14 @@
 //! An ELF file has the program header, and section headers. The program headers
 //! show the segments used at run time, whereas the section header lists the
 //! set of sections.
-use alloc::vec::Vec;
+use alloc::{string::String, vec::Vec};
 use core::convert::TryInto;
-use keos::{KernelError, fs::RegularFile, mm::page_table::Permission};
+use keos::{
+    KernelError,
+    fs::RegularFile,
+    mm::page_table::Permission,
+    task::{Symbol, SymbolTable},
+};
 
 /// Represents the ELF file header.
 ///
@@ -52,7 +57,8 @@ pub struct ELFHeader {
     pub e_ehsize: u16,
     /// Size of a program header table entry in bytes.
     pub e_phentsize: u16,
-    /// Number of entries in the program header table.
+    /// Number of entries in the program header table, or [`PN_XNUM`] if the
+    /// number does not fit in this field.
     pub e_phnum: u16,
     /// Size of a section header table entry in bytes.
     pub e_shentsize: u16,
@@ -62,6 +68,19 @@ pub struct ELFHeader {
     pub e_shstrndx: u16,
 }
 
+/// The value of [`ELFHeader::e_phnum`] telling that the number of the program
+/// headers does not fit in the field.
+///
+/// Then, the actual number is held in the `sh_info` field of the first section
+/// header ([`Shdr::sh_info`]).
+pub const PN_XNUM: u16 = 0xffff;
+
+/// The size of a program header entry ([`Phdr`]) of 64-bit binaries.
+const PHDR_SIZE: usize = 0x38;
+
+/// The size of a section header entry ([`Shdr`]) of 64-bit binaries.
+const SHDR_SIZE: usize = 0x40;
+
 /// Represents an ELF file in memory.
 ///
 /// This struct provides access to ELF metadata and program headers.
@@ -105,10 +124,50 @@ impl<'a, 'b> Elf<'a> {
         }
     }
 
+    /// Checks that the `len` bytes at the offset `ofs` lie within the file,
+    /// and returns the offset.
+    fn offset_in_file(&self, ofs: u64, len: usize) -> Result<usize, KernelError> {
+        let ofs = usize::try_from(ofs).map_err(|_| KernelError::NoExec)?;
+        ofs.checked_add(len)
+            .filter(|end| *end <= self.file.size())
+            .map(|_| ofs)
+            .ok_or(KernelError::NoExec)
+    }
+
+    /// Returns the number of the program headers.
+    ///
+    /// If [`ELFHeader::e_phnum`] is [`PN_XNUM`], the number is read from the
+    /// first section header.
+    ///
+    /// Returns [`KernelError::NoExec`] if the first section header is absent
+    /// or not in the file.
+    pub fn phnum(&self) -> Result<usize, KernelError> {
+        if self.header.e_phnum != PN_XNUM {
+            return Ok(self.header.e_phnum as usize);
+        }
+        if self.header.e_shoff == 0 {
+            return Err(KernelError::NoExec);
+        }
+        let base = self.offset_in_file(self.header.e_shoff, SHDR_SIZE)?;
+        let mut raw = [0; SHDR_SIZE];
+        self.file.read(base, &mut raw)?;
+        // The offset of `sh_info` in the section header.
+        Ok(u32::from_le_bytes(raw[0x2c..0x30].try_into().unwrap()) as usize)
+    }
+
     /// Returns an iterator over the program headers.
+    ///
+    /// Returns [`KernelError::NoExec`] if the program header table is
+    /// malformed, i.e., the size of an entry ([`ELFHeader::e_phentsize`]) is
+    /// not the size of [`Phdr`], or the table does not lie within the file.
     pub fn phdrs(&'b self) -> Result<PhdrIterator<'a, 'b>, KernelError> {
-        let (base, size) = (self.header.e_phoff.try_into().unwrap(), self.header.e_phnum);
-        let mut buffer = alloc::vec![0; size as usize * 0x38];
+        let phnum = self.phnum()?;
+        if phnum != 0 && self.header.e_phentsize as usize != PHDR_SIZE {
+            return Err(KernelError::NoExec);
+        }
+        let size = phnum.checked_mul(PHDR_SIZE).ok_or(KernelError::NoExec)?;
+        let base = self.offset_in_file(self.header.e_phoff, size)?;
+        let mut buffer = alloc::vec![0; size];
         self.file.read(base, buffer.as_mut())?;
         Ok(PhdrIterator {
             cursor: 0,
@@ -116,13 +175,111 @@ impl<'a, 'b> Elf<'a> {
             elf: self,
         })
     }
+
+    /// Returns the section headers.
+    ///
+    /// Returns [`KernelError::NoExec`] if the section header table does not
+    /// lie within the file.
+    pub fn shdrs(&self) -> Result<Vec<Shdr>, KernelError> {
+        union Reader {
+            shdr: Shdr,
+            _raw: [u8; SHDR_SIZE],
+        }
+
+        let size = (self.header.e_shnum as usize) * SHDR_SIZE;
+        let base = self.offset_in_file(self.header.e_shoff, size)?;
+        let mut buffer = alloc::vec![0; size];
+        self.file.read(base, buffer.as_mut())?;
+        Ok(buffer
+            .chunks_exact(SHDR_SIZE)
+            .map(|raw| unsafe {
+                let mut inner = Reader {
+                    _raw: [0; SHDR_SIZE],
+                };
+                inner._raw.copy_from_slice(raw);
+                inner.shdr
+            })
+            .collect())
+    }
+
+    /// Reads the function symbols from the symbol table (`.symtab`).
+    ///
+    /// Returns `None` if the binary is stripped, i.e., has no symbol table,
+    /// or if the symbol table or its string table does not lie within the
+    /// file.
+    pub fn symbols(&self) -> Option<SymbolTable> {
+        let shdrs = self.shdrs().ok()?;
+        let symtab = shdrs.iter().find(|shdr| shdr.sh_type == SHT_SYMTAB)?;
+        let strtab = shdrs.get(symtab.sh_link as usize)?;
+        let read = |shdr: &Shdr| {
+            let size = usize::try_from(shdr.sh_size).ok()?;
+            let base = self.offset_in_file(shdr.sh_offset, size).ok()?;
+            let mut buffer = alloc::vec![0; size];
+            self.file.read(base, buffer.as_mut()).ok().map(|_| buffer)
+        };
+        let (syms, strs) = (read(symtab)?, read(strtab)?);
+
+        let symbols = syms
+            .chunks_exact(0x18)
+            .filter(|sym| sym[4] & 0xf == STT_FUNC)
+            .filter_map(|sym| {
+                let name =
+                    strs.get(u32::from_le_bytes(sym[0..4].try_into().unwrap()) as usize..)?;
+                let name = &name[..name.iter().position(|b| *b == 0)?];
+                Some(Symbol {
+                    name: String::from(core::str::from_utf8(name).ok()?),
+                    start: u64::from_le_bytes(sym[8..16].try_into().unwrap()) as usize,
+                    size: u64::from_le_bytes(sym[16..24].try_into().unwrap()) as usize,
+                })
+            })
+            .collect();
+        Some(SymbolTable::new(symbols))
+    }
+}
+
+/// Section type of a symbol table.
+pub const SHT_SYMTAB: u32 = 2;
+
+/// Symbol type of a function.
+pub const STT_FUNC: u8 = 2;
+
+/// ELF section header for 64-bit binaries.
+///
+/// Each `Shdr` entry describes a section of the file. Sections are not needed
+/// to run the program, but carry extra information such as the symbol table.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Shdr {
+    /// Offset of the section name in the section name string table.
+    pub sh_name: u32,
+    /// Section type (e.g., [`SHT_SYMTAB`]).
+    pub sh_type: u32,
+    /// Section attributes.
+    pub sh_flags: u64,
+    /// Virtual address of the section in memory, if it is loaded.
+    pub sh_addr: u64,
+    /// Offset in the file where the section starts.
+    pub sh_offset: u64,
+    /// Size of the section in the file.
+    pub sh_size: u64,
+    /// Index of an associated section, e.g., the string table of a symbol
+    /// table.
+    pub sh_link: u32,
+    /// Extra information, depending on the section type. For the first
+    /// section header, the number of the program headers if
+    /// [`ELFHeader::e_phnum`] is [`PN_XNUM`].
+    pub sh_info: u32,
+    /// Alignment of the section.
+    pub sh_addralign: u64,
+    /// Size of an entry, for the sections holding a table.
+    pub sh_entsize: u64,
 }
 
 /// Iterator over program headers in an ELF binary.
 ///
 /// This iterator is created using [`Elf::phdrs`].
 pub struct PhdrIterator<'a, 'b> {
-    cursor: u16,
+    cursor: usize,
     elf: &'a Elf<'b>,
     buffer: Vec<u8>,
 }
@@ -132,14 +289,18 @@ impl<'a, 'b> core::iter::Iterator for PhdrIterator<'a, 'b> {
     fn next(&mut self) -> Option<Self::Item> {
         union Reader {
             phdr: Phdr,
-            _raw: [u8; 0x38],
+            _raw: [u8; PHDR_SIZE],
         }
 
-        if self.cursor as usize * 0x38 < self.buffer.len() {
+        if self.cursor * PHDR_SIZE < self.buffer.len() {
             unsafe {
-                let ofs = self.cursor as usize * 0x38;
-                let mut inner = Reader { _raw: [0; 0x38] };
-                inner._raw.copy_from_slice(&self.buffer[ofs..ofs + 0x38]);
+                let ofs = self.cursor * PHDR_SIZE;
+                let mut inner = Reader {
+                    _raw: [0; PHDR_SIZE],
+                };
+                inner
+                    ._raw
+                    .copy_from_slice(&self.buffer[ofs..ofs + PHDR_SIZE]);
                 self.cursor += 1;
                 Some(inner.phdr)
             }
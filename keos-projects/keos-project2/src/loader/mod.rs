@@ -50,7 +50,24 @@
 //!  - `p_vaddr` must be page-aligned. If not, round it down and adjust offsets
 //!    accordingly.
 //!  - Ensure segments do not overwrite existing mappings like the stack or
-//!    kernel memory.
+//!    kernel memory. [`LoadContext::load_phdr`] already rejects binaries whose
+//!    loadable segments leave the user address space, overlap the stack
+//!    region, or share a page with each other, before any of them is mapped.
+//!
+//! Besides [`PType::Load`], two other headers affect loading. A
+//! [`PType::GnuStack`] header tells whether the program needs an executable
+//! stack; when it is absent, the stack is mapped non-executable. A
+//! [`PType::Interp`] header names a dynamic linker, which KeOS does not
+//! provide, so such binaries are rejected with [`KernelError::NoExec`]. Both
+//! are already handled for you in [`LoadContext::load_phdr`].
+//!
+//! No region of a process is mapped both writable and executable (W^X), as
+//! such a region lets an attacker inject code by simply writing it. A
+//! [`PType::Load`] segment, or a stack requested by [`PType::GnuStack`], that
+//! asks for both is handled by the [`WxPolicy`] of the [`LoadContext`]: the
+//! binary is either rejected, or the region is mapped without the execute
+//! permission. Pass the permission of each segment through
+//! [`WxPolicy::apply`] before mapping it.
 //!
 //! ## State on Program Startup
 //!
@@ -66,14 +83,20 @@
 //! **Example command:** `/bin/ls -l foo bar`
 //!
 //! 1. Split the command into words: `"/bin/ls"`, `"-l"`, `"foo"`, `"bar"`.
-//! 2. Copy the argument strings to the top of the stack (order does not
-//!    matter).
-//! 3. Push their addresses, followed by a null sentinel (`argv[argc] = NULL`).
+//! 2. Copy the argument strings, followed by the environment strings (e.g.,
+//!    `"HOME=/"`), to the top of the stack (order does not matter).
+//! 3. Push the addresses of the environment strings, followed by a null
+//!    sentinel (`envp[n] = NULL`).
 //!    - Align the stack pointer to an 8-byte boundary for performance.
-//! 4. Set `%rdi = argc` (argument count) and `%rsi = argv` (argument array).
-//! 5. Push a fake return address to maintain stack integrity.
+//! 4. Push the addresses of the argument strings, followed by a null sentinel
+//!    (`argv[argc] = NULL`). Together with the previous step, this yields the
+//!    System V layout `argv[]`, `NULL`, `envp[]`, `NULL` from low to high
+//!    addresses.
+//! 5. Set `%rdi = argc` (argument count), `%rsi = argv` (argument array), and
+//!    `%rdx = envp` (environment array).
+//! 6. Push a fake return address to maintain stack integrity.
 //!
-//! **Example stack layout before execution:**
+//! **Example stack layout before execution (with an empty environment):**
 //!
 //! | Address    | Name           | Data       | Type        |
 //! | ---------- | -------------- | ---------- | ----------- |
@@ -82,15 +105,17 @@
 //! | 0x4747fff5 | argv\[1\]\[...\]   | '-l\0'     | char\[3\]     |
 //! | 0x4747ffed | argv\[0\]\[...\]   | '/bin/ls\0'| char\[8\]     |
 //! | 0x4747ffe8 | word-align     | 0          | uint8_t\[\]   |
-//! | 0x4747ffe0 | argv\[4\]        | 0          | char *      |
-//! | 0x4747ffd8 | argv\[3\]        | 0x4747fffc | char *      |
-//! | 0x4747ffd0 | argv\[2\]        | 0x4747fff8 | char *      |
-//! | 0x4747ffc8 | argv\[1\]        | 0x4747fff5 | char *      |
-//! | 0x4747ffc0 | argv\[0\]        | 0x4747ffed | char *      |
-//! | 0x4747ffb8 | return address | 0          | void (*) () |
+//! | 0x4747ffe0 | envp\[0\]        | 0          | char *      |
+//! | 0x4747ffd8 | argv\[4\]        | 0          | char *      |
+//! | 0x4747ffd0 | argv\[3\]        | 0x4747fffc | char *      |
+//! | 0x4747ffc8 | argv\[2\]        | 0x4747fff8 | char *      |
+//! | 0x4747ffc0 | argv\[1\]        | 0x4747fff5 | char *      |
+//! | 0x4747ffb8 | argv\[0\]        | 0x4747ffed | char *      |
+//! | 0x4747ffb0 | return address | 0          | void (*) () |
 //!
-//! The stack pointer (`rsp`) is initialized to `0x4747ffb8`. The first two
-//! arguments, `%rdi` and `%rsi`, should be `4` and `0x4747ffc0`, respectively.
+//! The stack pointer (`rsp`) is initialized to `0x4747ffb0`. The first three
+//! arguments, `%rdi`, `%rsi`, and `%rdx`, should be `4`, `0x4747ffb8`, and
+//! `0x4747ffe0`, respectively.
 //! The user program stack always starts at `0x47480000` in KeOS, and always
 //! grows downward.
 //!
@@ -131,19 +156,24 @@
 pub mod elf;
 pub mod stack_builder;
 
-use crate::{mm_struct::MmStruct, pager::Pager};
+use crate::{
+    mm_struct::{MmStruct, USER_SPACE_END, USER_SPACE_START},
+    pager::Pager,
+};
+use alloc::{sync::Arc, vec::Vec};
+use core::ops::Range;
 #[cfg(doc)]
 use elf::Phdr;
-use elf::{Elf, PType};
-#[cfg(doc)]
-use keos::mm::page_table::Permission;
+use elf::{Elf, PFlags, PType};
 use keos::{
     KernelError,
-    addressing::{PAGE_MASK, Va},
+    addressing::{PAGE_MASK, PAGE_SIZE, Va},
     fs::RegularFile,
+    mm::page_table::Permission,
     syscall::Registers,
+    task::SymbolTable,
 };
-use stack_builder::StackBuilder;
+use stack_builder::{STACK_SIZE, STACK_TOP, StackBuilder};
 
 /// A context that holds the necessary state for loading and initializing a user
 /// program.
@@ -158,9 +188,75 @@ pub struct LoadContext<P: Pager> {
     /// Initial CPU register values for the user process, including the
     /// instruction pointer.
     pub regs: Registers,
+    /// Permission of the user stack, decided by the [`PType::GnuStack`]
+    /// header while loading the program headers.
+    pub stack_permission: Permission,
+    /// How to handle a region requested both writable and executable.
+    pub wx_policy: WxPolicy,
+    /// The function symbols of the program, or `None` for a stripped
+    /// executable.
+    ///
+    /// They are installed with [`keos::task::set_user_symbols`] on the thread
+    /// that runs the program, so that the kernel can print `function+offset`
+    /// in the backtrace of a faulting program.
+    pub symbols: Option<Arc<SymbolTable>>,
+}
+
+/// The policy on a region that a binary requests both writable and
+/// executable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WxPolicy {
+    /// Refuse to load the binary with [`KernelError::NoExec`].
+    #[default]
+    Reject,
+    /// Map the region writable, but not executable.
+    StripExec,
+}
+
+impl WxPolicy {
+    /// Applies the policy on the `permission` requested for a region.
+    ///
+    /// # Returns
+    /// - `permission` itself if it is not both writable and executable.
+    /// - `permission` without [`Permission::EXECUTABLE`] under
+    ///   [`WxPolicy::StripExec`].
+    /// - [`KernelError::NoExec`] under [`WxPolicy::Reject`].
+    pub fn apply(self, permission: Permission) -> Result<Permission, KernelError> {
+        if !permission.contains(Permission::WRITE | Permission::EXECUTABLE) {
+            return Ok(permission);
+        }
+        match self {
+            WxPolicy::Reject => Err(KernelError::NoExec),
+            WxPolicy::StripExec => Ok(permission - Permission::EXECUTABLE),
+        }
+    }
+}
+
+impl<P: Pager> Default for LoadContext<P> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<P: Pager> LoadContext<P> {
+    /// Creates a new [`LoadContext`] with an empty memory layout, zeroed
+    /// registers, a non-executable stack, and the default [`WxPolicy`].
+    pub fn new() -> Self {
+        Self {
+            mm_struct: MmStruct::new(),
+            regs: Registers::new(),
+            stack_permission: Permission::READ | Permission::WRITE | Permission::USER,
+            wx_policy: WxPolicy::default(),
+            symbols: None,
+        }
+    }
+
+    /// Sets the [`WxPolicy`] applied while loading the program headers.
+    pub fn with_wx_policy(mut self, wx_policy: WxPolicy) -> Self {
+        self.wx_policy = wx_policy;
+        self
+    }
+
     /// Loads program headers ([`Phdr`]s) from an ELF binary into memory.
     ///
     /// This function iterates over the ELF program headers and maps the
@@ -177,21 +273,48 @@ impl<P: Pager> LoadContext<P> {
     /// - `Err(KernelError)` if any error occurs during the loading process,
     ///   such as an invalid memory mapping, insufficient memory, or an
     ///   unsupported segment type.
+    /// - `Err(KernelError::NoExec)` if the binary requests a dynamic linker
+    ///   through a [`PType::Interp`] header, if a [`PType::Load`] segment
+    ///   lies outside the user address space, overlaps the stack region, or
+    ///   overlaps another loadable segment, or if a region is requested both
+    ///   writable and executable under [`WxPolicy::Reject`], or if the program
+    ///   header table is malformed (see [`Elf::phdrs`]).
     ///
     /// # Behavior
     /// - Iterates over all program headers using [`Elf::phdrs`].
     /// - Maps each segment into memory if its type is [`PType::Load`].
-    /// - Applies appropriate memory permissions using [`Phdr::permission`].
+    /// - Records the stack permission from the [`PType::GnuStack`] header.
+    /// - Applies appropriate memory permissions using [`Phdr::permission`],
+    ///   filtered by [`WxPolicy::apply`].
     /// - Ensures proper alignment and memory allocation before mapping.
     pub fn load_phdr(&mut self, elf: Elf) -> Result<(), KernelError> {
         let mut bss = Va::new(0).unwrap();
 
-        for phdr in elf.phdrs().map_err(|_| KernelError::InvalidArgument)? {
-            if phdr.type_ == PType::Load {
-                let (vaddr, memsz, filesz, fileofs, perm): (Va, _, _, _, _) =
-                    (todo!(), todo!(), todo!(), todo!(), phdr.permission());
-                bss = bss.max(vaddr + filesz as usize);
-                todo!()
+        // KeOS has no dynamic linker; refuse binaries that need one before
+        // touching the address space.
+        if elf.phdrs()?.any(|phdr| phdr.type_ == PType::Interp) {
+            return Err(KernelError::NoExec);
+        }
+        Self::validate_segments(&elf)?;
+        self.validate_wx(&elf)?;
+
+        for phdr in elf.phdrs()? {
+            match phdr.type_ {
+                PType::Load => {
+                    let perm = self.wx_policy.apply(phdr.permission())?;
+                    let (vaddr, memsz, filesz, fileofs): (Va, _, _, _) =
+                        (todo!(), todo!(), todo!(), todo!());
+                    bss = bss.max(vaddr + filesz as usize);
+                    todo!()
+                }
+                PType::GnuStack => {
+                    self.stack_permission = Permission::READ | Permission::WRITE | Permission::USER;
+                    if phdr.p_flags.contains(PFlags::EXECUTABLE) {
+                        self.stack_permission |= Permission::EXECUTABLE;
+                    }
+                    self.stack_permission = self.wx_policy.apply(self.stack_permission)?;
+                }
+                _ => (),
             }
         }
 
@@ -205,15 +328,75 @@ impl<P: Pager> LoadContext<P> {
         Ok(())
     }
 
-    /// Builds a user stack and initializes it with arguments.
+    /// Checks that `elf` requests no region both writable and executable, if
+    /// the [`WxPolicy`] rejects such a region.
+    ///
+    /// This runs before any segment is mapped, so a rejected binary leaves
+    /// the address space untouched. The stack is always writable, so an
+    /// executable stack requested by [`PType::GnuStack`] counts as well.
+    fn validate_wx(&self, elf: &Elf) -> Result<(), KernelError> {
+        if self.wx_policy != WxPolicy::Reject {
+            return Ok(());
+        }
+        for phdr in elf.phdrs()? {
+            let wx = match phdr.type_ {
+                PType::Load => phdr.p_flags.contains(PFlags::WRITE | PFlags::EXECUTABLE),
+                PType::GnuStack => phdr.p_flags.contains(PFlags::EXECUTABLE),
+                _ => false,
+            };
+            if wx {
+                return Err(KernelError::NoExec);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the loadable segments of `elf` can be mapped without
+    /// clobbering anything.
+    ///
+    /// Each [`PType::Load`] segment must fit in the user address space
+    /// ([`USER_SPACE_START`]..[`USER_SPACE_END`]), stay clear of the region
+    /// reserved for the stack, and be disjoint from every other loadable
+    /// segment. Empty segments are ignored.
+    ///
+    /// The segments are mapped by pages, so each segment is rounded out to
+    /// the page boundaries before the checks: two segments sharing a page
+    /// would map the page twice, with one permission lost.
+    fn validate_segments(elf: &Elf) -> Result<(), KernelError> {
+        let stack = STACK_TOP - STACK_SIZE..STACK_TOP;
+        let mut segments: Vec<Range<usize>> = Vec::new();
+
+        for phdr in elf.phdrs()? {
+            if phdr.type_ != PType::Load || phdr.p_memsz == 0 {
+                continue;
+            }
+            let start = phdr.p_vaddr as usize & !PAGE_MASK;
+            let end = (phdr.p_vaddr as usize)
+                .checked_add(phdr.p_memsz as usize)
+                .and_then(|end| end.checked_next_multiple_of(PAGE_SIZE))
+                .filter(|end| start >= USER_SPACE_START && *end <= USER_SPACE_END)
+                .ok_or(KernelError::NoExec)?;
+            let overlaps = |other: &Range<usize>| start < other.end && other.start < end;
+            if overlaps(&stack) || segments.iter().any(overlaps) {
+                return Err(KernelError::NoExec);
+            }
+            segments.push(start..end);
+        }
+        Ok(())
+    }
+
+    /// Builds a user stack and initializes it with arguments and environment
+    /// variables.
     ///
     /// This function sets up a new stack for the process by allocating memory,
-    /// pushing program arguments (`argv`), and preparing the initial register
-    /// state.
+    /// pushing program arguments (`argv`) and environment variables (`envp`),
+    /// and preparing the initial register state.
     ///
     /// # Parameters
     /// - `arguments`: A slice of strs representing the command-line arguments
     ///   (`argv`).
+    /// - `environment`: A slice of `KEY=VALUE` strs representing the
+    ///   environment variables (`envp`).
     /// - `regs`: A mutable reference to the register state, which will be
     ///   updated with the initial stack pointer (`sp`) and argument count
     ///   (`argc`).
@@ -225,20 +408,28 @@ impl<P: Pager> LoadContext<P> {
     ///   argument copying.
     ///
     /// # Behavior
-    /// - Pushes the argument strings onto the stack.
-    /// - Sets up `argv` and `argc` for the process.
+    /// - Pushes the argument and environment strings onto the stack.
+    /// - Lays out `argv[]`, `NULL`, `envp[]`, `NULL` from low to high
+    ///   addresses.
+    /// - Sets up `argc`, `argv`, and `envp` for the process.
     /// - Aligns the stack pointer to ensure proper function call execution.
     ///
     /// # Safety
     /// - The function must be called before transferring control to user space.
     /// - The memory layout should follow the standard calling convention for
     ///   argument passing.
-    pub fn build_stack(&mut self, arguments: &[&str]) -> Result<(), KernelError> {
+    pub fn build_stack(
+        &mut self,
+        arguments: &[&str],
+        environment: &[&str],
+    ) -> Result<(), KernelError> {
         let Self {
             mm_struct: mm_state,
             regs,
+            stack_permission,
+            ..
         } = self;
-        let mut builder = StackBuilder::new(mm_state)?;
+        let mut builder = StackBuilder::new(mm_state, *stack_permission)?;
         todo!()
     }
 
@@ -266,13 +457,44 @@ impl<P: Pager> LoadContext<P> {
     /// - Loads program headers ([`PType::Load`]) into memory.
     /// - Allocates and builds the user stack.
     /// - Initializes the register state (`rip` -> entry point, `rsp` -> stack
-    ///   pointer, arg1 -> the number of arguments, arg1 -> address of arguments
-    ///   vector.).
-    pub fn load(mut self, file: &RegularFile, args: &[&str]) -> Result<Self, KernelError> {
+    ///   pointer, arg1 -> the number of arguments, arg2 -> address of arguments
+    ///   vector, arg3 -> address of the (empty) environment vector.).
+    pub fn load(self, file: &RegularFile, args: &[&str]) -> Result<Self, KernelError> {
+        self.load_with_env(file, args, &[])
+    }
+
+    /// Initializes a user process from an ELF executable, like
+    /// [`LoadContext::load`], additionally passing environment variables.
+    ///
+    /// This is the `execve`-style entry of the loader: `env` is a slice of
+    /// `KEY=VALUE` strs that the program receives as `envp`.
+    ///
+    /// # Parameters
+    /// - `file`: A reference to the ELF executable file.
+    /// - `args`: A slice of strs representing the command-line arguments
+    ///   (`argv`).
+    /// - `env`: A slice of strs representing the environment variables
+    ///   (`envp`).
+    ///
+    /// # Returns
+    /// - `Ok(Self)` on success.
+    /// - `Err(KernelError)` if an error occurs while loading the ELF file or
+    ///   setting up memory.
+    ///
+    /// On success, the function symbols of the program are kept in
+    /// [`LoadContext::symbols`]. The calling thread is left untouched, as it
+    /// may not be the one that runs the program.
+    pub fn load_with_env(
+        mut self,
+        file: &RegularFile,
+        args: &[&str],
+        env: &[&str],
+    ) -> Result<Self, KernelError> {
         if let Some(elf) = elf::Elf::from_file(file) {
+            self.symbols = elf.symbols().map(Arc::new);
             *self.regs.rip() = elf.header.e_entry as usize;
             self.load_phdr(elf)?;
-            self.build_stack(args)?;
+            self.build_stack(args, env)?;
 
             Ok(self)
         } else {
@@ -2,6 +2,11 @@
 use crate::{mm_struct::MmStruct, pager::Pager};
 use keos::{KernelError, addressing::Va, mm::page_table::Permission};
 
+/// The highest address of the user stack; the stack grows downward from here.
+pub const STACK_TOP: usize = 0x4748_0000;
+/// The size of the region reserved for the user stack below [`STACK_TOP`].
+pub const STACK_SIZE: usize = 0x10000;
+
 /// A utility for constructing a user-space stack layout.
 ///
 /// [`StackBuilder`] provides methods to allocate, align, and push data onto
@@ -30,21 +35,22 @@ impl<'a, P: Pager> StackBuilder<'a, P> {
     /// Creates a new [`StackBuilder`] instance for building a user-space stack.
     ///
     /// The stack is initialized at virtual address `0x4748_0000` and grows
-    /// downward as data is pushed onto it.
+    /// downward as data is pushed onto it. The stack region is mapped with
+    /// `permission`, which the loader derives from the `PT_GNU_STACK` header.
     ///
     /// # Returns
     /// A new [`StackBuilder`] with an empty stack and no allocated pages.
-    pub fn new(mm_state: &'a mut MmStruct<P>) -> Result<Self, KernelError> {
+    pub fn new(mm_state: &'a mut MmStruct<P>, permission: Permission) -> Result<Self, KernelError> {
         mm_state
             .do_mmap(
-                Va::new(0x4748_0000 - 0x10000).unwrap(),
-                0x10000,
-                Permission::READ | Permission::WRITE | Permission::USER,
+                Va::new(STACK_TOP - STACK_SIZE).unwrap(),
+                STACK_SIZE,
+                permission,
                 None,
                 0,
             )
             .map(|_| Self {
-                sp: Va::new(0x4748_0000).unwrap(),
+                sp: Va::new(STACK_TOP).unwrap(),
                 mm_state,
             })
     }
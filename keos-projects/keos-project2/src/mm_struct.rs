@@ -93,12 +93,34 @@ use crate::{page_table::PageTable, pager::Pager};
 use core::ops::Range;
 use keos::{
     KernelError,
-    addressing::Va,
+    addressing::{PAGE_MASK, Va},
     fs::RegularFile,
     mm::{PageRef, page_table::Permission},
 };
 use keos_project1::{file_struct::FileStruct, syscall::SyscallAbi};
 
+/// The start of the user address space.
+///
+/// The first page is never mapped, so that a NULL dereference faults.
+pub const USER_SPACE_START: usize = 0x1000;
+
+/// The end of the lower canonical half, where user mappings live.
+pub const USER_SPACE_END: usize = 0x0000_8000_0000_0000;
+
+/// Validates the address and length arguments of `mmap` and `munmap`.
+///
+/// Both must be non-zero and page-aligned, and the range they describe must
+/// lie within the user address space.
+pub fn user_range(addr: usize, length: usize) -> Result<Range<Va>, KernelError> {
+    if addr < USER_SPACE_START || length == 0 || (addr | length) & PAGE_MASK != 0 {
+        return Err(KernelError::InvalidArgument);
+    }
+    addr.checked_add(length)
+        .filter(|end| *end <= USER_SPACE_END)
+        .and_then(|end| Some(Va::new(addr)?..Va::new(end)?))
+        .ok_or(KernelError::InvalidArgument)
+}
+
 /// The [`MmStruct`] represents the memory state for a specific process,
 /// corresponding to the Linux kernel's `struct mm_struct`.
 ///
@@ -208,7 +230,7 @@ impl<P: Pager> MmStruct<P> {
     /// ```
     /// - `addr`: Desired starting address of the mapping (must be page-aligned
     ///   and non-zero).
-    /// - `length`: Number of bytes to map (must be non-zero).
+    /// - `length`: Number of bytes to map (must be page-aligned and non-zero).
     /// - `prot`: Desired memory protection flags.
     /// - `fd`: File descriptor of the file to be mapped.
     /// - `offset`: Offset in the file where mapping should begin.
@@ -226,7 +248,8 @@ impl<P: Pager> MmStruct<P> {
     /// conditions must be met:
     ///
     /// - `addr` must be non-zero and page-aligned.
-    /// - `length` must be non-zero.
+    /// - `length` must be non-zero and page-aligned.
+    /// - The mapping must lie within the user address space.
     /// - The file descriptor must refer to a regular file or -1 for anonymous
     ///   mapping.
     /// - The mapping must not overlap with any already mapped region, including
@@ -248,36 +271,39 @@ impl<P: Pager> MmStruct<P> {
         fstate: &mut FileStruct,
         abi: &SyscallAbi,
     ) -> Result<usize, KernelError> {
-        self.do_mmap(todo!(), todo!(), todo!(), todo!(), todo!())
+        // The address and length are checked in the same way as `munmap`.
+        let addr = user_range(abi.arg1, abi.arg2)?.start;
+        self.do_mmap(addr, abi.arg2, todo!(), todo!(), todo!())
     }
 
-    /// Unmaps a memory-mapped file region.
+    /// Unmaps a range of memory-mapped pages.
     ///
-    /// This function implements the `munmap` system call, which removes a
-    /// previously established memory mapping created by `mmap`. It releases
-    /// the virtual memory associated with the mapping.
+    /// This function implements the `munmap` system call, which removes the
+    /// pages in a range previously mapped by `mmap`. It releases the virtual
+    /// memory associated with the range.
     ///
     /// # Syscall API
     /// ```c
-    /// int munmap(void *addr);
+    /// int munmap(void *addr, size_t length);
     /// ```
-    /// - `addr`: The starting virtual address of the mapping to unmap. This
-    ///   must match the address returned by a previous call to `mmap` by the
-    ///   same process and must not have been unmapped already.
+    /// - `addr`: The starting virtual address of the range to unmap (must be
+    ///   page-aligned and non-zero).
+    /// - `length`: Number of bytes to unmap (must be page-aligned and
+    ///   non-zero).
     ///
     /// # Arguments
     ///
-    /// * `fstate` - Mutable reference to the current file state.
     /// * `abi` - A reference to the system call arguments, including the
-    ///   address to unmap.
+    ///   address and the length of the range to unmap.
     ///
     /// # Behavior
     ///
-    /// - Unmaps the virtual memory region starting at `addr` that was
-    ///   previously mapped via `mmap`.
+    /// - Unmaps the pages in `[addr, addr + length)`. Every page in the range
+    ///   must belong to a mapping established by `mmap` that is not unmapped
+    ///   yet.
+    /// - The range may cover only a part of a mapping. The rest of the mapping
+    ///   stays accessible, split in two if the range lies in its middle.
     /// - Unmodified pages are simply discarded.
-    /// - The virtual pages corresponding to the mapping are removed from the
-    ///   process's address space.
     ///
     /// # Additional Notes
     ///
@@ -288,11 +314,13 @@ impl<P: Pager> MmStruct<P> {
     ///
     /// # Returns
     ///
-    /// Returns `Ok(0)` on success or a [`KernelError`] if the address is
-    /// invalid or does not correspond to an active memory mapping.
+    /// Returns `Ok(0)` on success or a [`KernelError`] if the range is
+    /// invalid or is not entirely covered by active memory mappings.
     pub fn munmap(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
-        // Calls the pager's munmap method with placeholders for arguments.
-        self.pager.munmap(&mut self.page_table, todo!())
+        let range = user_range(abi.arg1, abi.arg2)?;
+        // Calls the real implementation in pager.
+        let Self { page_table, pager } = self;
+        pager.munmap(page_table, range.start, abi.arg2)
     }
 
     /// Find a mapped page at the given virtual address and apply a function to
@@ -331,4 +359,21 @@ impl<P: Pager> MmStruct<P> {
             Err(KernelError::BadAddress)
         }
     }
+
+    /// Returns the resident set size of the process, in pages.
+    ///
+    /// This implements the `getrss` system call:
+    /// ```c
+    /// long getrss(void);
+    /// ```
+    ///
+    /// The count is derived from the page table on each call rather than kept
+    /// as a separate counter, so it always agrees with the mappings, including
+    /// those the pager installs directly. Pages that are not yet faulted in
+    /// are not resident. A page shared copy-on-write after `fork` is counted
+    /// in full by every process mapping it, as Linux does, so the sizes of
+    /// related processes may add up to more than the memory in use.
+    pub fn rss(&self) -> usize {
+        self.page_table.resident_pages()
+    }
 }
@@ -131,13 +131,20 @@
 //! [`StaleTLBEntry`]: StaleTLBEntry
 //! [`section`]: crate::mm_struct
 
-use alloc::boxed::Box;
+use alloc::{boxed::Box, collections::BTreeMap};
 use core::ops::Deref;
 use keos::{
     addressing::{Kva, Pa, Va},
-    mm::{Page, page_table::*},
+    mm::{Page, PageRef, page_table::*},
+    sync::SpinLock,
 };
 
+/// Last-level page tables shared by [`PageTable::share_with`], keyed by their
+/// physical address, with the number of page tables that refer to each.
+///
+/// A table leaves this map when a single page table refers to it again.
+static SHARED_TABLES: SpinLock<BTreeMap<usize, usize>> = SpinLock::new(BTreeMap::new());
+
 /// Represents page table indices for a given virtual address (VA).
 ///
 /// In the x86_64 architecture, virtual addresses are translated to physical
@@ -286,6 +293,8 @@ impl PageTable {
         perm: Permission,
     ) -> Result<(), PageTableMappingError> {
         let indices = PtIndices::from_va(va)?;
+        // A table shared by `fork` must be copied before it is modified.
+        self.unshare(va);
         // Hint: Use `Page::new()` to allocate tables.
         todo!()
     }
@@ -306,6 +315,8 @@ impl PageTable {
     /// fails (e.g., the virtual address was not previously mapped).
     pub fn unmap(&mut self, va: Va) -> Result<Page, PageTableMappingError> {
         let indices = PtIndices::from_va(va)?;
+        // A table shared by `fork` must be copied before it is modified.
+        self.unshare(va);
         // Hint: Use `Page::from_pa()`.
         let pa = todo!();
         Ok(StaleTLBEntry::new(va, unsafe { Page::from_pa(pa) }).invalidate())
@@ -349,9 +360,240 @@ impl PageTable {
     /// does not exist (e.g., if the address is not mapped).
     pub fn walk_mut(&mut self, va: Va) -> Result<Walked<'_>, PageTableMappingError> {
         let indices = PtIndices::from_va(va)?;
+        // A table shared by `fork` must be copied before it is modified.
+        self.unshare(va);
         todo!()
     }
 
+    /// Shares the last-level tables of this page table with `child`.
+    ///
+    /// Instead of copying every mapping, `child` gets its own PML4, PDP, and
+    /// PD tables whose entries refer to the same last-level tables as this
+    /// page table. Each shared table is write-protected at its page directory
+    /// entry in both page tables, so the first write through either one
+    /// faults. Every method that modifies a mapping ([`PageTable::do_map`],
+    /// [`PageTable::unmap`], and [`PageTable::walk_mut`]) first copies the
+    /// shared table covering the address, so that the other side never
+    /// observes the change.
+    ///
+    /// Each page mapped by a shared table is referenced once per page table
+    /// sharing it, so either side can be torn down independently.
+    ///
+    /// The caller must shoot down the TLB of this page table afterward, as
+    /// its page directory entries lost the write permission.
+    pub fn share_with(&mut self, child: &mut PageTable) {
+        for pml4i in 0..PageTableRoot::KBASE {
+            let Some(_) = self.0[pml4i].pa() else {
+                continue;
+            };
+            let pdp = Page::new().into_raw();
+            child.0[pml4i] = Pml4e(pdp.into_usize() | self.0[pml4i].flags().bits());
+
+            let pdp_iter = self.0[pml4i].into_pdp_mut().unwrap().iter_mut();
+            let child_pdp = child.0[pml4i].into_pdp_mut().unwrap();
+            for (pdpe, child_pdpe) in pdp_iter.zip(child_pdp.iter_mut()) {
+                let Some(_) = pdpe.pa() else {
+                    continue;
+                };
+                let pd = Page::new().into_raw();
+                *child_pdpe = Pdpe(pd.into_usize() | pdpe.flags().bits());
+
+                let pd_iter = pdpe.into_pd_mut().unwrap().iter_mut();
+                let child_pd = child_pdpe.into_pd_mut().unwrap();
+                for (pde, child_pde) in pd_iter.zip(child_pd.iter_mut()) {
+                    let Some(pt) = pde.pa() else {
+                        continue;
+                    };
+                    // The child holds its own reference to the table and to
+                    // every page mapped by it.
+                    unsafe {
+                        PageRef::from_pa(pt).into_page().into_raw();
+                        for pa in pde.into_pt().unwrap().iter().filter_map(Pte::pa) {
+                            PageRef::from_pa(pa).into_page().into_raw();
+                        }
+                    }
+                    let mut shared = SHARED_TABLES.lock();
+                    *shared.entry(pt.into_usize()).or_insert(1) += 1;
+                    shared.unlock();
+
+                    let flags = pde.flags() - PdeFlags::RW;
+                    pde.set_flags(flags);
+                    *child_pde = Pde(pt.into_usize() | flags.bits());
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the last-level table covering `va` is write-protected
+    /// because it is (or was) shared by [`PageTable::share_with`].
+    ///
+    /// Pages mapped by such a table are read-only regardless of the
+    /// permission in their own page table entries.
+    pub fn is_table_write_protected(&self, va: Va) -> bool {
+        let va = va.into_usize();
+        self.0[(va >> 39) & 0x1ff]
+            .into_pdp()
+            .ok()
+            .and_then(|pdp| pdp[(va >> 30) & 0x1ff].into_pd().ok())
+            .map(|pd| pd[(va >> 21) & 0x1ff])
+            .is_some_and(|pde| pde.pa().is_some() && !pde.flags().contains(PdeFlags::RW))
+    }
+
+    /// Returns the number of user pages currently mapped by this page table.
+    ///
+    /// The count is taken from the present last-level entries below
+    /// [`PageTableRoot::KBASE`], so it covers every mapping regardless of
+    /// whether it was installed through [`PageTable::map`], a [`Walked`]
+    /// entry, or [`PageTable::share_with`]. A page shared by several page
+    /// tables is counted once in each of them.
+    pub fn resident_pages(&self) -> usize {
+        (0..PageTableRoot::KBASE)
+            .filter_map(|pml4i| self.0[pml4i].into_pdp().ok())
+            .flat_map(|pdp| pdp.iter().filter_map(|pdpe| pdpe.into_pd().ok()))
+            .flat_map(|pd| pd.iter().filter_map(|pde| pde.into_pt().ok()))
+            .map(|pt| pt.iter().filter(|pte| pte.pa().is_some()).count())
+            .sum()
+    }
+
+    /// Returns an iterator over the user mappings of this page table.
+    ///
+    /// The iterator walks the PML4, PDP, PD, and PT lazily in the order of
+    /// the virtual address, and yields the virtual address, the physical
+    /// address, and the effective [`Permission`] of each present page below
+    /// [`PageTableRoot::KBASE`]. A 2 MiB page mapped directly by a page
+    /// directory entry is yielded as its 512 pages of 4 KiB.
+    ///
+    /// The permission accounts for the write protection of a table shared by
+    /// [`PageTable::share_with`]. Every present page is readable.
+    pub fn iter_mappings(&self) -> impl Iterator<Item = (Va, Pa, Permission)> + '_ {
+        (0..PageTableRoot::KBASE)
+            .filter_map(|pml4i| Some((pml4i << 39, self.0[pml4i].into_pdp().ok()?)))
+            .flat_map(|(base, pdp)| {
+                pdp.iter()
+                    .enumerate()
+                    .filter_map(move |(pdpi, pdpe)| Some((base | pdpi << 30, pdpe.into_pd().ok()?)))
+            })
+            .flat_map(|(base, pd)| {
+                pd.iter()
+                    .enumerate()
+                    .filter(|(_, pde)| pde.pa().is_some())
+                    .map(move |(pdi, pde)| (base | pdi << 21, pde))
+            })
+            .flat_map(|(base, pde)| {
+                let huge = pde.flags().contains(PdeFlags::PS);
+                let pt = if huge { None } else { pde.into_pt().ok() };
+                (0..512).filter_map(move |pti| {
+                    let (pa, flags) = if huge {
+                        let flags =
+                            PteFlags::from_bits_truncate((pde.flags() - PdeFlags::PS).bits());
+                        (pde.pa()? + (pti << 12), flags)
+                    } else {
+                        let pte = pt?[pti];
+                        (pte.pa()?, pte.flags())
+                    };
+                    let mut perm = Permission::READ;
+                    if flags.contains(PteFlags::RW) && pde.flags().contains(PdeFlags::RW) {
+                        perm |= Permission::WRITE;
+                    }
+                    if !flags.contains(PteFlags::XD) {
+                        perm |= Permission::EXECUTABLE;
+                    }
+                    if flags.contains(PteFlags::US) {
+                        perm |= Permission::USER;
+                    }
+                    Some((Va::new(base | pti << 12)?, pa, perm))
+                })
+            })
+    }
+
+    /// Returns the page directory entry covering `va`, if any.
+    fn pde_mut(&mut self, va: Va) -> Option<&mut Pde> {
+        let va = va.into_usize();
+        let pdp = self.0[(va >> 39) & 0x1ff].into_pdp_mut().ok()?;
+        let pd = pdp[(va >> 30) & 0x1ff].into_pd_mut().ok()?;
+        Some(&mut pd[(va >> 21) & 0x1ff])
+    }
+
+    /// Gives this page table a private copy of the last-level table covering
+    /// `va`, if it is shared by [`PageTable::share_with`].
+    ///
+    /// Pages mapped by the table stay shared, so their entries are
+    /// write-protected in both copies; a later write to them is handled as an
+    /// ordinary copy-on-write fault. When this page table is the last one
+    /// referring to the table, it is reused in place.
+    fn unshare(&mut self, va: Va) {
+        let Some(pde) = self.pde_mut(va) else {
+            return;
+        };
+        let Some(pt) = pde.pa() else {
+            return;
+        };
+        if pde.flags().contains(PdeFlags::RW) {
+            return;
+        }
+
+        let mut shared = SHARED_TABLES.lock();
+        if let Some(refs) = shared.get_mut(&pt.into_usize()) {
+            let copy = Page::new();
+            let dst = unsafe {
+                core::slice::from_raw_parts_mut(copy.kva().into_usize() as *mut Pte, 512)
+            };
+            for (src, dst) in pde.into_pt_mut().unwrap().iter_mut().zip(dst.iter_mut()) {
+                if src.pa().is_some() {
+                    unsafe {
+                        src.set_flags(src.flags() - PteFlags::RW);
+                    }
+                    *dst = *src;
+                }
+            }
+            *refs -= 1;
+            if *refs == 1 {
+                shared.remove(&pt.into_usize());
+            }
+            *pde = Pde(copy.into_raw().into_usize() | (pde.flags() | PdeFlags::RW).bits());
+            // Drop the reference to the shared table. The pages it maps are
+            // now referenced by the private copy instead.
+            drop(unsafe { Page::from_pa(pt) });
+        } else {
+            pde.set_flags(pde.flags() | PdeFlags::RW);
+        }
+        shared.unlock();
+        tlb_shutdown(&self.0);
+    }
+
+    /// Releases every last-level table this page table still shares with
+    /// others, along with its references to the pages they map.
+    ///
+    /// Tables referred to only by this page table are left for
+    /// [`PageTable::clear`].
+    fn release_shared(&mut self) {
+        let mut shared = SHARED_TABLES.lock();
+        for pml4i in 0..PageTableRoot::KBASE {
+            let Ok(pdp) = self.0[pml4i].into_pdp_mut() else {
+                continue;
+            };
+            for pd in pdp.iter_mut().filter_map(|pdpe| pdpe.into_pd_mut().ok()) {
+                for pde in pd.iter_mut() {
+                    let Some(refs) = pde.pa().and_then(|pt| shared.get_mut(&pt.into_usize()))
+                    else {
+                        continue;
+                    };
+                    *refs -= 1;
+                    if *refs == 1 {
+                        shared.remove(&pde.pa().unwrap().into_usize());
+                    }
+                    unsafe {
+                        for pa in pde.into_pt().unwrap().iter().filter_map(Pte::pa) {
+                            drop(Page::from_pa(pa));
+                        }
+                        drop(Page::from_pa(pde.clear().unwrap()));
+                    }
+                }
+            }
+        }
+        shared.unlock();
+    }
+
     /// Clears all entries from the page table and deallocates associated pages.
     ///
     /// This function traverses all levels of the page table, unmapping each
@@ -395,6 +637,7 @@ impl Drop for PageTable {
             self.pa().into_usize(),
             "Trying to drop activated page table."
         );
+        self.release_shared();
         self.clear()
     }
 }
@@ -450,6 +693,26 @@ impl Walked<'_> {
             Ok(())
         }
     }
+
+    /// Changes the flags of the current mapping, keeping the mapped page.
+    ///
+    /// This is used to change the permission of a mapped page, e.g., to make
+    /// a write-protected page writable.
+    ///
+    /// # Returns
+    /// - `Some(StaleTLBEntry)` if the entry is mapped. The caller must
+    ///   invalidate it, so that the CPU observes the new flags.
+    /// - `None` if the entry is not valid.
+    pub fn set_flags(&mut self, flags: PteFlags) -> Option<StaleTLBEntry> {
+        let pa = self.pte.pa()?;
+        unsafe {
+            self.pte.set_flags(flags);
+            Some(StaleTLBEntry::new(
+                self.addr,
+                PageRef::from_pa(pa).into_page(),
+            ))
+        }
+    }
 }
 
 impl Deref for Walked<'_> {
@@ -66,19 +66,29 @@ pub trait Pager {
     where
         Self: Sized;
 
-    /// Unmaps a previously mapped memory region.
+    /// Unmaps a range of previously mapped memory.
     ///
-    /// This function removes memory mappings corresponding to `addr`.
+    /// This function removes the memory mappings in `[addr, addr + size)`.
+    /// The range may cover only a part of a region mapped by
+    /// [`Pager::mmap`]; the remaining parts of the region stay mapped.
     ///
     /// # Parameters
     /// - `page_table`: The process’s page table to modify.
-    /// - `addr`: Starting virtual address of the region to unmap.
+    /// - `addr`: Starting virtual address of the range to unmap. Must be
+    ///   page-aligned.
+    /// - `size`: The size of the range to unmap in bytes. Must be page-aligned
+    ///   and greater than zero.
     ///
     /// # Returns
     /// - `Ok(n)`: Number of bytes successfully unmapped.
     /// - `Err([KernelError])`: On invalid addresses, unmapped regions, or other
     ///   errors.
-    fn munmap(&mut self, page_table: &mut PageTable, addr: Va) -> Result<usize, KernelError>
+    fn munmap(
+        &mut self,
+        page_table: &mut PageTable,
+        addr: Va,
+        size: usize,
+    ) -> Result<usize, KernelError>
     where
         Self: Sized;
 
@@ -2,10 +2,22 @@
 //!
 //! This file defines the process model of the project2.
 
-use keos::{KernelError, thread::Current};
+use alloc::{string::String, vec::Vec};
+use keos::{
+    KernelError,
+    mm::page_table::load_pt,
+    syscall::{
+        Registers,
+        uaccess::{UserCString, UserPtrRO},
+    },
+    thread::Current,
+};
 use keos_project1::{file_struct::FileStruct, syscall::SyscallAbi};
 
-use crate::{eager_pager::EagerPager, mm_struct::MmStruct};
+use crate::{eager_pager::EagerPager, loader::LoadContext, mm_struct::MmStruct, pager::Pager};
+
+/// The maximum number of entries accepted in `argv` or `envp` by `execve`.
+const EXECVE_MAX_STRINGS: usize = 256;
 
 /// A process state of project 2, which contains file struct and mm struct.
 pub struct Process {
@@ -64,4 +76,101 @@ impl Process {
     pub fn exit(&self, abi: &SyscallAbi) -> Result<usize, KernelError> {
         Current::exit(abi.arg1 as i32)
     }
+
+    /// Replace the image of the calling process with a new program.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int execve(const char *pathname, char *const argv[], char *const envp[]);
+    /// ```
+    /// - `pathname`: Path of the ELF executable, resolved from the current
+    ///   working directory.
+    /// - `argv`: A NULL-terminated array of argument strings.
+    /// - `envp`: A NULL-terminated array of `KEY=VALUE` strings. NULL is
+    ///   treated as an empty environment.
+    ///
+    /// # Returns
+    /// - Never returns on success.
+    /// - Returns a `KernelError` if the program could not be loaded. The
+    ///   calling process is left untouched in this case.
+    ///
+    /// # Behavior
+    /// The program is loaded into a fresh [`MmStruct`] with
+    /// [`LoadContext::load_with_env`]. Only after loading succeeds, the new
+    /// address space replaces the old one, and the thread jumps to the new
+    /// entry point with [`Registers::launch`]. The working directory and the
+    /// open file descriptors are kept across `execve`, except the ones marked
+    /// close-on-exec, which are closed with [`FileStruct::close_on_exec`].
+    pub fn execve(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        let regs = self.exec_image(abi)?;
+        // Every temporary used while loading is released at this point, as
+        // `launch` never returns to run their destructors.
+        regs.launch()
+    }
+
+    /// Load the program requested by `execve` and install its address space,
+    /// returning the initial registers of the new image.
+    fn exec_image(&mut self, abi: &SyscallAbi) -> Result<Registers, KernelError> {
+        let LoadContext {
+            mm_struct,
+            regs,
+            symbols,
+            ..
+        } = load_image(&self.file_struct, abi)?;
+
+        // Switch to the new page table before the old one is released.
+        let old = core::mem::replace(&mut self.mm_struct, mm_struct);
+        load_pt(self.mm_struct.page_table.pa());
+        drop(old);
+        self.file_struct.close_on_exec();
+        keos::task::set_user_symbols(symbols);
+        Ok(regs)
+    }
+}
+
+/// Load the program requested by `execve` into a fresh [`MmStruct`],
+/// returning the [`LoadContext`] that holds it with the initial registers and
+/// the symbols of the new image.
+///
+/// The arguments are the ones of `execve`, and the path is resolved from the
+/// working directory of `file_struct`. Nothing of the calling process is
+/// changed, so that the caller can install the new image only after loading
+/// succeeds.
+pub fn load_image<P: Pager>(
+    file_struct: &FileStruct,
+    abi: &SyscallAbi,
+) -> Result<LoadContext<P>, KernelError> {
+    let path = UserCString::new(abi.arg1).read()?;
+    let args = read_user_strings(abi.arg2)?;
+    let env = read_user_strings(abi.arg3)?;
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let env = env.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let file = file_struct
+        .cwd
+        .open(&path)?
+        .into_regular_file()
+        .ok_or(KernelError::NoExec)?;
+    LoadContext::new().load_with_env(&file, &args, &env)
+}
+
+/// Read a NULL-terminated array of user strings at `addr`.
+///
+/// A NULL `addr` is read as an empty array.
+fn read_user_strings(addr: usize) -> Result<Vec<String>, KernelError> {
+    let mut strings = Vec::new();
+    if addr == 0 {
+        return Ok(strings);
+    }
+    loop {
+        let ptr =
+            UserPtrRO::<usize>::new(addr + strings.len() * core::mem::size_of::<usize>()).get()?;
+        if ptr == 0 {
+            return Ok(strings);
+        }
+        if strings.len() == EXECVE_MAX_STRINGS {
+            return Err(KernelError::InvalidArgument);
+        }
+        strings.push(UserCString::new(ptr).read()?);
+    }
 }
@@ -15,4 +15,9 @@ fn main() {
     }
 
     build_simple_fs("sfs.bin");
+
+    // The swap disk, at slot 2.
+    std::fs::File::create("swap.bin")
+        .and_then(|f| f.set_len(128 * 1024 * 1024))
+        .expect("Failed to create swap.bin");
 }
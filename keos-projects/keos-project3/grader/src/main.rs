@@ -31,9 +31,12 @@ pub unsafe fn main(_config_builder: SystemConfigurationBuilder) {
     keos::TestDriver::<Process>::start([
         // Mmap.
         &mm_struct::do_mmap,
+        &mm_struct::anon_demand_zero,
+        &mm_struct::anon_zero_page,
         &mm_struct::access_ok_normal,
         &mm_struct::access_ok_invalid,
         &mm_struct::bad_addr_0,
+        &mm_struct::fork_share_tables,
         // user programs.
         &userprog::arg_parse,
         &userprog::sys_open,
@@ -57,6 +60,7 @@ pub unsafe fn main(_config_builder: SystemConfigurationBuilder) {
         &userprog::mm_mmap_error_protection_exec,
         &userprog::mm_munmap,
         &userprog::mm_munmap2,
+        &userprog::mm_munmap_partial,
         &userprog::mm_munmap_error_bad_addr,
         &userprog::mm_munmap_error_double_free,
         &userprog::mm_munmap_error_unaligned,
@@ -68,6 +72,9 @@ pub unsafe fn main(_config_builder: SystemConfigurationBuilder) {
         &userprog_part_2::cow_perm,
         &userprog_part_2::cow_sys,
         &userprog_part_2::cow_cleanup_stress,
+        &userprog_part_2::rss,
+        &userprog_part_2::oom,
+        &userprog_part_2::swap,
         // CoW test
         &userprog_part_2::fork2,
     ]);
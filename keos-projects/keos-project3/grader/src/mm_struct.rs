@@ -1,10 +1,13 @@
 use keos::{
     KernelError,
-    addressing::Va,
-    mm::page_table::{Permission, Pml4e},
+    addressing::{PAGE_SIZE, Va},
+    mm::{
+        Page, free_page_count,
+        page_table::{Permission, Pml4e, PteFlags},
+    },
 };
-use keos_project2::mm_struct::MmStruct;
-use keos_project3::lazy_pager::LazyPager;
+use keos_project2::{mm_struct::MmStruct, pager::Pager};
+use keos_project3::lazy_pager::{LazyPager, PageFaultReason};
 
 pub fn do_mmap() {
     let mut mm: MmStruct<LazyPager> = MmStruct::new();
@@ -43,6 +46,148 @@ pub fn do_mmap() {
     );
 }
 
+#[validate_alloc]
+pub fn anon_demand_zero() {
+    const NPAGES: usize = 8;
+    let mut mm: MmStruct<LazyPager> = MmStruct::new();
+    let va = Va::new(0x1000_0000).unwrap();
+
+    assert_eq!(
+        mm.do_mmap(
+            va,
+            NPAGES * PAGE_SIZE,
+            Permission::READ | Permission::WRITE,
+            None,
+            0
+        ),
+        Ok(0x1000_0000),
+        "Anonymous mmap() to valid Virtual Address should succeed"
+    );
+    assert_eq!(
+        mm.rss(),
+        0,
+        "Anonymous mmap() should not allocate any page before the first access"
+    );
+
+    for i in 0..NPAGES {
+        let page_va = va + i * PAGE_SIZE;
+        assert_eq!(
+            mm.get_user_page_and(page_va, |page, _| page.inner().iter().all(|b| *b == 0)),
+            Ok(true),
+            "Anonymous page should be zero-filled on the first access"
+        );
+        assert_eq!(
+            mm.rss(),
+            i + 1,
+            "Only the accessed pages should be resident"
+        );
+        assert!(
+            mm.get_user_page_and(page_va, |mut page, _| page.inner_mut().fill(i as u8 + 1))
+                .is_ok()
+        );
+    }
+    for i in 0..NPAGES {
+        assert_eq!(
+            mm.get_user_page_and(va + i * PAGE_SIZE, |page, _| page
+                .inner()
+                .iter()
+                .all(|b| *b == i as u8 + 1)),
+            Ok(true),
+            "Anonymous page should keep the written contents"
+        );
+    }
+    assert_eq!(mm.rss(), NPAGES);
+
+    let free = free_page_count();
+    let MmStruct { page_table, pager } = &mut mm;
+    assert_eq!(pager.munmap(page_table, va, NPAGES * PAGE_SIZE), Ok(0));
+    assert_eq!(mm.rss(), 0, "munmap() should unmap every resident page");
+    assert!(
+        free_page_count() >= free + NPAGES,
+        "munmap() should free every resident page"
+    );
+    // `#[validate_alloc]` ensures that nothing else is left behind.
+}
+
+#[validate_alloc]
+pub fn anon_zero_page() {
+    const NPAGES: usize = 4096;
+    const WRITTEN: [usize; 3] = [0, 1000, NPAGES - 1];
+    let mut mm: MmStruct<LazyPager> = MmStruct::new();
+    let va = Va::new(0x1000_0000).unwrap();
+    let zero = Page::zero().pa();
+
+    assert_eq!(
+        mm.do_mmap(
+            va,
+            NPAGES * PAGE_SIZE,
+            Permission::READ | Permission::WRITE,
+            None,
+            0
+        ),
+        Ok(0x1000_0000),
+        "Anonymous mmap() to valid Virtual Address should succeed"
+    );
+    let MmStruct { page_table, pager } = &mut mm;
+
+    // Reading the whole region maps the zero page everywhere. Only the page
+    // tables are allocated.
+    let free = free_page_count();
+    for i in 0..NPAGES {
+        let reason = PageFaultReason {
+            fault_addr: va + i * PAGE_SIZE,
+            is_write_access: false,
+            is_present: false,
+        };
+        assert!(pager.handle_page_fault(page_table, &reason).is_ok());
+    }
+    let used = free - free_page_count();
+    assert!(
+        used <= NPAGES / 512 + 2,
+        "Reading demand-zero pages should not allocate them, but it used {used} pages"
+    );
+    for i in 0..NPAGES {
+        let pte = page_table.walk(va + i * PAGE_SIZE).unwrap();
+        assert_eq!(pte.pa(), Some(zero), "A read page should be the zero page");
+        assert!(
+            !pte.flags().contains(PteFlags::RW),
+            "The zero page must be mapped read-only"
+        );
+    }
+
+    // Only the written pages are backed by their own pages.
+    let free = free_page_count();
+    for i in WRITTEN {
+        let reason = PageFaultReason {
+            fault_addr: va + i * PAGE_SIZE,
+            is_write_access: true,
+            is_present: true,
+        };
+        assert!(pager.handle_page_fault(page_table, &reason).is_ok());
+    }
+    assert_eq!(
+        free - free_page_count(),
+        WRITTEN.len(),
+        "Only the written pages should be allocated"
+    );
+    for i in 0..NPAGES {
+        let pte = page_table.walk(va + i * PAGE_SIZE).unwrap();
+        if WRITTEN.contains(&i) {
+            assert_ne!(pte.pa(), Some(zero));
+            assert!(pte.flags().contains(PteFlags::RW));
+        } else {
+            assert_eq!(pte.pa(), Some(zero));
+        }
+    }
+    assert!(
+        Page::zero().inner().iter().all(|b| *b == 0),
+        "The zero page must stay zero-filled"
+    );
+
+    assert_eq!(pager.munmap(page_table, va, NPAGES * PAGE_SIZE), Ok(0));
+    // `#[validate_alloc]` ensures that the written pages are freed.
+}
+
 pub fn bad_addr_0() {
     let mut mm: MmStruct<LazyPager> = MmStruct::new();
     let null_va = Va::new(0).unwrap();
@@ -143,3 +288,64 @@ pub fn access_ok_invalid() {
         "access_ok() with write attempt to read-only memory area should return false"
     );
 }
+
+pub fn fork_share_tables() {
+    let mut mm: MmStruct<LazyPager> = MmStruct::new();
+    // A large, sparse mapping: every page lives in its own 2MiB region, so each
+    // of them needs a separate last-level table.
+    let vas = (0..256).map(|i| Va::new(0x4000_0000 + i * 0x20_0000).unwrap());
+    for va in vas.clone() {
+        assert!(
+            mm.page_table
+                .map(
+                    va,
+                    Page::new(),
+                    Permission::READ | Permission::WRITE | Permission::USER
+                )
+                .is_ok()
+        );
+    }
+
+    let free = free_page_count();
+    let mut child = LazyPager::write_protect_ptes(&mut mm).expect("fork should succeed");
+    let used = free - free_page_count();
+    assert!(
+        used < 16,
+        "fork() should share the last-level tables instead of copying them, but it used {used} pages"
+    );
+
+    for va in vas.clone() {
+        let (parent_pte, child_pte) = (
+            mm.page_table
+                .walk(va)
+                .expect("parent mapping should survive fork()"),
+            child
+                .page_table
+                .walk(va)
+                .expect("child should inherit the mapping"),
+        );
+        assert_eq!(parent_pte.pa(), child_pte.pa());
+        assert!(mm.page_table.is_table_write_protected(va));
+        assert!(child.page_table.is_table_write_protected(va));
+    }
+
+    // Modifying a mapping in the child copies only the table covering it.
+    let va = Va::new(0x4000_0000).unwrap();
+    let free = free_page_count();
+    let flags = child.page_table.walk_mut(va).map(|pte| pte.flags());
+    let used = free - free_page_count();
+    assert!(
+        (1..4).contains(&used),
+        "walk_mut() should copy exactly the shared table, but it used {used} pages"
+    );
+    assert!(!child.page_table.is_table_write_protected(va));
+    assert!(
+        flags.is_ok_and(|flags| !flags.contains(PteFlags::RW)),
+        "Pages under the copied table are still shared, so they must stay write-protected"
+    );
+    assert!(child.page_table.is_table_write_protected(va + 0x20_0000));
+    assert_eq!(
+        mm.page_table.walk(va).unwrap().pa(),
+        child.page_table.walk(va).unwrap().pa()
+    );
+}
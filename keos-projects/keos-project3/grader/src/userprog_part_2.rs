@@ -1,4 +1,5 @@
 use crate::userprog::run_elf;
+use keos_project3::swap::swapon;
 
 #[stdin(b"")]
 #[assert_output(
@@ -38,3 +39,23 @@ pub fn cow_cleanup_stress() {
         assert_eq!(run_elf("fork_cow_cleanup"), 0);
     }
 }
+
+pub fn rss() {
+    assert_eq!(run_elf("mm_rss"), 0);
+}
+
+pub fn oom() {
+    // Running out of memory kills only the faulting process.
+    assert_eq!(run_elf("mm_oom"), -1);
+    // ...and the memory it held is given back to the others.
+    for _ in 0..4 {
+        assert_eq!(run_elf("mm_exit_cleanup"), 0);
+    }
+}
+
+pub fn swap() {
+    // The swap disk is attached at slot 2.
+    swapon(2).expect("Failed to turn on swapping.");
+    // Writes and reads back more memory than the machine has.
+    assert_eq!(run_elf("mm_swap"), 0);
+}
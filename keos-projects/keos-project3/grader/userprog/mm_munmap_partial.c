@@ -0,0 +1 @@
+../../../keos-project2/grader/userprog/mm_munmap_partial.c
\ No newline at end of file
@@ -0,0 +1,22 @@
+#include <debug.h>
+#include <mman.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <syscall.h>
+
+#define TEST_BASE ((void *)0x30000000)
+#define TEST_SIZE (1024 * 1024 * 1024)
+#define PAGE_SIZE 4096
+
+int main(int argc, char *argv[]) {
+  uint8_t *buf = mmap(TEST_BASE, TEST_SIZE, PROT_READ | PROT_WRITE, -1, 0);
+  ASSERT(buf == (uint8_t *)TEST_BASE);
+
+  // The machine has far less memory than the mapping. The kernel must kill
+  // this process on the first fault it cannot serve.
+  for (size_t off = 0; off < TEST_SIZE; off += PAGE_SIZE) {
+    buf[off] = 0xcc;
+  }
+
+  return 0;
+}
@@ -0,0 +1,32 @@
+#include <debug.h>
+#include <mman.h>
+#include <stdint.h>
+#include <syscall.h>
+
+#define TEST_BASE ((void *)0x30000000)
+#define NPAGES 16
+#define PAGE_SIZE 4096
+
+int main(int argc, char *argv[]) {
+  long base = getrss();
+  ASSERT(base > 0);
+
+  uint8_t *buf = mmap(TEST_BASE, NPAGES * PAGE_SIZE, PROT_READ | PROT_WRITE, -1, 0);
+  ASSERT(buf == (uint8_t *)TEST_BASE);
+  // Nothing is resident until the pages are faulted in.
+  ASSERT(getrss() == base);
+
+  for (int i = 0; i < NPAGES; i++) {
+    buf[i * PAGE_SIZE] = (uint8_t)i;
+    ASSERT(getrss() == base + i + 1);
+  }
+
+  // Touching a resident page again does not change the count.
+  buf[0]++;
+  ASSERT(getrss() == base + NPAGES);
+
+  ASSERT(munmap(buf, NPAGES * PAGE_SIZE) == 0);
+  ASSERT(getrss() == base);
+
+  return 0;
+}
@@ -0,0 +1,29 @@
+#include <debug.h>
+#include <mman.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <syscall.h>
+
+#define TEST_BASE ((void *)0x30000000)
+#define TEST_SIZE (160 * 1024 * 1024)
+#define PAGE_SIZE 4096
+
+int main(int argc, char *argv[]) {
+  uint8_t *buf = mmap(TEST_BASE, TEST_SIZE, PROT_READ | PROT_WRITE, -1, 0);
+  ASSERT(buf == (uint8_t *)TEST_BASE);
+
+  // The mapping is larger than the memory of the machine, so the first pages
+  // must be swapped out by the time the last ones are written.
+  for (size_t off = 0; off < TEST_SIZE; off += PAGE_SIZE) {
+    *(size_t *)(buf + off) = off;
+    buf[off + PAGE_SIZE - 1] = (uint8_t)(off / PAGE_SIZE);
+  }
+
+  // Every page is brought back with its contents.
+  for (size_t off = 0; off < TEST_SIZE; off += PAGE_SIZE) {
+    ASSERT(*(size_t *)(buf + off) == off);
+    ASSERT(buf[off + PAGE_SIZE - 1] == (uint8_t)(off / PAGE_SIZE));
+  }
+
+  return 0;
+}
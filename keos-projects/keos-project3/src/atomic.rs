@@ -0,0 +1,95 @@
+//! # Atomic operations on the user memory.
+//!
+//! Lock-free data structures in user space are built on an atomic
+//! compare-and-swap. `atomic_cas` lets user programs run one on a `u64` in
+//! their memory, even where they cannot issue the instruction themselves.
+//!
+//! The kernel does not access the user address directly, as a page fault in
+//! the middle of the operation could not be retried safely. Instead, it
+//! first makes the page present and writable by the [`LazyPager`], as a write
+//! fault would, and then runs `lock cmpxchg` on the kernel mapping of the
+//! physical page. The memory struct stays locked meanwhile, so the page
+//! cannot be unmapped, copied or swapped out under the operation.
+//!
+//! The physical page is what the threads of the process, and the processes
+//! attaching the same shared memory segment, access as well. Therefore, the
+//! operation is atomic against both the other `atomic_cas` calls and the
+//! atomic instructions of user programs on any CPU.
+
+use crate::lazy_pager::{LazyPager, PageFaultReason};
+use core::sync::atomic::{AtomicU64, Ordering};
+use keos::{
+    KernelError,
+    addressing::{Pa, Va},
+    mm::page_table::PteFlags,
+};
+use keos_project1::syscall::SyscallAbi;
+use keos_project2::mm_struct::MmStruct;
+
+/// Returns the physical address of the user address `va`, making its page
+/// present and writable first.
+fn resolve_writable(mm: &mut MmStruct<LazyPager>, va: Va) -> Result<Pa, KernelError> {
+    let page = va.page_down();
+    // A demand paging fault may be followed by a copy-on-write fault.
+    for _ in 0..3 {
+        let present = match mm.page_table.walk(page) {
+            Ok(pte) => {
+                let flags = pte.flags();
+                if flags.contains(PteFlags::P | PteFlags::RW)
+                    && !mm.page_table.is_table_write_protected(page)
+                    && let Some(pa) = pte.pa()
+                {
+                    return Ok(pa + va.offset());
+                }
+                flags.contains(PteFlags::P)
+            }
+            Err(_) => false,
+        };
+        let MmStruct { page_table, pager } = mm;
+        pager
+            .handle_page_fault(
+                page_table,
+                &PageFaultReason {
+                    fault_addr: va,
+                    is_write_access: true,
+                    is_present: present,
+                },
+            )
+            .map_err(|_| KernelError::BadAddress)?;
+    }
+    Err(KernelError::BadAddress)
+}
+
+/// Atomically replaces a `u64` in the user memory if it holds the expected
+/// value.
+///
+/// # Syscall API
+/// ```c
+/// int atomic_cas(uint64_t *addr, uint64_t expected, uint64_t new);
+/// ```
+/// - `addr`: The address of the value. It must be aligned to 8 bytes.
+/// - `expected`: The value that `*addr` must hold to be replaced.
+/// - `new`: The value to store to `*addr`.
+///
+/// # Returns
+/// - 1 if `*addr` held `expected` and is replaced with `new`.
+/// - 0 if `*addr` held another value, which is left unchanged.
+/// - [`KernelError::InvalidArgument`] if `addr` is not aligned.
+/// - [`KernelError::BadAddress`] if `addr` is not writable.
+pub fn atomic_cas(mm: &mut MmStruct<LazyPager>, abi: &SyscallAbi) -> Result<usize, KernelError> {
+    let (addr, expected, new) = (abi.arg1, abi.arg2 as u64, abi.arg3 as u64);
+    if addr % core::mem::align_of::<u64>() != 0 {
+        return Err(KernelError::InvalidArgument);
+    }
+    let va = Va::new(addr).ok_or(KernelError::BadAddress)?;
+    if !mm.access_ok(va..va + core::mem::size_of::<u64>(), true) {
+        return Err(KernelError::BadAddress);
+    }
+    let pa = resolve_writable(mm, va)?;
+    // SAFETY: The aligned `u64` lies in a page mapped writable to the user,
+    // which is kept mapped as the memory struct is locked.
+    let value = unsafe { AtomicU64::from_ptr(pa.into_kva().into_usize() as *mut u64) };
+    Ok(value
+        .compare_exchange(expected, new, Ordering::SeqCst, Ordering::SeqCst)
+        .is_ok() as usize)
+}
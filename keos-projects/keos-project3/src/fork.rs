@@ -35,10 +35,13 @@
 //! In KeOS, copy-on-write works as follow:
 //! 1. When a process invokes a **fork** system call, the kernel makes copy of
 //!    [`FileStruct`].
-//! 2. The kernel write-protected ptes by calling
-//!    [`LazyPager::write_protect_ptes`] to make copy of [`MmStruct`]. This
-//!    marks all writable pages as read-only when the child is created. This
-//!    ensures any future writes will trigger a page fault.
+//! 2. The kernel write-protects the address space by calling
+//!    [`LazyPager::write_protect_ptes`] to make copy of [`MmStruct`]. Rather
+//!    than copying every page table entry, the parent and child share the
+//!    last-level page tables with [`PageTable::share_with`], and each shared
+//!    table is made read-only at its page directory entry. This makes every
+//!    page under it read-only, so any future writes will trigger a page
+//!    fault, while `fork` only allocates the upper-level tables of the child.
 //! 3. After write-protecting pages, the kernel **shuts down the TLB** entries
 //!    for those pages to remove stale writable translations from the CPU's
 //!    cache. This is done via [`tlb_shutdown`].
@@ -53,6 +56,13 @@
 //! page. After mapping the new page, the kernel **invalidates the old TLB
 //! entry** with the [`StaleTLBEntry::invalidate`].
 //!
+//! Note that the table sharing must be broken before the page sharing. When
+//! [`PageTable::walk_mut`] (as well as [`PageTable::do_map`] and
+//! [`PageTable::unmap`]) reaches a shared table, it first gives the process a
+//! private copy of the table, whose entries are write-protected since their
+//! pages are still shared. From then on, the write is an ordinary
+//! copy-on-write fault on a single page.
+//!
 //! ## Implementation Requirements
 //! You need to implement the followings:
 //! - [`LazyPager::write_protect_ptes`]
@@ -66,7 +76,7 @@
 
 use crate::lazy_pager::{LazyPager, PageFaultReason};
 #[cfg(doc)]
-use keos::mm::page_table::StaleTLBEntry;
+use keos::mm::{Page, page_table::StaleTLBEntry};
 use keos::{KernelError, thread::ThreadBuilder};
 use keos_project1::{file_struct::FileStruct, syscall::SyscallAbi};
 use keos_project2::{mm_struct::MmStruct, page_table::PageTable};
@@ -81,10 +91,15 @@ impl LazyPager {
     /// writable copy of the page while preserving the original contents for
     /// other processes that may still share the original page.
     ///
+    /// The first write to a page mapped to the shared zero page
+    /// ([`Page::zero`]) is handled here as well, in the same way.
+    ///
     /// ### Steps:
     /// 1. Find write-protected page table entry with [`PageTable::walk_mut`].
-    /// 2. Allocates a new page and copies the contents of the original page
-    ///    into it.
+    /// 2. Allocates a new page with [`Page::try_new`] and copies the contents
+    ///    of the original page into it. If the allocation fails, returns
+    ///    [`KernelError::NoMemory`] so that only the faulting process is
+    ///    killed.
     /// 3. Updates the page table to point to the new page with write
     ///    permissions.
     /// 4. Invalidates the TLB entry for the faulting address to ensure the CPU
@@ -106,11 +121,12 @@ impl LazyPager {
     /// layout.
     ///
     /// This method is called during `fork` to prepare the address space for
-    /// copy-on-write semantics. It traverses the entire virtual memory
-    /// layout, identifies writable mappings, and rewrites their page table
-    /// entries (PTEs) as read-only. This allows parent and child
-    /// processes to safely share physical memory until one performs a write, at
-    /// which point a private copy is created.
+    /// copy-on-write semantics. Instead of rewriting every writable page table
+    /// entry (PTE), it shares the last-level page tables between the parent
+    /// and the child with [`PageTable::share_with`], which write-protects them
+    /// at the page directory. This allows parent and child processes to safely
+    /// share physical memory until one performs a write, at which point a
+    /// private copy is created.
     ///
     /// After modifying the page tables, stale entries in the **Translation
     /// Lookaside Buffer (TLB)** are invalidated to ensure that the CPU
@@ -130,6 +146,7 @@ impl LazyPager {
     ) -> Result<MmStruct<LazyPager>, KernelError> {
         let MmStruct { page_table, pager } = mm_struct;
         let mut new_page_table = PageTable::new();
+        page_table.share_with(&mut new_page_table);
         todo!()
     }
 }
@@ -182,6 +199,8 @@ impl PageFaultReason {
 /// - Duplicates the parent's file descriptor table.
 /// - File objects are shared and reference-counted across parent and child,
 ///   consistent with the UNIX file model.
+/// - Copies the parent's working directory. A later `chdir` in either process
+///   does not move the other.
 ///
 /// ### ABI and Register State
 /// - Copies the parent’s ABI state into the child.
@@ -5,6 +5,7 @@
 use keos::{
     KernelError,
     addressing::{PAGE_MASK, Va},
+    mm::page_table::PteFlags,
 };
 use keos_project1::{file_struct::FileStruct, syscall::SyscallAbi};
 use keos_project2::mm_struct::MmStruct;
@@ -31,6 +32,9 @@ pub fn get_phys(
 
         Ok(if abi.arg2 == 0 {
             pte.pa().ok_or(KernelError::InvalidArgument)?.into_usize() | abi.arg1 & PAGE_MASK
+        } else if mm.page_table.is_table_write_protected(va) {
+            // Report the effective permission of a page under a shared table.
+            (pte.flags() - PteFlags::RW).bits()
         } else {
             pte.flags().bits()
         })
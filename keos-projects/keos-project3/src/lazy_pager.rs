@@ -50,6 +50,16 @@
 //! [`Page`] object corresponding to that virtual address. The returned page is
 //! then mapped into the page table by the pager.
 //!
+//! ## Zero Page
+//!
+//! Many programs map a large anonymous region and read only parts of it
+//! before writing, or never write some of it at all. Allocating a zero-filled
+//! page on each of these reads wastes memory. Instead, a read fault on a
+//! demand-zero mapping (see [`MmLoader::is_demand_zero`]) maps the single
+//! shared zero page ([`Page::zero`]) **read-only**, without allocating
+//! anything. The first write to such a page raises a copy-on-write fault,
+//! which gives the page a private copy just as after `fork`.
+//!
 //! This loader-based architecture provides a clean separation of concerns:
 //! [`VmAreaStruct`] tracks regions and permissions, while [`MmLoader`]
 //! encapsulates how pages are provisioned. This allows KeOS to support flexible
@@ -60,6 +70,7 @@
 //! - [`LazyPager`]
 //! - [`LazyPager::new`]
 //! - [`LazyPager::mmap`]
+//! - [`LazyPager::mmap_with_loader`]
 //! - [`LazyPager::munmap`]
 //! - [`LazyPager::get_user_page`]
 //! - [`LazyPager::access_ok`]
@@ -68,10 +79,12 @@
 //! - [`VmAreaStruct`]
 //! - [`FileBackedLoader`]
 //! - [`FileBackedLoader::load`]
+//! - [`LazyPager::reclaim`] (see [`swap`])
 //!
 //! After implement the functionalities, move on to the next [`section`].
 //!
 //! [`section`]: mod@crate::fork
+//! [`swap`]: crate::swap
 //! [`EagerPager`]: ../../keos_project2/mmap/struct.EagerPager.html
 
 use alloc::sync::Arc;
@@ -85,6 +98,8 @@ use keos::{
     task::PFErrorCode,
 };
 use keos_project2::{page_table::PageTable, pager::Pager};
+#[cfg(doc)]
+use {crate::swap, crate::swap::SwapEntry};
 
 /// A trait for loading the contents of a virtual memory page on demand.
 ///
@@ -110,9 +125,23 @@ where
     ///   loader.
     ///
     /// # Returns
-    /// - A newly allocated [`Page`] containing the initialized data for the
-    ///   page.
-    fn load(&self, addr: Va) -> Page;
+    /// - `Ok(Page)`: A newly allocated [`Page`] containing the initialized data
+    ///   for the page, or a page shared with other mappings (e.g., the page of
+    ///   a shared memory segment).
+    /// - `Err(KernelError::NoMemory)`: If a new page cannot be allocated.
+    ///
+    /// Loaders must allocate pages with [`Page::try_new`], not [`Page::new`].
+    /// Running out of memory while serving a user's page fault must kill
+    /// only the faulting process, not panic the kernel.
+    fn load(&self, addr: Va) -> Result<Page, KernelError>;
+
+    /// Returns `true` if every page of the mapping starts zero-filled.
+    ///
+    /// On a read fault in such a mapping, the pager maps the shared zero page
+    /// ([`Page::zero`]) read-only instead of calling [`MmLoader::load`].
+    fn is_demand_zero(&self) -> bool {
+        false
+    }
 }
 
 /// A loader for anonymous memory regions.
@@ -126,8 +155,13 @@ impl MmLoader for AnonLoader {
     ///
     /// Since anonymous memory is not backed by any persistent source, this
     /// implementation always returns a freshly zero-initialized [`Page`].
-    fn load(&self, _addr: Va) -> Page {
-        Page::new()
+    fn load(&self, _addr: Va) -> Result<Page, KernelError> {
+        Page::try_new().ok_or(KernelError::NoMemory)
+    }
+
+    /// Anonymous pages start zero-filled.
+    fn is_demand_zero(&self) -> bool {
+        true
     }
 }
 
@@ -153,7 +187,10 @@ impl MmLoader for FileBackedLoader {
     /// This implementation calculates the offset within the file and reads
     /// up to one page of data into memory. If the read returns fewer than
     /// `PAGE_SIZE` bytes, the remainder of the page is zero-filled.
-    fn load(&self, addr: Va) -> Page {
+    ///
+    /// Allocate the page with [`Page::try_new`] and return
+    /// [`KernelError::NoMemory`] if it fails.
+    fn load(&self, addr: Va) -> Result<Page, KernelError> {
         todo!()
     }
 }
@@ -201,6 +238,10 @@ impl Pager for LazyPager {
     /// This function creates the metadata for memory mappings, and delegate the
     /// real mappings on page fault.
     ///
+    /// When `file` is `None`, the mapping is anonymous: the region is backed
+    /// by an [`AnonLoader`] and no page is allocated here. Each page is
+    /// zero-filled on its first access, as a `malloc` heap expects.
+    ///
     /// Returns an address for the mapped area.
     fn mmap(
         &mut self,
@@ -216,12 +257,18 @@ impl Pager for LazyPager {
 
     /// Memory unmap function (`munmap`) for lazy paging.
     ///
-    /// This function would unmap a previously mapped memory region, releasing
-    /// any associated resources.
+    /// This function would unmap a range of previously mapped memory, releasing
+    /// any associated resources. When the range covers only a part of a
+    /// [`VmAreaStruct`], the remaining parts are kept as separate areas.
     ///
     /// # Returns
     /// - Zero (if succeed) or an error ([`KernelError`]).
-    fn munmap(&mut self, page_table: &mut PageTable, addr: Va) -> Result<usize, KernelError> {
+    fn munmap(
+        &mut self,
+        page_table: &mut PageTable,
+        addr: Va,
+        size: usize,
+    ) -> Result<usize, KernelError> {
         todo!()
     }
 
@@ -235,6 +282,10 @@ impl Pager for LazyPager {
     /// # Parameters
     /// - `addr`: The virtual address ([`Va`]) of the page to find.
     ///
+    /// The caller may write to the returned page. Never return
+    /// [`Page::zero`]: load a private page instead, and replace the zero page
+    /// if it is already mapped at `addr`.
+    ///
     /// # Returns
     /// - `Some(([`PageRef`], [`Permission`]))`: If the page is found.
     /// - `None`: If no mapped page is found at `addr`.
@@ -320,6 +371,28 @@ impl PageFaultReason {
 }
 
 impl LazyPager {
+    /// Records a lazy mapping whose pages are supplied by `loader`.
+    ///
+    /// This is the loader-agnostic part of [`LazyPager::mmap`]: it creates a
+    /// [`VmAreaStruct`] covering `[addr, addr + size)` with the permission
+    /// `prot`, without allocating any page. Mappings that are neither
+    /// anonymous nor file-backed, such as shared memory segments, are created
+    /// through this method directly.
+    ///
+    /// # Returns
+    /// - The start address of the mapped area.
+    /// - `Err(KernelError)`: If the range is invalid or overlaps an existing
+    ///   mapping.
+    pub fn mmap_with_loader(
+        &mut self,
+        addr: Va,
+        size: usize,
+        prot: Permission,
+        loader: Arc<dyn MmLoader>,
+    ) -> Result<usize, KernelError> {
+        todo!()
+    }
+
     /// Handles a page fault by performing **lazy loading** of the faulting
     /// page.
     ///
@@ -344,6 +417,18 @@ impl LazyPager {
     /// - `Ok(())` if the page was successfully loaded and mapped.
     /// - `Err(KernelError)`: If the faulting address is invalid, out of bounds,
     ///   or if page allocation fails.
+    ///
+    /// An allocation failure must be reported as [`KernelError::NoMemory`]
+    /// instead of panicking, so that only the faulting process is killed.
+    ///
+    /// On a read fault in a mapping whose loader [`MmLoader::is_demand_zero`],
+    /// map [`Page::zero`] without the write permission instead of loading a
+    /// page. The first write to it is then handled by
+    /// [`LazyPager::do_copy_on_write`].
+    ///
+    /// If the page was swapped out by [`LazyPager::reclaim`], restore its
+    /// contents with [`SwapEntry::swap_in`] instead of calling the
+    /// [`MmLoader`], and forget the [`SwapEntry`].
     pub fn do_lazy_load(
         &mut self,
         page_table: &mut PageTable,
@@ -382,4 +467,27 @@ impl LazyPager {
             Err(KernelError::InvalidAccess)
         }
     }
+
+    /// Evicts up to `target` pages of this address space under memory
+    /// pressure.
+    ///
+    /// This is called before handling a page fault when the memory runs low
+    /// (see [`swap`] for details). Walk the pages of the [`VmAreaStruct`]s in
+    /// a round-robin manner, resuming from where the last call stopped (the
+    /// "clock hand"), and try to evict each of them with [`swap::evict`].
+    /// Keep the [`SwapEntry`] of a swapped-out page, so that
+    /// [`LazyPager::do_lazy_load`] can restore it. A dropped page is loaded
+    /// again by the [`MmLoader`] as usual. The entries must be dropped when
+    /// the page is unmapped, which frees the slots of the swap space.
+    ///
+    /// Stop after `target` pages are evicted, or after the clock hand has gone
+    /// around twice, as the first round may only clear the accessed bits.
+    ///
+    /// # Returns
+    /// - The number of evicted pages.
+    ///
+    /// [`swap`]: crate::swap
+    pub fn reclaim(&mut self, page_table: &mut PageTable, target: usize) -> usize {
+        todo!()
+    }
 }
@@ -48,12 +48,16 @@ extern crate alloc;
 #[macro_use]
 extern crate keos;
 
+pub mod atomic;
 pub mod fork;
 pub mod get_phys;
 pub mod lazy_pager;
 pub mod process;
+pub mod shm;
+pub mod swap;
 
 use alloc::boxed::Box;
+use atomic::atomic_cas;
 use core::ops::Range;
 use fork::fork;
 use keos::{
@@ -70,6 +74,7 @@ use keos_project2::mm_struct::MmStruct;
 use lazy_pager::LazyPager;
 use lazy_pager::PageFaultReason;
 pub use process::Process;
+use shm::{shmat, shmget};
 
 /// Represents system call numbers used in project3.
 ///
@@ -101,6 +106,26 @@ pub enum SyscallNumber {
     Munmap = 9,
     /// Fork the process.
     Fork = 10,
+    /// Retrieves the file I/O statistics of the process.
+    IoStat = 21,
+    /// Create a pipe with the given capacity and flags.
+    Pipe2 = 23,
+    /// Wait until one of file descriptors becomes ready.
+    Poll = 24,
+    /// Get or set the flags of an open file.
+    Fcntl = 32,
+    /// Apply or remove an advisory lock on an open file.
+    Flock = 33,
+    /// Transfer data between open files within the kernel.
+    Sendfile = 35,
+    /// Get the resident set size of the process, in pages.
+    GetRss = 38,
+    /// Create or look up a shared memory segment.
+    ShmGet = 39,
+    /// Map a shared memory segment into the address space.
+    ShmAt = 40,
+    /// Atomically compare and swap a value in the user memory.
+    AtomicCas = 41,
     /// Get Physical Address of Page (for grading purposes only)
     GetPhys = 0x81,
 }
@@ -120,6 +145,16 @@ impl TryFrom<usize> for SyscallNumber {
             8 => Ok(SyscallNumber::Mmap),
             9 => Ok(SyscallNumber::Munmap),
             10 => Ok(SyscallNumber::Fork),
+            21 => Ok(SyscallNumber::IoStat),
+            23 => Ok(SyscallNumber::Pipe2),
+            24 => Ok(SyscallNumber::Poll),
+            32 => Ok(SyscallNumber::Fcntl),
+            33 => Ok(SyscallNumber::Flock),
+            35 => Ok(SyscallNumber::Sendfile),
+            38 => Ok(SyscallNumber::GetRss),
+            39 => Ok(SyscallNumber::ShmGet),
+            40 => Ok(SyscallNumber::ShmAt),
+            41 => Ok(SyscallNumber::AtomicCas),
             0x81 => Ok(SyscallNumber::GetPhys),
             _ => Err(KernelError::NoSuchSyscall),
         }
@@ -166,19 +201,25 @@ impl Task for Process {
     /// modifies the CPU registers accordingly.
     fn syscall(&mut self, regs: &mut Registers) {
         // ** YOU DON'T NEED TO CHANGE THIS FUNCTION **
-        let abi = SyscallAbi::from_registers(regs); // Extract ABI from the registers.
+        let mut abi = SyscallAbi::from_registers(regs); // Extract ABI from the registers.
         // Lookup the system call handler function based on the system call number.
         let return_val = SyscallNumber::try_from(abi.sysno).and_then(|no| match no {
             SyscallNumber::Exit => self.exit(&abi),
-            SyscallNumber::Open => self.file_struct.open(&abi),
-            SyscallNumber::Read => self.file_struct.read(&abi),
-            SyscallNumber::Write => self.file_struct.write(&abi),
-            SyscallNumber::Seek => self.file_struct.seek(&abi),
+            SyscallNumber::Open => self.file_struct.open_cloexec(&mut abi),
+            SyscallNumber::Read => self.file_struct.read_accounted(&abi),
+            SyscallNumber::Write => self.file_struct.write_accounted(&abi),
+            SyscallNumber::Seek => self.file_struct.seek_sparse(&abi),
             SyscallNumber::Tell => self.file_struct.tell(&abi),
-            SyscallNumber::Close => self.file_struct.close(&abi),
+            SyscallNumber::Close => self.file_struct.close_unlocking(&abi),
             SyscallNumber::Pipe => self.file_struct.pipe(&abi),
+            SyscallNumber::Pipe2 => self.file_struct.pipe2(&abi),
+            SyscallNumber::Poll => self.file_struct.poll(&abi),
             SyscallNumber::Mmap => self.mm_struct.mmap(&mut self.file_struct, &abi),
             SyscallNumber::Munmap => self.mm_struct.munmap(&abi),
+            SyscallNumber::GetRss => Ok(self.mm_struct.rss()),
+            SyscallNumber::ShmGet => shmget(&abi),
+            SyscallNumber::ShmAt => shmat(&mut self.mm_struct, &abi),
+            SyscallNumber::AtomicCas => atomic_cas(&mut self.mm_struct, &abi),
             SyscallNumber::Fork => fork(
                 &mut self.file_struct,
                 &mut self.mm_struct,
@@ -192,6 +233,10 @@ impl Task for Process {
                     })
                 },
             ),
+            SyscallNumber::IoStat => self.file_struct.iostat(&abi),
+            SyscallNumber::Fcntl => self.file_struct.fcntl(&abi),
+            SyscallNumber::Flock => self.file_struct.flock(&abi),
+            SyscallNumber::Sendfile => self.file_struct.sendfile(&abi),
             SyscallNumber::GetPhys => get_phys::get_phys(&self.mm_struct, &self.file_struct, &abi),
         });
         // Set the return value of the system call (success or error) back into the
@@ -238,6 +283,11 @@ impl Task for Process {
         // Delegate the fault handling to [`LazyPager::handle_page_fault`],
         // which will update the page table and allocate a physical page if necessary.
         let MmStruct { page_table, pager } = &mut self.mm_struct;
+        // Make room for the page first if the memory runs low.
+        let target = swap::reclaim_target();
+        if target != 0 {
+            pager.reclaim(page_table, target);
+        }
         if pager.handle_page_fault(page_table, &reason).is_err() {
             Current::exit(-1)
         }
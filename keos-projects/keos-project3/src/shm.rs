@@ -0,0 +1,196 @@
+//! # Shared memory segments.
+//!
+//! Processes created by `fork` start with the memory of their parent, but
+//! unrelated processes have no memory in common. They can share memory
+//! through System V style shared memory segments instead: `shmget` creates a
+//! segment for a key, or looks up the existing one, and `shmat` maps the
+//! segment into the caller's address space. Every process attaching the same
+//! segment sees the same physical pages.
+//!
+//! The segments are kept in a kernel table of refcounted pages. An attachment
+//! is a lazy mapping backed by a [`ShmLoader`], which supplies the pages of
+//! the segment instead of fresh ones. The attachment ends when the mapping is
+//! entirely unmapped or when the process exits, and the segment is freed when
+//! its last attachment ends. A segment that is never attached stays in the
+//! table.
+//!
+//! Pages of an attachment inherited through `fork` are copied on write like
+//! any other mapping, so they are no longer shared once written.
+
+use crate::lazy_pager::{LazyPager, MmLoader};
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use keos::{
+    KernelError,
+    addressing::{PAGE_SIZE, Va},
+    mm::{Page, page_table::Permission},
+    sync::SpinLock,
+};
+use keos_project1::syscall::SyscallAbi;
+use keos_project2::mm_struct::{MmStruct, user_range};
+
+/// The maximum size of a shared memory segment in bytes.
+pub const SHMMAX: usize = 0x100_0000;
+
+/// A shared memory segment.
+struct Segment {
+    /// The key given to `shmget`.
+    key: usize,
+    /// The pages of the segment.
+    pages: Arc<[Page]>,
+    /// The number of attachments to the segment.
+    attachers: usize,
+}
+
+/// The table of shared memory segments, indexed by their identifiers.
+static SEGMENTS: SpinLock<BTreeMap<usize, Segment>> = SpinLock::new(BTreeMap::new());
+
+/// The identifier of the next segment.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// A loader for an attachment of a shared memory segment.
+///
+/// [`ShmLoader`] supplies the page of the segment at the same offset as the
+/// faulting address, so that every attachment maps the same physical pages.
+/// Dropping the loader, which happens when the last part of the mapping is
+/// gone, ends the attachment.
+pub struct ShmLoader {
+    /// The identifier of the segment.
+    id: usize,
+    /// The start address of the attachment.
+    base: Va,
+    /// The pages of the segment.
+    pages: Arc<[Page]>,
+    /// Whether the segment is freed when this loader ends its last
+    /// attachment. Cleared for an attachment that failed to be mapped, which
+    /// leaves the segment as it was.
+    free_if_last: bool,
+}
+
+impl MmLoader for ShmLoader {
+    /// Returns the page of the segment for the given virtual address.
+    fn load(&self, addr: Va) -> Result<Page, KernelError> {
+        Ok(self.pages[(addr.into_usize() - self.base.into_usize()) / PAGE_SIZE].clone())
+    }
+}
+
+impl Drop for ShmLoader {
+    fn drop(&mut self) {
+        let mut segments = SEGMENTS.lock();
+        let segment = segments.get_mut(&self.id).unwrap();
+        segment.attachers -= 1;
+        let freed = if segment.attachers == 0 && self.free_if_last {
+            segments.remove(&self.id)
+        } else {
+            None
+        };
+        segments.unlock();
+        // Free the pages outside of the lock.
+        drop(freed);
+    }
+}
+
+/// Creates a shared memory segment, or looks up an existing one.
+///
+/// # Syscall API
+/// ```c
+/// int shmget(long key, size_t size);
+/// ```
+/// - `key`: The key of the segment. Processes passing the same key get the
+///   same segment.
+/// - `size`: The size of the segment in bytes, rounded up to a multiple of
+///   the page size.
+///
+/// A new segment is zero-filled. If a segment with `key` already exists, it
+/// is returned as is, provided that it is at least `size` bytes long.
+///
+/// # Returns
+/// - The identifier of the segment, to be passed to [`shmat`].
+/// - [`KernelError::InvalidArgument`] if `size` is zero or larger than
+///   [`SHMMAX`], or larger than the existing segment for `key`.
+pub fn shmget(abi: &SyscallAbi) -> Result<usize, KernelError> {
+    let (key, size) = (abi.arg1, abi.arg2);
+    if size == 0 || size > SHMMAX {
+        return Err(KernelError::InvalidArgument);
+    }
+    let npages = size.div_ceil(PAGE_SIZE);
+
+    let mut segments = SEGMENTS.lock();
+    let result = match segments.iter().find(|(_, segment)| segment.key == key) {
+        Some((id, segment)) if segment.pages.len() >= npages => Ok(*id),
+        Some(_) => Err(KernelError::InvalidArgument),
+        None => {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            segments.insert(
+                id,
+                Segment {
+                    key,
+                    pages: (0..npages).map(|_| Page::new()).collect(),
+                    attachers: 0,
+                },
+            );
+            Ok(id)
+        }
+    };
+    segments.unlock();
+    result
+}
+
+/// Maps a shared memory segment into the address space of the caller.
+///
+/// # Syscall API
+/// ```c
+/// void *shmat(int shmid, void *addr);
+/// ```
+/// - `shmid`: The identifier returned by [`shmget`].
+/// - `addr`: The address to map the segment at. It must be page-aligned and
+///   non-zero, as for `mmap`.
+///
+/// The segment is mapped readable and writable. It is detached by `munmap`ing
+/// the whole mapping, or when the process exits.
+///
+/// # Returns
+/// - The address of the mapping.
+/// - [`KernelError::InvalidArgument`] if `shmid` does not refer to a segment,
+///   or the mapping cannot be made at `addr`.
+pub fn shmat(mm: &mut MmStruct<LazyPager>, abi: &SyscallAbi) -> Result<usize, KernelError> {
+    let id = abi.arg1;
+    let mut segments = SEGMENTS.lock();
+    let Some(segment) = segments.get_mut(&id) else {
+        segments.unlock();
+        return Err(KernelError::InvalidArgument);
+    };
+    let size = segment.pages.len() * PAGE_SIZE;
+    let base = match user_range(abi.arg2, size) {
+        Ok(range) => range.start,
+        Err(e) => {
+            segments.unlock();
+            return Err(e);
+        }
+    };
+    segment.attachers += 1;
+    let pages = segment.pages.clone();
+    segments.unlock();
+
+    // From here on, dropping the loader ends the attachment.
+    let loader = Arc::new(ShmLoader {
+        id,
+        base,
+        pages,
+        free_if_last: true,
+    });
+    let result = mm.pager.mmap_with_loader(
+        base,
+        size,
+        Permission::READ | Permission::WRITE,
+        loader.clone(),
+    );
+    if result.is_err()
+        && let Some(mut loader) = Arc::into_inner(loader)
+    {
+        // Roll back the attachment without freeing the segment, which may
+        // never have been attached.
+        loader.free_if_last = false;
+    }
+    result
+}
@@ -0,0 +1,309 @@
+//! # Swapping.
+//!
+//! With lazy paging, processes can map far more memory than the machine has.
+//! Without any recourse, the first page fault that finds the memory exhausted
+//! kills the faulting process. Swapping lets such a workload complete: when
+//! the memory runs low, the kernel evicts pages that were not used recently,
+//! and brings them back on the next access.
+//!
+//! A page is evicted by one of two ways:
+//! - A clean page of a file-backed mapping is simply dropped, as its
+//!   contents can be read from the file again by the [`MmLoader`].
+//! - Other pages, that is, anonymous pages and written pages of a file-backed
+//!   mapping, are written to a **swap space** on a disk, and a [`SwapEntry`]
+//!   refers to the slot holding the contents.
+//!
+//! Either way, the page table entry of the page is cleared, so the next access
+//! to the page raises a demand paging fault. The [`LazyPager`] remembers the
+//! [`SwapEntry`] of each swapped-out page, and restores the page from the swap
+//! space with [`SwapEntry::swap_in`] instead of calling the [`MmLoader`].
+//!
+//! ## Reclaiming
+//!
+//! Swapping starts with [`swapon`], which prepares the swap space and spawns
+//! `kswapd`, the kernel thread watching the number of free pages. When it
+//! falls below [`LOW_WATERMARK`], `kswapd` requests enough evictions to bring
+//! it back up to [`HIGH_WATERMARK`].
+//!
+//! A page table is owned by its process, so pages are evicted in the context
+//! of a process, not of `kswapd`: before handling a page fault, the process
+//! takes its share of the requested evictions with [`reclaim_target`] and
+//! evicts that many of its own pages with [`LazyPager::reclaim`]. If the
+//! memory runs out faster than `kswapd` notices, the faulting process reclaims
+//! pages directly as well.
+//!
+//! Victims are chosen by the **second-chance** (clock) algorithm, an
+//! approximation of LRU. The CPU sets the accessed bit of a page table entry
+//! on every access of the page. [`evict`] spares a page whose accessed bit is
+//! set, clearing the bit instead, so only pages that are not accessed during
+//! a whole round of the clock are evicted. Pages shared with other page tables
+//! (by `fork`, or as a shared memory segment) are never evicted.
+//!
+//! [`MmLoader`]: crate::lazy_pager::MmLoader
+//! [`LazyPager`]: crate::lazy_pager::LazyPager
+//! [`LazyPager::reclaim`]: crate::lazy_pager::LazyPager::reclaim
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use keos::{
+    KernelError,
+    addressing::{PAGE_SIZE, Va},
+    fs::{Disk, Sector},
+    mm::{Page, PageRef, free_page_count, page_table::PteFlags},
+    sync::SpinLock,
+    thread::{Current, ThreadBuilder},
+};
+use keos_project2::page_table::PageTable;
+
+/// `kswapd` starts to reclaim pages when fewer pages than this are free.
+pub const LOW_WATERMARK: usize = 1024;
+
+/// `kswapd` reclaims pages until this many pages are free.
+pub const HIGH_WATERMARK: usize = 2048;
+
+/// A faulting process reclaims pages directly when fewer pages than this are
+/// free.
+pub const MIN_WATERMARK: usize = 256;
+
+/// The number of pages a process reclaims directly at once.
+pub const SWAP_CLUSTER: usize = 32;
+
+/// The number of sectors of a page.
+const SECTORS_PER_PAGE: usize = PAGE_SIZE / 512;
+
+/// The swap space.
+struct SwapSpace {
+    /// The slot index of the swap disk.
+    disk: usize,
+    /// Whether each page-sized slot of the disk is in use.
+    used: Vec<bool>,
+}
+
+/// The swap space, if swapping is on.
+static SWAP: SpinLock<Option<SwapSpace>> = SpinLock::new(None);
+
+/// Whether swapping is on.
+static SWAP_ON: AtomicBool = AtomicBool::new(false);
+
+/// The number of pages that `kswapd` requested to evict.
+static PRESSURE: AtomicUsize = AtomicUsize::new(0);
+
+/// A slot of the swap space, which is freed on drop.
+struct Slot(usize);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let mut swap = SWAP.lock();
+        swap.as_mut().unwrap().used[self.0] = false;
+        swap.unlock();
+    }
+}
+
+/// A reference to the contents of a page written to the swap space.
+///
+/// A [`SwapEntry`] stands for a swapped-out page in the metadata of a
+/// [`LazyPager`]. Cloning it, as `fork` does, shares the slot; the slot is
+/// freed when the last [`SwapEntry`] for it is dropped.
+///
+/// [`LazyPager`]: crate::lazy_pager::LazyPager
+#[derive(Clone)]
+pub struct SwapEntry(Arc<Slot>);
+
+impl core::fmt::Debug for SwapEntry {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "SwapEntry({})", self.0.0)
+    }
+}
+
+impl SwapEntry {
+    /// Writes the contents of `page` to a free slot of the swap space.
+    ///
+    /// # Returns
+    /// - The [`SwapEntry`] for the slot.
+    /// - [`KernelError::NoMemory`] if swapping is off or the swap space is
+    ///   full.
+    /// - [`KernelError::IOError`] if the write fails.
+    pub fn swap_out(page: &Page) -> Result<Self, KernelError> {
+        let mut swap = SWAP.lock();
+        let Some(SwapSpace { disk, used }) = swap.as_mut() else {
+            swap.unlock();
+            return Err(KernelError::NoMemory);
+        };
+        let Some(idx) = used.iter().position(|used| !used) else {
+            swap.unlock();
+            return Err(KernelError::NoMemory);
+        };
+        used[idx] = true;
+        let disk = Disk::new(*disk);
+        swap.unlock();
+
+        // From here on, dropping the entry frees the slot.
+        let entry = Self(Arc::new(Slot(idx)));
+        for (i, chunk) in page.inner().chunks_exact(512).enumerate() {
+            disk.write(
+                Sector(idx * SECTORS_PER_PAGE + i),
+                chunk.try_into().unwrap(),
+            )?;
+        }
+        Ok(entry)
+    }
+
+    /// Reads the contents of the slot into a newly allocated page.
+    ///
+    /// The slot is kept until the [`SwapEntry`] is dropped, so that other
+    /// entries sharing the slot can read it as well.
+    ///
+    /// # Returns
+    /// - The page holding the contents of the slot.
+    /// - [`KernelError::NoMemory`] if a page cannot be allocated.
+    /// - [`KernelError::IOError`] if the read fails.
+    pub fn swap_in(&self) -> Result<Page, KernelError> {
+        let mut swap = SWAP.lock();
+        let disk = Disk::new(swap.as_mut().unwrap().disk);
+        swap.unlock();
+
+        let mut page = Page::try_new().ok_or(KernelError::NoMemory)?;
+        for (i, chunk) in page.inner_mut().chunks_exact_mut(512).enumerate() {
+            disk.read(
+                Sector(self.0.0 * SECTORS_PER_PAGE + i),
+                chunk.try_into().unwrap(),
+            )?;
+        }
+        Ok(page)
+    }
+}
+
+/// The result of a successful [`evict`].
+#[derive(Debug)]
+pub enum Evicted {
+    /// The page was a clean page of a file-backed mapping, and is dropped.
+    Dropped,
+    /// The page was written to the swap space.
+    Swapped(SwapEntry),
+}
+
+/// Tries to evict the page mapped at `va` in `page_table`.
+///
+/// This is one step of the second-chance algorithm. A page that has been
+/// accessed since the last step on it gets a second chance: its accessed bit
+/// is cleared, and it is kept. Otherwise, the page is unmapped and its
+/// contents are either dropped (for a clean page of a file-backed mapping)
+/// or written to the swap space.
+///
+/// The page is unmapped, and its stale TLB entries are shot down, before it
+/// is written to the swap space. Otherwise, a write by another thread after
+/// the copy would be lost.
+///
+/// # Parameters
+/// - `page_table`: The page table of the current process.
+/// - `va`: The page-aligned virtual address to evict.
+/// - `file_backed`: Whether the mapping containing `va` is backed by a file,
+///   such that a clean page can be loaded again.
+///
+/// # Returns
+/// - `Ok(Some(Evicted))` if the page is evicted.
+/// - `Ok(None)` if the page is not mapped, shared, or accessed recently.
+/// - `Err(KernelError)` if the page cannot be written to the swap space. The
+///   original mapping is restored in this case.
+pub fn evict(
+    page_table: &mut PageTable,
+    va: Va,
+    file_backed: bool,
+) -> Result<Option<Evicted>, KernelError> {
+    // Look up with `walk` first, which does not copy a table shared by
+    // `fork` as `walk_mut` does.
+    let Ok(pte) = page_table.walk(va) else {
+        return Ok(None);
+    };
+    let flags = pte.flags();
+    let Some(pa) = pte.pa() else {
+        return Ok(None);
+    };
+    if !flags.contains(PteFlags::P) || unsafe { PageRef::from_pa(pa) }.is_shared() {
+        return Ok(None);
+    }
+
+    let mut walked = page_table
+        .walk_mut(va)
+        .map_err(|_| KernelError::InvalidAccess)?;
+    if flags.contains(PteFlags::A) {
+        // Give it a second chance.
+        if let Some(stale) = walked.set_flags(flags - PteFlags::A) {
+            stale.invalidate();
+        }
+        return Ok(None);
+    }
+
+    let flags = walked.flags();
+    let Some(stale) = walked.clear() else {
+        return Ok(None);
+    };
+    let page = stale.invalidate();
+    if file_backed && !flags.contains(PteFlags::D) {
+        return Ok(Some(Evicted::Dropped));
+    }
+    match SwapEntry::swap_out(&page) {
+        Ok(entry) => Ok(Some(Evicted::Swapped(entry))),
+        Err(e) => {
+            walked
+                .set_page(page, flags)
+                .map_err(|_| KernelError::InvalidAccess)?;
+            Err(e)
+        }
+    }
+}
+
+/// Returns the number of pages the current process should evict before
+/// handling a page fault.
+///
+/// This takes the evictions requested by `kswapd`, or [`SWAP_CLUSTER`] pages
+/// if the memory is about to run out. Returns zero if swapping is off.
+pub fn reclaim_target() -> usize {
+    if !SWAP_ON.load(Ordering::Acquire) {
+        return 0;
+    }
+    let requested = PRESSURE.swap(0, Ordering::AcqRel);
+    if free_page_count() < MIN_WATERMARK {
+        requested.max(SWAP_CLUSTER)
+    } else {
+        requested
+    }
+}
+
+/// The body of `kswapd`.
+fn kswapd() {
+    loop {
+        let free = free_page_count();
+        if free < LOW_WATERMARK {
+            PRESSURE.store(HIGH_WATERMARK - free, Ordering::Release);
+        }
+        Current::sleep(1);
+    }
+}
+
+/// Turns on swapping to the disk at slot `disk`.
+///
+/// The whole disk is used as the swap space. This spawns `kswapd` on the
+/// first call.
+///
+/// # Returns
+/// - `Ok(())` if swapping is on.
+/// - [`KernelError::IOError`] if the disk does not exist.
+/// - [`KernelError::Busy`] if swapping is already on.
+pub fn swapon(disk: usize) -> Result<(), KernelError> {
+    let nslots = Disk::new(disk).block_cnt()? / SECTORS_PER_PAGE;
+    let mut swap = SWAP.lock();
+    if swap.is_some() {
+        swap.unlock();
+        return Err(KernelError::Busy);
+    }
+    *swap = Some(SwapSpace {
+        disk,
+        used: vec![false; nslots],
+    });
+    swap.unlock();
+
+    SWAP_ON.store(true, Ordering::Release);
+    ThreadBuilder::new("kswapd").spawn(kswapd);
+    Ok(())
+}
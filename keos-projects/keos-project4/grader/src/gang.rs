@@ -0,0 +1,97 @@
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use keos::{
+    MAX_CPU, cmdline,
+    sync::atomic::AtomicBool,
+    thread::{Current, ThreadBuilder},
+    time::now_ticks,
+};
+use keos_project2::mm_struct::MmStruct;
+use keos_project4::Thread;
+
+/// Tests that the [`GangScheduler`] runs the threads of a group together.
+///
+/// The test runs a group of `MAX_CPU` busy threads along with as many busy
+/// threads without a group, and records the ticks in which each thread of the
+/// group runs. Under the plain round robin, the threads of the group are
+/// scheduled independently, so they rarely run all at once.
+///
+/// This test ensures that:
+/// - All threads of the group run at the same time in most of the ticks in
+///   which any of them runs.
+///
+/// This test must run with `sched=gang`.
+///
+/// [`GangScheduler`]: keos::thread::scheduler::GangScheduler
+pub fn co_scheduling() {
+    const TICKS: usize = 400;
+    assert_eq!(
+        cmdline::get("sched"),
+        Some("gang"),
+        "This test must run with `sched=gang`."
+    );
+
+    let stop = Arc::new(AtomicBool::new(false));
+    // Whether each thread of the group ran in each tick.
+    let ran = Arc::new(
+        (0..MAX_CPU)
+            .map(|_| {
+                (0..TICKS)
+                    .map(|_| AtomicBool::new(false))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>(),
+    );
+    let start = now_ticks();
+
+    let builders = (0..MAX_CPU)
+        .map(|_| ThreadBuilder::new("gang"))
+        .collect::<Vec<_>>();
+    let tgid = builders[0].get_tid();
+    let mut handles = builders
+        .into_iter()
+        .enumerate()
+        .map(|(i, builder)| {
+            let mut task = Thread::from_mm_struct(MmStruct::new(), builder.get_tid());
+            task.tgid = tgid;
+            let (stop, ran) = (stop.clone(), ran.clone());
+            builder.attach_task(Box::new(task)).spawn(move || {
+                while !stop.load() {
+                    if let Some(ran) = ran[i].get((now_ticks() - start) as usize) {
+                        ran.store(true);
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    handles.extend((0..MAX_CPU).map(|_| {
+        let stop = stop.clone();
+        ThreadBuilder::new("busy").spawn(move || {
+            while !stop.load() {
+                core::hint::spin_loop();
+            }
+        })
+    }));
+
+    Current::sleep(TICKS as u64);
+    stop.store(true);
+    for handle in handles {
+        assert_eq!(handle.join(), 0);
+    }
+
+    let (mut any, mut all) = (0, 0);
+    for tick in 0..TICKS {
+        match ran.iter().filter(|ran| ran[tick].load()).count() {
+            0 => (),
+            MAX_CPU => {
+                any += 1;
+                all += 1;
+            }
+            _ => any += 1,
+        }
+    }
+    assert!(any > 0, "The threads of the group must run.");
+    assert!(
+        all * 2 >= any,
+        "The threads of a group must run together, but all of them ran in only {all} of {any} ticks."
+    );
+}
@@ -14,11 +14,18 @@ extern crate keos_project4;
 #[macro_use]
 extern crate grading;
 
+mod gang;
+mod priority;
+mod random;
 mod round_robin;
 mod sync;
+mod timer;
 mod userprog;
 
-use keos::SystemConfigurationBuilder;
+use keos::{
+    SystemConfigurationBuilder,
+    thread::scheduler::{GangScheduler, PriorityScheduler},
+};
 pub use keos_project4::Thread;
 use keos_project4::round_robin::RoundRobin;
 
@@ -29,24 +36,62 @@ pub unsafe fn main(config_builder: SystemConfigurationBuilder) {
         keos::info!("Filesystem: use `SimpleFS`.");
         keos::fs::FileSystem::register(fs)
     }
-    config_builder.set_scheduler(RoundRobin::new());
+    let config_builder = config_builder.set_deadlock_detection(true);
+    // `sched=gang` selects the gang scheduler with an epoch of 10 ticks, and
+    // `sched=priority` the priority scheduler with a quantum of 5 ticks.
+    match keos::cmdline::get("sched") {
+        Some("gang") => config_builder.set_scheduler(GangScheduler::new(10)),
+        Some("priority") => config_builder.set_scheduler(PriorityScheduler::new(5)),
+        _ => config_builder.set_scheduler(RoundRobin::new()),
+    }
     keos::TestDriver::<Thread>::start([
         // Round robin Scheduler.
         &round_robin::functionality,
         &round_robin::balance,
         &round_robin::balance2,
         &round_robin::affinity,
+        &round_robin::sleep,
+        &round_robin::utilization,
+        &round_robin::preempt_guard,
+        &round_robin::lock_holder_preemption,
+        &round_robin::quantum,
+        &round_robin::wakeup_boost,
+        // Random scheduler.
+        &random::reproducible,
+        // Gang scheduler.
+        &gang::co_scheduling,
+        // Priority scheduler.
+        &priority::donate_mutex,
+        &priority::donate_join,
+        // Timer.
+        &timer::fire_in_order,
+        &timer::cancel,
+        &timer::burst,
         // Sync
         &sync::mutex::smoke,
         &sync::mutex::parking,
         &sync::mutex::smoke_many,
+        &sync::mutex::kill_blocked,
+        &sync::mutex::reentrant,
+        &sync::mutex::robust_owner_died,
+        &sync::mutex::try_lock_contended,
+        &sync::mutex::lock_timeout,
+        &sync::mutex::deadlock_detect,
         &sync::condition_variable::bounded_buffer_1,
         &sync::condition_variable::bounded_buffer_2,
+        &sync::condition_variable::signal_one,
+        &sync::condition_variable::cancel_blocked,
         &sync::semaphore::sema_0,
         &sync::semaphore::sema_1,
         &sync::semaphore::sema_2,
         &sync::semaphore::exec_order,
+        &sync::semaphore::fifo_order,
         &sync::semaphore::n_permits,
+        &sync::once::call_once_race,
+        &sync::once::lazy,
+        &sync::rwlock::writer_preferring,
+        &sync::spinlock::contention,
+        &sync::ticketlock::fifo_order,
         // Loader.
         &userprog::arg_parse,
         &userprog::sys_open,
@@ -77,6 +122,18 @@ pub unsafe fn main(config_builder: SystemConfigurationBuilder) {
         &userprog::thread_join_chain,
         &userprog::thread_join_complex,
         &userprog::thread_mm_shared,
+        &userprog::atomic_cas,
+        &userprog::sched_stats,
+        &userprog::sys_getpid,
+        &userprog::sys_waitpid,
+        &userprog::vfork,
+        &userprog::execve_threaded,
+        &userprog::spawn,
+        &userprog::sys_sleep,
+        &userprog::sys_clock,
+        &userprog::sys_fcntl,
+        &userprog::sys_flock,
+        &userprog::shm_two_processes,
     ]);
 }
 
@@ -0,0 +1,185 @@
+use alloc::{sync::Arc, vec::Vec};
+use keos::{
+    MAX_CPU, cmdline,
+    sync::atomic::{AtomicBool, AtomicUsize},
+    thread::{
+        Current, JoinHandle, ThreadBuilder, get_sched_stats_by_tid,
+        priority::{PRI_DEFAULT, PRI_MAX, PRI_MIN, get_priority_by_tid},
+        with_current,
+    },
+    time::now_ticks,
+};
+use keos_project4::sync::mutex::Mutex;
+
+// The priority of the threads keeping the low-priority thread off the CPU.
+const PRI_MEDIUM: u8 = (PRI_DEFAULT + PRI_MAX) / 2;
+// The timer ticks the low-priority thread runs for.
+const WORK: u64 = 20;
+
+// Spawns a medium-priority busy thread on each CPU, which runs until `stop`
+// is set.
+fn spawn_hogs(stop: &Arc<AtomicBool>) -> Vec<JoinHandle> {
+    (0..MAX_CPU)
+        .map(|_| {
+            let stop = stop.clone();
+            ThreadBuilder::new("hog").spawn(move || {
+                Current::set_priority(PRI_MEDIUM);
+                while !stop.load() {
+                    core::hint::spin_loop();
+                }
+            })
+        })
+        .collect()
+}
+
+// Runs on the CPU for `ticks` timer ticks, and returns the highest priority
+// of the current thread seen meanwhile.
+fn run_for(ticks: u64) -> u8 {
+    let tid = Current::get_tid();
+    let run_ticks = || get_sched_stats_by_tid(tid).unwrap().run_ticks;
+    let (start, mut highest) = (run_ticks(), 0);
+    while run_ticks() - start < ticks {
+        highest = highest.max(with_current(|th| th.priority()));
+        core::hint::spin_loop();
+    }
+    highest
+}
+
+/// Tests that a thread blocked on a [`Mutex`] donates its priority to the
+/// holder.
+///
+/// A low-priority thread holds a mutex for [`WORK`] ticks of running, while
+/// the busy medium-priority threads occupy every CPU. A high-priority thread
+/// then waits for the mutex. Without the donation, the holder never runs
+/// ahead of the medium-priority threads, and the mutex is never released.
+///
+/// This test ensures that:
+/// - The holder runs with the priority of the waiter, and releases the mutex
+///   to it in time.
+/// - The holder returns to its own priority when it releases the mutex.
+///
+/// This test must run with `sched=priority`.
+pub fn donate_mutex() {
+    assert_eq!(
+        cmdline::get("sched"),
+        Some("priority"),
+        "This test must run with `sched=priority`."
+    );
+    Current::set_priority(PRI_MAX);
+
+    let mutex = Arc::new(Mutex::new(0));
+    let locked = Arc::new(AtomicBool::new(false));
+    let boosted = Arc::new(AtomicUsize::new(0));
+    let holder = {
+        let (mutex, locked, boosted) = (mutex.clone(), locked.clone(), boosted.clone());
+        ThreadBuilder::new("holder").spawn(move || {
+            Current::set_priority(PRI_MIN);
+            let mut guard = mutex.lock();
+            locked.store(true);
+            boosted.store(run_for(WORK) as usize);
+            *guard += 1;
+            guard.unlock();
+        })
+    };
+    while !locked.load() {
+        Current::sleep(1);
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let hogs = spawn_hogs(&stop);
+    // Let the medium-priority threads take over the CPUs.
+    Current::sleep(5);
+
+    let guard = mutex.lock_timeout(WORK * 25);
+    // The holder drops the donation right after it releases the mutex, while
+    // the waiter may already run on another CPU.
+    let mut holder_priority = get_priority_by_tid(holder.tid);
+    for _ in 0..10 {
+        if holder_priority == Ok(PRI_MIN) {
+            break;
+        }
+        Current::sleep(1);
+        holder_priority = get_priority_by_tid(holder.tid);
+    }
+    stop.store(true);
+    Current::set_priority(PRI_DEFAULT);
+    for hog in hogs {
+        assert_eq!(hog.join(), 0);
+    }
+
+    let guard = guard.unwrap_or_else(|| {
+        panic!("The holder of the Mutex must run with the donated priority and release it.")
+    });
+    assert_eq!(*guard, 1);
+    guard.unlock();
+    assert_eq!(holder.join(), 0);
+    assert_eq!(
+        boosted.load(),
+        PRI_MAX as usize,
+        "The holder must run with the priority donated by the waiter."
+    );
+    assert_eq!(
+        holder_priority,
+        Ok(PRI_MIN),
+        "The holder must return to its own priority after the release."
+    );
+}
+
+/// Tests that a thread joining another one donates its priority to it.
+///
+/// A high-priority thread joins a low-priority thread that runs for [`WORK`]
+/// ticks, while the busy medium-priority threads occupy every CPU. Without
+/// the donation, the joined thread never runs ahead of the medium-priority
+/// threads, and the join never completes.
+///
+/// This test ensures that:
+/// - The joined thread runs with the priority of the joining thread, and the
+///   join completes in time.
+///
+/// This test must run with `sched=priority`.
+pub fn donate_join() {
+    assert_eq!(
+        cmdline::get("sched"),
+        Some("priority"),
+        "This test must run with `sched=priority`."
+    );
+    Current::set_priority(PRI_MAX);
+
+    let started = Arc::new(AtomicBool::new(false));
+    let boosted = Arc::new(AtomicUsize::new(0));
+    let worker = {
+        let (started, boosted) = (started.clone(), boosted.clone());
+        ThreadBuilder::new("worker").spawn(move || {
+            Current::set_priority(PRI_MIN);
+            started.store(true);
+            boosted.store(run_for(WORK) as usize);
+        })
+    };
+    while !started.load() {
+        Current::sleep(1);
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let hogs = spawn_hogs(&stop);
+    // Let the medium-priority threads take over the CPUs.
+    Current::sleep(5);
+
+    let start = now_ticks();
+    assert_eq!(worker.join(), 0);
+    let elapsed = now_ticks() - start;
+    stop.store(true);
+    Current::set_priority(PRI_DEFAULT);
+    for hog in hogs {
+        assert_eq!(hog.join(), 0);
+    }
+
+    assert_eq!(
+        boosted.load(),
+        PRI_MAX as usize,
+        "The joined thread must run with the priority donated by the joining thread."
+    );
+    assert!(
+        elapsed < WORK * 25,
+        "The join must complete in time, but it took {elapsed} ticks."
+    );
+}
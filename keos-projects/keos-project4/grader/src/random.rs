@@ -0,0 +1,71 @@
+use alloc::{format, vec::Vec};
+use keos::thread::{
+    Thread,
+    scheduler::{RandomScheduler, Scheduler},
+};
+
+const WORKERS: usize = 4;
+const INCREMENTS: usize = 8;
+
+// Runs `WORKERS` racy workers under a scheduler seeded with `seed`.
+//
+// Each worker increments a shared counter `INCREMENTS` times without
+// synchronization: one step loads the counter, and the next step stores the
+// loaded value plus one. The scheduler picks the worker that runs the next
+// step. Returns the order of the workers that ran, and the final counter.
+fn run_racy(seed: u64) -> (Vec<usize>, usize) {
+    let scheduler = RandomScheduler::new(seed);
+    let workers = (0..WORKERS)
+        .map(|i| {
+            let th = Thread::new(format!("worker{i}"));
+            let tid = th.tid;
+            scheduler.push_to_queue(th);
+            tid
+        })
+        .collect::<Vec<_>>();
+    // The loaded value and the number of steps of each worker.
+    let mut registers = [None; WORKERS];
+    let mut steps = [0; WORKERS];
+    let (mut order, mut counter) = (Vec::new(), 0);
+
+    while let Some(th) = scheduler.next_to_run() {
+        let i = workers.iter().position(|tid| *tid == th.tid).unwrap();
+        order.push(i);
+        match registers[i].take() {
+            None => registers[i] = Some(counter),
+            Some(v) => counter = v + 1,
+        }
+        steps[i] += 1;
+        if steps[i] != INCREMENTS * 2 {
+            scheduler.push_to_queue(th);
+        }
+    }
+    (order, counter)
+}
+
+/// Tests that the random scheduler reproduces an interleaving from the seed.
+///
+/// This test ensures that:
+/// - Every step of every worker is scheduled.
+/// - The same seed reproduces the same interleaving and the same result of the
+///   race.
+/// - Different seeds produce different interleavings.
+pub fn reproducible() {
+    let (order, counter) = run_racy(1);
+    assert_eq!(order.len(), WORKERS * INCREMENTS * 2);
+    for i in 0..WORKERS {
+        assert_eq!(order.iter().filter(|w| **w == i).count(), INCREMENTS * 2);
+    }
+    assert!(counter <= WORKERS * INCREMENTS);
+
+    assert_eq!(
+        run_racy(1),
+        (order.clone(), counter),
+        "The same seed must reproduce the same interleaving."
+    );
+    let (other, _) = run_racy(2);
+    assert_ne!(
+        order, other,
+        "Different seeds must produce different interleavings."
+    );
+}
@@ -2,10 +2,17 @@ use alloc::{collections::VecDeque, format, string::ToString, sync::Arc, vec::Vec
 use keos::{
     MAX_CPU,
     intrinsics::cpuid,
-    sync::atomic::{AtomicBool, AtomicUsize},
-    thread::{Thread, ThreadBuilder, scheduler::Scheduler},
+    sync::{
+        SpinLock,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize},
+    },
+    thread::{
+        Current, ParkHandle, Thread, ThreadBuilder, ThreadState, get_state_by_tid,
+        scheduler::{Scheduler, UTILIZATION_WINDOW, scheduler},
+    },
+    time::now_ticks,
 };
-use keos_project4::round_robin::RoundRobin;
+use keos_project4::round_robin::{DEFAULT_QUANTUM, RoundRobin};
 
 /// Tests the scheduler's ability to execute multiple threads in order.
 ///
@@ -249,3 +256,381 @@ pub fn affinity() {
         assert_eq!(handle.join(), 0);
     }
 }
+
+/// Tests that a sleeping thread is parked and woken up by the timer.
+///
+/// This test ensures that:
+/// - A thread sleeping with [`Current::sleep`] does not consume CPU, i.e., it
+///   is parked until the interval passes.
+/// - The thread wakes up after roughly the requested interval.
+/// - Sleeping for zero ticks returns immediately.
+pub fn sleep() {
+    const TICKS: u64 = 50;
+    let woke = Arc::new(AtomicBool::new(false));
+
+    let sleeper = {
+        let woke = woke.clone();
+        ThreadBuilder::new("sleeper").spawn(move || {
+            let start = now_ticks();
+            Current::sleep(TICKS);
+            let elapsed = now_ticks() - start;
+            assert!(elapsed >= TICKS, "Woke up too early: {elapsed} ticks.");
+            assert!(elapsed < TICKS * 10, "Woke up too late: {elapsed} ticks.");
+            woke.store(true);
+        })
+    };
+
+    while !woke.load() && get_state_by_tid(sleeper.tid) != Ok(ThreadState::Parked) {
+        core::hint::spin_loop();
+    }
+    assert!(
+        !woke.load(),
+        "A sleeping thread must be parked instead of spinning."
+    );
+    assert_eq!(sleeper.join(), 0);
+    assert!(woke.load());
+
+    let start = now_ticks();
+    Current::sleep(0);
+    assert!(
+        now_ticks() - start < TICKS,
+        "Sleeping for zero ticks must yield."
+    );
+}
+
+/// Tests the per-CPU utilization accounting.
+///
+/// This test ensures that:
+/// - Every CPU reads near 100% while all CPUs run busy loops.
+/// - Every CPU reads near 0% while all threads sleep.
+pub fn utilization() {
+    let window = UTILIZATION_WINDOW as u64;
+    let stop = Arc::new(AtomicBool::new(false));
+
+    // Keep every CPU busy.
+    let handles = (0..MAX_CPU)
+        .map(|_| {
+            let stop = stop.clone();
+            ThreadBuilder::new("busy").spawn(move || {
+                while !stop.load() {
+                    core::hint::spin_loop();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    let start = now_ticks();
+    while now_ticks() < start + window * 3 {
+        core::hint::spin_loop();
+    }
+    let busy = (0..MAX_CPU)
+        .map(|cpu| scheduler().cpu_utilization(cpu))
+        .collect::<Vec<_>>();
+    stop.store(true);
+    for handle in handles {
+        assert_eq!(handle.join(), 0);
+    }
+    for (cpu, utilization) in busy.into_iter().enumerate() {
+        assert!(
+            utilization >= 90,
+            "CPU {cpu} must be busy, but utilization is {utilization}%."
+        );
+    }
+
+    // Let every CPU idle.
+    Current::sleep(window * 3);
+    for cpu in 0..MAX_CPU {
+        let utilization = scheduler().cpu_utilization(cpu);
+        assert!(
+            utilization <= 10,
+            "CPU {cpu} must be idle, but utilization is {utilization}%."
+        );
+    }
+}
+
+/// Tests that a thread holding a [`PreemptGuard`] keeps its CPU.
+///
+/// This test ensures that:
+/// - The timer interrupt is still serviced while the preemption is disabled,
+///   and its reschedule is deferred.
+/// - No other thread runs on the CPU until the guard is dropped, even if
+///   other threads wait for the CPU.
+///
+/// [`PreemptGuard`]: keos::thread::PreemptGuard
+pub fn preempt_guard() {
+    let stop = Arc::new(AtomicBool::new(false));
+    let runs = Arc::new([0; MAX_CPU].map(|_| AtomicUsize::new(0)));
+
+    // Oversubscribe the CPUs, so that the scheduler preempts this thread.
+    let handles = (0..MAX_CPU * 2)
+        .map(|_| {
+            let (stop, runs) = (stop.clone(), runs.clone());
+            ThreadBuilder::new("busy").spawn(move || {
+                while !stop.load() {
+                    // Do not migrate between reading the cpu id and counting.
+                    let p = Thread::pin();
+                    runs[cpuid()].fetch_add(1);
+                    drop(p);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let guard = Thread::preempt_disable();
+    let cpu = cpuid();
+    let before = runs[cpu].load();
+    let start = now_ticks();
+    while !guard.is_resched_pending() {
+        assert!(
+            now_ticks() < start + 1000,
+            "The timer interrupt must be serviced while the preemption is disabled."
+        );
+        core::hint::spin_loop();
+    }
+    // Keep computing across a few more ticks.
+    let start = now_ticks();
+    while now_ticks() < start + 20 {
+        core::hint::spin_loop();
+    }
+    assert_eq!(cpuid(), cpu, "The thread must not migrate.");
+    assert_eq!(
+        runs[cpu].load(),
+        before,
+        "Other threads must not run on the CPU while the preemption is disabled."
+    );
+    drop(guard);
+
+    stop.store(true);
+    for handle in handles {
+        assert_eq!(handle.join(), 0);
+    }
+}
+
+/// Tests that a thread holding a [`Mutex`] is not preempted in a short
+/// critical section.
+///
+/// This test ensures that:
+/// - Threads running short sections while the CPUs are oversubscribed are
+///   preempted in some of the sections when they hold no lock.
+/// - The same sections are never preempted while holding a contended
+///   [`Mutex`], as the preemption is deferred until the mutex is released.
+///
+/// [`Mutex`]: keos_project4::sync::Mutex
+pub fn lock_holder_preemption() {
+    use keos_project4::sync::Mutex;
+
+    const SECTIONS: usize = 20;
+    const SECTION_TICKS: u64 = 2;
+
+    // Runs a section of `SECTION_TICKS` ticks, and returns whether another
+    // thread ran on the CPU in the meantime.
+    fn section(runs: &[AtomicUsize; MAX_CPU]) -> bool {
+        let p = Thread::pin();
+        let (cpu, before) = (cpuid(), runs[cpuid()].load());
+        drop(p);
+        let start = now_ticks();
+        while now_ticks() < start + SECTION_TICKS {
+            core::hint::spin_loop();
+        }
+        let p = Thread::pin();
+        let preempted = cpuid() != cpu || runs[cpu].load() != before;
+        drop(p);
+        preempted
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let runs = Arc::new([0; MAX_CPU].map(|_| AtomicUsize::new(0)));
+    let mutex = Arc::new(Mutex::new(()));
+
+    // Oversubscribe the CPUs, so that the scheduler preempts the workers.
+    let handles = (0..MAX_CPU * 2)
+        .map(|_| {
+            let (stop, runs) = (stop.clone(), runs.clone());
+            ThreadBuilder::new("busy").spawn(move || {
+                while !stop.load() {
+                    // Do not migrate between reading the cpu id and counting.
+                    let p = Thread::pin();
+                    runs[cpuid()].fetch_add(1);
+                    drop(p);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let run_workers = |locked: bool| {
+        let preempted = Arc::new(AtomicUsize::new(0));
+        let workers = (0..MAX_CPU)
+            .map(|_| {
+                let (runs, mutex, preempted) = (runs.clone(), mutex.clone(), preempted.clone());
+                ThreadBuilder::new("worker").spawn(move || {
+                    for _ in 0..SECTIONS {
+                        let guard = locked.then(|| mutex.lock());
+                        if section(&runs) {
+                            preempted.fetch_add(1);
+                        }
+                        if let Some(guard) = guard {
+                            guard.unlock();
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            assert_eq!(worker.join(), 0);
+        }
+        preempted.load()
+    };
+
+    let unlocked = run_workers(false);
+    let locked = run_workers(true);
+
+    stop.store(true);
+    for handle in handles {
+        assert_eq!(handle.join(), 0);
+    }
+
+    assert!(
+        unlocked > 0,
+        "Sections without a lock must be preempted on the oversubscribed CPUs."
+    );
+    assert_eq!(
+        locked,
+        0,
+        "Sections holding a mutex must not be preempted, but {locked} of {} were.",
+        MAX_CPU * SECTIONS
+    );
+}
+
+/// Tests the time slice configured by [`RoundRobin::with_quantum`].
+///
+/// This test ensures that:
+/// - [`RoundRobin::new`] uses the [`DEFAULT_QUANTUM`].
+/// - The running thread is rescheduled exactly once every quantum.
+/// - A longer quantum switches between a pair of CPU-bound threads fewer
+///   times.
+pub fn quantum() {
+    const TICKS: u64 = 200;
+    assert_eq!(RoundRobin::new().quantum(), DEFAULT_QUANTUM);
+
+    // Runs a pair of CPU-bound threads on a scheduler for `TICKS` timer ticks,
+    // and returns the number of context switches.
+    let switches = |quantum: u64| {
+        let scheduler = RoundRobin::with_quantum(quantum);
+        // Do not migrate while driving the scheduler on behalf of the CPU.
+        let _p = Thread::pin();
+        scheduler.push_to_queue(Thread::new("a"));
+        scheduler.push_to_queue(Thread::new("b"));
+        let mut running = scheduler.next_to_run().unwrap();
+        let mut switches = 0;
+        for _ in 0..TICKS {
+            if scheduler.tick() {
+                let prev = running.name.clone();
+                scheduler.push_to_queue(running);
+                running = scheduler.next_to_run().unwrap();
+                assert_ne!(running.name, prev, "The threads must take turns.");
+                switches += 1;
+            }
+        }
+        scheduler.push_to_queue(running);
+        while scheduler.next_to_run().is_some() {}
+        switches
+    };
+
+    let short = switches(DEFAULT_QUANTUM);
+    let long = switches(DEFAULT_QUANTUM * 4);
+    assert_eq!(
+        short,
+        TICKS / DEFAULT_QUANTUM,
+        "A thread must be rescheduled every {DEFAULT_QUANTUM} ticks."
+    );
+    assert_eq!(
+        long,
+        TICKS / (DEFAULT_QUANTUM * 4),
+        "A thread must be rescheduled every {} ticks.",
+        DEFAULT_QUANTUM * 4
+    );
+}
+
+/// Tests that a thread woken up from parking runs promptly.
+///
+/// A producer repeatedly wakes up a consumer parked on it, while the CPUs are
+/// oversubscribed by busy threads. The producer keeps the CPU after the
+/// wake-up, so the consumer has to wait for a reschedule.
+///
+/// This test ensures that:
+/// - The woken consumer runs within about a time slice, instead of waiting
+///   for a full round of the busy threads.
+pub fn wakeup_boost() {
+    const ROUNDS: usize = 10;
+    const BUSY_PER_CPU: usize = 4;
+    let stop = Arc::new(AtomicBool::new(false));
+    let handles = (0..MAX_CPU * BUSY_PER_CPU)
+        .map(|_| {
+            let stop = stop.clone();
+            ThreadBuilder::new("busy").spawn(move || {
+                while !stop.load() {
+                    core::hint::spin_loop();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // The handle of the parked consumer, the tick of the last wake-up, the
+    // total latency of the wake-ups, and the number of the wake-ups.
+    let state = Arc::new((
+        SpinLock::new(None::<ParkHandle>),
+        AtomicU64::new(0),
+        AtomicU64::new(0),
+        AtomicUsize::new(0),
+    ));
+    let consumer = {
+        let state = state.clone();
+        ThreadBuilder::new("consumer").spawn(move || {
+            let (slot, woken_at, latency, woken) = &*state;
+            for _ in 0..ROUNDS {
+                Current::park_with(|th| {
+                    let mut slot = slot.lock();
+                    *slot = Some(th);
+                    slot.unlock();
+                });
+                latency.fetch_add(now_ticks() - woken_at.load());
+                woken.fetch_add(1);
+            }
+        })
+    };
+
+    let (slot, woken_at, latency, woken) = &*state;
+    for round in 0..ROUNDS {
+        let th = loop {
+            let mut guard = slot.lock();
+            let th = guard.take();
+            guard.unlock();
+            if let Some(th) = th {
+                break th;
+            }
+            core::hint::spin_loop();
+        };
+        while get_state_by_tid(consumer.tid) != Ok(ThreadState::Parked) {
+            core::hint::spin_loop();
+        }
+        woken_at.store(now_ticks());
+        th.unpark();
+        // Keep the CPU until the consumer runs.
+        while woken.load() != round + 1 {
+            core::hint::spin_loop();
+        }
+    }
+    assert_eq!(consumer.join(), 0);
+
+    stop.store(true);
+    for handle in handles {
+        assert_eq!(handle.join(), 0);
+    }
+
+    // A full round of the busy threads on a CPU takes
+    // `BUSY_PER_CPU * DEFAULT_QUANTUM` ticks.
+    let average = latency.load() / ROUNDS as u64;
+    assert!(
+        average <= DEFAULT_QUANTUM * 2,
+        "A woken thread must run within about a time slice, but it waited for {average} ticks on average."
+    );
+}
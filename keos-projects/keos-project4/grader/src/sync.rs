@@ -1,10 +1,19 @@
 pub mod mutex {
-    use alloc::{sync::Arc, vec::Vec};
+    use alloc::{boxed::Box, sync::Arc, vec::Vec};
     use keos::{
         sync::atomic::{AtomicBool, AtomicUsize},
-        thread::{ThreadBuilder, ThreadState},
+        thread::{Current, ThreadBuilder, ThreadState},
+        time::now_ticks,
+    };
+    use keos_project2::mm_struct::MmStruct;
+    use keos_project4::{
+        Thread,
+        sync::{
+            deadlock,
+            mutex::{Mutex, ReentrantMutex, RobustMutex},
+        },
+        wait::ProcessHandle,
     };
-    use keos_project4::sync::mutex::Mutex;
 
     pub fn smoke() {
         const LENGTH: usize = 64;
@@ -71,11 +80,331 @@ pub mod mutex {
         guard.unlock();
         be_parked.join();
     }
+
+    pub fn reentrant() {
+        let mutex = Arc::new(ReentrantMutex::new(AtomicUsize::new(0)));
+        let outer = mutex.lock();
+        let inner = mutex.lock();
+        assert!(
+            mutex.try_lock().map(|guard| guard.unlock()).is_ok(),
+            "The owner must be able to lock the ReentrantMutex again."
+        );
+        inner.fetch_add(1);
+
+        let thread_spawned = Arc::new(AtomicBool::new(false));
+        let acquired = Arc::new(AtomicBool::new(false));
+        let be_parked = {
+            let (thread_spawned, acquired, mutex) =
+                (thread_spawned.clone(), acquired.clone(), mutex.clone());
+            ThreadBuilder::new("blockee").spawn(move || {
+                assert!(
+                    mutex.try_lock().is_err(),
+                    "Another thread must not acquire the held ReentrantMutex."
+                );
+                thread_spawned.store(true);
+                let guard = mutex.lock();
+                acquired.store(true);
+                assert_eq!(guard.load(), 1);
+                guard.unlock();
+            })
+        };
+
+        while !thread_spawned.load() {
+            core::hint::spin_loop();
+        }
+        for _ in 0..10000 {
+            core::hint::spin_loop();
+        }
+        inner.unlock();
+        for _ in 0..10000 {
+            core::hint::spin_loop();
+        }
+
+        assert!(
+            !acquired.load(),
+            "The ReentrantMutex must be held until the outermost guard is unlocked."
+        );
+        assert_eq!(
+            keos::thread::get_state_by_tid(be_parked.tid),
+            Ok(ThreadState::Parked),
+            "Blocked thread by ReentrantMutex should be in Parked state"
+        );
+
+        outer.unlock();
+        be_parked.join();
+        assert!(acquired.load());
+    }
+
+    pub fn try_lock_contended() {
+        let mutex = Arc::new(Mutex::new(0));
+        let guard = mutex.lock();
+
+        let contender = {
+            let mutex = mutex.clone();
+            ThreadBuilder::new("contender").spawn(move || {
+                assert!(
+                    mutex.try_lock().is_err(),
+                    "try_lock on a held Mutex must fail."
+                );
+            })
+        };
+        assert_eq!(contender.join(), 0);
+
+        guard.unlock();
+        let Ok(guard) = mutex.try_lock() else {
+            panic!("try_lock on a released Mutex must succeed.");
+        };
+        guard.unlock();
+    }
+
+    pub fn lock_timeout() {
+        let mutex = Arc::new(Mutex::new(0));
+        let guard = mutex.lock();
+
+        // The timeout expires while the lock stays held.
+        let expired = {
+            let mutex = mutex.clone();
+            ThreadBuilder::new("timeout").spawn(move || {
+                let start = now_ticks();
+                assert!(
+                    mutex.lock_timeout(10).is_none(),
+                    "lock_timeout on a held Mutex must expire."
+                );
+                assert!(
+                    now_ticks() - start >= 10,
+                    "lock_timeout must wait until the deadline."
+                );
+            })
+        };
+        assert_eq!(expired.join(), 0);
+
+        // The expired waiter must not be left in the wait queue. Otherwise, the
+        // unlock below wakes it up instead of the blocked thread.
+        let thread_spawned = Arc::new(AtomicBool::new(false));
+        let be_parked = {
+            let (thread_spawned, mutex) = (thread_spawned.clone(), mutex.clone());
+            ThreadBuilder::new("blockee").spawn(move || {
+                thread_spawned.store(true);
+                let mut guard = mutex.lock();
+                *guard += 1;
+                guard.unlock();
+            })
+        };
+        while !thread_spawned.load() {
+            core::hint::spin_loop();
+        }
+        for _ in 0..10000 {
+            core::hint::spin_loop();
+        }
+        guard.unlock();
+        be_parked.join();
+
+        // The lock is acquired if it is released before the deadline.
+        let guard = mutex.lock();
+        let acquired = {
+            let mutex = mutex.clone();
+            ThreadBuilder::new("timeout").spawn(move || {
+                let mut guard = mutex
+                    .lock_timeout(100_000)
+                    .expect("lock_timeout must acquire the released Mutex.");
+                *guard += 1;
+                guard.unlock();
+            })
+        };
+        for _ in 0..10000 {
+            core::hint::spin_loop();
+        }
+        guard.unlock();
+        assert_eq!(acquired.join(), 0);
+
+        let guard = mutex.lock();
+        assert_eq!(*guard, 2);
+        guard.unlock();
+    }
+
+    pub fn robust_owner_died() {
+        let mutex = Arc::new(RobustMutex::new(0));
+        let locked = Arc::new(AtomicBool::new(false));
+        let waiting = Arc::new(AtomicBool::new(false));
+
+        let holder = {
+            let (locked, mutex) = (locked.clone(), mutex.clone());
+            ThreadBuilder::new("holder").spawn(move || {
+                let mut guard = mutex.lock().unwrap();
+                *guard = 1;
+                locked.store(true);
+                loop {
+                    core::hint::spin_loop();
+                }
+            })
+        };
+        while !locked.load() {
+            core::hint::spin_loop();
+        }
+
+        let waiter = {
+            let (waiting, mutex) = (waiting.clone(), mutex.clone());
+            ThreadBuilder::new("waiter").spawn(move || {
+                waiting.store(true);
+                let Err(owner_died) = mutex.lock() else {
+                    panic!("Lock after the owner exited must return OwnerDied.");
+                };
+                let mut guard = owner_died.into_inner();
+                assert_eq!(*guard, 1);
+                *guard = 2;
+                guard.unlock();
+            })
+        };
+        while !waiting.load() {
+            core::hint::spin_loop();
+        }
+        for _ in 0..10000 {
+            core::hint::spin_loop();
+        }
+        assert_eq!(
+            keos::thread::get_state_by_tid(waiter.tid),
+            Ok(ThreadState::Parked),
+            "Blocked thread by RobustMutex should be in Parked state"
+        );
+
+        assert!(keos::thread::kill_by_tid(holder.tid, 3).is_ok());
+        assert_eq!(holder.join(), 3);
+        assert_eq!(waiter.join(), 0);
+
+        // The mutex is consistent again after the waiter unlocked it.
+        let Ok(guard) = mutex.lock() else {
+            panic!("Lock after a normal unlock must succeed.");
+        };
+        assert_eq!(*guard, 2);
+        guard.unlock();
+    }
+
+    pub fn deadlock_detect() {
+        assert!(
+            keos::sync::deadlock_detection(),
+            "The grader must enable the deadlock detection."
+        );
+        let (a, b) = (Arc::new(Mutex::new(())), Arc::new(Mutex::new(())));
+        let addr_a = &*a as *const Mutex<()> as usize;
+        let addr_b = &*b as *const Mutex<()> as usize;
+        assert!(deadlock::find_cycle(addr_a).is_none());
+
+        let guard_b = b.lock();
+        let locked_a = Arc::new(AtomicBool::new(false));
+        let blockee = {
+            let (a, b, locked_a) = (a.clone(), b.clone(), locked_a.clone());
+            ThreadBuilder::new("blockee").spawn(move || {
+                let guard_a = a.lock();
+                locked_a.store(true);
+                let guard_b = b.lock();
+                guard_b.unlock();
+                guard_a.unlock();
+            })
+        };
+        while !locked_a.load()
+            || keos::thread::get_state_by_tid(blockee.tid) != Ok(ThreadState::Parked)
+        {
+            core::hint::spin_loop();
+        }
+
+        // Waiting on A closes the cycle: this thread holds B, and the blockee
+        // holding A waits on B.
+        assert_eq!(
+            deadlock::find_cycle(addr_a),
+            Some(Vec::from([
+                (Current::get_tid(), addr_a),
+                (blockee.tid, addr_b)
+            ])),
+            "Waiting on A must be detected as a deadlock."
+        );
+        // Relocking B, which this thread holds, is a deadlock as well.
+        assert_eq!(
+            deadlock::find_cycle(addr_b),
+            Some(Vec::from([(Current::get_tid(), addr_b)])),
+            "Relocking a held mutex must be detected as a deadlock."
+        );
+
+        guard_b.unlock();
+        assert_eq!(blockee.join(), 0);
+        assert!(
+            deadlock::find_cycle(addr_a).is_none(),
+            "Released mutexes must be removed from the wait-for graph."
+        );
+    }
+
+    pub fn kill_blocked() {
+        const THREADS: usize = 8;
+        for _ in 0..20 {
+            let parent = ProcessHandle::new(Current::get_tid());
+            let mutex = Arc::new(Mutex::new(()));
+            let started = Arc::new(AtomicUsize::new(0));
+
+            // Build a process of THREADS threads: the first one holds the mutex
+            // forever, and the others block on it.
+            let builders = (0..THREADS)
+                .map(|_| ThreadBuilder::new("blocker"))
+                .collect::<Vec<_>>();
+            let tids = builders.iter().map(|b| b.get_tid()).collect::<Vec<_>>();
+            let mut tasks = tids
+                .iter()
+                .map(|tid| Box::new(Thread::from_mm_struct(MmStruct::new(), *tid)))
+                .collect::<Vec<_>>();
+            let (first, others) = tasks.split_first_mut().unwrap();
+            for (task, tid) in others.iter_mut().zip(&tids[1..]) {
+                task.process = first.process.clone_for(*tid);
+            }
+            parent.add_child(tids[0], &first.process);
+            // The thread calling `exit_group`.
+            let exiting = first.process.clone_for(Current::get_tid());
+
+            for (i, (builder, task)) in builders.into_iter().zip(tasks).enumerate() {
+                let (mutex, started) = (mutex.clone(), started.clone());
+                builder.attach_task(task).spawn(move || {
+                    if i == 0 {
+                        let _guard = mutex.lock();
+                        started.fetch_add(1);
+                        loop {
+                            core::hint::spin_loop();
+                        }
+                    } else {
+                        while started.load() == 0 {
+                            core::hint::spin_loop();
+                        }
+                        started.fetch_add(1);
+                        let guard = mutex.lock();
+                        guard.unlock();
+                        unreachable!("The mutex is never unlocked.");
+                    }
+                });
+            }
+
+            while started.load() != THREADS {
+                core::hint::spin_loop();
+            }
+            for _ in 0..10000 {
+                core::hint::spin_loop();
+            }
+
+            exiting.kill_siblings(3);
+            drop(exiting);
+            assert_eq!(parent.wait_child(tids[0]), Ok(3));
+            for tid in tids {
+                assert!(
+                    keos::thread::get_state_by_tid(tid).is_err(),
+                    "Killed thread should be exited."
+                );
+            }
+        }
+    }
 }
 
 pub mod condition_variable {
     use alloc::{sync::Arc, vec::Vec};
-    use keos::{MAX_CPU, sync::atomic::AtomicUsize, thread::ThreadBuilder};
+    use keos::{
+        MAX_CPU,
+        sync::atomic::AtomicUsize,
+        thread::{CANCELED, Current, ThreadBuilder, ThreadState, get_state_by_tid},
+    };
     use keos_project4::sync::{condition_variable::ConditionVariable, mutex::Mutex};
 
     const MAX: usize = 2;
@@ -129,6 +458,27 @@ pub mod condition_variable {
             self.full.signal(guard);
             item
         }
+
+        fn put_broadcast(&self, val: usize) {
+            let mut guard = self.full.wait_while(&self.inner, |b| b.is_full());
+            let tail = (guard.tail + 1) % MAX;
+            guard.tail = tail;
+            guard.item[tail] = val;
+            self.empty.broadcast(guard);
+        }
+
+        // Same as `get`, but counts the evaluations of the predicate.
+        fn get_counting(&self, checks: &AtomicUsize) -> usize {
+            let mut guard = self.empty.wait_while(&self.inner, |b| {
+                checks.fetch_add(1);
+                b.is_empty()
+            });
+            let front = (guard.front + 1) % MAX;
+            let item = guard.item[front];
+            guard.front = front;
+            self.full.signal(guard);
+            item
+        }
     }
 
     pub fn bounded_buffer_1() {
@@ -226,12 +576,214 @@ pub mod condition_variable {
         assert_eq!(&*output, &(0..MAX_CPU * 2 + 2).collect::<Vec<_>>());
         output.unlock();
     }
+
+    pub fn signal_one() {
+        const CONSUMERS: usize = MAX_CPU * 2;
+        let (buffer, checks, output) = (
+            Arc::new(Buffer {
+                inner: Mutex::new(BufferInner {
+                    item: [0; MAX],
+                    front: 0,
+                    tail: 0,
+                }),
+                full: ConditionVariable::new(),
+                empty: ConditionVariable::new(),
+            }),
+            Arc::new(AtomicUsize::new(0)),
+            Arc::new(Mutex::new(Vec::new())),
+        );
+        let consumed = || {
+            let guard = output.lock();
+            let len = guard.len();
+            guard.unlock();
+            len
+        };
+
+        let consumers = [0; CONSUMERS].map(|_| {
+            let (buffer, checks, output) = (buffer.clone(), checks.clone(), output.clone());
+            ThreadBuilder::new("consumer").spawn(move || {
+                let d = buffer.get_counting(&checks);
+                let mut guard = output.lock();
+                guard.push(d);
+                guard.unlock();
+            })
+        });
+        // Waits until the consumers that have not consumed an item are all
+        // parked on the empty buffer, and returns the number of the checks.
+        let settle = |remaining: usize| {
+            while consumers
+                .iter()
+                .filter(|c| get_state_by_tid(c.tid) == Ok(ThreadState::Parked))
+                .count()
+                != remaining
+            {
+                core::hint::spin_loop();
+            }
+            // Give the threads woken up by mistake the time to check again.
+            Current::sleep(10);
+            checks.load()
+        };
+        let before = settle(CONSUMERS);
+
+        // `signal` wakes up a single consumer, which takes the item.
+        buffer.put(0);
+        while consumed() != 1 {
+            core::hint::spin_loop();
+        }
+        let after = settle(CONSUMERS - 1);
+        assert_eq!(
+            after - before,
+            1,
+            "`signal` must wake up exactly one waiter and leave the rest parked."
+        );
+
+        // `broadcast` wakes up every consumer. One of them takes the item, and
+        // the others check the buffer again and go back to sleep.
+        buffer.put_broadcast(1);
+        while consumed() != 2 {
+            core::hint::spin_loop();
+        }
+        let before = after;
+        let after = settle(CONSUMERS - 2);
+        assert_eq!(
+            after - before,
+            CONSUMERS - 1,
+            "`broadcast` must wake up all waiters."
+        );
+
+        for i in 2..CONSUMERS {
+            buffer.put(i);
+        }
+        for consumer in consumers {
+            assert_eq!(consumer.join(), 0);
+        }
+        let mut output = output.lock();
+        output.sort();
+        assert_eq!(&*output, &(0..CONSUMERS).collect::<Vec<_>>());
+        output.unlock();
+    }
+
+    pub fn cancel_blocked() {
+        for _ in 0..20 {
+            let (mutex, condvar) = (
+                Arc::new(Mutex::new(false)),
+                Arc::new(ConditionVariable::new()),
+            );
+            let waiter = {
+                let (mutex, condvar) = (mutex.clone(), condvar.clone());
+                ThreadBuilder::new("waiter").spawn(move || {
+                    let guard = condvar.wait_while(&mutex, |ready| !*ready);
+                    guard.unlock();
+                    unreachable!("The condition is never met.");
+                })
+            };
+            while get_state_by_tid(waiter.tid) != Ok(ThreadState::Parked) {
+                core::hint::spin_loop();
+            }
+
+            waiter.cancel();
+            assert_eq!(
+                waiter.join(),
+                CANCELED,
+                "A thread blocked on a condition variable must exit on the cancellation."
+            );
+            // The canceled thread must not keep the mutex.
+            let guard = mutex.lock();
+            assert!(!*guard);
+            guard.unlock();
+        }
+    }
+}
+
+pub mod once {
+    use alloc::{sync::Arc, vec::Vec};
+    use keos::{
+        sync::atomic::{AtomicBool, AtomicUsize},
+        thread::ThreadBuilder,
+    };
+    use keos_project4::sync::{Lazy, Once};
+
+    pub fn call_once_race() {
+        const THREADS: usize = 16;
+        let once = Arc::new(Once::new());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let initialized = Arc::new(AtomicBool::new(false));
+        let start = Arc::new(AtomicBool::new(false));
+
+        let handles = (0..THREADS)
+            .map(|_| {
+                let (once, runs, initialized, start) = (
+                    once.clone(),
+                    runs.clone(),
+                    initialized.clone(),
+                    start.clone(),
+                );
+                ThreadBuilder::new("racer").spawn(move || {
+                    while !start.load() {
+                        core::hint::spin_loop();
+                    }
+                    once.call_once(|| {
+                        runs.fetch_add(1);
+                        // Take long enough for the others to block.
+                        for _ in 0..100000 {
+                            core::hint::spin_loop();
+                        }
+                        initialized.store(true);
+                    });
+                    assert!(
+                        initialized.load(),
+                        "call_once must return after the initialization finishes."
+                    );
+                })
+            })
+            .collect::<Vec<_>>();
+
+        start.store(true);
+        for handle in handles {
+            assert_eq!(handle.join(), 0);
+        }
+        assert_eq!(runs.load(), 1, "The closure must run exactly once.");
+        assert!(once.is_completed());
+
+        once.call_once(|| panic!("The closure must not run again."));
+    }
+
+    pub fn lazy() {
+        const THREADS: usize = 8;
+        let runs = Arc::new(AtomicUsize::new(0));
+        let lazy = {
+            let runs = runs.clone();
+            Arc::new(Lazy::new(move || {
+                runs.fetch_add(1);
+                (0..100).collect::<Vec<usize>>()
+            }))
+        };
+        assert_eq!(runs.load(), 0, "Lazy must not be initialized before use.");
+
+        let handles = (0..THREADS)
+            .map(|i| {
+                let lazy = lazy.clone();
+                ThreadBuilder::new("reader").spawn(move || {
+                    assert_eq!(lazy[i], i);
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(handle.join(), 0);
+        }
+        assert_eq!(lazy.len(), 100);
+        assert_eq!(runs.load(), 1, "Lazy must be initialized exactly once.");
+    }
 }
 
 pub mod semaphore {
     use alloc::sync::Arc;
+    use alloc::vec::Vec;
     use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
-    use keos::thread::ThreadBuilder;
+    use keos::{
+        sync::SpinLock,
+        thread::{ThreadBuilder, ThreadState, get_state_by_tid},
+    };
     use keos_project4::sync::{Mutex, Semaphore};
 
     pub fn sema_0() {
@@ -425,6 +977,64 @@ pub mod semaphore {
         assert_eq!(counter.load(Ordering::SeqCst), 0);
     }
 
+    pub fn fifo_order() {
+        const COUNT: usize = 10;
+        let sema = Arc::new(Semaphore::new(0, ()));
+        let order = Arc::new(SpinLock::new(Vec::new()));
+
+        let spawn = |i: usize| {
+            let (sema, order) = (sema.clone(), order.clone());
+            ThreadBuilder::new(alloc::format!("t{i}")).spawn(move || {
+                let permit = sema.wait();
+                let mut guard = order.lock();
+                guard.push(i);
+                guard.unlock();
+                core::mem::forget(permit);
+            })
+        };
+        let acquired = || {
+            let guard = order.lock();
+            let len = guard.len();
+            guard.unlock();
+            len
+        };
+
+        // Enqueue the threads one by one, so that the wait queue is in the
+        // order of their indices.
+        let mut handles = Vec::new();
+        for i in 0..COUNT {
+            let handle = spawn(i);
+            while get_state_by_tid(handle.tid) != Ok(ThreadState::Parked) {
+                core::hint::spin_loop();
+            }
+            handles.push(handle);
+        }
+
+        for i in 0..=COUNT {
+            sema.signal();
+            if i == 0 {
+                // A latecomer must wait behind the queued threads, even if it
+                // comes in while the first permit is being handed over.
+                handles.push(spawn(COUNT));
+            }
+            while acquired() != i + 1 {
+                core::hint::spin_loop();
+            }
+        }
+
+        for handle in handles {
+            assert_eq!(handle.join(), 0);
+        }
+        let guard = order.lock();
+        let order = guard.clone();
+        guard.unlock();
+        assert_eq!(
+            order,
+            (0..=COUNT).collect::<Vec<_>>(),
+            "Permits must be granted in the order the threads started waiting."
+        );
+    }
+
     pub fn n_permits() {
         const COUNT: usize = 5;
         const PERMITS: usize = 3;
@@ -479,3 +1089,227 @@ pub mod semaphore {
         guard.unlock();
     }
 }
+
+pub mod rwlock {
+    use alloc::{sync::Arc, vec::Vec};
+    use keos::{
+        sync::{
+            RwLock, RwLockPolicy, SpinLock,
+            atomic::{AtomicBool, AtomicUsize},
+        },
+        thread::{Current, ThreadBuilder},
+    };
+
+    // Runs a convoy of readers holding `lock`, then a writer, then late
+    // readers, and returns the order in which the writer (`usize::MAX`) and
+    // the late readers acquired the lock.
+    fn convoy(policy: RwLockPolicy) -> Vec<usize> {
+        const CONVOY: usize = 4;
+        const LATE: usize = 4;
+        let lock = Arc::new(RwLock::with_policy((), policy));
+        let order = Arc::new(SpinLock::new(Vec::new()));
+        let (holding, release) = (
+            Arc::new(AtomicUsize::new(0)),
+            Arc::new(AtomicBool::new(false)),
+        );
+        let record = |order: &SpinLock<Vec<usize>>, who| {
+            let mut guard = order.lock();
+            guard.push(who);
+            guard.unlock();
+        };
+
+        let mut handles = (0..CONVOY)
+            .map(|_| {
+                let (lock, holding, release) = (lock.clone(), holding.clone(), release.clone());
+                ThreadBuilder::new("convoy").spawn(move || {
+                    let guard = lock.read();
+                    holding.fetch_add(1);
+                    while !release.load() {
+                        core::hint::spin_loop();
+                    }
+                    drop(guard);
+                })
+            })
+            .collect::<Vec<_>>();
+        while holding.load() != CONVOY {
+            core::hint::spin_loop();
+        }
+
+        let (lock_, order_) = (lock.clone(), order.clone());
+        handles.push(ThreadBuilder::new("writer").spawn(move || {
+            let guard = lock_.write();
+            record(&order_, usize::MAX);
+            drop(guard);
+        }));
+        // Let the writer start waiting for the convoy.
+        Current::sleep(50);
+
+        for i in 0..LATE {
+            let (lock, order) = (lock.clone(), order.clone());
+            handles.push(ThreadBuilder::new("late").spawn(move || {
+                let guard = lock.read();
+                record(&order, i);
+                drop(guard);
+            }));
+        }
+        Current::sleep(50);
+
+        release.store(true);
+        for handle in handles {
+            assert_eq!(handle.join(), 0);
+        }
+        let guard = order.lock();
+        let order = guard.clone();
+        guard.unlock();
+        order
+    }
+
+    pub fn writer_preferring() {
+        // Late readers overtake the writer waiting for the convoy.
+        let order = convoy(RwLockPolicy::ReaderPreferring);
+        assert_eq!(
+            order.last(),
+            Some(&usize::MAX),
+            "A reader-preferring lock must admit the late readers first: {order:?}"
+        );
+
+        // Late readers wait behind the writer.
+        let order = convoy(RwLockPolicy::WriterPreferring);
+        assert_eq!(
+            order.first(),
+            Some(&usize::MAX),
+            "A writer-preferring lock must run the waiting writer first: {order:?}"
+        );
+    }
+}
+
+pub mod spinlock {
+    use alloc::{sync::Arc, vec::Vec};
+    use keos::{
+        MAX_CPU,
+        sync::{
+            SpinLock,
+            atomic::{AtomicBool, AtomicUsize},
+        },
+        thread::{Thread, ThreadBuilder},
+    };
+
+    /// Tests the spinlock under high contention.
+    ///
+    /// Every CPU hammers a single spinlock for a fixed number of acquisitions.
+    /// This test ensures that:
+    /// - The lock provides the mutual exclusion: no two CPUs are ever inside
+    ///   the critical section at once, and no increment is lost.
+    /// - No CPU is starved: every CPU completes all of its acquisitions.
+    pub fn contention() {
+        const ACQUISITIONS: usize = 10000;
+        let lock = Arc::new(SpinLock::new(0usize));
+        let inside = Arc::new(AtomicBool::new(false));
+        let pinned = Arc::new(AtomicUsize::new(0));
+
+        let handles = (0..MAX_CPU)
+            .map(|_| {
+                let (lock, inside, pinned) = (lock.clone(), inside.clone(), pinned.clone());
+                ThreadBuilder::new("hammer").spawn(move || {
+                    // Pin all cores so that every core hammers the lock.
+                    let _p = Thread::pin();
+                    pinned.fetch_add(1);
+                    while pinned.load() != MAX_CPU {
+                        core::hint::spin_loop();
+                    }
+
+                    for _ in 0..ACQUISITIONS {
+                        let mut guard = lock.lock();
+                        assert!(
+                            !inside.swap(true),
+                            "The spinlock must provide the mutual exclusion."
+                        );
+                        *guard += 1;
+                        inside.store(false);
+                        guard.unlock();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(handle.join(), 0, "A CPU failed while hammering the lock.");
+        }
+
+        let guard = lock.lock();
+        assert_eq!(
+            *guard,
+            MAX_CPU * ACQUISITIONS,
+            "Every acquisition of every CPU must be counted exactly once."
+        );
+        guard.unlock();
+    }
+}
+
+pub mod ticketlock {
+    use alloc::{sync::Arc, vec::Vec};
+    use keos::{
+        MAX_CPU,
+        sync::{SpinLock, TicketLock, atomic::AtomicUsize},
+        thread::{Thread, ThreadBuilder},
+    };
+
+    // Spins long enough for a thread running on another core to enqueue itself
+    // on the lock.
+    fn delay() {
+        for _ in 0..1 << 20 {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Tests the FIFO fairness of the ticket lock.
+    ///
+    /// A thread on one core holds the lock while the threads on the other cores
+    /// request it one after another. This test ensures that the lock is granted
+    /// in the order of the requests.
+    pub fn fifo_order() {
+        let lock = Arc::new(TicketLock::new(()));
+        let order = Arc::new(SpinLock::new(Vec::new()));
+        let turn = Arc::new(AtomicUsize::new(0));
+
+        let handles = (0..MAX_CPU)
+            .map(|i| {
+                let (lock, order, turn) = (lock.clone(), order.clone(), turn.clone());
+                ThreadBuilder::new("waiter").spawn(move || {
+                    // Pin all cores so that every waiter spins on its own core.
+                    let _p = Thread::pin();
+                    while turn.load() != i {
+                        core::hint::spin_loop();
+                    }
+                    // Let the previous thread enqueue itself first.
+                    delay();
+                    turn.store(i + 1);
+                    let guard = lock.lock();
+                    if i == 0 {
+                        // Hold the lock until every waiter is in line.
+                        while turn.load() != MAX_CPU {
+                            core::hint::spin_loop();
+                        }
+                        delay();
+                    }
+                    let mut order_guard = order.lock();
+                    order_guard.push(i);
+                    order_guard.unlock();
+                    guard.unlock();
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(handle.join(), 0);
+        }
+
+        let guard = order.lock();
+        let order = guard.clone();
+        guard.unlock();
+        assert_eq!(
+            order,
+            (0..MAX_CPU).collect::<Vec<_>>(),
+            "The ticket lock must be granted in the order of the requests."
+        );
+        assert!(lock.try_lock().map(|guard| guard.unlock()).is_ok());
+    }
+}
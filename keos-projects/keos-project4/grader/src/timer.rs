@@ -0,0 +1,112 @@
+use alloc::{sync::Arc, vec::Vec};
+use keos::{
+    sync::SpinLock,
+    thread::Current,
+    time::{MAX_CALLBACKS_PER_TICK, Timer, now_ticks},
+};
+
+/// Tests that timers fire in the order of their deadlines.
+///
+/// This test ensures that:
+/// - A callback runs after its deadline.
+/// - Timers fire in the order of their deadlines, regardless of the order of
+///   creation.
+/// - Timers with the same deadline fire in the order of creation.
+pub fn fire_in_order() {
+    const DELAYS: [u64; 6] = [30, 10, 50, 20, 40, 20];
+    let fired = Arc::new(SpinLock::new(Vec::new()));
+
+    let start = now_ticks();
+    for (i, delay) in DELAYS.into_iter().enumerate() {
+        let fired = fired.clone();
+        Timer::at(start + delay, move || {
+            let mut guard = fired.lock();
+            guard.push((i, now_ticks()));
+            guard.unlock();
+        });
+    }
+    Current::sleep(100);
+
+    let guard = fired.lock();
+    let fired = guard.clone();
+    guard.unlock();
+    assert_eq!(
+        fired.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+        [1, 3, 5, 0, 4, 2],
+        "Timers must fire in the order of deadlines."
+    );
+    for (i, tick) in fired {
+        assert!(tick >= start + DELAYS[i], "Timer {i} fired too early.");
+    }
+}
+
+/// Tests the cancellation of timers.
+///
+/// This test ensures that:
+/// - A cancelled timer never fires.
+/// - Cancelling a timer that already fired reports it.
+pub fn cancel() {
+    let fired = Arc::new(SpinLock::new(Vec::new()));
+    let timers = (0..4)
+        .map(|i| {
+            let fired = fired.clone();
+            Timer::after(10 + i as u64 * 10, move || {
+                let mut guard = fired.lock();
+                guard.push(i);
+                guard.unlock();
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut timers = timers.into_iter();
+    let first = timers.next().unwrap();
+    for (i, timer) in timers.enumerate() {
+        if i % 2 == 0 {
+            assert!(timer.cancel(), "A pending timer must be cancelled.");
+        }
+    }
+    Current::sleep(60);
+    assert!(!first.cancel(), "A fired timer must not be cancelled.");
+
+    let guard = fired.lock();
+    assert_eq!(*guard, [0, 2], "Cancelled timers must not fire.");
+    guard.unlock();
+}
+
+/// Tests that a burst of timers is deferred across ticks.
+///
+/// This test ensures that all the timers with the same deadline fire in the
+/// order of creation, even if they exceed the number of callbacks per tick.
+pub fn burst() {
+    const TIMERS: usize = MAX_CALLBACKS_PER_TICK * 3;
+    let fired = Arc::new(SpinLock::new(Vec::new()));
+
+    let deadline = now_ticks() + 10;
+    for i in 0..TIMERS {
+        let fired = fired.clone();
+        Timer::at(deadline, move || {
+            let mut guard = fired.lock();
+            guard.push((i, now_ticks()));
+            guard.unlock();
+        });
+    }
+    Current::sleep(50);
+
+    let guard = fired.lock();
+    let fired = guard.clone();
+    guard.unlock();
+    assert_eq!(
+        fired.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+        (0..TIMERS).collect::<Vec<_>>()
+    );
+    for chunk in fired
+        .chunks(MAX_CALLBACKS_PER_TICK)
+        .collect::<Vec<_>>()
+        .windows(2)
+    {
+        assert!(
+            chunk[0][0].1 < chunk[1][0].1,
+            "Excess callbacks must be deferred to the next tick."
+        );
+    }
+}
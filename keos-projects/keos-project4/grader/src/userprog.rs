@@ -1,33 +1,42 @@
 use crate::Thread;
 use alloc::boxed::Box;
-use keos::thread::ThreadBuilder;
-use keos_project2::{loader::LoadContext, mm_struct::MmStruct};
+use keos::thread::{JoinHandle, ThreadBuilder};
+use keos_project2::loader::LoadContext;
 
 pub fn run_elf(name: &str) -> i32 {
     run_elf_with_arg(name, &[name])
 }
 
 pub fn run_elf_with_arg(name: &str, args: &[&str]) -> i32 {
-    let LoadContext { mm_struct, regs } = LoadContext {
-        mm_struct: MmStruct::new(),
-        regs: keos::syscall::Registers::new(),
-    }
-    .load(
-        &keos::fs::FileSystem::root()
-            .open(name)
-            .unwrap()
-            .into_regular_file()
-            .unwrap(),
-        args,
-    )
-    .unwrap_or_else(|e| panic!("Failed to load elf: {}. reason: {:?}", name, e));
+    spawn_elf_with_arg(name, args).join()
+}
+
+/// Spawns a process running `name` without waiting for it to exit.
+pub fn spawn_elf_with_arg(name: &str, args: &[&str]) -> JoinHandle {
+    let LoadContext {
+        mm_struct,
+        regs,
+        symbols,
+        ..
+    } = LoadContext::new()
+        .load(
+            &keos::fs::FileSystem::root()
+                .open(name)
+                .unwrap()
+                .into_regular_file()
+                .unwrap(),
+            args,
+        )
+        .unwrap_or_else(|e| panic!("Failed to load elf: {}. reason: {:?}", name, e));
 
     let thread_build = ThreadBuilder::new(name);
     let tid = thread_build.get_tid();
     thread_build
         .attach_task(Box::new(Thread::from_mm_struct(mm_struct, tid)))
-        .spawn(move || regs.launch())
-        .join()
+        .spawn(move || {
+            keos::task::set_user_symbols(symbols);
+            regs.launch()
+        })
 }
 
 #[stdin(b"")]
@@ -205,3 +214,77 @@ pub fn thread_join_complex() {
 pub fn thread_mm_shared() {
     run_elf("thread_mm_shared");
 }
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn atomic_cas() {
+    run_elf("atomic_cas");
+}
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn sched_stats() {
+    run_elf("sched_stats");
+}
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn sys_getpid() {
+    run_elf("sys_getpid");
+}
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn sys_waitpid() {
+    run_elf("sys_waitpid");
+}
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn vfork() {
+    run_elf("vfork");
+}
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn execve_threaded() {
+    run_elf("execve_threaded");
+}
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn spawn() {
+    run_elf("spawn");
+}
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn sys_sleep() {
+    run_elf("sys_sleep");
+}
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn sys_clock() {
+    run_elf("sys_clock");
+}
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn sys_fcntl() {
+    run_elf("sys_fcntl");
+}
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn sys_flock() {
+    run_elf("sys_flock");
+}
+
+pub fn shm_two_processes() {
+    // The processes are unrelated: they find the segment through its key.
+    let writer = spawn_elf_with_arg("shm_writer", &["shm_writer"]);
+    let reader = spawn_elf_with_arg("shm_reader", &["shm_reader"]);
+    assert_eq!(writer.join(), 0);
+    assert_eq!(reader.join(), 0);
+}
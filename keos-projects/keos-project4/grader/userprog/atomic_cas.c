@@ -0,0 +1,56 @@
+#include <debug.h>
+#include <mman.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <syscall.h>
+#include <thread.h>
+
+#define ITERATIONS 100000
+
+volatile uint64_t counter = 0;
+
+int thread_fn(void *arg UNUSED) {
+  for (int i = 0; i < ITERATIONS; i++) {
+    uint64_t old;
+    do {
+      old = counter;
+    } while (atomic_cas((uint64_t *)&counter, old, old + 1) != 1);
+  }
+  exit(0);
+  __builtin_unreachable();
+}
+
+int main(int argc, char *argv[]) {
+  // A failed comparison leaves the value unchanged.
+  ASSERT(atomic_cas((uint64_t *)&counter, 1, 2) == 0);
+  ASSERT(counter == 0);
+  // Misaligned or unmapped addresses are rejected.
+  ASSERT(atomic_cas((uint64_t *)((char *)&counter + 1), 0, 1) < 0);
+  ASSERT(atomic_cas((uint64_t *)0x1000000, 0, 1) < 0);
+
+  void *stack_1 =
+      mmap((void *)0xA000, STACK_SIZE, PROT_READ | PROT_WRITE, -1, 0);
+  ASSERT(stack_1 == (void *)0xA000);
+
+  void *stack_2 =
+      mmap((void *)0xE000, STACK_SIZE, PROT_READ | PROT_WRITE, -1, 0);
+  ASSERT(stack_2 == (void *)0xE000);
+
+  int thread_id_1 =
+      thread_create("cas thread 1", stack_1 + STACK_SIZE, thread_fn, 0);
+  ASSERT(thread_id_1 > 0);
+  int thread_id_2 =
+      thread_create("cas thread 2", stack_2 + STACK_SIZE, thread_fn, 0);
+  ASSERT(thread_id_2 > 0);
+
+  int exitcode = -1;
+  ASSERT(thread_join(thread_id_1, &exitcode) == 0);
+  ASSERT(exitcode == 0);
+  ASSERT(thread_join(thread_id_2, &exitcode) == 0);
+  ASSERT(exitcode == 0);
+
+  // No increment is lost.
+  ASSERT(counter == 2 * ITERATIONS);
+  printf("success ");
+  return 0;
+}
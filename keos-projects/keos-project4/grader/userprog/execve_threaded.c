@@ -0,0 +1,33 @@
+#include <debug.h>
+#include <mman.h>
+#include <stdio.h>
+#include <syscall.h>
+#include <thread.h>
+
+static volatile int go;
+
+int thread_fn(void *arg) {
+  while (!go)
+    ;
+  exit(0);
+}
+
+int main(int argc, char *argv[]) {
+  void *stack = mmap((void *)0xA000, STACK_SIZE, PROT_READ | PROT_WRITE, -1, 0);
+  ASSERT(stack == (void *)0xA000);
+
+  int thread_id = thread_create("sibling", stack + STACK_SIZE, thread_fn, NULL);
+  ASSERT(thread_id > 0);
+
+  // The sibling still runs on the address space of this process.
+  char *args[] = {"vfork_child", NULL};
+  ASSERT(execve("vfork_child", args, NULL) < 0);
+
+  go = 1;
+  int exitcode = -1;
+  ASSERT(thread_join(thread_id, &exitcode) == 0);
+  ASSERT(exitcode == 0);
+
+  printf("success ");
+  return 0;
+}
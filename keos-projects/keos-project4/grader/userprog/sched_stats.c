@@ -0,0 +1,85 @@
+#include <debug.h>
+#include <mman.h>
+#include <stdio.h>
+#include <syscall.h>
+#include <thread.h>
+
+#define CPU_TICKS 200
+#define IO_ROUNDS 20
+
+volatile int cpu_done = 0;
+volatile int io_done = 0;
+volatile int collected = 0;
+
+int cpu_fn(void *arg UNUSED) {
+  struct clock_time start, now;
+  ASSERT(clock_gettime(&start) == 0);
+  do {
+    ASSERT(clock_gettime(&now) == 0);
+  } while (now.ticks - start.ticks < CPU_TICKS);
+
+  // Keep the thread alive until its statistics are read.
+  cpu_done = 1;
+  while (!collected) {
+  }
+  exit(0);
+  __builtin_unreachable();
+}
+
+int io_fn(void *arg UNUSED) {
+  for (int i = 0; i < IO_ROUNDS; i++) {
+    ASSERT(sleep(1) == 0);
+  }
+
+  io_done = 1;
+  while (!collected) {
+    ASSERT(sleep(1) == 0);
+  }
+  exit(0);
+  __builtin_unreachable();
+}
+
+int main(int argc, char *argv[]) {
+  struct sched_stats cpu, io;
+  int exitcode = -1;
+
+  void *stack_1 =
+      mmap((void *)0xA000, STACK_SIZE, PROT_READ | PROT_WRITE, -1, 0);
+  ASSERT(stack_1 == (void *)0xA000);
+
+  void *stack_2 =
+      mmap((void *)0xE000, STACK_SIZE, PROT_READ | PROT_WRITE, -1, 0);
+  ASSERT(stack_2 == (void *)0xE000);
+
+  int cpu_tid = thread_create("cpu bound", stack_1 + STACK_SIZE, cpu_fn, 0);
+  ASSERT(cpu_tid > 0);
+  int io_tid = thread_create("io bound", stack_2 + STACK_SIZE, io_fn, 0);
+  ASSERT(io_tid > 0);
+
+  while (!cpu_done || !io_done) {
+    ASSERT(sleep(1) == 0);
+  }
+  ASSERT(sched_stats(cpu_tid, &cpu) == 0);
+  ASSERT(sched_stats(io_tid, &io) == 0);
+  collected = 1;
+
+  ASSERT(thread_join(cpu_tid, &exitcode) == 0);
+  ASSERT(exitcode == 0);
+  ASSERT(thread_join(io_tid, &exitcode) == 0);
+  ASSERT(exitcode == 0);
+
+  // The statistics of an exited thread are gone.
+  ASSERT(sched_stats(cpu_tid, &cpu) < 0);
+
+  // The CPU-bound thread runs until it is preempted.
+  ASSERT(cpu.run_ticks >= CPU_TICKS / 2);
+  ASSERT(cpu.involuntary_switches > cpu.voluntary_switches);
+  // The I/O-bound thread gives up the CPU on every sleep.
+  ASSERT(io.voluntary_switches >= IO_ROUNDS);
+  ASSERT(io.voluntary_switches > io.involuntary_switches);
+  ASSERT(io.scheduled >= IO_ROUNDS);
+  ASSERT(cpu.run_ticks > io.run_ticks);
+
+  printf("success ");
+  return 0;
+}
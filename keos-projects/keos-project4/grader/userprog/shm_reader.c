@@ -0,0 +1,29 @@
+#include <debug.h>
+#include <mman.h>
+#include <string.h>
+#include <syscall.h>
+
+#define SHM_KEY 0x4b654f53
+#define SHM_SIZE 0x2000
+
+struct channel {
+  volatile int state;
+  char msg[64];
+};
+
+int main(int argc, char *argv[]) {
+  int id = shmget(SHM_KEY, SHM_SIZE);
+  ASSERT(id > 0);
+  // The segment is attached at a different address than in the writer.
+  struct channel *ch = shmat(id, (void *)0x30000000);
+  ASSERT(ch == (void *)0x30000000);
+
+  while (ch->state != 1)
+    sleep(1);
+  ASSERT(strcmp(ch->msg, "hello through shared memory") == 0);
+  ASSERT(((volatile char *)ch)[SHM_SIZE - 1] == 42);
+
+  // Answer the writer. Exiting detaches the segment.
+  ch->state = 2;
+  return 0;
+}
@@ -0,0 +1,37 @@
+#include <debug.h>
+#include <mman.h>
+#include <string.h>
+#include <syscall.h>
+
+#define SHM_KEY 0x4b654f53
+#define SHM_SIZE 0x2000
+
+struct channel {
+  volatile int state;
+  char msg[64];
+};
+
+int main(int argc, char *argv[]) {
+  int id = shmget(SHM_KEY, SHM_SIZE);
+  ASSERT(id > 0);
+  // A failed attachment leaves the segment as it was.
+  ASSERT((long)shmat(id, (void *)0x20000001) < 0);
+  void *busy = mmap((void *)0x30000000, 0x1000, PROT_READ | PROT_WRITE, -1, 0);
+  ASSERT(busy == (void *)0x30000000);
+  ASSERT((long)shmat(id, busy) < 0);
+  ASSERT(shmget(SHM_KEY, SHM_SIZE) == id);
+
+  struct channel *ch = shmat(id, (void *)0x20000000);
+  ASSERT(ch == (void *)0x20000000);
+
+  strlcpy(ch->msg, "hello through shared memory", sizeof(ch->msg));
+  // The last page of the segment is shared as well.
+  ((volatile char *)ch)[SHM_SIZE - 1] = 42;
+  ch->state = 1;
+
+  // Keep the segment attached until the reader answers.
+  while (ch->state != 2)
+    sleep(1);
+  ASSERT(munmap(ch, SHM_SIZE) == 0);
+  return 0;
+}
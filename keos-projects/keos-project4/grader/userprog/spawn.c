@@ -0,0 +1,39 @@
+#include <debug.h>
+#include <stdio.h>
+#include <string.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  int fds[2] = {0};
+  char buf[64] = {0};
+  char *args[] = {"spawn_child", "through a pipe", NULL};
+
+  ASSERT(pipe(fds) == 0);
+
+  // Run the child with its stdout redirected to the pipe.
+  struct spawn_action actions[] = {
+      {SPAWN_DUP2, fds[1], 1},
+      {SPAWN_CLOSE, fds[0], 0},
+      {SPAWN_CLOSE, fds[1], 0},
+  };
+  int pid = spawn("spawn_child", args, NULL, actions, 3);
+  ASSERT(pid > 0);
+
+  // The read end sees the end of file once the child exits.
+  ASSERT(close(fds[1]) == 0);
+  size_t len = 0;
+  ssize_t n;
+  while ((n = read(fds[0], buf + len, sizeof(buf) - 1 - len)) > 0)
+    len += n;
+  ASSERT(n == 0);
+  ASSERT(strcmp(buf, "hello through a pipe") == 0);
+  ASSERT(waitpid(pid) == 3);
+
+  // No process is created on error.
+  struct spawn_action bad[] = {{SPAWN_DUP2, 100, 1}};
+  ASSERT(spawn("spawn_child", args, NULL, bad, 1) < 0);
+  ASSERT(spawn("no_such_program", args, NULL, NULL, 0) < 0);
+
+  printf("success ");
+  return 0;
+}
@@ -0,0 +1,12 @@
+#include <debug.h>
+#include <stdio.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  ASSERT(argc == 2);
+  // Only the redirected stdout is left besides stdin and stderr.
+  ASSERT(write(3, "", 0) < 0);
+  ASSERT(write(4, "", 0) < 0);
+  printf("hello %s", argv[1]);
+  return 3;
+}
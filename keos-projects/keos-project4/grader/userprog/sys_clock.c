@@ -0,0 +1,28 @@
+#include <debug.h>
+#include <stdio.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  struct clock_time before, after;
+
+  ASSERT(clock_gettime(&before) == 0);
+  ASSERT(before.ns == before.ticks * 1000000);
+
+  ASSERT(sleep(20) == 0);
+  ASSERT(clock_gettime(&after) == 0);
+  ASSERT(after.ns == after.ticks * 1000000);
+  // The clock advances at least by the slept interval.
+  ASSERT(after.ticks >= before.ticks + 20);
+
+  // The clock never goes backward.
+  for (int i = 0; i < 1000; i++) {
+    ASSERT(clock_gettime(&before) == 0);
+    ASSERT(before.ticks >= after.ticks);
+    after = before;
+  }
+
+  ASSERT(clock_gettime(NULL) < 0);
+  ASSERT(clock_gettime((struct clock_time *)0xffffff0000100000) < 0);
+  printf("success ");
+  return 0;
+}
@@ -0,0 +1,31 @@
+#include <debug.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  int fds[2];
+  char buf[4];
+
+  ASSERT(pipe(fds) == 0);
+  ASSERT(fcntl(fds[0], F_GETFL, 0) == O_RDONLY);
+  ASSERT(fcntl(fds[1], F_GETFL, 0) == O_WRONLY);
+
+  // The access mode bits are ignored.
+  ASSERT(fcntl(fds[0], F_SETFL, O_RDWR | O_NONBLOCK) == 0);
+  ASSERT(fcntl(fds[0], F_GETFL, 0) == (O_RDONLY | O_NONBLOCK));
+
+  // A read on the empty pipe returns immediately.
+  ASSERT(read(fds[0], buf, sizeof(buf)) < 0);
+  ASSERT(write(fds[1], "ab", 2) == 2);
+  ASSERT(read(fds[0], buf, sizeof(buf)) == 2);
+
+  // Back to the blocking mode.
+  ASSERT(fcntl(fds[0], F_SETFL, 0) == 0);
+  ASSERT(fcntl(fds[0], F_GETFL, 0) == O_RDONLY);
+
+  ASSERT(fcntl(fds[0], 1234, 0) < 0);
+  ASSERT(fcntl(100, F_GETFL, 0) < 0);
+  printf("success ");
+  return 0;
+}
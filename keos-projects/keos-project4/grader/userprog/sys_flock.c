@@ -0,0 +1,50 @@
+#include <debug.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  int fds[2];
+  char buf[2];
+
+  ASSERT(pipe(fds) == 0);
+  int fd = open("hello", O_RDONLY);
+  ASSERT(fd >= 3);
+  ASSERT(flock(fd, LOCK_EX) == 0);
+
+  int pid = fork();
+  ASSERT(pid >= 0);
+  if (pid == 0) {
+    // The inherited file descriptor shares the lock with the parent.
+    ASSERT(flock(fd, LOCK_EX | LOCK_NB) == 0);
+
+    // A separately opened file contends for the lock.
+    int fd2 = open("hello", O_RDONLY);
+    ASSERT(fd2 >= 3);
+    ASSERT(flock(fd2, LOCK_SH | LOCK_NB) < 0);
+    ASSERT(flock(fd2, LOCK_EX) == 0);
+    ASSERT(write(fds[1], "c", 1) == 1);
+    // Exits while holding the lock.
+    return 0;
+  }
+
+  ASSERT(sleep(50) == 0);
+  ASSERT(write(fds[1], "p", 1) == 1);
+  // Closing the last file descriptor of the lock releases it.
+  ASSERT(close(fd) == 0);
+
+  // The child takes the lock only after the parent released it.
+  ASSERT(read(fds[0], buf, 2) == 2);
+  ASSERT(buf[0] == 'p' && buf[1] == 'c');
+
+  // The exit of the child releases its lock.
+  ASSERT(waitpid(pid) == 0);
+  fd = open("hello", O_RDONLY);
+  ASSERT(flock(fd, LOCK_EX) == 0);
+  ASSERT(flock(fd, LOCK_UN) == 0);
+
+  ASSERT(flock(fd, 0) < 0);
+  ASSERT(flock(fds[0], LOCK_EX) < 0);
+  printf("success ");
+  return 0;
+}
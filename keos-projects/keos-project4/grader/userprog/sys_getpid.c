@@ -0,0 +1,46 @@
+#include <debug.h>
+#include <mman.h>
+#include <stdio.h>
+#include <syscall.h>
+#include <thread.h>
+
+int pid;
+
+int thread_fn(void *arg) {
+  ASSERT(getpid() == pid);
+  ASSERT(gettid() == *(int *)arg);
+  exit(0);
+}
+
+int main(int argc, char *argv[]) {
+  int fds[2] = {0};
+  char buf[1] = {0};
+
+  pid = getpid();
+  ASSERT(pid > 0);
+  ASSERT(gettid() == pid);
+
+  // Threads share the process id.
+  int tid = 0;
+  void *stack = mmap((void *)0xA000, STACK_SIZE, PROT_READ | PROT_WRITE, -1, 0);
+  ASSERT(stack == (void *)0xA000);
+  tid = thread_create("my thread", stack + STACK_SIZE, thread_fn, &tid);
+  ASSERT(tid > 0 && tid != pid);
+  int exitcode = -1;
+  ASSERT(thread_join(tid, &exitcode) == 0);
+  ASSERT(exitcode == 0);
+
+  // A forked child is a new process whose parent is this process.
+  ASSERT(pipe(fds) == 0);
+  int child = fork();
+  ASSERT(child >= 0);
+  if (child == 0) {
+    ASSERT(getpid() != pid);
+    ASSERT(getppid() == pid);
+    ASSERT(write(fds[1], "\0", 1) == 1);
+    return 0;
+  }
+  ASSERT(read(fds[0], buf, 1) == 1);
+  printf("success ");
+  return 0;
+}
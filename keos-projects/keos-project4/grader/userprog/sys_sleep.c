@@ -0,0 +1,36 @@
+#include <debug.h>
+#include <mman.h>
+#include <stdio.h>
+#include <syscall.h>
+#include <thread.h>
+
+int fds[2];
+
+int thread_fn(void *arg) {
+  ASSERT(sleep(100) == 0);
+  ASSERT(write(fds[1], "\0", 1) == 1);
+  exit(0);
+}
+
+int main(int argc, char *argv[]) {
+  // Sleeping for zero ticks yields the CPU.
+  ASSERT(sleep(0) == 0);
+  ASSERT(sleep(1) == 0);
+
+  ASSERT(pipe(fds) == 0);
+  void *stack = mmap((void *)0xA000, STACK_SIZE, PROT_READ | PROT_WRITE, -1, 0);
+  ASSERT(stack == (void *)0xA000);
+  int tid = thread_create("sleeper", stack + STACK_SIZE, thread_fn, NULL);
+  ASSERT(tid > 0);
+
+  // The thread is still sleeping.
+  ASSERT(poll(&fds[0], 1, 10) == 0);
+  // Then wakes up to write.
+  ASSERT(poll(&fds[0], 1, -1) == 1);
+
+  int exitcode = -1;
+  ASSERT(thread_join(tid, &exitcode) == 0);
+  ASSERT(exitcode == 0);
+  printf("success ");
+  return 0;
+}
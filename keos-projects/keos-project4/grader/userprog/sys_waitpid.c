@@ -0,0 +1,27 @@
+#include <debug.h>
+#include <stdio.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  int pids[3] = {0};
+
+  for (int i = 0; i < 3; i++) {
+    pids[i] = fork();
+    ASSERT(pids[i] >= 0);
+    if (pids[i] == 0) {
+      return 10 + i;
+    }
+  }
+
+  // Children may already have exited.
+  for (int i = 2; i >= 0; i--) {
+    ASSERT(waitpid(pids[i]) == 10 + i);
+  }
+
+  // Reaped children and non-children are not waitable.
+  ASSERT(waitpid(pids[0]) < 0);
+  ASSERT(waitpid(getpid()) < 0);
+
+  printf("success ");
+  return 0;
+}
@@ -0,0 +1,38 @@
+#include <debug.h>
+#include <stdio.h>
+#include <string.h>
+#include <syscall.h>
+
+static volatile int shared;
+
+int main(int argc, char *argv[]) {
+  volatile int local = 0x1234;
+
+  // The child runs on the address space of the parent until it calls execve.
+  int pid = vfork();
+  ASSERT(pid >= 0);
+  if (pid == 0) {
+    char *args[] = {"vfork_child", "from-vfork", NULL};
+    shared = 1;
+    execve("vfork_child", args, NULL);
+    exit(1);
+  }
+  // The parent resumes after the execve, with its stack intact.
+  ASSERT(shared == 1);
+  ASSERT(local == 0x1234);
+  ASSERT(waitpid(pid) == 42);
+
+  // The parent resumes after the exit as well.
+  pid = vfork();
+  ASSERT(pid >= 0);
+  if (pid == 0) {
+    shared = 2;
+    exit(7);
+  }
+  ASSERT(shared == 2);
+  ASSERT(local == 0x1234);
+  ASSERT(waitpid(pid) == 7);
+
+  printf("success ");
+  return 0;
+}
@@ -0,0 +1,10 @@
+#include <debug.h>
+#include <string.h>
+#include <syscall.h>
+
+int main(int argc, char *argv[]) {
+  ASSERT(argc == 2);
+  ASSERT(strcmp(argv[0], "vfork_child") == 0);
+  ASSERT(strcmp(argv[1], "from-vfork") == 0);
+  return 42;
+}
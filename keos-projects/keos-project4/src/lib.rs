@@ -62,7 +62,9 @@ extern crate keos;
 
 pub mod process;
 pub mod round_robin;
+pub mod spawn;
 pub mod sync;
+pub mod wait;
 
 use alloc::boxed::Box;
 use core::ops::Range;
@@ -76,8 +78,15 @@ use keos::{
 };
 use keos_project1::syscall::SyscallAbi;
 use keos_project2::mm_struct::MmStruct;
-use keos_project3::{fork::fork, get_phys::get_phys, lazy_pager::PageFaultReason};
+use keos_project3::{
+    atomic::atomic_cas,
+    fork::fork,
+    get_phys::get_phys,
+    lazy_pager::PageFaultReason,
+    shm::{shmat, shmget},
+};
 pub use process::Thread;
+use spawn::spawn;
 
 /// Represents system call numbers used in project4.
 ///
@@ -114,6 +123,47 @@ pub enum SyscallNumber {
     ThreadJoin = 12,
     /// Terminates the process, by terminating all threads.
     ExitGroup = 13,
+    /// Retrieves the file I/O statistics of the process.
+    IoStat = 21,
+    /// Create a pipe with the given capacity and flags.
+    Pipe2 = 23,
+    /// Wait until one of file descriptors becomes ready.
+    Poll = 24,
+    /// Get the thread id.
+    GetTid = 25,
+    /// Get the process id.
+    GetPid = 26,
+    /// Get the process id of the parent process.
+    GetPpid = 27,
+    /// Wait for a child process to exit.
+    Waitpid = 28,
+    /// Sleep for the given number of timer ticks.
+    Sleep = 30,
+    /// Read the monotonic clock.
+    ClockGettime = 31,
+    /// Get or set the flags of an open file.
+    Fcntl = 32,
+    /// Apply or remove an advisory lock on an open file.
+    Flock = 33,
+    /// Transfer data between open files within the kernel.
+    Sendfile = 35,
+    /// Replace the process image with a new program.
+    Execve = 37,
+    /// Get the resident set size of the process, in pages.
+    GetRss = 38,
+    /// Create or look up a shared memory segment.
+    ShmGet = 39,
+    /// Map a shared memory segment into the address space.
+    ShmAt = 40,
+    /// Atomically compare and swap a value in the user memory.
+    AtomicCas = 41,
+    /// Read the scheduling statistics of a thread.
+    SchedStats = 42,
+    /// Create a child process sharing the address space, and wait until it
+    /// calls `execve` or exits.
+    Vfork = 43,
+    /// Create a child process running a new program.
+    Spawn = 44,
     /// Get Physical Address of Page (for grading purposes only)
     GetPhys = 0x81,
 }
@@ -136,6 +186,26 @@ impl TryFrom<usize> for SyscallNumber {
             11 => Ok(SyscallNumber::ThreadCreate),
             12 => Ok(SyscallNumber::ThreadJoin),
             13 => Ok(SyscallNumber::ExitGroup),
+            21 => Ok(SyscallNumber::IoStat),
+            23 => Ok(SyscallNumber::Pipe2),
+            24 => Ok(SyscallNumber::Poll),
+            25 => Ok(SyscallNumber::GetTid),
+            26 => Ok(SyscallNumber::GetPid),
+            27 => Ok(SyscallNumber::GetPpid),
+            28 => Ok(SyscallNumber::Waitpid),
+            30 => Ok(SyscallNumber::Sleep),
+            31 => Ok(SyscallNumber::ClockGettime),
+            32 => Ok(SyscallNumber::Fcntl),
+            33 => Ok(SyscallNumber::Flock),
+            35 => Ok(SyscallNumber::Sendfile),
+            37 => Ok(SyscallNumber::Execve),
+            38 => Ok(SyscallNumber::GetRss),
+            39 => Ok(SyscallNumber::ShmGet),
+            40 => Ok(SyscallNumber::ShmAt),
+            41 => Ok(SyscallNumber::AtomicCas),
+            42 => Ok(SyscallNumber::SchedStats),
+            43 => Ok(SyscallNumber::Vfork),
+            44 => Ok(SyscallNumber::Spawn),
             0x81 => Ok(SyscallNumber::GetPhys),
             _ => Err(KernelError::NoSuchSyscall),
         }
@@ -145,40 +215,100 @@ impl TryFrom<usize> for SyscallNumber {
 impl Task for Thread {
     /// Handles a system call request from a user program.
     fn syscall(&mut self, regs: &mut Registers) {
-        let abi = SyscallAbi::from_registers(regs); // Extract ABI from the registers.
+        let mut abi = SyscallAbi::from_registers(regs); // Extract ABI from the registers.
         // Lookup the system call handler function based on the system call number.
         let return_val = SyscallNumber::try_from(abi.sysno).and_then(|no| match no {
-            SyscallNumber::Exit => self.exit(&abi),
-            SyscallNumber::Open => self.with_file_struct_mut(|fs, abi| fs.open(abi), &abi),
-            SyscallNumber::Read => self.with_file_struct_mut(|fs, abi| fs.read(abi), &abi),
-            SyscallNumber::Write => self.with_file_struct_mut(|fs, abi| fs.write(abi), &abi),
-            SyscallNumber::Seek => self.with_file_struct_mut(|fs, abi| fs.seek(abi), &abi),
+            SyscallNumber::Exit => {
+                // Record the exit code of the process for `waitpid`.
+                self.process.set_exit_code(abi.arg1 as i32);
+                self.exit(&abi)
+            }
+            SyscallNumber::Open => {
+                self.with_file_struct_mut(|fs, abi| fs.open_cloexec(abi), &mut abi)
+            }
+            SyscallNumber::Read => {
+                self.with_file_struct_mut(|fs, abi| fs.read_accounted(abi), &abi)
+            }
+            SyscallNumber::Write => {
+                self.with_file_struct_mut(|fs, abi| fs.write_accounted(abi), &abi)
+            }
+            SyscallNumber::Seek => self.with_file_struct_mut(|fs, abi| fs.seek_sparse(abi), &abi),
             SyscallNumber::Tell => self.with_file_struct_mut(|fs, abi| fs.tell(abi), &abi),
-            SyscallNumber::Close => self.with_file_struct_mut(|fs, abi| fs.close(abi), &abi),
+            SyscallNumber::Close => {
+                self.with_file_struct_mut(|fs, abi| fs.close_unlocking(abi), &abi)
+            }
             SyscallNumber::Pipe => self.with_file_struct_mut(|fs, abi| fs.pipe(abi), &abi),
+            SyscallNumber::Pipe2 => self.with_file_struct_mut(|fs, abi| fs.pipe2(abi), &abi),
+            // Wait without holding the file struct, so that other threads can
+            // make the pipes ready.
+            SyscallNumber::Poll => self
+                .with_file_struct_mut(|fs, abi| fs.poll_targets(abi), &abi)
+                .and_then(|targets| keos_project1::poll::wait(&targets, abi.arg3)),
             SyscallNumber::Mmap => {
                 self.with_file_mm_struct_mut(|fs, mm, abi| mm.mmap(fs, abi), &abi)
             }
             SyscallNumber::Munmap => self.with_mm_struct_mut(|mm, abi| mm.munmap(abi), &abi),
-            SyscallNumber::Fork => self.with_file_mm_struct_mut(
-                |fs, mm, abi| {
-                    fork(fs, mm, abi, |file_struct, mm_struct| {
-                        with_current(|th| {
-                            let builder = ThreadBuilder::new(&th.name);
-                            let tid = builder.get_tid();
-                            builder.attach_task(Box::new(Thread::from_file_mm_struct(
-                                file_struct,
-                                mm_struct,
-                                tid,
-                            )))
+            SyscallNumber::GetRss => self.with_mm_struct_mut(|mm, _| Ok(mm.rss()), &abi),
+            SyscallNumber::ShmGet => shmget(&abi),
+            SyscallNumber::ShmAt => self.with_mm_struct_mut(shmat, &abi),
+            SyscallNumber::AtomicCas => self.with_mm_struct_mut(atomic_cas, &abi),
+            SyscallNumber::Fork => {
+                let (ppid, process) = (self.tgid, &self.process);
+                self.with_file_mm_struct_mut(
+                    |fs, mm, abi| {
+                        fork(fs, mm, abi, |file_struct, mm_struct| {
+                            with_current(|th| {
+                                let builder = ThreadBuilder::new(&th.name);
+                                let tid = builder.get_tid();
+                                let mut child =
+                                    Thread::from_file_mm_struct(file_struct, mm_struct, tid);
+                                child.ppid = ppid;
+                                process.add_child(tid, &child.process);
+                                builder.attach_task(Box::new(child))
+                            })
                         })
-                    })
-                },
-                &abi,
-            ),
+                    },
+                    &abi,
+                )
+            }
+            SyscallNumber::Spawn => {
+                let (ppid, process) = (self.tgid, &self.process);
+                self.with_file_struct_mut(
+                    |fs, abi| {
+                        spawn(fs, abi, |file_struct, mm_struct| {
+                            with_current(|th| {
+                                let builder = ThreadBuilder::new(&th.name);
+                                let tid = builder.get_tid();
+                                let mut child =
+                                    Thread::from_file_mm_struct(file_struct, mm_struct, tid);
+                                child.ppid = ppid;
+                                process.add_child(tid, &child.process);
+                                builder.attach_task(Box::new(child))
+                            })
+                        })
+                    },
+                    &abi,
+                )
+            }
             SyscallNumber::ThreadCreate => self.thread_create(&abi),
             SyscallNumber::ThreadJoin => self.thread_join(&abi),
             SyscallNumber::ExitGroup => self.exit_group(&abi),
+            SyscallNumber::GetTid => self.gettid(&abi),
+            SyscallNumber::GetPid => self.getpid(&abi),
+            SyscallNumber::GetPpid => self.getppid(&abi),
+            SyscallNumber::Waitpid => self.waitpid(&abi),
+            SyscallNumber::Sleep => self.sleep(&abi),
+            SyscallNumber::ClockGettime => self.clock_gettime(&abi),
+            SyscallNumber::SchedStats => self.sched_stats(&abi),
+            SyscallNumber::Vfork => self.vfork(&abi),
+            SyscallNumber::Execve => self.execve(&abi),
+            SyscallNumber::Fcntl => self.with_file_struct_mut(|fs, abi| fs.fcntl(abi), &abi),
+            SyscallNumber::Sendfile => self.with_file_struct_mut(|fs, abi| fs.sendfile(abi), &abi),
+            // Wait without holding the file struct, as `poll` does.
+            SyscallNumber::Flock => self
+                .with_file_struct_mut(|fs, abi| fs.flock_owner(abi), &abi)
+                .and_then(|owner| keos_project1::flock::apply(&owner, abi.arg2)),
+            SyscallNumber::IoStat => self.with_file_struct_mut(|fs, abi| fs.iostat(abi), &abi),
             SyscallNumber::GetPhys => {
                 self.with_file_mm_struct_mut(|fs, mm, abi| get_phys(mm, fs, abi), &abi)
             }
@@ -230,4 +360,10 @@ impl Task for Thread {
     fn with_page_table_pa(&self, f: &fn(Pa)) {
         f(self.page_table_pa)
     }
+
+    /// Returns the thread-group id, so that the threads of a process are
+    /// scheduled together by the gang scheduler.
+    fn thread_group(&self) -> Option<u64> {
+        Some(self.tgid)
+    }
 }
@@ -63,7 +63,11 @@
 //! invoke [`thread_join`], which blocks until the target thread exits and
 //! returns its result. This can be implemented using a [`Semaphore`]
 //! initialized with zero permits, where the exiting thread signals completion
-//! by releasing a permit.
+//! by releasing a permit. As the semaphore has no holder to donate a priority
+//! to, the joining thread lends its priority to the joined thread with
+//! [`priority::donate`] while it waits, so that a low-priority thread joined
+//! by a high-priority one is not kept off the CPU by medium-priority threads.
+//! The donation is dropped when the joined thread exits.
 //!
 //! In contrast, [`exit_group`] is used when the entire process must be
 //! terminated, bringing down all associated threads by calling
@@ -80,6 +84,23 @@
 //! thread life cycles in KeOS, balancing fine-grained control with process-wide
 //! coordination.
 //!
+//! #### `vfork`
+//!
+//! A process that creates a child only to run another program with `execve`
+//! wastes the copy-on-write setup of `fork`, as the child discards the copied
+//! address space right away. [`vfork`] skips it: the child process runs in the
+//! address space of its parent, as a thread does, while the calling thread is
+//! blocked until the child calls [`execve`] or exits. The child gets its own
+//! copy of the file descriptor table as in `fork`, so that it can redirect its
+//! standard streams before `execve` without affecting the parent.
+//!
+//! As the child also runs on the stack of the parent, it must not return from
+//! the function that called `vfork`, and should do nothing but `execve` or
+//! `exit`. Note that even calling `execve` writes a return address to the
+//! stack, where the parent's `vfork` kept its own. Therefore, the user-level
+//! `vfork` keeps the return address in a register across the system call,
+//! rather than on the stack.
+//!
 //! ## Implementation Requirements
 //! You need to implement the followings:
 //! - [`Thread`]
@@ -90,6 +111,8 @@
 //! - [`Thread::exit`]
 //! - [`Thread::thread_join`]
 //! - [`Thread::exit_group`]
+//! - [`Thread::vfork`]
+//! - [`Thread::execve`]
 //!
 //! By implementing this section, you can move on to the next [`section`] with
 //! the final form of execution model that widely used in modern OSes:
@@ -116,21 +139,57 @@
 //! [`thread_create`]: Thread::thread_create
 //! [`thread_join`]: Thread::thread_join
 //! [`exit_group`]: Thread::exit_group
+//! [`vfork`]: Thread::vfork
+//! [`execve`]: Thread::execve
 //! [`Arc`]: <https://doc.rust-lang.org/beta/alloc/sync/struct.Arc.html>
 //! [`section`]: crate::round_robin
 //! [`thread::kill_by_tid`]: keos::thread::kill_by_tid
+//! [`priority::donate`]: keos::thread::priority::donate
 //! [`Mutex`]: crate::sync::Mutex
 //! [`Semaphore`]: crate::sync::semaphore
 
+use crate::wait::ProcessHandle;
 use alloc::{boxed::Box, string::String};
-use keos::{KernelError, addressing::Pa, syscall::Registers, thread::ThreadBuilder};
+use keos::{
+    KernelError,
+    addressing::Pa,
+    mm::page_table::load_pt,
+    syscall::{Registers, uaccess::UserPtrWO},
+    thread::{self, Current, SchedStats, ThreadBuilder, priority},
+    time::{self, ClockTime},
+};
 use keos_project1::{file_struct::FileStruct, syscall::SyscallAbi};
-use keos_project2::mm_struct::MmStruct;
+use keos_project2::{loader::LoadContext, mm_struct::MmStruct, process::load_image};
 use keos_project3::lazy_pager::LazyPager;
 
+// Lends the priority of the current thread to the thread being joined, until
+// it is dropped.
+struct JoinDonation;
+
+impl JoinDonation {
+    fn to(tid: u64) -> Self {
+        priority::donate(tid, tid as usize);
+        Self
+    }
+}
+
+impl Drop for JoinDonation {
+    fn drop(&mut self) {
+        priority::withdraw();
+    }
+}
+
 /// A thread state of project 4, which contains file and memory state.
 pub struct Thread {
     pub tid: u64,
+    /// The thread-group id, which is the tid of the first thread of the
+    /// process, i.e., the process id.
+    pub tgid: u64,
+    /// The process id of the parent process that forked this process, or 0
+    /// if the process is not forked.
+    pub ppid: u64,
+    /// The state of the process shared by its threads.
+    pub process: ProcessHandle,
     pub page_table_pa: Pa,
     // TODO: Add and fix any member you need.
     pub file_struct: FileStruct,
@@ -162,6 +221,9 @@ impl Thread {
         Self {
             // TODO: Add and fix any member you need.
             tid,
+            tgid: tid,
+            ppid: 0,
+            process: ProcessHandle::new(tid),
             page_table_pa,
             mm_struct,
             file_struct,
@@ -258,7 +320,11 @@ impl Thread {
         let builder = ThreadBuilder::new(name);
         let tid = builder.get_tid();
 
-        let task: Box<Thread> = todo!();
+        let mut task: Box<Thread> = todo!();
+        // The new thread belongs to the process of the calling thread.
+        task.tgid = self.tgid;
+        task.ppid = self.ppid;
+        task.process = self.process.clone_for(tid);
 
         builder.attach_task(task).spawn(move || regs.launch());
         Ok(tid as usize)
@@ -285,7 +351,14 @@ impl Thread {
     /// - If the target thread has already exited, returns immediately with the
     ///   proper exit code.
     /// - If `retval` is non-null, the exit code of the target thread is stored.
+    /// - The calling thread donates its priority to the target thread while
+    ///   it waits, if the target is a live thread of the calling process.
     pub fn thread_join(&self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        // Donate only to a live thread of the current process, which is the
+        // only one that can be joined.
+        let tid = abi.arg1 as u64;
+        let _donation =
+            (tid != self.tid && self.process.has_thread(tid)).then(|| JoinDonation::to(tid));
         todo!()
     }
 
@@ -305,7 +378,225 @@ impl Thread {
     /// - This function does not return in normal execution, as it terminates
     ///   the process.
     /// - If an error occurs, it returns a `KernelError`
+    /// - The sibling threads are killed with
+    ///   [`ProcessHandle::kill_siblings`], which also wakes up the parked ones.
     pub fn exit_group(&self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        self.process.kill_siblings(abi.arg1 as i32);
+        todo!()
+    }
+
+    /// Create a child process sharing the address space of the calling
+    /// process, and wait until the child calls `execve` or exits.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int vfork(void);
+    /// ```
+    ///
+    /// # Behavior
+    /// - The child runs in the address space of the parent, instead of a
+    ///   copy-on-write copy of it, and on the parent's stack.
+    /// - The child gets a copy of the file struct of the parent, as in `fork`.
+    /// - The calling thread is blocked until the child releases the address
+    ///   space with [`Thread::execve`] or exits. The other threads of the
+    ///   parent keep running.
+    /// - The parent receives the child's PID, and the child receives `0`.
+    pub fn vfork(&self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        // TODO: Clone the register state and set the rax to be zero, as `fork`
+        // does.
+        let regs: Registers = todo!();
+
+        let builder = ThreadBuilder::new(thread::with_current(|th| th.name.clone()));
+        let tid = builder.get_tid();
+
+        // TODO: Create a thread sharing the address space of the calling
+        // thread as `thread_create` does, but with a copy of the file struct.
+        let mut task: Box<Thread> = todo!();
+        // The child is a new process, whose parent is the calling process.
+        task.tgid = tid;
+        task.ppid = self.tgid;
+        task.process = ProcessHandle::new(tid);
+        self.process.add_child(tid, &task.process);
+
+        builder.attach_task(task).spawn(move || regs.launch());
+        self.process.wait_child_released(tid)?;
+        Ok(tid as usize)
+    }
+
+    /// Replace the image of the calling process with a new program.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int execve(const char *pathname, char *const argv[], char *const envp[]);
+    /// ```
+    /// See `Process::execve` of project 2 for the arguments.
+    ///
+    /// # Behavior
+    /// - The program is loaded into a fresh [`MmStruct`] with [`load_image`],
+    ///   which becomes the address space of the calling thread alone. The old
+    ///   address space is not changed, as it may still be used by the parent
+    ///   blocked in [`Thread::vfork`].
+    /// - The file descriptors marked close-on-exec are closed.
+    /// - Resumes the parent blocked in [`Thread::vfork`], if any.
+    ///
+    /// # Returns
+    /// - Never returns on success.
+    /// - Returns [`KernelError::Busy`] if the process has other threads, which
+    ///   would keep running on the old address space. Call it from a
+    ///   single-threaded process, or from a child of `vfork`.
+    /// - Returns a `KernelError` if the program could not be loaded, leaving
+    ///   the calling process untouched.
+    pub fn execve(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        // Only the calling thread can create a thread in a single-threaded
+        // process, so the process stays single-threaded from here on.
+        if !self.process.is_single_threaded() {
+            return Err(KernelError::Busy);
+        }
+        let LoadContext {
+            mm_struct,
+            regs,
+            symbols,
+            ..
+        } = self.with_file_struct_mut(|fs, abi| load_image::<LazyPager>(fs, abi), abi)?;
+        keos::task::set_user_symbols(symbols);
+        // Switch to the new page table before the old one is released.
+        self.page_table_pa = mm_struct.page_table.pa();
+        load_pt(self.page_table_pa);
+        self.install_mm_struct(mm_struct);
+
+        self.with_file_struct_mut(|fs, _| fs.close_on_exec(), ());
+        self.process.release_address_space();
+        regs.launch()
+    }
+
+    /// Install `mm_struct` as the address space of this thread alone,
+    /// releasing this thread's reference to the old one.
+    ///
+    /// The old address space may still be used by the parent blocked in
+    /// [`Thread::vfork`], so it must not be changed.
+    fn install_mm_struct(&mut self, mm_struct: MmStruct<LazyPager>) {
+        // TODO: Install `mm_struct` as the address space of this thread.
         todo!()
     }
+
+    /// Get the thread id of the calling thread.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int gettid(void);
+    /// ```
+    ///
+    /// Returns the id returned by `thread_create` (or `fork`) for the calling
+    /// thread.
+    pub fn gettid(&self, _abi: &SyscallAbi) -> Result<usize, KernelError> {
+        Ok(self.tid as usize)
+    }
+
+    /// Get the process id of the calling thread.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int getpid(void);
+    /// ```
+    ///
+    /// Returns the thread-group id, which is shared by all threads of the
+    /// process and equals to the thread id of its first thread.
+    pub fn getpid(&self, _abi: &SyscallAbi) -> Result<usize, KernelError> {
+        Ok(self.tgid as usize)
+    }
+
+    /// Get the process id of the parent process.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int getppid(void);
+    /// ```
+    ///
+    /// Returns the process id of the process that forked the calling process,
+    /// or 0 if the calling process is not created by `fork`.
+    pub fn getppid(&self, _abi: &SyscallAbi) -> Result<usize, KernelError> {
+        Ok(self.ppid as usize)
+    }
+
+    /// Wait for a child process to exit.
+    ///
+    /// This function blocks the calling thread until the last thread of the
+    /// child process exits, and reaps the child. See [`wait`] for details.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int waitpid(int pid);
+    /// ```
+    /// - `pid`: Process id of the child, returned by `fork`.
+    ///
+    /// Returns the exit code of the child process.
+    ///
+    /// # Behavior
+    /// - If the child has already exited, returns immediately.
+    /// - Returns [`KernelError::NoSuchEntry`] if `pid` is not a child of the
+    ///   calling process, or is already reaped.
+    ///
+    /// [`wait`]: crate::wait
+    pub fn waitpid(&self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        self.process
+            .wait_child(abi.arg1 as u64)
+            .map(|exit_code| exit_code as usize)
+    }
+
+    /// Put the calling thread to sleep.
+    ///
+    /// The thread is parked until the timer interrupt wakes it up after the
+    /// interval, so it does not consume the CPU while sleeping. See
+    /// [`Current::sleep`] for details.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int sleep(unsigned long ticks);
+    /// ```
+    /// - `ticks`: The number of timer ticks (1ms) to sleep. `0` yields the CPU
+    ///   to another runnable thread.
+    ///
+    /// Returns 0.
+    pub fn sleep(&self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        Current::sleep(abi.arg1 as u64);
+        Ok(0)
+    }
+
+    /// Read the monotonic clock.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int clock_gettime(struct clock_time *tp);
+    /// ```
+    /// - `tp`: Buffer to store the timer ticks and the nanoseconds since boot.
+    ///   See [`ClockTime`] for the layout.
+    ///
+    /// Returns 0 if success.
+    pub fn clock_gettime(&self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        UserPtrWO::<ClockTime>::new(abi.arg1).put(time::now())?;
+        Ok(0)
+    }
+
+    /// Read the scheduling statistics of a thread.
+    ///
+    /// # Syscall API
+    /// ```c
+    /// int sched_stats(int tid, struct sched_stats *stats);
+    /// ```
+    /// - `tid`: The thread ID to read the statistics of, which must be a
+    ///   thread of the calling process.
+    /// - `stats`: Buffer to store the statistics. See [`SchedStats`] for the
+    ///   layout.
+    ///
+    /// Returns 0 if success, or [`KernelError::InvalidArgument`] if there is
+    /// no such thread in the calling process.
+    pub fn sched_stats(&self, abi: &SyscallAbi) -> Result<usize, KernelError> {
+        let tid = abi.arg1 as u64;
+        if !self.process.has_thread(tid) {
+            return Err(KernelError::InvalidArgument);
+        }
+        let stats = thread::get_sched_stats_by_tid(tid)?;
+        UserPtrWO::<SchedStats>::new(abi.arg2).put(stats)?;
+        Ok(0)
+    }
 }
@@ -85,15 +85,19 @@
 //! participate in thread execution without interference.
 //!
 //! In a round-robin policy, each runnable thread is assigned a fixed time slice
-//! (quantum) during which it can execute before being preempted. You will use
-//! the default quantum as 5 milliseconds. When a thread exhausts its time
-//! slice, it is reschdules with [`Scheduler::reschedule`]. This ensures fair
-//! CPU allocation among all threads and prevents starvation.
+//! (quantum) during which it can execute before being preempted. The quantum
+//! is [`DEFAULT_QUANTUM`] (5 milliseconds) unless the scheduler is created
+//! with [`RoundRobin::with_quantum`]. A longer quantum switches less often,
+//! trading the latency of the waiting threads for the throughput of the
+//! running ones. When a thread exhausts its time slice, it is reschdules with
+//! [`Scheduler::reschedule`]. This ensures fair CPU allocation among all
+//! threads and prevents starvation.
 //!
 //! KeOS employs a periodic timer interrupt that fires every 1 millisecond on
 //! each core. These timer interrupts invoke [`Scheduler::timer_tick`], which
-//! updates the scheduling state, decrements the current thread's time slice,
-//! and triggers a context switch if the quantum has expired.
+//! decrements the current thread's time slice with [`RoundRobin::tick`], and
+//! triggers a context switch if the quantum has expired. The time slice is
+//! refilled with the quantum whenever a thread is picked to run.
 //!
 //! A challenge in per-core scheduling arises when a core's local run queue is
 //! empty: the CPU becomes idle, even though other cores may have work queued.
@@ -103,6 +107,25 @@
 //! used efficiently and that no core remains idle while runnable threads exist
 //! elsewhere in the system.
 //!
+//! A thread preempted while holding a [`Mutex`] stalls every thread waiting
+//! for the mutex until it runs again, which is called **lock-holder
+//! preemption**. KeOS mitigates this in [`Scheduler::reschedule`]: when the
+//! timer tick reschedules a thread in a critical section, the thread keeps the
+//! CPU for an extension of up to [`CRITICAL_SECTION_EXTENSION`] ticks, and
+//! yields as soon as it leaves the critical section. The deferral is
+//! transparent to [`RoundRobin::timer_tick`], which requests the reschedule
+//! as usual.
+//!
+//! A thread woken up from parking, e.g., by a condition variable or a pipe,
+//! is usually an interactive one that runs briefly and parks again. Queued
+//! behind the CPU-bound threads, it would wait for a full round before
+//! responding. To keep such threads responsive, the kernel tags a woken
+//! thread (see [`Thread::is_boosted`]) until it runs, and
+//! [`RoundRobin::push_to_queue`] places a tagged thread at the **front** of
+//! the run queue. The woken thread then runs as soon as the time slice of the
+//! running thread expires. The boost lasts for a single time slice: when the
+//! thread is preempted, it is pushed to the back as usual.
+//!
 //! Overall, the round-robin scheduler in KeOS offers a simple yet effective
 //! baseline for multicore scheduling, balancing responsiveness, fairness, and
 //! throughput across all available cores.
@@ -111,13 +134,14 @@
 //! You need to implement the followings:
 //! - [`RoundRobin::next_to_run`]
 //! - [`RoundRobin::push_to_queue`]
-//! - [`RoundRobin::timer_tick`]
+//! - [`RoundRobin::tick`]
 //!
 //! This ends project 4.
 //!
 //! [`Scheduler::reschedule`]:../../keos/thread/scheduler/trait.Scheduler.html#method.reschedule
 //! [`RoundRobin`]: RoundRobin
 //! [`Thread::run`]: keos::thread::Thread::run
+//! [`Thread::is_boosted`]: keos::thread::Thread::is_boosted
 //! [`Box`]: https://doc.rust-lang.org/alloc/boxed/struct.Box.html
 //! [`VecDeque`]: https://doc.rust-lang.org/alloc/collections/vec_deque/struct.VecDeque.html
 //! [`STACK_SIZE`]: keos::thread::STACK_SIZE
@@ -125,6 +149,8 @@
 //! [`ThreadBuilder::spawn`]: keos::thread::ThreadBuilder::spawn
 //! [`Scheduler`]: keos::thread::scheduler::Scheduler
 //! [`Scheduler::next_to_run`]: keos::thread::scheduler::Scheduler::next_to_run
+//! [`Mutex`]: crate::sync::Mutex
+//! [`CRITICAL_SECTION_EXTENSION`]: keos::thread::scheduler::CRITICAL_SECTION_EXTENSION
 
 use alloc::{boxed::Box, collections::VecDeque};
 use keos::{
@@ -132,9 +158,15 @@ use keos::{
     intrinsics::cpuid,
     sync::SpinLock,
     sync::atomic::AtomicIsize,
-    thread::{Thread, scheduler::Scheduler},
+    thread::{
+        Thread,
+        scheduler::{Scheduler, scheduler},
+    },
 };
 
+/// The default time slice of a thread in timer ticks (1ms).
+pub const DEFAULT_QUANTUM: u64 = 5;
+
 /// Per-core scheduler state.
 ///
 /// The [`PerCore`] struct represents the per-core scheduling state in a
@@ -169,6 +201,7 @@ pub struct PerCore {
 /// time among processes.
 pub struct RoundRobin {
     percores: [PerCore; MAX_CPU],
+    quantum: u64,
 }
 unsafe impl Send for RoundRobin {}
 unsafe impl Sync for RoundRobin {}
@@ -180,29 +213,63 @@ impl Default for RoundRobin {
 }
 
 impl RoundRobin {
-    /// Create a new [`RoundRobin`] scheduler.
+    /// Create a new [`RoundRobin`] scheduler with the [`DEFAULT_QUANTUM`].
     pub fn new() -> Self {
+        Self::with_quantum(DEFAULT_QUANTUM)
+    }
+
+    /// Create a new [`RoundRobin`] scheduler whose time slice is `ticks`
+    /// timer ticks.
+    ///
+    /// # Panics
+    /// Panics if `ticks` is zero.
+    pub fn with_quantum(ticks: u64) -> Self {
+        assert!(ticks > 0, "The quantum must be at least a tick.");
         Self {
             percores: [0; MAX_CPU].map(|_| PerCore {
                 run_queue: SpinLock::new(VecDeque::new()),
                 remain: AtomicIsize::new(0),
             }),
+            quantum: ticks,
         }
     }
+
+    /// Returns the time slice of a thread in timer ticks.
+    pub fn quantum(&self) -> u64 {
+        self.quantum
+    }
+
+    /// Accounts a timer tick to the thread running on the current CPU.
+    ///
+    /// This decrements the remaining time slice (`remain`) of the current CPU,
+    /// and returns `true` if the time slice is exhausted, i.e., the running
+    /// thread should be rescheduled. The time slice must be refilled with
+    /// [`RoundRobin::quantum`] when [`RoundRobin::next_to_run`] picks a thread
+    /// on the CPU.
+    ///
+    /// This never reschedules by itself, so that the accounting can be
+    /// inspected without switching threads.
+    pub fn tick(&self) -> bool {
+        let coreid = cpuid();
+        todo!()
+    }
 }
 
 impl Scheduler for RoundRobin {
     fn next_to_run(&self) -> Option<Box<Thread>> {
+        // Hint: refill the time slice of the current CPU with the quantum when
+        // you pick a thread to run. See [`RoundRobin::tick`].
         todo!()
     }
     fn push_to_queue(&self, thread: Box<Thread>) {
+        // Hint: push a thread woken up from parking to the front of the queue.
+        // See [`Thread::is_boosted`].
         let coreid = cpuid();
         todo!()
     }
     fn timer_tick(&self) {
-        // Hint: you can yield the current thread by calling
-        // [`keos::thread::scheduler::Scheduler::reschedule`]
-        let coreid = cpuid();
-        todo!()
+        if self.tick() {
+            scheduler().reschedule();
+        }
     }
 }
@@ -0,0 +1,166 @@
+//! # Spawning a process.
+//!
+//! A shell runs a program by `fork`ing itself and calling `execve` in the
+//! child, after redirecting the standard streams of the child (e.g., onto the
+//! ends of a pipe for a pipeline). The copy-on-write setup of `fork` is
+//! wasted in this pattern, as the child discards the copied address space
+//! right away. `spawn` does both at once: it creates a child process that
+//! runs the given program in a fresh address space, with the file descriptor
+//! table of the parent rearranged by a list of [`SpawnAction`]s.
+//!
+//! The child is created atomically: the actions are applied to a copy of the
+//! file descriptor table of the parent, and the program is loaded from the
+//! working directory of the copy. The child starts running only if all of
+//! them succeed; Otherwise, no process is created and the parent receives
+//! the error. The file descriptors marked close-on-exec are closed in the
+//! child after the actions, as `execve` does.
+//!
+//! The child is a regular child process of the caller, which is waited for
+//! with `waitpid`.
+
+use alloc::vec::Vec;
+use keos::{KernelError, syscall::uaccess::UserPtrRO, thread::ThreadBuilder};
+use keos_project1::{
+    file_struct::{FileDescriptor, FileStruct},
+    syscall::SyscallAbi,
+};
+use keos_project2::{loader::LoadContext, mm_struct::MmStruct, process::load_image};
+use keos_project3::lazy_pager::LazyPager;
+
+/// The maximum number of [`SpawnAction`]s accepted by `spawn`.
+const SPAWN_MAX_ACTIONS: usize = 64;
+
+/// Duplicates `fd` onto `newfd`, closing `newfd` first if it is open.
+pub const SPAWN_DUP2: i32 = 0;
+/// Closes `fd`.
+pub const SPAWN_CLOSE: i32 = 1;
+
+/// An action on the file descriptor table of the child of `spawn`.
+///
+/// ```c
+/// struct spawn_action {
+///   int action;
+///   int fd;
+///   int newfd;
+/// };
+/// ```
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SpawnAction {
+    /// The kind of the action, either [`SPAWN_DUP2`] or [`SPAWN_CLOSE`].
+    pub action: i32,
+    /// The file descriptor to duplicate or close.
+    pub fd: i32,
+    /// The file descriptor to duplicate onto. Ignored by [`SPAWN_CLOSE`].
+    pub newfd: i32,
+}
+
+impl SpawnAction {
+    /// Applies the action on `file_struct`.
+    ///
+    /// The actions work on the file descriptor table directly, as
+    /// [`FileStruct::close_on_exec`] does, so that the copy of `dup2` shares
+    /// the open file and its advisory lock with `fd`.
+    fn apply(&self, file_struct: &mut FileStruct) -> Result<(), KernelError> {
+        let (fd, newfd) = (FileDescriptor(self.fd), FileDescriptor(self.newfd));
+        match self.action {
+            SPAWN_DUP2 => {
+                let file = file_struct
+                    .files
+                    .get(&fd)
+                    .cloned()
+                    .ok_or(KernelError::BadFileDescriptor)?;
+                if newfd.0 < 0 {
+                    return Err(KernelError::BadFileDescriptor);
+                }
+                if fd == newfd {
+                    // As `dup2`, the file descriptor is left as is, except for
+                    // the close-on-exec mark which the duplicate never has.
+                    file_struct.cloexec.remove(&newfd);
+                    return Ok(());
+                }
+                file_struct.files.insert(newfd, file);
+                match file_struct.flocks.get(&fd).cloned() {
+                    Some(owner) => file_struct.flocks.insert(newfd, owner),
+                    None => file_struct.flocks.remove(&newfd),
+                };
+                file_struct.cloexec.remove(&newfd);
+            }
+            SPAWN_CLOSE => {
+                file_struct
+                    .files
+                    .remove(&fd)
+                    .ok_or(KernelError::BadFileDescriptor)?;
+                file_struct.flocks.remove(&fd);
+                file_struct.cloexec.remove(&fd);
+            }
+            _ => return Err(KernelError::InvalidArgument),
+        }
+        Ok(())
+    }
+}
+
+/// Read the `len` [`SpawnAction`]s at `addr`.
+fn read_actions(addr: usize, len: usize) -> Result<Vec<SpawnAction>, KernelError> {
+    if len > SPAWN_MAX_ACTIONS {
+        return Err(KernelError::InvalidArgument);
+    }
+    (0..len)
+        .map(|i| {
+            UserPtrRO::<SpawnAction>::new(addr + i * core::mem::size_of::<SpawnAction>()).get()
+        })
+        .collect()
+}
+
+/// Create a child process running a new program.
+///
+/// # Syscall API
+/// ```c
+/// int spawn(const char *pathname, char *const argv[], char *const envp[],
+///           const struct spawn_action *actions, int nactions);
+/// ```
+/// - `pathname`, `argv`, `envp`: The program to run and its arguments, as in
+///   `execve`.
+/// - `actions`: The array of [`SpawnAction`]s, applied in order to the copy
+///   of the file descriptor table for the child.
+/// - `nactions`: The number of the actions. `actions` may be NULL if it is 0.
+///
+/// ### Parameters
+/// - `file_struct`: The parent's file descriptor table to be copied.
+/// - `abi`: The arguments of the system call.
+/// - `create_task`: A closure for creating the new process, as in `fork`.
+///
+/// ### Returns
+/// - `Ok(pid)`: The parent receives the child process ID.
+/// - [`KernelError::BadFileDescriptor`] if an action refers to a file
+///   descriptor that is not open.
+/// - [`KernelError::InvalidArgument`] if an action is unknown, or there are
+///   too many actions.
+/// - Any error of loading the program, as in `execve`.
+///
+/// No process is created on error.
+pub fn spawn(
+    file_struct: &mut FileStruct,
+    abi: &SyscallAbi,
+    create_task: impl FnOnce(FileStruct, MmStruct<LazyPager>) -> ThreadBuilder,
+) -> Result<usize, KernelError> {
+    let mut file_struct = file_struct.clone();
+    for action in read_actions(abi.arg4, abi.arg5)? {
+        action.apply(&mut file_struct)?;
+    }
+    let LoadContext {
+        mm_struct,
+        regs,
+        symbols,
+        ..
+    } = load_image::<LazyPager>(&file_struct, abi)?;
+    file_struct.close_on_exec();
+
+    // The symbols belong to the child only; the parent keeps running its own
+    // program.
+    let handle = create_task(file_struct, mm_struct).spawn(move || {
+        keos::task::set_user_symbols(symbols);
+        regs.launch()
+    });
+    Ok(handle.tid as usize)
+}
@@ -29,6 +29,19 @@
 //! - [`ConditionVariable::signal`] wakes **one** waiting thread and
 //! - [`ConditionVariable::broadcast`] wakes **all** waiting threads.
 //!
+//! They are also known as `notify_one` and `notify_all`. [`signal`] must wake
+//! up exactly one thread: it takes only the head of the wait queue, and the
+//! other threads stay parked. Waking up every thread on each signal is
+//! correct, but wasteful: all of them contend for the mutex only to find the
+//! predicate still true and sleep again, which is known as the **thundering
+//! herd** problem. Use [`broadcast`] only when the change may let more than
+//! one waiter proceed.
+//!
+//! A woken thread is not guaranteed that the predicate is false: another
+//! thread may take the mutex first and change the state again, or the thread
+//! may be woken up for other reasons. This is why [`wait_while`] re-checks the
+//! predicate after reacquiring the mutex, and parks again if it is still true.
+//!
 //! ## Implementation Requirements
 //! You need to implement the followings:
 //! - [`ConditionVariable::wait_while`]
@@ -38,6 +51,9 @@
 //! After implement the functionalities, move on to the next [`section`].
 //!
 //! [`Mutex`]: crate::sync::Mutex
+//! [`signal`]: ConditionVariable::signal
+//! [`broadcast`]: ConditionVariable::broadcast
+//! [`wait_while`]: ConditionVariable::wait_while
 //! [`section`]: crate::sync::semaphore
 
 use super::mutex::{Mutex, MutexGuard};
@@ -94,8 +110,9 @@ impl ConditionVariable {
 
     /// Wakes up one blocked thread on this condvar.
     ///
-    /// If there is a blocked thread on this condition variable, then it will
-    /// be woken up from its call to [`wait_while`]. Calls to `signal` are not
+    /// If there is a blocked thread on this condition variable, then the one
+    /// at the head of the wait queue will be woken up from its call to
+    /// [`wait_while`], and the others remain blocked. Calls to `signal` are not
     /// buffered in any way.
     ///
     /// To wake up all threads, see [`broadcast`].
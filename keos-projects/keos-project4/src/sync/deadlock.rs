@@ -0,0 +1,159 @@
+//! # Deadlock detection.
+//!
+//! A **deadlock** occurs when threads wait for each other in a cycle: for
+//! example, a thread holding a lock `A` waits for a lock `B`, while another
+//! thread holding `B` waits for `A` (the *AB-BA* deadlock). None of them can
+//! proceed, and the threads sleep forever.
+//!
+//! For debugging, [`Mutex`] can detect such a cycle when a thread is about to
+//! block. The detector maintains a global **wait-for graph**, which has two
+//! kinds of edges:
+//!
+//! - A thread waiting on a mutex points to the mutex.
+//! - A held mutex points to its owner thread.
+//!
+//! A thread that is about to wait on a mutex follows the edges from the mutex.
+//! If it comes back to the thread itself, waiting on the mutex never ends,
+//! so the detector panics with the threads and the mutexes in the cycle.
+//!
+//! A thread killed while waiting on a mutex never acquires it nor gives up.
+//! Its edge is removed on its exit path instead, with the hook registered by
+//! [`register_robust_lock`] for the duration of the wait.
+//!
+//! The detection is disabled by default, as maintaining the graph slows down
+//! every lock operation. It is enabled with the `deadlock_detection` feature of
+//! KeOS, or with [`SystemConfigurationBuilder::set_deadlock_detection`].
+//!
+//! [`Mutex`]: crate::sync::Mutex
+//! [`SystemConfigurationBuilder::set_deadlock_detection`]: keos::SystemConfigurationBuilder::set_deadlock_detection
+//! [`register_robust_lock`]: keos::thread::register_robust_lock
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use keos::{sync::SpinLock, thread::Current};
+
+struct WaitForGraph {
+    // The owner thread of each held mutex, keyed by the address of the mutex.
+    owners: BTreeMap<usize, u64>,
+    // The mutex that each thread waits on.
+    waiting: BTreeMap<u64, usize>,
+}
+
+static GRAPH: SpinLock<WaitForGraph> = SpinLock::new(WaitForGraph {
+    owners: BTreeMap::new(),
+    waiting: BTreeMap::new(),
+});
+
+impl WaitForGraph {
+    // Follows the edges from the mutex `lock` that the thread `tid` is about to
+    // wait on, and returns the cycle back to `tid` if any.
+    fn find_cycle(&self, tid: u64, lock: usize) -> Option<Vec<(u64, usize)>> {
+        let mut cycle = Vec::from([(tid, lock)]);
+        let mut lock = lock;
+        while let Some(&owner) = self.owners.get(&lock) {
+            // A mutex whose owner exited (e.g., killed while holding it) can
+            // not be a part of a cycle.
+            if keos::thread::get_state_by_tid(owner).is_err() {
+                return None;
+            }
+            if owner == tid {
+                return Some(cycle);
+            }
+            // A cycle that does not involve `tid` is detected by one of its
+            // threads. Stop here not to loop forever.
+            if cycle.iter().any(|(t, _)| *t == owner) {
+                return None;
+            }
+            lock = *self.waiting.get(&owner)?;
+            cycle.push((owner, lock));
+        }
+        None
+    }
+}
+
+/// Returns the cycle of lock-wait dependencies that is closed if the current
+/// thread waits on the mutex at the address `lock`.
+///
+/// Each element of the cycle is a pair of a thread and the mutex that the
+/// thread waits on, starting from the current thread. The mutex of an element
+/// is held by the thread of the next element, and the mutex of the last one is
+/// held by the current thread.
+pub fn find_cycle(lock: usize) -> Option<Vec<(u64, usize)>> {
+    let tid = Current::get_tid();
+    let guard = GRAPH.lock();
+    let cycle = guard.find_cycle(tid, lock);
+    guard.unlock();
+    cycle
+}
+
+/// Records that the current thread is about to wait on the mutex at the
+/// address `lock`.
+///
+/// # Panics
+/// Panics if the wait closes a cycle of lock-wait dependencies.
+pub fn wait_for(lock: usize) {
+    if !keos::sync::deadlock_detection() {
+        return;
+    }
+    let tid = Current::get_tid();
+    let mut guard = GRAPH.lock();
+    let cycle = guard.find_cycle(tid, lock);
+    if cycle.is_none() {
+        guard.waiting.insert(tid, lock);
+    }
+    guard.unlock();
+    if cycle.is_none() {
+        keos::thread::register_robust_lock(lock, forget_waiter);
+    }
+    if let Some(cycle) = cycle {
+        let mut msg = alloc::string::String::new();
+        for (tid, lock) in cycle {
+            msg += &alloc::format!("\n  Thread {tid} waits on Mutex@{lock:#x}");
+        }
+        panic!("Deadlock detected:{msg}");
+    }
+}
+
+/// Records that the current thread acquired the mutex at the address `lock`.
+pub fn acquired(lock: usize) {
+    if !keos::sync::deadlock_detection() {
+        return;
+    }
+    let tid = Current::get_tid();
+    let mut guard = GRAPH.lock();
+    guard.waiting.remove(&tid);
+    guard.owners.insert(lock, tid);
+    guard.unlock();
+    keos::thread::unregister_robust_lock(lock);
+}
+
+/// Records that the current thread stopped waiting on the mutex at the
+/// address `lock` without acquiring it, e.g., on a timeout.
+pub fn gave_up(lock: usize) {
+    if !keos::sync::deadlock_detection() {
+        return;
+    }
+    forget_waiter(lock, Current::get_tid());
+    keos::thread::unregister_robust_lock(lock);
+}
+
+// Removes the edge from the thread `tid` to the mutex at the address `lock`,
+// if the thread still waits on it. Also called on the exit path of a thread
+// killed while waiting.
+fn forget_waiter(lock: usize, tid: u64) {
+    let mut guard = GRAPH.lock();
+    if guard.waiting.get(&tid) == Some(&lock) {
+        guard.waiting.remove(&tid);
+    }
+    guard.unlock();
+}
+
+/// Records that the current thread is about to release the mutex at the
+/// address `lock`.
+pub fn released(lock: usize) {
+    if !keos::sync::deadlock_detection() {
+        return;
+    }
+    let mut guard = GRAPH.lock();
+    guard.owners.remove(&lock);
+    guard.unlock();
+}
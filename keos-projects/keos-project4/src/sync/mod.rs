@@ -48,7 +48,7 @@
 //! | [`SpinLock`]          | No (busy wait) | No       | Short, uncontended critical sections in the kernel |
 //! | [`Mutex`]             | Yes            | Yes      | Exclusive access to shared data                 |
 //! | [`ConditionVariable`] | Yes            | Yes      | Waiting for a condition to become true          |
-//! | [`Semaphore`]         | Yes            | Depends  | Limiting access to a bounded resource            |
+//! | [`Semaphore`]         | Yes            | Yes      | Limiting access to a bounded resource            |
 //!
 //! - **SpinLock** spins in a loop until the lock becomes available. This is
 //!   suitable for extremely short operations in low-contention paths.
@@ -60,13 +60,21 @@
 //! - **Semaphore** tracks a count of available permits and is often used to
 //!   control access to a pool of resources or to implement thread joins. It can
 //!   be used when multiple threads can proceed concurrently, up to a fixed
-//!   limit.
+//!   limit. The permits are granted in the order the threads started
+//!   waiting.
 //!
 //! ## Implementation Orders
 //! 1. [`mutex`]
 //! 2. [`condition_variable`]
 //! 3. [`semaphore`]
 //!
+//! On top of them, [`Once`] runs a one-time initialization, blocking the
+//! concurrent callers until it finishes, and [`Lazy`] initializes a value on
+//! its first access.
+//!
+//! [`Mutex`] can optionally detect deadlocks among threads, which is helpful
+//! for debugging. See [`deadlock`] for details.
+//!
 //! [`mutex`]: self::mutex
 //! [`condition_variable`]: self::condition_variable
 //! [`semaphore`]: self::semaphore
@@ -74,11 +82,16 @@
 //! [`Mutex`]: crate::sync::mutex::Mutex
 //! [`ConditionVariable`]: crate::sync::condition_variable::ConditionVariable
 //! [`Semaphore`]: crate::sync::semaphore::Semaphore
+//! [`Once`]: crate::sync::once::Once
+//! [`Lazy`]: crate::sync::once::Lazy
 
 pub mod condition_variable;
+pub mod deadlock;
 pub mod mutex;
+pub mod once;
 pub mod semaphore;
 
 pub use condition_variable::*;
 pub use mutex::*;
+pub use once::*;
 pub use semaphore::*;
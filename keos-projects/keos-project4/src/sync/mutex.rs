@@ -29,6 +29,58 @@
 //! sections or when a lock may be held for a non-trivial amount of time, as
 //! sleeping threads do not waste CPU.
 //!
+//! ## Reentrant Mutex
+//!
+//! Locking a [`Mutex`] again in the thread that already holds it deadlocks.
+//! Some kernel paths, however, want to re-acquire a lock they already hold,
+//! e.g., a function that locks a resource and calls another function that
+//! locks it again. [`ReentrantMutex`] allows this by recording the owner
+//! thread and the recursion count of the lock: the owner can lock it
+//! repeatedly, and the lock is released only when the outermost guard is
+//! unlocked. Other threads still block until then. As multiple guards of
+//! a [`ReentrantMutex`] can exist at once, they only provide shared access to
+//! the data.
+//!
+//! ## Robust Mutex
+//!
+//! A thread can exit while holding a mutex, e.g., when it is killed with
+//! [`kill_by_tid`] or by a fault in the critical section. Its guard is never
+//! unlocked, so the mutex is held forever and the waiters sleep forever.
+//! [`RobustMutex`] registers itself to the kernel with
+//! [`register_robust_lock`] while it is held. When the holder exits, the
+//! kernel releases the mutex on its exit path and marks it as released by a
+//! dead owner. The next [`RobustMutex::lock`] acquires the mutex but returns
+//! [`OwnerDied`], as the data may be left inconsistent by the interrupted
+//! critical section.
+//!
+//! ## Lock-Holder Preemption
+//!
+//! When the thread holding a mutex is preempted, the threads waiting for the
+//! mutex cannot proceed until the holder is scheduled again. To mitigate
+//! this, [`Mutex::lock`] marks the holder as being in a critical section with
+//! [`Current::enter_critical_section`], and [`MutexGuard::unlock`] clears the
+//! mark after releasing the mutex. The kernel then defers the preemption of
+//! the holder by a bounded extension of its time slice, so that a short
+//! critical section is completed before it is preempted. Like the hooks of
+//! the [`deadlock`](super::deadlock) detection, the guards of
+//! [`Mutex::try_lock`] are not marked, so the guard records whether its
+//! holder entered the critical section, and [`MutexGuard::unlock`] only
+//! leaves the critical sections that were entered.
+//!
+//! ## Priority Donation
+//!
+//! Under a priority scheduler, a high-priority thread waiting for a mutex
+//! held by a low-priority thread also waits for every medium-priority thread
+//! that keeps the holder off the CPU. To bound this priority inversion, the
+//! [`Mutex`] records the thread that acquired it with [`Mutex::lock`], and a
+//! thread about to block on the mutex donates its priority to that thread
+//! with [`priority::donate`]. [`MutexGuard::unlock`] drops the donations made
+//! for the mutex with [`priority::revoke`] after releasing it, which returns
+//! the holder to its own priority. The waiters take back their donations
+//! with [`priority::withdraw`] when they wake up. Like the critical sections,
+//! the holders of the guards of [`Mutex::try_lock`] are not recorded, so
+//! they receive no donation.
+//!
 //! ## Implementation Requirements
 //! You need to implement the followings:
 //! - [`Mutex`]
@@ -40,17 +92,30 @@
 //!
 //! [`section`]: crate::sync::condition_variable
 //! [`Current::park_with`]: keos::thread::Current::park_with
+//! [`Current::enter_critical_section`]: keos::thread::Current::enter_critical_section
+//! [`kill_by_tid`]: keos::thread::kill_by_tid
+//! [`register_robust_lock`]: keos::thread::register_robust_lock
+//! [`priority::donate`]: keos::thread::priority::donate
+//! [`priority::revoke`]: keos::thread::priority::revoke
+//! [`priority::withdraw`]: keos::thread::priority::withdraw
 
+use super::deadlock;
 use alloc::collections::vec_deque::VecDeque;
 use core::{
     cell::UnsafeCell,
     ops::{Deref, DerefMut},
 };
 use keos::{
-    sync::{SpinLock, WouldBlock, atomic::AtomicBool},
-    thread::{Current, ParkHandle},
+    sync::{
+        SpinLock, SpinLockGuard, WouldBlock,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize},
+    },
+    thread::{Current, ParkHandle, priority},
 };
 
+// The `owner` of a mutex that is not recorded to be held.
+const NO_OWNER: u64 = u64::MAX;
+
 /// A mutual exclusion primitive useful for protecting shared data
 ///
 /// This mutex will block threads waiting for the lock to become available.
@@ -101,6 +166,13 @@ pub struct Mutex<T> {
     // TODO: Define any member you need.
     t: UnsafeCell<T>,
     waiters: SpinLock<VecDeque<ParkHandle>>,
+    // The number of the calls to `MutexGuard::unlock`, which lets
+    // `lock_timeout` notice a release without calling `try_lock`.
+    unlocks: AtomicUsize,
+    // The tid of the thread holding the mutex through `lock` or
+    // `lock_timeout`, or `NO_OWNER`. It is updated while holding `waiters`,
+    // and receives the priority donations of the waiters.
+    owner: AtomicU64,
 }
 
 unsafe impl<T: Send> Send for Mutex<T> {}
@@ -122,6 +194,8 @@ impl<T> Mutex<T> {
             // TODO: Initialize the members you added.
             t: UnsafeCell::new(t),
             waiters: SpinLock::new(VecDeque::new()),
+            unlocks: AtomicUsize::new(0),
+            owner: AtomicU64::new(NO_OWNER),
         }
     }
 }
@@ -156,7 +230,60 @@ impl<T> Mutex<T> {
     /// assert_eq!(*mutex.lock().unwrap(), 10);
     /// ```
     pub fn lock(&self) -> MutexGuard<'_, T> {
-        todo!()
+        // Keep the hooks for the deadlock detection around your
+        // implementation. See [`deadlock`](super::deadlock) for details.
+        deadlock::wait_for(self as *const _ as usize);
+        // Hint: block with `self.park(waiters, None)`, which donates the
+        // priority of the current thread to the holder of the mutex.
+        let mut guard: MutexGuard<'_, T> = todo!();
+        deadlock::acquired(self as *const _ as usize);
+        self.set_owner(Current::get_tid());
+        Current::enter_critical_section();
+        guard.critical = true;
+        guard
+    }
+
+    // Records `tid` as the holder of the mutex.
+    fn set_owner(&self, tid: u64) {
+        let waiters = self.waiters.lock();
+        self.owner.store(tid);
+        waiters.unlock();
+    }
+
+    // Parks the current thread in the wait queue of the mutex, which is held
+    // as `waiters`, until it is unparked or the `deadline` passes.
+    //
+    // The current thread donates its priority to the holder of the mutex
+    // while it is parked. On the deadline, the handle of the thread is
+    // removed from the wait queue.
+    fn park(&self, mut waiters: SpinLockGuard<'_, VecDeque<ParkHandle>>, deadline: Option<u64>) {
+        let owner = self.owner.load();
+        if owner != NO_OWNER {
+            priority::donate(owner, self as *const _ as usize);
+        }
+        let woken = match deadline {
+            Some(deadline) => Current::park_with_deadline(deadline, |th| {
+                waiters.push_back(th);
+                waiters.unlock();
+            }),
+            None => {
+                Current::park_with(|th| {
+                    waiters.push_back(th);
+                    waiters.unlock();
+                });
+                true
+            }
+        };
+        priority::withdraw();
+        if !woken {
+            // Remove the stale handle. If it is already taken by the unlock,
+            // the mutex is released for this thread and the following
+            // `try_lock` acquires it.
+            let tid = Current::get_tid();
+            let mut waiters = self.waiters.lock();
+            waiters.retain(|th| th.tid() != tid);
+            waiters.unlock();
+        }
     }
     /// Attempts to acquire this lock.
     ///
@@ -196,9 +323,72 @@ impl<T> Mutex<T> {
         Ok(MutexGuard {
             t: unsafe { &mut *self.t.get() },
             lock: self,
+            critical: false,
         })
     }
 
+    /// Acquires a mutex, blocking the current thread until it is able to do
+    /// so or `ticks` timer ticks (1ms) elapse.
+    ///
+    /// Returns [`None`] if the mutex could not be acquired until the deadline.
+    ///
+    /// The wait queue of the mutex serves as a condition variable whose
+    /// predicate is [`Mutex::try_lock`] failing. The thread waits in the same
+    /// queue with the threads blocked in [`Mutex::lock`], and is woken up
+    /// either by [`MutexGuard::unlock`] or by the timer on the deadline. On
+    /// the timeout, the waiter is removed from the queue, so that a later
+    /// [`MutexGuard::unlock`] does not try to wake it up. This requires the
+    /// unlock to release the mutex before waking a waiter, which then
+    /// competes for the mutex with [`Mutex::try_lock`].
+    ///
+    /// [`Mutex::try_lock`] is never called while holding the wait queue.
+    /// Instead, [`MutexGuard::unlock`] counts the calls to it before
+    /// releasing the mutex, and the thread parks only if no unlock started
+    /// since its failed [`Mutex::try_lock`]. An unlock that started earlier
+    /// either let the [`Mutex::try_lock`] succeed, or finds the thread in the
+    /// queue when it wakes a waiter.
+    ///
+    /// Like [`Mutex::lock`], the wait is recorded for the
+    /// [`deadlock`](super::deadlock) detection, and the thread donates its
+    /// priority to the holder while it waits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use keos_project4::sync::Mutex;
+    ///
+    /// let mutex = Mutex::new(0);
+    /// let guard = mutex.lock();
+    /// // Fails after 10ms, as the mutex is held.
+    /// assert!(mutex.lock_timeout(10).is_none());
+    /// guard.unlock();
+    /// ```
+    pub fn lock_timeout(&self, ticks: u64) -> Option<MutexGuard<'_, T>> {
+        let deadline = keos::time::now_ticks().saturating_add(ticks);
+        deadlock::wait_for(self as *const _ as usize);
+        loop {
+            let unlocks = self.unlocks.load();
+            if let Ok(mut guard) = self.try_lock() {
+                deadlock::acquired(self as *const _ as usize);
+                self.set_owner(Current::get_tid());
+                Current::enter_critical_section();
+                guard.critical = true;
+                return Some(guard);
+            }
+            if deadline <= keos::time::now_ticks() {
+                deadlock::gave_up(self as *const _ as usize);
+                return None;
+            }
+            let waiters = self.waiters.lock();
+            if self.unlocks.load() != unlocks {
+                // The mutex may be released after the check; check it again.
+                waiters.unlock();
+                continue;
+            }
+            self.park(waiters, Some(deadline));
+        }
+    }
+
     /// Consumes this mutex, returning the underlying data.
     ///
     /// # Examples
@@ -255,6 +445,10 @@ pub struct MutexGuard<'a, T: 'a> {
     // Define any member you need.
     t: &'a mut T,
     lock: &'a Mutex<T>,
+    // Whether the holder entered the critical section on the acquisition,
+    // which is not the case for the guards of `Mutex::try_lock`. Initialize
+    // it to `false`.
+    critical: bool,
 }
 
 impl<T> !Send for MutexGuard<'_, T> {}
@@ -279,6 +473,14 @@ impl<T> MutexGuard<'_, T> {
     /// ```
     /// [`unlock`]: MutexGuard::unlock
     pub fn unlock(mut self) {
+        deadlock::released(self.lock as *const _ as usize);
+        // Count the unlock before releasing the mutex (see
+        // `Mutex::lock_timeout`).
+        self.lock.unlocks.fetch_add(1);
+        self.lock.set_owner(NO_OWNER);
+        // Drop the donations and leave the critical section after the mutex
+        // is released.
+        let _critical = CriticalSection(self.lock as *const _ as usize, self.critical);
         todo!()
     }
 }
@@ -288,3 +490,368 @@ impl<T> Drop for MutexGuard<'_, T> {
         panic!("`.unlock()` must be explicitly called for MutexGuard.");
     }
 }
+
+// Drops the priority donations made for the mutex at the address, and leaves
+// its critical section on drop if it was entered.
+struct CriticalSection(usize, bool);
+
+impl Drop for CriticalSection {
+    fn drop(&mut self) {
+        priority::revoke(self.0);
+        if self.1 {
+            Current::exit_critical_section();
+        }
+    }
+}
+
+struct ReentrantState {
+    // The tid of the thread holding the lock.
+    owner: Option<u64>,
+    // The number of guards held by the owner.
+    count: usize,
+    waiters: VecDeque<ParkHandle>,
+}
+
+/// A mutex that can be locked multiple times by the thread holding it.
+///
+/// See the [module-level documentation](self) for details.
+///
+/// # Examples
+///
+/// ```
+/// use keos_project4::sync::ReentrantMutex;
+///
+/// let mutex = ReentrantMutex::new(0);
+/// let outer = mutex.lock();
+/// // Locking again in the same thread does not block.
+/// let inner = mutex.lock();
+/// inner.unlock();
+/// // The lock is released here.
+/// outer.unlock();
+/// ```
+pub struct ReentrantMutex<T> {
+    t: T,
+    state: SpinLock<ReentrantState>,
+}
+
+unsafe impl<T: Send> Send for ReentrantMutex<T> {}
+unsafe impl<T: Send> Sync for ReentrantMutex<T> {}
+
+impl<T> ReentrantMutex<T> {
+    /// Creates a new reentrant mutex in an unlocked state ready for use.
+    #[inline]
+    pub const fn new(t: T) -> ReentrantMutex<T> {
+        ReentrantMutex {
+            t,
+            state: SpinLock::new(ReentrantState {
+                owner: None,
+                count: 0,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    ///
+    /// If the current thread already holds the mutex, this returns a new
+    /// guard immediately.
+    pub fn lock(&self) -> ReentrantMutexGuard<'_, T> {
+        let tid = Current::get_tid();
+        loop {
+            let mut guard = self.state.lock();
+            match guard.owner {
+                Some(owner) if owner != tid => {
+                    Current::park_with(|th| {
+                        guard.waiters.push_back(th);
+                        guard.unlock();
+                    });
+                }
+                _ => {
+                    guard.owner = Some(tid);
+                    guard.count += 1;
+                    guard.unlock();
+                    return ReentrantMutexGuard { lock: self };
+                }
+            }
+        }
+    }
+
+    /// Attempts to acquire the mutex.
+    ///
+    /// This function does not block.
+    ///
+    /// # Errors
+    ///
+    /// If the mutex is held by another thread, then this call will return
+    /// the [`WouldBlock`] error.
+    pub fn try_lock(&self) -> Result<ReentrantMutexGuard<'_, T>, WouldBlock> {
+        let tid = Current::get_tid();
+        let mut guard = self.state.lock();
+        let result = match guard.owner {
+            Some(owner) if owner != tid => Err(WouldBlock),
+            _ => {
+                guard.owner = Some(tid);
+                guard.count += 1;
+                Ok(ReentrantMutexGuard { lock: self })
+            }
+        };
+        guard.unlock();
+        result
+    }
+
+    /// Consumes this mutex, returning the underlying data.
+    pub fn into_inner(self) -> T {
+        self.t
+    }
+}
+
+/// An implementation of a "scoped lock" of a reentrant mutex. When this
+/// structure is dropped (falls out of scope) without unlocking, the panic
+/// occurs.
+///
+/// The lock must be explicitly unlocked by [`unlock`] method. The data
+/// protected by the mutex can be read through this guard.
+///
+/// This structure is created by the [`lock`] and [`try_lock`] methods on
+/// [`ReentrantMutex`].
+///
+/// [`lock`]: ReentrantMutex::lock
+/// [`try_lock`]: ReentrantMutex::try_lock
+/// [`unlock`]: ReentrantMutexGuard::unlock
+pub struct ReentrantMutexGuard<'a, T: 'a> {
+    lock: &'a ReentrantMutex<T>,
+}
+
+impl<T> !Send for ReentrantMutexGuard<'_, T> {}
+unsafe impl<T: Sync> Sync for ReentrantMutexGuard<'_, T> {}
+
+impl<T> Deref for ReentrantMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.lock.t
+    }
+}
+
+impl<T> ReentrantMutexGuard<'_, T> {
+    /// Releases the guard.
+    ///
+    /// The underlying lock is released when the outermost guard is unlocked,
+    /// waking up one of the threads waiting for it.
+    pub fn unlock(self) {
+        let mut guard = self.lock.state.lock();
+        guard.count -= 1;
+        let waiter = if guard.count == 0 {
+            guard.owner = None;
+            guard.waiters.pop_front()
+        } else {
+            None
+        };
+        guard.unlock();
+        core::mem::forget(self);
+        if let Some(th) = waiter {
+            th.unpark();
+        }
+    }
+}
+
+impl<T> Drop for ReentrantMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        panic!("`.unlock()` must be explicitly called for ReentrantMutexGuard.");
+    }
+}
+
+struct RobustState {
+    // The tid of the thread holding the lock.
+    owner: Option<u64>,
+    // Whether the lock is released by the exit of its owner.
+    owner_died: bool,
+    waiters: VecDeque<ParkHandle>,
+}
+
+impl RobustState {
+    // Wake up the waiter that has waited the longest. A stale handle of a
+    // waiter that is already woken up, e.g., by a kill signal, is skipped so
+    // that the released mutex is not left without a thread to take it.
+    fn wake_one(&mut self) {
+        while let Some(th) = self.waiters.pop_front() {
+            if th.try_unpark() {
+                break;
+            }
+        }
+    }
+}
+
+/// A mutex that is released when the thread holding it exits.
+///
+/// See the [module-level documentation](self) for details.
+///
+/// # Examples
+///
+/// ```
+/// use keos_project4::sync::RobustMutex;
+///
+/// let mutex = RobustMutex::new(0);
+/// let mut guard = mutex.lock().unwrap();
+/// *guard += 1;
+/// guard.unlock();
+/// ```
+pub struct RobustMutex<T> {
+    t: UnsafeCell<T>,
+    state: SpinLock<RobustState>,
+}
+
+unsafe impl<T: Send> Send for RobustMutex<T> {}
+unsafe impl<T: Send> Sync for RobustMutex<T> {}
+
+/// The error returned by [`RobustMutex::lock`] when the previous owner of the
+/// mutex exited while holding it.
+///
+/// The mutex is acquired anyway. The guard can be recovered with
+/// [`OwnerDied::into_inner`] after checking or repairing the data.
+pub struct OwnerDied<G>(G);
+
+impl<G> OwnerDied<G> {
+    /// Consumes this error, returning the guard of the acquired mutex.
+    pub fn into_inner(self) -> G {
+        self.0
+    }
+}
+
+impl<G> core::fmt::Debug for OwnerDied<G> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("OwnerDied")
+    }
+}
+
+impl<T> RobustMutex<T> {
+    /// Creates a new robust mutex in an unlocked state ready for use.
+    #[inline]
+    pub const fn new(t: T) -> RobustMutex<T> {
+        RobustMutex {
+            t: UnsafeCell::new(t),
+            state: SpinLock::new(RobustState {
+                owner: None,
+                owner_died: false,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    ///
+    /// # Errors
+    ///
+    /// If the previous owner exited while holding the mutex, the mutex is
+    /// acquired and this call returns the guard in the [`OwnerDied`] error.
+    pub fn lock(&self) -> Result<RobustMutexGuard<'_, T>, OwnerDied<RobustMutexGuard<'_, T>>> {
+        let tid = Current::get_tid();
+        loop {
+            let mut guard = self.state.lock();
+            if guard.owner.is_none() {
+                return self.acquire(guard, tid);
+            }
+            Current::park_with(|th| {
+                guard.waiters.push_back(th);
+                guard.unlock();
+            });
+        }
+    }
+
+    // Acquires the unlocked mutex for the thread `tid`.
+    fn acquire(
+        &self,
+        mut guard: SpinLockGuard<'_, RobustState>,
+        tid: u64,
+    ) -> Result<RobustMutexGuard<'_, T>, OwnerDied<RobustMutexGuard<'_, T>>> {
+        guard.owner = Some(tid);
+        let owner_died = core::mem::replace(&mut guard.owner_died, false);
+        // Register while holding the state, so that the exit of this thread
+        // always finds the mutex.
+        keos::thread::register_robust_lock(self as *const _ as usize, Self::release_dead_owner);
+        guard.unlock();
+        let guard = RobustMutexGuard {
+            t: unsafe { &mut *self.t.get() },
+            lock: self,
+        };
+        if owner_died {
+            Err(OwnerDied(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    // Releases the mutex held by the exiting thread `tid`.
+    fn release_dead_owner(lock: usize, tid: u64) {
+        // SAFETY: The mutex is registered while it is held, and can not be
+        // dropped until then as it is borrowed by the guard.
+        let lock = unsafe { &*(lock as *const RobustMutex<T>) };
+        let mut guard = lock.state.lock();
+        if guard.owner == Some(tid) {
+            guard.owner = None;
+            guard.owner_died = true;
+            guard.wake_one();
+        }
+        guard.unlock();
+    }
+
+    /// Consumes this mutex, returning the underlying data.
+    pub fn into_inner(self) -> T {
+        self.t.into_inner()
+    }
+}
+
+/// An implementation of a "scoped lock" of a robust mutex. When this
+/// structure is dropped (falls out of scope) without unlocking, the panic
+/// occurs.
+///
+/// The lock must be explicitly unlocked by [`unlock`] method. The data
+/// protected by the mutex can be accessed through this guard.
+///
+/// This structure is created by the [`lock`] method on [`RobustMutex`].
+///
+/// [`lock`]: RobustMutex::lock
+/// [`unlock`]: RobustMutexGuard::unlock
+pub struct RobustMutexGuard<'a, T: 'a> {
+    t: &'a mut T,
+    lock: &'a RobustMutex<T>,
+}
+
+impl<T> !Send for RobustMutexGuard<'_, T> {}
+unsafe impl<T: Sync> Sync for RobustMutexGuard<'_, T> {}
+
+impl<T> Deref for RobustMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.t
+    }
+}
+
+impl<T> DerefMut for RobustMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.t
+    }
+}
+
+impl<T> RobustMutexGuard<'_, T> {
+    /// Releases the underlying [`RobustMutex`], waking up one of the threads
+    /// waiting for it.
+    pub fn unlock(self) {
+        let lock = self.lock;
+        core::mem::forget(self);
+        let mut guard = lock.state.lock();
+        guard.owner = None;
+        keos::thread::unregister_robust_lock(lock as *const _ as usize);
+        guard.wake_one();
+        guard.unlock();
+    }
+}
+
+impl<T> Drop for RobustMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        panic!("`.unlock()` must be explicitly called for RobustMutexGuard.");
+    }
+}
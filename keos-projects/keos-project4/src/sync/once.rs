@@ -0,0 +1,162 @@
+//! # One-time initialization.
+//!
+//! Kernel subsystems often have a state that must be initialized exactly once,
+//! at its first use. When multiple threads reach the first use concurrently,
+//! only one of them must run the initialization, and the others must wait
+//! until it finishes, rather than observe a half-initialized state.
+//!
+//! [`Once`] provides this with [`Once::call_once`]. The first caller runs the
+//! given closure, and the concurrent callers sleep on a [`ConditionVariable`]
+//! until the closure returns. After that, [`Once::call_once`] returns
+//! immediately without touching the mutex.
+//!
+//! [`Lazy`] builds on [`Once`] to initialize a value on the first access
+//! through [`Deref`].
+//!
+//! #### Usage Example
+//!
+//! ```rust
+//! let table = Lazy::new(|| build_table()); // Not built yet.
+//!
+//! // The first access builds the table; the others wait for it.
+//! let entry = table.get(0);
+//! ```
+
+use super::{condition_variable::ConditionVariable, mutex::Mutex};
+use core::{
+    cell::{Cell, UnsafeCell},
+    ops::Deref,
+};
+use keos::sync::atomic::AtomicBool;
+
+#[derive(PartialEq, Eq)]
+enum OnceState {
+    Incomplete,
+    Running,
+    Complete,
+}
+
+/// A synchronization primitive which runs a one-time initialization.
+///
+/// See the [module-level documentation](self) for details.
+pub struct Once {
+    state: Mutex<OnceState>,
+    cv: ConditionVariable,
+    // Whether the initialization is complete, checked without the mutex.
+    done: AtomicBool,
+}
+
+impl Once {
+    /// Creates a new [`Once`] whose initialization is not run yet.
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(OnceState::Incomplete),
+            cv: ConditionVariable::new(),
+            done: AtomicBool::new(false),
+        }
+    }
+
+    /// Runs `f` if this is the first call on this [`Once`].
+    ///
+    /// If another thread is running the initialization, the current thread
+    /// blocks until it finishes. When this function returns, the
+    /// initialization is complete, whichever thread ran it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use keos_project4::sync::Once;
+    ///
+    /// let once = Once::new();
+    /// once.call_once(|| println!("Initialized."));
+    /// // Does not print again.
+    /// once.call_once(|| println!("Initialized."));
+    /// ```
+    pub fn call_once(&self, f: impl FnOnce()) {
+        if self.done.load() {
+            return;
+        }
+        let mut guard = self
+            .cv
+            .wait_while(&self.state, |state| *state == OnceState::Running);
+        if *guard == OnceState::Complete {
+            guard.unlock();
+            return;
+        }
+        *guard = OnceState::Running;
+        guard.unlock();
+
+        f();
+
+        let mut guard = self.state.lock();
+        *guard = OnceState::Complete;
+        self.done.store(true);
+        self.cv.broadcast(guard);
+    }
+
+    /// Returns `true` if the initialization is complete.
+    pub fn is_completed(&self) -> bool {
+        self.done.load()
+    }
+}
+
+impl Default for Once {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A value which is initialized on the first access.
+///
+/// The value is built by the function given to [`Lazy::new`], with [`Once`]
+/// so that the concurrent first accesses run the function only once.
+///
+/// # Examples
+///
+/// ```
+/// use keos_project4::sync::Lazy;
+///
+/// let lazy = Lazy::new(|| 92);
+/// assert_eq!(*lazy, 92);
+/// ```
+pub struct Lazy<T, F = fn() -> T> {
+    once: Once,
+    init: Cell<Option<F>>,
+    value: UnsafeCell<Option<T>>,
+}
+
+unsafe impl<T: Send + Sync, F: Send> Sync for Lazy<T, F> {}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    /// Creates a new lazy value with the given initializing function.
+    pub fn new(init: F) -> Self {
+        Self {
+            once: Once::new(),
+            init: Cell::new(Some(init)),
+            value: UnsafeCell::new(None),
+        }
+    }
+
+    /// Forces the evaluation of this lazy value, and returns a reference to
+    /// the result.
+    ///
+    /// This is equivalent to the [`Deref`] implementation.
+    pub fn force(this: &Self) -> &T {
+        this.once.call_once(|| {
+            let init = this.init.take().expect("Lazy is initialized twice.");
+            // SAFETY: `Once` excludes the other accesses to the value until
+            // the initialization is complete.
+            unsafe { *this.value.get() = Some(init()) };
+        });
+        // SAFETY: The value is initialized, and never modified again.
+        unsafe { (*this.value.get()).as_ref().unwrap() }
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Lazy::force(self)
+    }
+}
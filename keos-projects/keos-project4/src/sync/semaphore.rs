@@ -10,7 +10,10 @@
 //! Conceptually, a semaphore can be implemented using a combination of a
 //! mutex for mutual exclusion and a condition variable to support blocking and
 //! waking threads. This approach ensures thread-safe and efficient control over
-//! the internal permit count.
+//! the internal permit count. However, it does not decide which thread gets a
+//! released permit: a woken thread competes with the other woken threads and
+//! with the threads newly calling `wait`, and a thread can lose repeatedly and
+//! starve.
 //!
 //! Semaphores are widely used in operating systems to solve classic concurrency
 //! problems. One common example is the **producer-consumer** pattern, in which
@@ -50,6 +53,20 @@
 //! expressive tool for coordinating access to shared resources and implementing
 //! complex thread synchronization patterns.
 //!
+//! #### Fairness
+//!
+//! The [`Semaphore`] in KeOS is strictly first-in, first-out: the permits are
+//! granted to the blocked threads in the order they called
+//! [`Semaphore::wait`]. To this end, the semaphore maintains its own queue of
+//! parked threads, instead of relying on the wake-up order of a condition
+//! variable. [`Semaphore::signal`] hands the permit **directly** to the thread
+//! at the head of the queue and unparks it, without making the permit
+//! available to others. As a result, a woken thread already owns the permit
+//! and returns without checking the count again, and a thread calling
+//! [`Semaphore::wait`] while others are blocked queues up behind them even if
+//! it comes in between. Only when the queue is empty does
+//! [`Semaphore::signal`] increment the number of available permits.
+//!
 //! #### Usage Example
 //!
 //! ```rust
@@ -82,9 +99,12 @@
 //! [`ConditionVariable`]: crate::sync::ConditionVariable
 //! [`section`]: crate::process
 
+use alloc::collections::vec_deque::VecDeque;
 use core::ops::Deref;
-
-use super::{condition_variable::ConditionVariable, mutex::Mutex};
+use keos::{
+    sync::SpinLock,
+    thread::{Current, ParkHandle},
+};
 
 /// Counting semaphore.
 ///
@@ -92,9 +112,13 @@ use super::{condition_variable::ConditionVariable, mutex::Mutex};
 /// synchronize access to a shared resource. A semaphore differs from a mutex in
 /// that it can allow more than one concurrent caller to access the shared
 /// resource at a time.
+///
+/// The permits are granted in the order the threads started waiting. See
+/// the [module-level documentation](self) for details.
 pub struct Semaphore<T> {
     resource: T,
     // TODO: Add any member you need.
+    waiters: SpinLock<VecDeque<ParkHandle>>,
 }
 
 impl<T> Semaphore<T> {
@@ -109,13 +133,16 @@ impl<T> Semaphore<T> {
         Self {
             resource,
             // TODO: Initialize the members you added.
+            waiters: SpinLock::new(VecDeque::new()),
         }
     }
 
     /// Waits until a permit becomes available and then acquires it.
     ///
-    /// If no permits are available, this function will block the current thread
-    /// until another thread calls `signal()` to release a permit.
+    /// If no permits are available, or other threads are already waiting, this
+    /// function will block the current thread at the tail of the wait queue
+    /// until `signal()` hands a permit to it. Use [`Current::park_with`] to
+    /// enqueue the thread and park it.
     ///
     /// This method returns a [`SemaphorePermits`] RAII guard. When the guard is
     /// dropped, it will automatically release the acquired permit.
@@ -125,9 +152,9 @@ impl<T> Semaphore<T> {
 
     /// Releases a permit back to the semaphore.
     ///
-    /// This method increases the number of available permits by one, and if any
-    /// threads are blocked in `wait()`, one will be woken up to acquire the
-    /// newly released permit.
+    /// If any threads are blocked in `wait()`, this method hands the permit
+    /// to the one that has waited the longest and wakes it up. Otherwise, it
+    /// increases the number of available permits by one.
     ///
     /// Normally, you don’t call this directly except for signaling an event
     /// with a zero-initialized semaphore. Instead, it's automatically invoked
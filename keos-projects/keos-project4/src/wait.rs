@@ -0,0 +1,270 @@
+//! # Waiting for child processes.
+//!
+//! `thread_join` waits for a single *thread*. A parent process, however,
+//! usually wants to wait for a *process* that it created with `fork`, which
+//! may consist of multiple threads. A process exits when its last thread
+//! exits, and the parent collects its exit code with the `waitpid` system
+//! call.
+//!
+//! To this end, every thread holds a [`ProcessHandle`] to the
+//! [`ProcessState`] shared by all threads of its process. The handles track
+//! the live threads of the process: creating a thread makes a new handle with
+//! [`ProcessHandle::clone_for`], and dropping the last handle marks the
+//! process as exited and wakes up the parent waiting on it. The parent keeps
+//! the [`ProcessState`] of its children until it reaps them with `waitpid`,
+//! so that it can wait on a child that already exited.
+//!
+//! The exit code of a process is the status given to `exit_group`, or to the
+//! last call to `exit` of its threads. It is -1 if the process is killed
+//! without calling them.
+//!
+//! ## Waiting for `vfork`
+//!
+//! A child created by `vfork` runs in the address space of its parent, and the
+//! parent is blocked in [`ProcessHandle::wait_child_released`] meanwhile. The
+//! child gives the address space back either by loading a new image with
+//! `execve`, which calls [`ProcessHandle::release_address_space`], or by
+//! exiting. Both wake up the waiters on the [`ProcessState`] of the child.
+//!
+//! ## Terminating a process
+//!
+//! `exit_group` terminates the sibling threads with
+//! [`ProcessHandle::kill_siblings`], which sends a kill signal to every other
+//! thread with [`kill_by_tid`]. A signaled thread exits when it is scheduled
+//! next time, and a parked thread (e.g., one blocked on a [`Mutex`]) is woken
+//! up to do so. Therefore, a mutex held by a killed thread is never unlocked,
+//! but this does not block the process from exiting: all the other threads
+//! waiting on it are killed as well. A thread created while the process is
+//! being killed is killed immediately.
+//!
+//! [`kill_by_tid`]: keos::thread::kill_by_tid
+//! [`Mutex`]: crate::sync::Mutex
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    vec::Vec,
+};
+use keos::{
+    KernelError,
+    sync::SpinLock,
+    thread::{Current, ParkHandle, kill_by_tid},
+};
+
+struct ExitState {
+    // The tids of live threads.
+    threads: BTreeSet<u64>,
+    exit_code: i32,
+    // Whether the process is being killed by `exit_group`.
+    killed: bool,
+    exited: bool,
+    // Whether the process stopped using the address space of its parent.
+    released: bool,
+    waiters: Vec<ParkHandle>,
+}
+
+/// The state of a process shared by its threads and its parent.
+pub struct ProcessState {
+    exit: SpinLock<ExitState>,
+    // Children that are not reaped yet, keyed by their process id.
+    children: SpinLock<BTreeMap<u64, Arc<ProcessState>>>,
+}
+
+impl ProcessState {
+    /// Waits until the process exits, and returns its exit code.
+    ///
+    /// Returns immediately if the process already exited.
+    pub fn wait(&self) -> i32 {
+        loop {
+            let mut guard = self.exit.lock();
+            if guard.exited {
+                let exit_code = guard.exit_code;
+                guard.unlock();
+                return exit_code;
+            }
+            Current::park_with(|th| {
+                guard.waiters.push(th);
+                guard.unlock();
+            });
+        }
+    }
+
+    /// Waits until the process stops using the address space of its parent,
+    /// either by `execve` or by exiting.
+    pub fn wait_released(&self) {
+        loop {
+            let mut guard = self.exit.lock();
+            if guard.released || guard.exited {
+                guard.unlock();
+                return;
+            }
+            Current::park_with(|th| {
+                guard.waiters.push(th);
+                guard.unlock();
+            });
+        }
+    }
+}
+
+/// A thread's handle to the [`ProcessState`] of its process.
+///
+/// The process exits when all handles to it are dropped.
+pub struct ProcessHandle {
+    state: Arc<ProcessState>,
+    tid: u64,
+}
+
+impl ProcessHandle {
+    /// Creates a handle to a new process whose first thread is `tid`.
+    pub fn new(tid: u64) -> Self {
+        Self {
+            state: Arc::new(ProcessState {
+                exit: SpinLock::new(ExitState {
+                    threads: BTreeSet::from([tid]),
+                    exit_code: -1,
+                    killed: false,
+                    exited: false,
+                    released: false,
+                    waiters: Vec::new(),
+                }),
+                children: SpinLock::new(BTreeMap::new()),
+            }),
+            tid,
+        }
+    }
+
+    /// Creates a handle for a new thread `tid` of the process.
+    ///
+    /// If the process is being killed, the new thread is killed as well.
+    pub fn clone_for(&self, tid: u64) -> Self {
+        let mut guard = self.state.exit.lock();
+        guard.threads.insert(tid);
+        let (killed, exit_code) = (guard.killed, guard.exit_code);
+        guard.unlock();
+        if killed {
+            let _ = kill_by_tid(tid, exit_code);
+        }
+        Self {
+            state: self.state.clone(),
+            tid,
+        }
+    }
+
+    /// Kills all the other threads of the process, and records `exit_code`
+    /// as the exit code of the process.
+    pub fn kill_siblings(&self, exit_code: i32) {
+        let mut guard = self.state.exit.lock();
+        guard.killed = true;
+        guard.exit_code = exit_code;
+        let siblings = guard
+            .threads
+            .iter()
+            .copied()
+            .filter(|tid| *tid != self.tid)
+            .collect::<Vec<_>>();
+        guard.unlock();
+        for tid in siblings {
+            // The thread may have exited in the meantime.
+            let _ = kill_by_tid(tid, exit_code);
+        }
+    }
+
+    /// Returns `true` if the thread of this handle is the only live thread of
+    /// the process.
+    pub fn is_single_threaded(&self) -> bool {
+        let guard = self.state.exit.lock();
+        let single = guard.threads.len() == 1;
+        guard.unlock();
+        single
+    }
+
+    /// Returns `true` if `tid` is a live thread of the process.
+    pub fn has_thread(&self, tid: u64) -> bool {
+        let guard = self.state.exit.lock();
+        let live = guard.threads.contains(&tid);
+        guard.unlock();
+        live
+    }
+
+    /// Records the exit code of the process.
+    ///
+    /// This is ignored if the process is being killed, which already has the
+    /// exit code given to [`ProcessHandle::kill_siblings`].
+    pub fn set_exit_code(&self, exit_code: i32) {
+        let mut guard = self.state.exit.lock();
+        if !guard.killed {
+            guard.exit_code = exit_code;
+        }
+        guard.unlock();
+    }
+
+    /// Registers `child` as a child process of this process with the process
+    /// id `pid`.
+    pub fn add_child(&self, pid: u64, child: &ProcessHandle) {
+        let mut guard = self.state.children.lock();
+        guard.insert(pid, child.state.clone());
+        guard.unlock();
+    }
+
+    /// Marks that the process stopped using the address space of its parent,
+    /// and resumes the parent blocked in `vfork`.
+    pub fn release_address_space(&self) {
+        let mut guard = self.state.exit.lock();
+        guard.released = true;
+        // `waitpid` waiters are woken up as well, and park again.
+        let waiters = core::mem::take(&mut guard.waiters);
+        guard.unlock();
+        for th in waiters {
+            th.unpark();
+        }
+    }
+
+    /// Waits until the child process `pid` stops using the address space of
+    /// this process. See [`ProcessState::wait_released`].
+    ///
+    /// Returns [`KernelError::NoSuchEntry`] if `pid` is not a child of this
+    /// process.
+    pub fn wait_child_released(&self, pid: u64) -> Result<(), KernelError> {
+        let guard = self.state.children.lock();
+        let child = guard.get(&pid).cloned();
+        guard.unlock();
+
+        child.ok_or(KernelError::NoSuchEntry)?.wait_released();
+        Ok(())
+    }
+
+    /// Waits for the child process `pid` to exit, then reaps it.
+    ///
+    /// Returns the exit code of the child, or
+    /// [`KernelError::NoSuchEntry`] if `pid` is not a child of this process
+    /// or is already reaped.
+    pub fn wait_child(&self, pid: u64) -> Result<i32, KernelError> {
+        let guard = self.state.children.lock();
+        let child = guard.get(&pid).cloned();
+        guard.unlock();
+
+        let exit_code = child.ok_or(KernelError::NoSuchEntry)?.wait();
+
+        let mut guard = self.state.children.lock();
+        guard.remove(&pid);
+        guard.unlock();
+        Ok(exit_code)
+    }
+}
+
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        let mut guard = self.state.exit.lock();
+        guard.threads.remove(&self.tid);
+        let waiters = if guard.threads.is_empty() {
+            guard.exited = true;
+            core::mem::take(&mut guard.waiters)
+        } else {
+            Vec::new()
+        };
+        guard.unlock();
+        for th in waiters {
+            th.unpark();
+        }
+    }
+}
@@ -193,6 +193,11 @@ fn main() {
         (512 * offset.0.into_sector().0 + 32) as u64,
     )
     .unwrap();
+    file.write_at(
+        &0o700_u32.to_le_bytes(), // mode
+        (512 * offset.0.into_sector().0 + 160) as u64,
+    )
+    .unwrap();
 
     // Fill Root Directory Entry (inode 1)
     let inode_bitmap = SuperBlock::ROOT_INODE_NUMBER
@@ -1,11 +1,19 @@
-use alloc::{borrow::ToOwned, boxed::Box};
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, sync::Arc, vec::Vec};
+use core::arch::x86_64::_rdtsc;
 use keos::{
     KernelError,
-    fs::{Disk, FileSystem, InodeNumber, RegularFile},
+    fs::{Disk, FileBlockNumber, FileSystem, InodeNumber, RegularFile},
     println,
 };
 use keos_project2::loader::LoadContext;
-use keos_project5::{ffs, page_cache::PageCache};
+use keos_project5::{
+    ffs::{
+        self, INODE_CACHE_SIZE,
+        access_control::{self, ref_leaks, track_refs},
+        fs_objects::Directory,
+    },
+    page_cache::PageCache,
+};
 
 pub fn root() {
     // The only requirement is not to panic.
@@ -147,6 +155,48 @@ pub fn dib() {
     }
 }
 
+pub fn locality() {
+    const BLOCKS: usize = 32;
+
+    println!();
+    let fs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+    FileSystem::register(PageCache::new(fs));
+    let root = FileSystem::root();
+
+    let file = root
+        .create("locality", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    let buf = Box::new([0x42u8; 0x1000]);
+    for fba in 0..BLOCKS {
+        file.write(fba * 0x1000, &*buf).unwrap();
+    }
+    file.writeback().unwrap();
+
+    let ffs = ffs::FastFileSystem::from_disk(Disk::new(2), false, false).unwrap();
+    let inode = ffs.get_inode(file.ino()).unwrap();
+    let lbas = (0..BLOCKS)
+        .map(|fba| {
+            inode
+                .read()
+                .get(&ffs.0, FileBlockNumber(fba))
+                .unwrap()
+                .unwrap()
+                .into_u64()
+        })
+        .collect::<Vec<_>>();
+
+    // Only the indirect block may be placed in between.
+    let contiguous = lbas.windows(2).filter(|w| w[1] == w[0] + 1).count();
+    assert!(
+        contiguous + 2 >= BLOCKS - 1,
+        "The data blocks must be placed together: {lbas:?}"
+    );
+    let (min, max) = (lbas.iter().min().unwrap(), lbas.iter().max().unwrap());
+    assert!(max - min < 2 * BLOCKS as u64, "{lbas:?}");
+}
+
 pub fn add_directory() {
     println!();
     let fs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
@@ -337,12 +387,14 @@ pub fn remove_dir() {
 
 pub fn simple_elf() {
     pub fn run_elf_regularfile(elf: &RegularFile, name: &str) -> i32 {
-        let LoadContext { mm_struct, regs } = LoadContext {
-            mm_struct: keos_project2::mm_struct::MmStruct::new(),
-            regs: keos::syscall::Registers::new(),
-        }
-        .load(elf, &[name])
-        .unwrap_or_else(|e| panic!("Failed to load elf: {:?}", e));
+        let LoadContext {
+            mm_struct,
+            regs,
+            symbols,
+            ..
+        } = LoadContext::new()
+            .load(elf, &[name])
+            .unwrap_or_else(|e| panic!("Failed to load elf: {:?}", e));
 
         let thread_build = keos::thread::ThreadBuilder::new(name);
         let tid = thread_build.get_tid();
@@ -350,7 +402,10 @@ pub fn simple_elf() {
             .attach_task(Box::new(keos_project4::Thread::from_mm_struct(
                 mm_struct, tid,
             )))
-            .spawn(move || regs.launch())
+            .spawn(move || {
+                keos::task::set_user_symbols(symbols);
+                regs.launch()
+            })
             .join()
     }
 
@@ -389,3 +444,497 @@ pub fn simple_elf() {
     drop(file);
     root.unlink("the_answer").unwrap();
 }
+
+pub fn mount() {
+    let fs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+    FileSystem::register(PageCache::new(fs));
+    let root = FileSystem::root();
+
+    root.create("mnt", true).unwrap();
+    root.create("mnt/hidden", false).unwrap();
+    let on_root = root.create("on_root", false).unwrap();
+
+    FileSystem::mount("//mnt/", simple_fs::FileSystem::load(1).unwrap())
+        .expect("Mounting SimpleFS on `/mnt' must succeed.");
+
+    let mnt = root
+        .open("mnt")
+        .unwrap()
+        .into_directory()
+        .expect("The mount point must be a directory.");
+    assert_eq!(mnt.path(), Some("/mnt"));
+    root.open("/mnt/os-release")
+        .expect("`/mnt/os-release' must be opened from SimpleFS.")
+        .into_regular_file()
+        .expect("`/mnt/os-release' must be a RegularFile.");
+
+    // The file systems are isolated from each other.
+    assert_eq!(
+        root.open("mnt/hidden").map(|_| ()),
+        Err(KernelError::NoSuchEntry),
+        "The original contents of the mount point must be hidden."
+    );
+    assert_eq!(
+        root.open("os-release").map(|_| ()),
+        Err(KernelError::NoSuchEntry)
+    );
+    assert_eq!(
+        mnt.open("on_root").map(|_| ()),
+        Err(KernelError::NoSuchEntry)
+    );
+    assert_eq!(
+        root.create("/mnt/./created", false).map(|_| ()),
+        Err(KernelError::NotSupportedOperation),
+        "A file under `/mnt' must be created on the read-only SimpleFS."
+    );
+
+    // `..` goes back across the mount point.
+    assert_eq!(mnt.open("../on_root").unwrap().ino(), on_root.ino());
+    assert_eq!(
+        mnt.open("..").unwrap().into_directory().unwrap().path(),
+        Some("/")
+    );
+
+    assert_eq!(
+        root.rename("/mnt/os-release", "/os-release"),
+        Err(KernelError::InvalidArgument)
+    );
+    assert_eq!(
+        root.rename("on_root", "mnt/on_root"),
+        Err(KernelError::InvalidArgument)
+    );
+    assert_eq!(
+        root.rename("/mnt/os-release", "/mnt/renamed"),
+        Err(KernelError::NotSupportedOperation),
+        "A file on the read-only SimpleFS must not be renamed."
+    );
+    assert_eq!(root.rename("mnt", "mnt2"), Err(KernelError::Busy));
+
+    // Renames within the file system mounted on `/'.
+    root.create("dir", true).unwrap();
+    root.rename("on_root", "dir/moved")
+        .expect("Moving a file to another directory must succeed.");
+    assert_eq!(
+        root.open("on_root").map(|_| ()),
+        Err(KernelError::NoSuchEntry)
+    );
+    assert_eq!(root.open("/dir/moved").unwrap().ino(), on_root.ino());
+    root.create("dir/other", false).unwrap();
+    assert_eq!(
+        root.rename("dir/moved", "dir/other"),
+        Err(KernelError::FileExist)
+    );
+    root.rename("dir", "renamed_dir")
+        .expect("Renaming a directory must succeed.");
+    assert_eq!(root.open("renamed_dir/moved").unwrap().ino(), on_root.ino());
+    root.create("renamed_dir/sub", true).unwrap();
+    assert_eq!(
+        root.rename("renamed_dir", "renamed_dir/sub/dir"),
+        Err(KernelError::InvalidArgument),
+        "A directory must not be moved into its subdirectory."
+    );
+    root.rename("renamed_dir/sub", "sub").unwrap();
+    let sub = root.open("sub").unwrap().into_directory().unwrap();
+    assert!(
+        sub.read_dir()
+            .unwrap()
+            .contains(&(root.ino(), String::from(".."))),
+        "`..' of a moved directory must point to its new parent."
+    );
+    drop(sub);
+    for path in [
+        "sub",
+        "renamed_dir/moved",
+        "renamed_dir/other",
+        "renamed_dir",
+    ] {
+        root.unlink(path).unwrap();
+    }
+
+    assert_eq!(root.unlink("mnt"), Err(KernelError::Busy));
+
+    assert_eq!(FileSystem::umount("/mnt/"), Ok(()));
+    assert_eq!(
+        FileSystem::umount("/mnt"),
+        Err(KernelError::InvalidArgument)
+    );
+    root.open("mnt/hidden")
+        .expect("The original contents of the mount point must be back after umount.");
+}
+
+pub fn inode_leak_check() {
+    // Without the page cache, which keeps the files open.
+    let fs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+    let inner = fs.0.clone();
+    FileSystem::register(fs);
+    let root = FileSystem::root();
+    drop(root.create("leak_check", false).unwrap());
+
+    // A balanced open and close is not reported.
+    track_refs();
+    let file = root.open("leak_check").unwrap();
+    let ino = file.ino();
+    drop(file);
+    assert_eq!(ref_leaks(), Default::default());
+
+    // The test forgets to close the file.
+    track_refs();
+    let forgotten = root.open("leak_check").unwrap();
+    let leaks = ref_leaks();
+    assert!(
+        leaks.inodes.iter().any(|(leaked, _)| *leaked == ino),
+        "The file left open must be reported: {leaks:?}"
+    );
+
+    // The test keeps a reference to a metadata block.
+    track_refs();
+    let sb = inner.sb.clone();
+    let leaks = ref_leaks();
+    assert_eq!(
+        leaks.blocks,
+        [(ffs::types::LogicalBlockAddress::new(1).unwrap(), 1)],
+        "The superblock left referenced must be reported: {leaks:?}"
+    );
+
+    drop(sb);
+    drop(forgotten);
+    root.unlink("leak_check").unwrap();
+}
+
+#[validate_refs(access_control)]
+pub fn inode_cache() {
+    const FILES: usize = INODE_CACHE_SIZE * 4;
+    // Without the page cache, which keeps the files open.
+    let fs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+    let inner = fs.0.clone();
+    FileSystem::register(fs);
+    let root = FileSystem::root();
+    // Returns the number of the live and the cached inodes.
+    let in_memory = || {
+        let inodes = inner.inodes.lock();
+        let cache = inner.inode_cache.lock();
+        let result = (inodes.len(), cache.len());
+        cache.unlock();
+        inodes.unlock();
+        result
+    };
+    let (live, _) = in_memory();
+
+    root.create("inode_cache", true).unwrap();
+    for i in 0..FILES {
+        let file = root
+            .create(&format!("inode_cache/{i}"), false)
+            .unwrap()
+            .into_regular_file()
+            .unwrap();
+        file.write(0, &i.to_le_bytes()).unwrap();
+        drop(file);
+
+        let (now_live, cached) = in_memory();
+        assert!(
+            now_live <= live && cached <= INODE_CACHE_SIZE,
+            "Closed files must not be kept beyond the cache: {now_live} live and {cached} cached inodes."
+        );
+    }
+
+    // The evicted inodes are read from the disk again.
+    for i in 0..FILES {
+        let file = root
+            .open(&format!("inode_cache/{i}"))
+            .unwrap()
+            .into_regular_file()
+            .unwrap();
+        let mut buf = [0; core::mem::size_of::<usize>()];
+        assert_eq!(file.size(), buf.len());
+        assert_eq!(file.read(0, &mut buf), Ok(buf.len()));
+        assert_eq!(usize::from_le_bytes(buf), i);
+    }
+    let (now_live, cached) = in_memory();
+    assert!(now_live <= live && cached <= INODE_CACHE_SIZE);
+
+    for i in 0..FILES {
+        root.unlink(&format!("inode_cache/{i}")).unwrap();
+    }
+    root.unlink("inode_cache").unwrap();
+}
+
+#[validate_refs(access_control)]
+pub fn hashed_directory() {
+    const FILES: usize = 2000;
+    const LOOKUPS: usize = 100;
+    let fs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+    let inner = fs.0.clone();
+    FileSystem::register(fs);
+    let root = FileSystem::root();
+    // The root directory written by the mkfs is in the linear format.
+    let root_dir = Directory::new(
+        inner.get_inode(InodeNumber::new(1).unwrap()).unwrap(),
+        Arc::downgrade(&inner),
+    )
+    .unwrap();
+    assert!(!root_dir.inode.read().hashed);
+
+    let ino = root.create("hashed_directory", true).unwrap().ino();
+    assert_eq!(root_dir.find(&inner, "hashed_directory"), Ok(ino));
+    let dir = Directory::new(inner.get_inode(ino).unwrap(), Arc::downgrade(&inner)).unwrap();
+    assert!(
+        dir.inode.read().hashed,
+        "A new directory must be in the hashed format."
+    );
+
+    // Returns the number of the blocks of the directory, the number of the
+    // blocks that may hold an entry, and the cycles to look up absent entries.
+    let measure = || {
+        let (blocks, probes) = {
+            let inode = dir.inode.read();
+            (
+                inode.size.div_ceil(4096),
+                Directory::buckets(&inode, "absent").count(),
+            )
+        };
+        let cycles = (0..5)
+            .map(|_| {
+                let start = unsafe { _rdtsc() };
+                for i in 0..LOOKUPS {
+                    assert_eq!(
+                        dir.find(&inner, &format!("absent{i}")),
+                        Err(KernelError::NoSuchEntry)
+                    );
+                }
+                let end = unsafe { _rdtsc() };
+                end - start
+            })
+            .min()
+            .unwrap();
+        (blocks, probes, cycles)
+    };
+
+    let mut inos = Vec::new();
+    let mut small = None;
+    for i in 0..FILES {
+        let file = root
+            .create(&format!("hashed_directory/{i}"), false)
+            .unwrap();
+        inos.push(file.ino());
+        if i + 1 == FILES / 16 {
+            small = Some(measure());
+        }
+    }
+    let (small_blocks, small_probes, small_cycles) = small.unwrap();
+    let (blocks, probes, cycles) = measure();
+    println!(
+        "{} entries: {small_blocks} blocks, {small_probes} probed, {small_cycles} cycles",
+        FILES / 16
+    );
+    println!("{FILES} entries: {blocks} blocks, {probes} probed, {cycles} cycles");
+    assert!(
+        probes <= small_probes + 8 && probes * 8 <= blocks,
+        "A lookup must probe a bucket per level, not the whole directory."
+    );
+    // The directory is 16 times larger. Scanning it takes 16 times longer.
+    assert!(
+        cycles < small_cycles * 6,
+        "Lookups must not scale linearly with the size of the directory."
+    );
+
+    for (i, ino) in inos.iter().enumerate() {
+        assert_eq!(dir.find(&inner, &format!("{i}")), Ok(*ino));
+    }
+    assert_eq!(dir.read_dir(&inner).unwrap().len(), FILES + 2);
+
+    for i in 0..FILES {
+        root.unlink(&format!("hashed_directory/{i}")).unwrap();
+    }
+    assert_eq!(dir.read_dir(&inner).unwrap().len(), 2);
+    drop(dir);
+    root.unlink("hashed_directory").unwrap();
+}
+
+#[validate_refs(access_control)]
+pub fn bulk_read() {
+    // 1 MiB, spanning the direct blocks and the indirect block.
+    const BLOCKS: usize = 256;
+    let fs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+    FileSystem::register(fs);
+    let root = FileSystem::root();
+
+    let file = root
+        .create("bulk_read", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    let data = (0..BLOCKS * 0x1000)
+        .map(|i| (i / 0x1000 + i * 7) as u8)
+        .collect::<Vec<_>>();
+    assert_eq!(file.write(0, &data), Ok(data.len()));
+    file.writeback().unwrap();
+
+    let before = Disk::batched_reads();
+    let mut buf = alloc::vec![0u8; data.len()];
+    assert_eq!(file.read(0, &mut buf), Ok(data.len()));
+    let requests = Disk::batched_reads() - before;
+    println!("{BLOCKS} blocks read with {requests} requests");
+    assert!(requests > 0, "The read must take the batched path.");
+    assert!(
+        requests as usize * 4 <= BLOCKS,
+        "Contiguous blocks must be read with a single request."
+    );
+    assert!(
+        buf == data,
+        "The batched read must return the written data."
+    );
+
+    // The batched read agrees with the reads of a block at a time.
+    for fba in 0..BLOCKS {
+        let mut block = [0u8; 4096];
+        assert_eq!(file.0.read(FileBlockNumber(fba), &mut block), Ok(true));
+        assert_eq!(block[..], data[fba * 0x1000..(fba + 1) * 0x1000]);
+    }
+
+    // Unaligned at both ends, with whole blocks in between.
+    let mut buf = alloc::vec![0u8; 40 * 0x1000];
+    assert_eq!(file.read(0x1234, &mut buf), Ok(buf.len()));
+    assert!(buf[..] == data[0x1234..0x1234 + buf.len()]);
+
+    // A read past the end of the file stops at the end.
+    assert_eq!(file.read((BLOCKS - 2) * 0x1000, &mut buf), Ok(2 * 0x1000));
+    assert!(buf[..2 * 0x1000] == data[(BLOCKS - 2) * 0x1000..]);
+
+    drop(file);
+    root.unlink("bulk_read").unwrap();
+}
+
+#[validate_refs(access_control)]
+pub fn tib() {
+    use ffs::inode::{BlockIndex, MAX_FILE_BLOCKS};
+    const DIB_END: usize = 12 + 512 + 512 * 512;
+
+    // The boundaries of each level of the indexing structure.
+    for (fba, index) in [
+        (11, Some(BlockIndex::Direct(11))),
+        (12, Some(BlockIndex::Indirect(0))),
+        (523, Some(BlockIndex::Indirect(511))),
+        (524, Some(BlockIndex::DoubleIndirect(0, 0))),
+        (1035, Some(BlockIndex::DoubleIndirect(0, 511))),
+        (1036, Some(BlockIndex::DoubleIndirect(1, 0))),
+        (DIB_END - 1, Some(BlockIndex::DoubleIndirect(511, 511))),
+        (DIB_END, Some(BlockIndex::TripleIndirect(0, 0, 0))),
+        (DIB_END + 512, Some(BlockIndex::TripleIndirect(0, 1, 0))),
+        (
+            DIB_END + 512 * 512,
+            Some(BlockIndex::TripleIndirect(1, 0, 0)),
+        ),
+        (
+            MAX_FILE_BLOCKS - 1,
+            Some(BlockIndex::TripleIndirect(511, 511, 511)),
+        ),
+        (MAX_FILE_BLOCKS, None),
+    ] {
+        assert_eq!(BlockIndex::of(FileBlockNumber(fba)), index, "fba {fba}");
+    }
+
+    let fs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+    FileSystem::register(fs.clone());
+    let root = FileSystem::root();
+    let blocks_in_use = || fs.0.sb.read().block_count_inused;
+    let before = blocks_in_use();
+
+    let file = root
+        .create("tib", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    // Spans the direct, indirect and double indirect blocks.
+    let buf = Box::new([0x42u8; 0x1000]);
+    for fba in 0..1037 {
+        assert_eq!(file.write(fba * 0x1000, &*buf), Ok(0x1000));
+    }
+    file.writeback().unwrap();
+    // 1037 data blocks, the indirect block, the double indirect block, and
+    // the two indirect blocks under it.
+    assert_eq!(blocks_in_use(), before + 1037 + 4);
+
+    let mut read_buf = Box::new([0u8; 0x1000]);
+    assert_eq!(file.read(1036 * 0x1000, &mut *read_buf), Ok(0x1000));
+    assert_eq!(read_buf[..], buf[..]);
+
+    // The disk is smaller than the double indirect limit, so the file jumps
+    // to the triple indirect blocks with a hole. The write crosses the
+    // boundary of the double and triple indirect blocks.
+    let data = (0..0x1000).map(|i| i as u8).collect::<Vec<_>>();
+    let ofs = DIB_END * 0x1000 - 0x800;
+    assert_eq!(file.write(ofs, &data), Ok(data.len()));
+    file.writeback().unwrap();
+    assert_eq!(file.size(), ofs + data.len());
+    // 2 data blocks, the last indirect block under the double indirect block,
+    // and a triple indirect, a double indirect and an indirect block.
+    assert_eq!(blocks_in_use(), before + 1037 + 4 + 2 + 4);
+    assert_eq!(file.blocks(), 1037 + 2);
+
+    let mut read_buf = alloc::vec![0u8; 0x2000];
+    assert_eq!(file.read(ofs - 0x800, &mut read_buf), Ok(0x1800));
+    assert!(read_buf[..0x800].iter().all(|b| *b == 0));
+    assert!(read_buf[0x800..0x1800] == data[..]);
+    // The hole in between reads as zeros.
+    assert_eq!(file.read(DIB_END / 2 * 0x1000, &mut read_buf), Ok(0x2000));
+    assert!(read_buf.iter().all(|b| *b == 0));
+
+    // A file cannot grow past the maximum size.
+    assert_eq!(
+        file.write(MAX_FILE_BLOCKS * 0x1000, b"x"),
+        Err(KernelError::NoSpace)
+    );
+
+    // Removing the file frees every block, including the indirect blocks.
+    drop(file);
+    root.unlink("tib").unwrap();
+    assert_eq!(blocks_in_use(), before);
+}
+
+pub fn sparse() {
+    const OFS: usize = 10 * 1024 * 1024;
+
+    let fs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+    FileSystem::register(PageCache::new(fs.clone()));
+    let root = FileSystem::root();
+    let blocks_in_use = || fs.0.sb.read().block_count_inused;
+    let before = blocks_in_use();
+
+    let file = root
+        .create("sparse", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    assert_eq!(file.write(OFS, b"x"), Ok(1));
+    file.writeback().unwrap();
+    assert_eq!(file.size(), OFS + 1);
+
+    // Only the written block and the indirect blocks leading to it are
+    // allocated.
+    assert_eq!(file.blocks(), 1, "A hole must not be allocated.");
+    let used = blocks_in_use() - before;
+    println!("{used} blocks are used for a file of {} bytes", OFS + 1);
+    assert!(used <= 3, "A hole must not be allocated.");
+
+    // The hole reads as zeros.
+    let mut buf = alloc::vec![0xffu8; 0x10000];
+    assert_eq!(file.read(0, &mut buf), Ok(buf.len()));
+    assert!(buf.iter().all(|b| *b == 0), "A hole must read as zeros.");
+    assert_eq!(file.read(OFS - 0x1000, &mut buf), Ok(0x1001));
+    assert!(buf[..0x1000].iter().all(|b| *b == 0));
+    assert_eq!(buf[0x1000], b'x');
+
+    // A write into the hole allocates only the written block.
+    assert_eq!(file.write(0x5000 + 0x10, b"hole"), Ok(4));
+    file.writeback().unwrap();
+    assert_eq!(file.blocks(), 2);
+    assert_eq!(file.read(0x5000, &mut buf[..0x20]), Ok(0x20));
+    assert!(buf[..0x10].iter().all(|b| *b == 0));
+    assert_eq!(&buf[0x10..0x14], b"hole");
+    assert!(buf[0x14..0x20].iter().all(|b| *b == 0));
+
+    // Removing the file frees the blocks.
+    drop(file);
+    root.unlink("sparse").unwrap();
+    assert_eq!(blocks_in_use(), before);
+}
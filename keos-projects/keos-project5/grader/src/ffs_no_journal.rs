@@ -347,12 +347,14 @@ pub fn remove_dir() {
 
 pub fn simple_elf() {
     pub fn run_elf_regularfile(elf: &RegularFile, name: &str) -> i32 {
-        let LoadContext { mm_struct, regs } = LoadContext {
-            mm_struct: keos_project2::mm_struct::MmStruct::new(),
-            regs: keos::syscall::Registers::new(),
-        }
-        .load(elf, &[name])
-        .unwrap_or_else(|e| panic!("Failed to load elf: {:?}", e));
+        let LoadContext {
+            mm_struct,
+            regs,
+            symbols,
+            ..
+        } = LoadContext::new()
+            .load(elf, &[name])
+            .unwrap_or_else(|e| panic!("Failed to load elf: {:?}", e));
 
         let thread_build = keos::thread::ThreadBuilder::new(name);
         let tid = thread_build.get_tid();
@@ -360,7 +362,10 @@ pub fn simple_elf() {
             .attach_task(Box::new(keos_project4::Thread::from_mm_struct(
                 mm_struct, tid,
             )))
-            .spawn(move || regs.launch())
+            .spawn(move || {
+                keos::task::set_user_symbols(symbols);
+                regs.launch()
+            })
             .join()
     }
 
@@ -1,8 +1,8 @@
-use alloc::sync::Arc;
+use alloc::{format, sync::Arc};
 use keos::{
     KernelError,
-    fs::{Disk, Sector, traits::FileSystem},
-    sync::atomic::{AtomicBool, AtomicI32},
+    fs::{Disk, FileBlockNumber, Sector, traits::FileSystem},
+    sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicUsize},
     thread::{Current, ThreadBuilder},
 };
 use keos_project5::ffs;
@@ -111,3 +111,369 @@ pub fn recovery() {
     });
     assert_eq!(final_verifier.join(), 0);
 }
+
+pub fn mtime_recovery() {
+    static INODE_SECTORS: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+    static CRASHED: AtomicBool = AtomicBool::new(false);
+    static WRITTEN_AT: AtomicU64 = AtomicU64::new(0);
+    static INO: AtomicU64 = AtomicU64::new(0);
+
+    // Crashes after the transaction is committed to the journal, by failing
+    // the checkpoint of the inode.
+    let fail_checkpoint = Arc::new(|sector: Sector, _data: &[u8; 512], write: bool| {
+        if write
+            && CRASHED.load()
+            && (INODE_SECTORS[0].load()..INODE_SECTORS[1].load()).contains(&sector.0)
+        {
+            return Err(KernelError::IOError);
+        }
+        Ok(())
+    });
+
+    let writer = ThreadBuilder::new("writer").spawn(move || {
+        let ffs = ffs::FastFileSystem::from_disk(Disk::new(2).hook(fail_checkpoint), true, false)
+            .unwrap();
+        // Reads must not change the inode while crashing.
+        ffs.set_atime_mode(ffs::AtimeMode::Never);
+        let inodes = ffs.0.inode();
+        INODE_SECTORS[0].store(inodes.start.into_sector().0);
+        INODE_SECTORS[1].store(inodes.end.into_sector().0);
+
+        let file = ffs
+            .root()
+            .unwrap()
+            .create("journal__mtime", false)
+            .unwrap()
+            .into_regular_file()
+            .unwrap();
+        INO.store(file.ino().into_u32() as u64);
+
+        Current::sleep(2);
+        WRITTEN_AT.store(keos::time::now_ticks());
+        CRASHED.store(true);
+        let result = file.write(0, &[0x42; 0x1000]);
+        CRASHED.store(false);
+        Current::exit(result.is_ok() as i32)
+    });
+    // The checkpoint fails, so the write reports the failure.
+    assert_eq!(writer.join(), 0);
+
+    let verifier = ThreadBuilder::new("verifier").spawn(move || {
+        let ffs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+        let ino = keos::fs::InodeNumber::new(INO.load() as u32).unwrap();
+        let inode = ffs.get_inode(ino).unwrap();
+        let (size, mtime) = {
+            let inode = inode.read();
+            (inode.size, inode.mtime)
+        };
+        assert_eq!(size, 0x1000);
+        assert!(mtime >= WRITTEN_AT.load());
+
+        ffs.root().unwrap().unlink("journal__mtime").unwrap();
+        Current::exit(0)
+    });
+    assert_eq!(verifier.join(), 0);
+}
+
+pub fn group_commit() {
+    const OPS: usize = 64;
+    static JOURNAL_SECTORS: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+    static COMMITS: AtomicUsize = AtomicUsize::new(0);
+    static CRASHED: AtomicBool = AtomicBool::new(false);
+
+    // Counts the commits, each of which writes a `TxEnd` and marks the journal
+    // superblock as committed. Once crashed, fails every write outside the
+    // journal, so that the committed group is never checkpointed.
+    let hook = Arc::new(|sector: Sector, data: &[u8; 512], write: bool| {
+        let journal = JOURNAL_SECTORS[0].load()..JOURNAL_SECTORS[1].load();
+        if write && sector.0 == journal.start && data[8..16] != [0; 8] {
+            COMMITS.fetch_add(1);
+        }
+        if write && CRASHED.load() && !journal.contains(&sector.0) {
+            return Err(KernelError::IOError);
+        }
+        Ok(())
+    });
+
+    let writer = ThreadBuilder::new("writer").spawn(move || {
+        let ffs = ffs::FastFileSystem::from_disk(Disk::new(2).hook(hook), true, false).unwrap();
+        let journal = ffs.0.journal();
+        JOURNAL_SECTORS[0].store(journal.start.into_sector().0);
+        JOURNAL_SECTORS[1].store(journal.end.into_sector().0);
+        ffs.set_commit_window(10 * keos::time::TICKS_PER_SEC);
+        let root = ffs.root().unwrap();
+
+        COMMITS.store(0);
+        for i in 0..OPS {
+            root.create(&format!("journal__group{i}"), false).unwrap();
+        }
+        ffs.flush_journal().unwrap();
+        let commits = COMMITS.load();
+        assert!(
+            0 < commits && commits * 8 <= OPS,
+            "{OPS} transactions must be grouped into a few commits, but got {commits}."
+        );
+
+        // Crash after the group of the unlinks is committed.
+        CRASHED.store(true);
+        for i in 0..OPS {
+            root.unlink(&format!("journal__group{i}")).unwrap();
+        }
+        assert!(ffs.flush_journal().is_err());
+        drop(root);
+        drop(ffs);
+        Current::exit(0)
+    });
+    assert_eq!(writer.join(), 0);
+
+    // The recovery replays the whole group.
+    let verifier = ThreadBuilder::new("verifier").spawn(move || {
+        let ffs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+        let root = ffs.root().unwrap();
+        for i in 0..OPS {
+            assert_eq!(
+                root.open(&format!("journal__group{i}")).err(),
+                Some(KernelError::NoSuchEntry)
+            );
+        }
+        Current::exit(0)
+    });
+    assert_eq!(verifier.join(), 0);
+}
+
+pub fn group_commit_delay() {
+    const WINDOW: u64 = 10;
+    let ffs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+    ffs.set_commit_window(WINDOW);
+    let root = ffs.root().unwrap();
+    drop(root.create("journal__group_delay", false).unwrap());
+    assert!(
+        !ffs.0.group_commit.is_empty(),
+        "The transaction must be grouped."
+    );
+
+    // No other transaction commits, so the group must be flushed in the
+    // background once its window is over.
+    Current::sleep(3 * WINDOW);
+    assert!(
+        ffs.0.group_commit.is_empty(),
+        "A group must be flushed within two windows even if nothing else commits."
+    );
+
+    root.unlink("journal__group_delay").unwrap();
+    ffs.flush_journal().unwrap();
+}
+
+pub fn torn_commit() {
+    static JOURNAL_SECTORS: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+    static CRASHED: AtomicBool = AtomicBool::new(false);
+
+    // Crashes after the transaction is committed to the journal, by failing
+    // every write outside the journal.
+    let hook = Arc::new(|sector: Sector, _data: &[u8; 512], write: bool| {
+        let journal = JOURNAL_SECTORS[0].load()..JOURNAL_SECTORS[1].load();
+        if write && CRASHED.load() && !journal.contains(&sector.0) {
+            return Err(KernelError::IOError);
+        }
+        Ok(())
+    });
+
+    let writer = ThreadBuilder::new("writer").spawn(move || {
+        let ffs = ffs::FastFileSystem::from_disk(Disk::new(2).hook(hook), true, false).unwrap();
+        let journal = ffs.0.journal();
+        JOURNAL_SECTORS[0].store(journal.start.into_sector().0);
+        JOURNAL_SECTORS[1].store(journal.end.into_sector().0);
+
+        CRASHED.store(true);
+        assert!(ffs.root().unwrap().create("journal__torn", false).is_err());
+        CRASHED.store(false);
+
+        // Tear the first journaled block of the commit.
+        let garbage = [0xa5; 512];
+        Disk::new(2)
+            .write((journal.start + 2).into_sector(), &garbage)
+            .unwrap();
+        Current::exit(0)
+    });
+    assert_eq!(writer.join(), 0);
+
+    // The recovery discards the torn commit.
+    let verifier = ThreadBuilder::new("verifier").spawn(move || {
+        let ffs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+        let root = ffs.root().unwrap();
+        assert_eq!(
+            root.open("journal__torn").err(),
+            Some(KernelError::NoSuchEntry)
+        );
+        assert!(
+            root.read_dir()
+                .unwrap()
+                .iter()
+                .all(|(_, name)| name != "journal__torn")
+        );
+        Current::exit(0)
+    });
+    assert_eq!(verifier.join(), 0);
+}
+
+pub fn dir_fsync() {
+    static CRASHED: AtomicBool = AtomicBool::new(false);
+
+    // Crashes by failing every write, so that nothing is written to the disk
+    // after the crash, not even to the journal.
+    let hook = Arc::new(|_sector: Sector, _data: &[u8; 512], write: bool| {
+        if write && CRASHED.load() {
+            return Err(KernelError::IOError);
+        }
+        Ok(())
+    });
+
+    let writer = ThreadBuilder::new("writer").spawn(move || {
+        let ffs = ffs::FastFileSystem::from_disk(Disk::new(2).hook(hook), true, false).unwrap();
+        // Keep the transactions in the group, so that they are not durable
+        // until synchronized.
+        ffs.set_commit_window(10 * keos::time::TICKS_PER_SEC);
+        let dir = ffs
+            .root()
+            .unwrap()
+            .create("journal__dir_fsync", true)
+            .unwrap()
+            .into_directory()
+            .unwrap();
+
+        dir.create("synced", false).unwrap();
+        dir.writeback().unwrap();
+        dir.create("unsynced", false).unwrap();
+
+        CRASHED.store(true);
+        drop(dir);
+        drop(ffs);
+        CRASHED.store(false);
+        Current::exit(0)
+    });
+    assert_eq!(writer.join(), 0);
+
+    // The recovery finds the entry synchronized by `fsync` on the directory,
+    // but not the one created after it.
+    let verifier = ThreadBuilder::new("verifier").spawn(move || {
+        let ffs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+        let root = ffs.root().unwrap();
+        assert!(root.open("journal__dir_fsync/synced").is_ok());
+        assert_eq!(
+            root.open("journal__dir_fsync/unsynced").err(),
+            Some(KernelError::NoSuchEntry)
+        );
+
+        root.unlink("journal__dir_fsync/synced").unwrap();
+        root.unlink("journal__dir_fsync").unwrap();
+        Current::exit(0)
+    });
+    assert_eq!(verifier.join(), 0);
+}
+
+pub fn ordered_data() {
+    static JOURNAL_SECTORS: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+    static ARMED: AtomicBool = AtomicBool::new(false);
+    static DATA_WRITTEN: AtomicBool = AtomicBool::new(false);
+    static COMMITTED_BEFORE_DATA: AtomicBool = AtomicBool::new(false);
+    static CRASHING: AtomicBool = AtomicBool::new(false);
+    static CRASH_DATA_SECTORS: AtomicUsize = AtomicUsize::new(0);
+    const BLOCK_SECTORS: usize = 0x1000 / 512;
+
+    // Tracks whether the contents of the file reach their location before the
+    // commit of the journal superblock. While crashing, the disk dies right
+    // after the whole block of the contents is written, failing every later
+    // write, so that the crash falls between the data write and the commit of
+    // the metadata.
+    let hook = Arc::new(|sector: Sector, data: &[u8; 512], write: bool| {
+        let journal = JOURNAL_SECTORS[0].load()..JOURNAL_SECTORS[1].load();
+        if !write {
+            return Ok(());
+        }
+        if CRASHING.load() && CRASH_DATA_SECTORS.load() == BLOCK_SECTORS {
+            return Err(KernelError::IOError);
+        }
+        if !journal.contains(&sector.0) {
+            if ARMED.load() && data == &[0x42; 512] {
+                DATA_WRITTEN.store(true);
+            }
+            if CRASHING.load() && data == &[0x24; 512] {
+                CRASH_DATA_SECTORS.fetch_add(1);
+            }
+        }
+        if ARMED.load()
+            && sector.0 == journal.start
+            && data[8..16] != [0; 8]
+            && !DATA_WRITTEN.load()
+        {
+            COMMITTED_BEFORE_DATA.store(true);
+        }
+        Ok(())
+    });
+
+    let writer = ThreadBuilder::new("writer").spawn(move || {
+        let ffs = ffs::FastFileSystem::from_disk(Disk::new(2).hook(hook), true, false).unwrap();
+        // Reads must not change the inode while crashing.
+        ffs.set_atime_mode(ffs::AtimeMode::Never);
+        let journal = ffs.0.journal();
+        JOURNAL_SECTORS[0].store(journal.start.into_sector().0);
+        JOURNAL_SECTORS[1].store(journal.end.into_sector().0);
+
+        let file = ffs
+            .root()
+            .unwrap()
+            .create("journal__ordered", false)
+            .unwrap()
+            .into_regular_file()
+            .unwrap();
+
+        // The data is written before the inode referring to it is committed.
+        ARMED.store(true);
+        file.write(0, &[0x42; 0x1000]).unwrap();
+        ARMED.store(false);
+        assert!(DATA_WRITTEN.load(), "The data must be written to the disk.");
+        assert!(
+            !COMMITTED_BEFORE_DATA.load(),
+            "The metadata must not be committed before the data is written."
+        );
+
+        // Crash after the data of a new block is written, but before the
+        // metadata referring to it is committed. The disk stays dead, so that
+        // nothing is written after the crash.
+        CRASHING.store(true);
+        assert!(
+            file.write(0x1000, &[0x24; 0x1000]).is_err(),
+            "The metadata must not be committed before the data is written."
+        );
+        assert_eq!(CRASH_DATA_SECTORS.load(), BLOCK_SECTORS);
+        Current::exit(0)
+    });
+    assert_eq!(writer.join(), 0);
+
+    // The recovery finds no reference to the block written before the crash,
+    // as the metadata referring to it is never committed.
+    let verifier = ThreadBuilder::new("verifier").spawn(move || {
+        let ffs = ffs::FastFileSystem::from_disk(Disk::new(2), true, false).unwrap();
+        let root = ffs.root().unwrap();
+        let file = root
+            .open("journal__ordered")
+            .unwrap()
+            .into_regular_file()
+            .unwrap();
+        let inode = ffs.get_inode(file.ino()).unwrap();
+        {
+            let inode = inode.read();
+            assert_eq!(inode.size, 0x1000);
+            assert_eq!(inode.get(&ffs.0, FileBlockNumber(1)), Ok(None));
+        }
+
+        let mut buf = [0; 0x1000];
+        assert_eq!(file.read(0, &mut buf), Ok(0x1000));
+        assert!(buf.iter().all(|b| *b == 0x42));
+
+        drop(file);
+        root.unlink("journal__ordered").unwrap();
+        Current::exit(0)
+    });
+    assert_eq!(verifier.join(), 0);
+}
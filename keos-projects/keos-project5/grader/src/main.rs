@@ -6,6 +6,7 @@
 #![feature(slice_as_array)]
 
 extern crate alloc;
+#[macro_use]
 extern crate grading;
 extern crate keos;
 extern crate keos_project1;
@@ -39,6 +40,7 @@ pub unsafe fn main(config_builder: SystemConfigurationBuilder) {
         /* Page Cache Tests */
         &page_cache::simplefs,
         &page_cache::readahead,
+        &page_cache::clock,
         /* FFS Functionality Tests */
         &ffs_no_journal::root,
         &ffs_no_journal::root_open_self,
@@ -56,17 +58,45 @@ pub unsafe fn main(config_builder: SystemConfigurationBuilder) {
         /* Page Cache + FFS Tests */
         &page_cache::fastfilesystem,
         &page_cache::readahead_ffs,
+        &page_cache::readahead_window,
+        &page_cache::direct_io,
         &page_cache::writeback,
+        &page_cache::concurrent_readers,
+        &page_cache::sharded_lock,
         /* FS1 Directory primitive syscall tests */
         &syscall_part_2::open_dir,
         &syscall_part_2::dir_rw,
         &syscall_part_2::dir_seek,
+        &syscall_part_2::readdir_large,
         /* Directory system call tests (basic) */
         &syscall_part_2::create,
         &syscall_part_2::unlink,
         &syscall_part_2::chdir,
+        &syscall_part_2::path_resolution,
+        /* File I/O statistics tests */
+        &syscall_part_2::iostat,
+        /* Page cache read semantics tests */
+        &syscall_part_2::read_eof,
+        &syscall_part_2::open_direct,
+        /* Named pipe tests */
+        &syscall_part_2::mkfifo,
+        /* Virtual console tests */
+        &syscall_part_2::tty,
+        &syscall_part_2::stat,
+        &syscall_part_2::fallocate,
+        &syscall_part_2::seek_hole_data,
+        &syscall_part_2::sendfile,
+        &syscall_part_2::chmod,
+        &syscall_part_2::msync,
+        &syscall_part_2::mmap_dirty,
         /* FFS Journaling Tests */
         &journal::recovery,
+        &journal::mtime_recovery,
+        &journal::group_commit,
+        &journal::group_commit_delay,
+        &journal::torn_commit,
+        &journal::dir_fsync,
+        &journal::ordered_data,
         /* FFS Functionality with Journaling Tests */
         &ffs::root,
         &ffs::root_open_self,
@@ -74,6 +104,7 @@ pub unsafe fn main(config_builder: SystemConfigurationBuilder) {
         &ffs::add_file,
         &ffs::ib,
         &ffs::dib,
+        &ffs::locality,
         &ffs::add_directory,
         &ffs::file_in_dir,
         &ffs::remove_file,
@@ -81,11 +112,19 @@ pub unsafe fn main(config_builder: SystemConfigurationBuilder) {
         &ffs::remove_dir,
         &ffs::remove_root,
         &ffs::simple_elf,
+        &ffs::mount,
+        &ffs::inode_leak_check,
+        &ffs::inode_cache,
+        &ffs::hashed_directory,
+        &ffs::bulk_read,
+        &ffs::tib,
+        &ffs::sparse,
         /* User Program */
         &userprog::sha256sum,
         &userprog::ls,
         &userprog::tar,
         &userprog::tar_gen,
+        &userprog::cwd_fork,
     ]);
 }
 
@@ -1,18 +1,32 @@
+use alloc::{sync::Arc, vec::Vec};
 use keos::{
-    fs::{Disk, FileBlockNumber, RegularFile, traits::FileSystem},
+    fs::{Disk, FileBlockNumber, RegularFile, Sector, traits::FileSystem},
     println,
+    sync::atomic::{AtomicBool, AtomicUsize},
+    thread::{Current, ThreadBuilder, with_current},
+    time::now_ticks,
 };
 use keos_project5::{
+    clock::ClockCache,
     ffs,
-    page_cache::{PageCache, PageCacheState},
+    lru::LRUCache,
+    page_cache::{
+        PageCache,
+        readahead::{READAHEAD_INITIAL_WINDOW, READAHEAD_MAX_WINDOW},
+        replacement::ReplacementPolicy,
+        shard::{PageCacheShards, SHARDS},
+    },
 };
 
-fn cache_exists(
-    page_cache_state: &mut PageCacheState,
+fn cache_exists<FS: FileSystem>(
+    page_cache: &PageCache<FS>,
     file: RegularFile,
     fba: FileBlockNumber,
 ) -> bool {
-    page_cache_state.get((file.0.ino(), fba)).is_some()
+    let mut guard = page_cache.0.shards.lock((file.0.ino(), fba));
+    let exists = guard.get((file.0.ino(), fba)).is_some();
+    guard.unlock();
+    exists
 }
 
 pub fn readahead() {
@@ -56,13 +70,11 @@ pub fn readahead() {
         prime_count
     );
 
-    let mut guard = fs.0.inner.lock();
     assert!(
-        cache_exists(&mut guard, f, FileBlockNumber(1)),
+        cache_exists(&fs, f, FileBlockNumber(1)),
         "File block 1 should be cached by
     read-ahead after reading block 0"
     );
-    guard.unlock();
 
     // Prevent fs drop after the test finish
     keos::fs::FileSystem::register(fs);
@@ -123,13 +135,236 @@ pub fn readahead_ffs() {
         prime_count
     );
 
-    let mut guard = page_cache.0.inner.lock();
     assert!(
-        cache_exists(&mut guard, f, FileBlockNumber(1)),
+        cache_exists(&page_cache, f, FileBlockNumber(1)),
         "File block 1 should be cached by
     read-ahead after reading block 0"
     );
+
+    // Prevent fs drop after the test finish
+    keos::fs::FileSystem::register(page_cache);
+}
+
+pub fn readahead_window() {
+    println!();
+    let ffs = ffs::FastFileSystem::from_disk(Disk::new(2), false, false).unwrap();
+    let fs: &dyn keos::fs::traits::FileSystem = &ffs;
+
+    let root = fs.root().expect("Root directory must be present");
+
+    let f: RegularFile = root
+        .create("page_cache__readahead_window", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+
+    let mut buffer: [u8; 4096] = [0u8; 4096];
+    f.write(63 * 0x1000, &buffer).unwrap();
+    f.writeback().unwrap();
+    drop(f);
+
+    let page_cache = PageCache::new(ffs);
+    let fs: &dyn keos::fs::traits::FileSystem = &page_cache;
+
+    let root = fs.root().expect("Root directory must be present");
+
+    let f: RegularFile = root
+        .open("page_cache__readahead_window")
+        .expect(
+            "Created file `page_cache__readahead_window' must be present on the root directory.",
+        )
+        .into_regular_file()
+        .expect("Created file `page_cache__readahead_window' must be a RegularFile");
+
+    let window = || page_cache.0.shards.readahead_window(f.ino());
+
+    // A long sequential read ramps the window up to the cap.
+    let mut prev = 0;
+    for fba in 0..32 {
+        f.read(fba * 0x1000, &mut buffer).unwrap();
+        let current = window();
+        assert!(
+            current >= prev,
+            "Sequential access must not shrink the readahead window ({} -> {}).",
+            prev,
+            current
+        );
+        prev = current;
+    }
+    assert_eq!(
+        prev, READAHEAD_MAX_WINDOW,
+        "A long sequential read must grow the readahead window up to the cap."
+    );
+
+    // An interleaved random pattern keeps the window small.
+    for fba in [50, 3, 51, 17, 40, 41, 9, 60, 25, 26, 5] {
+        f.read(fba * 0x1000, &mut buffer).unwrap();
+        assert!(
+            window() <= READAHEAD_INITIAL_WINDOW,
+            "Random access must keep the readahead window small (got {}).",
+            window()
+        );
+    }
+    f.read(10 * 0x1000, &mut buffer).unwrap();
+    assert_eq!(
+        window(),
+        0,
+        "A random jump must shrink the readahead window to zero."
+    );
+
+    // Prevent fs drop after the test finish
+    keos::fs::FileSystem::register(page_cache);
+}
+
+pub fn direct_io() {
+    println!();
+    let ffs = ffs::FastFileSystem::from_disk(Disk::new(2), false, false).unwrap();
+    let page_cache = PageCache::new(ffs.clone());
+    let fs: &dyn keos::fs::traits::FileSystem = &page_cache;
+
+    let root = fs.root().expect("Root directory must be present");
+
+    let cached: RegularFile = root
+        .create("page_cache__direct_io", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    let direct: RegularFile = root
+        .open("page_cache__direct_io")
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    direct.set_direct(true).unwrap();
+
+    // Writes through the direct handle never populate the cache.
+    let mut buffer: [u8; 4096] = [0u8; 4096];
+    buffer[..18].copy_from_slice(b"Direct to the disk");
+    for fba in 0..4 {
+        direct.write(fba * 0x1000, &buffer).unwrap();
+    }
+    for fba in 0..4 {
+        assert!(
+            !cache_exists(&page_cache, direct.clone(), FileBlockNumber(fba)),
+            "Direct write must not leave a slot for block {} in the page cache",
+            fba
+        );
+    }
+
+    // Writes through the direct handle invalidate the already-cached slots.
+    cached.read(0, &mut buffer).unwrap();
+    assert!(
+        cache_exists(&page_cache, cached.clone(), FileBlockNumber(0)),
+        "File block must be cached after reading it"
+    );
+
+    buffer[..18].copy_from_slice(b"Is this reflected?");
+    direct.write(0, &buffer).unwrap();
+    assert!(
+        !cache_exists(&page_cache, cached.clone(), FileBlockNumber(0)),
+        "Direct write must invalidate the cached slot of the block"
+    );
+
+    // Direct writes go straight to the disk.
+    let inode = ffs.get_inode(direct.ino()).unwrap();
+    let lba = inode
+        .read()
+        .get(&ffs.0, FileBlockNumber(0))
+        .unwrap()
+        .unwrap();
+    let mut sector = [0u8; 512];
+    Disk::new(2).read(lba.into_sector(), &mut sector).unwrap();
+    assert_eq!(
+        &sector[..18],
+        b"Is this reflected?",
+        "Direct write must be reflected to the disk without writeback"
+    );
+
+    let mut buffer: [u8; 4096] = [0u8; 4096];
+    cached.read(0, &mut buffer).unwrap();
+    assert_eq!(&buffer[..18], b"Is this reflected?");
+
+    // Prevent fs drop after the test finish
+    keos::fs::FileSystem::register(page_cache);
+}
+
+// Replays the trace of `put` and `get` on a cache of 3 entries, and returns
+// the evicted keys in order.
+macro_rules! replay {
+    ($cache:expr) => {{
+        let mut cache = $cache;
+        let mut evicted = Vec::new();
+        for (op, k) in [
+            ("put", 1),
+            ("put", 2),
+            ("put", 3),
+            ("get", 1),
+            ("put", 4),
+            ("get", 3),
+            ("get", 4),
+            ("put", 5),
+            ("get", 1),
+            ("put", 6),
+            ("put", 7),
+        ] {
+            if op == "get" {
+                cache.get(k);
+            } else {
+                let present: Vec<u32> = (1..=7).filter(|k| cache.contains(k)).collect();
+                cache.put(k, k);
+                evicted.extend(present.into_iter().filter(|k| !cache.contains(k)));
+            }
+        }
+        evicted
+    }};
+}
+
+pub fn clock() {
+    println!();
+
+    let lru = replay!(LRUCache::<u32, u32, 3>::new());
+    assert_eq!(
+        lru,
+        [2, 1, 3, 4],
+        "LRUCache must evict the least recently used entry"
+    );
+
+    // Key 1 is referenced right after the cache is filled, so it survives the
+    // first sweep (second chance) while the unreferenced key 2 is evicted.
+    // Its reference is used up by that sweep, so the next one evicts it. On
+    // the last insertion, the hand evicts key 5, which is never referenced,
+    // even though key 4 is the least recently used one.
+    let clock = replay!(ClockCache::<u32, u32, 3>::new());
+    assert_eq!(
+        clock,
+        [2, 1, 3, 5],
+        "ClockCache must evict the first entry not referenced since the last sweep"
+    );
+
+    let page_cache = PageCache::with_policy(
+        simple_fs::FileSystem::load(1).unwrap(),
+        ReplacementPolicy::Clock,
+    );
+    let fs: &dyn keos::fs::traits::FileSystem = &page_cache;
+
+    let root = fs.root().expect("Root directory must be present");
+
+    let f: RegularFile = root
+        .open("os-release")
+        .expect("file `os-release' must be present on the root directory.")
+        .into_regular_file()
+        .expect("file `os-release' must be a RegularFile");
+
+    let mut buffer: [u8; 4096] = [0u8; 4096];
+    f.read(0, &mut buffer)
+        .expect("Reading file `os-release' must succeed");
+
+    let guard = page_cache.0.shards.lock_shard(0);
+    assert_eq!(guard.policy(), ReplacementPolicy::Clock);
     guard.unlock();
+    assert!(
+        cache_exists(&page_cache, f, FileBlockNumber(0)),
+        "File block must be cached after reading it"
+    );
 
     // Prevent fs drop after the test finish
     keos::fs::FileSystem::register(page_cache);
@@ -166,12 +401,10 @@ pub fn fastfilesystem() {
     f.read(0, &mut buffer)
         .expect("Reading file `file' must succeed");
 
-    let mut guard = page_cache.0.inner.lock();
     assert!(
-        cache_exists(&mut guard, f, FileBlockNumber(0)),
+        cache_exists(&page_cache, f, FileBlockNumber(0)),
         "File block must be cached after reading it"
     );
-    guard.unlock();
 
     // Prevent fs drop after the test finish
     keos::fs::FileSystem::register(page_cache);
@@ -196,12 +429,10 @@ pub fn simplefs() {
     f.read(0, &mut buffer)
         .expect("Reading file `os-release' must succeed");
 
-    let mut guard = page_cache.0.inner.lock();
     assert!(
-        cache_exists(&mut guard, f, FileBlockNumber(0)),
+        cache_exists(&page_cache, f, FileBlockNumber(0)),
         "File block must be cached after reading it"
     );
-    guard.unlock();
 
     // Prevent fs drop after the test finish
     keos::fs::FileSystem::register(page_cache);
@@ -253,3 +484,229 @@ pub fn writeback() {
         "After writeback of page cache, the disk content should be reflected"
     );
 }
+
+pub fn concurrent_readers() {
+    const BLOCKS: usize = 256;
+    const READERS: usize = 8;
+    static IN_DISK: AtomicUsize = AtomicUsize::new(0);
+    static PEAK: AtomicUsize = AtomicUsize::new(0);
+    static WAITED: AtomicBool = AtomicBool::new(false);
+
+    // Counts the readers reading the disk at once. The first reader to read
+    // the disk waits there for a while, so that another reader missing the
+    // cache joins it unless the misses are serialized.
+    let hook = Arc::new(|_sector: Sector, _data: &[u8; 512], write: bool| {
+        if write || with_current(|th| th.name != "reader") {
+            return Ok(());
+        }
+        PEAK.fetch_max(IN_DISK.fetch_add(1) + 1);
+        if !WAITED.swap(true) {
+            let start = now_ticks();
+            while PEAK.load() < 2 && now_ticks() - start < 20 {
+                core::hint::spin_loop();
+            }
+        }
+        IN_DISK.fetch_sub(1);
+        Ok(())
+    });
+    println!();
+    let ffs = ffs::FastFileSystem::from_disk(Disk::new(2).hook(hook), false, false).unwrap();
+    let fs: &dyn keos::fs::traits::FileSystem = &ffs;
+
+    let root = fs.root().expect("Root directory must be present");
+    let f: RegularFile = root
+        .create("page_cache__concurrent_readers", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    for fba in 0..BLOCKS {
+        f.write(fba * 0x1000, &[fba as u8; 4096]).unwrap();
+    }
+    f.writeback().unwrap();
+    drop(f);
+
+    let page_cache = PageCache::new(ffs);
+    let fs: &dyn keos::fs::traits::FileSystem = &page_cache;
+    let root = fs.root().expect("Root directory must be present");
+    let f: RegularFile = root
+        .open("page_cache__concurrent_readers")
+        .expect(
+            "Created file `page_cache__concurrent_readers' must be present on the root directory.",
+        )
+        .into_regular_file()
+        .expect("Created file `page_cache__concurrent_readers' must be a RegularFile");
+
+    // The first round misses the cache, and the later ones hit it.
+    let started = Arc::new(AtomicUsize::new(0));
+    for round in 0..4 {
+        let readers = (0..READERS)
+            .map(|_| {
+                let (f, started) = (f.clone(), started.clone());
+                ThreadBuilder::new("reader").spawn(move || {
+                    // Start reading at once.
+                    started.fetch_add(1);
+                    while started.load() < READERS * (round + 1) {
+                        core::hint::spin_loop();
+                    }
+                    let mut buf = alloc::vec![0u8; BLOCKS * 0x1000];
+                    assert_eq!(f.read(0, &mut buf).unwrap(), BLOCKS * 0x1000);
+                    for (fba, block) in buf.chunks(0x1000).enumerate() {
+                        assert!(
+                            block.iter().all(|b| *b == fba as u8),
+                            "Block {} of the file must be read intact",
+                            fba
+                        );
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for reader in readers {
+            assert_eq!(reader.join(), 0);
+        }
+    }
+
+    assert!(
+        PEAK.load() > 1,
+        "Readers missing the cache must read the disk in parallel (at most {} at once).",
+        PEAK.load()
+    );
+
+    // Prevent fs drop after the test finish
+    keos::fs::FileSystem::register(page_cache);
+}
+
+pub fn sharded_lock() {
+    const FILES: usize = 4;
+    const BLOCKS: usize = 8;
+    const ROUNDS: usize = 64;
+    // The order the readers read the blocks in. No block follows the one
+    // before it, so that the readahead stays disabled.
+    const ORDER: [usize; BLOCKS] = [0, 3, 6, 1, 4, 7, 2, 5];
+    println!();
+    let ffs = ffs::FastFileSystem::from_disk(Disk::new(2), false, false).unwrap();
+    let fs: &dyn keos::fs::traits::FileSystem = &ffs;
+
+    // Pick the files cached in distinct shards. All blocks of a file fall
+    // into the same shard, as the file is shorter than `SHARD_SPAN`.
+    let layout = PageCacheShards::new(SHARDS, ReplacementPolicy::Lru);
+    let root = fs.root().expect("Root directory must be present");
+    let mut shards = Vec::new();
+    let mut names = Vec::new();
+    for i in 0.. {
+        assert!(
+            i < SHARDS * 8,
+            "Files must be spread over the shards of the page cache."
+        );
+        let name = alloc::format!("page_cache__sharded_lock{}", i);
+        let f: RegularFile = root
+            .create(&name, false)
+            .unwrap()
+            .into_regular_file()
+            .unwrap();
+        let shard = layout.shard_of((f.ino(), FileBlockNumber(0)));
+        if shards.contains(&shard) {
+            continue;
+        }
+        for fba in 0..BLOCKS {
+            f.write(fba * 0x1000, &[(names.len() * BLOCKS + fba) as u8; 4096])
+                .unwrap();
+        }
+        f.writeback().unwrap();
+        shards.push(shard);
+        names.push(name);
+        if names.len() == FILES {
+            break;
+        }
+    }
+
+    // Each thread reads its own file over and over again, and counts how
+    // many times a thread waited for a shard held by another.
+    let contended = |page_cache: &PageCache<ffs::FastFileSystem>| {
+        let fs: &dyn keos::fs::traits::FileSystem = page_cache;
+        let root = fs.root().expect("Root directory must be present");
+        let files = names
+            .iter()
+            .map(|name| {
+                root.open(name)
+                    .expect("Created file must be present on the root directory.")
+                    .into_regular_file()
+                    .expect("Created file must be a RegularFile")
+            })
+            .collect::<Vec<RegularFile>>();
+
+        // Load the files into the cache. The first access starts a readahead
+        // of the blocks 1 to 4, which is over once they are cached. The rest
+        // are read out of order, which disables the readahead.
+        let mut buf = [0u8; 0x1000];
+        for f in files.iter() {
+            f.read(0, &mut buf).unwrap();
+            let mut waited = 0;
+            while !(1..=READAHEAD_INITIAL_WINDOW)
+                .all(|fba| cache_exists(page_cache, f.clone(), FileBlockNumber(fba)))
+            {
+                assert!(waited < 100, "The readahead must load the blocks 1 to 4.");
+                Current::sleep(1);
+                waited += 1;
+            }
+            for fba in [6, 5, 7] {
+                f.read(fba * 0x1000, &mut buf).unwrap();
+            }
+        }
+
+        let contended = page_cache.0.shards.contended();
+        let started = Arc::new(AtomicUsize::new(0));
+        let readers = files
+            .into_iter()
+            .enumerate()
+            .map(|(i, f)| {
+                let started = started.clone();
+                ThreadBuilder::new("reader").spawn(move || {
+                    // Start reading at once.
+                    started.fetch_add(1);
+                    while started.load() < FILES {
+                        core::hint::spin_loop();
+                    }
+                    let mut buf = [0u8; 0x1000];
+                    for _ in 0..ROUNDS {
+                        for fba in ORDER {
+                            assert_eq!(f.read(fba * 0x1000, &mut buf).unwrap(), 0x1000);
+                            assert!(
+                                buf.iter().all(|b| *b == (i * BLOCKS + fba) as u8),
+                                "Block {} of the file {} must be read intact",
+                                fba,
+                                i
+                            );
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for reader in readers {
+            assert_eq!(reader.join(), 0);
+        }
+        page_cache.0.shards.contended() - contended
+    };
+
+    let single = PageCache::with_shards(ffs.clone(), ReplacementPolicy::Lru, 1);
+    let single_contended = contended(&single);
+    drop(single);
+
+    let sharded = PageCache::with_shards(ffs, ReplacementPolicy::Lru, SHARDS);
+    let sharded_contended = contended(&sharded);
+
+    println!(
+        "Contended: {} with a single lock, {} with {} shards.",
+        single_contended, sharded_contended, SHARDS
+    );
+    assert!(
+        single_contended > 0,
+        "Readers of the different files must contend for the single lock."
+    );
+    assert_eq!(
+        sharded_contended, 0,
+        "Readers of the files in the different shards must not contend."
+    );
+
+    // Prevent fs drop after the test finish
+    keos::fs::FileSystem::register(sharded);
+}
@@ -1,8 +1,20 @@
-use alloc::boxed::Box;
+use alloc::{boxed::Box, collections::BTreeSet, format, string::String, vec, vec::Vec};
+use core::ffi::CStr;
 use grading::syscall;
-use keos::{KernelError, addressing::Va, fs::FileSystem};
-use keos_project1::file_struct::FileStruct;
-use keos_project5::{ACCESS_CHECK_BYPASS_LIST, SyscallNumber};
+use keos::{
+    KernelError,
+    addressing::Va,
+    fs::{Disk, FileBlockNumber, FileSystem, RegularFile, Sector},
+    syscall::flags::{FileMode, O_DIRECT, O_NONBLOCK, SEEK_DATA, SEEK_HOLE},
+    teletype::Tty,
+    thread::{Current, ThreadBuilder},
+};
+use keos_project1::file_struct::{FileStruct, IoStats};
+use keos_project5::{
+    ACCESS_CHECK_BYPASS_LIST, SyscallNumber,
+    advanced_file_structs::{Dentry, Stat},
+    ffs,
+};
 
 struct AccessCheckBypasser<T> {
     inner: *const T,
@@ -124,10 +136,95 @@ pub fn dir_seek() {
         );
     }
 
-    assert_eq!(
-        syscall!(SyscallNumber::Seek as usize, fd, 0, 0).try_into(),
-        Ok(KernelError::InvalidArgument),
+    // Rewinding the directory is the only valid seek.
+    assert_eq!(syscall!(SyscallNumber::Seek as usize, fd, 0, 0), 0);
+}
+
+pub fn readdir_large() {
+    const FILES: usize = 500;
+    const BATCH: usize = 16;
+
+    let root = FileSystem::root();
+    let dir = root
+        .create("readdir_large", true)
+        .unwrap()
+        .into_directory()
+        .unwrap();
+    for i in 0..FILES {
+        dir.create(&format!("f{i}"), false).unwrap();
+    }
+
+    let fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"readdir_large".as_ptr(), 14)
+            .unwrap()
+            .as_ptr(),
+        0
     );
+    assert!(fd >= 3, "Opening the directory must succeed.");
+
+    let readdir = || {
+        let mut dentries = Box::new(
+            [Dentry {
+                ino: 0,
+                name: [0; 256],
+            }; BATCH],
+        );
+        let n = syscall!(
+            SyscallNumber::Readdir as usize,
+            fd,
+            AccessCheckBypasser::new(dentries.as_mut_ptr(), BATCH)
+                .unwrap()
+                .as_ptr(),
+            BATCH
+        );
+        assert!(
+            (0..=BATCH as isize).contains(&n),
+            "Reading the directory must succeed."
+        );
+        dentries[..n as usize]
+            .iter()
+            .map(|en| {
+                let len = en.name.iter().position(|b| *b == 0).unwrap();
+                String::from(core::str::from_utf8(&en.name[..len]).unwrap())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let first = readdir();
+    assert_eq!(first.len(), BATCH);
+    let mut seen = BTreeSet::new();
+    seen.extend(first.iter().cloned());
+
+    // An entry removed in the middle of the iteration must not be read.
+    let victim = (0..FILES)
+        .map(|i| format!("f{i}"))
+        .find(|name| !seen.contains(name))
+        .unwrap();
+    dir.unlink(&victim).unwrap();
+
+    loop {
+        let entries = readdir();
+        if entries.is_empty() {
+            break;
+        }
+        for name in entries {
+            assert!(seen.insert(name), "An entry must be read exactly once.");
+        }
+    }
+
+    let mut expected = (0..FILES)
+        .map(|i| format!("f{i}"))
+        .filter(|name| name != &victim)
+        .collect::<BTreeSet<_>>();
+    expected.insert(String::from("."));
+    expected.insert(String::from(".."));
+    assert_eq!(seen, expected);
+
+    // Rewinding restarts the iteration.
+    assert_eq!(syscall!(SyscallNumber::Seek as usize, fd, 0, 0), 0);
+    assert_eq!(readdir(), first);
+    assert_eq!(syscall!(SyscallNumber::Close as usize, fd), 0);
 }
 
 pub fn create() {
@@ -275,3 +372,1045 @@ pub fn chdir() {
         "After chdir() to the directory `chdir__dir', cwd must be `chdir__dir'."
     );
 }
+
+pub fn path_resolution() {
+    let root = FileSystem::root();
+    let file_struct = unsafe {
+        &*(syscall!(SyscallNumber::GetPhys as usize, 0, 0x80041337u32 as i32) as usize
+            as *const FileStruct)
+    };
+    let on_path = |no: SyscallNumber, path: &CStr| {
+        syscall!(
+            no as usize,
+            AccessCheckBypasser::new(path.as_ptr(), path.count_bytes() + 1)
+                .unwrap()
+                .as_ptr(),
+            0
+        )
+    };
+
+    // Repeated and trailing slashes are ignored.
+    assert_eq!(on_path(SyscallNumber::Mkdir, c"//paths//"), 0);
+    let a = root
+        .open("paths")
+        .unwrap()
+        .into_directory()
+        .expect("`//paths//' must be created as `/paths'.");
+    assert_eq!(root.open("//paths//").unwrap().ino(), a.ino());
+
+    // Every component before `..` must exist.
+    assert_eq!(
+        on_path(SyscallNumber::Mkdir, c"/paths/./b/../b").try_into(),
+        Ok(KernelError::NoSuchEntry)
+    );
+    assert_eq!(on_path(SyscallNumber::Mkdir, c"/paths/./b"), 0);
+    assert_eq!(on_path(SyscallNumber::Create, c"/paths/./b/../b/c"), 0);
+    let c = root
+        .open("paths/b/c")
+        .unwrap()
+        .into_regular_file()
+        .expect("`/paths/./b/../b/c' must be created as `/paths/b/c'.");
+    assert_eq!(root.open("/paths/./b/../b/c").unwrap().ino(), c.ino());
+    assert_eq!(
+        root.open("/paths/b/c/../c").map(|_| ()),
+        Err(KernelError::NotDirectory)
+    );
+
+    // `..` at the root is the root itself.
+    assert_eq!(root.open("/..").unwrap().ino(), root.ino());
+    assert_eq!(root.open("/../../paths/..").unwrap().ino(), root.ino());
+    assert_eq!(
+        on_path(SyscallNumber::Mkdir, c"/..").try_into(),
+        Ok(KernelError::FileExist)
+    );
+
+    // A path with a trailing slash or `.` must name a directory.
+    let fd = on_path(SyscallNumber::Open, c"/paths/./b/../b/c");
+    assert!(fd >= 3, "Opening `/paths/./b/../b/c' must succeed.");
+    assert_eq!(syscall!(SyscallNumber::Close as usize, fd), 0);
+    for path in [c"/paths/b/c/", c"/paths/b/c/."] {
+        assert_eq!(
+            on_path(SyscallNumber::Open, path).try_into(),
+            Ok(KernelError::NotDirectory)
+        );
+    }
+
+    // Relative paths are resolved from the current working directory.
+    assert_eq!(on_path(SyscallNumber::Chdir, c"paths//b/"), 0);
+    assert_eq!(file_struct.cwd.ino(), root.open("paths/b").unwrap().ino());
+    let fd = on_path(SyscallNumber::Open, c"../b/./c");
+    assert!(fd >= 3, "Opening `../b/./c' from `/paths/b' must succeed.");
+    assert_eq!(syscall!(SyscallNumber::Close as usize, fd), 0);
+    assert_eq!(on_path(SyscallNumber::Chdir, c".."), 0);
+    assert_eq!(file_struct.cwd.ino(), a.ino());
+    assert_eq!(on_path(SyscallNumber::Chdir, c"../../.."), 0);
+    assert_eq!(file_struct.cwd.ino(), root.ino());
+
+    assert_eq!(
+        on_path(SyscallNumber::Unlink, c"/paths/b/..").try_into(),
+        Ok(KernelError::Busy)
+    );
+    assert_eq!(
+        on_path(SyscallNumber::Unlink, c"/paths/b/c/").try_into(),
+        Ok(KernelError::NotDirectory)
+    );
+    assert_eq!(on_path(SyscallNumber::Unlink, c"//paths/b/./c"), 0);
+    assert_eq!(
+        root.open("paths/b/c").map(|_| ()),
+        Err(KernelError::NoSuchEntry)
+    );
+    assert_eq!(on_path(SyscallNumber::Unlink, c"paths/b/"), 0);
+    assert_eq!(on_path(SyscallNumber::Unlink, c"paths"), 0);
+}
+
+pub fn iostat() {
+    let root = FileSystem::root();
+
+    let file = root
+        .create("iostat", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    let data = Box::new([0x42u8; 0x3000]);
+    assert_eq!(file.write(0, &*data), Ok(0x3000));
+
+    let fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"iostat".as_ptr(), 7)
+            .unwrap()
+            .as_ptr(),
+        2
+    );
+    assert!(fd >= 3, "Opening the file must succeed.");
+
+    let query = || {
+        let mut stats = IoStats::default();
+        assert_eq!(
+            syscall!(
+                SyscallNumber::IoStat as usize,
+                AccessCheckBypasser::new(&raw mut stats, 1)
+                    .unwrap()
+                    .as_ptr()
+            ),
+            0
+        );
+        stats
+    };
+
+    let base = query();
+    let buf = Box::new([0u8; 0x3000]);
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Read as usize,
+            fd,
+            AccessCheckBypasser::new(&*buf, 1).unwrap().as_ptr(),
+            0x3000
+        ),
+        0x3000
+    );
+    let after_read = query();
+    assert_eq!(after_read.bytes_read - base.bytes_read, 0x3000);
+    assert_eq!(after_read.read_calls - base.read_calls, 1);
+    assert_eq!(after_read.bytes_written, base.bytes_written);
+    assert_eq!(after_read.write_calls, base.write_calls);
+
+    // Every block is cached by now; reading again must only hit the cache.
+    assert_eq!(syscall!(SyscallNumber::Seek as usize, fd, 0, 0), 0);
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Read as usize,
+            fd,
+            AccessCheckBypasser::new(&*buf, 1).unwrap().as_ptr(),
+            0x3000
+        ),
+        0x3000
+    );
+    let reread = query();
+    assert!(
+        reread.cache_hits - after_read.cache_hits >= 3,
+        "Re-reading cached blocks must be accounted as cache hits."
+    );
+    assert_eq!(
+        reread.cache_misses, after_read.cache_misses,
+        "Re-reading cached blocks must not miss the cache."
+    );
+
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Write as usize,
+            fd,
+            AccessCheckBypasser::new(&*buf, 1).unwrap().as_ptr(),
+            100
+        ),
+        100
+    );
+    let after_write = query();
+    assert_eq!(after_write.bytes_written - reread.bytes_written, 100);
+    assert_eq!(after_write.write_calls - reread.write_calls, 1);
+    assert_eq!(after_write.bytes_read, reread.bytes_read);
+}
+
+pub fn read_eof() {
+    let root = FileSystem::root();
+
+    let file = root
+        .create("read_eof", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    assert_eq!(file.write(0, &[0x11u8; 5000]), Ok(5000));
+
+    let fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"read_eof".as_ptr(), 9)
+            .unwrap()
+            .as_ptr(),
+        0
+    );
+    assert!(fd >= 3, "Opening the file must succeed.");
+
+    let mut buf = Box::new([0u8; 0x2000]);
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Read as usize,
+            fd,
+            AccessCheckBypasser::new(&*buf, 1).unwrap().as_ptr(),
+            0x2000
+        ),
+        5000,
+        "Reading beyond the end of file should return the remaining bytes."
+    );
+    assert!(buf[..5000].iter().all(|b| *b == 0x11));
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Read as usize,
+            fd,
+            AccessCheckBypasser::new(&*buf, 1).unwrap().as_ptr(),
+            0x2000
+        ),
+        0,
+        "Reading at the end of file should return 0 (EOF)."
+    );
+
+    // Extend the file through another handle on another thread.
+    ThreadBuilder::new("read_eof_writer")
+        .spawn(|| {
+            let file = FileSystem::root()
+                .open("read_eof")
+                .unwrap()
+                .into_regular_file()
+                .unwrap();
+            assert_eq!(file.write(5000, &[0x22u8; 100]), Ok(100));
+        })
+        .join();
+
+    buf.fill(0);
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Read as usize,
+            fd,
+            AccessCheckBypasser::new(&*buf, 1).unwrap().as_ptr(),
+            0x2000
+        ),
+        100,
+        "Reading after the file is extended should return the new bytes."
+    );
+    assert!(buf[..100].iter().all(|b| *b == 0x22));
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Read as usize,
+            fd,
+            AccessCheckBypasser::new(&*buf, 1).unwrap().as_ptr(),
+            0x2000
+        ),
+        0,
+        "Reading at the end of the extended file should return 0 (EOF)."
+    );
+}
+
+pub fn open_direct() {
+    let root = FileSystem::root();
+
+    root.create("open_direct", false).unwrap();
+
+    let fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"open_direct".as_ptr(), 12)
+            .unwrap()
+            .as_ptr(),
+        FileMode::ReadWrite as usize | O_DIRECT
+    );
+    assert!(fd >= 3, "Opening the file with O_DIRECT must succeed.");
+
+    let mut buf = Box::new([0x33u8; 0x2000]);
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Write as usize,
+            fd,
+            AccessCheckBypasser::new(&*buf, 1).unwrap().as_ptr(),
+            5000
+        ),
+        5000
+    );
+    assert_eq!(syscall!(SyscallNumber::Seek as usize, fd, 0, 0), 0);
+
+    buf.fill(0);
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Read as usize,
+            fd,
+            AccessCheckBypasser::new(&*buf, 1).unwrap().as_ptr(),
+            0x2000
+        ),
+        5000,
+        "Reading beyond the end of file should return the remaining bytes."
+    );
+    assert!(buf[..5000].iter().all(|b| *b == 0x33));
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Read as usize,
+            fd,
+            AccessCheckBypasser::new(&*buf, 1).unwrap().as_ptr(),
+            0x2000
+        ),
+        0,
+        "Reading at the end of file should return 0 (EOF)."
+    );
+}
+
+pub fn mkfifo() {
+    let root = FileSystem::root();
+
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Mkfifo as usize,
+            AccessCheckBypasser::new(c"mkfifo".as_ptr(), 7)
+                .unwrap()
+                .as_ptr()
+        ),
+        0
+    );
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Mkfifo as usize,
+            AccessCheckBypasser::new(c"mkfifo".as_ptr(), 7)
+                .unwrap()
+                .as_ptr()
+        )
+        .try_into(),
+        Ok(KernelError::FileExist),
+    );
+    assert!(
+        root.open("mkfifo").unwrap().into_fifo().is_some(),
+        "The created entry must be a FIFO."
+    );
+
+    // Open the write end on another thread. Opening blocks until the read end
+    // is opened below.
+    let writer = ThreadBuilder::new("mkfifo_writer").spawn(|| {
+        let tx = FileSystem::root()
+            .open("mkfifo")
+            .unwrap()
+            .into_fifo()
+            .unwrap()
+            .open_write();
+        for i in 0..100u8 {
+            assert!(tx.send(i).is_ok());
+        }
+    });
+
+    let fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"mkfifo".as_ptr(), 7)
+            .unwrap()
+            .as_ptr(),
+        0
+    );
+    assert!(fd >= 3, "Opening the FIFO must succeed.");
+
+    let buf = Box::new([0u8; 100]);
+    let mut read = 0;
+    while read < 100 {
+        let n = syscall!(
+            SyscallNumber::Read as usize,
+            fd,
+            AccessCheckBypasser::new(&buf[read as usize], 1)
+                .unwrap()
+                .as_ptr(),
+            100 - read
+        );
+        assert!(0 < n && n <= 100 - read, "Reading the FIFO must succeed.");
+        read += n;
+    }
+    writer.join();
+    assert!(buf.iter().enumerate().all(|(i, b)| *b == i as u8));
+
+    // The read end is still open, so a later writer does not wait, and joins
+    // the same pipe.
+    let tx = FileSystem::root()
+        .open("mkfifo")
+        .unwrap()
+        .into_fifo()
+        .unwrap()
+        .open_write();
+    assert!(tx.send(42).is_ok());
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Read as usize,
+            fd,
+            AccessCheckBypasser::new(&buf[0], 1).unwrap().as_ptr(),
+            1
+        ),
+        1,
+        "The later writer must share the pipe of the open reader."
+    );
+    assert_eq!(buf[0], 42);
+    assert_eq!(syscall!(SyscallNumber::Close as usize, fd), 0);
+}
+
+pub fn tty() {
+    let open = |path: &CStr, flags: usize| {
+        syscall!(
+            SyscallNumber::Open as usize,
+            AccessCheckBypasser::new(path.as_ptr(), path.count_bytes() + 1)
+                .unwrap()
+                .as_ptr(),
+            flags
+        )
+    };
+    let tty0 = Tty::get(0).unwrap();
+    let tty1 = Tty::get(1).unwrap();
+    tty0.take_output();
+    tty1.take_output();
+
+    // Each process writes to its own console.
+    let out0 = open(c"/dev/tty0", 1);
+    let out1 = open(c"/dev/tty1", 1);
+    assert!(out0 >= 3 && out1 >= 3, "Opening the consoles must succeed.");
+    for (fd, data) in [(out0, b"zero".as_slice()), (out1, b"one".as_slice())] {
+        assert_eq!(
+            syscall!(
+                SyscallNumber::Write as usize,
+                fd,
+                AccessCheckBypasser::new(data.as_ptr(), data.len())
+                    .unwrap()
+                    .as_ptr(),
+                data.len()
+            ),
+            data.len() as isize,
+            "Writing to a console must succeed."
+        );
+    }
+    assert_eq!(
+        tty0.take_output(),
+        b"zero",
+        "tty0 must get only its output."
+    );
+    assert_eq!(tty1.take_output(), b"one", "tty1 must get only its output.");
+
+    // The input typed on a console is read only from that console.
+    tty1.input(b"one");
+    tty0.input(b"zero");
+    for (path, expected) in [(c"/dev/tty0", b"zero".as_slice()), (c"/dev/tty1", b"one")] {
+        let fd = open(path, 0);
+        assert!(fd >= 3, "Opening the consoles must succeed.");
+        let buf = Box::new([0u8; 4]);
+        let mut read = 0;
+        while read < expected.len() {
+            let n = syscall!(
+                SyscallNumber::Read as usize,
+                fd,
+                AccessCheckBypasser::new(&buf[read], 1).unwrap().as_ptr(),
+                expected.len() - read
+            );
+            assert!(
+                0 < n && n as usize <= expected.len() - read,
+                "Reading a console must succeed."
+            );
+            read += n as usize;
+        }
+        assert_eq!(&buf[..read], expected);
+        assert_eq!(syscall!(SyscallNumber::Close as usize, fd), 0);
+    }
+
+    // Only the existing consoles are device files.
+    assert_eq!(
+        open(c"/dev/tty2", 1).try_into(),
+        Ok(KernelError::NoSuchEntry)
+    );
+    assert_eq!(syscall!(SyscallNumber::Close as usize, out0), 0);
+    assert_eq!(syscall!(SyscallNumber::Close as usize, out1), 0);
+}
+
+pub fn stat() {
+    let root = FileSystem::root();
+    root.create("stat", false).unwrap();
+
+    let stat = || {
+        let mut stat = Stat::default();
+        assert_eq!(
+            syscall!(
+                SyscallNumber::Stat as usize,
+                AccessCheckBypasser::new(c"stat".as_ptr(), 5)
+                    .unwrap()
+                    .as_ptr(),
+                AccessCheckBypasser::new(&raw mut stat, 1).unwrap().as_ptr()
+            ),
+            0
+        );
+        stat
+    };
+
+    let created = stat();
+    assert_eq!(created.ty, 0, "`stat' must be a regular file.");
+    assert_eq!(created.size, 0);
+    assert_eq!(created.blocks, 0);
+    assert_eq!(created.link_count, 1);
+    assert_eq!(
+        created.inode,
+        root.open("stat").unwrap().ino().into_u32() as u64
+    );
+
+    // Let the clock advance, so that the write changes the time.
+    Current::sleep(2);
+    let fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"stat".as_ptr(), 5)
+            .unwrap()
+            .as_ptr(),
+        2
+    );
+    assert!(fd >= 3, "Opening the file must succeed.");
+    let data = Box::new([0x42u8; 0x1800]);
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Write as usize,
+            fd,
+            AccessCheckBypasser::new(&*data, 1).unwrap().as_ptr(),
+            0x1800
+        ),
+        0x1800
+    );
+
+    let written = stat();
+    assert_eq!(written.size, 0x1800);
+    assert_eq!(written.blocks, 2);
+    assert!(
+        written.mtime > created.mtime,
+        "A write must update the modification time."
+    );
+    assert_eq!(syscall!(SyscallNumber::Close as usize, fd), 0);
+}
+
+// Returns the number of the data blocks in use on the disk.
+fn blocks_in_use() -> u64 {
+    let ffs = ffs::FastFileSystem::from_disk(Disk::new(2), false, false).unwrap();
+    ffs.0.sb.read().block_count_inused
+}
+
+pub fn fallocate() {
+    const LEN: usize = 0x100000;
+
+    let root = FileSystem::root();
+    let file = root
+        .create("fallocate", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    let fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"fallocate".as_ptr(), 10)
+            .unwrap()
+            .as_ptr(),
+        2
+    );
+    assert!(fd >= 3, "Opening the file must succeed.");
+
+    let before = blocks_in_use();
+    assert_eq!(
+        syscall!(SyscallNumber::Fallocate as usize, fd, 0, 0).try_into(),
+        Ok(KernelError::InvalidArgument),
+    );
+    assert_eq!(syscall!(SyscallNumber::Fallocate as usize, fd, 0, LEN), 0);
+    assert_eq!(file.size(), LEN);
+    assert!(
+        blocks_in_use() >= before + (LEN / 0x1000) as u64,
+        "The blocks must be allocated on the disk."
+    );
+
+    // Allocating a range within the file changes nothing.
+    let allocated = blocks_in_use();
+    assert_eq!(
+        syscall!(SyscallNumber::Fallocate as usize, fd, 0x1000, 0x1000),
+        0
+    );
+    assert_eq!(file.size(), LEN);
+    assert_eq!(blocks_in_use(), allocated);
+
+    let mut buf = Box::new([0xffu8; 0x1000]);
+    for _ in 0..LEN / 0x1000 {
+        assert_eq!(
+            syscall!(
+                SyscallNumber::Read as usize,
+                fd,
+                AccessCheckBypasser::new(&raw mut *buf, 1).unwrap().as_ptr(),
+                0x1000
+            ),
+            0x1000
+        );
+        assert!(
+            buf.iter().all(|b| *b == 0),
+            "The allocated blocks must read back as zeros."
+        );
+        buf.fill(0xff);
+    }
+    assert_eq!(syscall!(SyscallNumber::Close as usize, fd), 0);
+
+    // The file is opened read-only.
+    let fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"fallocate".as_ptr(), 10)
+            .unwrap()
+            .as_ptr(),
+        0
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Fallocate as usize, fd, 0, 2 * LEN).try_into(),
+        Ok(KernelError::BadFileDescriptor),
+    );
+    assert_eq!(syscall!(SyscallNumber::Close as usize, fd), 0);
+}
+
+pub fn seek_hole_data() {
+    const SIZE: usize = 0x20001;
+
+    let root = FileSystem::root();
+    let file = root
+        .create("seek_hole_data", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    // Data in [0, 0x1000), [0x5000, 0x6000) and [0x20000, SIZE), with holes
+    // in between.
+    let buf = Box::new([0x42u8; 0x1000]);
+    assert_eq!(file.write(0, &*buf), Ok(0x1000));
+    assert_eq!(file.write(0x5000, &*buf), Ok(0x1000));
+    assert_eq!(file.write(0x20000, b"x"), Ok(1));
+    assert_eq!(file.size(), SIZE);
+
+    let fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"seek_hole_data".as_ptr(), 15)
+            .unwrap()
+            .as_ptr(),
+        0
+    );
+    assert!(fd >= 3, "Opening the file must succeed.");
+
+    for (ofs, whence, expected) in [
+        (0, SEEK_DATA, 0),
+        (0, SEEK_HOLE, 0x1000),
+        (0x800, SEEK_DATA, 0x800),
+        (0x800, SEEK_HOLE, 0x1000),
+        (0x1000, SEEK_HOLE, 0x1000),
+        (0x1000, SEEK_DATA, 0x5000),
+        (0x3210, SEEK_DATA, 0x5000),
+        (0x5123, SEEK_DATA, 0x5123),
+        (0x5000, SEEK_HOLE, 0x6000),
+        (0x6000, SEEK_DATA, 0x20000),
+        // The end of the file is a hole.
+        (0x20000, SEEK_HOLE, SIZE),
+    ] {
+        assert_eq!(
+            syscall!(SyscallNumber::Seek as usize, fd, ofs, whence),
+            expected as isize,
+            "seek({ofs:#x}, {whence}) must return {expected:#x}"
+        );
+        assert_eq!(
+            syscall!(SyscallNumber::Tell as usize, fd),
+            expected as isize
+        );
+    }
+
+    // Nothing is beyond the end of the file.
+    for whence in [SEEK_DATA, SEEK_HOLE] {
+        assert_eq!(
+            syscall!(SyscallNumber::Seek as usize, fd, SIZE, whence).try_into(),
+            Ok(KernelError::NoSuchDeviceOrAddress),
+        );
+    }
+    assert_eq!(syscall!(SyscallNumber::Close as usize, fd), 0);
+
+    drop(file);
+    root.unlink("seek_hole_data").unwrap();
+}
+
+pub fn sendfile() {
+    const LEN: usize = 0x2345;
+    const PIPE: usize = 0x100;
+
+    let root = FileSystem::root();
+    let src = root
+        .create("sendfile_src", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    let data = (0..LEN)
+        .map(|i| (i * 7 + i / 256) as u8)
+        .collect::<Vec<_>>();
+    assert_eq!(src.write(0, &data), Ok(LEN));
+    root.create("sendfile_dst", false).unwrap();
+
+    let in_fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"sendfile_src".as_ptr(), 13)
+            .unwrap()
+            .as_ptr(),
+        0
+    );
+    let out_fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"sendfile_dst".as_ptr(), 13)
+            .unwrap()
+            .as_ptr(),
+        2
+    );
+    assert!(in_fd >= 3 && out_fd >= 3, "Opening the files must succeed.");
+
+    // The input must be readable, and the output must be writable.
+    assert_eq!(
+        syscall!(SyscallNumber::Sendfile as usize, in_fd, in_fd, LEN).try_into(),
+        Ok(KernelError::BadFileDescriptor),
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Sendfile as usize, out_fd, 0, LEN).try_into(),
+        Ok(KernelError::InvalidArgument),
+    );
+
+    // The transfer stops at the end of the input.
+    assert_eq!(
+        syscall!(SyscallNumber::Sendfile as usize, out_fd, in_fd, 2 * LEN),
+        LEN as isize
+    );
+    assert_eq!(syscall!(SyscallNumber::Tell as usize, in_fd), LEN as isize);
+    assert_eq!(syscall!(SyscallNumber::Tell as usize, out_fd), LEN as isize);
+    assert_eq!(
+        syscall!(SyscallNumber::Sendfile as usize, out_fd, in_fd, LEN),
+        0
+    );
+
+    let mut buf = vec![0u8; LEN];
+    assert_eq!(syscall!(SyscallNumber::Seek as usize, out_fd, 0, 0), 0);
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Read as usize,
+            out_fd,
+            AccessCheckBypasser::new(buf.as_mut_ptr(), LEN)
+                .unwrap()
+                .as_ptr(),
+            LEN
+        ),
+        LEN as isize
+    );
+    assert!(buf == data, "The copy must match the source byte-for-byte.");
+
+    // A non-blocking pipe takes only the bytes that fit in it.
+    let mut fds = [0i32; 2];
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Pipe2 as usize,
+            AccessCheckBypasser::new(&raw mut fds, 1).unwrap().as_ptr(),
+            O_NONBLOCK,
+            PIPE
+        ),
+        0
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Seek as usize, in_fd, 0x1000, 0),
+        0x1000
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Sendfile as usize, fds[1], in_fd, LEN),
+        PIPE as isize
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Tell as usize, in_fd),
+        (0x1000 + PIPE) as isize
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Sendfile as usize, fds[1], in_fd, LEN).try_into(),
+        Ok(KernelError::Busy),
+    );
+    let mut piped = [0u8; PIPE];
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Read as usize,
+            fds[0],
+            AccessCheckBypasser::new(&raw mut piped, 1)
+                .unwrap()
+                .as_ptr(),
+            PIPE
+        ),
+        PIPE as isize
+    );
+    assert_eq!(piped, data[0x1000..0x1000 + PIPE]);
+
+    for fd in [in_fd, out_fd, fds[0] as isize, fds[1] as isize] {
+        assert_eq!(syscall!(SyscallNumber::Close as usize, fd), 0);
+    }
+}
+
+pub fn chmod() {
+    let root = FileSystem::root();
+    let file = root
+        .create("chmod", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    assert_eq!(file.mode(), 0o700, "A new file must be owned with rwx.");
+    let open = |mode: usize| {
+        syscall!(
+            SyscallNumber::Open as usize,
+            AccessCheckBypasser::new(c"chmod".as_ptr(), 6)
+                .unwrap()
+                .as_ptr(),
+            mode
+        )
+    };
+    let chmod = |mode: usize| {
+        syscall!(
+            SyscallNumber::Chmod as usize,
+            AccessCheckBypasser::new(c"chmod".as_ptr(), 6)
+                .unwrap()
+                .as_ptr(),
+            mode
+        )
+    };
+
+    let fd = open(2);
+    assert!(fd >= 3, "Opening the file must succeed.");
+
+    // Make the file read-only. Only the owner bits are kept.
+    assert_eq!(chmod(0o444), 0);
+    assert_eq!(file.mode(), 0o400);
+    assert_eq!(open(1).try_into(), Ok(KernelError::InvalidAccess));
+    assert_eq!(open(2).try_into(), Ok(KernelError::InvalidAccess));
+    let rfd = open(0);
+    assert!(rfd >= 3, "Opening the file for read must succeed.");
+
+    // The file opened before the chmod is still writable.
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Write as usize,
+            fd,
+            AccessCheckBypasser::new(b"KeOS".as_ptr(), 4)
+                .unwrap()
+                .as_ptr(),
+            4
+        ),
+        4
+    );
+
+    assert_eq!(chmod(0o4700).try_into(), Ok(KernelError::InvalidArgument));
+    assert_eq!(chmod(0o200), 0);
+    assert_eq!(open(0).try_into(), Ok(KernelError::InvalidAccess));
+    let wfd = open(1);
+    assert!(wfd >= 3, "Opening the file for write must succeed.");
+    assert_eq!(chmod(0o600), 0);
+
+    for fd in [fd, rfd, wfd] {
+        assert_eq!(syscall!(SyscallNumber::Close as usize, fd), 0);
+    }
+}
+
+// Returns the first sector of the block `fba` of the `file` on the disk.
+fn disk_sector(file: &RegularFile, fba: usize) -> Sector {
+    let ffs = ffs::FastFileSystem::from_disk(Disk::new(2), false, false).unwrap();
+    ffs.get_inode(file.ino())
+        .unwrap()
+        .read()
+        .get(&ffs.0, FileBlockNumber(fba))
+        .unwrap()
+        .unwrap()
+        .into_sector()
+}
+
+// Reads the first sector of the block `fba` of the `file` from the disk,
+// bypassing the page cache.
+fn read_disk(file: &RegularFile, fba: usize) -> [u8; 512] {
+    let mut buf = [0u8; 512];
+    Disk::new(2).read(disk_sector(file, fba), &mut buf).unwrap();
+    buf
+}
+
+pub fn msync() {
+    let root = FileSystem::root();
+
+    let file = root
+        .create("msync", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    file.write(0, &[0u8; 0x2000]).unwrap();
+    file.writeback().unwrap();
+
+    let fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"msync".as_ptr(), 6)
+            .unwrap()
+            .as_ptr(),
+        FileMode::ReadWrite as usize
+    );
+    assert!(fd >= 3, "Opening the file must succeed.");
+
+    let addr = 0x4000_0000usize;
+    assert_eq!(
+        syscall!(SyscallNumber::Mmap as usize, addr, 0x2000, 0x3, fd, 0),
+        addr as isize,
+        "Mapping the file must succeed."
+    );
+    unsafe {
+        core::ptr::copy_nonoverlapping(b"Synced by msync!".as_ptr(), addr as *mut u8, 16);
+        core::ptr::copy_nonoverlapping(b"Second page".as_ptr(), (addr + 0x1000) as *mut u8, 11);
+    }
+
+    assert_ne!(
+        &read_disk(&file, 0)[..16],
+        b"Synced by msync!",
+        "Before msync, the disk content should be left intact."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Msync as usize, addr, 0x1000),
+        0,
+        "msync on the first page must succeed."
+    );
+    assert_eq!(
+        &read_disk(&file, 0)[..16],
+        b"Synced by msync!",
+        "After msync, the disk content should be reflected."
+    );
+    assert_ne!(
+        &read_disk(&file, 1)[..11],
+        b"Second page",
+        "msync must not write back the pages out of the given region."
+    );
+    assert_eq!(syscall!(SyscallNumber::Msync as usize, addr, 0x2000), 0);
+    assert_eq!(
+        &read_disk(&file, 1)[..11],
+        b"Second page",
+        "After msync, the disk content should be reflected."
+    );
+
+    assert_eq!(
+        syscall!(SyscallNumber::Msync as usize, addr + 1, 0x1000).try_into(),
+        Ok(KernelError::InvalidArgument),
+        "msync on an unaligned address must fail."
+    );
+    assert_eq!(
+        syscall!(SyscallNumber::Msync as usize, addr + 0x2000, 0x1000).try_into(),
+        Ok(KernelError::BadAddress),
+        "msync on an unmapped region must fail."
+    );
+
+    // msync on an anonymous mapping does nothing.
+    let anon = addr + 0x10_0000;
+    assert_eq!(
+        syscall!(
+            SyscallNumber::Mmap as usize,
+            anon,
+            0x1000,
+            0x3,
+            usize::MAX,
+            0
+        ),
+        anon as isize,
+        "Anonymous mapping must succeed."
+    );
+    unsafe { (anon as *mut u8).write(0x77) };
+    assert_eq!(syscall!(SyscallNumber::Msync as usize, anon, 0x1000), 0);
+}
+
+pub fn mmap_dirty() {
+    let root = FileSystem::root();
+
+    let file = root
+        .create("mmap_dirty", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    file.write(0, &[0x11u8; 0x4000]).unwrap();
+    file.writeback().unwrap();
+
+    let fd = syscall!(
+        SyscallNumber::Open as usize,
+        AccessCheckBypasser::new(c"mmap_dirty".as_ptr(), 11)
+            .unwrap()
+            .as_ptr(),
+        FileMode::ReadWrite as usize
+    );
+    assert!(fd >= 3, "Opening the file must succeed.");
+
+    let addr = 0x4100_0000usize;
+    assert_eq!(
+        syscall!(SyscallNumber::Mmap as usize, addr, 0x4000, 0x3, fd, 0),
+        addr as isize,
+        "Mapping the file must succeed."
+    );
+    unsafe {
+        // Page 0 is only read, page 1 is written, page 2 is written after
+        // read, and page 3 is never accessed.
+        assert_eq!((addr as *const u8).read_volatile(), 0x11);
+        ((addr + 0x1000) as *mut u8).write_volatile(0x22);
+        assert_eq!(((addr + 0x2000) as *const u8).read_volatile(), 0x11);
+        ((addr + 0x2000) as *mut u8).write_volatile(0x33);
+    }
+
+    // Overwrite the clean blocks on the disk behind the page cache. They are
+    // left intact if `fsync` writes back only the modified blocks.
+    for fba in [0, 3] {
+        Disk::new(2)
+            .write(disk_sector(&file, fba), &[0xeeu8; 512])
+            .unwrap();
+    }
+
+    assert_eq!(syscall!(SyscallNumber::Fsync as usize, fd), 0);
+    assert_eq!(
+        read_disk(&file, 1)[0],
+        0x22,
+        "A block written through the mapping must be written back."
+    );
+    assert_eq!(
+        read_disk(&file, 2)[0],
+        0x33,
+        "A block written after read through the mapping must be written back."
+    );
+    assert!(
+        read_disk(&file, 0).iter().all(|b| *b == 0xee),
+        "A block only read through the mapping must not be written back."
+    );
+    assert!(
+        read_disk(&file, 3).iter().all(|b| *b == 0xee),
+        "A block not accessed through the mapping must not be written back."
+    );
+
+    // The page is writable now, so the next write does not fault. It must be
+    // written back as well.
+    unsafe { ((addr + 0x1000) as *mut u8).write_volatile(0x44) };
+    assert_eq!(syscall!(SyscallNumber::Fsync as usize, fd), 0);
+    assert_eq!(
+        read_disk(&file, 1)[0],
+        0x44,
+        "A block written again after fsync must be written back."
+    );
+
+    // The page is not written since the last fsync, so the next one leaves
+    // the block on the disk intact.
+    Disk::new(2)
+        .write(disk_sector(&file, 1), &[0xeeu8; 512])
+        .unwrap();
+    assert_eq!(syscall!(SyscallNumber::Fsync as usize, fd), 0);
+    assert!(
+        read_disk(&file, 1).iter().all(|b| *b == 0xee),
+        "A block not written since the last fsync must not be written back again."
+    );
+}
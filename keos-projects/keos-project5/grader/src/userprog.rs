@@ -2,7 +2,7 @@ use crate::Thread;
 use alloc::boxed::Box;
 use grading::*;
 use keos::{fs::Disk, thread::ThreadBuilder};
-use keos_project2::{loader::LoadContext, mm_struct::MmStruct};
+use keos_project2::loader::LoadContext;
 use keos_project5::{ffs, page_cache::PageCache};
 
 pub fn run_elf(name: &str) -> i32 {
@@ -10,25 +10,30 @@ pub fn run_elf(name: &str) -> i32 {
 }
 
 pub fn run_elf_with_arg(name: &str, args: &[&str]) -> i32 {
-    let LoadContext { mm_struct, regs } = LoadContext {
-        mm_struct: MmStruct::new(),
-        regs: keos::syscall::Registers::new(),
-    }
-    .load(
-        &keos::fs::FileSystem::root()
-            .open(name)
-            .unwrap()
-            .into_regular_file()
-            .unwrap(),
-        args,
-    )
-    .unwrap_or_else(|e| panic!("Failed to load elf: {}. reason: {:?}", name, e));
+    let LoadContext {
+        mm_struct,
+        regs,
+        symbols,
+        ..
+    } = LoadContext::new()
+        .load(
+            &keos::fs::FileSystem::root()
+                .open(name)
+                .unwrap()
+                .into_regular_file()
+                .unwrap(),
+            args,
+        )
+        .unwrap_or_else(|e| panic!("Failed to load elf: {}. reason: {:?}", name, e));
 
     let thread_build = ThreadBuilder::new(name);
     let tid = thread_build.get_tid();
     thread_build
         .attach_task(Box::new(Thread::from_mm_struct(mm_struct, tid)))
-        .spawn(move || regs.launch())
+        .spawn(move || {
+            keos::task::set_user_symbols(symbols);
+            regs.launch()
+        })
         .join()
 }
 
@@ -314,3 +319,29 @@ pub fn tar_gen() {
         0
     );
 }
+
+#[stdin(b"")]
+#[assert_output(b"success ")]
+pub fn cwd_fork() {
+    let fs = ffs::FastFileSystem::from_disk(Disk::new(2), false, false).unwrap();
+    keos::fs::FileSystem::register(PageCache::new(fs));
+    let root = keos::fs::FileSystem::root();
+
+    let simple_fs = simple_fs::FileSystem::load(1).unwrap();
+    let simple_fs: &dyn keos::fs::traits::FileSystem = &simple_fs;
+    let org_cwd_fork = simple_fs
+        .root()
+        .unwrap()
+        .open("cwd_fork")
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    let new_cwd_fork = root
+        .create("cwd_fork", false)
+        .unwrap()
+        .into_regular_file()
+        .unwrap();
+    keos::util::copy_file(&org_cwd_fork, &new_cwd_fork).unwrap();
+
+    assert_eq!(run_elf("cwd_fork"), 0);
+}
@@ -0,0 +1,41 @@
+#include <debug.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <syscall.h>
+
+// Returns whether `pathname` can be opened.
+static int exists(char *pathname) {
+  int fd = open(pathname, O_RDONLY);
+  if (fd < 0)
+    return 0;
+  close(fd);
+  return 1;
+}
+
+int main(int argc, char *argv[]) {
+  ASSERT(mkdir("cwd_fork__parent") == 0);
+  ASSERT(mkdir("cwd_fork__child") == 0);
+  ASSERT(chdir("cwd_fork__parent") == 0);
+  ASSERT(create("parent") == 0);
+
+  int pid = fork();
+  ASSERT(pid >= 0);
+  if (pid == 0) {
+    // The child starts from the working directory of the parent.
+    ASSERT(exists("parent"));
+    ASSERT(chdir("../cwd_fork__child") == 0);
+    ASSERT(create("child") == 0);
+    ASSERT(exists("child"));
+    ASSERT(!exists("parent"));
+    return 0;
+  }
+  ASSERT(waitpid(pid) == 0);
+
+  // The chdir of the child does not move the parent.
+  ASSERT(exists("parent"));
+  ASSERT(!exists("child"));
+  ASSERT(exists("../cwd_fork__child/child"));
+
+  printf("success ");
+  return 0;
+}
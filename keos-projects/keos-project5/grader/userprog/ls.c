@@ -127,7 +127,7 @@ int main(int argc, char *argv[]) {
     char *filenames = mmap((void*)0xB000, MAX_FILES * MAX_FILENAME_LEN, PROT_READ | PROT_WRITE, -1, 0);
     if (filenames < 0) {
         printf("ls: failed to allocate memory with mmap\n");
-        munmap(buffer);
+        munmap(buffer, 0x1000);
         close(dir_fd);
         return 1;
     }
@@ -175,7 +175,7 @@ int main(int argc, char *argv[]) {
     // Check for errors on syscall
     if (nread < 0) {
         printf("ls: error reading directory entries (errno %lld)\n", nread);
-        munmap(filenames);
+        munmap(filenames, MAX_FILES * MAX_FILENAME_LEN);
         close(dir_fd);
         return 1;
     }
@@ -220,7 +220,7 @@ int main(int argc, char *argv[]) {
     }
 
     close(dir_fd);
-    munmap(buffer);
-    munmap(filenames);
+    munmap(buffer, 0x1000);
+    munmap(filenames, MAX_FILES * MAX_FILENAME_LEN);
     return 0;
 }
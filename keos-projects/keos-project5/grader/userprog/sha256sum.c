@@ -33,12 +33,12 @@ int main(int argc, char *argv[]) {
         fd = open(source_name, O_RDONLY);
         if (fd < 0) {
             printf("Error opening file %s: %d\n", source_name, fd);
-            munmap(buffer);
+            munmap(buffer, 0x1000);
             return 1;
         }
     } else {
         printf("Usage: %s [filename]\n", argv[0]);
-        munmap(buffer);
+        munmap(buffer, 0x1000);
         return 1;
     }
 
@@ -51,7 +51,7 @@ int main(int argc, char *argv[]) {
         if (fd != STDIN_FILENO) {
             close(fd);
         }
-        munmap(buffer);
+        munmap(buffer, 0x1000);
         return 1;
     }
 
@@ -59,7 +59,7 @@ int main(int argc, char *argv[]) {
         close(fd);
     }
     
-    munmap(buffer);
+    munmap(buffer, 0x1000);
     sha256_final(&ctx, hash);
 
     for (int i = 0; i < SHA256_BLOCK_SIZE; i++) {
@@ -10,6 +10,7 @@
 #include <string.h>
 
 #define loop while(1)
+#define PAGE_ROUND_UP(x) (((x) + 0xfff) & ~0xfffUL)
 
 // Tar header structure (POSIX.1-1988 "ustar" format)
 #define TAR_BLOCK_SIZE 512
@@ -149,7 +150,8 @@ static int do_archive(int tar_fd, const char *path) {
         }
 
         // Use mmap to read file content without a large buffer
-        char *file_data = mmap((void*)0xA000, st.st_size, PROT_READ, file_fd, 0);
+        size_t map_size = PAGE_ROUND_UP(st.st_size);
+        char *file_data = mmap((void*)0xA000, map_size, PROT_READ, file_fd, 0);
         if (file_data < 0) {
             printf("mmap: errno %lld\n", (uint64_t)file_data);
             close(file_fd);
@@ -159,7 +161,7 @@ static int do_archive(int tar_fd, const char *path) {
         int write_bytes;
         if ((write_bytes = write(tar_fd, file_data, st.st_size)) != st.st_size) {
             printf("write file data: errno %d\n", write_bytes);
-            munmap(file_data);
+            munmap(file_data, map_size);
             close(file_fd);
             return -1;
         }
@@ -173,7 +175,7 @@ static int do_archive(int tar_fd, const char *path) {
             }
         }
 
-        munmap(file_data);
+        munmap(file_data, map_size);
         close(file_fd);
 
     } else if (S_ISDIR(st.st_mode)) {
@@ -190,7 +192,7 @@ static int do_archive(int tar_fd, const char *path) {
         uint64_t random_addr;
         getrandom(&random_addr, sizeof(uint64_t), 0);
         random_addr &= 0x00000FFFFFFFF000;
-        char *dir_buf = mmap((void*)random_addr, dir_buf_size, PROT_READ | PROT_WRITE, -1, 0);
+        char *dir_buf = mmap((void*)random_addr, PAGE_ROUND_UP(dir_buf_size), PROT_READ | PROT_WRITE, -1, 0);
         if (dir_buf < 0) {
             printf("mmap: errno %lld\n", (uint64_t)dir_buf);
             close(dir_fd);
@@ -211,7 +213,7 @@ static int do_archive(int tar_fd, const char *path) {
                     snprintf(full_path, sizeof(full_path), "%s/%s", path, d->d_name);
                     // Recursively archive the entry
                     if (do_archive(tar_fd, full_path) < 0) {
-                        munmap(dir_buf);
+                        munmap(dir_buf, PAGE_ROUND_UP(dir_buf_size));
                         close(dir_fd);
                         return -1;
                     }
@@ -219,7 +221,7 @@ static int do_archive(int tar_fd, const char *path) {
                 bpos += d->d_reclen;
             }
         }
-        munmap(dir_buf);
+        munmap(dir_buf, PAGE_ROUND_UP(dir_buf_size));
         close(dir_fd);
     }
 
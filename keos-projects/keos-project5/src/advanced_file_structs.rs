@@ -19,6 +19,11 @@
 //! - [`AdvancedFileStructs::stat`]
 //! - [`AdvancedFileStructs::fsync`]
 //!
+//! The paths given to these system calls are resolved by the path-based
+//! methods of [`keos::fs::Directory`], such as [`keos::fs::Directory::open`],
+//! which already canonicalize `.`, `..`, and repeated or trailing slashes.
+//! Resolve the paths with them instead of splitting the paths yourself.
+//!
 //! # Final Remarks
 //! 🎉 Congratulations! By completing this section, you have successfully
 //! finished the entire **KeOS** project. You have built, from the ground up, a
@@ -56,7 +61,7 @@ pub struct Dentry {
 ///
 /// This struct is typically returned by `stat()` to provide information about a
 /// file.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 #[repr(C)]
 pub struct Stat {
     /// The inode number of the file or directory.
@@ -64,11 +69,25 @@ pub struct Stat {
     /// The type of the file:
     /// - `0` = regular file
     /// - `1` = directory
+    /// - `2` = named pipe (FIFO)
     pub ty: u32,
     /// The size of the file in bytes.
     pub size: u64,
     #[doc(hidden)]
     pub __must_be_zero: u32,
+    /// The number of links to the file.
+    pub link_count: u64,
+    /// The number of the data blocks (4096 bytes each) allocated to the file.
+    pub blocks: u64,
+    /// The time of the last modification of the file, in timer ticks since
+    /// boot.
+    pub mtime: u64,
+    /// The time of the last access of the file, in timer ticks since boot.
+    pub atime: u64,
+    /// The permission bits of the file, within [`S_IRWXU`].
+    ///
+    /// [`S_IRWXU`]: keos::syscall::flags::S_IRWXU
+    pub mode: u32,
 }
 
 impl Stat {
@@ -76,13 +95,18 @@ impl Stat {
     pub fn new(file: &File) -> Self {
         Self {
             inode: file.ino().into_u32() as u64,
-            ty: if matches!(file, File::RegularFile(_)) {
-                0
-            } else {
-                1
+            ty: match file {
+                File::RegularFile(_) => 0,
+                File::Directory(_) => 1,
+                File::Fifo(_) => 2,
             },
             size: file.size(),
             __must_be_zero: 0,
+            link_count: file.link_count(),
+            blocks: file.blocks(),
+            mtime: file.mtime(),
+            atime: file.atime(),
+            mode: file.mode(),
         }
     }
 }
@@ -169,7 +193,12 @@ pub trait AdvancedFileStructs {
     /// ```c
     /// int fsync(int fd);
     /// ```
-    /// - `fd`: File descriptor of the file to synchronize.
+    /// - `fd`: File descriptor of the file or directory to synchronize.
+    ///
+    /// Synchronizing a directory makes the changes of its entries (e.g., by
+    /// `create` or `unlink`) durable with
+    /// [`Directory::writeback`](keos::fs::Directory::writeback), so that a
+    /// file created and followed by `fsync` on its directory survives a crash.
     ///
     /// Returns `0` on success.
     fn fsync(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError>;
@@ -238,7 +267,13 @@ impl AdvancedFileStructs for FileStruct {
     /// - `buf`: a pointer to the array of the dentries.
     /// - `count`: the number of entries in the array.
     ///
-    /// Returns the number of entries read into the buffer.
+    /// Successive calls continue from the entry after the last one read, by
+    /// keeping the cursor of
+    /// [`read_dir_from`](keos::fs::Directory::read_dir_from) in the position
+    /// of the open directory. Seeking the directory to 0 rewinds the cursor.
+    ///
+    /// Returns the number of entries read into the buffer, or 0 at the end of
+    /// the directory.
     fn readdir(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
         todo!()
     }
@@ -263,7 +298,12 @@ impl AdvancedFileStructs for FileStruct {
     /// ```c
     /// int fsync(int fd);
     /// ```
-    /// - `fd`: File descriptor of the file to synchronize.
+    /// - `fd`: File descriptor of the file or directory to synchronize.
+    ///
+    /// Synchronizing a directory makes the changes of its entries (e.g., by
+    /// `create` or `unlink`) durable with
+    /// [`Directory::writeback`](keos::fs::Directory::writeback), so that a
+    /// file created and followed by `fsync` on its directory survives a crash.
     ///
     /// Returns `0` on success.
     fn fsync(&mut self, abi: &SyscallAbi) -> Result<usize, KernelError> {
@@ -0,0 +1,219 @@
+//! A CLOCK (second-chance) Cache.
+//!
+//! `ClockCache<K, V, MAX_SIZE>` stores up to `MAX_SIZE` key-value pairs, like
+//! [`LRUCache`]. Instead of maintaining the exact access order on every
+//! access, each entry carries a *reference bit* that is set when the entry is
+//! accessed. When the capacity is exceeded, a rotating *hand* sweeps over the
+//! entries: a referenced entry gets a second chance (its bit is cleared and
+//! the hand moves on), and the first unreferenced entry is evicted. This
+//! approximates LRU while making an access as cheap as setting a bit.
+//!
+//! Evicted values are dropped, so that the eviction behavior of the value
+//! (e.g., write-back of a dirty [`Slot`]) is the same as in [`LRUCache`].
+//!
+//! # Example
+//! ```
+//! let mut cache: ClockCache<i32, String, 2> = ClockCache::new();
+//!
+//! cache.put(1, "one".to_string());
+//! cache.put(2, "two".to_string());
+//!
+//! // Access key 1, giving it a second chance.
+//! assert_eq!(cache.get(1).map(|v| v.as_str()), Some("one"));
+//!
+//! // Insert new key, evicting key 2 (not referenced since inserted).
+//! cache.put(3, "three".to_string());
+//!
+//! assert!(cache.get(2).is_none()); // evicted
+//! assert!(cache.get(1).is_some());
+//! assert!(cache.get(3).is_some());
+//! ```
+//!
+//! [`LRUCache`]: crate::lru::LRUCache
+//! [`Slot`]: crate::page_cache::Slot
+use alloc::{collections::BTreeMap, vec::Vec};
+
+struct Entry<K, V> {
+    k: K,
+    v: V,
+    referenced: bool,
+}
+
+/// A CLOCK (second-chance) Cache with capacity `MAX_SIZE`.
+pub struct ClockCache<K: Ord + Clone, V, const MAX_SIZE: usize> {
+    // Position of each key in the ring.
+    index: BTreeMap<K, usize>,
+    // The ring of entries swept by the hand. `None` is a hole left by removal.
+    ring: Vec<Option<Entry<K, V>>>,
+    // Holes in the ring.
+    free: Vec<usize>,
+    // The clock hand.
+    hand: usize,
+}
+
+impl<K: Ord + Clone, V, const MAX_SIZE: usize> Default for ClockCache<K, V, MAX_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone, V, const MAX_SIZE: usize> ClockCache<K, V, MAX_SIZE> {
+    // Sweep the ring from the hand, and evict the first unreferenced entry.
+    // Returns the position and the evicted entry.
+    fn evict(&mut self) -> (usize, Entry<K, V>) {
+        loop {
+            let pos = self.hand;
+            self.hand = (self.hand + 1) % self.ring.len();
+            match self.ring[pos].as_mut() {
+                Some(entry) if entry.referenced => entry.referenced = false,
+                Some(_) => {
+                    let entry = self.ring[pos].take().unwrap();
+                    self.index.remove(&entry.k);
+                    return (pos, entry);
+                }
+                None => (),
+            }
+        }
+    }
+
+    // Returns the inserted entry, and the entry displaced by the insertion.
+    fn __put(&mut self, k: K, v: V) -> (&mut Entry<K, V>, Option<(K, V)>) {
+        if let Some(pos) = self.index.get(&k).copied() {
+            let entry = self.ring[pos].as_mut().unwrap();
+            let old = core::mem::replace(&mut entry.v, v);
+            entry.referenced = true;
+            return (entry, Some((k, old)));
+        }
+        let (pos, evicted) = if MAX_SIZE <= self.index.len() {
+            let (pos, entry) = self.evict();
+            (pos, Some((entry.k, entry.v)))
+        } else if let Some(pos) = self.free.pop() {
+            (pos, None)
+        } else {
+            self.ring.push(None);
+            (self.ring.len() - 1, None)
+        };
+        self.index.insert(k.clone(), pos);
+        let entry = self.ring[pos].insert(Entry {
+            k,
+            v,
+            referenced: false,
+        });
+        (entry, evicted)
+    }
+
+    /// Makes a new, empty `ClockCache`.
+    ///
+    /// Does not allocate anything on its own.
+    pub const fn new() -> Self {
+        Self {
+            index: BTreeMap::new(),
+            ring: Vec::new(),
+            free: Vec::new(),
+            hand: 0,
+        }
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key and
+    /// set its reference bit.
+    pub fn get(&mut self, k: K) -> Option<&mut V> {
+        let pos = *self.index.get(&k)?;
+        let entry = self.ring[pos].as_mut().unwrap();
+        entry.referenced = true;
+        Some(&mut entry.v)
+    }
+
+    /// Inserts the value computed with `f` into the `ClockCache` if it is not
+    /// present, then returns a reference to the value in the `ClockCache`.
+    pub fn get_or_insert_with<E>(
+        &mut self,
+        k: K,
+        f: impl FnOnce() -> Result<V, E>,
+    ) -> Result<&mut V, E> {
+        Ok(if let Some(pos) = self.index.get(&k).copied() {
+            let entry = self.ring[pos].as_mut().unwrap();
+            entry.referenced = true;
+            &mut entry.v
+        } else {
+            &mut self.__put(k, f()?).0.v
+        })
+    }
+
+    /// Returns `true` if the `ClockCache` contains a value for the key.
+    ///
+    /// Unlike [`ClockCache::get`], this does not set the reference bit.
+    pub fn contains(&self, k: &K) -> bool {
+        self.index.contains_key(k)
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// Unlike [`ClockCache::get`], this does not set the reference bit.
+    pub fn peek(&self, k: &K) -> Option<&V> {
+        let pos = *self.index.get(k)?;
+        self.ring[pos].as_ref().map(|entry| &entry.v)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    ///
+    /// Unlike [`ClockCache::get`], this does not set the reference bit.
+    pub fn peek_mut(&mut self, k: &K) -> Option<&mut V> {
+        let pos = *self.index.get(k)?;
+        self.ring[pos].as_mut().map(|entry| &mut entry.v)
+    }
+
+    /// Inserts a key-value pair into the `ClockCache`.
+    ///
+    /// If the map did have this key present, the value is updated and its
+    /// reference bit is set.
+    ///
+    /// If the cache size is overflowed after insertion, the hand sweeps the
+    /// entries and evicts the first one that is not referenced.
+    pub fn put(&mut self, k: K, v: V) {
+        self.__put(k, v);
+    }
+
+    /// Inserts a key-value pair like [`ClockCache::put`], and returns the
+    /// entry displaced by the insertion: the previous value of the key, or the
+    /// evicted entry.
+    pub fn push(&mut self, k: K, v: V) -> Option<(K, V)> {
+        self.__put(k, v).1
+    }
+
+    /// Removes a key from the ClockCache, returning the stored value if the
+    /// key was previously in the ClockCache.
+    pub fn remove(&mut self, k: &K) -> Option<V> {
+        let pos = self.index.remove(k)?;
+        self.free.push(pos);
+        self.ring[pos].take().map(|entry| entry.v)
+    }
+
+    /// Retains only the elements specified by the predicate.
+    /// In other words, remove all pairs (k, v) for which f(&k, &mut v) returns
+    /// false.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        let retain_targets = self
+            .ring
+            .iter_mut()
+            .flatten()
+            .filter_map(|entry| {
+                if !f(&entry.k, &mut entry.v) {
+                    Some(entry.k.clone())
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        for target in retain_targets.into_iter() {
+            self.remove(&target);
+        }
+    }
+
+    /// Iterates over the key-value pairs in the ClockCache.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.ring
+            .iter_mut()
+            .flatten()
+            .map(|entry| (&entry.k, &mut entry.v))
+    }
+}
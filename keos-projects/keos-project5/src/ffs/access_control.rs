@@ -10,13 +10,15 @@
 //!
 //! [`disk_layout`]: super::disk_layout
 use crate::ffs::{
-    FastFileSystemInner, LogicalBlockAddress, RunningTransaction,
+    FastFileSystemInner, InodeNumber, LogicalBlockAddress, RunningTransaction,
     disk_layout::{InodeArray, Private, SuperBlock},
     inode::Inode,
 };
 use alloc::{
     boxed::Box,
+    collections::BTreeMap,
     sync::{Arc, Weak},
+    vec::Vec,
 };
 use keos::{
     KernelError,
@@ -52,11 +54,7 @@ pub trait MetaData: Sized + Default {
         ffs: &FastFileSystemInner,
         lba: LogicalBlockAddress,
     ) -> Result<BlockPointsTo<Self>, KernelError> {
-        Ok(BlockPointsTo {
-            lba,
-            b: ffs.read_meta(lba)?,
-            _m: core::marker::PhantomData,
-        })
+        Ok(BlockPointsTo::new(lba, ffs.read_meta(lba)?, true))
     }
 }
 
@@ -86,11 +84,13 @@ impl SuperBlock {
             }
             guard.unlock();
         }
-        Ok(BlockPointsTo {
-            lba: LogicalBlockAddress::new(1).unwrap(),
+        // The file system holds the superblock as long as it is alive, which
+        // is not a leak.
+        Ok(BlockPointsTo::new(
+            LogicalBlockAddress::new(1).unwrap(),
             b,
-            _m: core::marker::PhantomData,
-        })
+            false,
+        ))
     }
 }
 
@@ -106,10 +106,12 @@ impl SuperBlock {
 /// This abstraction allows safe and typed access to the underlying bytes as
 /// metadata structures, while supporting transactional read/write operations.
 ///
+/// Each `BlockPointsTo` is a reference to the block, which is tracked by
+/// [`track_refs`] to detect the leaked ones.
+///
 /// # Type Parameters
 /// - `M`: The type of metadata this block contains. Must implement
 ///   [`MetaData`].
-#[derive(Clone)]
 pub struct BlockPointsTo<M: MetaData> {
     /// Logical block address (LBA) where this block resides on disk.
     lba: LogicalBlockAddress,
@@ -120,9 +122,38 @@ pub struct BlockPointsTo<M: MetaData> {
 
     /// Marker to associate this block with metadata type `M`.
     _m: core::marker::PhantomData<M>,
+
+    /// Whether this reference is accounted by [`track_refs`].
+    tracked: bool,
+}
+
+impl<M: MetaData> Clone for BlockPointsTo<M> {
+    fn clone(&self) -> Self {
+        Self::new(self.lba, self.b.clone(), true)
+    }
+}
+
+impl<M: MetaData> Drop for BlockPointsTo<M> {
+    fn drop(&mut self) {
+        if self.tracked {
+            account_block_ref(self.lba, -1);
+        }
+    }
 }
 
 impl<M: MetaData> BlockPointsTo<M> {
+    fn new(lba: LogicalBlockAddress, b: Arc<SpinLock<[u8; 4096]>>, tracked: bool) -> Self {
+        if tracked {
+            account_block_ref(lba, 1);
+        }
+        Self {
+            lba,
+            b,
+            _m: core::marker::PhantomData,
+            tracked,
+        }
+    }
+
     /// Acquires a read-only guard to the underlying block contents.
     ///
     /// # Returns
@@ -311,11 +342,139 @@ impl<M: MetaData> Drop for BlockPointsToWriteGuard<'_, '_, '_, M> {
 /// This type provides read access through a shared guard and write access via
 /// a transactional context, ensuring consistency between the in-memory and
 /// on-disk representations of the inode.
-#[derive(Clone)]
 pub struct TrackedInode(Arc<RwLock<Inode>>, Weak<FastFileSystemInner>);
 
+// The change of the number of live references to each tracked object since
+// `track_refs`.
+#[derive(Default)]
+struct RefCounts {
+    // Keyed by the address of the in-memory inode.
+    inodes: BTreeMap<usize, (Weak<RwLock<Inode>>, isize)>,
+    blocks: BTreeMap<LogicalBlockAddress, isize>,
+}
+
+static REFS: SpinLock<Option<RefCounts>> = SpinLock::new(None);
+
+fn account_inode_ref(inode: &Arc<RwLock<Inode>>, delta: isize) {
+    let mut guard = REFS.lock();
+    if let Some(refs) = guard.as_mut() {
+        let key = Arc::as_ptr(inode) as usize;
+        let (_, count) = refs
+            .inodes
+            .entry(key)
+            .or_insert_with(|| (Arc::downgrade(inode), 0));
+        *count += delta;
+        if *count == 0 {
+            refs.inodes.remove(&key);
+        }
+    }
+    guard.unlock();
+}
+
+fn account_block_ref(lba: LogicalBlockAddress, delta: isize) {
+    let mut guard = REFS.lock();
+    if let Some(refs) = guard.as_mut() {
+        let count = refs.blocks.entry(lba).or_insert(0);
+        *count += delta;
+        if *count == 0 {
+            refs.blocks.remove(&lba);
+        }
+    }
+    guard.unlock();
+}
+
+/// The references that are still alive, reported by [`ref_leaks`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RefLeaks {
+    /// The leaked [`TrackedInode`]s: the inode number and the number of the
+    /// references.
+    pub inodes: Vec<(InodeNumber, usize)>,
+    /// The leaked [`BlockPointsTo`]s: the address of the block and the number
+    /// of the references.
+    pub blocks: Vec<(LogicalBlockAddress, usize)>,
+}
+
+impl RefLeaks {
+    /// Returns `true` if no reference is leaked.
+    pub fn is_empty(&self) -> bool {
+        self.inodes.is_empty() && self.blocks.is_empty()
+    }
+}
+
+/// Starts tracking the references to the in-memory inodes and the metadata
+/// blocks.
+///
+/// From this call, every creation and drop of a [`TrackedInode`] is
+/// accounted per inode, and that of a [`BlockPointsTo`] per block, until
+/// [`ref_leaks`] is called. As an open file holds a [`TrackedInode`], an open
+/// file that is never closed shows up as a leaked reference to its inode.
+///
+/// The grader checks the references at the end of a test with the
+/// `#[validate_refs]` attribute, which calls this function and
+/// [`validate_refs`] around the test.
+pub fn track_refs() {
+    let mut guard = REFS.lock();
+    *guard = Some(RefCounts::default());
+    guard.unlock();
+}
+
+/// Stops tracking the references, and returns the objects that gained
+/// references since [`track_refs`].
+///
+/// The references taken before the tracking started and released during the
+/// tracking are not reported.
+pub fn ref_leaks() -> RefLeaks {
+    let mut guard = REFS.lock();
+    let refs = guard.take().unwrap_or_default();
+    guard.unlock();
+    RefLeaks {
+        inodes: refs
+            .inodes
+            .into_values()
+            .filter(|(_, count)| *count > 0)
+            .filter_map(|(inode, count)| Some((inode.upgrade()?.read().ino, count as usize)))
+            .collect(),
+        blocks: refs
+            .blocks
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(lba, count)| (lba, count as usize))
+            .collect(),
+    }
+}
+
+/// Stops tracking the references, and panics if any object gained
+/// references since [`track_refs`].
+///
+/// See [`ref_leaks`] for details.
+pub fn validate_refs() {
+    let leaks = ref_leaks();
+    if !leaks.is_empty() {
+        let mut report = alloc::string::String::new();
+        for (ino, count) in leaks.inodes.iter() {
+            report.push_str(&alloc::format!("  {ino:?}: {count} leaked references\n"));
+        }
+        for (lba, count) in leaks.blocks.iter() {
+            report.push_str(&alloc::format!("  {lba:?}: {count} leaked references\n"));
+        }
+        panic!(
+            "Grader: Validating references failed: Detecting {} leaked inodes and {} leaked blocks.\n{report}",
+            leaks.inodes.len(),
+            leaks.blocks.len()
+        );
+    }
+}
+
+impl Clone for TrackedInode {
+    fn clone(&self) -> Self {
+        account_inode_ref(&self.0, 1);
+        Self(self.0.clone(), self.1.clone())
+    }
+}
+
 impl Drop for TrackedInode {
     fn drop(&mut self) {
+        account_inode_ref(&self.0, -1);
         if let Some(ffs) = self.1.upgrade() {
             let ino = self.read().ino;
             if let Some(inode) = ffs.remove_inode(ino) {
@@ -364,6 +523,7 @@ impl Drop for TrackedInode {
 impl TrackedInode {
     /// Create a new [`TrackedInode`] reference.
     pub fn new(inner: Arc<RwLock<Inode>>, ffs: Weak<FastFileSystemInner>) -> Self {
+        account_inode_ref(&inner, 1);
         Self(inner, ffs)
     }
 
@@ -433,6 +593,18 @@ impl TrackedInode {
             index,
         })
     }
+
+    /// Updates the access time of the in-memory inode to `now`, without a
+    /// transaction.
+    ///
+    /// The inode is marked with [`Inode::atime_dirty`], and the access time
+    /// is written to the disk by the next [`TrackedInodeWriteGuard::submit`]
+    /// of the inode.
+    pub fn touch_atime(&self, now: u64) {
+        let mut inode = self.0.write();
+        inode.atime = now;
+        inode.atime_dirty = true;
+    }
 }
 
 /// A guard that provides read-only access to the in-memory [`Inode`] structure.
@@ -511,6 +683,7 @@ impl TrackedInodeWriteGuard<'_, '_, '_, '_> {
     /// consumed.
     pub fn submit(mut self) {
         self.disk_layout[self.index] = self.mem_layout.into_disk_format();
+        self.mem_layout.atime_dirty = false;
         self.do_submit();
     }
 
@@ -297,8 +297,31 @@ pub struct Inode {
     /// This allows for even larger file sizes by introducing an extra level
     /// of indirection.
     pub diblock: Option<LogicalBlockAddress>,
+    /// The time of the last modification of the file, in timer ticks since
+    /// boot.
+    pub mtime: u64,
+    /// The time of the last access to the file, in timer ticks since boot.
+    pub atime: u64,
+    /// The permission bits of the file, within [`S_IRWXU`].
+    ///
+    /// [`S_IRWXU`]: keos::syscall::flags::S_IRWXU
+    pub mode: u32,
+    /// The flags of the inode, a combination of [`Inode::HASHED`].
+    pub flags: u32,
+    /// A triply indirect block, which contains pointers to doubly indirect
+    /// blocks.
+    pub tiblock: Option<LogicalBlockAddress>,
     /// A padding to align to the power of two.
-    pub _pad: [u8; 112],
+    pub _pad: [u8; 80],
+}
+
+impl Inode {
+    /// A flag marking a directory whose entries are placed by the hash of
+    /// their names.
+    ///
+    /// A directory without this flag is in the linear format, where an entry
+    /// may be placed in any block.
+    pub const HASHED: u32 = 1 << 0;
 }
 
 impl Default for Inode {
@@ -312,7 +335,12 @@ impl Default for Inode {
             dblocks: [None; 12],
             iblock: None,
             diblock: None,
-            _pad: [0; 112],
+            mtime: 0,
+            atime: 0,
+            mode: 0,
+            flags: 0,
+            tiblock: None,
+            _pad: [0; 80],
         }
     }
 }
@@ -358,8 +386,8 @@ const_assert!(core::mem::size_of::<InodeArray>() == 4096);
 /// the inode structure.
 ///
 /// # Usage
-/// Typically used as part of indirect, or double-indirectblock addressing
-/// schemes to support large file sizes.
+/// Typically used as part of indirect, double-indirect, or triple-indirect
+/// block addressing schemes to support large file sizes.
 #[repr(C)]
 pub struct IndirectBlock {
     lbas: [Option<LogicalBlockAddress>; 512],
@@ -454,6 +482,17 @@ impl DirectoryBlockEntry {
         self.inode
             .and_then(|_| core::str::from_utf8(&self.name[..self.name_len as usize]).ok())
     }
+
+    /// Returns the hash of the name, which places the entry in a hashed
+    /// directory.
+    ///
+    /// This is the 32-bit FNV-1a hash of the name. As it decides the on-disk
+    /// location of the entries, it must not be changed.
+    pub fn hash_name(name: &str) -> u32 {
+        name.bytes().fold(0x811c_9dc5, |hash, b| {
+            (hash ^ b as u32).wrapping_mul(0x0100_0193)
+        })
+    }
 }
 
 const_assert!(core::mem::size_of::<DirectoryBlockEntry>() == 256);
@@ -624,18 +663,29 @@ const_assert!(core::mem::size_of::<JournalTxBegin>() == 4096);
 ///
 /// This structure is used during transaction commit to mark the end of
 /// transaction.
+#[repr(C)]
 pub struct JournalTxEnd {
     /// Transaction id.
     pub tx_id: u64,
-    _pad: [u8; 4088],
+    /// Checksum of the journaled blocks of the transaction.
+    ///
+    /// See [`Crc32`](crate::ffs::journal::Crc32) for how it is computed.
+    pub checksum: u32,
+    /// Number of the journaled blocks of the transaction, which must match
+    /// the used slots of the `TxBegin` block.
+    pub count: u32,
+    _pad: [u8; 4080],
 }
 
 impl JournalTxEnd {
-    /// Creates a new, empty journal `TxEnd` block.
-    pub fn new(tx_id: u64) -> Box<Self> {
+    /// Creates a new journal `TxEnd` block with the number of the journaled
+    /// blocks and the checksum of the transaction.
+    pub fn new(tx_id: u64, count: u32, checksum: u32) -> Box<Self> {
         Box::new(Self {
             tx_id,
-            _pad: [0; 4088],
+            checksum,
+            count,
+            _pad: [0; 4080],
         })
     }
 
@@ -649,7 +699,7 @@ impl JournalTxEnd {
     /// - `Ok(Box<Self>)`: The loaded journal transaction block.
     /// - `Err(KernelError)`: If the block could not be read or parsed.
     pub fn from_io(io: &JournalIO, lba: LogicalBlockAddress) -> Result<Box<Self>, KernelError> {
-        let mut b = Self::new(0);
+        let mut b = Self::new(0, 0, 0);
         {
             let inner =
                 unsafe { core::slice::from_raw_parts_mut(&mut *b as *mut _ as *mut u8, 4096) };
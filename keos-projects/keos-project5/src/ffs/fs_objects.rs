@@ -27,6 +27,25 @@
 //! of entries. The directory **MUST** start with two entries: "." and "..",
 //! which points to itself and the parent directory respectively.
 //!
+//! ### Hashed Directories
+//! Scanning every block of a directory makes a lookup slow on a large
+//! directory. A directory created by this file system is therefore in the
+//! **hashed** format, marked by [`Inode::hashed`]. Its blocks are grouped into
+//! *levels*: the first level is the first block, and each next level is twice
+//! as wide as the previous one, up to [`MAX_LEVEL_BLOCKS`] blocks. An entry is
+//! placed in a single *bucket* block of a level, chosen by the hash of its name
+//! ([`DirectoryBlockEntry::hash_name`]). When all the buckets of a name are
+//! full, the directory grows by a new level.
+//!
+//! As a result, an entry is looked up only within its buckets, one block per
+//! level, returned by [`Directory::buckets`]. Entries never move between the
+//! blocks, and the blocks are the same [`DirectoryBlock`] as the linear
+//! format, so listing the directory is not affected by the format.
+//!
+//! A directory without the flag, such as the root directory written by the
+//! `mkfs`, remains in the **linear** format, where every block is a bucket of
+//! every name.
+//!
 //! ## Implementation Requirements
 //! You need to implement the followings:
 //! - [`RegularFile::read`]
@@ -47,16 +66,16 @@
 //! **maintaining crash consistency in the filesystem**..
 //!
 //! [`section`]: mod@crate::ffs::journal
-#[cfg(doc)]
-use crate::ffs::inode::Inode;
 use crate::ffs::{
     FastFileSystemInner, FileBlockNumber, InodeNumber,
     access_control::{MetaData, TrackedInode},
     disk_layout::{DirectoryBlock, DirectoryBlockEntry},
+    inode::{Inode, MAX_FILE_BLOCKS},
     journal::RunningTransaction,
     types::FileType,
 };
 use alloc::{
+    boxed::Box,
     string::String,
     sync::{Arc, Weak},
     vec::Vec,
@@ -65,6 +84,25 @@ use alloc::{
 use keos::fs::traits::{Directory as _Directory, RegularFile as _RegularFile};
 use keos::{KernelError, sync::atomic::AtomicBool};
 
+// The number of the entries in a directory block.
+const ENTRIES_PER_BLOCK: usize = 4096 / core::mem::size_of::<DirectoryBlockEntry>();
+
+/// The maximum number of the blocks in a level of a hashed directory.
+///
+/// A level is written in a single transaction when the directory grows, so
+/// that it must fit in a journal transaction (see
+/// [`MAX_GROUP_BLOCKS`](crate::ffs::journal::MAX_GROUP_BLOCKS)).
+pub const MAX_LEVEL_BLOCKS: usize = 256;
+
+// Returns the levels of a hashed directory of `blocks` blocks, as tuples of
+// the first block and the number of the blocks of each level.
+fn levels(blocks: usize) -> impl Iterator<Item = (usize, usize)> {
+    core::iter::successors(Some((0, 1)), |&(start, width)| {
+        Some((start + width, (width * 2).min(MAX_LEVEL_BLOCKS)))
+    })
+    .take_while(move |&(start, _)| start < blocks)
+}
+
 /// A handle to a regular file in the filesystem.
 ///
 /// This struct represents a low-level kernel handle to a regular file,
@@ -98,6 +136,34 @@ impl RegularFile {
             None
         }
     }
+
+    /// Updates the access time of the file after a read, if the access time
+    /// mode of the file system asks for it.
+    ///
+    /// `atime` and `mtime` are the times of the file when it was read.
+    fn accessed(&self, ffs: &FastFileSystemInner, atime: u64, mtime: u64) {
+        let now = keos::time::now_ticks();
+        let guard = ffs.atime_mode.lock();
+        let mode = *guard;
+        guard.unlock();
+        if mode.should_update(atime, mtime, now) {
+            self.inode.touch_atime(now);
+        }
+    }
+
+    /// Writes the access time updated by the reads to the disk, if it is not
+    /// written yet (see [`Inode::atime_dirty`]).
+    fn sync_atime(&self, ffs: &FastFileSystemInner) -> Result<(), KernelError> {
+        if !self.inode.read().atime_dirty {
+            return Ok(());
+        }
+        let tx = ffs.open_transaction("RegularFile::sync_atime");
+        self.inode.write_with(&tx, |inode| {
+            inode.submit();
+            Ok(())
+        })?;
+        tx.commit()
+    }
 }
 
 impl keos::fs::traits::RegularFile for RegularFile {
@@ -111,12 +177,52 @@ impl keos::fs::traits::RegularFile for RegularFile {
         self.inode.read().size
     }
 
+    /// Link count of the file.
+    fn link_count(&self) -> usize {
+        self.inode.read().link_count
+    }
+
+    /// Number of the data blocks allocated to the file, excluding the holes.
+    fn blocks(&self) -> usize {
+        let ffs = self.ffs.upgrade().unwrap();
+        self.inode.read().blocks(&ffs).unwrap_or(0)
+    }
+
+    /// Time of the last modification of the file.
+    fn mtime(&self) -> u64 {
+        self.inode.read().mtime
+    }
+
+    /// Time of the last access of the file.
+    fn atime(&self) -> u64 {
+        self.inode.read().atime
+    }
+
+    /// Permission bits of the file.
+    fn mode(&self) -> u32 {
+        self.inode.read().mode
+    }
+
+    /// Changes the permission bits of the file.
+    fn set_mode(&self, mode: u32) -> Result<(), KernelError> {
+        let ffs = self.ffs.upgrade().unwrap();
+        let tx = ffs.open_transaction("RegularFile::set_mode");
+        self.inode.write_with(&tx, |mut inode| {
+            inode.mode = mode;
+            inode.submit();
+            Ok(())
+        })?;
+        tx.commit()
+    }
+
     /// Reads data from the file into the provided buffer.
     ///
     /// # Parameters
     /// - `ofs`: The `FileBlockNumber` which to read.
     /// - `buf`: A mutable array where the file content will be stored.
     ///
+    /// A hole of a sparse file reads as zeros.
+    ///
     /// # Returns
     /// - `Ok(true)`: If the read success.
     /// - `Ok(false)`: If the read failed.
@@ -124,11 +230,87 @@ impl keos::fs::traits::RegularFile for RegularFile {
     fn read(&self, fba: FileBlockNumber, buf: &mut [u8; 4096]) -> Result<bool, keos::KernelError> {
         let ffs = self.ffs.upgrade().unwrap();
         let inode = self.inode.read();
-        match inode.get(&ffs, fba)? {
+        let result = match inode.get(&ffs, fba)? {
             Some(lba) => {
                 todo!();
             }
+            None if 0x1000 * fba.0 < inode.size => {
+                buf.fill(0);
+                Ok(true)
+            }
             None => Ok(false),
+        };
+
+        let (atime, mtime) = (inode.atime, inode.mtime);
+        drop(inode);
+        if result.is_ok() {
+            self.accessed(&ffs, atime, mtime);
+        }
+        result
+    }
+
+    /// Reads consecutive blocks of the file starting from `fba`.
+    ///
+    /// The blocks that are contiguous on the disk are fetched together with
+    /// [`FastFileSystemInner::read_data_blocks`], so a sequential read of a
+    /// file laid out contiguously takes a single disk request. The holes read
+    /// as zeros.
+    fn read_many(
+        &self,
+        fba: FileBlockNumber,
+        bufs: &mut [[u8; 4096]],
+    ) -> Result<usize, keos::KernelError> {
+        let ffs = self.ffs.upgrade().unwrap();
+        let inode = self.inode.read();
+        let count = inode
+            .size
+            .div_ceil(0x1000)
+            .saturating_sub(fba.0)
+            .min(bufs.len());
+        let mut lbas = Vec::with_capacity(count);
+        for i in 0..count {
+            lbas.push(inode.get(&ffs, fba + i)?);
+        }
+        let (atime, mtime) = (inode.atime, inode.mtime);
+        drop(inode);
+
+        let mut read = 0;
+        for run in lbas.chunk_by(|prev, next| match (prev, next) {
+            (Some(prev), Some(next)) => *prev + 1 == *next,
+            (None, None) => true,
+            _ => false,
+        }) {
+            let bufs = &mut bufs[read..read + run.len()];
+            match run[0] {
+                Some(lba) => ffs.read_data_blocks(lba, bufs)?,
+                None => bufs.iter_mut().for_each(|buf| buf.fill(0)),
+            }
+            read += run.len();
+        }
+        self.accessed(&ffs, atime, mtime);
+        Ok(read)
+    }
+
+    /// Finds the data or the hole at or after `ofs` from the allocation of
+    /// the blocks.
+    ///
+    /// A block is either wholly data or wholly a hole, so the found offset is
+    /// the start of a block unless it is `ofs` itself.
+    fn seek_hole_data(&self, ofs: usize, hole: bool) -> Result<usize, KernelError> {
+        let ffs = self.ffs.upgrade().unwrap();
+        let inode = self.inode.read();
+        if ofs >= inode.size {
+            return Err(KernelError::NoSuchDeviceOrAddress);
+        }
+        for fba in (ofs / 0x1000..inode.size.div_ceil(0x1000)).map(FileBlockNumber) {
+            if inode.get(&ffs, fba)?.is_none() == hole {
+                return Ok((fba.0 * 0x1000).max(ofs));
+            }
+        }
+        if hole {
+            Ok(inode.size)
+        } else {
+            Err(KernelError::NoSuchDeviceOrAddress)
         }
     }
 
@@ -142,6 +324,9 @@ impl keos::fs::traits::RegularFile for RegularFile {
     /// However, if the target block lies beyond the current file size **and**
     /// `new_size` is insufficient to reach it, the write will fail.
     ///
+    /// Only the target block is allocated, so the blocks between the old end
+    /// of the file and the target block are left as a hole.
+    ///
     /// # Parameters
     /// - `fba`: The `FileBlockNumber` indicating the block to write to.
     /// - `buf`: A buffer containing exactly 4096 bytes of data to write.
@@ -152,20 +337,25 @@ impl keos::fs::traits::RegularFile for RegularFile {
     /// # Returns
     /// - `Ok(())` if the write is successful.
     /// - `Err(KernelError)` if the operation fails (e.g., out-of-bounds write,
-    ///   I/O error).
+    ///   I/O error). [`KernelError::NoSpace`] is returned if `fba` is beyond
+    ///   the maximum file size.
     fn write(
         &self,
         fba: FileBlockNumber,
         buf: &[u8; 4096],
         min_size: usize,
     ) -> Result<(), keos::KernelError> {
+        if fba.0 >= MAX_FILE_BLOCKS {
+            return Err(KernelError::NoSpace);
+        }
         let ffs = self.ffs.upgrade().unwrap();
         let tx = ffs.open_transaction("RegularFile::write");
         self.inode.write_with(&tx, |mut inode| {
+            inode.mtime = keos::time::now_ticks();
             // Hint: Must conduct the following step
-            // 1: Grow.
+            // 1: Allocate the block with [`Inode::allocate`].
             // 2: Update the field `size`.
-            // 3: Write to the data block.
+            // 3: Write to the data block with [`RunningTransaction::write_data`].
             // 4: Submit change of the inode.
             todo!();
         })?;
@@ -174,8 +364,58 @@ impl keos::fs::traits::RegularFile for RegularFile {
         Ok(())
     }
 
+    /// Allocates the blocks for the byte range `[ofs, ofs + len)` in a
+    /// transaction.
+    ///
+    /// This fills the holes within the range, and extends the file if the
+    /// range is beyond the end of the file. The new blocks are zeroed, as they
+    /// may hold the data of a removed file.
+    fn allocate(&self, ofs: usize, len: usize) -> Result<(), keos::KernelError> {
+        let end = ofs
+            .checked_add(len)
+            .filter(|_| len != 0)
+            .ok_or(KernelError::InvalidArgument)?;
+        if end.div_ceil(0x1000) > MAX_FILE_BLOCKS {
+            return Err(KernelError::NoSpace);
+        }
+        let ffs = self.ffs.upgrade().unwrap();
+        let tx = ffs.open_transaction("RegularFile::allocate");
+        self.inode.write_with(&tx, |mut inode| {
+            let zero = Box::new([0; 4096]);
+            let mut changed = false;
+            for fba in (ofs / 0x1000..end.div_ceil(0x1000)).map(FileBlockNumber) {
+                if inode.get(&ffs, fba)?.is_none() {
+                    let lba = inode.allocate(&ffs, fba, &tx)?;
+                    // Zeroed before the transaction is committed, which
+                    // keeps the order of the ordered data without staging a
+                    // copy of the zeros for every block.
+                    ffs.write_data_block(lba, &zero)?;
+                    changed = true;
+                }
+            }
+            if end > inode.size {
+                inode.size = end;
+                changed = true;
+            }
+            if changed {
+                inode.mtime = keos::time::now_ticks();
+            }
+            inode.submit();
+            Ok(())
+        })?;
+        tx.commit()
+    }
+
     fn writeback(&self) -> Result<(), keos::KernelError> {
-        Ok(())
+        let ffs = self
+            .ffs
+            .upgrade()
+            .ok_or(KernelError::FilesystemCorrupted("File system closed."))?;
+        // The access time is a hint; a failure to record it (e.g., on a
+        // read-only disk) must not fail the write-back.
+        let _ = self.sync_atime(&ffs);
+        // Make the grouped transactions durable.
+        ffs.flush_journal()
     }
 }
 
@@ -237,6 +477,70 @@ impl Directory {
         Ok(output)
     }
 
+    /// Reads at most `max` entries of the directory, starting from the
+    /// `cursor`.
+    ///
+    /// The cursor is the index of the entry slot in the directory blocks, and
+    /// is advanced past the slots that are read. As an entry never moves
+    /// between the slots, the entries added or removed between the calls do
+    /// not make the other entries repeated or skipped; the empty slots are
+    /// skipped.
+    ///
+    /// # Returns
+    /// - `Ok(entries)`: The entries read from the cursor. An empty list marks
+    ///   the end of the directory.
+    /// - `Err(Error)`: An error if the read operation fails.
+    pub fn read_dir_from(
+        &self,
+        ffs: &FastFileSystemInner,
+        cursor: &mut usize,
+        max: usize,
+    ) -> Result<Vec<(InodeNumber, String)>, KernelError> {
+        let mut output = Vec::new();
+        let inode = self.inode.read();
+        let slots = inode.size.div_ceil(4096) * ENTRIES_PER_BLOCK;
+        while output.len() < max && *cursor < slots {
+            let fba = FileBlockNumber(*cursor / ENTRIES_PER_BLOCK);
+            let lba = inode
+                .get(ffs, fba)?
+                .ok_or(KernelError::FilesystemCorrupted("DirectoryBlock"))?;
+            let blk = DirectoryBlock::load(ffs, lba)?;
+            let guard = blk.read();
+            for en in guard[*cursor % ENTRIES_PER_BLOCK..].iter() {
+                if output.len() == max {
+                    break;
+                }
+                *cursor += 1;
+                if let (Some(ino), Some(name)) = (en.inode, en.name()) {
+                    output.push((ino, String::from(name)));
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    /// Returns the blocks of the directory that may hold the entry named
+    /// `entry`.
+    ///
+    /// For a hashed directory, these are the buckets of the name, one block
+    /// per level. For a directory in the linear format, these are all the
+    /// blocks of the directory.
+    pub fn buckets(inode: &Inode, entry: &str) -> impl Iterator<Item = FileBlockNumber> + use<> {
+        let blocks = inode.size.div_ceil(4096);
+        let hash = DirectoryBlockEntry::hash_name(entry) as usize;
+        let (hashed, linear) = if inode.hashed {
+            (Some(levels(blocks)), None)
+        } else {
+            (None, Some(0..blocks))
+        };
+        hashed
+            .into_iter()
+            .flatten()
+            .map(move |(start, width)| start + hash % width)
+            .chain(linear.into_iter().flatten())
+            .map(FileBlockNumber)
+    }
+
     /// Finds the inode number corresponding to a directory entry by name.
     ///
     /// # Arguments
@@ -246,6 +550,9 @@ impl Directory {
     /// # Returns
     /// - `Ok(inode_number)`: if the entry is found in the directory.
     /// - `Err(KernelError)`: if the entry is not found or other errors occurs.
+    ///
+    /// # Hint
+    /// Only the blocks returned by [`Directory::buckets`] may hold the entry.
     pub fn find(&self, ffs: &FastFileSystemInner, entry: &str) -> Result<InodeNumber, KernelError> {
         todo!()
     }
@@ -278,9 +585,11 @@ impl Directory {
         {
             let inode = self.inode.read();
             // Find reusable entry.
-            for fba in (0..inode.size.div_ceil(4096)).map(FileBlockNumber) {
-                let lba = inode.get(ffs, fba)?;
-                let blk = DirectoryBlock::load(ffs, lba.unwrap())?;
+            for fba in Self::buckets(&inode, entry) {
+                let lba = inode
+                    .get(ffs, fba)?
+                    .ok_or(KernelError::FilesystemCorrupted("DirectoryBlock"))?;
+                let blk = DirectoryBlock::load(ffs, lba)?;
                 let mut fit = None;
                 {
                     let guard = blk.read();
@@ -306,17 +615,34 @@ impl Directory {
         }
 
         self.inode.write_with(tx, |mut inode| {
-            // Grow the directory if no available space.
-            let until = FileBlockNumber(inode.size.div_ceil(0x1000));
-            inode.grow(ffs, until, tx)?;
-            inode.size += 0x1000;
-
-            // Fill the entry.
-            let lba = inode.get(ffs, until)?;
-            let blk = DirectoryBlock::load(ffs, lba.unwrap())?;
-            let mut guard = blk.write(tx);
-            guard[0] = en;
-            guard.submit();
+            // Grow the directory if no available space. A hashed directory grows
+            // by a level, and the entry is placed in its bucket of the level.
+            let from = inode.size.div_ceil(0x1000);
+            let (fit, until) = if inode.hashed {
+                let (start, width) = levels(usize::MAX)
+                    .find(|&(start, _)| start >= from)
+                    .unwrap();
+                let hash = DirectoryBlockEntry::hash_name(entry) as usize;
+                (start + hash % width, start + width - 1)
+            } else {
+                (from, from)
+            };
+            inode.grow(ffs, FileBlockNumber(until), tx)?;
+            inode.size = (until + 1) * 0x1000;
+
+            // Fill the entry, clearing the stale contents of the new blocks.
+            for fba in from..=until {
+                let lba = inode
+                    .get(ffs, FileBlockNumber(fba))?
+                    .ok_or(KernelError::FilesystemCorrupted("DirectoryBlock"))?;
+                let blk = DirectoryBlock::load(ffs, lba)?;
+                let mut guard = blk.write(tx);
+                guard.fill(DirectoryBlockEntry::default());
+                if fba == fit {
+                    guard[0] = en;
+                }
+                guard.submit();
+            }
             inode.submit();
             ffs.get_inode(ino).unwrap().write_with(tx, |mut inode| {
                 inode.link_count += 1;
@@ -344,9 +670,11 @@ impl Directory {
         tx: &RunningTransaction,
     ) -> Result<TrackedInode, KernelError> {
         let guard = self.inode.read();
-        for fba in (0..guard.size.div_ceil(4096)).map(FileBlockNumber) {
-            let lba = guard.get(ffs, fba)?;
-            let blk = DirectoryBlock::load(ffs, lba.unwrap())?;
+        for fba in Self::buckets(&guard, entry) {
+            let lba = guard
+                .get(ffs, fba)?
+                .ok_or(KernelError::FilesystemCorrupted("DirectoryBlock"))?;
+            let blk = DirectoryBlock::load(ffs, lba)?;
             let mut fit = None;
             {
                 let guard = blk.read();
@@ -386,6 +714,33 @@ impl keos::fs::traits::Directory for Directory {
         self.inode.read().link_count
     }
 
+    /// Time of the last modification of the directory.
+    fn mtime(&self) -> u64 {
+        self.inode.read().mtime
+    }
+
+    /// Time of the last access of the directory.
+    fn atime(&self) -> u64 {
+        self.inode.read().atime
+    }
+
+    /// Permission bits of the directory.
+    fn mode(&self) -> u32 {
+        self.inode.read().mode
+    }
+
+    /// Changes the permission bits of the directory.
+    fn set_mode(&self, mode: u32) -> Result<(), KernelError> {
+        let ffs = self.ffs.upgrade().unwrap();
+        let tx = ffs.open_transaction("Directory::set_mode");
+        self.inode.write_with(&tx, |mut inode| {
+            inode.mode = mode;
+            inode.submit();
+            Ok(())
+        })?;
+        tx.commit()
+    }
+
     /// Opens an entry by name.
     ///
     /// # Parameters
@@ -403,6 +758,9 @@ impl keos::fs::traits::Directory for Directory {
         // Find the inode corresponding to the entry from the directory.
         let ino = self.find(&ffs, entry)?;
         let inode = ffs.get_inode(ino)?;
+        if inode.read().ftype == FileType::Fifo {
+            return Ok(keos::fs::File::Fifo(keos::fs::Fifo::new(ino)));
+        }
         todo!()
     }
 
@@ -435,6 +793,39 @@ impl keos::fs::traits::Directory for Directory {
         }
     }
 
+    /// Add a named pipe (FIFO) entry by name.
+    ///
+    /// # Parameters
+    /// - `entry`: The name of the FIFO to add.
+    ///
+    /// # Returns
+    /// - `Ok(File)`: The created [`keos::fs::File::Fifo`].
+    /// - `Err(Error)`: An error if the add fails.
+    fn create_fifo_entry(&self, entry: &str) -> Result<keos::fs::File, keos::KernelError> {
+        // Get the filesystem from the weak reference.
+        let ffs = self
+            .ffs
+            .upgrade()
+            .ok_or(KernelError::FilesystemCorrupted("File system closed."))?;
+        // Find whether the duplicated entry exists.
+        match self.find(&ffs, entry) {
+            Err(KernelError::NoSuchEntry) => {
+                let tx = ffs.open_transaction("Directory::add_fifo_entry");
+                let (ino, inode) = ffs.allocate_inode(false, &tx)?;
+                inode.write_with(&tx, |mut inode| {
+                    inode.ftype = FileType::Fifo;
+                    inode.submit();
+                    Ok(())
+                })?;
+                self.add_entry(&ffs, entry, ino, &tx)?;
+                tx.commit()?;
+                Ok(keos::fs::File::Fifo(keos::fs::Fifo::new(ino)))
+            }
+            Ok(_) => Err(KernelError::FileExist),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Removes a directory entry by name.
     ///
     /// # Errors
@@ -487,6 +878,89 @@ impl keos::fs::traits::Directory for Directory {
         tx.commit()
     }
 
+    /// Moves an entry of this directory to the directory `dst` under the
+    /// name `new_entry`.
+    ///
+    /// The entry is added to `dst` and taken out of this directory in a
+    /// single transaction. A moved directory has its `..` entry pointed to
+    /// `dst`.
+    ///
+    /// # Errors
+    /// - Returns [`KernelError::NoSuchEntry`] if `entry` does not exist.
+    /// - Returns [`KernelError::FileExist`] if `new_entry` already exists in
+    ///   `dst` as another file.
+    /// - Returns [`KernelError::InvalidArgument`] when moving a directory
+    ///   into itself or its subdirectory, or when renaming `.` or `..`.
+    ///
+    /// # Parameters
+    /// - `entry`: The name of the entry to move.
+    /// - `dst`: The directory to move the entry into.
+    /// - `new_entry`: The new name of the entry.
+    fn rename_entry(
+        &self,
+        entry: &str,
+        dst: &keos::fs::Directory,
+        new_entry: &str,
+    ) -> Result<(), KernelError> {
+        // Get the filesystem from the weak reference.
+        let ffs = self
+            .ffs
+            .upgrade()
+            .ok_or(KernelError::FilesystemCorrupted("File system closed."))?;
+        if self.removed.load() || dst.removed()?.load() {
+            return Err(KernelError::NoSuchEntry);
+        }
+        if matches!(entry, "." | "..") || matches!(new_entry, "." | "..") {
+            return Err(KernelError::InvalidArgument);
+        }
+        let dst = Directory::new(ffs.get_inode(dst.ino())?, self.ffs.clone())
+            .ok_or(KernelError::NotDirectory)?;
+        let (src_ino, dst_ino) = (self.inode.read().ino, dst.inode.read().ino);
+
+        let ino = self.find(&ffs, entry)?;
+        match dst.find(&ffs, new_entry) {
+            Ok(found) if found == ino => return Ok(()),
+            Ok(_) => return Err(KernelError::FileExist),
+            Err(KernelError::NoSuchEntry) => (),
+            Err(e) => return Err(e),
+        }
+        let is_dir = ffs.get_inode(ino)?.read().ftype == FileType::Directory;
+        // Moving a directory under itself would detach it from the tree.
+        if is_dir && src_ino != dst_ino {
+            let root = InodeNumber::new(1).unwrap();
+            let mut cursor = dst_ino;
+            while cursor != root {
+                if cursor == ino {
+                    return Err(KernelError::InvalidArgument);
+                }
+                cursor = Directory::new(ffs.get_inode(cursor)?, self.ffs.clone())
+                    .ok_or(KernelError::FilesystemCorrupted("DirectoryEntry"))?
+                    .find(&ffs, "..")?;
+            }
+        }
+
+        let tx = ffs.open_transaction("Directory::rename_entry");
+        dst.add_entry(&ffs, new_entry, ino, &tx)?;
+        let inode = self.take_entry(&ffs, entry, &tx)?;
+        inode.write_with(&tx, |mut inode| {
+            inode.link_count -= 1;
+            inode.submit();
+            Ok(())
+        })?;
+        if is_dir && src_ino != dst_ino {
+            let moved = Directory::new(inode, self.ffs.clone())
+                .ok_or(KernelError::FilesystemCorrupted("DirectoryEntry"))?;
+            moved.take_entry(&ffs, "..", &tx)?;
+            moved.add_entry(&ffs, "..", dst_ino, &tx)?;
+            self.inode.write_with(&tx, |mut inode| {
+                inode.link_count -= 1;
+                inode.submit();
+                Ok(())
+            })?;
+        }
+        tx.commit()
+    }
+
     /// Reads the contents of the directory.
     ///
     /// This function lists all the entries within the directory.
@@ -503,6 +977,36 @@ impl keos::fs::traits::Directory for Directory {
         self.read_dir(&ffs)
     }
 
+    /// Reads at most `max` entries of the directory, starting from the
+    /// `cursor`.
+    fn read_dir_from(
+        &self,
+        cursor: &mut usize,
+        max: usize,
+    ) -> Result<Vec<(InodeNumber, String)>, keos::KernelError> {
+        let ffs = self
+            .ffs
+            .upgrade()
+            .ok_or(KernelError::FilesystemCorrupted("File system closed."))?;
+        self.read_dir_from(&ffs, cursor, max)
+    }
+
+    /// Writes back the entries of the directory to disk.
+    ///
+    /// The entries are changed by the journaled transactions, which are
+    /// checkpointed on the commit unless they are grouped (see
+    /// [`GroupCommit`]). This flushes and checkpoints the grouped
+    /// transactions, so that the entries are on disk regardless of the commit
+    /// window.
+    ///
+    /// [`GroupCommit`]: crate::ffs::journal::GroupCommit
+    fn writeback(&self) -> Result<(), keos::KernelError> {
+        self.ffs
+            .upgrade()
+            .ok_or(KernelError::FilesystemCorrupted("File system closed."))?
+            .flush_journal()
+    }
+
     /// Returns [`AtomicBool`] which contains whether directory is removed.
     ///
     /// This is important because directory operations against the removed
@@ -99,22 +99,42 @@
 //!   pointers to *indirect blocks*, each of which then contains pointers to
 //!   data blocks. This extra level of indirection allows extremely large files
 //!   to be addressed.
+//
+//! - **Triple indirect block (`tiblock`)** For the largest files, the inode
+//!   points to a block of pointers to *double indirect blocks*. It is not
+//!   drawn above, as it simply adds one more level on top of the double
+//!   indirection.
 //!
-//! Together, these three levels form a hierarchical mapping from a
+//! Together, these four levels form a hierarchical mapping from a
 //! [`FileBlockNumber`] (position within a file) to a
-//! [`LogicalBlockAddress`] (actual block on disk).
+//! [`LogicalBlockAddress`] (actual block on disk). [`BlockIndex::of`]
+//! computes which level, and which entries of each level, hold a file block.
+//!
+//! As an indirect block holds 512 pointers, a file can have at most
+//! [`MAX_FILE_BLOCKS`] blocks:
+//! ```text
+//! 12 + 512 + 512^2 + 512^3 = 134,480,396 blocks (about 513 GiB)
+//! ```
+//! Without the triple indirect block, a file would be limited to 262,668
+//! blocks (about 1 GiB).
 //!
 //! Your task is to implement the two core file access functions based on the
-//! indexing structure: [`Inode::get`] and [`Inode::grow`].
+//! indexing structure: [`Inode::get`] and [`Inode::allocate`].
 //!
 //! - [`Inode::get`] retrieves the disk location of a specific file block. It
 //!   traverses the inode’s indexing structure and returns the corresponding
 //!   disk block address, or `None` if the block has not been allocated.
 //!
-//! - [`Inode::grow`] ensures that the inode can cover a target file block
-//!   number. If needed, it allocates new blocks and updates the inode’s
-//!   indexing structure. All modifications are performed transactionally to
-//!   guarantee consistency.
+//! - [`Inode::allocate`] ensures that the inode covers a target file block
+//!   number. If needed, it allocates the block and the indirect blocks leading
+//!   to it, and updates the inode’s indexing structure. All modifications are
+//!   performed transactionally to guarantee consistency.
+//!
+//! A file in FFS may be **sparse**: writing far beyond the end of a file
+//! allocates only the written block, leaving the blocks in between
+//! unallocated. Such an unallocated range, called a **hole**, takes no space
+//! on the disk and reads as zeros. Therefore, any entry of the indexing
+//! structure may be `None`, even within the size of the file.
 //!
 //! These functions use [`MetaData::load`] to access or create
 //! [`IndirectBlock`]s, and they update these blocks via the transaction API
@@ -132,7 +152,7 @@
 //! ## Implementation Requirements
 //! You need to implement the followings:
 //! - [`Inode::get`]
-//! - [`Inode::grow`]
+//! - [`Inode::allocate`]
 //!
 //! After implement the functionalities, move on to the next [`section`].
 //!
@@ -148,9 +168,61 @@ use crate::ffs::{
     access_control::{self, BlockPointsTo, BlockPointsToWriteGuard, TrackedInode},
     fs_objects::Directory,
 };
-use keos::KernelError;
+use alloc::vec::Vec;
 #[cfg(doc)]
 use keos::fs::traits::Directory as _Directory;
+use keos::{KernelError, syscall::flags::S_IRWXU};
+
+/// The maximum number of blocks of a file.
+///
+/// This is the number of blocks that the direct, indirect, double indirect and
+/// triple indirect blocks can address together.
+pub const MAX_FILE_BLOCKS: usize = 12 + 512 + 512 * 512 + 512 * 512 * 512;
+
+/// The position of a file block in the indexing structure of an [`Inode`].
+///
+/// Each variant holds the indices to follow from the inode, from the top
+/// level to the bottom level. For example, `DoubleIndirect(i, j)` refers to
+/// the `j`-th entry of the indirect block at the `i`-th entry of the double
+/// indirect block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockIndex {
+    /// An index to [`Inode::dblocks`].
+    Direct(usize),
+    /// An index to the indirect block.
+    Indirect(usize),
+    /// The indices to the double indirect block and the indirect block.
+    DoubleIndirect(usize, usize),
+    /// The indices to the triple indirect block, the double indirect block and
+    /// the indirect block.
+    TripleIndirect(usize, usize, usize),
+}
+
+impl BlockIndex {
+    /// Computes the position of the file block `fba`.
+    ///
+    /// Returns `None` if `fba` is not less than [`MAX_FILE_BLOCKS`].
+    pub fn of(fba: FileBlockNumber) -> Option<Self> {
+        const N: usize = 512;
+        let mut fba = fba.0;
+        if fba < 12 {
+            return Some(Self::Direct(fba));
+        }
+        fba -= 12;
+        if fba < N {
+            return Some(Self::Indirect(fba));
+        }
+        fba -= N;
+        if fba < N * N {
+            return Some(Self::DoubleIndirect(fba / N, fba % N));
+        }
+        fba -= N * N;
+        if fba < N * N * N {
+            return Some(Self::TripleIndirect(fba / (N * N), fba / N % N, fba % N));
+        }
+        None
+    }
+}
 
 /// Represents an inode in memory, the metadata structure for a file or
 /// directory.
@@ -186,6 +258,43 @@ pub struct Inode {
     /// This allows for even larger file sizes by introducing an extra level
     /// of indirection.
     pub diblock: Option<LogicalBlockAddress>,
+    /// A triply indirect block, which contains pointers to doubly indirect
+    /// blocks.
+    ///
+    /// This is the last level of indirection, which is used only by files
+    /// larger than about 1 GiB.
+    pub tiblock: Option<LogicalBlockAddress>,
+    /// The time of the last modification of the file, in timer ticks since
+    /// boot.
+    ///
+    /// It is updated when the contents of the file change, i.e., on a write
+    /// or a truncation, within the transaction that changes them.
+    pub mtime: u64,
+    /// The time of the last access to the file, in timer ticks since boot.
+    ///
+    /// It is updated on a read, as configured by [`AtimeMode`].
+    ///
+    /// [`AtimeMode`]: crate::ffs::AtimeMode
+    pub atime: u64,
+    /// Whether [`Inode::atime`] is updated in memory but not yet written to
+    /// the disk.
+    ///
+    /// A read updates the access time without a transaction (see
+    /// [`TrackedInode::touch_atime`]); the next transaction that writes the
+    /// inode carries the access time to the disk and clears this flag.
+    ///
+    /// [`TrackedInode::touch_atime`]: crate::ffs::access_control::TrackedInode::touch_atime
+    pub atime_dirty: bool,
+    /// The permission bits of the file, within [`S_IRWXU`].
+    ///
+    /// They are checked when the file is opened, and changed by `chmod`.
+    pub mode: u32,
+    /// Whether the directory is in the hashed format.
+    ///
+    /// See the [`fs_objects`] module for the format of the directories.
+    ///
+    /// [`fs_objects`]: crate::ffs::fs_objects
+    pub hashed: bool,
 }
 
 impl Inode {
@@ -217,6 +326,12 @@ impl Inode {
             dblocks: inode.dblocks,
             iblock: inode.iblock,
             diblock: inode.diblock,
+            tiblock: inode.tiblock,
+            mtime: inode.mtime,
+            atime: inode.atime,
+            atime_dirty: false,
+            mode: inode.mode,
+            hashed: inode.flags & disk_layout::Inode::HASHED != 0,
         })
     }
 
@@ -233,13 +348,23 @@ impl Inode {
             ftype: match self.ftype {
                 FileType::RegularFile => 0,
                 FileType::Directory => 1,
+                FileType::Fifo => 2,
             },
             size: self.size as u64,
             link_count: self.link_count as u64,
             dblocks: self.dblocks,
             iblock: self.iblock,
             diblock: self.diblock,
-            _pad: [0; 112],
+            mtime: self.mtime,
+            atime: self.atime,
+            mode: self.mode,
+            flags: if self.hashed {
+                disk_layout::Inode::HASHED
+            } else {
+                0
+            },
+            tiblock: self.tiblock,
+            _pad: [0; 80],
         }
     }
 
@@ -247,7 +372,7 @@ impl Inode {
     ///
     /// This function is used to initialize a fresh inode in memory before it is
     /// ever written to disk. It sets the inode number and whether the inode
-    /// represents a directory.
+    /// represents a directory. A new directory is in the hashed format.
     ///
     /// # Parameters
     /// - `ino`: The inode number.
@@ -269,6 +394,12 @@ impl Inode {
             dblocks: [None; 12],
             iblock: None,
             diblock: None,
+            tiblock: None,
+            mtime: keos::time::now_ticks(),
+            atime: keos::time::now_ticks(),
+            atime_dirty: false,
+            mode: S_IRWXU,
+            hashed: is_dir,
         }
     }
 
@@ -279,10 +410,15 @@ impl Inode {
     /// - `fba`: [`FileBlockNumber`], relative to the beginning of the file.
     ///
     /// # Returns
-    /// - `Ok(lba)`: The logical block address where the specified file block is
-    ///   stored.
-    /// - `Err(KernelError)`: If the block is not allocated or the block number
-    ///   is out of bounds.
+    /// - `Ok(Some(lba))`: The logical block address where the specified file
+    ///   block is stored.
+    /// - `Ok(None)`: If the block is beyond the end of the file, or is a hole,
+    ///   i.e., a block that has never been written. A hole reads as zeros.
+    /// - `Err(KernelError)`: If an indirect block cannot be read.
+    ///
+    /// # Hint
+    /// [`BlockIndex::of`] tells which entries to follow from the inode. Any
+    /// entry on the way may be `None` in a sparse file.
     pub fn get(
         &self,
         ffs: &FastFileSystemInner,
@@ -294,8 +430,51 @@ impl Inode {
         todo!()
     }
 
+    /// Allocates the file block `fba`, if it is not allocated yet.
+    ///
+    /// Only the block `fba` and the indirect blocks leading to it are
+    /// allocated; the other blocks are left as they are. Therefore, a write
+    /// far beyond the end of a file leaves a hole in between, which takes no
+    /// space on the disk.
+    ///
+    /// This does not change the size of the inode, so `fba` may lie beyond the
+    /// end of the file.
+    ///
+    /// # Arguments
+    /// - `ffs`: Reference to the file system.
+    /// - `fba`: The [`FileBlockNumber`] to allocate.
+    /// - `tx`: The running transaction used to log allocation changes.
+    ///
+    /// # Returns
+    /// - `Ok(lba)`: The logical block address of the block, either newly
+    ///   allocated or previously allocated.
+    /// - `Err(KernelError)`: If allocation fails. [`KernelError::NoSpace`] is
+    ///   returned if `fba` is not less than [`MAX_FILE_BLOCKS`].
+    ///
+    /// An indirect block of any level is allocated when the first block under
+    /// it is allocated, and is zero-filled so that all of its entries are
+    /// `None`. A newly allocated data block is not initialized; the caller
+    /// writes the whole block.
+    pub fn allocate(
+        &mut self,
+        ffs: &FastFileSystemInner,
+        fba: FileBlockNumber,
+        tx: &RunningTransaction,
+    ) -> Result<LogicalBlockAddress, KernelError> {
+        // Hint: use [`FastFileSystemInner::allocate_block`] to allocate an free block.
+        //       To keep the blocks of the file together, pass the block after the
+        //       previous block of the file as the hint, or
+        //       [`FastFileSystemInner::block_hint`] if the previous block is not
+        //       allocated.
+        //       Note that [`Inode::get`] returns `None` beyond the end of the file.
+        todo!()
+    }
+
     /// Grows the inode to include at least the given number of file blocks.
     ///
+    /// Unlike [`Inode::allocate`], this allocates every block up to `until`,
+    /// leaving no hole. It is used for directories, which have no holes.
+    ///
     /// # Arguments
     /// - `ffs`: Reference to the file system.
     /// - `until`: The target [`FileBlockNumber`] (inclusive) that the inode
@@ -305,32 +484,90 @@ impl Inode {
     /// # Returns
     /// - `Ok(())`: If the inode was successfully extended.
     /// - `Err(KernelError)`: If allocation fails or the inode cannot be grown.
-    ///
-    /// This function ensures that all blocks up to `until` are allocated,
-    /// performing allocation of direct and indirect blocks as needed. The
-    /// transaction log is updated to support crash consistency.
     pub fn grow(
         &mut self,
         ffs: &FastFileSystemInner,
         until: FileBlockNumber,
         tx: &RunningTransaction,
     ) -> Result<(), KernelError> {
-        // Hint: use [`FastFileSystemInner::allocate_block`] to allocate an free block.
-        todo!()
+        for fba in (0..=until.0).map(FileBlockNumber) {
+            self.allocate(ffs, fba, tx)?;
+        }
+        Ok(())
+    }
+
+    /// Visits the blocks in the indexing structure under `lba`, including
+    /// `lba` itself.
+    ///
+    /// `depth` is the level of `lba`: 0 for a data block, 1 for an indirect
+    /// block, 2 for a double indirect block, and 3 for a triple indirect block.
+    /// `f` is called with each block and its level.
+    fn visit(
+        ffs: &FastFileSystemInner,
+        lba: LogicalBlockAddress,
+        depth: usize,
+        f: &mut impl FnMut(LogicalBlockAddress, usize),
+    ) -> Result<(), KernelError> {
+        f(lba, depth);
+        if depth > 0 {
+            let blk = disk_layout::IndirectBlock::load(ffs, lba)?;
+            let children: Vec<_> = blk.read().iter().flatten().copied().collect();
+            for child in children {
+                Self::visit(ffs, child, depth - 1, f)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Visits every allocated block of the inode, both the data blocks and the
+    /// indirect blocks, with [`Inode::visit`].
+    ///
+    /// The holes are skipped without reading them.
+    fn visit_blocks(
+        &self,
+        ffs: &FastFileSystemInner,
+        f: &mut impl FnMut(LogicalBlockAddress, usize),
+    ) -> Result<(), KernelError> {
+        for lba in self.dblocks.iter().flatten() {
+            f(*lba, 0);
+        }
+        for (lba, depth) in [(self.iblock, 1), (self.diblock, 2), (self.tiblock, 3)] {
+            if let Some(lba) = lba {
+                Self::visit(ffs, lba, depth, f)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of the data blocks allocated to the inode.
+    ///
+    /// The holes of a sparse file and the indirect blocks are not counted.
+    pub fn blocks(&self, ffs: &FastFileSystemInner) -> Result<usize, KernelError> {
+        let mut count = 0;
+        self.visit_blocks(ffs, &mut |_, depth| {
+            if depth == 0 {
+                count += 1;
+            }
+        })?;
+        Ok(count)
     }
 
     /// Deallocate inner blocks and set the inode's size to zero.
     ///
+    /// Both the data blocks and the indirect blocks of every level are freed,
+    /// and the inode no longer refers to any block.
+    ///
     /// Note that submitting the InodeWriteGuard is the caller's responsibility.
     pub fn zeroify(
         ino: &mut TrackedInodeWriteGuard,
         tx: &RunningTransaction,
         ffs: &FastFileSystemInner,
     ) {
-        let mut sb = ffs.sb.write(tx);
-        for fba in 0..(ino.size.div_ceil(0x1000)) {
-            let lba = ino.get(ffs, FileBlockNumber(fba)).unwrap().unwrap();
+        let mut lbas = Vec::new();
+        ino.visit_blocks(ffs, &mut |lba, _| lbas.push(lba)).unwrap();
 
+        let mut sb = ffs.sb.write(tx);
+        for lba in lbas {
             let (b_lba, offset) = lba.into_bitmap_lba_offset(ffs).unwrap();
             let bitmap = disk_layout::BlockBitmap::load(ffs, b_lba).unwrap();
 
@@ -342,6 +579,11 @@ impl Inode {
         }
 
         sb.submit();
+        ino.dblocks = [None; 12];
+        ino.iblock = None;
+        ino.diblock = None;
+        ino.tiblock = None;
         ino.size = 0;
+        ino.mtime = keos::time::now_ticks();
     }
 }
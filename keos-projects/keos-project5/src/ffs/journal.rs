@@ -122,11 +122,62 @@
 //! entirely. This rollback ensures consistency by ignoring partially written
 //! or aborted transactions.
 //!
+//! The `TxEnd` block follows the last journaled block, and records the number
+//! of the journaled blocks and a [`Crc32`] checksum of them. A transaction is
+//! a torn commit (e.g., a journaled block is not fully written) if the
+//! checksum does not match, or if the `TxBegin` block does not agree with the
+//! `TxEnd` block on the transaction id and the number of the blocks. A torn
+//! commit is discarded as if it were never committed, rather than replaying
+//! the garbage.
+//!
 //! This recovery approach is both **bounded** and **idempotent**: it scans only
 //! the small, fixed-size journal area, avoiding costly full file system
 //! traversal, and it can safely retry recovery without side effects if
 //! interrupted again.
 //!
+//! ### Group Commit: [`GroupCommit`]
+//!
+//! Committing every transaction on its own writes the `TxBegin`, the data
+//! blocks, the `TxEnd` and the journal superblock for each small update, and
+//! rewrites the same blocks (e.g., the inode bitmap) over and over under a
+//! burst of updates.
+//!
+//! When the commit window is set with [`FastFileSystem::set_commit_window`],
+//! KeOS instead gathers the transactions that commit within the window into a
+//! single **group**. The blocks of a group are merged by their locations, and
+//! the whole group is written as one transaction: a single `TxBegin`/`TxEnd`
+//! pair, which is replayed or discarded as a whole by the recovery. The group
+//! is flushed by the first commit after the window, when the group becomes
+//! full, by `fsync()`, or when the file system is dropped. Until then, the
+//! grouped transactions are not durable. A background thread flushes a group
+//! whose window is over even if no other transaction commits, so that a
+//! grouped transaction becomes durable within two windows at the latest.
+//!
+//! The `TxBegin` block of a group is given a fresh transaction id when the
+//! group is flushed, as the ids of the grouped transactions are not written to
+//! the journal. A single transaction that modifies more blocks than the
+//! `TxBegin` block holds ([`MAX_GROUP_BLOCKS`]) cannot be journaled at all, and
+//! is rejected by [`RunningTransaction::commit`].
+//!
+//! [`FastFileSystem::set_commit_window`]: crate::ffs::FastFileSystem::set_commit_window
+//!
+//! ### Ordered Data: [`RunningTransaction::write_data`]
+//!
+//! Only the metadata is journaled; the file contents are written in place.
+//! If the metadata referring to a new data block (e.g., the block pointer of
+//! an inode) were committed before the contents of the block reach the disk,
+//! a crash in between would leave the file pointing at a block holding stale
+//! data, possibly of a removed file.
+//!
+//! To prevent this, the data blocks written within a transaction are staged
+//! with [`RunningTransaction::write_data`], and written to their locations by
+//! [`RunningTransaction::commit`] **before** the metadata is written to the
+//! journal. If writing the data fails, the metadata is never committed. As
+//! the checkpoint follows the commit, the data is always durable before the
+//! metadata is either committed or checkpointed. The page cache writes back
+//! a dirty slot through `RegularFile::write`, so the write-back of a slot
+//! follows the same order.
+//!
 //! ## Implementation Requirements
 //! You need to implement the followings:
 //!   - [`Journal::recovery`]
@@ -144,9 +195,17 @@ use crate::ffs::{
     FastFileSystemInner, JournalIO, LogicalBlockAddress,
     disk_layout::{JournalSb, JournalTxBegin, JournalTxEnd},
 };
-use alloc::{boxed::Box, vec::Vec};
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
 use core::cell::RefCell;
-use keos::{KernelError, sync::SpinLockGuard};
+use keos::{
+    KernelError,
+    sync::{
+        SpinLock, SpinLockGuard,
+        atomic::{AtomicBool, AtomicU64},
+    },
+    thread::{Current, ThreadBuilder},
+    time::now_ticks,
+};
 
 /// A structure representing the journal metadata used for crash consistency.
 ///
@@ -167,7 +226,83 @@ pub struct Journal {
     pub sb: Box<JournalSb>,
 }
 
+/// A CRC-32 (IEEE 802.3) checksum of the journaled blocks.
+///
+/// The checksum of a transaction covers the logical block address and the
+/// contents of each journaled block, in the order of the `TxBegin` slots.
+pub struct Crc32(u32);
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self(!0)
+    }
+}
+
+impl Crc32 {
+    /// Feeds `bytes` into the checksum.
+    pub fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u32;
+            for _ in 0..8 {
+                self.0 = (self.0 >> 1) ^ (0xedb8_8320 & (self.0 & 1).wrapping_neg());
+            }
+        }
+    }
+
+    /// Feeds a journaled block into the checksum.
+    pub fn update_block(&mut self, lba: LogicalBlockAddress, block: &[u8; 4096]) {
+        self.update(&lba.into_u64().to_le_bytes());
+        self.update(block);
+    }
+
+    /// Returns the checksum.
+    pub fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
 impl Journal {
+    /// Checks whether the committed transaction in the journal is written
+    /// in whole.
+    ///
+    /// The `TxBegin` block is validated before its slots are trusted: the
+    /// used slots must be packed at the front, and each must name a block of
+    /// the file system outside the journal. The `TxEnd` block must then agree
+    /// with it on the transaction id and the number of the blocks, and its
+    /// checksum must match the journaled blocks.
+    pub fn is_intact(
+        &self,
+        ffs: &FastFileSystemInner,
+        io: &JournalIO,
+    ) -> Result<bool, KernelError> {
+        let journal = ffs.journal();
+        let tx_begin = JournalTxBegin::from_io(io, journal.start + 1)?;
+        let count = tx_begin
+            .lbas
+            .iter()
+            .take_while(|slot| slot.is_some())
+            .count();
+        let lbas = tx_begin.lbas[..count].iter().flatten();
+        if tx_begin.lbas[count..].iter().any(Option::is_some)
+            || lbas
+                .clone()
+                .any(|lba| lba.into_u64() as usize >= ffs.block_count || journal.contains(lba))
+        {
+            return Ok(false);
+        }
+
+        let mut crc = Crc32::default();
+        let mut block = Box::new([0; 4096]);
+        for (i, lba) in lbas.enumerate() {
+            io.read_journal(journal.start + 2 + i, &mut block)?;
+            crc.update_block(*lba, &block);
+        }
+        let tx_end = JournalTxEnd::from_io(io, journal.start + 2 + count)?;
+        Ok(tx_end.tx_id == tx_begin.tx_id
+            && tx_end.count as usize == count
+            && tx_end.checksum == crc.finish())
+    }
+
     /// Recovers and commited but not checkpointed transactions from the
     /// journal.
     ///
@@ -193,6 +328,11 @@ impl Journal {
         ffs: &FastFileSystemInner,
         io: &JournalIO,
     ) -> Result<(), KernelError> {
+        if self.sb.commited != 0 && !self.is_intact(ffs, io)? {
+            // A torn commit. Discard it.
+            self.sb.commited = 0;
+            return self.sb.writeback(io, ffs);
+        }
         todo!()
     }
 
@@ -243,6 +383,185 @@ impl Journal {
     }
 }
 
+/// The maximum number of the blocks in a group or a transaction, which is the
+/// number of the slots in the `TxBegin` block.
+pub const MAX_GROUP_BLOCKS: usize = 511;
+
+// The transactions committed but not flushed to the journal yet.
+#[derive(Default)]
+struct PendingGroup {
+    blocks: BTreeMap<LogicalBlockAddress, Box<[u8; 4096]>>,
+    // The number of the transactions in the group.
+    count: usize,
+    // When the first transaction of the group is committed.
+    since: u64,
+}
+
+/// Batches the transactions that commit within a window into a single journal
+/// commit.
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Default)]
+pub struct GroupCommit {
+    // The commit window in timer ticks. 0 disables the group commit.
+    window: AtomicU64,
+    pending: SpinLock<PendingGroup>,
+    // Whether the thread flushing the expired groups is running.
+    flusher: AtomicBool,
+}
+
+impl GroupCommit {
+    /// Returns the commit window in timer ticks, or 0 if the group commit is
+    /// disabled.
+    pub fn window(&self) -> u64 {
+        self.window.load()
+    }
+
+    /// Sets the commit window of the group commit of `ffs` in timer ticks. 0
+    /// disables the group commit.
+    ///
+    /// The transactions that are already grouped are flushed by the next
+    /// flush of the group. Enabling the group commit starts a thread that
+    /// flushes the group once its window is over, which runs until the group
+    /// commit is disabled or the file system is dropped.
+    pub fn set_window(ffs: &Arc<FastFileSystemInner>, ticks: u64) {
+        let group = &ffs.group_commit;
+        group.window.store(ticks);
+        if ticks == 0 || group.flusher.swap(true) {
+            return;
+        }
+        let ffs = Arc::downgrade(ffs);
+        ThreadBuilder::new("[GroupCommit]").spawn(move || {
+            while let Some(ffs) = ffs.upgrade() {
+                let (group, window) = (&ffs.group_commit, ffs.group_commit.window());
+                if window == 0 {
+                    group.flusher.store(false);
+                    // Keep running if the group commit is enabled again
+                    // meanwhile, as no other flusher is started then.
+                    if group.window() == 0 || group.flusher.swap(true) {
+                        break;
+                    }
+                    continue;
+                }
+                if group.is_expired(now_ticks()) {
+                    // A failed flush keeps the group, which is retried on the
+                    // next flush.
+                    let _ = ffs.flush_journal();
+                }
+                drop(ffs);
+                Current::sleep(window);
+            }
+        });
+    }
+
+    /// Returns `true` if no transaction is waiting for the flush.
+    pub fn is_empty(&self) -> bool {
+        let guard = self.pending.lock();
+        let empty = guard.count == 0;
+        guard.unlock();
+        empty
+    }
+
+    // Returns `true` if the window of a non-empty group is over at `now`.
+    fn is_expired(&self, now: u64) -> bool {
+        let guard = self.pending.lock();
+        let expired = guard.count != 0 && now.saturating_sub(guard.since) >= self.window();
+        guard.unlock();
+        expired
+    }
+
+    /// Copies the grouped contents of the block at `lba` into `buf`.
+    ///
+    /// The grouped blocks are not written to their locations until the group
+    /// is flushed, so that a metadata block read from the disk must be
+    /// patched with this.
+    ///
+    /// Returns `true` if the block is in the group.
+    pub fn lookup(&self, lba: LogicalBlockAddress, buf: &mut [u8; 4096]) -> bool {
+        let guard = self.pending.lock();
+        let found = guard.blocks.get(&lba).map(|b| buf.copy_from_slice(&**b));
+        guard.unlock();
+        found.is_some()
+    }
+
+    // Adds the blocks of a transaction to the group. A later write of a block
+    // replaces the earlier one.
+    //
+    // Returns `true` if the window of the group is over.
+    fn add(&self, tx: Vec<(LogicalBlockAddress, Box<[u8; 4096]>)>, now: u64) -> bool {
+        let mut guard = self.pending.lock();
+        if guard.count == 0 {
+            guard.since = now;
+        }
+        guard.count += 1;
+        guard.blocks.extend(tx);
+        let expired = now - guard.since >= self.window();
+        guard.unlock();
+        expired
+    }
+
+    // Returns `true` if the blocks of `tx` may not fit in the group.
+    fn overflows(&self, tx: &[(LogicalBlockAddress, Box<[u8; 4096]>)]) -> bool {
+        let guard = self.pending.lock();
+        let overflows = guard.blocks.len() + tx.len() > MAX_GROUP_BLOCKS;
+        guard.unlock();
+        overflows
+    }
+
+    /// Writes the group to the journal as a single transaction, and
+    /// checkpoints it.
+    ///
+    /// The transaction id of the group is allocated from the journal
+    /// superblock here, so that the groups flushed in a row never share an id.
+    /// The group is kept until the checkpoint succeeds, as its blocks are not
+    /// in their locations until then.
+    ///
+    /// # Returns
+    /// - The locked journal and I/O handle.
+    /// - `Err(KernelError)` if the commit or the checkpoint fails.
+    pub fn flush<'a>(
+        &self,
+        mut journal: SpinLockGuard<'a, Journal>,
+        io: JournalIO<'a>,
+        ffs: &'a FastFileSystemInner,
+        debug_journal: bool,
+    ) -> Result<(SpinLockGuard<'a, Journal>, JournalIO<'a>), KernelError> {
+        let guard = self.pending.lock();
+        let count = guard.count;
+        let tx = guard
+            .blocks
+            .iter()
+            .map(|(lba, b)| (*lba, b.clone()))
+            .collect::<Vec<_>>();
+        guard.unlock();
+        if count == 0 {
+            return Ok((journal, io));
+        }
+        let tx_id = journal.sb.tx_id;
+        journal.sb.tx_id += 1;
+
+        if debug_journal {
+            println!(
+                "[FFS-Journal]: Group of {} transactions as #{}.",
+                count, tx_id
+            );
+        }
+        let (mut journal, io) = JournalWriter::new(tx, journal, io, ffs, tx_id)
+            .write_tx_begin()?
+            .write_blocks()?
+            .write_tx_end()?;
+        if let Err(e) = journal.checkpoint(ffs, &io, debug_journal) {
+            journal.unlock();
+            return Err(e);
+        }
+
+        let mut guard = self.pending.lock();
+        *guard = PendingGroup::default();
+        guard.unlock();
+        Ok((journal, io))
+    }
+}
+
 /// Represents an in-progress file system transaction using write-ahead
 /// journaling.
 ///
@@ -259,6 +578,8 @@ impl Journal {
 /// # Fields
 /// - `tx`: A buffer that stores staged metadata writes as a list of (LBA, data)
 ///   tuples.
+/// - `data`: A buffer that stores staged data block writes, which are written
+///   before the metadata is committed.
 /// - `journal`: A locked handle to the global `Journal`, used during commit.
 /// - `tx_id`: Unique identifier for the current transaction.
 /// - `io`: The journal I/O interface used for block-level reads/writes.
@@ -266,6 +587,7 @@ impl Journal {
 /// - `ffs`: A reference to the file system's core structure.
 pub struct RunningTransaction<'a> {
     tx: RefCell<Vec<(LogicalBlockAddress, Box<[u8; 4096]>)>>,
+    data: RefCell<Vec<(LogicalBlockAddress, Box<[u8; 4096]>)>>,
     journal: Option<SpinLockGuard<'a, Journal>>,
     tx_id: u64,
     io: Option<JournalIO<'a>>,
@@ -305,6 +627,7 @@ impl<'a> RunningTransaction<'a> {
         }
         RunningTransaction {
             tx: RefCell::new(Vec::new()),
+            data: RefCell::new(Vec::new()),
             journal,
             io: Some(io),
             tx_id,
@@ -335,23 +658,61 @@ impl<'a> RunningTransaction<'a> {
         self.tx.borrow_mut().push((lba, data));
     }
 
+    /// Buffers a data block write for inclusion in the transaction.
+    ///
+    /// The data block is not journaled. Instead, it is written to its location
+    /// by `commit()` before the metadata, so that the committed metadata never
+    /// refers to a data block whose contents are not on the disk.
+    ///
+    /// # Parameters
+    /// - `lba`: The logical block address of the data block.
+    /// - `data`: The contents of the data block.
+    #[inline]
+    pub fn write_data(&self, lba: LogicalBlockAddress, data: &[u8; 4096]) {
+        if self.debug_journal {
+            println!("[FFS-Journal]:      data: {:?},", lba);
+        }
+        let mut block = Box::new([0; 4096]);
+        block.copy_from_slice(data);
+        self.data.borrow_mut().push((lba, block));
+    }
+
     /// Commits the transaction to the journal and applies changes to disk.
     ///
     /// This method performs the following steps:
-    /// 1. Writes all staged metadata blocks to the journal region on disk.
-    /// 2. Updates the journal superblock.
-    /// 3. Checkpoint the journal.
+    /// 1. Writes all staged data blocks to their locations.
+    /// 2. Writes all staged metadata blocks to the journal region on disk.
+    /// 3. Updates the journal superblock.
+    /// 4. Checkpoint the journal.
     ///
     /// # Returns
     /// - `Ok(())`: If the transaction was successfully committed and
     ///   checkpointed.
-    /// - `Err(KernelError)`: If an I/O or consistency error occurred.
+    /// - `Err(KernelError)`: If an I/O or consistency error occurred. If
+    ///   writing a data block fails, the metadata is not committed.
+    /// - `Err(KernelError::NoSpace)`: If the transaction modifies more than
+    ///   [`MAX_GROUP_BLOCKS`] metadata blocks, which do not fit in the
+    ///   journal. Nothing is written in this case.
     pub fn commit(mut self) -> Result<(), KernelError> {
-        // In real filesystem, there exist more optimizations to reduce disk I/O, such
-        // as merging the same LBA in a journal into one block.
-        let (io, tx, journal, tx_id, ffs, debug_journal) = (
+        // A block modified several times in the transaction is journaled once,
+        // with its last contents.
+        let tx = core::mem::take(&mut *self.tx.borrow_mut())
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        if self.journal.is_some() && tx.len() > MAX_GROUP_BLOCKS {
+            return Err(KernelError::NoSpace);
+        }
+
+        // The data blocks must be durable before the metadata referring to them
+        // is committed. On failure, the transaction is dropped as a whole.
+        for (lba, block) in core::mem::take(&mut *self.data.borrow_mut()) {
+            self.ffs.write_data_block(lba, &block)?;
+        }
+
+        let (io, journal, tx_id, ffs, debug_journal) = (
             self.io.take().unwrap(),
-            core::mem::take(&mut *self.tx.borrow_mut()),
             self.journal.take(),
             self.tx_id,
             self.ffs,
@@ -359,6 +720,21 @@ impl<'a> RunningTransaction<'a> {
         );
 
         if let Some(journal) = journal {
+            let group = &ffs.group_commit;
+            if group.window() != 0 {
+                if debug_journal {
+                    println!("[FFS-Journal]: ] Grouped.");
+                }
+                let (mut journal, mut io) = (journal, io);
+                if group.overflows(&tx) {
+                    (journal, io) = group.flush(journal, io, ffs, debug_journal)?;
+                }
+                if group.add(tx, now_ticks()) {
+                    (journal, _) = group.flush(journal, io, ffs, debug_journal)?;
+                }
+                journal.unlock();
+                return Ok(());
+            }
             if debug_journal {
                 println!("[FFS-Journal]: ] Commited.");
             }
@@ -530,7 +906,9 @@ impl<'a> JournalWriter<'a, TxEnd> {
     ///
     /// This signals a successfully completed transaction and allows recovery
     /// mechanisms to apply the journal contents to the actual file system
-    /// metadata.
+    /// metadata. The `TxEnd` block must be written right after the last
+    /// journaled block, as the recovery finds it there to verify the
+    /// checksum.
     ///
     /// # Returns
     /// - The locked journal and I/O handle, to checkpoint the journal.
@@ -538,7 +916,11 @@ impl<'a> JournalWriter<'a, TxEnd> {
     pub fn write_tx_end(
         mut self,
     ) -> Result<(SpinLockGuard<'a, Journal>, JournalIO<'a>), KernelError> {
-        let tx_end = JournalTxEnd::new(self.tx_id);
+        let mut crc = Crc32::default();
+        for (lba, block) in self.tx.iter() {
+            crc.update_block(*lba, block);
+        }
+        let tx_end = JournalTxEnd::new(self.tx_id, self.tx.len() as u32, crc.finish());
         // In the real-file system, this TxEnd block usally omitted to reduce the disk
         // I/O.
         todo!();
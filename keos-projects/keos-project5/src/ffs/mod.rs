@@ -107,7 +107,7 @@ use core::ops::Range;
 use disk_layout::{InodeArray, InodeBitmap, JournalSb};
 use fs_objects::Directory;
 use inode::Inode;
-use journal::{Journal, RunningTransaction};
+use journal::{GroupCommit, Journal, RunningTransaction};
 use keos::{
     KernelError,
     fs::{Disk, FileBlockNumber, InodeNumber},
@@ -176,6 +176,49 @@ impl JournalIO<'_> {
     }
 }
 
+/// How the access time of a file is updated on a read.
+///
+/// Updating the access time turns every read into a change of the inode. A
+/// read only updates the in-memory inode, and the access time reaches the disk
+/// with the next transaction that writes the inode or on the write-back of the
+/// file, so a read never opens a transaction of its own. The mount option
+/// trades the accuracy of the access time for the cost of these writes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AtimeMode {
+    /// Updates the access time on every read.
+    Strict,
+    /// Updates the access time only if it is not newer than the modification
+    /// time, or is older than [`RELATIME_INTERVAL`]. This is the default.
+    ///
+    /// This is enough to tell whether a file is read since its last
+    /// modification, while most reads do not write the inode.
+    Relative,
+    /// Never updates the access time.
+    Never,
+}
+
+/// The maximum age of the access time under [`AtimeMode::Relative`], in
+/// timer ticks (a day).
+pub const RELATIME_INTERVAL: u64 = 24 * 60 * 60 * keos::time::TICKS_PER_SEC;
+
+impl AtimeMode {
+    /// Returns `true` if a read at `now` updates the access time `atime` of a
+    /// file modified at `mtime`.
+    pub fn should_update(self, atime: u64, mtime: u64, now: u64) -> bool {
+        match self {
+            AtimeMode::Strict => atime != now,
+            // The ticks restart from zero on boot, so the access time recorded
+            // before a reboot may be ahead of `now`.
+            AtimeMode::Relative => atime <= mtime || now.saturating_sub(atime) >= RELATIME_INTERVAL,
+            AtimeMode::Never => false,
+        }
+    }
+}
+
+/// The maximum number of unreferenced inodes kept in memory by
+/// [`FastFileSystemInner::inode_cache`].
+pub const INODE_CACHE_SIZE: usize = 64;
+
 /// Represents the internal structure of a Fast File System (FFS).
 ///
 /// This structure encapsulates the core components of the FFS implementation,
@@ -211,14 +254,36 @@ pub struct FastFileSystemInner {
     pub sb: BlockPointsTo<disk_layout::SuperBlock>,
 
     /// In-memory table mapping inode numbers to their live representations.
+    ///
+    /// An inode stays in this table while a [`TrackedInode`] refers to it,
+    /// so that every user shares a single view of the inode.
     pub inodes: SpinLock<BTreeMap<InodeNumber, Arc<RwLock<Inode>>>>,
 
+    /// Recently used inodes that are no longer referenced.
+    ///
+    /// When the last [`TrackedInode`] of a linked inode is dropped, the inode
+    /// moves from [`FastFileSystemInner::inodes`] to this cache, so that
+    /// opening the file again does not read the inode from the disk. The cache
+    /// holds at most [`INODE_CACHE_SIZE`] inodes, and evicts the least
+    /// recently released one beyond it. As every change of an inode but the
+    /// access time is written to its on-disk inode array within the same
+    /// transaction, a cached inode is simply dropped on the eviction, losing
+    /// at most an access time not yet written (see [`Inode::atime_dirty`]).
+    pub inode_cache: SpinLock<LRUCache<InodeNumber, Arc<RwLock<Inode>>, INODE_CACHE_SIZE>>,
+
     /// The current state of the journal (if present), wrapped in a
     /// lock to allow mutable access during journal operations.
     pub journal: Option<SpinLock<Journal>>,
 
     /// Whether trace the transactions for debugging purpose.
     pub debug_journal: bool,
+
+    /// How the access time of a file is updated on a read.
+    pub atime_mode: SpinLock<AtimeMode>,
+
+    /// The transactions committed within the commit window, which are
+    /// written to the journal together.
+    pub group_commit: GroupCommit,
 }
 
 impl FastFileSystemInner {
@@ -247,8 +312,11 @@ impl FastFileSystemInner {
                 blocks: SpinLock::new(LRUCache::new()),
                 sb,
                 inodes: SpinLock::new(BTreeMap::new()),
+                inode_cache: SpinLock::new(LRUCache::new()),
                 journal: None,
                 debug_journal,
+                atime_mode: SpinLock::new(AtimeMode::Relative),
+                group_commit: GroupCommit::default(),
             };
 
             if this.has_journal > 0 && !disable_journal {
@@ -359,21 +427,39 @@ impl FastFileSystemInner {
             "[FFS-ERROR] You must cannot directly read the metadata. Use `MetaData::load` or `JournalIO`."
         );
         let mut b = Box::new([0u8; 0x1000]);
-        for i in 0..8 {
-            self.disk.read(
-                lba.into_sector() + i,
-                b[512 * i..512 * (i + 1)].as_mut_array().unwrap(),
-            )?;
-        }
+        self.disk.read_many(lba.into_sector(), &mut b[..])?;
 
         Ok(b)
     }
 
+    /// Reads contiguous data blocks from disk.
+    ///
+    /// This function retrieves `blocks.len()` blocks starting from the logical
+    /// block address (LBA) `lba` into `blocks`, fetching them from the disk
+    /// in a single request if the device supports it. It is used for
+    /// reading the contiguous blocks of a file at once.
+    pub fn read_data_blocks(
+        &self,
+        lba: LogicalBlockAddress,
+        blocks: &mut [[u8; 4096]],
+    ) -> Result<(), KernelError> {
+        assert!(
+            self.data_block_start() <= lba,
+            "[FFS-ERROR] You must cannot directly read the metadata. Use `MetaData::load` or `JournalIO`."
+        );
+        self.disk
+            .read_many(lba.into_sector(), blocks.as_flattened_mut())
+    }
+
     /// Writes a 4 KiB data block to disk.
     ///
     /// This function stores the given buffer at the specified logical block
     /// address (LBA) on the underlying disk. It is typically used for writing
     /// file contents.
+    ///
+    /// Within a transaction, stage the write with
+    /// [`RunningTransaction::write_data`] instead, so that the data is written
+    /// before the metadata referring to it is committed.
     pub fn write_data_block(
         &self,
         lba: LogicalBlockAddress,
@@ -473,6 +559,8 @@ impl FastFileSystemInner {
                             guard[512 * i..512 * (i + 1)].as_mut_array().unwrap(),
                         )?;
                     }
+                    // The grouped transactions are not checkpointed yet.
+                    self.group_commit.lookup(lba, &mut guard);
                     guard.unlock();
                 }
                 Ok(b)
@@ -482,6 +570,26 @@ impl FastFileSystemInner {
         result
     }
 
+    /// Writes the grouped transactions to the journal, and checkpoints them.
+    ///
+    /// See [`GroupCommit`] for details.
+    pub fn flush_journal(&self) -> Result<(), KernelError> {
+        let Some(journal) = self.journal.as_ref() else {
+            return Ok(());
+        };
+        if self.group_commit.is_empty() {
+            return Ok(());
+        }
+        let (journal, _) = self.group_commit.flush(
+            journal.lock(),
+            JournalIO { ffs: self },
+            self,
+            self.debug_journal,
+        )?;
+        journal.unlock();
+        Ok(())
+    }
+
     /// Allocates a new inode in the file system.
     ///
     /// This function creates a new inode on disk and returns both its
@@ -530,19 +638,48 @@ impl FastFileSystemInner {
         Err(KernelError::NoSpace)
     }
 
-    /// Allocates a new data block on disk.
+    /// Returns the block near which the data blocks of the inode `ino` are
+    /// allocated, when the file has no block to be placed next to.
+    ///
+    /// Like the cylinder groups of the original FFS, the data region is split
+    /// evenly among the inodes. This places the blocks of a file together,
+    /// apart from those of the other files.
+    pub fn block_hint(&self, ino: InodeNumber) -> LogicalBlockAddress {
+        let index = (ino.into_u32() - 1) as usize;
+        let start = self.data_block_start();
+        let data_blocks = self.block_count - start.into_u64() as usize;
+        start + index * data_blocks / self.inode_count
+    }
+
+    /// Allocates a new data block on disk, near the `hint`.
     ///
     /// This function reserves a free block for use in the file system,
     /// recording the allocation in the active transaction. The block is
     /// marked as used in the allocation bitmap and returned to the caller.
+    ///
+    /// The search starts from the `hint` (e.g., the block after the previous
+    /// block of the file, or [`FastFileSystemInner::block_hint`]), and
+    /// proceeds forward through the bitmap block that covers it, wrapping
+    /// around at its end. Only if the bitmap block is full, the other bitmap
+    /// blocks are scanned.
     pub fn allocate_block(
         &self,
+        hint: LogicalBlockAddress,
         tx: &RunningTransaction,
     ) -> Result<LogicalBlockAddress, KernelError> {
-        for (i, lba) in self.block_bitmap().enumerate() {
+        let bitmaps = self.block_bitmap();
+        let first = bitmaps.start;
+        let (local, from) = hint.into_bitmap_lba_offset(self).unwrap_or((first, 0));
+        for lba in core::iter::once(local).chain(bitmaps.filter(|lba| *lba != local)) {
+            let i = (lba.into_u64() - first.into_u64()) as usize;
+            let from = if lba == local { from } else { 0 };
             let bitmap = disk_layout::BlockBitmap::load(self, lba)?;
             let mut bitmap = bitmap.write(tx);
-            for pos in 0..4096 * 8 {
+            for pos in (from..4096 * 8).chain(0..from) {
+                // The last bitmap block covers the blocks past the disk end.
+                if pos + i * 4096 * 8 >= self.block_count {
+                    continue;
+                }
                 if bitmap.try_allocate(pos) {
                     bitmap.submit();
                     let mut sb = self.sb.write(tx);
@@ -559,8 +696,10 @@ impl FastFileSystemInner {
     /// Retrieves an inode from disk or cache.
     ///
     /// This function returns a [`TrackedInode`] corresponding to the given
-    /// inode number. If the inode is cached in memory, it is returned
-    /// directly; otherwise, it is read from disk and added to the cache.
+    /// inode number. If the inode is in memory, either referenced or in the
+    /// [`FastFileSystemInner::inode_cache`], it is returned directly;
+    /// otherwise, it is read from disk. Either way, the inode is added to the
+    /// table of the live inodes.
     ///
     /// This method manages a "unique view" of a single inode.
     pub fn get_inode(self: &Arc<Self>, ino: InodeNumber) -> Result<TrackedInode, KernelError> {
@@ -568,6 +707,15 @@ impl FastFileSystemInner {
         let result = match guard.entry(ino) {
             Entry::Occupied(en) => Ok(TrackedInode::new(en.get().clone(), Arc::downgrade(self))),
             Entry::Vacant(en) => {
+                let mut cache = self.inode_cache.lock();
+                let cached = cache.remove(&ino);
+                cache.unlock();
+                if let Some(inode) = cached {
+                    en.insert(inode.clone());
+                    guard.unlock();
+                    return Ok(TrackedInode::new(inode, Arc::downgrade(self)));
+                }
+
                 // Lookup inode bitmap.
                 let (lba, offset) = self.get_inode_bitmap_lba_index(ino).unwrap();
                 let bitmap_block = InodeBitmap::load(self, lba)?;
@@ -593,24 +741,47 @@ impl FastFileSystemInner {
 
     /// Removes an inode from the in-memory inode table.
     ///
-    /// This function evicts the given inode from the inode cache maintained
-    /// by the file system. It does not remove the inode’s contents on disk,
+    /// This function is called when a reference to the inode is dropped, and
+    /// removes the inode from the table of the live inodes if the caller holds
+    /// the last reference. It does not remove the inode’s contents on disk,
     /// only its in-memory representation.
+    ///
+    /// An inode that is still linked moves to the
+    /// [`FastFileSystemInner::inode_cache`]. Only an unlinked inode is
+    /// returned, so that the caller frees it on the disk.
     pub fn remove_inode(&self, ino: InodeNumber) -> Option<Arc<RwLock<Inode>>> {
         let mut guard = self.inodes.lock();
         if let Entry::Occupied(en) = guard.entry(ino)
             && Arc::strong_count(en.get()) == 2
         {
-            let result = en.remove();
-            guard.unlock();
             // This means the caller only has the sole reference to the inode.
-            return Some(result);
+            let inode = en.remove();
+            let result = if inode.read().link_count > 0 {
+                // Cache it while holding the table, so that the inode is never
+                // read from the disk while being cached.
+                let mut cache = self.inode_cache.lock();
+                cache.put(ino, inode);
+                cache.unlock();
+                None
+            } else {
+                Some(inode)
+            };
+            guard.unlock();
+            return result;
         }
         guard.unlock();
         None
     }
 }
 
+impl Drop for FastFileSystemInner {
+    fn drop(&mut self) {
+        // Do not lose the grouped transactions on unmount. If the flush
+        // fails, the recovery handles them as a crash.
+        let _ = self.flush_journal();
+    }
+}
+
 /// A reference-counted wrapper around [`FastFileSystemInner`].
 ///
 /// This structure provides access to a Fast File System instance
@@ -655,13 +826,36 @@ impl FastFileSystem {
     pub fn get_inode(&self, ino: InodeNumber) -> Result<TrackedInode, KernelError> {
         self.0.get_inode(ino)
     }
+
+    /// Sets the window of the group commit in timer ticks.
+    ///
+    /// The transactions that commit within the window are written to the
+    /// journal together, as a single transaction. 0 disables the group
+    /// commit, which is the default. See [`GroupCommit`] for details.
+    pub fn set_commit_window(&self, ticks: u64) {
+        GroupCommit::set_window(&self.0, ticks);
+    }
+
+    /// Writes the grouped transactions to the journal, and checkpoints them.
+    pub fn flush_journal(&self) -> Result<(), KernelError> {
+        self.0.flush_journal()
+    }
+
+    /// Sets how the access time of a file is updated on a read.
+    ///
+    /// The default is [`AtimeMode::Relative`].
+    pub fn set_atime_mode(&self, mode: AtimeMode) {
+        let mut guard = self.0.atime_mode.lock();
+        *guard = mode;
+        guard.unlock();
+    }
 }
 
 impl keos::fs::traits::FileSystem for FastFileSystem {
     fn root(&self) -> Option<keos::fs::Directory> {
-        Some(keos::fs::Directory(Arc::new(Directory::new(
+        Some(keos::fs::Directory::new(Directory::new(
             self.get_inode(Self::ROOT_INODE_NUMBER).unwrap(),
             Arc::downgrade(&self.0),
-        )?)))
+        )?))
     }
 }
@@ -34,6 +34,12 @@ pub enum FileType {
     /// typically a structured list of directory entries that allow for
     /// hierarchical navigation within the filesystem.
     Directory = 1,
+
+    /// A named pipe (FIFO).
+    ///
+    /// A FIFO has no data blocks. It only names a rendezvous point where
+    /// readers and writers are connected with a pipe when they open it.
+    Fifo = 2,
 }
 
 impl TryFrom<u32> for FileType {
@@ -42,6 +48,7 @@ impl TryFrom<u32> for FileType {
         match value {
             0 => Ok(Self::RegularFile),
             1 => Ok(Self::Directory),
+            2 => Ok(Self::Fifo),
             _ => Err(KernelError::FilesystemCorrupted("Invalid inode type")),
         }
     }
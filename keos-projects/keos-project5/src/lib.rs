@@ -54,6 +54,7 @@ macro_rules! const_assert {
 }
 
 pub mod advanced_file_structs;
+pub mod clock;
 pub mod ffs;
 pub mod lru;
 pub mod page_cache;
@@ -62,17 +63,35 @@ pub mod process;
 use core::ops::Range;
 
 use advanced_file_structs::AdvancedFileStructs;
-use alloc::{boxed::Box, collections::btree_set::BTreeSet};
+use alloc::{boxed::Box, collections::btree_set::BTreeSet, vec::Vec};
 use keos::{
     KernelError,
-    addressing::{Pa, Va},
+    addressing::{PAGE_SIZE, Pa, Va},
+    fs::InodeNumber,
+    mm::page_table::{Permission, PteFlags},
     sync::SpinLock,
-    syscall::Registers,
+    syscall::{
+        Registers,
+        flags::{FileMode, O_CLOEXEC, O_DIRECT, S_IRUSR, S_IRWXU, S_IWUSR},
+        uaccess::UserCString,
+    },
     task::{PFErrorCode, Task},
+    teletype::Tty,
     thread::with_current,
 };
-use keos_project1::syscall::SyscallAbi;
-use keos_project3::{fork::fork, get_phys::get_phys};
+use keos_project1::{
+    file_struct::{File, FileDescriptor, FileKind, FileStruct},
+    syscall::SyscallAbi,
+};
+use keos_project2::mm_struct::MmStruct;
+use keos_project3::{
+    atomic::atomic_cas,
+    fork::fork,
+    get_phys::get_phys,
+    lazy_pager::{LazyPager, PageFaultReason},
+    shm::{shmat, shmget},
+};
+use keos_project4::spawn::spawn;
 pub use process::Thread;
 
 #[doc(hidden)]
@@ -132,11 +151,314 @@ pub enum SyscallNumber {
     Stat = 19,
     /// Synchronize a file's in-memory state with disk.
     Fsync = 20,
+    /// Retrieves the file I/O statistics of the process.
+    IoStat = 21,
+    /// Make a named pipe (FIFO).
+    Mkfifo = 22,
+    /// Create a pipe with the given capacity and flags.
+    Pipe2 = 23,
+    /// Wait until one of file descriptors becomes ready.
+    Poll = 24,
+    /// Get the thread id.
+    GetTid = 25,
+    /// Get the process id.
+    GetPid = 26,
+    /// Get the process id of the parent process.
+    GetPpid = 27,
+    /// Wait for a child process to exit.
+    Waitpid = 28,
+    /// Write back the modified pages of a file-backed memory mapping.
+    Msync = 29,
+    /// Sleep for the given number of timer ticks.
+    Sleep = 30,
+    /// Read the monotonic clock.
+    ClockGettime = 31,
+    /// Get or set the flags of an open file.
+    Fcntl = 32,
+    /// Apply or remove an advisory lock on an open file.
+    Flock = 33,
+    /// Preallocate the blocks of a file.
+    Fallocate = 34,
+    /// Transfer data between open files within the kernel.
+    Sendfile = 35,
+    /// Change the permission bits of a file.
+    Chmod = 36,
+    /// Replace the process image with a new program.
+    Execve = 37,
+    /// Get the resident set size of the process, in pages.
+    GetRss = 38,
+    /// Create or look up a shared memory segment.
+    ShmGet = 39,
+    /// Map a shared memory segment into the address space.
+    ShmAt = 40,
+    /// Atomically compare and swap a value in the user memory.
+    AtomicCas = 41,
+    /// Read the scheduling statistics of a thread.
+    SchedStats = 42,
+    /// Create a child process sharing the address space, and wait until it
+    /// calls `execve` or exits.
+    Vfork = 43,
+    /// Create a child process running a new program.
+    Spawn = 44,
     // == Grading Only ==
     /// Get Physical Address of Page (for grading purposes only)
     GetPhys = 0x81,
 }
 
+/// Opens a file, honoring the [`O_DIRECT`] and [`O_CLOEXEC`] flags, named
+/// pipes (FIFOs), and virtual consoles.
+///
+/// The flags are stripped before calling [`FileStruct::open`]. The opened
+/// regular file is then switched to the direct I/O mode, and the file
+/// descriptor of any kind of file is marked close-on-exec.
+///
+/// The device files `/dev/tty0`, `/dev/tty1`, ... open the virtual consoles
+/// ([`Tty`]) instead of a file of the file system. Like a FIFO, a console is
+/// opened as either its input end for reading or its output end for writing.
+///
+/// Opening a FIFO blocks until its other end is opened. The rendezvous is made
+/// without holding the [`FileStruct`], so that another thread of the process
+/// can open the other end meanwhile.
+///
+/// A regular file is opened only if its permission bits allow the requested
+/// access mode ([`S_IRUSR`] to read and [`S_IWUSR`] to write); otherwise,
+/// [`KernelError::InvalidAccess`] is returned.
+fn open(th: &Thread, abi: &mut SyscallAbi) -> Result<usize, KernelError> {
+    let direct = abi.arg2 & O_DIRECT != 0;
+    let cloexec = abi.arg2 & O_CLOEXEC != 0;
+    abi.arg2 &= !(O_DIRECT | O_CLOEXEC);
+    let install = |fs: &mut FileStruct, file| {
+        let fd = fs.install_file(file)?;
+        fs.set_cloexec(fd, cloexec);
+        Ok(fd.0 as usize)
+    };
+    if let Some(tty) = Tty::from_path(&UserCString::new(abi.arg1).read()?) {
+        let file = match abi.arg2 {
+            0 => File {
+                mode: FileMode::Read,
+                file: FileKind::Rx(tty.open_read()),
+            },
+            1 => File {
+                mode: FileMode::Write,
+                file: FileKind::Tx(tty.open_write()),
+            },
+            _ => return Err(KernelError::InvalidArgument),
+        };
+        return th.with_file_struct_mut(install, file);
+    }
+    let fifo = th.with_file_struct_mut(
+        |fs, abi| {
+            let path = UserCString::new(abi.arg1).read()?;
+            let file = fs.cwd.open(&path).ok();
+            if let Some(keos::fs::File::RegularFile(r)) = &file {
+                let required = match abi.arg2 {
+                    0 => S_IRUSR,
+                    1 => S_IWUSR,
+                    2 => S_IRUSR | S_IWUSR,
+                    _ => 0,
+                };
+                if r.mode() & required != required {
+                    return Err(KernelError::InvalidAccess);
+                }
+            }
+            Ok(file.and_then(|f| f.into_fifo()))
+        },
+        &*abi,
+    )?;
+    if let Some(fifo) = fifo {
+        let file = match abi.arg2 {
+            0 => File {
+                mode: FileMode::Read,
+                file: FileKind::Rx(fifo.open_read()),
+            },
+            1 => File {
+                mode: FileMode::Write,
+                file: FileKind::Tx(fifo.open_write()),
+            },
+            _ => return Err(KernelError::InvalidArgument),
+        };
+        return th.with_file_struct_mut(install, file);
+    }
+    th.with_file_struct_mut(
+        |fs, abi| {
+            let fd = fs.open(abi)?;
+            fs.set_cloexec(FileDescriptor(fd as i32), cloexec);
+            if direct
+                && let Some(File {
+                    file: FileKind::RegularFile { file, .. },
+                    ..
+                }) = fs.files.get(&FileDescriptor(fd as i32))
+            {
+                file.set_direct(true)?;
+            }
+            Ok(fd)
+        },
+        &*abi,
+    )
+}
+
+/// Makes a named pipe (FIFO).
+///
+/// # Syscall API
+/// ```c
+/// int mkfifo(const char *pathname);
+/// ```
+/// - `pathname`: Path of the new FIFO to create.
+///
+/// Returns `0` on success.
+fn mkfifo(fs: &mut FileStruct, abi: &SyscallAbi) -> Result<usize, KernelError> {
+    let path = UserCString::new(abi.arg1).read()?;
+    fs.cwd.create_fifo(&path)?;
+    Ok(0)
+}
+
+/// Preallocates the blocks of a regular file.
+///
+/// # Syscall API
+/// ```c
+/// int fallocate(int fd, off_t offset, off_t len);
+/// ```
+/// - `fd`: File descriptor of the file, opened for writing.
+/// - `offset`: The start of the byte range to allocate.
+/// - `len`: The length of the byte range, which must not be 0.
+///
+/// The blocks for the range are allocated without writing the data, and the
+/// file is extended to `offset + len` bytes if it is shorter. The new part of
+/// the file reads back as zeros. See [`keos::fs::RegularFile::allocate`].
+///
+/// Returns `0` on success.
+fn fallocate(fs: &mut FileStruct, abi: &SyscallAbi) -> Result<usize, KernelError> {
+    match fs.files.get(&FileDescriptor(abi.arg1 as i32)) {
+        Some(File {
+            mode: FileMode::Write | FileMode::ReadWrite,
+            file: FileKind::RegularFile { file, .. },
+        }) => file.allocate(abi.arg2, abi.arg3).map(|_| 0),
+        Some(File {
+            file: FileKind::RegularFile { .. },
+            ..
+        })
+        | None => Err(KernelError::BadFileDescriptor),
+        Some(_) => Err(KernelError::InvalidArgument),
+    }
+}
+
+/// Changes the permission bits of a file.
+///
+/// # Syscall API
+/// ```c
+/// int chmod(const char *pathname, mode_t mode);
+/// ```
+/// - `pathname`: Path of the file.
+/// - `mode`: The new permission bits.
+///
+/// As KeOS has no users, only the owner bits ([`S_IRWXU`]) are kept, and the
+/// bits for the group and the others are ignored. The other bits of `mode`
+/// must be zero. The new bits are checked on the next [`open`] of the file;
+/// the files already opened are not affected.
+///
+/// Returns `0` on success.
+fn chmod(fs: &mut FileStruct, abi: &SyscallAbi) -> Result<usize, KernelError> {
+    let path = UserCString::new(abi.arg1).read()?;
+    if abi.arg2 & !0o777 != 0 {
+        return Err(KernelError::InvalidArgument);
+    }
+    fs.cwd.open(&path)?.set_mode(abi.arg2 as u32 & S_IRWXU)?;
+    Ok(0)
+}
+
+/// Writes back the modified pages of a memory mapping to the disk.
+///
+/// # Syscall API
+/// ```c
+/// int msync(void *addr, size_t len);
+/// ```
+/// - `addr`: The page-aligned start address of the region.
+/// - `len`: The length of the region in bytes.
+///
+/// A file-backed mapping shares its pages with the page cache. A page written
+/// through the mapping is found by the dirty bit of its page table entry,
+/// and the corresponding slot of the page cache is written back with
+/// [`keos::fs::FileSystem::msync`]. Pages that are not loaded yet and pages of
+/// an anonymous mapping are skipped. The data is durable when this returns.
+///
+/// Returns `0` on success.
+fn msync(mm: &mut MmStruct<LazyPager>, abi: &SyscallAbi) -> Result<usize, KernelError> {
+    let addr = Va::new(abi.arg1).ok_or(KernelError::InvalidArgument)?;
+    if addr.into_usize() & (PAGE_SIZE - 1) != 0 {
+        return Err(KernelError::InvalidArgument);
+    }
+    if abi.arg2 == 0 {
+        return Ok(0);
+    }
+    let end = abi
+        .arg1
+        .checked_add(abi.arg2)
+        .and_then(Va::new)
+        .ok_or(KernelError::BadAddress)?;
+    if !mm.access_ok(addr..end, false) {
+        return Err(KernelError::BadAddress);
+    }
+    for va in (addr.into_usize()..end.into_usize()).step_by(PAGE_SIZE) {
+        sync_mapped_page(mm, Va::new(va).unwrap())?;
+    }
+    Ok(0)
+}
+
+// Writes back the page of the page cache mapped at `va`, if it is written
+// through the mapping since the last write-back.
+//
+// The dirty bit of the page table entry is cleared before the write-back, so
+// that a write after it sets the bit again and is found by the next sync.
+fn sync_mapped_page(mm: &mut MmStruct<LazyPager>, va: Va) -> Result<(), KernelError> {
+    let Ok(mut walked) = mm.page_table.walk_mut(va) else {
+        return Ok(());
+    };
+    let flags = walked.flags();
+    let Some(pa) = walked.pa() else {
+        return Ok(());
+    };
+    if !keos::fs::FileSystem::is_cached(pa) {
+        return Ok(());
+    }
+    let dirty = flags.contains(PteFlags::D);
+    if dirty && let Some(stale) = walked.set_flags(flags - PteFlags::D) {
+        stale.invalidate();
+    }
+    keos::fs::FileSystem::msync(pa, dirty)
+}
+
+// Marks the slots of the file `ino` written through the writable mappings of
+// the process dirty, before `fsync` writes back the dirty slots of the file.
+//
+// A page already written through a mapping is writable, so a later write to
+// it does not fault and is known only by the dirty bit. The bit is moved to
+// the slot, which is shared by all mappings of the page, so that the write is
+// not lost even if it is written back through another file descriptor.
+fn sync_mapped_pages(mm: &mut MmStruct<LazyPager>, ino: InodeNumber) {
+    let writable = mm
+        .page_table
+        .iter_mappings()
+        .filter(|(_, _, perm)| perm.contains(Permission::WRITE))
+        .map(|(va, _, _)| va)
+        .collect::<Vec<_>>();
+    for va in writable {
+        let Ok(mut walked) = mm.page_table.walk_mut(va) else {
+            continue;
+        };
+        let flags = walked.flags();
+        let Some(pa) = walked.pa() else {
+            continue;
+        };
+        if !flags.contains(PteFlags::D) || keos::fs::FileSystem::cached_ino(pa) != Some(ino) {
+            continue;
+        }
+        if let Some(stale) = walked.set_flags(flags - PteFlags::D) {
+            stale.invalidate();
+        }
+        keos::fs::FileSystem::mark_dirty(pa);
+    }
+}
+
 impl TryFrom<usize> for SyscallNumber {
     type Error = KernelError;
     fn try_from(no: usize) -> Result<SyscallNumber, Self::Error> {
@@ -162,6 +484,30 @@ impl TryFrom<usize> for SyscallNumber {
             18 => Ok(SyscallNumber::Readdir),
             19 => Ok(SyscallNumber::Stat),
             20 => Ok(SyscallNumber::Fsync),
+            21 => Ok(SyscallNumber::IoStat),
+            22 => Ok(SyscallNumber::Mkfifo),
+            23 => Ok(SyscallNumber::Pipe2),
+            24 => Ok(SyscallNumber::Poll),
+            25 => Ok(SyscallNumber::GetTid),
+            26 => Ok(SyscallNumber::GetPid),
+            27 => Ok(SyscallNumber::GetPpid),
+            28 => Ok(SyscallNumber::Waitpid),
+            29 => Ok(SyscallNumber::Msync),
+            30 => Ok(SyscallNumber::Sleep),
+            31 => Ok(SyscallNumber::ClockGettime),
+            32 => Ok(SyscallNumber::Fcntl),
+            33 => Ok(SyscallNumber::Flock),
+            34 => Ok(SyscallNumber::Fallocate),
+            35 => Ok(SyscallNumber::Sendfile),
+            36 => Ok(SyscallNumber::Chmod),
+            37 => Ok(SyscallNumber::Execve),
+            38 => Ok(SyscallNumber::GetRss),
+            39 => Ok(SyscallNumber::ShmGet),
+            40 => Ok(SyscallNumber::ShmAt),
+            41 => Ok(SyscallNumber::AtomicCas),
+            42 => Ok(SyscallNumber::SchedStats),
+            43 => Ok(SyscallNumber::Vfork),
+            44 => Ok(SyscallNumber::Spawn),
             0x81 => Ok(SyscallNumber::GetPhys),
             _ => Err(KernelError::NoSuchSyscall),
         }
@@ -208,47 +554,117 @@ impl Task for Thread {
     /// modifies the CPU registers accordingly.
     fn syscall(&mut self, regs: &mut Registers) {
         // ** YOU DON'T NEED TO CHANGE THIS FUNCTION **
-        let abi = SyscallAbi::from_registers(regs); // Extract ABI from the registers.
+        let mut abi = SyscallAbi::from_registers(regs); // Extract ABI from the registers.
         // Lookup the system call handler function based on the system call number.
         let return_val = SyscallNumber::try_from(abi.sysno).and_then(|no| match no {
             SyscallNumber::Exit => self.exit_group(&abi),
-            SyscallNumber::Open => self.with_file_struct_mut(|fs, abi| fs.open(abi), &abi),
-            SyscallNumber::Read => self.with_file_struct_mut(|fs, abi| fs.read(abi), &abi),
-            SyscallNumber::Write => self.with_file_struct_mut(|fs, abi| fs.write(abi), &abi),
-            SyscallNumber::Seek => self.with_file_struct_mut(|fs, abi| fs.seek(abi), &abi),
+            SyscallNumber::Open => open(self, &mut abi),
+            SyscallNumber::Read => {
+                self.with_file_struct_mut(|fs, abi| fs.read_accounted(abi), &abi)
+            }
+            SyscallNumber::Write => {
+                self.with_file_struct_mut(|fs, abi| fs.write_accounted(abi), &abi)
+            }
+            SyscallNumber::Seek => self.with_file_struct_mut(|fs, abi| fs.seek_sparse(abi), &abi),
             SyscallNumber::Tell => self.with_file_struct_mut(|fs, abi| fs.tell(abi), &abi),
-            SyscallNumber::Close => self.with_file_struct_mut(|fs, abi| fs.close(abi), &abi),
+            SyscallNumber::Close => {
+                self.with_file_struct_mut(|fs, abi| fs.close_unlocking(abi), &abi)
+            }
             SyscallNumber::Pipe => self.with_file_struct_mut(|fs, abi| fs.pipe(abi), &abi),
+            SyscallNumber::Pipe2 => self.with_file_struct_mut(|fs, abi| fs.pipe2(abi), &abi),
+            // Wait without holding the file struct, so that other threads can
+            // make the pipes ready.
+            SyscallNumber::Poll => self
+                .with_file_struct_mut(|fs, abi| fs.poll_targets(abi), &abi)
+                .and_then(|targets| keos_project1::poll::wait(&targets, abi.arg3)),
             SyscallNumber::Mmap => {
                 self.with_file_mm_struct_mut(|fs, mm, abi| mm.mmap(fs, abi), &abi)
             }
             SyscallNumber::Munmap => self.with_mm_struct_mut(|mm, abi| mm.munmap(abi), &abi),
-            SyscallNumber::Fork => self.with_file_mm_struct_mut(
-                |fs, mm, abi| {
-                    fork(fs, mm, abi, |file_struct, mm_struct| {
-                        with_current(|th| {
-                            let builder = keos::thread::ThreadBuilder::new(&th.name);
-                            let tid = builder.get_tid();
-                            builder.attach_task(Box::new(Thread::from_fs_mm_struct(
-                                file_struct,
-                                mm_struct,
-                                tid,
-                            )))
+            SyscallNumber::GetRss => self.with_mm_struct_mut(|mm, _| Ok(mm.rss()), &abi),
+            SyscallNumber::ShmGet => shmget(&abi),
+            SyscallNumber::ShmAt => self.with_mm_struct_mut(shmat, &abi),
+            SyscallNumber::AtomicCas => self.with_mm_struct_mut(atomic_cas, &abi),
+            SyscallNumber::Fork => {
+                let (ppid, process) = (self.tgid, &self.process);
+                self.with_file_mm_struct_mut(
+                    |fs, mm, abi| {
+                        fork(fs, mm, abi, |file_struct, mm_struct| {
+                            with_current(|th| {
+                                let builder = keos::thread::ThreadBuilder::new(&th.name);
+                                let tid = builder.get_tid();
+                                let mut child =
+                                    Thread::from_fs_mm_struct(file_struct, mm_struct, tid);
+                                child.ppid = ppid;
+                                process.add_child(tid, &child.process);
+                                builder.attach_task(Box::new(child))
+                            })
                         })
-                    })
-                },
-                &abi,
-            ),
+                    },
+                    &abi,
+                )
+            }
+            SyscallNumber::Spawn => {
+                let (ppid, process) = (self.tgid, &self.process);
+                self.with_file_struct_mut(
+                    |fs, abi| {
+                        spawn(fs, abi, |file_struct, mm_struct| {
+                            with_current(|th| {
+                                let builder = keos::thread::ThreadBuilder::new(&th.name);
+                                let tid = builder.get_tid();
+                                let mut child =
+                                    Thread::from_fs_mm_struct(file_struct, mm_struct, tid);
+                                child.ppid = ppid;
+                                process.add_child(tid, &child.process);
+                                builder.attach_task(Box::new(child))
+                            })
+                        })
+                    },
+                    &abi,
+                )
+            }
             SyscallNumber::ThreadCreate => self.thread_create(&abi),
             SyscallNumber::ThreadJoin => self.thread_join(&abi),
             SyscallNumber::ExitGroup => self.exit_group(&abi),
+            SyscallNumber::GetTid => self.gettid(&abi),
+            SyscallNumber::GetPid => self.getpid(&abi),
+            SyscallNumber::GetPpid => self.getppid(&abi),
+            SyscallNumber::Waitpid => self.waitpid(&abi),
+            SyscallNumber::Sleep => self.sleep(&abi),
+            SyscallNumber::ClockGettime => self.clock_gettime(&abi),
+            SyscallNumber::SchedStats => self.sched_stats(&abi),
+            SyscallNumber::Vfork => self.vfork(&abi),
+            SyscallNumber::Execve => self.execve(&abi),
+            SyscallNumber::Fcntl => self.with_file_struct_mut(|fs, abi| fs.fcntl(abi), &abi),
+            SyscallNumber::Sendfile => self.with_file_struct_mut(|fs, abi| fs.sendfile(abi), &abi),
+            // Wait without holding the file struct, as `poll` does.
+            SyscallNumber::Flock => self
+                .with_file_struct_mut(|fs, abi| fs.flock_owner(abi), &abi)
+                .and_then(|owner| keos_project1::flock::apply(&owner, abi.arg2)),
             SyscallNumber::Create => self.with_file_struct_mut(|fs, abi| fs.create(abi), &abi),
             SyscallNumber::Mkdir => self.with_file_struct_mut(|fs, abi| fs.mkdir(abi), &abi),
             SyscallNumber::Unlink => self.with_file_struct_mut(|fs, abi| fs.unlink(abi), &abi),
             SyscallNumber::Chdir => self.with_file_struct_mut(|fs, abi| fs.chdir(abi), &abi),
             SyscallNumber::Readdir => self.with_file_struct_mut(|fs, abi| fs.readdir(abi), &abi),
             SyscallNumber::Stat => self.with_file_struct_mut(|fs, abi| fs.stat(abi), &abi),
-            SyscallNumber::Fsync => self.with_file_struct_mut(|fs, abi| fs.fsync(abi), &abi),
+            SyscallNumber::Fsync => self.with_file_mm_struct_mut(
+                |fs, mm, abi| {
+                    if let Some(File {
+                        file: FileKind::RegularFile { file, .. },
+                        ..
+                    }) = fs.files.get(&FileDescriptor(abi.arg1 as i32))
+                    {
+                        sync_mapped_pages(mm, file.ino());
+                    }
+                    fs.fsync(abi)
+                },
+                &abi,
+            ),
+            SyscallNumber::IoStat => self.with_file_struct_mut(|fs, abi| fs.iostat(abi), &abi),
+            SyscallNumber::Mkfifo => self.with_file_struct_mut(mkfifo, &abi),
+            SyscallNumber::Fallocate => self.with_file_struct_mut(fallocate, &abi),
+            SyscallNumber::Chmod => self.with_file_struct_mut(chmod, &abi),
+            SyscallNumber::Msync => self.with_mm_struct_mut(msync, &abi),
             SyscallNumber::GetPhys => {
                 self.with_file_mm_struct_mut(|fs, mm, abi| get_phys(mm, fs, abi), &abi)
             }
@@ -273,11 +689,22 @@ impl Task for Thread {
 
     #[inline]
     fn page_fault(&mut self, ec: PFErrorCode, cr2: Va) {
-        self.0.page_fault(ec, cr2)
+        // Route the writes to file-backed mappings to the page cache. See
+        // [`process`] for the details.
+        let reason = PageFaultReason::new(ec, cr2);
+        if !self.do_mmap_write(&reason) {
+            self.0.page_fault(ec, cr2);
+            self.track_mmap_load(&reason);
+        }
     }
 
     #[inline]
     fn with_page_table_pa(&self, f: &fn(Pa)) {
         self.0.with_page_table_pa(f)
     }
+
+    #[inline]
+    fn thread_group(&self) -> Option<u64> {
+        self.0.thread_group()
+    }
 }
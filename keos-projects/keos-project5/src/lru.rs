@@ -109,27 +109,64 @@ impl<K: Ord + Clone, V, const MAX_SIZE: usize> LRUCache<K, V, MAX_SIZE> {
             self.detach(prev, next);
             &mut self.attach(k).v
         } else {
-            &mut self.__put(k, f()?).v
+            &mut self.__put(k, f()?).0.v
         })
     }
 
-    fn __put(&mut self, k: K, v: V) -> &mut Node<K, V> {
-        if let Some(node) = self.inner.get_mut(&k) {
-            node.v = v;
+    /// Returns the number of entries in the `LRUCache`.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if the `LRUCache` contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns `true` if the `LRUCache` contains a value for the key.
+    ///
+    /// Unlike [`LRUCache::get`], this does not update the last access time.
+    pub fn contains(&self, k: &K) -> bool {
+        self.inner.contains_key(k)
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// Unlike [`LRUCache::get`], this does not update the last access time.
+    pub fn peek(&self, k: &K) -> Option<&V> {
+        self.inner.get(k).map(|node| &node.v)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    ///
+    /// Unlike [`LRUCache::get`], this does not update the last access time.
+    pub fn peek_mut(&mut self, k: &K) -> Option<&mut V> {
+        self.inner.get_mut(k).map(|node| &mut node.v)
+    }
+
+    // Returns the inserted node, and the entry displaced by the insertion.
+    fn __put(&mut self, k: K, v: V) -> (&mut Node<K, V>, Option<(K, V)>) {
+        let displaced = if let Some(node) = self.inner.get_mut(&k) {
+            let old = core::mem::replace(&mut node.v, v);
             let (prev, next) = (node.prev.take(), node.next.take());
             self.detach(prev, next);
+            Some((k.clone(), old))
         } else {
-            if MAX_SIZE <= self.inner.len() {
-                self.remove(&self.head.clone().unwrap());
-            }
+            let evicted = if MAX_SIZE <= self.inner.len() {
+                let head = self.head.clone().unwrap();
+                self.remove(&head).map(|v| (head, v))
+            } else {
+                None
+            };
             let node = Node {
                 v,
                 prev: self.tail.clone(),
                 next: None,
             };
             self.inner.insert(k.clone(), node);
-        }
-        self.attach(k)
+            evicted
+        };
+        (self.attach(k), displaced)
     }
 
     /// Inserts a key-value pair into the `LRUCache`.
@@ -144,6 +181,13 @@ impl<K: Ord + Clone, V, const MAX_SIZE: usize> LRUCache<K, V, MAX_SIZE> {
         self.__put(k, v);
     }
 
+    /// Inserts a key-value pair like [`LRUCache::put`], and returns the entry
+    /// displaced by the insertion: the previous value of the key, or the
+    /// evicted entry.
+    pub fn push(&mut self, k: K, v: V) -> Option<(K, V)> {
+        self.__put(k, v).1
+    }
+
     /// Removes a key from the LRUCache, returning the stored value if the
     /// key was previously in the LRUCache.
     ///
@@ -66,10 +66,11 @@
 //! - [`PageCacheInner`]: An internal wrapper that coordinates low-level
 //!   interactions between the cache, and storage layer.
 //!
-//! - [`PageCacheState`]: This is the high-level cache manager. It embeds an
-//!   [`LRUCache`] keyed by `(InodeNumber, FileBlockNumber)` and manages up to
-//!   512 slots (~2MiB). It is the central entry point for page-cache-aware file
-//!   I/O.
+//! - [`PageCacheState`]: This is the high-level cache manager. It embeds a
+//!   [`SlotCache`] keyed by `(InodeNumber, FileBlockNumber)` and manages up to
+//!   64 slots (256KiB). It is the central entry point for page-cache-aware file
+//!   I/O. By default, the page cache is split into [`SHARDS`] shards of
+//!   [`PageCacheState`], which hold 512 slots (~2MiB) in total.
 //!
 //! - [`Slot`]: A [`Slot`] represents a single cached file block. It contains
 //!   the owning [`RegularFile`], the corresponding file block number (`FBA`),
@@ -78,11 +79,13 @@
 //!
 //! ### Readahead Policy
 //!
-//! KeOS employs a simple readahead policy: when a file block is read, the cache
-//! preemptively loads up to 16 subsequent blocks. This heuristic is designed to
-//! optimize sequential access workloads (e.g., file scans or streaming),
-//! reducing future read latency and improving throughput. Random workloads
-//! remain unaffected, since readahead is limited and opportunistic.
+//! KeOS employs an adaptive readahead policy: when a file block is read, the
+//! cache preemptively loads the subsequent blocks within the *readahead
+//! window* of the file. The window is tracked per file by the
+//! [`ReadaheadHistory`] of [`PageCacheShards`]; it grows on consecutive
+//! sequential accesses (e.g., file scans or streaming), reducing future read
+//! latency and improving throughput, and shrinks to zero on a random jump, so
+//! that random workloads do not waste I/O on blocks that are never read.
 //!
 //! ### Cache Replacement: LRU
 //!
@@ -93,6 +96,12 @@
 //! hot (recently accessed) pages while discarding cold ones. All these
 //! functionalities are provided by the [`LRUCache`] struct.
 //!
+//! As maintaining the strict access order is expensive under heavy churn, the
+//! page cache can instead be created with [`ReplacementPolicy::Clock`]
+//! through [`PageCache::with_policy`]. Then the slots are kept in a
+//! [`ClockCache`], which approximates LRU by giving a second chance to the
+//! slots referenced since the last sweep of its clock hand.
+//!
 //! ### Workflow
 //!
 //! 1. **Read**: On a read request, the cache checks for an existing slot. If
@@ -103,7 +112,14 @@
 //!    dirty and write-back occurs lazily, either via explicit sync or eviction.
 //!
 //! 3. **mmap**: Pages can be directly mapped into user space from the page
-//!    cache. Faults are resolved by pulling in the corresponding slot.
+//!    cache. Faults are resolved by pulling in the corresponding slot. A page
+//!    is mapped read-only until the first write to it, whose fault marks the
+//!    slot dirty with [`PageCacheState::do_mark_dirty`] and makes the mapping
+//!    writable. A later write to the page is found by the dirty bit of the
+//!    page table entry. `msync` writes the slot back with
+//!    [`PageCacheState::do_msync`], while `fsync` marks the slots of the file
+//!    dirty again with [`PageCacheState::do_mark_dirty`] and writes back the
+//!    dirty slots of the file.
 //!
 //! 4. **Unlink**: When a file is deleted, all its slots are invalidated without
 //!    flushing, ensuring consistency with the file system state.
@@ -112,6 +128,33 @@
 //!    opportunistically during eviction. This ensures persistence while
 //!    reducing redundant disk I/O.
 //!
+//! 6. **Direct I/O**: A file opened with `O_DIRECT` bypasses the cache. Before
+//!    accessing the file system, the slot of the accessed block is written
+//!    back (if dirty) and invalidated to keep the cache consistent.
+//!
+//! ### Concurrency
+//!
+//! Each opened file has a read/write lock in its [`FileState`], shared by all
+//! handles of the file. Reads and `mmap` take it shared, so that the threads
+//! reading the same file proceed in parallel; Writes take it exclusive, so
+//! that a read never observes a half-written block.
+//!
+//! The cache is split into the shards of [`PageCacheShards`], each of which
+//! is a [`PageCacheState`] guarded by its own lock. A block is cached in the
+//! shard chosen by the hash of its `(InodeNumber, FileBlockNumber)`, so that
+//! the threads accessing different files rarely wait for each other. The lock
+//! of a shard is held only while the slots are looked up or updated. On a read
+//! miss, the block is loaded from the disk by [`PageCache::fill`] without
+//! holding it, so that a thread waiting for the disk does not block the hits
+//! of the other threads. The readahead thread and the direct I/O access the
+//! disk without it as well. Instead, the readahead thread takes the lock of
+//! the file shared like a read, so that a write to the file never races with
+//! the blocks being read ahead.
+//!
+//! The eviction of a dirty slot still writes it back with the lock of the
+//! shard held, as a slot evicted before its contents reach the disk could be
+//! loaded again with the stale contents by a miss in the meantime.
+//!
 //! The following diagram depicts the work-flow of the page cache subsystem of
 //! the KeOS.
 //! ```text
@@ -169,19 +212,35 @@
 //! After implement the functionalities, move on to the next [`section`].
 //!
 //! [`section`]: mod@crate::ffs
-use crate::lru::LRUCache;
-use alloc::{string::ToString, sync::Arc};
+//! [`ReadaheadHistory`]: readahead::ReadaheadHistory
+//! [`LRUCache`]: crate::lru::LRUCache
+//! [`ClockCache`]: crate::clock::ClockCache
+use alloc::{
+    collections::BTreeMap,
+    string::ToString,
+    sync::{Arc, Weak},
+};
 use core::ops::{Deref, DerefMut};
 use keos::{
     KernelError,
+    addressing::Pa,
     channel::{Sender, channel},
     fs::{FileBlockNumber, InodeNumber, RegularFile, traits::FileSystem},
     mm::Page,
+    sync::{
+        RwLock, RwLockReadGuard, RwLockWriteGuard, SpinLock,
+        atomic::{AtomicU64, AtomicUsize},
+    },
     thread::{JoinHandle, ThreadBuilder},
 };
-use keos_project4::sync::mutex::Mutex;
 
 pub mod overlaying;
+pub mod readahead;
+pub mod replacement;
+pub mod shard;
+
+use replacement::{ReplacementPolicy, SlotCache};
+use shard::{PageCacheShards, SHARDS};
 
 /// A single entry in the page cache.
 ///
@@ -234,6 +293,16 @@ impl Slot {
     /// - On success, clears the `writeback_size` to `None`.
     ///
     /// If the slot is clean, this does not trigger the I/O.
+    ///
+    /// The write goes through [`traits::RegularFile::write`] of the file
+    /// system. On the [`ffs`], the data block is written before the inode
+    /// referring to it is committed to the journal (see [ordered data]), so a
+    /// crash during the write-back never leaves the file pointing at a block
+    /// with stale contents.
+    ///
+    /// [`traits::RegularFile::write`]: keos::fs::traits::RegularFile::write
+    /// [`ffs`]: crate::ffs
+    /// [ordered data]: crate::ffs::journal
     pub fn writeback(&mut self) -> Result<(), keos::KernelError> {
        todo!() 
     }
@@ -246,21 +315,22 @@ impl Drop for Slot {
     }
 }
 
-/// The global page cache state.
+/// A shard of the page cache state.
 ///
-/// [`PageCacheState`] wraps an [`LRUCache`] mapping `(InodeNumber,
-/// FileBlockNumber)` to [`Slot`] entries. It enforces a bounded capacity of 512
-/// slots, corresponding to 2 MiB of cached file data.
+/// [`PageCacheState`] wraps a [`SlotCache`] mapping `(InodeNumber,
+/// FileBlockNumber)` to [`Slot`] entries. It enforces a bounded capacity of 64
+/// slots, corresponding to 256 KiB of cached file data. The slots are replaced
+/// with the [`ReplacementPolicy`] of the [`SlotCache`].
 ///
-/// This state is protected by a [`Mutex`] inside [`PageCacheInner`], allowing
-/// concurrent access from multiple threads with safe eviction.
-#[repr(transparent)]
+/// Each shard is protected by its own lock inside [`PageCacheShards`],
+/// allowing concurrent access from multiple threads with safe eviction. All
+/// slots of a shard belong to the shard by [`PageCacheShards::shard_of`].
 pub struct PageCacheState(
-    LRUCache<(InodeNumber, FileBlockNumber), Slot, 512>, // 2MiB
+    SlotCache, // 256KiB
 );
 
 impl Deref for PageCacheState {
-    type Target = LRUCache<(InodeNumber, FileBlockNumber), Slot, 512>;
+    type Target = SlotCache;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
@@ -273,13 +343,26 @@ impl DerefMut for PageCacheState {
 }
 
 impl PageCacheState {
-    /// Perform readahead on sequential file blocks.
+    /// Makes a new, empty shard with the replacement `policy`.
+    pub const fn new(policy: ReplacementPolicy) -> Self {
+        Self(SlotCache::new(policy))
+    }
+
+    /// Insert the blocks read ahead into the cache.
     ///
-    /// Reads up to **16 consecutive blocks** after the given `fba`
-    /// (file block address) into the cache.
+    /// `blocks` holds the contents of the consecutive blocks after the given
+    /// `fba` (file block address), which [`PageCacheShards::readahead`] read
+    /// from the file system without holding the lock of this shard. All of
+    /// the blocks belong to this shard.
     ///
-    /// Existing cached slots are not overwritten.
-    pub fn readahead(&mut self, file: keos::fs::RegularFile, fba: FileBlockNumber) {
+    /// Existing cached slots are not overwritten, as they may be newer than
+    /// the blocks read.
+    pub fn readahead(
+        &mut self,
+        file: keos::fs::RegularFile,
+        fba: FileBlockNumber,
+        blocks: &[[u8; 4096]],
+    ) {
         todo!()
     }
 
@@ -361,6 +444,23 @@ impl PageCacheState {
         });
     }
 
+    /// Remove the slot of the given file block from the cache.
+    ///
+    /// Unlike [`PageCacheState::do_unlink`], a dirty slot is written back
+    /// before being dropped, so that the file system holds the latest
+    /// contents of the block. This is used by the direct I/O, which bypasses
+    /// the cache and accesses the file system directly.
+    pub fn do_invalidate(
+        &mut self,
+        file: keos::fs::RegularFile,
+        fba: FileBlockNumber,
+    ) -> Result<(), keos::KernelError> {
+        if let Some(mut slot) = self.0.remove(&(file.0.ino(), fba)) {
+            slot.writeback()?;
+        }
+        Ok(())
+    }
+
     /// Write back all dirty slots belonging to the given file.
     ///
     /// Ensures that all cached modifications to the file are persisted
@@ -377,14 +477,92 @@ impl PageCacheState {
 
         Ok(())
     }
+
+    /// Write back the slot whose backing page is at the physical address
+    /// `pa`.
+    ///
+    /// This is used by `msync`, which finds the slots of a memory mapping by
+    /// their pages. A write through the mapping does not mark the slot dirty,
+    /// so the caller tells whether the page was modified with `dirty`. Does
+    /// nothing if no slot is backed by `pa`.
+    pub fn do_msync(&mut self, pa: Pa, dirty: bool) -> Result<(), keos::KernelError> {
+        if let Some(slot) = self.find_page(pa) {
+            if dirty && slot.writeback_size.is_none() {
+                slot.writeback_size = Some(slot.file.size());
+            }
+            slot.writeback()?;
+        }
+        Ok(())
+    }
+
+    /// Mark the slot whose backing page is at the physical address `pa`
+    /// dirty, as the page is mapped writable into a process.
+    ///
+    /// Returns `false` if no slot is backed by `pa`.
+    pub fn do_mark_dirty(&mut self, pa: Pa) -> bool {
+        if let Some(slot) = self.find_page(pa) {
+            if slot.writeback_size.is_none() {
+                slot.writeback_size = Some(slot.file.size());
+            }
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// The state of an opened regular file, shared among all handles of the
+/// file (see [`PageCacheInner::states`]).
+pub struct FileState {
+    /// The size of the file in bytes.
+    pub size: AtomicUsize,
+    /// The time of the last modification of the file, in timer ticks since
+    /// boot.
+    pub mtime: AtomicU64,
+    /// The read/write lock of the file.
+    ///
+    /// Reads take the lock shared, so that the threads reading the same file
+    /// proceed in parallel, while writes take it exclusive. A read therefore
+    /// never observes a half-written block.
+    pub io: RwLock<()>,
+}
+
+impl FileState {
+    /// Creates the state of a file of `size` bytes, last modified at
+    /// `mtime`.
+    pub fn new(size: usize, mtime: u64) -> Self {
+        Self {
+            size: AtomicUsize::new(size),
+            mtime: AtomicU64::new(mtime),
+            io: RwLock::new(()),
+        }
+    }
+
+    /// Locks the file for reading.
+    pub fn read_lock(&self) -> RwLockReadGuard<'_, ()> {
+        self.io.read()
+    }
+
+    /// Locks the file for writing.
+    pub fn write_lock(&self) -> RwLockWriteGuard<'_, ()> {
+        self.io.write()
+    }
 }
 
 /// Internal representation of a [`PageCache`].
 pub struct PageCacheInner<FS: FileSystem> {
     /// The file system that the page cache operates on.
     pub fs: FS,
-    /// The shared state of the page cache.
-    pub inner: Arc<Mutex<PageCacheState>>,
+    /// The shards of the page cache.
+    pub shards: Arc<PageCacheShards>,
+    /// States of the opened regular files, shared among all handles of a
+    /// file.
+    ///
+    /// As writes are deferred by the cache, the size and the modification
+    /// time recorded in the underlying file system lag behind until
+    /// write-back. Sharing the state lets every handle observe the changes
+    /// made by another one.
+    pub states: Arc<SpinLock<BTreeMap<InodeNumber, Weak<FileState>>>>,
     /// Channel for sending read-ahead requests to the background thread.
     pub request: Sender<(keos::fs::RegularFile, FileBlockNumber)>,
     /// Join handle for the read-ahead thread.
@@ -409,29 +587,92 @@ impl<FS: FileSystem> PageCache<FS> {
     ///
     /// Spawns a background thread to service read-ahead requests.
     pub fn new(fs: FS) -> Self {
-        info!("Mounting {} to PageCache.", core::any::type_name::<FS>());
-        let (request, rx) = channel(100);
-        let inner = Arc::new(Mutex::new(PageCacheState(LRUCache::new())));
-        let cloned_inner = inner.clone();
+        Self::with_policy(fs, ReplacementPolicy::Lru)
+    }
+
+    /// Create a new page cache associated with the given file system, which
+    /// replaces the slots with the given `policy`.
+    ///
+    /// Spawns a background thread to service read-ahead requests.
+    pub fn with_policy(fs: FS, policy: ReplacementPolicy) -> Self {
+        Self::with_shards(fs, policy, SHARDS)
+    }
+
+    /// Create a new page cache associated with the given file system, which
+    /// is split into `count` shards replacing the slots with the given
+    /// `policy`.
+    ///
+    /// Spawns a background thread to service read-ahead requests.
+    pub fn with_shards(fs: FS, policy: ReplacementPolicy, count: usize) -> Self {
+        info!(
+            "Mounting {} to PageCache ({:?}, {} shards).",
+            core::any::type_name::<FS>(),
+            policy,
+            count
+        );
+        let (request, rx) = channel::<(RegularFile, FileBlockNumber)>(100);
+        let shards = Arc::new(PageCacheShards::new(count, policy));
+        let states = Arc::new(SpinLock::new(
+            BTreeMap::<InodeNumber, Weak<FileState>>::new(),
+        ));
+        let (cloned_shards, cloned_states) = (shards.clone(), states.clone());
         let _readahead_thread = ThreadBuilder::new("[Readahead]".to_string()).spawn(move || {
             println!(
                 "Start [Readahead] (TID: {})",
                 keos::thread::Current::get_tid()
             );
             while let Ok((file, fba)) = rx.recv() {
-                let mut guard = cloned_inner.lock();
-                guard.readahead(file, fba);
+                let guard = cloned_states.lock();
+                let state = guard.get(&file.ino()).and_then(Weak::upgrade);
                 guard.unlock();
+                let _file_guard = state.as_ref().map(|state| state.read_lock());
+                cloned_shards.readahead(file, fba);
             }
         });
         PageCache(Arc::new(PageCacheInner {
             fs,
-            inner,
+            shards,
+            states,
             request,
             _readahead_thread,
         }))
     }
 
+    /// Load the file block `fba` into the cache, unless it is cached.
+    ///
+    /// The block is read from the file system without holding the lock of its
+    /// shard, so that a read missing the cache does not block the other
+    /// threads accessing the cache during the disk I/O. The block is inserted
+    /// only if no one cached it meanwhile, as the cached one may be newer.
+    ///
+    /// The caller must hold the lock of the file (see [`FileState::io`]), so
+    /// that the file is not written while its block is being read.
+    pub fn fill(
+        &self,
+        file: &keos::fs::RegularFile,
+        fba: FileBlockNumber,
+    ) -> Result<(), KernelError> {
+        let id = (file.ino(), fba);
+        let guard = self.0.shards.lock(id);
+        let cached = guard.contains(&id);
+        guard.unlock();
+        if cached {
+            return Ok(());
+        }
+
+        let mut page = Page::new();
+        // Leave the blocks past the end of the file to `PageCache::read`.
+        if !file.0.read(fba, page.inner_mut().try_into().unwrap())? {
+            return Ok(());
+        }
+        let mut guard = self.0.shards.lock(id);
+        if !guard.contains(&id) {
+            guard.insert(id, Slot::new(file.clone(), fba, page));
+        }
+        guard.unlock();
+        Ok(())
+    }
+
     /// Read a page from the cache or underlying file system.
     ///
     /// A read-ahead request for subsequent pages is issued to the
@@ -443,7 +684,8 @@ impl<FS: FileSystem> PageCache<FS> {
         buf: &mut [u8; 4096],
     ) -> Result<bool, KernelError> {
         // TODO:
-        // 1. read the requested file synchronously.
+        // 1. read the requested file synchronously from the shard of the
+        //    block (see `PageCacheShards::lock`).
         // 2. send a read-ahead request to the readahead thread.
         todo!()
     }
@@ -451,13 +693,12 @@ impl<FS: FileSystem> PageCache<FS> {
 
 impl<FS: FileSystem> Drop for PageCacheInner<FS> {
     fn drop(&mut self) {
+        // Closing the channel ends the loop of the read-ahead thread once it
+        // drains the pending requests, without killing it in the middle of a
+        // read-ahead.
+        self.request.close();
         if keos::PANIC_DEPTH.load(core::sync::atomic::Ordering::SeqCst) == 0 {
-            let readahead_tid = self._readahead_thread.tid;
-            println!(
-                "Stop [Readahead] (TID: {}) / success: {}",
-                readahead_tid,
-                keos::thread::kill_by_tid(readahead_tid, 0).is_ok()
-            );
+            println!("Stop [Readahead] (TID: {})", self._readahead_thread.tid);
         }
     }
 }
@@ -1,11 +1,13 @@
 //! An overlaying mechanism for appling page cache to any file system.
 
-use super::PageCache;
-use alloc::{string::String, vec::Vec};
+use super::{FileState, PageCache};
+use alloc::{string::String, sync::Arc, vec::Vec};
 use keos::{
-    fs::{FileBlockNumber, InodeNumber, traits::FileSystem},
+    addressing::Pa,
+    fs::{CacheAccounting, FileBlockNumber, InodeNumber, traits::FileSystem},
     mm::Page,
-    sync::atomic::AtomicUsize,
+    sync::atomic::AtomicBool,
+    time::now_ticks,
 };
 
 /// An overlay on the Directory.
@@ -24,53 +26,90 @@ impl<FS: FileSystem> keos::fs::traits::Directory for Directory<FS> {
         self.0.link_count()
     }
 
+    fn blocks(&self) -> usize {
+        self.0.blocks()
+    }
+
+    fn mtime(&self) -> u64 {
+        self.0.mtime()
+    }
+
+    fn atime(&self) -> u64 {
+        self.0.atime()
+    }
+
+    fn mode(&self) -> u32 {
+        self.0.mode()
+    }
+
+    fn set_mode(&self, mode: u32) -> Result<(), keos::KernelError> {
+        self.0.set_mode(mode)
+    }
+
     fn open_entry(&self, entry: &str) -> Result<keos::fs::File, keos::KernelError> {
         self.0.open(entry).map(|en| match en {
             keos::fs::File::RegularFile(r) => {
-                keos::fs::File::RegularFile(keos::fs::RegularFile::new(RegularFile {
-                    size: AtomicUsize::new(r.size()),
-                    file: r,
-                    cache: self.1.clone(),
-                }))
+                keos::fs::File::RegularFile(RegularFile::overlay(r, self.1.clone()))
             }
             keos::fs::File::Directory(d) => {
                 keos::fs::File::Directory(keos::fs::Directory::new(Directory(d, self.1.clone())))
             }
+            keos::fs::File::Fifo(f) => keos::fs::File::Fifo(f),
         })
     }
 
     fn create_entry(&self, entry: &str, is_dir: bool) -> Result<keos::fs::File, keos::KernelError> {
         self.0.create(entry, is_dir).map(|en| match en {
             keos::fs::File::RegularFile(r) => {
-                keos::fs::File::RegularFile(keos::fs::RegularFile::new(RegularFile {
-                    size: AtomicUsize::new(r.size()),
-                    file: r,
-                    cache: self.1.clone(),
-                }))
+                keos::fs::File::RegularFile(RegularFile::overlay(r, self.1.clone()))
             }
             keos::fs::File::Directory(d) => {
                 keos::fs::File::Directory(keos::fs::Directory::new(Directory(d, self.1.clone())))
             }
+            keos::fs::File::Fifo(f) => keos::fs::File::Fifo(f),
         })
     }
 
+    fn create_fifo_entry(&self, entry: &str) -> Result<keos::fs::File, keos::KernelError> {
+        self.0.0.create_fifo_entry(entry)
+    }
+
     fn unlink_entry(&self, entry: &str) -> Result<(), keos::KernelError> {
         self.0.open(entry).map(|en| {
             if let keos::fs::File::RegularFile(r) = en {
                 // Remove the slot from the cache
-                let mut guard = self.1.0.inner.lock();
-                guard.do_unlink(r);
-                guard.unlock();
+                self.1.0.shards.unlink(r);
             }
         })?;
 
         self.0.unlink(entry)
     }
 
+    fn rename_entry(
+        &self,
+        entry: &str,
+        dst: &keos::fs::Directory,
+        new_entry: &str,
+    ) -> Result<(), keos::KernelError> {
+        self.0.0.rename_entry(entry, dst, new_entry)
+    }
+
     fn read_dir(&self) -> Result<Vec<(InodeNumber, String)>, keos::KernelError> {
         self.0.read_dir()
     }
 
+    fn read_dir_from(
+        &self,
+        cursor: &mut usize,
+        max: usize,
+    ) -> Result<Vec<(InodeNumber, String)>, keos::KernelError> {
+        self.0.read_dir_from(cursor, max)
+    }
+
+    fn writeback(&self) -> Result<(), keos::KernelError> {
+        self.0.writeback()
+    }
+
     fn removed(&self) -> Result<&keos::sync::atomic::AtomicBool, keos::KernelError> {
         self.0.removed()
     }
@@ -79,8 +118,73 @@ impl<FS: FileSystem> keos::fs::traits::Directory for Directory<FS> {
 /// An overlay on the RegularFile.
 pub struct RegularFile<FS: FileSystem> {
     file: keos::fs::RegularFile,
-    size: AtomicUsize,
+    state: Arc<FileState>,
     cache: PageCache<FS>,
+    direct: AtomicBool,
+}
+
+impl<FS: FileSystem + 'static> RegularFile<FS> {
+    /// Overlays the page cache on the regular file `file`.
+    ///
+    /// All handles of the same file share a single [`FileState`], so that a
+    /// write to the file is visible to every handle even before it is written
+    /// back.
+    fn overlay(file: keos::fs::RegularFile, cache: PageCache<FS>) -> keos::fs::RegularFile {
+        let mut states = cache.0.states.lock();
+        let state = match states.get(&file.ino()).and_then(|state| state.upgrade()) {
+            Some(state) => state,
+            None => {
+                states.retain(|_, state| state.strong_count() != 0);
+                let state = Arc::new(FileState::new(file.size(), file.mtime()));
+                states.insert(file.ino(), Arc::downgrade(&state));
+                state
+            }
+        };
+        states.unlock();
+        keos::fs::RegularFile::new(RegularFile {
+            file,
+            state,
+            cache,
+            direct: AtomicBool::new(false),
+        })
+    }
+}
+
+impl<FS: FileSystem> RegularFile<FS> {
+    /// Reads the block `fba`, with the file locked for reading.
+    ///
+    /// A miss is filled with [`PageCache::fill`] first, so that the disk I/O
+    /// does not hold the lock of the shard.
+    fn read_locked(
+        &self,
+        fba: FileBlockNumber,
+        buf: &mut [u8; 4096],
+    ) -> Result<bool, keos::KernelError> {
+        let shards = &self.cache.0.shards;
+        let id = (self.file.ino(), fba);
+        if self.direct.load() {
+            let mut guard = shards.lock(id);
+            let result = guard.do_invalidate(self.file.clone(), fba);
+            guard.unlock();
+            result?;
+            return self.file.0.read(fba, buf);
+        }
+        let guard = shards.lock(id);
+        let hit = guard.contains(&id);
+        guard.unlock();
+        shards.record_access(self.file.ino(), fba);
+        CacheAccounting::record(hit);
+        if !hit {
+            self.cache.fill(&self.file, fba)?;
+        }
+        self.cache.read(&self.file, fba, buf)
+    }
+
+    /// Returns `true` if the file system holds the block `fba`, i.e., the
+    /// block is not a hole on the disk.
+    fn is_on_disk(&self, fba: FileBlockNumber) -> bool {
+        self.file.0.seek_hole_data(fba.0 * 0x1000, false) == Ok(fba.0 * 0x1000)
+    }
 }
 
 impl<FS: FileSystem> keos::fs::traits::RegularFile for RegularFile<FS> {
@@ -89,11 +193,108 @@ impl<FS: FileSystem> keos::fs::traits::RegularFile for RegularFile<FS> {
     }
 
     fn size(&self) -> usize {
-        self.size.load()
+        self.state.size.load()
+    }
+
+    fn link_count(&self) -> usize {
+        self.file.link_count()
+    }
+
+    // A dirty block may fill a hole of a sparse file, which the file system
+    // does not count until the block is written back.
+    fn blocks(&self) -> usize {
+        let holes = self
+            .cache
+            .0
+            .shards
+            .dirty_blocks(self.file.ino())
+            .into_iter()
+            .filter(|fba| !self.is_on_disk(*fba))
+            .count();
+        self.file.blocks() + holes
+    }
+
+    // The write-back also updates the time in the file system, so the later
+    // one is the time of the last modification.
+    fn mtime(&self) -> u64 {
+        self.state.mtime.load().max(self.file.mtime())
+    }
+
+    // Only the reads that miss the cache reach the file system, which keeps
+    // the access time with the granularity of the mount option anyway.
+    fn atime(&self) -> u64 {
+        self.file.atime()
+    }
+
+    fn mode(&self) -> u32 {
+        self.file.mode()
+    }
+
+    fn set_mode(&self, mode: u32) -> Result<(), keos::KernelError> {
+        self.file.set_mode(mode)
     }
 
     fn read(&self, fba: FileBlockNumber, buf: &mut [u8; 4096]) -> Result<bool, keos::KernelError> {
-        self.cache.read(&self.file, fba, buf)
+        let _guard = self.state.read_lock();
+        self.read_locked(fba, buf)
+    }
+
+    // A direct read skips the cache, so the file system can fetch the blocks
+    // at once. Otherwise, each block is served by the cache.
+    fn read_many(
+        &self,
+        fba: FileBlockNumber,
+        bufs: &mut [[u8; 4096]],
+    ) -> Result<usize, keos::KernelError> {
+        let _guard = self.state.read_lock();
+        if self.direct.load() {
+            (0..bufs.len()).try_for_each(|i| {
+                let mut guard = self.cache.0.shards.lock((self.file.ino(), fba + i));
+                let result = guard.do_invalidate(self.file.clone(), fba + i);
+                guard.unlock();
+                result
+            })?;
+            return self.file.0.read_many(fba, bufs);
+        }
+        for (i, buf) in bufs.iter_mut().enumerate() {
+            if !self.read_locked(fba + i, buf)? {
+                return Ok(i);
+            }
+        }
+        Ok(bufs.len())
+    }
+
+    // Like `blocks`, the holes of the file system that the dirty blocks fill
+    // are data.
+    fn seek_hole_data(&self, ofs: usize, hole: bool) -> Result<usize, keos::KernelError> {
+        let size = self.size();
+        if ofs >= size {
+            return Err(keos::KernelError::NoSuchDeviceOrAddress);
+        }
+        let dirty = self.cache.0.shards.dirty_blocks(self.file.ino());
+        if !hole {
+            let cached = dirty
+                .range(FileBlockNumber(ofs / 0x1000)..)
+                .next()
+                .map(|fba| (fba.0 * 0x1000).max(ofs));
+            let on_disk = self.file.0.seek_hole_data(ofs, false).ok();
+            return cached
+                .into_iter()
+                .chain(on_disk)
+                .min()
+                .filter(|data| *data < size)
+                .ok_or(keos::KernelError::NoSuchDeviceOrAddress);
+        }
+        let mut pos = ofs;
+        while pos < size {
+            // Beyond the end of the file on the disk is a hole.
+            let found = self.file.0.seek_hole_data(pos, true).unwrap_or(pos);
+            if !dirty.contains(&FileBlockNumber(found / 0x1000)) {
+                return Ok(found.min(size));
+            }
+            pos = (found / 0x1000 + 1) * 0x1000;
+        }
+        Ok(size)
     }
 
     fn write(
@@ -102,33 +303,55 @@ impl<FS: FileSystem> keos::fs::traits::RegularFile for RegularFile<FS> {
         buf: &[u8; 4096],
         min_size: usize,
     ) -> Result<(), keos::KernelError> {
-        if self.size() < min_size {
-            self.size.store(min_size);
-            let mut guard = self.cache.0.inner.lock();
-            let result = guard.do_write(self.file.clone(), fba, buf, min_size);
+        let _file_guard = self.state.write_lock();
+        let mut guard = self.cache.0.shards.lock((self.file.ino(), fba));
+        let result = if self.direct.load() {
+            let result = guard.do_invalidate(self.file.clone(), fba);
             guard.unlock();
-            result
+            // The block cannot be cached again until the file system has the
+            // new contents, as a miss or a readahead on the file waits for
+            // the lock of the file.
+            result.and_then(|_| self.file.0.write(fba, buf, min_size.max(self.size())))
         } else {
-            let mut guard = self.cache.0.inner.lock();
-            let result = guard.do_write(self.file.clone(), fba, buf, self.size.load());
+            let result = guard.do_write(self.file.clone(), fba, buf, min_size.max(self.size()));
             guard.unlock();
             result
+        };
+        // Publish the new size only after the data is written, so that a
+        // concurrent reader never reads past the written data.
+        if result.is_ok() {
+            self.state.size.fetch_max(min_size);
+            self.state.mtime.fetch_max(now_ticks());
         }
+        result
     }
 
     fn writeback(&self) -> Result<(), keos::KernelError> {
-        let mut guard = self.cache.0.inner.lock();
-        let result = guard.do_writeback(self.file.clone());
-        guard.unlock();
-        result
+        self.cache.0.shards.writeback(self.file.clone())
     }
 
     fn mmap(&self, fba: FileBlockNumber) -> Result<Page, keos::KernelError> {
-        let mut guard = self.cache.0.inner.lock();
+        let _file_guard = self.state.read_lock();
+        let mut guard = self.cache.0.shards.lock((self.file.ino(), fba));
         let result = guard.do_mmap(self.file.clone(), fba);
         guard.unlock();
         result
     }
+
+    fn set_direct(&self, direct: bool) -> Result<(), keos::KernelError> {
+        self.direct.store(direct);
+        Ok(())
+    }
+
+    // The cached pages in the range are kept, as they are newer than the
+    // zeroed blocks and are written back over them.
+    fn allocate(&self, ofs: usize, len: usize) -> Result<(), keos::KernelError> {
+        let _guard = self.state.write_lock();
+        self.file.0.allocate(ofs, len)?;
+        self.state.size.fetch_max(ofs + len);
+        self.state.mtime.fetch_max(now_ticks());
+        Ok(())
+    }
 }
 
 impl<FS: FileSystem + 'static> FileSystem for PageCache<FS> {
@@ -138,4 +361,20 @@ impl<FS: FileSystem + 'static> FileSystem for PageCache<FS> {
             .root()
             .map(|n| keos::fs::Directory::new(Directory(n, Self(self.0.clone()))))
     }
+
+    fn msync(&self, pa: Pa, dirty: bool) -> Result<(), keos::KernelError> {
+        self.0.shards.msync(pa, dirty)
+    }
+
+    fn is_cached(&self, pa: Pa) -> bool {
+        self.0.shards.is_cached(pa)
+    }
+
+    fn mark_dirty(&self, pa: Pa) -> bool {
+        self.0.shards.mark_dirty(pa)
+    }
+
+    fn cached_ino(&self, pa: Pa) -> Option<InodeNumber> {
+        self.0.shards.cached_ino(pa)
+    }
 }
@@ -0,0 +1,89 @@
+//! Adaptive readahead window.
+//!
+//! A fixed-size readahead over-reads on random access and under-reads on
+//! long sequential scans. [`ReadaheadHistory`] instead remembers, for each
+//! recently accessed file, the last block read and the current readahead
+//! window:
+//!
+//! - The first access to a file starts with [`READAHEAD_INITIAL_WINDOW`].
+//! - Each sequential access (the block right after the last one) doubles the
+//!   window, up to [`READAHEAD_MAX_WINDOW`].
+//! - A random jump shrinks the window to zero, disabling readahead until the
+//!   access pattern becomes sequential again.
+//!
+//! Re-reading the last block leaves the window untouched.
+
+use crate::lru::LRUCache;
+use keos::fs::{FileBlockNumber, InodeNumber};
+
+/// The readahead window for the first access of a file.
+pub const READAHEAD_INITIAL_WINDOW: usize = 4;
+
+/// The maximum readahead window.
+pub const READAHEAD_MAX_WINDOW: usize = 32;
+
+/// Sequential-access history of a single file.
+#[derive(Clone, Copy)]
+struct History {
+    last: FileBlockNumber,
+    window: usize,
+}
+
+/// Per-file sequential-access history that drives the readahead window.
+///
+/// Only the 64 most recently accessed files are tracked; a file whose
+/// history is evicted simply restarts from [`READAHEAD_INITIAL_WINDOW`].
+pub struct ReadaheadHistory(LRUCache<InodeNumber, History, 64>);
+
+impl Default for ReadaheadHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReadaheadHistory {
+    /// Makes a new, empty `ReadaheadHistory`.
+    pub const fn new() -> Self {
+        Self(LRUCache::new())
+    }
+
+    /// Records an access to the block `fba` of the file `ino`, and returns
+    /// the updated readahead window of the file.
+    pub fn record(&mut self, ino: InodeNumber, fba: FileBlockNumber) -> usize {
+        let history = match self.0.get(ino) {
+            Some(History { last, window }) if last.0 + 1 == fba.0 => History {
+                last: fba,
+                window: (*window * 2).clamp(READAHEAD_INITIAL_WINDOW, READAHEAD_MAX_WINDOW),
+            },
+            Some(History { last, window }) if *last == fba => History {
+                last: fba,
+                window: *window,
+            },
+            Some(_) => History {
+                last: fba,
+                window: 0,
+            },
+            None => History {
+                last: fba,
+                window: READAHEAD_INITIAL_WINDOW,
+            },
+        };
+        self.0.put(ino, history);
+        history.window
+    }
+
+    /// Returns the current readahead window of the file `ino`.
+    ///
+    /// A file without any recorded access has the initial window.
+    pub fn window(&self, ino: InodeNumber) -> usize {
+        self.0
+            .peek(&ino)
+            .map(|history| history.window)
+            .unwrap_or(READAHEAD_INITIAL_WINDOW)
+    }
+
+    /// Forgets the access history of the file `ino`.
+    pub fn forget(&mut self, ino: InodeNumber) {
+        self.0.remove(&ino);
+    }
+}
@@ -0,0 +1,183 @@
+//! Cache replacement policies of the page cache.
+//!
+//! The slots of [`PageCacheState`] are kept in a [`SlotCache`], which is
+//! backed by either a strict [`LRUCache`] or an approximate [`ClockCache`]
+//! according to the [`ReplacementPolicy`] chosen when the page cache is
+//! created. Each shard of the page cache has its own [`SlotCache`], so a
+//! slot is evicted only to make room in its own shard. Both policies evict a
+//! slot by dropping it, so a dirty [`Slot`] is flushed back to disk before
+//! being discarded regardless of the policy.
+//!
+//! [`PageCacheState`]: super::PageCacheState
+
+use super::Slot;
+use crate::{clock::ClockCache, lru::LRUCache};
+use alloc::collections::BTreeMap;
+use keos::{
+    addressing::Pa,
+    fs::{FileBlockNumber, InodeNumber},
+};
+
+/// The key of a [`Slot`] in the page cache.
+pub type SlotKey = (InodeNumber, FileBlockNumber);
+
+/// The cache replacement policy of the page cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReplacementPolicy {
+    /// Evicts the least recently used slot.
+    #[default]
+    Lru,
+    /// Evicts the first slot not referenced since the last sweep of the clock
+    /// hand.
+    Clock,
+}
+
+/// The number of slots in a shard of the page cache.
+///
+/// The capacity is per shard, so the whole page cache holds as many slots as
+/// this times the number of its shards (see [`shard`] for the trade-off).
+///
+/// [`shard`]: super::shard
+pub const SHARD_CAPACITY: usize = 64;
+
+/// The store of the cached slots of a shard, which bounds the capacity to
+/// [`SHARD_CAPACITY`] slots (256 KiB).
+///
+/// It also keeps the slots by the physical address of their pages, so that
+/// the slot of a page mapped into a process is found without scanning the
+/// slots (see [`SlotCache::find_page`]).
+pub struct SlotCache {
+    store: SlotStore,
+    // The key of the slot backed by each page.
+    pages: BTreeMap<Pa, SlotKey>,
+}
+
+enum SlotStore {
+    Lru(LRUCache<SlotKey, Slot, SHARD_CAPACITY>),
+    Clock(ClockCache<SlotKey, Slot, SHARD_CAPACITY>),
+}
+
+impl SlotCache {
+    /// Makes a new, empty `SlotCache` with the replacement `policy`.
+    pub const fn new(policy: ReplacementPolicy) -> Self {
+        let store = match policy {
+            ReplacementPolicy::Lru => SlotStore::Lru(LRUCache::new()),
+            ReplacementPolicy::Clock => SlotStore::Clock(ClockCache::new()),
+        };
+        Self {
+            store,
+            pages: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the replacement policy of the store.
+    pub fn policy(&self) -> ReplacementPolicy {
+        match self.store {
+            SlotStore::Lru(_) => ReplacementPolicy::Lru,
+            SlotStore::Clock(_) => ReplacementPolicy::Clock,
+        }
+    }
+
+    /// Returns a mutable reference to the slot corresponding to the key, and
+    /// marks it as accessed.
+    pub fn get(&mut self, k: SlotKey) -> Option<&mut Slot> {
+        match &mut self.store {
+            SlotStore::Lru(c) => c.get(k),
+            SlotStore::Clock(c) => c.get(k),
+        }
+    }
+
+    /// Inserts the slot computed with `f` into the store if it is not present,
+    /// then returns a reference to the slot in the store.
+    pub fn get_or_insert_with<E>(
+        &mut self,
+        k: SlotKey,
+        f: impl FnOnce() -> Result<Slot, E>,
+    ) -> Result<&mut Slot, E> {
+        if !self.contains(&k) {
+            self.put(k, f()?);
+        }
+        Ok(self.get(k).unwrap())
+    }
+
+    /// Returns `true` if the store contains a slot for the key.
+    ///
+    /// This does not mark the slot as accessed.
+    pub fn contains(&self, k: &SlotKey) -> bool {
+        match &self.store {
+            SlotStore::Lru(c) => c.contains(k),
+            SlotStore::Clock(c) => c.contains(k),
+        }
+    }
+
+    /// Returns a reference to the slot corresponding to the key.
+    ///
+    /// This does not mark the slot as accessed.
+    pub fn peek(&self, k: &SlotKey) -> Option<&Slot> {
+        match &self.store {
+            SlotStore::Lru(c) => c.peek(k),
+            SlotStore::Clock(c) => c.peek(k),
+        }
+    }
+
+    /// Returns the slot whose backing page is at the physical address `pa`.
+    ///
+    /// This does not mark the slot as accessed.
+    pub fn find_page(&mut self, pa: Pa) -> Option<&mut Slot> {
+        let k = *self.pages.get(&pa)?;
+        match &mut self.store {
+            SlotStore::Lru(c) => c.peek_mut(&k),
+            SlotStore::Clock(c) => c.peek_mut(&k),
+        }
+    }
+
+    /// Inserts a slot into the store, evicting a slot chosen by the
+    /// replacement policy if the store is full.
+    pub fn put(&mut self, k: SlotKey, v: Slot) {
+        let pa = v.page.pa();
+        let displaced = match &mut self.store {
+            SlotStore::Lru(c) => c.push(k, v),
+            SlotStore::Clock(c) => c.push(k, v),
+        };
+        if let Some((_, slot)) = displaced.as_ref() {
+            self.pages.remove(&slot.page.pa());
+        }
+        self.pages.insert(pa, k);
+    }
+
+    /// Removes a key from the store, returning the slot if the key was
+    /// previously in the store.
+    pub fn remove(&mut self, k: &SlotKey) -> Option<Slot> {
+        let slot = match &mut self.store {
+            SlotStore::Lru(c) => c.remove(k),
+            SlotStore::Clock(c) => c.remove(k),
+        }?;
+        self.pages.remove(&slot.page.pa());
+        Some(slot)
+    }
+
+    /// Retains only the slots specified by the predicate.
+    pub fn retain(&mut self, mut f: impl FnMut(&SlotKey, &mut Slot) -> bool) {
+        let pages = &mut self.pages;
+        let mut f = |k: &SlotKey, v: &mut Slot| {
+            let retain = f(k, v);
+            if !retain {
+                pages.remove(&v.page.pa());
+            }
+            retain
+        };
+        match &mut self.store {
+            SlotStore::Lru(c) => c.retain(&mut f),
+            SlotStore::Clock(c) => c.retain(&mut f),
+        }
+    }
+
+    /// Iterates over the key-slot pairs in the store.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&SlotKey, &mut Slot)> {
+        let (lru, clock) = match &mut self.store {
+            SlotStore::Lru(c) => (Some(c.iter_mut()), None),
+            SlotStore::Clock(c) => (None, Some(c.iter_mut())),
+        };
+        lru.into_iter().flatten().chain(clock.into_iter().flatten())
+    }
+}
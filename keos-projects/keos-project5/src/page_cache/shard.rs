@@ -0,0 +1,274 @@
+//! Sharded locking of the page cache.
+//!
+//! A single lock over the whole page cache serializes every access to it,
+//! even when the threads touch unrelated files. [`PageCacheShards`] instead
+//! splits the cache into independent [`PageCacheState`]s, each with its own
+//! lock, capacity and replacement state. A slot lives in the shard chosen by
+//! a hash of its `(InodeNumber, FileBlockNumber)`, so that the threads
+//! accessing different blocks rarely wait for each other.
+//!
+//! The [`SHARD_SPAN`] consecutive blocks of a file that start at a multiple
+//! of [`SHARD_SPAN`] fall into the same shard. As a readahead loads at most
+//! [`READAHEAD_MAX_WINDOW`] blocks following the accessed one, it touches at
+//! most two shards.
+//!
+//! Eviction and write-back work per shard: a full shard evicts only its own
+//! slots, and the write-back of a file visits the shards one at a time
+//! instead of locking the whole cache at once.
+//!
+//! Each shard holds at most [`SHARD_CAPACITY`] slots, so the [`SHARDS`]
+//! shards of the default page cache hold as many slots (512) as the single
+//! cache did. The capacity is no longer pooled, though: a shard fills up
+//! while the others still have room if the accessed blocks hash unevenly,
+//! e.g., a few hot [`SHARD_SPAN`]-block groups of a file. Such a workload
+//! evicts earlier than with a single cache of 512 slots, which is the price
+//! of not sharing a lock among the shards. A page cache made of fewer shards
+//! with [`PageCache::with_shards`] is smaller as well.
+//!
+//! The [`ReadaheadHistory`] is split in the same way: the history of a file
+//! is kept next to the shard of its first block, so that the reads of the
+//! files in different shards do not wait for each other on the history
+//! either.
+//!
+//! [`PageCache::with_shards`]: super::PageCache::with_shards
+//! [`SHARD_CAPACITY`]: super::replacement::SHARD_CAPACITY
+
+use super::{
+    PageCacheState,
+    readahead::{READAHEAD_MAX_WINDOW, ReadaheadHistory},
+    replacement::{ReplacementPolicy, SlotKey},
+};
+use alloc::{boxed::Box, collections::BTreeSet, vec};
+use keos::{
+    KernelError,
+    addressing::Pa,
+    fs::{FileBlockNumber, InodeNumber, RegularFile},
+    sync::{SpinLock, atomic::AtomicUsize},
+};
+use keos_project4::sync::mutex::{Mutex, MutexGuard};
+
+/// The default number of shards of the page cache.
+pub const SHARDS: usize = 8;
+
+/// The number of consecutive blocks of a file kept in the same shard.
+pub const SHARD_SPAN: usize = READAHEAD_MAX_WINDOW;
+
+/// The shards of the page cache.
+///
+/// It also keeps the [`ReadaheadHistory`] of the cached files, one per
+/// shard. The history of a file lives with the shard of its first block
+/// (see [`PageCacheShards::history_of`]).
+pub struct PageCacheShards {
+    shards: Box<[Mutex<PageCacheState>]>,
+    histories: Box<[SpinLock<ReadaheadHistory>]>,
+    contended: AtomicUsize,
+}
+
+impl PageCacheShards {
+    /// Makes `count` empty shards, which replace the slots with `policy`.
+    ///
+    /// # Panics
+    /// Panics if `count` is zero.
+    pub fn new(count: usize, policy: ReplacementPolicy) -> Self {
+        assert!(count > 0, "The page cache must have at least one shard.");
+        Self {
+            shards: (0..count)
+                .map(|_| Mutex::new(PageCacheState::new(policy)))
+                .collect(),
+            histories: (0..count)
+                .map(|_| SpinLock::new(ReadaheadHistory::new()))
+                .collect(),
+            contended: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of the shards.
+    pub fn count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the index of the shard holding the slot of `id`.
+    pub fn shard_of(&self, (ino, fba): SlotKey) -> usize {
+        let group = (fba.0 / SHARD_SPAN) as u64;
+        let hash = (ino.into_u32() as u64)
+            .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            .rotate_left(31)
+            ^ group.wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+        (hash.wrapping_mul(0x1656_67b1_9e37_79f9) >> 32) as usize % self.shards.len()
+    }
+
+    /// Locks the shard holding the slot of `id`.
+    pub fn lock(&self, id: SlotKey) -> MutexGuard<'_, PageCacheState> {
+        self.lock_shard(self.shard_of(id))
+    }
+
+    /// Locks the `index`-th shard.
+    ///
+    /// If the shard is held by another thread, the contention is counted
+    /// (see [`PageCacheShards::contended`]) before waiting for it.
+    pub fn lock_shard(&self, index: usize) -> MutexGuard<'_, PageCacheState> {
+        let shard = &self.shards[index];
+        match shard.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => {
+                self.contended.fetch_add(1);
+                shard.lock()
+            }
+        }
+    }
+
+    /// Returns the number of times a thread had to wait for a shard held by
+    /// another thread.
+    pub fn contended(&self) -> usize {
+        self.contended.load()
+    }
+
+    /// Runs `f` on each shard in turn, holding the lock of only the shard
+    /// being visited.
+    ///
+    /// Stops at the first shard for which `f` returns `Some`, and returns the
+    /// value.
+    pub fn find_map<R>(&self, mut f: impl FnMut(&mut PageCacheState) -> Option<R>) -> Option<R> {
+        for index in 0..self.shards.len() {
+            let mut guard = self.lock_shard(index);
+            let result = f(&mut guard);
+            guard.unlock();
+            if result.is_some() {
+                return result;
+            }
+        }
+        None
+    }
+
+    /// Returns the readahead history holding the file `ino`.
+    ///
+    /// It is the history of the shard holding the first block of the file.
+    pub fn history_of(&self, ino: InodeNumber) -> &SpinLock<ReadaheadHistory> {
+        &self.histories[self.shard_of((ino, FileBlockNumber(0)))]
+    }
+
+    /// Records an access to the block `fba` of the file `ino` into the
+    /// readahead history, and returns the updated readahead window.
+    pub fn record_access(&self, ino: InodeNumber, fba: FileBlockNumber) -> usize {
+        let mut history = self.history_of(ino).lock();
+        let window = history.record(ino, fba);
+        history.unlock();
+        window
+    }
+
+    /// Returns the current readahead window (in blocks) of the file `ino`.
+    pub fn readahead_window(&self, ino: InodeNumber) -> usize {
+        let history = self.history_of(ino).lock();
+        let window = history.window(ino);
+        history.unlock();
+        window
+    }
+
+    /// Perform readahead on the blocks following `fba` of `file`.
+    ///
+    /// The readahead window of the file is split at the boundaries of the
+    /// shards. Like [`PageCache::fill`], each part is read from the file
+    /// system with [`traits::RegularFile::read_many`] without holding the lock
+    /// of its shard, and then inserted by [`PageCacheState::readahead`]. A
+    /// part already cached entirely is not read again.
+    ///
+    /// The caller must hold the lock of the file if it is opened (see
+    /// [`FileState::io`]), so that the file is not written meanwhile.
+    ///
+    /// [`PageCache::fill`]: super::PageCache::fill
+    /// [`traits::RegularFile::read_many`]: keos::fs::traits::RegularFile::read_many
+    /// [`FileState::io`]: super::FileState::io
+    pub fn readahead(&self, file: RegularFile, fba: FileBlockNumber) {
+        let ino = file.ino();
+        let mut window = self.readahead_window(ino);
+        let mut last = fba;
+        while window > 0 {
+            let next = last + 1;
+            let count = window.min(SHARD_SPAN - next.0 % SHARD_SPAN);
+            let guard = self.lock((ino, next));
+            let cached = (0..count).all(|i| guard.contains(&(ino, next + i)));
+            guard.unlock();
+            if !cached {
+                let mut blocks = vec![[0; 4096]; count];
+                let read = file.0.read_many(next, &mut blocks).unwrap_or(0);
+                let mut guard = self.lock((ino, next));
+                guard.readahead(file.clone(), last, &blocks[..read]);
+                guard.unlock();
+                // Stop at the end of the file.
+                if read < count {
+                    return;
+                }
+            }
+            last = last + count;
+            window -= count;
+        }
+    }
+
+    /// Remove all slots associated with a given file from every shard,
+    /// without flushing them (see [`PageCacheState::do_unlink`]).
+    pub fn unlink(&self, file: RegularFile) {
+        let mut history = self.history_of(file.ino()).lock();
+        history.forget(file.ino());
+        history.unlock();
+        self.find_map(|shard| {
+            shard.do_unlink(file.clone());
+            None::<()>
+        });
+    }
+
+    /// Write back all dirty slots belonging to the given file, one shard at
+    /// a time (see [`PageCacheState::do_writeback`]).
+    pub fn writeback(&self, file: RegularFile) -> Result<(), KernelError> {
+        self.find_map(|shard| shard.do_writeback(file.clone()).err())
+            .map_or(Ok(()), Err)
+    }
+
+    /// Returns the blocks of the file `ino` held by the dirty slots, which
+    /// the file system does not know yet.
+    pub fn dirty_blocks(&self, ino: InodeNumber) -> BTreeSet<FileBlockNumber> {
+        let mut blocks = BTreeSet::new();
+        self.find_map(|shard| {
+            blocks.extend(
+                shard
+                    .iter_mut()
+                    .filter(|((id_ino, _), slot)| *id_ino == ino && slot.writeback_size.is_some())
+                    .map(|((_, fba), _)| *fba),
+            );
+            None::<()>
+        });
+        blocks
+    }
+
+    /// Write back the slot backed by the page at `pa` (see
+    /// [`PageCacheState::do_msync`]).
+    pub fn msync(&self, pa: Pa, dirty: bool) -> Result<(), KernelError> {
+        self.find_map(|shard| {
+            shard
+                .find_page(pa)
+                .is_some()
+                .then(|| shard.do_msync(pa, dirty))
+        })
+        .unwrap_or(Ok(()))
+    }
+
+    /// Returns `true` if a slot is backed by the page at `pa`.
+    pub fn is_cached(&self, pa: Pa) -> bool {
+        self.find_map(|shard| shard.find_page(pa).map(|_| ()))
+            .is_some()
+    }
+
+    /// Returns the inode number of the file of the slot backed by the page at
+    /// `pa`.
+    pub fn cached_ino(&self, pa: Pa) -> Option<InodeNumber> {
+        self.find_map(|shard| shard.find_page(pa).map(|slot| slot.file.ino()))
+    }
+
+    /// Mark the slot backed by the page at `pa` dirty (see
+    /// [`PageCacheState::do_mark_dirty`]).
+    ///
+    /// Returns `false` if no slot is backed by `pa`.
+    pub fn mark_dirty(&self, pa: Pa) -> bool {
+        self.find_map(|shard| shard.do_mark_dirty(pa).then_some(()))
+            .is_some()
+    }
+}
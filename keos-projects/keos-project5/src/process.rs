@@ -1,10 +1,29 @@
 //! The process model for project5.
 //!
 //! This file defines the process model of the project5.
+//!
+//! ## Tracking writes to file-backed mappings
+//!
+//! A file-backed mapping shares its pages with the page cache. A write through
+//! the mapping modifies the cached block without the page cache knowing it, so
+//! the page cache cannot tell which slots must be written back. To track the
+//! writes, the page fault handler emulates the dirty bit:
+//!
+//! 1. A page of the page cache is mapped read-only when it is loaded by a read
+//!    access, even if the mapping is writable.
+//! 2. The first write to the page raises a write-protection fault, which is
+//!    routed to the page cache by [`Thread::do_mmap_write`]. It marks the
+//!    slot dirty and makes the page writable, so that the following writes do
+//!    not fault.
+//!
+//! A page loaded by a write access is marked dirty right away. Note that a
+//! write to a file-backed page after `fork` also goes to the page cache,
+//! instead of copying the page, as the mapping is shared with the file.
 
+use keos::{fs::FileSystem, mm::page_table::PteFlags};
 use keos_project1::file_struct::FileStruct;
 use keos_project2::mm_struct::MmStruct;
-use keos_project3::lazy_pager::LazyPager;
+use keos_project3::lazy_pager::{LazyPager, PageFaultReason};
 
 /// A thread state of project 5, which contains file and memory state.
 #[repr(transparent)]
@@ -40,4 +59,70 @@ impl Thread {
             tid,
         ))
     }
+
+    /// Handles a write-protection fault on a clean page of a file-backed
+    /// mapping.
+    ///
+    /// If `reason` is a write to a read-only page of the page cache within a
+    /// writable mapping, marks the backing slot dirty, makes the page
+    /// writable, and returns `true`. Otherwise, returns `false` and the fault
+    /// must be handled by the pager.
+    pub fn do_mmap_write(&self, reason: &PageFaultReason) -> bool {
+        if !reason.is_present || !reason.is_write_access {
+            return false;
+        }
+        self.with_mm_struct_mut(
+            |mm, va| {
+                if !mm.access_ok(va..va + 1, true) {
+                    return false;
+                }
+                let Ok(mut walked) = mm.page_table.walk_mut(va) else {
+                    return false;
+                };
+                let flags = walked.flags();
+                match walked.pa() {
+                    Some(pa) if !flags.contains(PteFlags::RW) && FileSystem::mark_dirty(pa) => {
+                        walked
+                            .set_flags(flags | PteFlags::RW)
+                            .map(|stale| stale.invalidate())
+                            .is_some()
+                    }
+                    _ => false,
+                }
+            },
+            reason.fault_addr.page_down(),
+        )
+    }
+
+    /// Tracks a page of a file-backed mapping newly loaded by the pager.
+    ///
+    /// A page of the page cache loaded by a write is marked dirty, and one
+    /// loaded by a read is write-protected until the first write to it (see
+    /// [`Thread::do_mmap_write`]).
+    pub fn track_mmap_load(&self, reason: &PageFaultReason) {
+        if reason.is_present {
+            return;
+        }
+        self.with_mm_struct_mut(
+            |mm, (va, is_write)| {
+                let Ok(mut walked) = mm.page_table.walk_mut(va) else {
+                    return;
+                };
+                let flags = walked.flags();
+                let Some(pa) = walked.pa() else {
+                    return;
+                };
+                if !flags.contains(PteFlags::RW) {
+                    // The page is not writable at all.
+                } else if is_write {
+                    FileSystem::mark_dirty(pa);
+                } else if FileSystem::is_cached(pa)
+                    && let Some(stale) = walked.set_flags(flags - PteFlags::RW)
+                {
+                    stale.invalidate();
+                }
+            },
+            (reason.fault_addr.page_down(), reason.is_write_access),
+        )
+    }
 }
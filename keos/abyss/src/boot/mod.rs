@@ -445,6 +445,10 @@ fn idt_init() {
         ExceptionType::Interrupt,
         double_fault,
     );
+    // The double fault of a kernel stack overflow must be handled on another
+    // stack.
+    idt.double_fault
+        .set_ist(crate::x86_64::tss::DOUBLE_FAULT_IST);
     idt.device_not_available.set(
         Segment::KernelCode.into_selector(),
         ExceptionType::Interrupt,
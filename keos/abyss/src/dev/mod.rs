@@ -62,8 +62,19 @@ pub trait BlockOps {
     fn read(&self, sector: Sector, buf: &mut [u8; 512]) -> bool;
     /// Write 512 bytes to disk starting from sector.
     fn write(&self, sector: Sector, buf: &[u8; 512]) -> bool;
-    #[doc(hidden)]
-    fn read_block_many(&self, _offset: usize, _buf: &mut [u8]) -> bool {
-        unimplemented!()
+    /// Whether [`BlockOps::read_block_many`] reads the sectors in a single
+    /// device request.
+    fn can_read_many(&self) -> bool {
+        false
+    }
+    /// Read `buf.len()` bytes from disk starting from the byte `offset`.
+    ///
+    /// Both `offset` and the length of `buf` must be multiples of 512. The
+    /// default implementation reads a sector at a time, for devices that
+    /// cannot batch the read.
+    fn read_block_many(&self, offset: usize, buf: &mut [u8]) -> bool {
+        buf.chunks_exact_mut(512)
+            .enumerate()
+            .all(|(i, chunk)| self.read(Sector(offset / 512 + i), chunk.try_into().unwrap()))
     }
 }
@@ -231,6 +231,10 @@ impl BlockOps for VirtIoBlock {
             .is_ok()
     }
 
+    fn can_read_many(&self) -> bool {
+        true
+    }
+
     fn read_block_many(&self, offset: usize, buf: &mut [u8]) -> bool {
         self.read_bios(&mut Some((offset, buf)).into_iter()).is_ok()
     }
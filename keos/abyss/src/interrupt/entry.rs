@@ -11,6 +11,7 @@ global_asm!(include_str!("entry.s"));
 
 unsafe extern "Rust" {
     fn kill_current_thread() -> !;
+    fn check_stack_overflow(fault_addr: usize);
 }
 
 // Load interrupt descriptor table.
@@ -31,6 +32,11 @@ extern "C" fn handle_double_fault(
     frame: &mut Registers,
     _: crate::x86_64::interrupt::MustbeZero,
 ) -> ! {
+    // A page fault on the guard page of a kernel stack ends up here, as the
+    // exception frame can not be pushed on the exhausted stack.
+    unsafe {
+        check_stack_overflow(crate::x86_64::Cr2::current().into_usize());
+    }
     panic!("Double Fault!\n{:#?}", frame);
 }
 
@@ -263,7 +263,7 @@
 
 mk_intr_with_ec page_fault handle_page_fault
 mk_intr_no_ec nmi handle_nmi
-mk_intr_no_ec double_fault handle_double_fault
+mk_intr_with_ec double_fault handle_double_fault
 mk_intr_with_ec general_protection_fault handle_general_protection_fault
 mk_intr_no_ec device_not_available handle_device_not_available
 mk_intr_no_ec invalid_opcode handle_invalid_opcode
@@ -6,6 +6,10 @@ use core::{
     sync::atomic::{AtomicBool, Ordering},
 };
 
+/// The maximum number of `pause` instructions between the polls of a contended
+/// spinlock.
+const MAX_BACKOFF: usize = 1 << 8;
+
 /// The lock could not be acquired at this time because the operation would
 /// otherwise block.
 pub struct WouldBlock;
@@ -119,15 +123,25 @@ impl<T: ?Sized> SpinLock<T> {
     /// ```
     #[track_caller]
     pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        let mut backoff = 1;
         let guard = loop {
             let guard = crate::interrupt::InterruptGuard::new();
 
-            core::hint::spin_loop();
             if !self.locked.fetch_or(true, Ordering::SeqCst) {
                 break guard;
             }
 
             drop(guard);
+            // Test-and-test-and-set: wait until the lock is observed free
+            // before retrying the atomic swap, so that the waiters spin on
+            // their own copy of the cache line. The wait is backed off
+            // exponentially to reduce the traffic on the release.
+            while self.locked.load(Ordering::Relaxed) {
+                for _ in 0..backoff {
+                    core::hint::spin_loop();
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
         };
 
         SpinLockGuard {
@@ -173,6 +187,9 @@ impl<T: ?Sized> SpinLock<T> {
     /// ```
     #[track_caller]
     pub fn try_lock(&self) -> Result<SpinLockGuard<'_, T>, WouldBlock> {
+        if self.locked.load(Ordering::Relaxed) {
+            return Err(WouldBlock);
+        }
         let guard = crate::interrupt::InterruptGuard::new();
         let acquired = !self.locked.fetch_or(true, Ordering::SeqCst);
         if acquired {
@@ -612,6 +612,16 @@ impl<F> InterruptGateDescriptor<F> {
             _ty: core::marker::PhantomData,
         }
     }
+
+    /// Switch to the stack of the `ist`-th interrupt stack table entry of the
+    /// TSS on entering this gate.
+    ///
+    /// `ist` must be between 1 and 7.
+    #[inline(always)]
+    pub fn set_ist(&mut self, ist: u16) {
+        debug_assert!((1..=7).contains(&ist));
+        self.options = (self.options & !0x7) | ist;
+    }
 }
 
 macro_rules! define_interrupt_handler {
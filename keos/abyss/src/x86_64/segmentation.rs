@@ -342,6 +342,7 @@ impl SegmentTable {
         unsafe {
             let cpuid = crate::x86_64::intrinsics::cpuid();
             let tss = &mut TSS[cpuid].0;
+            tss.ist1 = super::tss::double_fault_stack_top(cpuid);
 
             (tss as *mut TaskStateSegment)
                 .as_mut()
@@ -3,6 +3,26 @@
 use super::PrivilegeLevel;
 use super::segmentation::{SegmentAccess64, SegmentDescriptor64};
 
+/// The interrupt stack table entry for the double fault.
+pub const DOUBLE_FAULT_IST: u16 = 1;
+
+/// The size of the stack for the double fault.
+pub const DOUBLE_FAULT_STACK_SIZE: usize = 0x10000;
+
+#[repr(C, align(16))]
+struct InterruptStack([u8; DOUBLE_FAULT_STACK_SIZE]);
+
+// The per-cpu stacks for the double fault.
+static mut DOUBLE_FAULT_STACKS: [InterruptStack; crate::MAX_CPU] =
+    [const { InterruptStack([0; DOUBLE_FAULT_STACK_SIZE]) }; crate::MAX_CPU];
+
+/// Returns the top of the double fault stack of the cpu `cpuid`.
+pub fn double_fault_stack_top(cpuid: usize) -> u64 {
+    unsafe {
+        core::ptr::addr_of!(DOUBLE_FAULT_STACKS[cpuid]) as u64 + DOUBLE_FAULT_STACK_SIZE as u64
+    }
+}
+
 /// 64bit task state segment.
 ///
 /// See Intel (R) 64 and IA-32 Architectures Software Developer’s Manual, Volume
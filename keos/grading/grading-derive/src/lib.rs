@@ -1,6 +1,6 @@
 use proc_macro::TokenStream;
 use quote::quote_spanned;
-use syn::{ItemFn, LitByteStr, LitInt, parse_macro_input, spanned::Spanned};
+use syn::{ItemFn, LitByteStr, LitInt, Path, parse_macro_input, spanned::Spanned};
 
 #[proc_macro_attribute]
 pub fn stdin(attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -85,3 +85,21 @@ pub fn validate_alloc(_attr: TokenStream, item: TokenStream) -> TokenStream {
         #input_fn
     })
 }
+
+#[proc_macro_attribute]
+pub fn validate_refs(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let tracker = parse_macro_input!(attr as Path);
+    let mut input_fn = parse_macro_input!(item as ItemFn);
+    let block = *input_fn.block;
+    *input_fn.block = syn::parse_quote! {
+        {
+            #tracker::track_refs();
+            let _return_val = (move || { #block })();
+            #tracker::validate_refs();
+            _return_val
+        }
+    };
+    TokenStream::from(quote_spanned! { input_fn.span() =>
+        #input_fn
+    })
+}
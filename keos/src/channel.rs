@@ -10,6 +10,13 @@
 //! sender and receiver are clone-able (multi-producer) such that many threads
 //! can send simultaneously to multiple receiver (multi-consumer).
 //!
+//! Each value sent is received exactly once, by one of the receivers. The
+//! threads blocked on the channel sleep in a FIFO queue, and a send (or a
+//! receive) wakes up the one that has waited the longest. A wake-up is never
+//! lost: if the woken thread is already gone, e.g., killed, the next one is
+//! woken up instead, and a receiver taking a value passes the wake-up on
+//! while values are left in the buffer.
+//!
 //!
 //! [`send`]: Sender::send
 //!
@@ -24,15 +31,23 @@
 //! continue to make progress, so `Err` will be returned. Many applications
 //! will continue to `unwrap` the results returned from this module,
 //! instigating a propagation of failure among threads if one unexpectedly dies.
+//!
+//! A channel can also be closed explicitly with [`Sender::close`] or
+//! [`Receiver::close`], while the other handles are still alive. A closed
+//! channel behaves as if both halves have hung up: the values already in the
+//! buffer can still be received, after which the receivers observe the end of
+//! the stream, and any further send fails. The threads blocked on the channel
+//! are woken up to observe the closure.
 
 use crate::{
+    poll::Poller,
     spinlock::SpinLock,
     thread::{Current, ParkHandle},
 };
-use alloc::{boxed::Box, vec::Vec};
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc, vec::Vec};
 use core::{
     fmt,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
 };
 use crossbeam_queue::ArrayQueue;
 
@@ -40,19 +55,48 @@ pub(crate) struct ChannelInner<T> {
     pub q: ArrayQueue<T>,
     pub tx_cnt: AtomicUsize,
     pub rx_cnt: AtomicUsize,
-    tx_waiter: SpinLock<Vec<ParkHandle>>,
-    rx_waiter: SpinLock<Vec<ParkHandle>>,
+    // The number of the senders and the receivers. The last handle to drop
+    // deallocates the channel.
+    handles: AtomicUsize,
+    closed: AtomicBool,
+    tx_waiter: SpinLock<VecDeque<ParkHandle>>,
+    rx_waiter: SpinLock<VecDeque<ParkHandle>>,
+    pollers: SpinLock<Vec<Arc<Poller>>>,
 }
 
 impl<T> ChannelInner<T> {
     #[inline]
     pub fn has_receiver(&self) -> bool {
-        self.rx_cnt.load(Ordering::Acquire) != 0
+        self.rx_cnt.load(Ordering::Acquire) != 0 && !self.is_closed()
     }
 
     #[inline]
     pub fn has_sender(&self) -> bool {
-        self.tx_cnt.load(Ordering::Acquire) != 0
+        self.tx_cnt.load(Ordering::Acquire) != 0 && !self.is_closed()
+    }
+
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    // Close the channel, waking up all the blocked senders and receivers.
+    fn close(&self) {
+        // Close under the locks of both waiters, so that a thread either sees
+        // the closure before parking or is woken up here.
+        let mut rx_guard = self.rx_waiter.lock();
+        let mut tx_guard = self.tx_waiter.lock();
+        let first = !self.closed.swap(true, Ordering::AcqRel);
+        let mut waiters = core::mem::take(&mut *rx_guard);
+        waiters.append(&mut tx_guard);
+        tx_guard.unlock();
+        rx_guard.unlock();
+        for th in waiters {
+            th.unpark();
+        }
+        if first {
+            self.notify_pollers();
+        }
     }
 
     #[inline]
@@ -60,35 +104,82 @@ impl<T> ChannelInner<T> {
         self.q.capacity()
     }
 
-    pub fn push(
-        &self,
-        value: T,
-        do_unpark: impl Fn(ParkHandle) -> Result<(), ()>,
-    ) -> Result<(), T> {
-        match self.q.push(value) {
-            Ok(_) => {
-                let mut guard = self.rx_waiter.lock();
-                if let Some(th) = guard.pop() {
-                    do_unpark(th).expect("Failed to unpark channel tx waiter.")
-                }
-                guard.unlock();
-                Ok(())
-            }
-            Err(e) => Err(e),
+    // Notify the registered pollers about a change of the channel state.
+    fn notify_pollers(&self) {
+        let guard = self.pollers.lock();
+        for poller in guard.iter() {
+            poller.notify();
         }
+        guard.unlock();
     }
-    pub fn pop(&self, do_unpark: impl Fn(ParkHandle) -> Result<(), ()>) -> Option<T> {
-        match self.q.pop() {
-            Some(v) => {
-                let mut guard = self.tx_waiter.lock();
 
-                if let Some(th) = guard.pop() {
-                    do_unpark(th).expect("Failed to unpark channel rx waiter.")
-                }
-                guard.unlock();
-                Some(v)
+    fn register_poller(&self, poller: &Arc<Poller>) {
+        let mut guard = self.pollers.lock();
+        guard.push(poller.clone());
+        guard.unlock();
+    }
+
+    fn deregister_poller(&self, poller: &Arc<Poller>) {
+        let mut guard = self.pollers.lock();
+        guard.retain(|p| !Arc::ptr_eq(p, poller));
+        guard.unlock();
+    }
+
+    // Wake up the waiter that has waited the longest. A stale handle of a
+    // waiter that is already woken up, e.g., by a kill signal, is skipped so
+    // that the wake-up is not lost.
+    fn wake_one(waiters: &SpinLock<VecDeque<ParkHandle>>) {
+        let mut guard = waiters.lock();
+        while let Some(th) = guard.pop_front() {
+            if th.try_unpark() {
+                break;
             }
-            None => None,
+        }
+        guard.unlock();
+    }
+
+    pub fn push(&self, value: T) -> Result<(), T> {
+        self.q.push(value)?;
+        Self::wake_one(&self.rx_waiter);
+        self.notify_pollers();
+        Ok(())
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let value = self.q.pop()?;
+        Self::wake_one(&self.tx_waiter);
+        // Several values may be pushed while the woken receivers are yet to
+        // run. Pass the wake-up on, so that no value is left in the buffer
+        // while receivers are sleeping.
+        if !self.q.is_empty() {
+            Self::wake_one(&self.rx_waiter);
+        }
+        self.notify_pollers();
+        Some(value)
+    }
+
+    // Allocate a channel with `halves` senders and receivers each, and
+    // `handles` handles in total.
+    fn allocate(bound: usize, halves: usize, handles: usize) -> *mut Self {
+        Box::into_raw(Box::new(ChannelInner {
+            q: ArrayQueue::new(bound),
+            tx_cnt: AtomicUsize::new(halves),
+            rx_cnt: AtomicUsize::new(halves),
+            handles: AtomicUsize::new(handles),
+            closed: AtomicBool::new(false),
+            tx_waiter: SpinLock::new(VecDeque::new()),
+            rx_waiter: SpinLock::new(VecDeque::new()),
+            pollers: SpinLock::new(Vec::new()),
+        }))
+    }
+
+    // Drop a handle of the channel, deallocating it if this is the last one.
+    //
+    // # Safety
+    // `this` must not be used after the call.
+    unsafe fn release(this: *mut Self) {
+        if unsafe { &*this }.handles.fetch_sub(1, Ordering::AcqRel) == 1 {
+            unsafe { drop(Box::from_raw(this)) }
         }
     }
 }
@@ -103,6 +194,7 @@ impl<T> ChannelInner<T> {
 /// [`recv`]: Receiver::recv
 pub struct Receiver<T: core::marker::Send + 'static> {
     inner: *mut ChannelInner<T>,
+    nonblocking: bool,
 }
 
 // The receiver port can be sent from place to place, so long as it
@@ -160,6 +252,7 @@ pub struct IntoIter<T: core::marker::Send + 'static> {
 /// [`send`]: Sender::send
 pub struct Sender<T: core::marker::Send + 'static> {
     inner: *mut ChannelInner<T>,
+    nonblocking: bool,
 }
 
 // The send port can be sent from place to place, so long as it
@@ -246,14 +339,79 @@ pub enum TryRecvError {
 /// [`send`]: Sender::send
 /// [`recv`]: Receiver::recv
 pub fn channel<T: core::marker::Send + 'static>(bound: usize) -> (Sender<T>, Receiver<T>) {
-    let chan = Box::into_raw(Box::new(ChannelInner {
-        q: ArrayQueue::new(bound),
-        tx_cnt: AtomicUsize::new(1),
-        rx_cnt: AtomicUsize::new(1),
-        tx_waiter: SpinLock::new(Vec::new()),
-        rx_waiter: SpinLock::new(Vec::new()),
-    }));
-    (Sender { inner: chan }, Receiver { inner: chan })
+    let chan = ChannelInner::allocate(bound, 1, 2);
+    (
+        Sender {
+            inner: chan,
+            nonblocking: false,
+        },
+        Receiver {
+            inner: chan,
+            nonblocking: false,
+        },
+    )
+}
+
+/// A handle that keeps a channel alive without being either half of it.
+///
+/// The halves of the channel hang up as if this handle did not exist, but new
+/// halves can be made from it at any time. The named pipes use it to connect
+/// the ends opened later to the same channel.
+pub(crate) struct ChannelRef<T: core::marker::Send + 'static> {
+    inner: *mut ChannelInner<T>,
+}
+
+unsafe impl<T: Send> Send for ChannelRef<T> {}
+unsafe impl<T: Send> Sync for ChannelRef<T> {}
+
+impl<T: core::marker::Send + 'static> ChannelRef<T> {
+    /// Creates a channel with no half, buffering at most `bound` values.
+    pub(crate) fn new(bound: usize) -> Self {
+        Self {
+            inner: ChannelInner::allocate(bound, 0, 1),
+        }
+    }
+
+    #[inline]
+    fn inner<'a>(&self) -> &'a ChannelInner<T> {
+        unsafe { &*self.inner }
+    }
+
+    /// Makes a new sending half of the channel.
+    pub(crate) fn sender(&self) -> Sender<T> {
+        self.inner().handles.fetch_add(1, Ordering::Relaxed);
+        self.inner().tx_cnt.fetch_add(1, Ordering::AcqRel);
+        Sender {
+            inner: self.inner,
+            nonblocking: false,
+        }
+    }
+
+    /// Makes a new receiving half of the channel.
+    pub(crate) fn receiver(&self) -> Receiver<T> {
+        self.inner().handles.fetch_add(1, Ordering::Relaxed);
+        self.inner().rx_cnt.fetch_add(1, Ordering::AcqRel);
+        Receiver {
+            inner: self.inner,
+            nonblocking: false,
+        }
+    }
+
+    /// The number of the live sending halves.
+    pub(crate) fn senders(&self) -> usize {
+        self.inner().tx_cnt.load(Ordering::Acquire)
+    }
+
+    /// The number of the live receiving halves.
+    pub(crate) fn receivers(&self) -> usize {
+        self.inner().rx_cnt.load(Ordering::Acquire)
+    }
+}
+
+impl<T: core::marker::Send + 'static> Drop for ChannelRef<T> {
+    fn drop(&mut self) {
+        unsafe { ChannelInner::release(self.inner) }
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -290,8 +448,8 @@ impl<T: core::marker::Send + 'static> Sender<T> {
     /// the data if this function returns success.
     ///
     /// This function will never panic, but it may return `Err` if the
-    /// [`Receiver`] has disconnected and is no longer able to receive
-    /// information.
+    /// [`Receiver`] has disconnected or the channel is closed, and is no
+    /// longer able to receive information.
     pub fn send(&self, t: T) -> Result<(), SendError<T>> {
         let inner = self.inner();
         let mut t_ = t;
@@ -299,19 +457,18 @@ impl<T: core::marker::Send + 'static> Sender<T> {
             if !inner.has_receiver() {
                 break Err(SendError(t_));
             } else {
-                match inner.push(t_, |th| {
-                    th.unpark();
-                    Ok(())
-                }) {
+                match inner.push(t_) {
                     Err(e) => {
                         t_ = e;
                         if inner.q.is_full() {
                             let mut guard = inner.tx_waiter.lock();
-                            if inner.q.is_full() {
+                            if inner.q.is_full() && inner.has_receiver() {
                                 Current::park_with(move |th| {
-                                    guard.push(th);
-                                    drop(guard)
+                                    guard.push_back(th);
+                                    guard.unlock();
                                 });
+                            } else {
+                                guard.unlock();
                             }
                         }
                     }
@@ -339,10 +496,7 @@ impl<T: core::marker::Send + 'static> Sender<T> {
         if !inner.has_receiver() {
             Err(TrySendError::Disconnected(t))
         } else {
-            match inner.push(t, |th| {
-                th.unpark();
-                Ok(())
-            }) {
+            match inner.push(t) {
                 Err(t) => Err(TrySendError::Full(t)),
                 _ => Ok(()),
             }
@@ -353,25 +507,95 @@ impl<T: core::marker::Send + 'static> Sender<T> {
     pub fn capacity(&self) -> usize {
         self.inner().capacity()
     }
+
+    /// Closes the channel.
+    ///
+    /// The receivers receive the values already sent, and then observe the end
+    /// of the stream as if all senders have hung up. Further sends on any
+    /// sender fail. Closing a closed channel does nothing.
+    pub fn close(&self) {
+        self.inner().close()
+    }
+
+    /// Returns `true` if the channel is closed by [`Sender::close`] or
+    /// [`Receiver::close`].
+    pub fn is_closed(&self) -> bool {
+        self.inner().is_closed()
+    }
+
+    /// Returns `true` if this sender is in the non-blocking mode.
+    ///
+    /// The mode is a hint for the owner of the sender, which is expected to
+    /// use [`try_send`] instead of [`send`] in the non-blocking mode. The mode
+    /// is inherited by the clones of this sender.
+    ///
+    /// [`send`]: Self::send
+    /// [`try_send`]: Self::try_send
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblocking
+    }
+
+    /// Sets the non-blocking mode of this sender.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+
+    /// Returns `true` if [`send`] would not block, i.e., the buffer has a
+    /// room or all receivers have hung up.
+    ///
+    /// [`send`]: Self::send
+    pub fn is_ready(&self) -> bool {
+        let inner = self.inner();
+        !inner.q.is_full() || !inner.has_receiver()
+    }
+
+    /// Registers a [`Poller`] that is notified whenever the state of this
+    /// channel changes.
+    pub fn register_poller(&self, poller: &Arc<Poller>) {
+        self.inner().register_poller(poller)
+    }
+
+    /// Deregisters a [`Poller`] registered with [`register_poller`].
+    ///
+    /// [`register_poller`]: Self::register_poller
+    pub fn deregister_poller(&self, poller: &Arc<Poller>) {
+        self.inner().deregister_poller(poller)
+    }
 }
 
 impl<T: core::marker::Send + 'static> Clone for Sender<T> {
     fn clone(&self) -> Sender<T> {
+        self.inner().handles.fetch_add(1, Ordering::Relaxed);
         if self.inner().tx_cnt.fetch_add(1, Ordering::Relaxed) > isize::MAX as usize {
             panic!("sender count overflowed.");
         }
-        Sender { inner: self.inner }
+        Sender {
+            inner: self.inner,
+            nonblocking: self.nonblocking,
+        }
     }
 }
 
 impl<T: core::marker::Send + 'static> Drop for Sender<T> {
     fn drop(&mut self) {
         let inner = self.inner();
-        if inner.tx_cnt.fetch_sub(1, Ordering::AcqRel) == 1
-            && inner.rx_cnt.load(Ordering::Relaxed) == 0
-        {
-            unsafe { drop(Box::from_raw(self.inner)) }
+        // Hang up under the lock of the receiver waiters, so that a receiver
+        // either sees the hang-up before parking or is woken up here.
+        let mut guard = inner.rx_waiter.lock();
+        let last = inner.tx_cnt.fetch_sub(1, Ordering::AcqRel) == 1;
+        let waiters = if last {
+            core::mem::take(&mut *guard)
+        } else {
+            VecDeque::new()
+        };
+        guard.unlock();
+        for th in waiters {
+            th.unpark();
         }
+        if last {
+            inner.notify_pollers();
+        }
+        unsafe { ChannelInner::release(self.inner) }
     }
 }
 
@@ -411,40 +635,31 @@ impl<T: core::marker::Send + 'static> Receiver<T> {
     /// sent to the corresponding [`Sender`] (or [`Sender`]), then this
     /// receiver will wake up and return that message.
     ///
-    /// If the corresponding [`Sender`] has disconnected, or it disconnects
-    /// while this call is blocking, this call will wake up and return
-    /// `Err` to indicate that no more messages can ever be received on
-    /// this channel. However, since channels are buffered, messages sent
+    /// If the corresponding [`Sender`] has disconnected or the channel is
+    /// closed, or it happens while this call is blocking, this call will wake
+    /// up and return `Err` to indicate that no more messages can ever be
+    /// received on this channel. However, since channels are buffered, messages sent
     /// before the disconnect will still be properly received.
     pub fn recv(&self) -> Result<T, RecvError> {
         let inner = self.inner();
         loop {
-            match inner.pop(|th| {
-                th.unpark();
-                Ok(())
-            }) {
+            match inner.pop() {
                 Some(n) => break Ok(n),
                 None if !inner.has_sender() => {
-                    break inner
-                        .pop(|th| {
-                            th.unpark();
-                            Ok(())
-                        })
-                        .ok_or(RecvError);
+                    break inner.pop().ok_or(RecvError);
                 }
                 None => {
                     let mut guard = inner.rx_waiter.lock();
-                    match inner.pop(|th| {
-                        th.unpark();
-                        Ok(())
-                    }) {
+                    match inner.pop() {
                         Some(n) => {
                             guard.unlock();
                             break Ok(n);
                         }
-                        _ => {
+                        // The last sender hung up after the check above.
+                        None if !inner.has_sender() => guard.unlock(),
+                        None => {
                             Current::park_with(|handle| {
-                                guard.push(handle);
+                                guard.push_back(handle);
                                 guard.unlock();
                             });
                         }
@@ -469,17 +684,9 @@ impl<T: core::marker::Send + 'static> Receiver<T> {
     /// [`recv`]: Self::recv
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
         let inner = self.inner();
-        match inner.pop(|th| {
-            th.unpark();
-            Ok(())
-        }) {
+        match inner.pop() {
             Some(n) => Ok(n),
-            None if !inner.has_sender() => inner
-                .pop(|th| {
-                    th.unpark();
-                    Ok(())
-                })
-                .ok_or(TryRecvError::Disconnected),
+            None if !inner.has_sender() => inner.pop().ok_or(TryRecvError::Disconnected),
             None => Err(TryRecvError::Empty),
         }
     }
@@ -502,6 +709,62 @@ impl<T: core::marker::Send + 'static> Receiver<T> {
     pub fn capacity(&self) -> usize {
         self.inner().capacity()
     }
+
+    /// Closes the channel.
+    ///
+    /// The values already sent can still be received, and then [`recv`]
+    /// returns [`RecvError`]. Further sends on any sender fail. Closing a
+    /// closed channel does nothing.
+    ///
+    /// [`recv`]: Self::recv
+    pub fn close(&self) {
+        self.inner().close()
+    }
+
+    /// Returns `true` if the channel is closed by [`Sender::close`] or
+    /// [`Receiver::close`].
+    pub fn is_closed(&self) -> bool {
+        self.inner().is_closed()
+    }
+
+    /// Returns `true` if this receiver is in the non-blocking mode.
+    ///
+    /// The mode is a hint for the owner of the receiver, which is expected to
+    /// use [`try_recv`] instead of [`recv`] in the non-blocking mode. The mode
+    /// is inherited by the clones of this receiver.
+    ///
+    /// [`recv`]: Self::recv
+    /// [`try_recv`]: Self::try_recv
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblocking
+    }
+
+    /// Sets the non-blocking mode of this receiver.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+
+    /// Returns `true` if [`recv`] would not block, i.e., the buffer has a
+    /// value or all senders have hung up.
+    ///
+    /// [`recv`]: Self::recv
+    pub fn is_ready(&self) -> bool {
+        let inner = self.inner();
+        !inner.q.is_empty() || !inner.has_sender()
+    }
+
+    /// Registers a [`Poller`] that is notified whenever the state of this
+    /// channel changes.
+    pub fn register_poller(&self, poller: &Arc<Poller>) {
+        self.inner().register_poller(poller)
+    }
+
+    /// Deregisters a [`Poller`] registered with [`register_poller`].
+    ///
+    /// [`register_poller`]: Self::register_poller
+    pub fn deregister_poller(&self, poller: &Arc<Poller>) {
+        self.inner().deregister_poller(poller)
+    }
 }
 
 impl<T: core::marker::Send + 'static> Iterator for Iter<'_, T> {
@@ -547,21 +810,37 @@ impl<T: core::marker::Send + 'static> IntoIterator for Receiver<T> {
 
 impl<T: core::marker::Send + 'static> Clone for Receiver<T> {
     fn clone(&self) -> Receiver<T> {
+        self.inner().handles.fetch_add(1, Ordering::Relaxed);
         if self.inner().rx_cnt.fetch_add(1, Ordering::Relaxed) > isize::MAX as usize {
             panic!("receiver count overflowed.");
         }
-        Receiver { inner: self.inner }
+        Receiver {
+            inner: self.inner,
+            nonblocking: self.nonblocking,
+        }
     }
 }
 
 impl<T: core::marker::Send + 'static> Drop for Receiver<T> {
     fn drop(&mut self) {
         let inner = self.inner();
-        if inner.rx_cnt.fetch_sub(1, Ordering::AcqRel) == 1
-            && inner.tx_cnt.load(Ordering::Relaxed) == 0
-        {
-            unsafe { drop(Box::from_raw(self.inner)) }
+        // Hang up under the lock of the sender waiters, so that a sender
+        // either sees the hang-up before parking or is woken up here.
+        let mut guard = inner.tx_waiter.lock();
+        let last = inner.rx_cnt.fetch_sub(1, Ordering::AcqRel) == 1;
+        let waiters = if last {
+            core::mem::take(&mut *guard)
+        } else {
+            VecDeque::new()
+        };
+        guard.unlock();
+        for th in waiters {
+            th.unpark();
+        }
+        if last {
+            inner.notify_pollers();
         }
+        unsafe { ChannelInner::release(self.inner) }
     }
 }
 
@@ -0,0 +1,82 @@
+//! Kernel command line.
+//!
+//! The bootloader passes the kernel a command line, which is a list of words
+//! separated by spaces. A word is either an option of the form `key=value`
+//! (e.g., `loglevel=warn`) or a bare flag (e.g., `strace`). The command line
+//! is parsed once at boot, and the options are looked up with [`get`].
+//!
+//! The kernel understands the following options:
+//!
+//! - `loglevel=<debug|info|warn|error>`: sets the log level threshold of the
+//!   kernel (see [`crate::log_level`]). It takes precedence over
+//!   [`SystemConfigurationBuilder::set_log_level`].
+//! - `sched_seed=<n>`: the seed of the [`RandomScheduler`] created by
+//!   [`RandomScheduler::from_cmdline`].
+//! - `strace`: traces the system calls of every thread (see
+//!   [`crate::syscall::trace`]).
+//!
+//! The other bare words that do not start with `-` select the tests to run by
+//! the [`TestDriver`] (see [`words`]).
+//!
+//! [`SystemConfigurationBuilder::set_log_level`]: crate::SystemConfigurationBuilder::set_log_level
+//! [`TestDriver`]: crate::TestDriver
+//! [`RandomScheduler`]: crate::thread::scheduler::RandomScheduler
+//! [`RandomScheduler::from_cmdline`]: crate::thread::scheduler::RandomScheduler::from_cmdline
+
+use alloc::{collections::BTreeMap, string::String};
+
+struct Cmdline {
+    raw: &'static str,
+    options: BTreeMap<&'static str, &'static str>,
+}
+
+static mut CMDLINE: Option<Cmdline> = None;
+
+/// The bare flags understood by the kernel, which are never taken as the
+/// names of tests.
+const FLAGS: &[&str] = &["strace"];
+
+/// Parses the kernel command line.
+///
+/// Called once by the bootstrap processor before running the `main`.
+pub(crate) unsafe fn init(cmd: &[u8]) {
+    let raw = String::leak(
+        String::from_utf8_lossy(cmd)
+            .trim_end_matches('\0')
+            .trim()
+            .into(),
+    );
+    let options = raw
+        .split(' ')
+        .filter(|word| !word.is_empty())
+        .map(|word| word.split_once('=').unwrap_or((word, "")))
+        .collect();
+    unsafe {
+        CMDLINE = Some(Cmdline { raw, options });
+    }
+}
+
+/// Returns the whole kernel command line.
+pub fn raw() -> &'static str {
+    unsafe { CMDLINE.as_ref().map(|cmdline| cmdline.raw).unwrap_or("") }
+}
+
+/// Looks up the option `key` on the kernel command line.
+///
+/// Returns the value of `key=value`, or an empty string if `key` is given as a
+/// bare flag. If an option is given more than once, the last one wins.
+/// Returns `None` if `key` is not on the command line.
+pub fn get(key: &str) -> Option<&'static str> {
+    unsafe { CMDLINE.as_ref()?.options.get(key).copied() }
+}
+
+/// Returns an iterator over the bare words of the kernel command line.
+///
+/// Options of the form `key=value`, words starting with `-` (e.g.,
+/// `--quite`), and the flags understood by the kernel (e.g., `strace`) are
+/// skipped.
+pub fn words() -> impl Iterator<Item = &'static str> {
+    raw().split(' ').filter(|word| {
+        !word.is_empty() && !word.starts_with('-') && !word.contains('=') && !FLAGS.contains(word)
+    })
+}
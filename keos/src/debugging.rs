@@ -38,7 +38,10 @@
 //!    * User–kernel interactions go through the syscall interface, so if it
 //!      executed at all, it likely issued a syscall.
 //!         * Add a log in `Process::syscall` to print the syscall
-//!           **number/args** and the **return_val**.
+//!           **number/args** and the **return_val**, or pass `strace` on the
+//!           command line (e.g., `cargo run -- userprog::arg_parse strace`)
+//!           to print every syscall with its decoded arguments and return
+//!           value (see [`crate::syscall::trace`]).
 //!         * If you see this log, the program executed, made a syscall, and the
 //!           kernel handled it without panicking.
 //!         * Check whether `return_val` is what you expect. If not, dig deeper:
@@ -5,7 +5,7 @@ pub mod traits {
     use alloc::{string::String, vec::Vec};
 
     use super::{File, FileBlockNumber, InodeNumber};
-    use crate::{KernelError, mm::Page, sync::atomic::AtomicBool};
+    use crate::{KernelError, addressing::Pa, mm::Page, sync::atomic::AtomicBool};
 
     /// Trait representing a filesystem.
     ///
@@ -22,6 +22,46 @@ pub mod traits {
         /// - `None`: If the root directory is inaccessible or the filesystem is
         ///   uninitialized.
         fn root(&self) -> Option<super::Directory>;
+
+        /// Synchronizes the file block cached on the physical page `pa` with
+        /// the disk.
+        ///
+        /// `pa` is a page returned by [`RegularFile::mmap`]. If `dirty` is
+        /// set, the page has been modified through a memory mapping, and
+        /// must be written back even if the file system does not know it.
+        ///
+        /// A file system without a cache has nothing to synchronize, so the
+        /// default implementation does nothing.
+        fn msync(&self, _pa: Pa, _dirty: bool) -> Result<(), KernelError> {
+            Ok(())
+        }
+
+        /// Returns `true` if the physical page `pa` caches a file block.
+        ///
+        /// A memory mapping of such a page is write-protected until it is
+        /// written, so that the write can be tracked with
+        /// [`FileSystem::mark_dirty`]. The default implementation returns
+        /// `false`, as a file system without a cache has no such page.
+        fn is_cached(&self, _pa: Pa) -> bool {
+            false
+        }
+
+        /// Marks the file block cached on the physical page `pa` dirty, as
+        /// the page is about to be written through a writable memory mapping.
+        ///
+        /// Returns `false` if `pa` does not cache a file block.
+        fn mark_dirty(&self, _pa: Pa) -> bool {
+            false
+        }
+
+        /// Returns the inode number of the file whose block is cached on the
+        /// physical page `pa`.
+        ///
+        /// The default implementation returns `None`, as a file system
+        /// without a cache has no such page.
+        fn cached_ino(&self, _pa: Pa) -> Option<InodeNumber> {
+            None
+        }
     }
 
     /// Trait representing a regular file in the filesystem.
@@ -38,6 +78,64 @@ pub mod traits {
         /// Returns the size of the file in bytes.
         fn size(&self) -> usize;
 
+        /// Returns the link count of the file.
+        ///
+        /// The default implementation returns 1, for the file systems without
+        /// hard links.
+        fn link_count(&self) -> usize {
+            1
+        }
+
+        /// Returns the number of the data blocks allocated to the file.
+        ///
+        /// The default implementation assumes that every block within the
+        /// size of the file is allocated. A file system supporting sparse
+        /// files overrides this not to count the holes.
+        fn blocks(&self) -> usize {
+            self.size().div_ceil(4096)
+        }
+
+        /// Returns the time of the last modification of the file, in timer
+        /// ticks since boot (see [`crate::time`]).
+        ///
+        /// A file system that does not record the time returns 0, as the
+        /// default implementation does.
+        fn mtime(&self) -> u64 {
+            0
+        }
+
+        /// Returns the time of the last access of the file, in timer ticks
+        /// since boot (see [`crate::time`]).
+        ///
+        /// A file system that does not record the time returns 0, as the
+        /// default implementation does.
+        fn atime(&self) -> u64 {
+            0
+        }
+
+        /// Returns the permission bits of the file, within [`S_IRWXU`].
+        ///
+        /// The default implementation grants every permission, for the file
+        /// systems that do not record them.
+        ///
+        /// [`S_IRWXU`]: crate::syscall::flags::S_IRWXU
+        fn mode(&self) -> u32 {
+            crate::syscall::flags::S_IRWXU
+        }
+
+        /// Changes the permission bits of the file.
+        ///
+        /// # Parameters
+        /// - `mode`: The new permission bits, within [`S_IRWXU`].
+        ///
+        /// The default implementation returns
+        /// [`KernelError::NotSupportedOperation`].
+        ///
+        /// [`S_IRWXU`]: crate::syscall::flags::S_IRWXU
+        fn set_mode(&self, _mode: u32) -> Result<(), KernelError> {
+            Err(KernelError::NotSupportedOperation)
+        }
+
         /// Reads data from the file into the provided buffer.
         ///
         /// # Parameters
@@ -50,6 +148,59 @@ pub mod traits {
         /// - `Err(Error)`: An error occured while the read operation.
         fn read(&self, fba: FileBlockNumber, buf: &mut [u8; 4096]) -> Result<bool, KernelError>;
 
+        /// Reads consecutive blocks of the file starting from `fba`.
+        ///
+        /// A file system may override this to fetch contiguous blocks from the
+        /// disk at once. The default implementation reads a block at a time
+        /// with [`RegularFile::read`].
+        ///
+        /// # Parameters
+        /// - `fba`: The `FileBlockNumber` of the first block to read.
+        /// - `bufs`: The buffers to store the blocks, one per block.
+        ///
+        /// # Returns
+        /// - `Ok(n)`: The number of blocks read, which is less than
+        ///   `bufs.len()` if the read reaches the end of the file.
+        /// - `Err(Error)`: An error occured while the read operation.
+        fn read_many(
+            &self,
+            fba: FileBlockNumber,
+            bufs: &mut [[u8; 4096]],
+        ) -> Result<usize, KernelError> {
+            for (i, buf) in bufs.iter_mut().enumerate() {
+                if !self.read(fba + i, buf)? {
+                    return Ok(i);
+                }
+            }
+            Ok(bufs.len())
+        }
+
+        /// Finds the data or the hole at or after `ofs`, as `SEEK_DATA` and
+        /// `SEEK_HOLE` do.
+        ///
+        /// The end of the file is considered a hole, so a hole is always
+        /// found within the file. The default implementation, for the file
+        /// systems without sparse files, considers the whole file as data.
+        ///
+        /// # Parameters
+        /// - `ofs`: The offset to start the search from.
+        /// - `hole`: Whether to find a hole (`true`) or data (`false`).
+        ///
+        /// # Returns
+        /// - `Ok(ofs)`: The offset of the found data or hole.
+        /// - [`KernelError::NoSuchDeviceOrAddress`] if `ofs` is not within the
+        ///   file, or there is no data at or after `ofs`.
+        fn seek_hole_data(&self, ofs: usize, hole: bool) -> Result<usize, KernelError> {
+            let size = self.size();
+            if ofs >= size {
+                Err(KernelError::NoSuchDeviceOrAddress)
+            } else if hole {
+                Ok(size)
+            } else {
+                Ok(ofs)
+            }
+        }
+
         /// Writes a 4096-byte page of data into the specified file block.
         ///
         /// This method writes the contents of `buf` to the file block indicated
@@ -106,6 +257,29 @@ pub mod traits {
 
         /// Write back the file to disk.
         fn writeback(&self) -> Result<(), KernelError>;
+
+        /// Allocates the blocks of the file for the byte range
+        /// `[ofs, ofs + len)`, without writing the data.
+        ///
+        /// The file is extended to `ofs + len` bytes if it is shorter, and the
+        /// new part of the file reads back as zeros.
+        ///
+        /// The default implementation does not support the preallocation.
+        ///
+        /// # Returns
+        /// - `Ok(())` if the blocks are allocated.
+        /// - `Err(KernelError)` if the operation fails (e.g., no space).
+        fn allocate(&self, _ofs: usize, _len: usize) -> Result<(), KernelError> {
+            Err(KernelError::NotSupportedOperation)
+        }
+
+        /// Sets whether the I/O on this file bypasses the page cache.
+        ///
+        /// A file system without a page cache always performs the I/O
+        /// directly, so the default implementation does nothing.
+        fn set_direct(&self, _direct: bool) -> Result<(), KernelError> {
+            Ok(())
+        }
     }
 
     /// Trait representing a directory in the filesystem.
@@ -124,6 +298,55 @@ pub mod traits {
         /// Returns the link count of the directory.
         fn link_count(&self) -> usize;
 
+        /// Returns the number of the data blocks allocated to the directory.
+        ///
+        /// The default implementation assumes that every block within the
+        /// size of the directory is allocated.
+        fn blocks(&self) -> usize {
+            self.size().div_ceil(4096)
+        }
+
+        /// Returns the time of the last modification of the directory, in
+        /// timer ticks since boot (see [`crate::time`]).
+        ///
+        /// A file system that does not record the time returns 0, as the
+        /// default implementation does.
+        fn mtime(&self) -> u64 {
+            0
+        }
+
+        /// Returns the time of the last access of the directory, in timer
+        /// ticks since boot (see [`crate::time`]).
+        ///
+        /// A file system that does not record the time returns 0, as the
+        /// default implementation does.
+        fn atime(&self) -> u64 {
+            0
+        }
+
+        /// Returns the permission bits of the directory, within [`S_IRWXU`].
+        ///
+        /// The default implementation grants every permission, for the file
+        /// systems that do not record them.
+        ///
+        /// [`S_IRWXU`]: crate::syscall::flags::S_IRWXU
+        fn mode(&self) -> u32 {
+            crate::syscall::flags::S_IRWXU
+        }
+
+        /// Changes the permission bits of the directory.
+        ///
+        /// # Parameters
+        /// - `mode`: The new permission bits, within [`S_IRWXU`].
+        ///
+        /// The default implementation returns
+        /// [`KernelError::NotSupportedOperation`].
+        ///
+        /// [`S_IRWXU`]: crate::syscall::flags::S_IRWXU
+        fn set_mode(&self, _mode: u32) -> Result<(), KernelError> {
+            Err(KernelError::NotSupportedOperation)
+        }
+
         /// Opens an entry by name.
         ///
         /// # Parameters
@@ -146,6 +369,21 @@ pub mod traits {
         /// - `Err(Error)`: An error if the add fails.
         fn create_entry(&self, entry: &str, is_dir: bool) -> Result<File, KernelError>;
 
+        /// Create a named pipe (FIFO) entry by name.
+        ///
+        /// File systems that cannot store FIFOs do not support this
+        /// operation.
+        ///
+        /// # Parameters
+        /// - `entry`: The name of the entry to add.
+        ///
+        /// # Returns
+        /// - `Ok(File)`: The created [`File::Fifo`].
+        /// - `Err(Error)`: An error if the add fails.
+        fn create_fifo_entry(&self, _entry: &str) -> Result<File, KernelError> {
+            Err(KernelError::NotSupportedOperation)
+        }
+
         /// Unlinks a directory entry by name.
         ///
         /// # Parameters
@@ -156,6 +394,25 @@ pub mod traits {
         /// - `Err(Error)`: An error if the removal fails.
         fn unlink_entry(&self, entry: &str) -> Result<(), KernelError>;
 
+        /// Moves an entry of this directory to the directory `dst` under the
+        /// name `new_entry`.
+        ///
+        /// `dst` is in the same file system as this directory. The default
+        /// implementation returns [`KernelError::NotSupportedOperation`].
+        ///
+        /// # Parameters
+        /// - `entry`: The name of the entry to move.
+        /// - `dst`: The directory to move the entry into.
+        /// - `new_entry`: The new name of the entry.
+        fn rename_entry(
+            &self,
+            _entry: &str,
+            _dst: &super::Directory,
+            _new_entry: &str,
+        ) -> Result<(), KernelError> {
+            Err(KernelError::NotSupportedOperation)
+        }
+
         /// Reads the contents of the directory.
         ///
         /// This function lists all the entries within the directory.
@@ -165,6 +422,47 @@ pub mod traits {
         /// - `Err(Error)`: An error if the read operation fails.
         fn read_dir(&self) -> Result<Vec<(InodeNumber, String)>, KernelError>;
 
+        /// Reads at most `max` entries of the directory, starting from the
+        /// `cursor`.
+        ///
+        /// The cursor is an opaque position in the directory that starts from
+        /// 0, and is advanced past the returned entries. Successive calls
+        /// with the same cursor list every entry once, and an empty list
+        /// marks the end of the directory.
+        ///
+        /// A file system should implement the cursor so that the entries
+        /// added or removed in between the calls do not make the others
+        /// repeated or skipped. The default implementation lists the whole
+        /// directory on every call, and uses the index of the entry as the
+        /// cursor.
+        ///
+        /// # Returns
+        /// - `Ok(entries)`: The entries read from the cursor.
+        /// - `Err(Error)`: An error if the read operation fails.
+        fn read_dir_from(
+            &self,
+            cursor: &mut usize,
+            max: usize,
+        ) -> Result<Vec<(InodeNumber, String)>, KernelError> {
+            let entries: Vec<_> = self
+                .read_dir()?
+                .into_iter()
+                .skip(*cursor)
+                .take(max)
+                .collect();
+            *cursor += entries.len();
+            Ok(entries)
+        }
+
+        /// Writes back the entries of the directory to disk.
+        ///
+        /// Once this returns, the entries added to or removed from the
+        /// directory survive a crash. The default implementation does nothing,
+        /// for the file systems that write the entries to disk synchronously.
+        fn writeback(&self) -> Result<(), KernelError> {
+            Ok(())
+        }
+
         /// Returns a reference of [`AtomicBool`] which contains whether
         /// directory is removed.
         ///
@@ -179,9 +477,26 @@ pub mod traits {
     }
 }
 
-use crate::{KernelError, mm::Page, sync::atomic::AtomicBool};
+use crate::{
+    KernelError,
+    addressing::Pa,
+    channel::{ChannelRef, Receiver, Sender, channel},
+    mm::Page,
+    sync::{
+        SpinLock,
+        atomic::{AtomicBool, AtomicU64},
+    },
+    thread::{Current, ParkHandle, with_current},
+};
 pub use abyss::dev::{BlockOps, Sector};
-use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, btree_map},
+    format,
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
 use core::{iter::Step, num::NonZeroU32};
 
 /// A global file system abstraction.
@@ -202,6 +517,10 @@ pub struct FileSystem {
 
 static mut FS: Option<Box<dyn traits::FileSystem>> = None;
 
+// The mount table, keyed by the canonical absolute path of the mount point.
+static MOUNTS: SpinLock<BTreeMap<String, Arc<dyn traits::FileSystem>>> =
+    SpinLock::new(BTreeMap::new());
+
 impl FileSystem {
     /// Retrieves the root directory of the filesystem.
     ///
@@ -210,17 +529,169 @@ impl FileSystem {
     pub fn root() -> Directory {
         unsafe { FS.as_ref() }
             .and_then(|fs| fs.root())
+            .map(|root| root.with_path(String::from("/")))
             .expect("Filesystem is not available.")
     }
 
+    // Runs `f` on the root file system and then on the mounted ones, until
+    // it returns `Some`.
+    fn find_map<T>(mut f: impl FnMut(&dyn traits::FileSystem) -> Option<T>) -> Option<T> {
+        if let Some(v) = unsafe { FS.as_ref() }.and_then(|fs| f(&**fs)) {
+            return Some(v);
+        }
+        let guard = MOUNTS.lock();
+        let mounted: Vec<_> = guard.values().cloned().collect();
+        guard.unlock();
+        mounted.iter().find_map(|fs| f(&**fs))
+    }
+
+    /// Synchronizes the file block mapped on the physical page `pa` with the
+    /// disk.
+    ///
+    /// See [`traits::FileSystem::msync`] for the details.
+    pub fn msync(pa: Pa, dirty: bool) -> Result<(), KernelError> {
+        Self::find_map(|fs| fs.is_cached(pa).then(|| fs.msync(pa, dirty))).unwrap_or(Ok(()))
+    }
+
+    /// Returns `true` if the physical page `pa` caches a file block.
+    ///
+    /// See [`traits::FileSystem::is_cached`] for the details.
+    pub fn is_cached(pa: Pa) -> bool {
+        Self::find_map(|fs| fs.is_cached(pa).then_some(())).is_some()
+    }
+
+    /// Marks the file block cached on the physical page `pa` dirty.
+    ///
+    /// See [`traits::FileSystem::mark_dirty`] for the details.
+    pub fn mark_dirty(pa: Pa) -> bool {
+        Self::find_map(|fs| fs.mark_dirty(pa).then_some(())).is_some()
+    }
+
+    /// Returns the inode number of the file whose block is cached on the
+    /// physical page `pa`.
+    ///
+    /// See [`traits::FileSystem::cached_ino`] for the details.
+    pub fn cached_ino(pa: Pa) -> Option<InodeNumber> {
+        Self::find_map(|fs| fs.cached_ino(pa))
+    }
+
     /// Register the global file system.
+    ///
+    /// The file system is mounted on the root directory `/`. Other file
+    /// systems can be mounted under it with [`FileSystem::mount`].
     pub fn register(fs: impl traits::FileSystem + 'static) {
         unsafe {
             FS = Some(Box::new(fs));
         }
     }
+
+    /// Mounts a file system on the directory `path`.
+    ///
+    /// After mounting, a path that goes through the mount point is resolved
+    /// in the root directory of `fs`, and the original contents of the mount
+    /// point are hidden until [`FileSystem::umount`]. The path is resolved
+    /// to the longest matching mount point, so a file system can be mounted
+    /// on a directory of another mounted file system.
+    ///
+    /// # Parameters
+    /// - `path`: The path to an existing directory, which is canonicalized
+    ///   as in [`Directory::open`].
+    /// - `fs`: The file system to mount.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the file system is mounted.
+    /// - `Err(KernelError::Busy)`: If a file system is already mounted on
+    ///   `path`, including the root file system on `/`.
+    /// - `Err(Error)`: An error if `path` cannot be opened as a directory.
+    pub fn mount(path: &str, fs: impl traits::FileSystem + 'static) -> Result<(), KernelError> {
+        let dir = Self::root()
+            .open(path)?
+            .into_directory()
+            .ok_or(KernelError::NotDirectory)?;
+        let key = match dir.path() {
+            Some(key) if key != "/" => String::from(key),
+            _ => return Err(KernelError::Busy),
+        };
+        let mut guard = MOUNTS.lock();
+        let result = match guard.entry(key) {
+            btree_map::Entry::Occupied(_) => Err(KernelError::Busy),
+            btree_map::Entry::Vacant(en) => {
+                en.insert(Arc::new(fs));
+                Ok(())
+            }
+        };
+        guard.unlock();
+        result
+    }
+
+    /// Unmounts the file system mounted on the directory `path`.
+    ///
+    /// The files of the file system must not be in use, as they are no
+    /// longer reachable once the file system is unmounted.
+    ///
+    /// # Parameters
+    /// - `path`: The path to the mount point.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the file system is unmounted.
+    /// - `Err(KernelError::Busy)`: If another file system is mounted under
+    ///   `path`.
+    /// - `Err(KernelError::InvalidArgument)`: If no file system is mounted
+    ///   on `path`.
+    pub fn umount(path: &str) -> Result<(), KernelError> {
+        let dir = Self::root()
+            .open(path)?
+            .into_directory()
+            .ok_or(KernelError::NotDirectory)?;
+        let key = dir.path().ok_or(KernelError::InvalidArgument)?;
+        let mut guard = MOUNTS.lock();
+        let (result, fs) = if guard
+            .keys()
+            .any(|k| k.strip_prefix(key).is_some_and(|r| r.starts_with('/')))
+        {
+            (Err(KernelError::Busy), None)
+        } else {
+            match guard.remove(key) {
+                Some(fs) => (Ok(()), Some(fs)),
+                None => (Err(KernelError::InvalidArgument), None),
+            }
+        };
+        guard.unlock();
+        // Drop the file system outside the lock, as it may write back to
+        // the disk.
+        drop(fs);
+        result
+    }
+
+    // Returns the root directory of the file system mounted on `path`.
+    fn mounted_root(path: &str) -> Option<Directory> {
+        let guard = MOUNTS.lock();
+        let fs = guard.get(path).cloned();
+        guard.unlock();
+        fs.and_then(|fs| fs.root())
+    }
+
+    // Returns the mount point of the file system that contains `path`, i.e.,
+    // the longest mount point that prefixes `path`.
+    fn mount_point_of(path: &str) -> String {
+        let guard = MOUNTS.lock();
+        let mount_point = guard
+            .keys()
+            .filter(|k| {
+                path.strip_prefix(k.as_str())
+                    .is_some_and(|r| r.is_empty() || r.starts_with('/'))
+            })
+            .max_by_key(|k| k.len())
+            .cloned()
+            .unwrap_or_else(|| String::from("/"));
+        guard.unlock();
+        mount_point
+    }
 }
 
+/// The maximum number of blocks that [`RegularFile::read`] reads in a batch.
+pub const READ_BATCH: usize = 16;
+
 /// A handle to a regular file.
 ///
 /// This struct provides a reference-counted handle to a file that supports
@@ -257,8 +728,41 @@ impl RegularFile {
         self.0.size()
     }
 
+    /// Link count of the file.
+    pub fn link_count(&self) -> usize {
+        self.0.link_count()
+    }
+
+    /// Number of the data blocks allocated to the file.
+    pub fn blocks(&self) -> usize {
+        self.0.blocks()
+    }
+
+    /// Time of the last modification of the file, in timer ticks since boot.
+    pub fn mtime(&self) -> u64 {
+        self.0.mtime()
+    }
+
+    /// Time of the last access of the file, in timer ticks since boot.
+    pub fn atime(&self) -> u64 {
+        self.0.atime()
+    }
+
+    /// Permission bits of the file.
+    pub fn mode(&self) -> u32 {
+        self.0.mode()
+    }
+
+    /// Changes the permission bits of the file.
+    pub fn set_mode(&self, mode: u32) -> Result<(), KernelError> {
+        self.0.set_mode(mode)
+    }
+
     /// Reads data from the file into the provided buffer.
     ///
+    /// The blocks wholly covered by `buf` are read in batches of up to
+    /// [`READ_BATCH`] blocks with [`traits::RegularFile::read_many`].
+    ///
     /// # Parameters
     /// - `fba`: The `FileBlockNumber` which to read.
     /// - `buf`: A mutable slice where the file content will be stored.
@@ -286,6 +790,29 @@ impl RegularFile {
             position += read_bytes;
         }
 
+        // Read the whole blocks in batches, so that the file system can fetch
+        // the contiguous blocks at once.
+        if max_read - read_bytes >= 0x1000 {
+            let mut batch =
+                alloc::vec![[0; 4096]; ((max_read - read_bytes) / 0x1000).min(READ_BATCH)];
+            while max_read - read_bytes >= 0x1000 {
+                let count = ((max_read - read_bytes) / 0x1000).min(batch.len());
+                let read = self
+                    .0
+                    .read_many(FileBlockNumber::from_offset(position), &mut batch[..count])?;
+                for block in &batch[..read] {
+                    buf[read_bytes..read_bytes + 0x1000].copy_from_slice(block);
+                    position += 0x1000;
+                    read_bytes += 0x1000;
+                }
+                // The file ended before the blocks covered by its size, e.g.,
+                // as it is truncated concurrently.
+                if read < count {
+                    return Ok(read_bytes);
+                }
+            }
+        }
+
         for i in (read_bytes..max_read).step_by(0x1000) {
             self.0
                 .read(FileBlockNumber::from_offset(position), &mut bounce_buffer)?;
@@ -370,6 +897,18 @@ impl RegularFile {
         Ok(write_bytes)
     }
 
+    /// Returns the offset of the next data at or after `ofs`, for
+    /// `SEEK_DATA`.
+    pub fn seek_data(&self, ofs: usize) -> Result<usize, KernelError> {
+        self.0.seek_hole_data(ofs, false)
+    }
+
+    /// Returns the offset of the next hole at or after `ofs`, for
+    /// `SEEK_HOLE`. The end of the file is considered a hole.
+    pub fn seek_hole(&self, ofs: usize) -> Result<usize, KernelError> {
+        self.0.seek_hole_data(ofs, true)
+    }
+
     /// Maps a file block into memory.
     ///
     /// This method retrieves the contents of the file at the specified file
@@ -398,6 +937,17 @@ impl RegularFile {
     pub fn writeback(&self) -> Result<(), KernelError> {
         self.0.writeback()
     }
+
+    /// Allocates the blocks of the file for the byte range `[ofs, ofs + len)`
+    /// without writing the data, extending the file with zeros if needed.
+    pub fn allocate(&self, ofs: usize, len: usize) -> Result<(), KernelError> {
+        self.0.allocate(ofs, len)
+    }
+
+    /// Sets whether the I/O on this file bypasses the page cache.
+    pub fn set_direct(&self, direct: bool) -> Result<(), KernelError> {
+        self.0.set_direct(direct)
+    }
 }
 
 /// A handle to a directory.
@@ -405,7 +955,12 @@ impl RegularFile {
 /// This struct represents a reference-counted directory that supports
 /// file entry management, including opening and removing entries.
 #[derive(Clone)]
-pub struct Directory(pub Arc<dyn traits::Directory>);
+pub struct Directory(
+    pub Arc<dyn traits::Directory>,
+    /// The canonical absolute path of the directory, if it is opened by a
+    /// path.
+    Option<Arc<str>>,
+);
 
 impl Directory {
     /// Inode number of the directory.
@@ -424,6 +979,32 @@ impl Directory {
         self.0.link_count()
     }
 
+    /// Number of the data blocks allocated to the directory.
+    pub fn blocks(&self) -> usize {
+        self.0.blocks()
+    }
+
+    /// Time of the last modification of the directory, in timer ticks since
+    /// boot.
+    pub fn mtime(&self) -> u64 {
+        self.0.mtime()
+    }
+
+    /// Time of the last access of the directory, in timer ticks since boot.
+    pub fn atime(&self) -> u64 {
+        self.0.atime()
+    }
+
+    /// Permission bits of the directory.
+    pub fn mode(&self) -> u32 {
+        self.0.mode()
+    }
+
+    /// Changes the permission bits of the directory.
+    pub fn set_mode(&self, mode: u32) -> Result<(), KernelError> {
+        self.0.set_mode(mode)
+    }
+
     /// Creates a new [`Directory`] handle from a given implementation of
     /// [`traits::Directory`].
     ///
@@ -438,11 +1019,126 @@ impl Directory {
     /// A [`Directory`] handle that enables reference-counted access to the
     /// underlying file.
     pub fn new(r: impl traits::Directory + 'static) -> Self {
-        Self(Arc::new(r))
+        Self(Arc::new(r), None)
+    }
+
+    /// Returns the canonical absolute path of the directory.
+    ///
+    /// The path is known if the directory is opened by a path from
+    /// [`FileSystem::root`], or from another directory whose path is known.
+    pub fn path(&self) -> Option<&str> {
+        self.1.as_deref()
+    }
+
+    fn with_path(self, path: String) -> Self {
+        Self(self.0, Some(Arc::from(path)))
+    }
+
+    // Returns the path of the entry of this directory, if the path of this
+    // directory is known.
+    fn child_path(&self, entry: &str) -> Option<String> {
+        self.path().map(|path| match path {
+            "/" => format!("/{entry}"),
+            path => format!("{path}/{entry}"),
+        })
+    }
+
+    // Opens the entry of this directory.
+    //
+    // A directory opened through a directory of a known path knows its path,
+    // and if a file system is mounted on it, the root directory of the file
+    // system is opened instead.
+    fn enter(&self, entry: &str) -> Result<File, KernelError> {
+        let file = self.0.open_entry(entry)?;
+        Ok(match (file, self.child_path(entry)) {
+            (File::Directory(d), Some(path)) => {
+                File::Directory(FileSystem::mounted_root(&path).unwrap_or(d).with_path(path))
+            }
+            (file, _) => file,
+        })
+    }
+
+    // Returns the parent directory.
+    //
+    // The parent of the root directory is the root itself, and the parent of
+    // the root directory of a mounted file system is the parent of the mount
+    // point.
+    fn parent(&self) -> Result<Directory, KernelError> {
+        match self.path() {
+            Some("/") => Ok(self.clone()),
+            Some(path) => {
+                let (parent, _) = path.rsplit_once('/').unwrap();
+                FileSystem::root()
+                    .open(if parent.is_empty() { "/" } else { parent })?
+                    .into_directory()
+                    .ok_or(KernelError::NotDirectory)
+            }
+            None => self
+                .0
+                .open_entry("..")?
+                .into_directory()
+                .ok_or(KernelError::NotDirectory),
+        }
+    }
+
+    // Walks the path from this directory, or from the root if the path is
+    // absolute, and returns the directory that contains the last component
+    // along with the name of it.
+    //
+    // Repeated slashes and "." are skipped, and ".." goes back to the parent
+    // directory; ".." at the root stays at the root. A directory on which a
+    // file system is mounted is replaced by the root directory of the file
+    // system, and ".." goes back across it. Every component but the
+    // last must be a directory. If the path ends with "." or "..", or has no
+    // component at all (e.g., "/"), the path names the returned directory
+    // itself, and no name is returned.
+    fn walk<'a>(&self, path: &'a str) -> Result<(Directory, Option<&'a str>), KernelError> {
+        let mut stack = Vec::from([if path.starts_with('/') {
+            FileSystem::root()
+        } else {
+            self.clone()
+        }]);
+        let mut parts = path.split('/').filter(|s| !s.is_empty()).peekable();
+        while let Some(part) = parts.next() {
+            match part {
+                "." => (),
+                ".." if stack.len() > 1 => {
+                    stack.pop();
+                }
+                // Going above the starting directory.
+                ".." => stack[0] = stack[0].parent()?,
+                _ if parts.peek().is_none() => return Ok((stack.pop().unwrap(), Some(part))),
+                _ => {
+                    let dir = stack
+                        .last()
+                        .unwrap()
+                        .enter(part)?
+                        .into_directory()
+                        .ok_or(KernelError::NotDirectory)?;
+                    stack.push(dir);
+                }
+            }
+        }
+        Ok((stack.pop().unwrap(), None))
+    }
+
+    // Walks the path to the parent directory of a new or removed entry, which
+    // must be named by the path.
+    fn walk_parent<'a>(&self, path: &'a str) -> Result<(Directory, &'a str), KernelError> {
+        match self.walk(path)? {
+            (dir, Some(entry)) => Ok((dir, entry)),
+            (_, None) if path.is_empty() => Err(KernelError::InvalidArgument),
+            (_, None) => Err(KernelError::FileExist),
+        }
     }
 
     /// Opens a path from the directory.
     ///
+    /// The path is canonicalized as POSIX does: repeated slashes and `.`
+    /// components are ignored, `..` refers to the parent directory, and `..`
+    /// at the root refers to the root itself. A path with a trailing slash
+    /// must name a directory.
+    ///
     /// # Parameters
     /// - `path`: The path to the entry.
     ///
@@ -450,25 +1146,26 @@ impl Directory {
     /// - `Ok(File)`: The type of the file (e.g., regular file, directory).
     /// - `Err(Error)`: An error if the entry cannot be found or accessed.
     #[inline]
-    pub fn open(&self, mut path: &str) -> Result<File, KernelError> {
-        let mut ret = File::Directory(if path.starts_with("/") {
-            path = &path[1..];
-            FileSystem::root()
-        } else {
-            self.clone()
-        });
-
-        for part in path.split("/").filter(|&s| !s.is_empty()) {
-            match ret {
-                File::Directory(d) => ret = d.0.open_entry(part)?,
-                File::RegularFile(_) => return Err(KernelError::NotDirectory),
+    pub fn open(&self, path: &str) -> Result<File, KernelError> {
+        let file = match self.walk(path)? {
+            (dir, Some(entry)) => dir.enter(entry)?,
+            (dir, None) => File::Directory(dir),
+        };
+        match file {
+            File::RegularFile(_) | File::Fifo(_) if path.ends_with('/') => {
+                Err(KernelError::NotDirectory)
             }
+            file => Ok(file),
         }
-        Ok(ret)
     }
 
     /// Create an entry in the directory.
     ///
+    /// The path is canonicalized as in [`Directory::open`]. A path that names
+    /// an existing directory, e.g., `a/..`, fails with
+    /// [`KernelError::FileExist`], and a path with a trailing slash can only
+    /// create a directory.
+    ///
     /// # Parameters
     /// - `path`: The path to the entry.
     /// - `is_dir`: Indicate whether the entry is directory or not.
@@ -477,30 +1174,40 @@ impl Directory {
     /// - `Ok(())`: If the entry was successfully added.
     /// - `Err(Error)`: An error if the add fails.
     #[inline]
-    pub fn create(&self, mut path: &str, is_dir: bool) -> Result<File, KernelError> {
-        let mut dstdir = if path.starts_with("/") {
-            path = &path[1..];
-            FileSystem::root()
-        } else {
-            self.clone()
-        };
-
-        let mut list: Vec<&str> = path.split("/").filter(|&s| !s.is_empty()).collect();
-        let entry = list.pop().ok_or(KernelError::InvalidArgument)?;
-
-        for part in list {
-            dstdir = dstdir
-                .0
-                .open_entry(part)?
-                .into_directory()
-                .ok_or(KernelError::NoSuchEntry)?;
+    pub fn create(&self, path: &str, is_dir: bool) -> Result<File, KernelError> {
+        let (dstdir, entry) = self.walk_parent(path)?;
+        if !is_dir && path.ends_with('/') {
+            return Err(KernelError::NotDirectory);
         }
-
         dstdir.0.create_entry(entry, is_dir)
     }
 
+    /// Create a named pipe (FIFO) in the directory.
+    ///
+    /// The path is canonicalized as in [`Directory::create`].
+    ///
+    /// # Parameters
+    /// - `path`: The path to the FIFO.
+    ///
+    /// # Returns
+    /// - `Ok(File)`: The created [`File::Fifo`].
+    /// - `Err(Error)`: An error if the add fails.
+    #[inline]
+    pub fn create_fifo(&self, path: &str) -> Result<File, KernelError> {
+        let (dstdir, entry) = self.walk_parent(path)?;
+        if path.ends_with('/') {
+            return Err(KernelError::NotDirectory);
+        }
+        dstdir.0.create_fifo_entry(entry)
+    }
+
     /// Unlink an entry in the directory.
     ///
+    /// The path is canonicalized as in [`Directory::open`]. A path that ends
+    /// with `.` or `..` names a directory in use, which fails with
+    /// [`KernelError::Busy`], and a path with a trailing slash must name a
+    /// directory.
+    ///
     /// # Parameters
     /// - `path`: The path to the entry.
     ///
@@ -508,26 +1215,57 @@ impl Directory {
     /// - `Ok(())`: If the entry was successfully unlinked.
     /// - `Err(Error)`: An error if the unlink operation fails.
     #[inline]
-    pub fn unlink(&self, mut path: &str) -> Result<(), KernelError> {
-        let mut dstdir = if path.starts_with("/") {
-            path = &path[1..];
-            FileSystem::root()
-        } else {
-            self.clone()
+    pub fn unlink(&self, path: &str) -> Result<(), KernelError> {
+        let (dstdir, entry) = match self.walk(path)? {
+            (dir, Some(entry)) => (dir, entry),
+            (_, None) if path.is_empty() => return Err(KernelError::InvalidArgument),
+            (_, None) => return Err(KernelError::Busy),
         };
+        if path.ends_with('/') && dstdir.0.open_entry(entry)?.into_directory().is_none() {
+            return Err(KernelError::NotDirectory);
+        }
+        if dstdir.is_mount_point(entry) {
+            return Err(KernelError::Busy);
+        }
+        dstdir.0.unlink_entry(entry)
+    }
 
-        let mut list: Vec<&str> = path.split("/").filter(|&s| !s.is_empty()).collect();
-        let entry = list.pop().ok_or(KernelError::InvalidArgument)?;
-
-        for part in list {
-            dstdir = dstdir
-                .0
-                .open_entry(part)?
-                .into_directory()
-                .ok_or(KernelError::NoSuchEntry)?;
+    /// Renames an entry, moving it to another directory if needed.
+    ///
+    /// Both paths are canonicalized as in [`Directory::open`], and must be
+    /// in the same file system. A mount point cannot be renamed.
+    ///
+    /// # Parameters
+    /// - `from`: The path to the entry.
+    /// - `to`: The new path of the entry.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the entry was successfully renamed.
+    /// - `Err(KernelError::InvalidArgument)`: If the paths are in different
+    ///   file systems.
+    /// - `Err(Error)`: An error if the rename operation fails.
+    pub fn rename(&self, from: &str, to: &str) -> Result<(), KernelError> {
+        let (src, entry) = match self.walk(from)? {
+            (dir, Some(entry)) => (dir, entry),
+            (_, None) if from.is_empty() => return Err(KernelError::InvalidArgument),
+            (_, None) => return Err(KernelError::Busy),
+        };
+        let (dst, new_entry) = self.walk_parent(to)?;
+        if src.is_mount_point(entry) {
+            return Err(KernelError::Busy);
+        }
+        let mount_point = |dir: &Directory| dir.path().map(FileSystem::mount_point_of);
+        if mount_point(&src) != mount_point(&dst) {
+            return Err(KernelError::InvalidArgument);
         }
+        src.0.rename_entry(entry, &dst, new_entry)
+    }
 
-        dstdir.0.unlink_entry(entry)
+    // Returns `true` if a file system is mounted on the entry of this
+    // directory.
+    fn is_mount_point(&self, entry: &str) -> bool {
+        self.child_path(entry)
+            .is_some_and(|path| FileSystem::mounted_root(&path).is_some())
     }
 
     /// Reads the contents of the directory.
@@ -542,6 +1280,28 @@ impl Directory {
         self.0.read_dir()
     }
 
+    /// Reads at most `max` entries of the directory, starting from the
+    /// `cursor`, and advances the cursor past them.
+    ///
+    /// The cursor starts from 0. An empty list marks the end of the
+    /// directory. See [`traits::Directory::read_dir_from`] for details.
+    #[inline]
+    pub fn read_dir_from(
+        &self,
+        cursor: &mut usize,
+        max: usize,
+    ) -> Result<Vec<(InodeNumber, String)>, KernelError> {
+        self.0.read_dir_from(cursor, max)
+    }
+
+    /// Writes back the entries of the directory to disk.
+    ///
+    /// See [`traits::Directory::writeback`] for details.
+    #[inline]
+    pub fn writeback(&self) -> Result<(), KernelError> {
+        self.0.writeback()
+    }
+
     /// Returns [`AtomicBool`] which contains whether directory is removed.
     ///
     /// This is important because directory operations against the removed
@@ -556,6 +1316,251 @@ impl Directory {
     }
 }
 
+/// The capacity of the pipe that connects the readers and writers of a
+/// [`Fifo`].
+const FIFO_CAPACITY: usize = 4096;
+
+// The pipe of an opened FIFO.
+struct Rendezvous {
+    pipe: ChannelRef<u8>,
+    // Doorbells of the readers waiting for a writer.
+    readers: Vec<Sender<()>>,
+    // Doorbells of the writers waiting for a reader.
+    writers: Vec<Sender<()>>,
+}
+
+// The pipes of the opened FIFOs, keyed by the inode number of the FIFO.
+static RENDEZVOUS: SpinLock<BTreeMap<InodeNumber, Rendezvous>> = SpinLock::new(BTreeMap::new());
+
+/// A handle to a named pipe (FIFO).
+///
+/// A FIFO holds no data on the file system. It is a rendezvous point where
+/// the readers and writers that open it by path are connected with a pipe:
+/// opening either end blocks while the other end is not opened, and the
+/// opened ends then behave like the ends of an anonymous pipe. All the ends
+/// opened at the same time share the pipe, which is discarded once every end
+/// is closed.
+#[derive(Clone)]
+pub struct Fifo(InodeNumber);
+
+impl Fifo {
+    /// Creates a new [`Fifo`] handle for the FIFO inode `ino`.
+    pub fn new(ino: InodeNumber) -> Self {
+        Self(ino)
+    }
+
+    /// Inode number of the FIFO.
+    pub fn ino(&self) -> InodeNumber {
+        self.0
+    }
+
+    /// Opens the read end of the FIFO.
+    ///
+    /// Blocks while no writer has the FIFO opened.
+    pub fn open_read(&self) -> Receiver<u8> {
+        self.meet(true, |pipe| pipe.receiver())
+    }
+
+    /// Opens the write end of the FIFO.
+    ///
+    /// Blocks while no reader has the FIFO opened.
+    pub fn open_write(&self) -> Sender<u8> {
+        self.meet(false, |pipe| pipe.sender())
+    }
+
+    // Opens an end of the pipe with `open`, then waits while the peer count
+    // is 0.
+    //
+    // The waiting peers are woken up, as the count of their peers is no
+    // longer 0. The pipe whose ends are all closed is discarded, so that a
+    // new pipe is made for the next open.
+    fn meet<T>(&self, is_reader: bool, open: impl FnOnce(&ChannelRef<u8>) -> T) -> T {
+        let mut guard = RENDEZVOUS.lock();
+        guard.retain(|_, en| en.pipe.senders() != 0 || en.pipe.receivers() != 0);
+        let en = guard.entry(self.0).or_insert_with(|| Rendezvous {
+            pipe: ChannelRef::new(FIFO_CAPACITY),
+            readers: Vec::new(),
+            writers: Vec::new(),
+        });
+        let end = open(&en.pipe);
+        let (peers, bells) = if is_reader {
+            (en.pipe.senders(), core::mem::take(&mut en.writers))
+        } else {
+            (en.pipe.receivers(), core::mem::take(&mut en.readers))
+        };
+        let wait = (peers == 0).then(|| {
+            let (bell, wait) = channel(1);
+            if is_reader {
+                en.readers.push(bell);
+            } else {
+                en.writers.push(bell);
+            }
+            wait
+        });
+        guard.unlock();
+        for bell in bells {
+            let _ = bell.send(());
+        }
+        if let Some(wait) = wait {
+            let _ = wait.recv();
+        }
+        end
+    }
+}
+
+/// The kind of an advisory lock on a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlockKind {
+    /// A shared lock, which can be held by multiple owners at once.
+    Shared,
+    /// An exclusive lock, which excludes all the other locks.
+    Exclusive,
+}
+
+// The advisory locks held on a file.
+#[derive(Default)]
+struct Flocks {
+    // The number of the shared locks.
+    shared: usize,
+    // Whether the exclusive lock is held.
+    exclusive: bool,
+    // The threads waiting for the locks to be released.
+    waiters: Vec<ParkHandle>,
+}
+
+impl Flocks {
+    // Releases a lock of `kind`, and returns the waiters to wake up if no
+    // lock is left.
+    fn release(&mut self, kind: FlockKind) -> Vec<ParkHandle> {
+        match kind {
+            FlockKind::Shared => self.shared -= 1,
+            FlockKind::Exclusive => self.exclusive = false,
+        }
+        if self.shared == 0 && !self.exclusive {
+            core::mem::take(&mut self.waiters)
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+// Held advisory locks, keyed by the inode number of the locked file.
+static FLOCKS: SpinLock<BTreeMap<InodeNumber, Flocks>> = SpinLock::new(BTreeMap::new());
+
+/// An owner of an advisory lock on a file.
+///
+/// Advisory locks coordinate the processes that access a shared file: a file
+/// is locked either by any number of [`FlockKind::Shared`] locks, or by a
+/// single [`FlockKind::Exclusive`] lock. The locks are *advisory*, as they
+/// never restrict the I/O on the file; they only exclude each other.
+///
+/// An owner holds at most one lock at a time, and releases it when dropped.
+/// The owners of the same file exclude each other, even if they belong to the
+/// same process.
+pub struct FileLock {
+    ino: InodeNumber,
+    held: SpinLock<Option<FlockKind>>,
+}
+
+impl FileLock {
+    /// Creates a new owner of the advisory lock on the inode `ino`, which
+    /// holds no lock.
+    pub fn new(ino: InodeNumber) -> Self {
+        Self {
+            ino,
+            held: SpinLock::new(None),
+        }
+    }
+
+    /// Inode number of the locked file.
+    pub fn ino(&self) -> InodeNumber {
+        self.ino
+    }
+
+    /// Returns the kind of the held lock, if any.
+    pub fn held(&self) -> Option<FlockKind> {
+        let guard = self.held.lock();
+        let held = *guard;
+        guard.unlock();
+        held
+    }
+
+    /// Acquires a lock of `kind`, converting the held lock if any.
+    ///
+    /// Sleeps until the conflicting locks of the other owners are released.
+    /// If `nonblocking` is set, fails with [`KernelError::Busy`] instead.
+    ///
+    /// As in Linux, a conversion is not atomic: the held lock is released
+    /// first, so that another owner may take the lock in between. Each
+    /// attempt is made with the owner locked, though, so that the concurrent
+    /// calls on the same owner never take more than one lock.
+    pub fn lock(&self, kind: FlockKind, nonblocking: bool) -> Result<(), KernelError> {
+        loop {
+            let mut held = self.held.lock();
+            if *held == Some(kind) {
+                held.unlock();
+                return Ok(());
+            }
+            let mut guard = FLOCKS.lock();
+            let en = guard.entry(self.ino).or_default();
+            // Releasing the last lock of the file grants any kind, so there
+            // is a waiter to wake up only if the lock is granted.
+            let released = held.take().map(|old| en.release(old)).unwrap_or_default();
+            if !en.exclusive && (kind == FlockKind::Shared || en.shared == 0) {
+                match kind {
+                    FlockKind::Shared => en.shared += 1,
+                    FlockKind::Exclusive => en.exclusive = true,
+                }
+                *held = Some(kind);
+                guard.unlock();
+                held.unlock();
+                for th in released {
+                    th.unpark();
+                }
+                return Ok(());
+            }
+            if nonblocking {
+                guard.unlock();
+                held.unlock();
+                return Err(KernelError::Busy);
+            }
+            // Retry after the locks are released.
+            let ino = self.ino;
+            Current::park_with(move |th| {
+                guard.get_mut(&ino).unwrap().waiters.push(th);
+                guard.unlock();
+                held.unlock();
+            });
+        }
+    }
+
+    /// Releases the held lock, if any.
+    pub fn unlock(&self) {
+        let mut held = self.held.lock();
+        let Some(kind) = held.take() else {
+            held.unlock();
+            return;
+        };
+        let mut guard = FLOCKS.lock();
+        let en = guard.get_mut(&self.ino).unwrap();
+        let waiters = en.release(kind);
+        if en.shared == 0 && !en.exclusive {
+            guard.remove(&self.ino);
+        }
+        guard.unlock();
+        held.unlock();
+        for th in waiters {
+            th.unpark();
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        self.unlock();
+    }
+}
+
 /// Represents a file system entry, which can be either a file or a directory.
 ///
 /// This enum allows distinguishing between regular files and directories within
@@ -574,6 +1579,12 @@ pub enum File {
     /// This variant represents a directory in the filesystem, which can contain
     /// other files or directories.
     Directory(Directory),
+
+    /// A named pipe (FIFO).
+    ///
+    /// This variant represents a FIFO in the filesystem, which connects the
+    /// readers and writers that open it by path.
+    Fifo(Fifo),
 }
 
 impl File {
@@ -620,11 +1631,21 @@ impl File {
         }
     }
 
+    /// Converts the `File` into a [`Fifo`], if it is one.
+    pub fn into_fifo(self) -> Option<Fifo> {
+        if let File::Fifo(f) = self {
+            Some(f)
+        } else {
+            None
+        }
+    }
+
     /// Get [`InodeNumber`] of this [`File`] regardless of its inner type.
     pub fn ino(&self) -> InodeNumber {
         match self {
             File::RegularFile(r) => r.ino(),
             File::Directory(d) => d.ino(),
+            File::Fifo(f) => f.ino(),
         }
     }
 
@@ -633,6 +1654,70 @@ impl File {
         match self {
             File::RegularFile(r) => r.size() as u64,
             File::Directory(d) => d.size() as u64,
+            File::Fifo(_) => 0,
+        }
+    }
+
+    /// Get link count of this [`File`] regardless of its inner type.
+    pub fn link_count(&self) -> u64 {
+        match self {
+            File::RegularFile(r) => r.link_count() as u64,
+            File::Directory(d) => d.link_count() as u64,
+            File::Fifo(_) => 1,
+        }
+    }
+
+    /// Get the number of the data blocks of this [`File`] regardless of its
+    /// inner type.
+    pub fn blocks(&self) -> u64 {
+        match self {
+            File::RegularFile(r) => r.blocks() as u64,
+            File::Directory(d) => d.blocks() as u64,
+            File::Fifo(_) => 0,
+        }
+    }
+
+    /// Get the last modification time of this [`File`] regardless of its
+    /// inner type.
+    pub fn mtime(&self) -> u64 {
+        match self {
+            File::RegularFile(r) => r.mtime(),
+            File::Directory(d) => d.mtime(),
+            File::Fifo(_) => 0,
+        }
+    }
+
+    /// Get the last access time of this [`File`] regardless of its inner
+    /// type.
+    pub fn atime(&self) -> u64 {
+        match self {
+            File::RegularFile(r) => r.atime(),
+            File::Directory(d) => d.atime(),
+            File::Fifo(_) => 0,
+        }
+    }
+
+    /// Get the permission bits of this [`File`] regardless of its inner
+    /// type.
+    ///
+    /// A FIFO can always be read and written by its owner.
+    pub fn mode(&self) -> u32 {
+        match self {
+            File::RegularFile(r) => r.mode(),
+            File::Directory(d) => d.mode(),
+            File::Fifo(_) => crate::syscall::flags::S_IRUSR | crate::syscall::flags::S_IWUSR,
+        }
+    }
+
+    /// Change the permission bits of this [`File`] regardless of its inner
+    /// type.
+    ///
+    /// The permissions of a FIFO cannot be changed.
+    pub fn set_mode(&self, mode: u32) -> Result<(), KernelError> {
+        match self {
+            File::RegularFile(r) => r.set_mode(mode),
+            File::Directory(d) => d.set_mode(mode),
+            File::Fifo(_) => Err(KernelError::NotSupportedOperation),
         }
     }
 }
@@ -715,6 +1800,48 @@ impl core::ops::Add<usize> for FileBlockNumber {
     }
 }
 
+/// Page cache accounting of a thread.
+///
+/// Each thread counts how many file blocks it found in the page cache (hits)
+/// and how many blocks had to be loaded from the disk (misses). The page cache
+/// layer reports each access with [`CacheAccounting::record`], and the
+/// per-process I/O statistics are built from the differences of
+/// [`CacheAccounting::current`] around a system call.
+#[derive(Default)]
+pub struct CacheAccounting {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheAccounting {
+    /// Records a page cache access of the current thread.
+    ///
+    /// # Parameters
+    /// - `hit`: Whether the requested block was already cached.
+    pub fn record(hit: bool) {
+        with_current(|th| {
+            if hit {
+                th.cache_accounting.hits.fetch_add(1);
+            } else {
+                th.cache_accounting.misses.fetch_add(1);
+            }
+        });
+    }
+
+    /// Returns the `(hits, misses)` counters of the current thread.
+    pub fn current() -> (u64, u64) {
+        with_current(|th| {
+            (
+                th.cache_accounting.hits.load(),
+                th.cache_accounting.misses.load(),
+            )
+        })
+    }
+}
+
+/// The number of batched reads made by [`Disk::read_many`].
+static BATCHED_READS: AtomicU64 = AtomicU64::new(0);
+
 // The type for disk hooking.
 #[doc(hidden)]
 pub type Hook =
@@ -757,6 +1884,13 @@ impl Disk {
         }
     }
 
+    /// Get the number of sectors of the disk.
+    pub fn block_cnt(&self) -> Result<usize, KernelError> {
+        abyss::dev::get_bdev(self.index)
+            .map(|dev| dev.block_cnt())
+            .ok_or(KernelError::IOError)
+    }
+
     /// Read 512 bytes from disk starting from sector.
     pub fn read(&self, sector: Sector, buf: &mut [u8; 512]) -> Result<(), KernelError> {
         let dev = abyss::dev::get_bdev(self.index).ok_or(KernelError::IOError)?;
@@ -770,6 +1904,41 @@ impl Disk {
         }
     }
 
+    /// Read `buf.len()` bytes from disk starting from sector.
+    ///
+    /// The length of `buf` must be a multiple of 512. The contiguous sectors
+    /// are fetched in a single device request with
+    /// [`BlockOps::read_block_many`] if the device supports it; otherwise,
+    /// they are read one at a time.
+    pub fn read_many(&self, sector: Sector, buf: &mut [u8]) -> Result<(), KernelError> {
+        assert_eq!(buf.len() % 512, 0, "Disk::read_many: unaligned buffer.");
+        let dev = abyss::dev::get_bdev(self.index).ok_or(KernelError::IOError)?;
+        if let Some(hook) = self.hook.as_ref() {
+            for (i, chunk) in buf.chunks_exact(512).enumerate() {
+                hook(sector + i, chunk.try_into().unwrap(), false)?;
+            }
+        }
+        let ok = if dev.can_read_many() {
+            BATCHED_READS.fetch_add(1);
+            dev.read_block_many(sector.into_offset(), buf)
+        } else {
+            buf.chunks_exact_mut(512)
+                .enumerate()
+                .all(|(i, chunk)| dev.read(sector + i, chunk.try_into().unwrap()))
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(KernelError::IOError)
+        }
+    }
+
+    /// Returns the number of [`Disk::read_many`] calls served by a single
+    /// device request since boot.
+    pub fn batched_reads() -> u64 {
+        BATCHED_READS.load()
+    }
+
     /// Write 512 bytes to disk starting from sector.
     pub fn write(&self, sector: Sector, buf: &[u8; 512]) -> Result<(), KernelError> {
         let dev = abyss::dev::get_bdev(self.index).ok_or(KernelError::IOError)?;
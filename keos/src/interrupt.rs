@@ -1,7 +1,7 @@
 //! Interrupt management.
 use crate::{sync::SpinLock, thread::with_current};
 use abyss::{
-    x86_64::{Cr2, PrivilegeLevel, interrupt::PFErrorCode},
+    x86_64::{Cr2, PrivilegeLevel, interrupt::PFErrorCode, kernel_gs},
     {addressing::Va, interrupt::Registers},
 };
 use alloc::sync::Arc;
@@ -44,9 +44,19 @@ pub extern "C" fn handle_page_fault(frame: &mut Registers, ec: PFErrorCode) {
     with_current(|th| match th.task.as_mut() {
         Some(task) => {
             let cr2 = Va::new(Cr2::current().into_usize()).unwrap();
+            // Expose the user registers as the interrupt frame, as the system
+            // call entry does, so that the fault can be traced back into the
+            // user program.
+            let gs = unsafe { kernel_gs::current() };
+            let prev = gs.interrupt_frame;
+            if frame.interrupt_stack_frame.cs.dpl() == PrivilegeLevel::Ring3 {
+                gs.interrupt_frame = frame;
+            }
             // Enable interrupt after resolving the faulting address.
             unsafe { abyss::interrupt::InterruptState::enable() };
             task.page_fault(ec, cr2);
+            // The thread may have moved to another core in the meantime.
+            unsafe { kernel_gs::current() }.interrupt_frame = prev;
         }
         _ => {
             panic!("Unexpected page fault: {:?} {:#?}", ec, frame);
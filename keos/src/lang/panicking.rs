@@ -1,13 +1,16 @@
 //! KEOS panic handler.
-use crate::thread::STACK_SIZE;
+use crate::{
+    syscall::uaccess::copy_from_user, task::SymbolTable, teletype::Teletype, thread::STACK_SIZE,
+};
 use abyss::{
     dev::x86_64::apic::{IPIDest, Mode, send_ipi},
     interrupt::{InterruptGuard, NMI_EXPECTED_PANICKING},
     unwind::{DwarfReader, Peeker, StackFrame, UnwindBacktrace},
-    x86_64::{intrinsics::cpuid, kernel_gs, pio::Pio},
+    x86_64::{PrivilegeLevel, intrinsics::cpuid, kernel_gs, pio::Pio},
 };
 use addr2line::{Context, Frame};
-use alloc::{borrow::Cow, sync::Arc};
+use alloc::{borrow::Cow, string::String, sync::Arc};
+use core::fmt::Write;
 use core::mem::ManuallyDrop;
 use core::sync::atomic::Ordering;
 
@@ -109,9 +112,67 @@ fn do_backtrace(state: &mut (isize, bool), frame: &StackFrame) {
         }
         return;
     }
+    if pc >> 48 != 0xffff
+        && let Some(symbols) = user_symbols()
+        && let Some((name, offset)) = symbols.resolve(pc as usize)
+    {
+        println!("  {:2}: 0x{:016x}  - {}+0x{:x}", depth, pc, name, offset);
+        return;
+    }
     println!("  {:2}: 0x{:016x}  - ?", depth, pc);
 }
 
+/// Returns the symbol table of the user program of the current thread, if the
+/// stack of the thread is intact.
+fn user_symbols() -> Option<Arc<SymbolTable>> {
+    crate::thread::__with_current(|th| crate::task::user_symbols_of(&th.user_symbols))
+        .ok()
+        .flatten()
+}
+
+/// Prints the backtrace of the user program that trapped into the kernel.
+///
+/// The backtrace starts from the user registers saved on the entry to the
+/// kernel, and follows the frame pointers on the user stack. Each address is
+/// printed as `function+offset` if the symbol table of the program is known,
+/// or as a bare address otherwise. It is written to the teletype, so that it
+/// is also captured by the grading hook.
+pub(crate) fn print_user_backtrace() {
+    let Some(frame) = (unsafe { kernel_gs::current().interrupt_frame.as_ref() })
+        .filter(|frame| frame.interrupt_stack_frame.cs.dpl() == PrivilegeLevel::Ring3)
+    else {
+        return;
+    };
+    let symbols = crate::task::user_symbols();
+    let mut report = String::from("User backtrace:\n");
+    let (mut pc, mut fp) = (frame.interrupt_stack_frame.rip, frame.gprs.rbp);
+    for depth in 0..USER_BACKTRACE_DEPTH {
+        let _ = write!(report, "  {depth:2}: 0x{pc:016x}");
+        match symbols.as_ref().and_then(|symbols| symbols.resolve(pc)) {
+            Some((name, offset)) => {
+                let _ = writeln!(report, "  - {name}+0x{offset:x}");
+            }
+            None => report.push_str("  - ?\n"),
+        }
+        // The frame pointer points to the saved frame pointer of the caller,
+        // followed by the return address.
+        let Ok([next_fp, ret]) = copy_from_user::<[usize; 2]>(fp) else {
+            break;
+        };
+        if next_fp <= fp || ret == 0 {
+            break;
+        }
+        // Point within the call instruction, as for the kernel frames.
+        (pc, fp) = (ret - 1, next_fp);
+    }
+    let mut serial = crate::teletype::serial().lock();
+    let _ = serial.write(report.as_bytes());
+    serial.unlock();
+}
+
+/// The maximum number of frames printed by [`print_user_backtrace`].
+const USER_BACKTRACE_DEPTH: usize = 16;
+
 static mut DEBUG_CONTEXT: Option<Context<gimli::EndianArcSlice<gimli::LittleEndian>>> = None;
 
 #[allow(dead_code)]
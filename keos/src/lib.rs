@@ -142,24 +142,28 @@ pub mod debugging;
 pub mod tips;
 
 pub mod channel;
+pub mod cmdline;
 pub mod fs;
 #[doc(hidden)]
 pub mod interrupt;
 mod lang;
 pub mod mm;
+pub mod poll;
 pub mod sync;
 pub mod syscall;
 pub mod task;
 pub mod teletype;
 pub mod thread;
+pub mod time;
 pub mod util;
 
 use abyss::spinlock;
 pub use abyss::{
+    kprint::{LogLevel, capture_output, log_enabled, log_level},
     x86_64::intrinsics,
-    {MAX_CPU, addressing, debug, info, print, println, warning},
+    {MAX_CPU, addressing, debug, error, info, print, println, warning},
 };
-use alloc::{boxed::Box, collections::btree_set::BTreeSet, ffi::CString, vec::Vec};
+use alloc::{boxed::Box, collections::btree_set::BTreeSet, vec::Vec};
 use task::Task;
 use thread::scheduler::{BOOT_DONE, Scheduler, scheduler};
 
@@ -177,6 +181,8 @@ pub enum KernelError {
     NoSuchEntry,
     /// IO Error. (EIO)
     IOError,
+    /// No such device or address. (ENXIO)
+    NoSuchDeviceOrAddress,
     /// Exec format error. (ENOEXEC)
     NoExec,
     /// BAD file descriptor. (EBADF)
@@ -224,6 +230,7 @@ impl KernelError {
             KernelError::OperationNotPermitted => -1isize,
             KernelError::NoSuchEntry => -2,
             KernelError::IOError => -5,
+            KernelError::NoSuchDeviceOrAddress => -6,
             KernelError::NoExec => -8,
             KernelError::BadFileDescriptor => -9,
             KernelError::NoMemory => -12,
@@ -261,6 +268,7 @@ impl TryFrom<isize> for KernelError {
             -1 => Ok(Self::OperationNotPermitted),
             -2 => Ok(Self::NoSuchEntry),
             -5 => Ok(Self::IOError),
+            -6 => Ok(Self::NoSuchDeviceOrAddress),
             -8 => Ok(Self::NoExec),
             -9 => Ok(Self::BadFileDescriptor),
             -12 => Ok(Self::NoMemory),
@@ -284,9 +292,6 @@ impl TryFrom<isize> for KernelError {
     }
 }
 
-#[doc(hidden)]
-static mut KERNEL_CMDLINE: Option<CString> = None;
-
 /// Panic depth level.
 ///
 /// Used for determining double panic, and notifying drop handlers that panic is
@@ -316,6 +321,28 @@ impl SystemConfigurationBuilder {
             thread::scheduler::set_scheduler(scheduler);
         }
     }
+
+    /// Enables or disables the deadlock detection of the sleeping locks.
+    ///
+    /// When enabled, a thread about to block on a lock checks whether the
+    /// wait closes a cycle of lock-wait dependencies, and panics if so. See
+    /// [`sync::deadlock_detection`].
+    pub fn set_deadlock_detection(self, enabled: bool) -> Self {
+        sync::set_deadlock_detection(enabled);
+        self
+    }
+
+    /// Sets the log level threshold of the kernel.
+    ///
+    /// Messages of [`debug!`], [`info!`], [`warning!`] and [`error!`] below
+    /// `level` are discarded. All messages are printed by default. See
+    /// [`log_enabled`].
+    ///
+    /// The `loglevel=` option on the [`cmdline`] overrides this setting.
+    pub fn set_log_level(self, level: LogLevel) -> Self {
+        abyss::kprint::set_log_level(level);
+        self
+    }
 }
 
 /// The entry of the KeOS for bootstrap processor.
@@ -337,9 +364,8 @@ Copyright 2025 Computer Architecture and Systems Lab\n"
     unsafe {
         info!("Memory: init memory.");
         crate::mm::init_mm(regions);
-        if let Some(cmd) = cmd {
-            KERNEL_CMDLINE = CString::from_vec_with_nul(cmd.into()).ok();
-        }
+        cmdline::init(cmd.unwrap_or(&[]));
+        syscall::trace::init();
     }
     info!("Devices: init pci devices.");
     unsafe {
@@ -356,6 +382,14 @@ Copyright 2025 Computer Architecture and Systems Lab\n"
     }
     unsafe {
         main(SystemConfigurationBuilder { _p: () });
+        match cmdline::get("loglevel") {
+            Some("debug") => abyss::kprint::set_log_level(LogLevel::Debug),
+            Some("info") => abyss::kprint::set_log_level(LogLevel::Info),
+            Some("warn") => abyss::kprint::set_log_level(LogLevel::Warning),
+            Some("error") => abyss::kprint::set_log_level(LogLevel::Error),
+            Some(level) => warning!("Command line: unknown log level `{level}`."),
+            None => (),
+        }
         abyss::boot::ap_init();
         // Kill the kernel low address.
         (abyss::addressing::Pa::new({
@@ -370,16 +404,14 @@ Copyright 2025 Computer Architecture and Systems Lab\n"
             .as_mut()
             .unwrap()
             .set_flags(mm::page_table::Pml4eFlags::empty());
-        println!(
-            "Command line: {}",
-            KERNEL_CMDLINE
-                .as_ref()
-                .and_then(|cmd| cmd.to_str().ok())
-                .unwrap_or("")
-        );
+        println!("Command line: {}", cmdline::raw());
     }
 
-    crate::interrupt::register(32, |_| scheduler().timer_tick());
+    crate::interrupt::register(32, |_| {
+        time::timer_tick();
+        thread::scheduler::account_tick();
+        thread::scheduler::timer_tick(|| scheduler().timer_tick())
+    });
     crate::interrupt::register(126, mm::tlb::handler);
     crate::interrupt::register(127, |_regs| { /* no-op */ });
     BOOT_DONE.store(true, core::sync::atomic::Ordering::SeqCst);
@@ -443,23 +475,18 @@ impl<T: Task + Default + 'static> TestDriver<T> {
     /// Run the given tests.
     pub fn start<const TC: usize>(tests: [&'static dyn TestCase; TC]) {
         crate::thread::ThreadBuilder::new("test_main").spawn(move || {
-            let tests = match unsafe { KERNEL_CMDLINE.as_ref() } {
-                Some(cmd) if cmd.to_str() != Ok("") => {
-                    let filter = cmd
-                        .to_str()
-                        .expect("Failed to parse cmd")
-                        .split(" ")
-                        .collect::<BTreeSet<_>>();
-                    tests
-                        .iter()
-                        .filter(|test| {
-                            let name = test.name();
-                            let r = name.split("::").next().map(|n| n.len() + 2).unwrap_or(0);
-                            filter.contains(&name[r..])
-                        })
-                        .collect::<Vec<_>>()
-                }
-                _ => tests.iter().collect::<Vec<_>>(),
+            let filter = cmdline::words().collect::<BTreeSet<_>>();
+            let tests = if filter.is_empty() {
+                tests.iter().collect::<Vec<_>>()
+            } else {
+                tests
+                    .iter()
+                    .filter(|test| {
+                        let name = test.name();
+                        let r = name.split("::").next().map(|n| n.len() + 2).unwrap_or(0);
+                        filter.contains(&name[r..])
+                    })
+                    .collect::<Vec<_>>()
             };
             let (total, mut succ) = (tests.len(), 0);
             println!(
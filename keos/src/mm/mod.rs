@@ -81,6 +81,15 @@ impl PageRef<'_> {
         self.kva.into_pa()
     }
 
+    /// Returns `true` if more than one [`Page`] refers to this page.
+    ///
+    /// See [`Page::is_shared`].
+    pub fn is_shared(&self) -> bool {
+        // Borrow the reference count without taking a reference.
+        let page = core::mem::ManuallyDrop::new(unsafe { Page::from_pa(self.pa()) });
+        page.is_shared()
+    }
+
     /// Get a reference to the underlying slice of the page (read-only).
     ///
     /// This method allows access to the contents of the page as a byte slice.
@@ -144,10 +153,37 @@ impl Page {
     /// Allocate a new page.
     ///
     /// This function allocates a new memory page.
+    ///
+    /// # Panics
+    /// Panics if the physical memory is exhausted. Allocations driven by
+    /// user programs (e.g. page faults) should use [`Page::try_new`]
+    /// instead.
     #[inline]
     #[track_caller]
     pub fn new() -> Self {
-        let loc = core::panic::Location::caller();
+        Self::alloc(core::panic::Location::caller(), 0).expect("Failed to allocate page.")
+    }
+
+    /// Try to allocate a new page.
+    ///
+    /// Unlike [`Page::new`], this returns `None` when the memory is
+    /// exhausted. A small number of pages ([`Page::RESERVED_PAGES`]) is
+    /// kept back from this function so that the kernel can still allocate
+    /// its own metadata (e.g. page tables) while it cleans up a process
+    /// that ran out of memory.
+    #[inline]
+    #[track_caller]
+    pub fn try_new() -> Option<Self> {
+        Self::alloc(core::panic::Location::caller(), Self::RESERVED_PAGES)
+    }
+
+    /// The number of pages that [`Page::try_new`] never hands out.
+    pub const RESERVED_PAGES: usize = 64;
+
+    fn alloc(loc: &'static core::panic::Location<'static>, reserve: usize) -> Option<Self> {
+        if reserve != 0 && free_page_count() <= reserve {
+            return None;
+        }
         ContigPages::new(0x1000)
             .map(|inner| Self { inner })
             .inspect(|pg| {
@@ -159,7 +195,6 @@ impl Page {
                     guard.unlock();
                 });
             })
-            .expect("Failed to allocate page.")
     }
 
     /// Get the kernel virtual address of this page.
@@ -180,6 +215,34 @@ impl Page {
         self.inner.kva.into_pa()
     }
 
+    /// Returns a reference to the zero page.
+    ///
+    /// The zero page is a single page filled with zeroes, shared by every
+    /// mapping that has only been read. It must be mapped read-only, and
+    /// replaced with a private copy on the first write, as for a
+    /// copy-on-write page. Unlike other pages, the zero page is never freed,
+    /// so it does not count as an allocation of the current thread.
+    pub fn zero() -> Self {
+        let pa = Pa::new(ZERO_PAGE.load(Ordering::SeqCst) as usize).unwrap();
+        unsafe { PageRef::from_pa(pa) }.into_page()
+    }
+
+    /// Returns `true` if this is the zero page (see [`Page::zero`]).
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        self.pa().into_usize() as u64 == ZERO_PAGE.load(Ordering::SeqCst)
+    }
+
+    /// Returns `true` if other [`Page`]s refer to the same physical page.
+    ///
+    /// This is the case for a page cloned by `fork` or shared between
+    /// processes, whose contents must not be changed behind the back of
+    /// the other owners.
+    #[inline]
+    pub fn is_shared(&self) -> bool {
+        self.inner.ref_cnt.load(Ordering::SeqCst) > 1
+    }
+
     /// Consumes the page, returning its physical address.
     ///
     /// This method "consumes" the [`Page`] and returns its physical address.
@@ -244,6 +307,9 @@ impl Page {
 
 impl Drop for Page {
     fn drop(&mut self) {
+        if self.is_zero() {
+            return;
+        }
         crate::thread::with_current(|th| {
             let mut guard = th.allocations.lock();
             if let Some(alloc) = &mut *guard {
@@ -269,6 +335,9 @@ impl core::fmt::Display for BytePP {
     }
 }
 
+/// The physical address of the zero page.
+static ZERO_PAGE: AtomicU64 = AtomicU64::new(0);
+
 /// Initialize the physical memory allocator.
 #[doc(hidden)]
 pub unsafe fn init_mm(regions: Regions) {
@@ -297,6 +366,11 @@ pub unsafe fn init_mm(regions: Regions) {
             }
         }
     }
+    // The zero page lives forever, so its allocation is never released.
+    let zero = core::mem::ManuallyDrop::new(
+        ContigPages::new(0x1000).expect("Failed to allocate the zero page."),
+    );
+    ZERO_PAGE.store(zero.kva.into_pa().into_usize() as u64, Ordering::SeqCst);
 }
 
 // Physical memory allocators.
@@ -432,6 +506,27 @@ impl PhysicalAllocator {
     }
 }
 
+/// Returns the number of free physical pages.
+#[doc(hidden)]
+pub fn free_page_count() -> usize {
+    let allocator = PALLOC.lock();
+    let count = allocator
+        .inner
+        .iter()
+        .take(allocator.max_idx)
+        .flatten()
+        .map(|arena| {
+            arena
+                .bitmap
+                .iter()
+                .map(|bits| bits.count_ones() as usize)
+                .sum::<usize>()
+        })
+        .sum();
+    allocator.unlock();
+    count
+}
+
 /// A contiguous pages representation.
 pub struct ContigPages {
     arena_idx: usize,
@@ -0,0 +1,107 @@
+//! Waiting for events from multiple sources.
+//!
+//! A thread that waits on a single [`channel`] parks itself on the wait queue
+//! of the channel. To wait on *multiple* channels at once (e.g., for the
+//! `poll` system call), the thread instead registers a shared [`Poller`] on
+//! every channel of interest. The first channel that changes its state
+//! notifies the poller, which wakes the thread up. The waiting thread then
+//! re-checks the channels and deregisters the poller from all of them.
+//!
+//! ## Avoiding lost wakeups
+//!
+//! An event may arrive between checking the channels and parking. A
+//! [`Poller`] remembers such an event: [`Poller::notify`] on a poller that is
+//! not parked yet marks it as *notified*, and the following [`Poller::wait`]
+//! returns immediately. Therefore, a waiter must follow the order below:
+//!
+//! 1. Register the poller on every channel.
+//! 2. Check the readiness of the channels, and stop if any is ready.
+//! 3. [`Poller::wait`], then go back to 2.
+//!
+//! Every event after the step 1 either is observed in the step 2, or notifies
+//! the poller before or during the step 3.
+//!
+//! ## Timeout
+//!
+//! A waiter may bound the wait with a deadline in timer ticks (see
+//! [`now_ticks`]). The poller schedules a [`Timer`] that notifies itself on the
+//! deadline.
+//!
+//! [`channel`]: crate::channel
+
+use crate::{
+    spinlock::SpinLock,
+    thread::{Current, ParkHandle},
+    time::{Timer, now_ticks},
+};
+use alloc::sync::Arc;
+
+enum State {
+    Idle,
+    Notified,
+    Parked(ParkHandle),
+}
+
+/// A wake-up source shared among multiple event sources.
+///
+/// See the [module-level documentation](self) for the usage.
+pub struct Poller {
+    state: SpinLock<State>,
+}
+
+impl Poller {
+    /// Creates a new [`Poller`].
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: SpinLock::new(State::Idle),
+        })
+    }
+
+    /// Notifies an event to the poller.
+    ///
+    /// Wakes up the thread waiting on the poller, if any. Otherwise, the
+    /// notification is kept until the next [`Poller::wait`].
+    pub fn notify(&self) {
+        let mut guard = self.state.lock();
+        let state = core::mem::replace(&mut *guard, State::Notified);
+        guard.unlock();
+        if let State::Parked(th) = state {
+            th.unpark();
+        }
+    }
+
+    /// Parks the current thread until the poller is notified, or the tick
+    /// count reaches the `deadline`.
+    ///
+    /// Returns immediately if the poller was notified since the last wait.
+    /// Returns `false` if the wait is timed out.
+    pub fn wait(self: &Arc<Self>, deadline: Option<u64>) -> bool {
+        let timer = match deadline {
+            Some(deadline) if deadline <= now_ticks() => return false,
+            Some(deadline) => {
+                let poller = self.clone();
+                Some(Timer::at(deadline, move || poller.notify()))
+            }
+            None => None,
+        };
+
+        let mut guard = self.state.lock();
+        if matches!(*guard, State::Notified) {
+            *guard = State::Idle;
+            guard.unlock();
+        } else {
+            Current::park_with(|th| {
+                *guard = State::Parked(th);
+                guard.unlock();
+            });
+            let mut guard = self.state.lock();
+            *guard = State::Idle;
+            guard.unlock();
+        }
+
+        if let Some(timer) = timer {
+            timer.cancel();
+        }
+        deadline.is_none_or(|deadline| now_ticks() < deadline)
+    }
+}
@@ -82,6 +82,26 @@
 pub mod atomic;
 pub mod rwlock;
 pub mod spinlock;
+pub mod ticketlock;
 
 pub use rwlock::*;
 pub use spinlock::*;
+pub use ticketlock::*;
+
+static DEADLOCK_DETECTION: atomic::AtomicBool =
+    atomic::AtomicBool::new(cfg!(feature = "deadlock_detection"));
+
+/// Returns `true` if the deadlock detection of the sleeping locks is enabled.
+///
+/// The detection is enabled at build time with the `deadlock_detection`
+/// feature, or at boot time with
+/// [`SystemConfigurationBuilder::set_deadlock_detection`].
+///
+/// [`SystemConfigurationBuilder::set_deadlock_detection`]: crate::SystemConfigurationBuilder::set_deadlock_detection
+pub fn deadlock_detection() -> bool {
+    DEADLOCK_DETECTION.load()
+}
+
+pub(crate) fn set_deadlock_detection(enabled: bool) {
+    DEADLOCK_DETECTION.store(enabled);
+}
@@ -19,9 +19,14 @@ use core::{
 /// to become available. An `RwLock` will allow any number of readers to acquire
 /// the lock as long as a writer is not holding the lock.
 ///
-/// The priority policy of the lock is dependent on the underlying operating
-/// system's implementation, and this type does not guarantee that any
-/// particular policy will be used.
+/// The priority between readers and writers is selected by the
+/// [`RwLockPolicy`] given to [`RwLock::with_policy`]. A lock created by
+/// [`RwLock::new`] prefers readers: a reader acquires the lock whenever no
+/// writer holds it, at the risk of starving the writers. A writer-preferring
+/// lock blocks new readers once a writer is waiting, until the writer has
+/// acquired and released the lock, so that a steady stream of readers cannot
+/// starve the writer. In this mode, acquiring the read lock again on a thread
+/// that already holds it can deadlock with a waiting writer.
 ///
 /// The type parameter `T` represents the data that this lock protects. It is
 /// required that `T` satisfies [`Send`] to be shared across threads and
@@ -40,10 +45,25 @@ where
     // 1: Writer is waiting.
     // 2: Writer holds the lock.
     state: AtomicUsize,
+    // The number of writers waiting for the lock.
+    writers: AtomicUsize,
+    policy: RwLockPolicy,
     owner: SpinLock<Option<(u64, &'static core::panic::Location<'static>)>>,
     data: UnsafeCell<T>,
 }
 
+/// The priority policy between the readers and the writers of a [`RwLock`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RwLockPolicy {
+    /// Readers acquire the lock whenever no writer holds it, even if writers
+    /// are waiting.
+    #[default]
+    ReaderPreferring,
+    /// Readers wait while a writer is waiting for the lock, so that writers
+    /// are not starved by a steady stream of readers.
+    WriterPreferring,
+}
+
 const STATE_MASK: usize = 0b1 << (usize::BITS - 2);
 const STATE_WRITER_LOCKED: usize = 0b1 << (usize::BITS - 2);
 
@@ -147,14 +167,31 @@ where
     T: Send,
 {
     /// Creates a new instance of an `RwLock<T>` which is unlocked.
+    ///
+    /// The lock prefers readers; use [`RwLock::with_policy`] for a
+    /// writer-preferring lock.
     pub const fn new(data: T) -> RwLock<T> {
+        Self::with_policy(data, RwLockPolicy::ReaderPreferring)
+    }
+
+    /// Creates a new instance of an `RwLock<T>` which is unlocked, with the
+    /// priority `policy` between readers and writers.
+    pub const fn with_policy(data: T, policy: RwLockPolicy) -> RwLock<T> {
         RwLock {
             state: AtomicUsize::new(0),
+            writers: AtomicUsize::new(0),
+            policy,
             owner: SpinLock::new(None),
             data: UnsafeCell::new(data),
         }
     }
 
+    // Returns `true` if a new reader must give way to a waiting writer.
+    #[inline]
+    fn yield_to_writer(&self) -> bool {
+        self.policy == RwLockPolicy::WriterPreferring && self.writers.load(Ordering::Acquire) > 0
+    }
+
     #[inline]
     fn validate_state(
         &self,
@@ -180,6 +217,9 @@ where
             if is_write_locked(prev) {
                 self.validate_state(guard);
                 core::hint::spin_loop();
+            } else if self.yield_to_writer() {
+                guard.unlock();
+                core::hint::spin_loop();
             } else if self
                 .state
                 .compare_exchange(prev, prev + 1, Ordering::Acquire, Ordering::Acquire)
@@ -196,12 +236,9 @@ where
     /// Locks this rwlock with shared read access, blocking the current thread
     /// until it can be acquired.
     ///
-    /// The call
-    /// ing thread will be blocked until there are no more writers which
-    /// hold the lock. There may be other readers currently inside the lock when
-    /// this method returns. This method does not provide any guarantees with
-    /// respect to the ordering of whether contentious readers or writers will
-    /// acquire the lock first.
+    /// The calling thread will be blocked until there are no more writers which
+    /// hold the lock, or, if the lock prefers writers, wait for it. There may
+    /// be other readers currently inside the lock when this method returns.
     ///
     /// Returns an RAII guard which will release this thread's shared access
     /// once it is dropped.
@@ -225,11 +262,8 @@ where
     /// Otherwise, an RAII guard is returned which will release the shared
     /// access when it is dropped.
     ///
-    /// This function does not block.
-    ///
-    /// This function does not provide any guarantees with respect to the
-    /// ordering of whether contentious readers or writers will acquire the
-    /// lock first.
+    /// This function does not block. If the lock prefers writers, it fails
+    /// while a writer is waiting for the lock.
     #[inline]
     #[track_caller]
     pub fn try_read(&self) -> Result<RwLockReadGuard<'_, T>, crate::spinlock::WouldBlock> {
@@ -239,6 +273,9 @@ where
             if is_write_locked(prev) {
                 self.validate_state(guard);
                 break Err(crate::spinlock::WouldBlock);
+            } else if self.yield_to_writer() {
+                guard.unlock();
+                break Err(crate::spinlock::WouldBlock);
             } else if self
                 .state
                 .compare_exchange(prev, prev + 1, Ordering::Acquire, Ordering::Acquire)
@@ -254,22 +291,6 @@ where
         }
     }
 
-    #[inline]
-    fn write_lock(&self) {
-        loop {
-            let prev = self.state.load(Ordering::Relaxed);
-            if prev > 0 {
-                core::hint::spin_loop();
-            } else if self
-                .state
-                .compare_exchange(0, STATE_WRITER_LOCKED, Ordering::Acquire, Ordering::Acquire)
-                .is_ok()
-            {
-                break;
-            }
-        }
-    }
-
     /// Locks this rwlock with exclusive write access, blocking the current
     /// thread until it can be acquired.
     ///
@@ -284,17 +305,17 @@ where
         if let Ok(guard) = self.try_write() {
             guard
         } else {
-            let mut guard = self.owner.lock();
-            self.write_lock();
-            *guard = Some((
-                crate::thread::Current::get_tid(),
-                core::panic::Location::caller(),
-            ));
-            guard.unlock();
-            RwLockWriteGuard {
-                lock: self,
-                data: unsafe { &mut *self.data.get() },
-            }
+            // Announce the waiting writer, so that the new readers give way to
+            // it if the lock prefers writers.
+            self.writers.fetch_add(1, Ordering::AcqRel);
+            let guard = loop {
+                if let Ok(guard) = self.try_write() {
+                    break guard;
+                }
+                core::hint::spin_loop();
+            };
+            self.writers.fetch_sub(1, Ordering::AcqRel);
+            guard
         }
     }
 
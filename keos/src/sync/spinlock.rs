@@ -16,6 +16,12 @@
 //! The step 1 and 2 must be executed ATOMICALLY with the atomic
 //! read-modify-write instructions of the CPU.
 //!
+//! Under contention, the atomic instructions of the waiters bounce the cache
+//! line of the lock between the cores. To reduce this traffic, a waiter that
+//! fails to acquire the lock polls it with plain loads until it is observed
+//! free before retrying the atomic instruction (test-and-test-and-set), and
+//! doubles the pause between the polls up to a cap (exponential backoff).
+//!
 //! This module introduce the support of the SMP-supported spinlock in KeOS.
 
 pub use abyss::spinlock::WouldBlock;
@@ -0,0 +1,227 @@
+//! A fair queue spinlock.
+//!
+//! A [`SpinLock`] grants the lock to whichever waiter happens to win the race
+//! on the atomic instruction after the release. Under contention, a core that
+//! keeps losing the race can starve. [`TicketLock`] instead serves the waiters
+//! in the order of their requests.
+//!
+//! The lock is an MCS lock. A waiter takes its place in line by appending its
+//! own queue node to the tail of the lock with a single atomic swap, then
+//! spins on a flag in its own node, rather than on the lock shared by every
+//! waiter. On release, the holder passes the lock to the next node in line by
+//! clearing its flag. Therefore, only the next waiter observes the release,
+//! which minimizes the cache coherence traffic under contention.
+//!
+//! [`SpinLock`]: crate::sync::SpinLock
+
+use abyss::{interrupt::InterruptGuard, spinlock::WouldBlock};
+use alloc::boxed::Box;
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    ptr::null_mut,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+// A place in line of a TicketLock.
+struct Node {
+    // Whether the owner of the node is waiting for the lock.
+    waiting: AtomicBool,
+    // The next node in line.
+    next: AtomicPtr<Node>,
+}
+
+/// A mutual exclusion primitive that serves the waiters in FIFO order.
+///
+/// [`TicketLock`] can be used in the same places as [`SpinLock`], with the
+/// same interface: the data can only be accessed through the guards returned
+/// from [`lock`] and [`try_lock`], and the guard must be explicitly released
+/// with [`TicketLockGuard::unlock`]. Unlike [`SpinLock`], the lock is granted
+/// in the order in which [`lock`] is called, so no waiter starves. See the
+/// [module-level documentation] for details.
+///
+/// Each acquisition allocates a queue node on the heap, so this lock must not
+/// be used inside the memory allocator.
+///
+/// [`SpinLock`]: crate::sync::SpinLock
+/// [`lock`]: Self::lock
+/// [`try_lock`]: Self::try_lock
+/// [module-level documentation]: self
+pub struct TicketLock<T: ?Sized> {
+    // The last node in line, or null if the lock is free.
+    tail: AtomicPtr<Node>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for TicketLock<T> {}
+unsafe impl<T: ?Sized + Send> Sync for TicketLock<T> {}
+
+impl<T> TicketLock<T> {
+    /// Creates a new ticket lock in an unlocked state ready for use.
+    #[inline]
+    pub const fn new(t: T) -> TicketLock<T> {
+        TicketLock {
+            tail: AtomicPtr::new(null_mut()),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Consumes this lock, returning the underlying data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> TicketLock<T> {
+    /// Acquires the lock, spinning until it is served.
+    ///
+    /// The waiters are served in the order of their calls to this function.
+    /// The interrupts are disabled from the call until the guard is unlocked,
+    /// so that a waiter is not preempted while holding its place in line.
+    ///
+    /// Locking a ticket lock in the thread which already holds the lock
+    /// deadlocks.
+    #[track_caller]
+    pub fn lock(&self) -> TicketLockGuard<'_, T> {
+        let guard = InterruptGuard::new();
+        let node = Box::into_raw(Box::new(Node {
+            waiting: AtomicBool::new(true),
+            next: AtomicPtr::new(null_mut()),
+        }));
+        let prev = self.tail.swap(node, Ordering::SeqCst);
+        if !prev.is_null() {
+            // Get in line behind the previous node, and wait for its owner to
+            // pass the lock.
+            unsafe {
+                (*prev).next.store(node, Ordering::SeqCst);
+                while (*node).waiting.load(Ordering::SeqCst) {
+                    core::hint::spin_loop();
+                }
+            }
+        }
+
+        TicketLockGuard {
+            caller: core::panic::Location::caller(),
+            lock: self,
+            node,
+            guard: Some(guard),
+        }
+    }
+
+    /// Attempts to acquire this lock.
+    ///
+    /// This function does not block.
+    ///
+    /// # Errors
+    ///
+    /// If the lock is held or waited for by another thread, then this call
+    /// will return the [`WouldBlock`] error.
+    #[track_caller]
+    pub fn try_lock(&self) -> Result<TicketLockGuard<'_, T>, WouldBlock> {
+        if !self.tail.load(Ordering::SeqCst).is_null() {
+            return Err(WouldBlock);
+        }
+        let guard = InterruptGuard::new();
+        let node = Box::into_raw(Box::new(Node {
+            waiting: AtomicBool::new(false),
+            next: AtomicPtr::new(null_mut()),
+        }));
+        match self
+            .tail
+            .compare_exchange(null_mut(), node, Ordering::SeqCst, Ordering::SeqCst)
+        {
+            Ok(_) => Ok(TicketLockGuard {
+                caller: core::panic::Location::caller(),
+                lock: self,
+                node,
+                guard: Some(guard),
+            }),
+            Err(_) => {
+                drop(unsafe { Box::from_raw(node) });
+                Err(WouldBlock)
+            }
+        }
+    }
+}
+
+impl<T: Default> Default for TicketLock<T> {
+    /// Creates a `TicketLock<T>`, with the `Default` value for T.
+    fn default() -> TicketLock<T> {
+        TicketLock::new(Default::default())
+    }
+}
+
+/// An implementation of a "scoped lock" of a ticket lock. When this structure
+/// is dropped (falls out of scope) without unlock, panic occurs.
+///
+/// The lock must be explicitly unlocked by [`unlock`] method.
+///
+/// This structure is created by the [`lock`] and [`try_lock`] methods on
+/// [`TicketLock`].
+///
+/// [`lock`]: TicketLock::lock
+/// [`try_lock`]: TicketLock::try_lock
+/// [`unlock`]: Self::unlock
+pub struct TicketLockGuard<'a, T: ?Sized + 'a> {
+    caller: &'static core::panic::Location<'static>,
+    lock: &'a TicketLock<T>,
+    node: *mut Node,
+    guard: Option<InterruptGuard>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for TicketLockGuard<'_, T> {}
+
+impl<T: ?Sized> TicketLockGuard<'_, T> {
+    /// Releases the underlying [`TicketLock`], passing it to the next waiter
+    /// in line.
+    pub fn unlock(mut self) {
+        let node = self.node;
+        unsafe {
+            let mut next = (*node).next.load(Ordering::SeqCst);
+            if next.is_null()
+                && self
+                    .lock
+                    .tail
+                    .compare_exchange(node, null_mut(), Ordering::SeqCst, Ordering::SeqCst)
+                    .is_err()
+            {
+                // A waiter has swapped the tail, but not yet linked its node
+                // behind ours.
+                while next.is_null() {
+                    core::hint::spin_loop();
+                    next = (*node).next.load(Ordering::SeqCst);
+                }
+            }
+            if !next.is_null() {
+                (*next).waiting.store(false, Ordering::SeqCst);
+            }
+            drop(Box::from_raw(node));
+        }
+        self.guard.take();
+        core::mem::forget(self);
+    }
+}
+
+impl<T: ?Sized> Deref for TicketLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for TicketLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for TicketLockGuard<'_, T> {
+    fn drop(&mut self) {
+        panic!(
+            "`.unlock()` must be explicitly called before dropping TicketLockGuard.
+The lock is held at {:?}.",
+            self.caller
+        );
+    }
+}
@@ -3,11 +3,15 @@ use crate::thread::with_current;
 pub use abyss::interrupt::Registers;
 use abyss::x86_64::PrivilegeLevel;
 
+pub mod trace;
 pub mod uaccess;
 
 #[doc(hidden)]
 #[unsafe(no_mangle)]
 pub extern "C" fn do_handle_syscall(frame: &mut Registers) {
+    // The entry of a system call is a safe point of the cancellation.
+    crate::thread::Current::test_cancel();
+    let traced = trace::enter(frame);
     with_current(|th| match th.task.as_mut() {
         Some(task) => {
             task.syscall(frame);
@@ -16,6 +20,9 @@ pub extern "C" fn do_handle_syscall(frame: &mut Registers) {
             panic!("Unexpected `syscall` instruction.")
         }
     });
+    if let Some(call) = traced {
+        trace::exit(call, frame);
+    }
 
     if frame.interrupt_stack_frame.cs.dpl() == PrivilegeLevel::Ring3 {
         crate::thread::__check_for_signal();
@@ -58,4 +65,69 @@ pub mod flags {
         /// data.
         ReadWrite = 2,
     }
+
+    /// Opens the file in direct I/O mode.
+    ///
+    /// This flag can be OR-ed with a [`FileMode`] when opening a regular file.
+    /// The I/O on the opened file bypasses the page cache and goes straight to
+    /// the file system, which is useful for measuring the raw disk
+    /// throughput.
+    pub const O_DIRECT: usize = 0o40000;
+
+    /// Opens the file in non-blocking mode.
+    ///
+    /// An I/O on a non-blocking pipe that cannot make any progress fails with
+    /// [`KernelError::Busy`] instead of waiting for the other end.
+    ///
+    /// [`KernelError::Busy`]: crate::KernelError::Busy
+    pub const O_NONBLOCK: usize = 0o4000;
+
+    /// Marks the opened file descriptor close-on-exec.
+    ///
+    /// The file descriptor is closed when the process replaces its image with
+    /// `execve`. The mark can be changed later with [`F_SETFD`].
+    pub const O_CLOEXEC: usize = 0o2000000;
+
+    /// The `fcntl` command that gets the file descriptor flags.
+    pub const F_GETFD: usize = 1;
+
+    /// The `fcntl` command that sets the file descriptor flags.
+    ///
+    /// The only file descriptor flag is [`FD_CLOEXEC`].
+    pub const F_SETFD: usize = 2;
+
+    /// The file descriptor flag that closes the file descriptor on `execve`.
+    pub const FD_CLOEXEC: usize = 1;
+
+    /// The `whence` of `seek` that moves to the next data of a sparse file at
+    /// or after the offset.
+    pub const SEEK_DATA: usize = 3;
+
+    /// The `whence` of `seek` that moves to the next hole of a sparse file at
+    /// or after the offset. The end of the file is considered a hole.
+    pub const SEEK_HOLE: usize = 4;
+
+    /// The `fcntl` command that gets the access mode and the status flags of
+    /// an open file.
+    pub const F_GETFL: usize = 3;
+
+    /// The `fcntl` command that sets the status flags of an open file.
+    ///
+    /// Only [`O_NONBLOCK`] can be changed; the access mode bits are ignored.
+    pub const F_SETFL: usize = 4;
+
+    /// The permission bit that allows the owner to read the file.
+    pub const S_IRUSR: u32 = 0o400;
+
+    /// The permission bit that allows the owner to write the file.
+    pub const S_IWUSR: u32 = 0o200;
+
+    /// The permission bit that allows the owner to execute the file.
+    pub const S_IXUSR: u32 = 0o100;
+
+    /// The mask of the permission bits of a file.
+    ///
+    /// As KeOS has no users, a file only has the permission bits of its
+    /// owner.
+    pub const S_IRWXU: u32 = 0o700;
 }
@@ -0,0 +1,168 @@
+//! System call tracing.
+//!
+//! When tracing is enabled for a thread, the kernel prints every system call
+//! the thread makes together with its arguments and return value, like the
+//! `strace` utility of Linux:
+//!
+//! ```text
+//! [strace 5] open(path="hello", mode=0) = 3
+//! [strace 5] read(fd=3, buf=0x4747fe80, count=7) = 7
+//! [strace 5] close(fd=3) = 0
+//! [strace 5] close(fd=3) = -9 (BadFileDescriptor)
+//! [strace 5] exit(status=0) = ?
+//! ```
+//!
+//! The number in the brackets is the thread id. The arguments of the common
+//! system calls are decoded by their meaning (e.g., a file descriptor, a
+//! count, or a path); the others are printed as raw numbers. A call that
+//! never returns, such as `exit`, is printed before it runs.
+//!
+//! Tracing is enabled for the current thread with [`set_trace`], and is
+//! inherited by the threads it creates. The `strace` option on the
+//! [kernel command line] enables it for every thread.
+//!
+//! The trace is written to the console, so it is interleaved with the output
+//! of the traced program.
+//!
+//! [kernel command line]: crate::cmdline
+
+use super::{Registers, uaccess::UserCString};
+use crate::{KernelError, teletype::Teletype, thread::with_current};
+use alloc::{format, string::String};
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static TRACE_ALL: AtomicBool = AtomicBool::new(false);
+
+// How an argument of a system call is printed.
+#[derive(Clone, Copy)]
+enum Arg {
+    // A signed decimal number, e.g., a file descriptor or a count.
+    Int(&'static str),
+    // An address in the user space.
+    Ptr(&'static str),
+    // A null-terminated string in the user space.
+    Str(&'static str),
+}
+
+use Arg::*;
+
+// The name and the arguments of a system call. The numbers are shared by
+// every project.
+fn describe(sysno: usize) -> Option<(&'static str, &'static [Arg])> {
+    Some(match sysno {
+        0 => ("exit", &[Int("status")]),
+        1 => ("open", &[Str("path"), Int("mode")]),
+        2 => ("read", &[Int("fd"), Ptr("buf"), Int("count")]),
+        3 => ("write", &[Int("fd"), Ptr("buf"), Int("count")]),
+        4 => ("seek", &[Int("fd"), Int("offset"), Int("whence")]),
+        5 => ("tell", &[Int("fd")]),
+        6 => ("close", &[Int("fd")]),
+        7 => ("pipe", &[Ptr("fds")]),
+        8 => (
+            "mmap",
+            &[
+                Ptr("addr"),
+                Int("size"),
+                Int("prot"),
+                Int("fd"),
+                Int("offset"),
+            ],
+        ),
+        9 => ("munmap", &[Ptr("addr"), Int("size")]),
+        10 => ("fork", &[]),
+        13 => ("exit_group", &[Int("status")]),
+        14 => ("create", &[Str("path")]),
+        15 => ("mkdir", &[Str("path")]),
+        16 => ("unlink", &[Str("path")]),
+        17 => ("chdir", &[Str("path")]),
+        20 => ("fsync", &[Int("fd")]),
+        25 => ("gettid", &[]),
+        26 => ("getpid", &[]),
+        27 => ("getppid", &[]),
+        30 => ("sleep", &[Int("ticks")]),
+        37 => ("execve", &[Str("path"), Ptr("argv"), Ptr("envp")]),
+        _ => return None,
+    })
+}
+
+/// Enables or disables the system call tracing of the current thread.
+///
+/// The threads created afterwards by the current thread inherit the setting.
+pub fn set_trace(enabled: bool) {
+    with_current(|th| th.syscall_trace.store(enabled, Ordering::SeqCst));
+}
+
+/// Returns `true` if the system calls of the current thread are traced.
+pub fn tracing() -> bool {
+    TRACE_ALL.load(Ordering::SeqCst) || with_current(|th| th.syscall_trace.load(Ordering::SeqCst))
+}
+
+// Reads the `strace` option from the kernel command line.
+pub(crate) fn init() {
+    TRACE_ALL.store(crate::cmdline::get("strace").is_some(), Ordering::SeqCst);
+}
+
+fn emit(line: &str) {
+    let tid = with_current(|th| th.tid);
+    let line = format!("[strace {tid}] {line}\n");
+    let mut serial = crate::teletype::serial().lock();
+    let _ = serial.write(line.as_bytes());
+    serial.unlock();
+}
+
+// Called on the entry of a system call.
+//
+// Returns the decoded call to be completed by `exit` with the return value, or
+// `None` if the call is not traced or never returns.
+pub(crate) fn enter(frame: &Registers) -> Option<String> {
+    if !tracing() {
+        return None;
+    }
+    let gprs = &frame.gprs;
+    let args = [gprs.rdi, gprs.rsi, gprs.rdx, gprs.r10, gprs.r8, gprs.r9];
+    let mut call = String::new();
+    match describe(gprs.rax) {
+        Some((name, kinds)) => {
+            let _ = write!(call, "{name}(");
+            for (i, (kind, arg)) in kinds.iter().zip(args).enumerate() {
+                if i != 0 {
+                    call.push_str(", ");
+                }
+                let _ = match kind {
+                    Int(name) => write!(call, "{name}={}", arg as isize),
+                    Ptr(name) => write!(call, "{name}=0x{arg:x}"),
+                    Str(name) => match UserCString::new(arg).read() {
+                        Ok(s) => write!(call, "{name}={s:?}"),
+                        Err(_) => write!(call, "{name}=0x{arg:x}"),
+                    },
+                };
+            }
+            call.push(')');
+            if name == "exit" || name == "exit_group" {
+                emit(&format!("{call} = ?"));
+                return None;
+            }
+        }
+        None => {
+            let _ = write!(
+                call,
+                "syscall_{}(0x{:x}, 0x{:x}, 0x{:x}, 0x{:x}, 0x{:x}, 0x{:x})",
+                gprs.rax, args[0], args[1], args[2], args[3], args[4], args[5]
+            );
+        }
+    }
+    Some(call)
+}
+
+// Called on the return of a traced system call.
+pub(crate) fn exit(call: String, frame: &Registers) {
+    let ret = frame.gprs.rax as isize;
+    match KernelError::try_from(ret) {
+        Ok(e) if ret < 0 => emit(&format!("{call} = {ret} ({e:?})")),
+        _ if call.starts_with("mmap(") => emit(&format!("{call} = 0x{ret:x}")),
+        _ => emit(&format!("{call} = {ret}")),
+    }
+}
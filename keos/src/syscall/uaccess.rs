@@ -23,6 +23,14 @@
 //!   user-space. It provides methods for reading and converting the string into
 //!   a `String` in the kernel.
 //!
+//! All of them are built on top of the following primitives, which can also be
+//! used directly:
+//!
+//! - [`copy_from_user`]: Copies an object of type `T` from user-space.
+//! - [`copy_to_user`]: Copies an object of type `T` to user-space.
+//! - [`strncpy_from_user`]: Copies a null-terminated string of a bounded length
+//!   from user-space.
+//!
 //! These types use unsafe code to access memory directly. The user-space
 //! addresses must be valid and within bounds to prevent undefined behavior or
 //! security vulnerabilities. To ensure the memory safety, these types use
@@ -31,14 +39,99 @@
 //! potential security vulnerabilities and undefined behavior. If the memory is
 //! not accessible, the operation will fail gracefully instead of causing
 //! undefined behavior.
+//!
+//! A user memory range may span multiple pages, some of which may not be
+//! mapped. Therefore, the range is validated page by page, and the access
+//! fails with [`KernelError::BadAddress`] if any page in the range is not
+//! accessible, before any byte of the range is touched.
 use crate::KernelError;
 #[cfg(doc)]
 use crate::task::Task;
 use crate::thread::with_current;
-use abyss::addressing::Va;
+use abyss::addressing::{PAGE_MASK, PAGE_SIZE, Va};
 use alloc::string::String;
 use alloc::vec::Vec;
 
+// Checks that the current task can access the user memory
+// `[addr, addr + len)`, page by page.
+fn check_user_range(addr: usize, len: usize, is_write: bool) -> Result<(), KernelError> {
+    let end = addr.checked_add(len).ok_or(KernelError::BadAddress)?;
+    with_current(|th| {
+        let task = th
+            .task
+            .as_ref()
+            .expect("Try to access the user memory on the kernel thread.");
+        let mut start = addr;
+        loop {
+            let next = (start & !PAGE_MASK)
+                .checked_add(PAGE_SIZE)
+                .map_or(end, |page_end| page_end.min(end));
+            let range = Va::new(start).ok_or(KernelError::BadAddress)?
+                ..Va::new(next).ok_or(KernelError::BadAddress)?;
+            if !task.access_ok(range, is_write) {
+                return Err(KernelError::BadAddress);
+            }
+            if next == end {
+                return Ok(());
+            }
+            start = next;
+        }
+    })
+}
+
+/// Copies an object of type `T` from the user-space address `addr`.
+///
+/// Returns `Err(KernelError::BadAddress)` if any page of the object is not
+/// readable by the current task.
+pub fn copy_from_user<T: Copy>(addr: usize) -> Result<T, KernelError> {
+    check_user_range(addr, core::mem::size_of::<T>(), false)?;
+    // Safety: The range is validated by `check_user_range`.
+    Ok(unsafe { (addr as *const T).read_unaligned() })
+}
+
+/// Copies `val` to the user-space address `addr`.
+///
+/// Returns `Err(KernelError::BadAddress)` if any page of the object is not
+/// writable by the current task. In this case, nothing is written.
+pub fn copy_to_user<T: Copy>(addr: usize, val: T) -> Result<(), KernelError> {
+    check_user_range(addr, core::mem::size_of::<T>(), true)?;
+    // Safety: The range is validated by `check_user_range`.
+    unsafe { (addr as *mut T).write_unaligned(val) };
+    Ok(())
+}
+
+/// Copies a null-terminated string from the user-space address `addr`.
+///
+/// At most `max` bytes, including the null-terminator, are read. The string
+/// is read page by page, so the pages beyond the null-terminator do not need
+/// to be accessible.
+///
+/// # Errors
+/// - [`KernelError::BadAddress`] if a page of the string is not readable.
+/// - [`KernelError::NameTooLong`] if no null-terminator is found in `max`
+///   bytes.
+/// - [`KernelError::InvalidArgument`] if the string is not a valid UTF-8.
+pub fn strncpy_from_user(addr: usize, max: usize) -> Result<String, KernelError> {
+    let mut result = Vec::new();
+    let mut ptr = addr;
+    while result.len() < max {
+        let page_end = (ptr & !PAGE_MASK)
+            .checked_add(PAGE_SIZE)
+            .ok_or(KernelError::BadAddress)?;
+        let len = (page_end - ptr).min(max - result.len());
+        check_user_range(ptr, len, false)?;
+        // Safety: The range is validated by `check_user_range`.
+        let chunk = unsafe { core::slice::from_raw_parts(ptr as *const u8, len) };
+        if let Some(pos) = chunk.iter().position(|b| *b == 0) {
+            result.extend_from_slice(&chunk[..pos]);
+            return String::from_utf8(result).map_err(|_| KernelError::InvalidArgument);
+        }
+        result.extend_from_slice(chunk);
+        ptr += len;
+    }
+    Err(KernelError::NameTooLong)
+}
+
 /// A one-time, read-only pointer to a user-space object of type `T`.
 ///
 /// This struct allows the kernel to read from user-space while ensuring
@@ -75,19 +168,7 @@ where
     /// Returns `Ok(T)` if successful, otherwise
     /// `Err(KernelError::BadAddress)`.
     pub fn get(self) -> Result<T, KernelError> {
-        let access_range = Va::new(self.addr).ok_or(KernelError::BadAddress)?
-            ..Va::new(self.addr + core::mem::size_of::<T>()).ok_or(KernelError::BadAddress)?;
-        with_current(|th| {
-            let task = th
-                .task
-                .as_ref()
-                .expect("Try to call UserPtrRO::get() on the kernel thread.");
-            if task.access_ok(access_range, false) {
-                Ok(unsafe { { self.addr as *const T }.read_unaligned() })
-            } else {
-                Err(KernelError::BadAddress)
-            }
-        })
+        copy_from_user(self.addr)
     }
 }
 
@@ -127,25 +208,7 @@ where
     /// Returns `Ok(usize)` indicating the number of bytes written, or
     /// `Err(KernelError::BadAddress)` on failure.
     pub fn put(self, other: T) -> Result<usize, KernelError> {
-        let access_range = Va::new(self.addr).ok_or(KernelError::BadAddress)?
-            ..Va::new(self.addr + core::mem::size_of::<T>()).ok_or(KernelError::BadAddress)?;
-        with_current(|th| {
-            let task = th
-                .task
-                .as_ref()
-                .expect("Try to call UserPtrWO::put() on the kernel thread.");
-            if task.access_ok(access_range, true) {
-                let target = self.addr as *mut T;
-                unsafe {
-                    // Safety: By calling access_ok, verifying `target` is valid, aligned, and
-                    // accessible.
-                    *target = other;
-                }
-                Ok(core::mem::size_of::<T>())
-            } else {
-                Err(KernelError::BadAddress)
-            }
-        })
+        copy_to_user(self.addr, other).map(|_| core::mem::size_of::<T>())
     }
 }
 
@@ -174,23 +237,12 @@ impl UserU8SliceRO {
     /// Returns `Ok(Vec<u8>)` containing the data if successful, otherwise
     /// `Err(KernelError::BadAddress)`.
     pub fn get(self) -> Result<Vec<u8>, KernelError> {
-        let access_range = Va::new(self.addr).ok_or(KernelError::BadAddress)?
-            ..Va::new(self.addr + self.len).ok_or(KernelError::BadAddress)?;
-        with_current(|th| {
-            let task = th
-                .task
-                .as_ref()
-                .expect("Try to call UserU8SliceRO::get() on the kernel thread.");
-            if task.access_ok(access_range, false) {
-                let mut result = Vec::new();
-                result.extend_from_slice(unsafe {
-                    core::slice::from_raw_parts(self.addr as *const u8, self.len)
-                });
-                Ok(result)
-            } else {
-                Err(KernelError::BadAddress)
-            }
-        })
+        check_user_range(self.addr, self.len, false)?;
+        let mut result = Vec::new();
+        result.extend_from_slice(unsafe {
+            core::slice::from_raw_parts(self.addr as *const u8, self.len)
+        });
+        Ok(result)
     }
 }
 
@@ -219,26 +271,11 @@ impl UserU8SliceWO {
     /// `Err(KernelError::BadAddress)` on failure.
     pub fn put(self, other: &[u8]) -> Result<usize, KernelError> {
         let size = self.len.min(other.len());
-        let access_range = Va::new(self.addr).ok_or(KernelError::BadAddress)?
-            ..Va::new(self.addr + self.len).ok_or(KernelError::BadAddress)?;
-        with_current(|th| {
-            let task = th
-                .task
-                .as_ref()
-                .expect("Try to call UserU8SliceWO::put() on the kernel thread.");
-            if task.access_ok(access_range, true) {
-                unsafe {
-                    core::ptr::copy_nonoverlapping(
-                        other[..size].as_ptr(),
-                        self.addr as *mut u8,
-                        size,
-                    );
-                }
-                Ok(size)
-            } else {
-                Err(KernelError::BadAddress)
-            }
-        })
+        check_user_range(self.addr, self.len, true)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(other[..size].as_ptr(), self.addr as *mut u8, size);
+        }
+        Ok(size)
     }
 }
 
@@ -267,24 +304,6 @@ impl UserCString {
     /// Returns `Some(String)` if successful, otherwise `None` if the operation
     /// fails.
     pub fn read(self) -> Result<String, KernelError> {
-        let mut ptr = self.addr;
-        let mut result = Vec::new();
-        // Iterate over the bytes to find the null-terminator (0x00).
-        // If the byte is 0, we've found the null-terminator.
-        loop {
-            match UserPtrRO::<u8>::new(ptr).get() {
-                Ok(0) => {
-                    return core::str::from_utf8(&result)
-                        .ok()
-                        .map(String::from)
-                        .ok_or(KernelError::InvalidArgument);
-                }
-                Ok(v) => {
-                    ptr += 1;
-                    result.push(v);
-                }
-                Err(e) => return Err(e),
-            }
-        }
+        strncpy_from_user(self.addr, usize::MAX)
     }
 }
@@ -1,11 +1,19 @@
 //! Task trait for interact with user process.
+//!
+//! This module also keeps the [`SymbolTable`] of the user program run by each
+//! thread, so that the kernel can name the functions of the user program when
+//! it reports a fault of the program.
 
-use crate::thread::kill_current_thread;
+use crate::{
+    spinlock::SpinLock,
+    thread::{kill_current_thread, with_current},
+};
 pub use abyss::x86_64::interrupt::PFErrorCode;
 use abyss::{
     addressing::{Pa, Va},
     interrupt::Registers,
 };
+use alloc::{string::String, sync::Arc, vec::Vec};
 use core::ops::Range;
 
 /// Represents a **task** executed by a thread.
@@ -42,6 +50,7 @@ pub trait Task {
                 },
                 cr2.into_usize(),
             );
+            crate::lang::panicking::print_user_backtrace();
             kill_current_thread();
         } else {
             panic!(
@@ -66,6 +75,16 @@ pub trait Task {
 
     /// Run a closure with physical address of the page table.
     fn with_page_table_pa(&self, _f: &fn(Pa)) {}
+
+    /// Returns the thread group (i.e., the process) of the task, if any.
+    ///
+    /// The [`GangScheduler`] dispatches the threads of a group together. A
+    /// thread without a group forms a group by itself.
+    ///
+    /// [`GangScheduler`]: crate::thread::scheduler::GangScheduler
+    fn thread_group(&self) -> Option<u64> {
+        None
+    }
 }
 
 impl Task for () {
@@ -73,3 +92,82 @@ impl Task for () {
         unreachable!()
     }
 }
+
+/// A function symbol of a user program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    /// The name of the function.
+    pub name: String,
+    /// The address of the first instruction of the function.
+    pub start: usize,
+    /// The size of the function in bytes, or 0 if unknown.
+    pub size: usize,
+}
+
+/// The function symbols of a user program.
+///
+/// The table is built by the loader from the symbol table of the ELF
+/// executable, and is used to resolve an instruction address of the user
+/// program to `function+offset`.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    /// The symbols, sorted by their start addresses.
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// Creates a table from the given symbols.
+    pub fn new(mut symbols: Vec<Symbol>) -> Self {
+        symbols.sort_by_key(|symbol| symbol.start);
+        Self { symbols }
+    }
+
+    /// Resolves `addr` to the function that contains it.
+    ///
+    /// A symbol of unknown size is taken to extend to the next symbol, if any.
+    ///
+    /// # Returns
+    /// - `Some((name, offset))`: The name of the function and the offset of
+    ///   `addr` from its start.
+    /// - `None`: If no function contains `addr`.
+    pub fn resolve(&self, addr: usize) -> Option<(&str, usize)> {
+        let index = self.symbols.partition_point(|symbol| symbol.start <= addr);
+        let symbol = self.symbols.get(index.checked_sub(1)?)?;
+        let end = match symbol.size {
+            0 => self
+                .symbols
+                .get(index)
+                .map_or(symbol.start + 1, |next| next.start),
+            size => symbol.start + size,
+        };
+        (addr < end).then_some((symbol.name.as_str(), addr - symbol.start))
+    }
+}
+
+/// Sets the symbol table of the user program run by the current thread.
+///
+/// The table is inherited by the threads spawned from the current thread
+/// afterward. `None` clears the table, e.g., for a stripped executable.
+pub fn set_user_symbols(symbols: Option<Arc<SymbolTable>>) {
+    with_current(|th| {
+        let mut guard = th.user_symbols.lock();
+        *guard = symbols;
+        guard.unlock();
+    })
+}
+
+/// Returns the symbol table of the user program run by the current thread.
+pub fn user_symbols() -> Option<Arc<SymbolTable>> {
+    with_current(|th| user_symbols_of(&th.user_symbols))
+}
+
+// Returns the table in `slot` without waiting, as this is also called while
+// panicking.
+pub(crate) fn user_symbols_of(
+    slot: &SpinLock<Option<Arc<SymbolTable>>>,
+) -> Option<Arc<SymbolTable>> {
+    let guard = slot.try_lock().ok()?;
+    let symbols = guard.clone();
+    guard.unlock();
+    symbols
+}
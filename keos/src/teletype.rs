@@ -3,8 +3,25 @@
 //! This module provides a trait [`Teletype`] that defines an interface for
 //! reading from and writing to a teletype device, such as a serial port.
 //! The [`Serial`] struct implements this interface for x86_64 systems.
+//!
+//! Input is delivered either as raw bytes or line by line, as selected by
+//! [`TtyMode`]. In the canonical mode, a `read` waits for a whole line, which
+//! can be edited with backspace while it is typed, and the typed characters
+//! are echoed back to the output. The ANSI escape sequences in the output can
+//! be stripped with [`AnsiParser`], as selected by [`AnsiFilter`].
+//!
+//! Besides the serial console, the kernel provides [`NR_TTY`] virtual
+//! consoles, [`Tty`], each with its own input and output buffers. They are
+//! exposed to user programs as the device files `/dev/tty0`, `/dev/tty1`, and
+//! so on, so that separate processes can be attached to separate consoles.
 
-use crate::{KernelError, spinlock::SpinLock, thread::with_current};
+use crate::{
+    KernelError,
+    channel::{Receiver, Sender, channel},
+    spinlock::SpinLock,
+    thread::{TtyState, with_current},
+};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 
 /// The `Teletype` trait represents a generic character-based input/output
 /// device.
@@ -37,6 +54,134 @@ pub trait Teletype {
     fn read(&mut self, data: &mut [u8]) -> Result<usize, KernelError>;
 }
 
+/// The input mode of a teletype.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum TtyMode {
+    /// Bytes are delivered to `read` as they arrive, without echo.
+    #[default]
+    Raw,
+    /// Input is buffered until a newline, and delivered line by line.
+    ///
+    /// Backspace (`0x08`) or delete (`0x7f`) erases the last character of the
+    /// line being typed. Typed characters are echoed, and an erase is echoed
+    /// as `"\x08 \x08"`. A line longer than the buffer of `read` is
+    /// delivered over several reads. At the end of input, the partial line is
+    /// delivered as is.
+    Canonical,
+}
+
+/// Sets the input mode of the teletype of the current thread.
+///
+/// The mode applies to the teletype hooked for the current thread and the
+/// threads spawned from it. The serial console always edits its input line
+/// by line, so the mode has no effect when no teletype is hooked.
+pub fn set_mode(mode: TtyMode) {
+    with_hooked(|tty| tty.mode = mode)
+}
+
+/// How the ANSI escape sequences written to a teletype are handled.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum AnsiFilter {
+    /// Escape sequences are removed from the output.
+    ///
+    /// The control sequences (`ESC [ ... final`), such as the color and the
+    /// cursor movement escapes, the operating system commands
+    /// (`ESC ] ... BEL`), and the other two-byte escapes (`ESC c`) are
+    /// dropped. A sequence split across writes is still recognized.
+    #[default]
+    Strip,
+    /// Escape sequences are kept in the output as written.
+    Keep,
+}
+
+/// The state of an [`AnsiParser`].
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+enum AnsiState {
+    /// Outside of an escape sequence.
+    #[default]
+    Ground,
+    /// After `ESC`.
+    Escape,
+    /// Inside of a control sequence, after `ESC [`.
+    Csi,
+    /// Inside of an operating system command, after `ESC ]`.
+    Osc,
+    /// After `ESC` inside of an operating system command.
+    OscEscape,
+}
+
+/// A parser of the ANSI escape sequences in a stream of characters.
+///
+/// The parser keeps its state between calls of [`AnsiParser::filter`], so a
+/// stream can be fed piece by piece.
+#[derive(Clone, Debug, Default)]
+pub struct AnsiParser {
+    state: AnsiState,
+}
+
+impl AnsiParser {
+    /// Creates a parser outside of any escape sequence.
+    pub const fn new() -> Self {
+        Self {
+            state: AnsiState::Ground,
+        }
+    }
+
+    /// Feeds `s` to the parser, pushing the characters that are not a part of
+    /// an escape sequence to `out`.
+    ///
+    /// A non-ASCII character aborts the escape sequence it appears in, and is
+    /// pushed as is.
+    pub fn filter(&mut self, s: &str, out: &mut String) {
+        for c in s.chars() {
+            self.state = match (self.state, c) {
+                (_, c) if !c.is_ascii() => {
+                    out.push(c);
+                    AnsiState::Ground
+                }
+                (AnsiState::Ground, '\x1b') => AnsiState::Escape,
+                (AnsiState::Ground, c) => {
+                    out.push(c);
+                    AnsiState::Ground
+                }
+                (AnsiState::Escape, '[') => AnsiState::Csi,
+                (AnsiState::Escape, ']') => AnsiState::Osc,
+                (AnsiState::Escape, _) => AnsiState::Ground,
+                (AnsiState::Csi, '\x40'..='\x7e') => AnsiState::Ground,
+                (AnsiState::Csi, _) => AnsiState::Csi,
+                (AnsiState::Osc, '\x07') => AnsiState::Ground,
+                (AnsiState::Osc, '\x1b') => AnsiState::OscEscape,
+                (AnsiState::Osc, _) => AnsiState::Osc,
+                (AnsiState::OscEscape, _) => AnsiState::Ground,
+            }
+        }
+    }
+}
+
+/// Sets how the ANSI escape sequences written to the teletype of the current
+/// thread are handled.
+///
+/// The output captured by the teletype hooked for grading strips the escape
+/// sequences by default, so that the comparison of the output does not
+/// depend on its formatting. The serial console passes the sequences to the
+/// terminal, so the filter has no effect when no teletype is hooked.
+pub fn set_ansi_filter(filter: AnsiFilter) {
+    with_hooked(|tty| tty.ansi_filter = filter)
+}
+
+/// Runs `f` on the teletype hooked for the current thread, if any.
+fn with_hooked(f: impl FnOnce(&mut TtyState)) {
+    with_current(|th| {
+        let guard = th.tty_hook.lock();
+        if let Some(tty) = guard.as_ref() {
+            let mut tty = tty.lock();
+            f(&mut tty);
+            tty.unlock();
+        }
+        guard.unlock();
+    })
+}
+
 /// A serial teletype interface for x86_64 systems.
 ///
 /// This struct provides a basic implementation of a serial TTY using the
@@ -184,3 +329,90 @@ impl Teletype for Serial {
         })
     }
 }
+
+/// The number of virtual consoles.
+pub const NR_TTY: usize = 2;
+
+/// The size of the input and output buffers of a virtual console, in bytes.
+pub const TTY_BUFFER_SIZE: usize = 0x1000;
+
+/// A virtual console.
+///
+/// A [`Tty`] has an input buffer, filled by the console side with
+/// [`Tty::input`] and read by the processes attached to it, and an output
+/// buffer, written by the processes and drained by the console side with
+/// [`Tty::take_output`]. The processes access the buffers through the ends
+/// returned by [`Tty::open_read`] and [`Tty::open_write`], which are shared
+/// by every process that opens the console.
+///
+/// As the console holds both ends of each buffer, reading an empty input
+/// buffer waits for the next input instead of returning the end of file, and
+/// writing to a full output buffer waits until it is drained.
+pub struct Tty {
+    input: (Sender<u8>, Receiver<u8>),
+    output: (Sender<u8>, Receiver<u8>),
+}
+
+/// The virtual consoles created so far, indexed by their numbers.
+static TTYS: SpinLock<BTreeMap<usize, Arc<Tty>>> = SpinLock::new(BTreeMap::new());
+
+impl Tty {
+    /// Returns the virtual console numbered `index`, creating it on the first
+    /// access.
+    ///
+    /// # Returns
+    /// - `Some(tty)`: The console, if `index` is less than [`NR_TTY`].
+    /// - `None`: Otherwise.
+    pub fn get(index: usize) -> Option<Arc<Tty>> {
+        if index >= NR_TTY {
+            return None;
+        }
+        let mut ttys = TTYS.lock();
+        let tty = ttys
+            .entry(index)
+            .or_insert_with(|| {
+                Arc::new(Tty {
+                    input: channel(TTY_BUFFER_SIZE),
+                    output: channel(TTY_BUFFER_SIZE),
+                })
+            })
+            .clone();
+        ttys.unlock();
+        Some(tty)
+    }
+
+    /// Returns the virtual console of the device file at `path`.
+    ///
+    /// The consoles are named `/dev/tty0` to `/dev/tty{NR_TTY - 1}`.
+    pub fn from_path(path: &str) -> Option<Arc<Tty>> {
+        let index = path.strip_prefix("/dev/tty")?;
+        // Only the canonical decimal numbers name a console.
+        if !index.bytes().all(|b| b.is_ascii_digit()) || (index.len() > 1 && index.starts_with('0'))
+        {
+            return None;
+        }
+        Tty::get(index.parse().ok()?)
+    }
+
+    /// Opens the input buffer of the console for a process to read.
+    pub fn open_read(&self) -> Receiver<u8> {
+        self.input.1.clone()
+    }
+
+    /// Opens the output buffer of the console for a process to write.
+    pub fn open_write(&self) -> Sender<u8> {
+        self.output.0.clone()
+    }
+
+    /// Feeds `data` into the input buffer of the console, as if it were typed.
+    pub fn input(&self, data: &[u8]) {
+        for b in data {
+            let _ = self.input.0.send(*b);
+        }
+    }
+
+    /// Drains the output buffer of the console.
+    pub fn take_output(&self) -> Vec<u8> {
+        self.output.1.try_iter().collect()
+    }
+}
@@ -5,26 +5,38 @@
 //! An executing kernel consists of a collection of threads,
 //! each with their own stack and local state. Threads can be named, and
 //! provide some built-in support for low-level synchronization.
+pub mod priority;
 pub mod scheduler;
+pub mod stack_guard;
 
-use crate::{KernelError, mm::page_table::load_pt, spinlock::SpinLock, task::Task};
+use crate::{KernelError, mm::page_table::load_pt, spinlock::SpinLock, task::Task, time::Timer};
 use abyss::{
     addressing::{Kva, Pa},
     dev::x86_64::apic::{IPIDest, Mode},
     interrupt::InterruptGuard,
     x86_64::intrinsics::cpuid,
 };
-use alloc::{boxed::Box, collections::btree_map::BTreeMap, string::String, sync::Arc};
+use alloc::{boxed::Box, collections::btree_map::BTreeMap, string::String, sync::Arc, vec::Vec};
 use core::{
     arch::{asm, naked_asm},
+    marker::PhantomData,
     panic::Location,
-    sync::atomic::{AtomicI32, AtomicU64, Ordering},
+    sync::atomic::{AtomicBool, AtomicI32, AtomicU8, AtomicU64, AtomicUsize, Ordering},
 };
 
 /// Size of each thread's stack.
 pub const STACK_SIZE: usize = 0x100000;
 /// Thread magic to detect stack overflow.
 pub const THREAD_MAGIC: usize = 0xdeadbeefcafebabe;
+/// The exit code of a thread canceled by [`JoinHandle::cancel`]
+/// (`-ECANCELED`).
+pub const CANCELED: i32 = -125;
+
+// The states of the cancellation request of a thread.
+// A cancellation is requested, but not delivered yet.
+const CANCEL_PENDING: u8 = 1;
+// The thread is woken up from parking to exit by the cancellation.
+const CANCEL_WOKEN: u8 = 2;
 
 /// The Thread stack.
 ///
@@ -43,6 +55,12 @@ pub(crate) struct ThreadStack {
     _pin: core::marker::PhantomPinned,
 }
 
+impl Drop for ThreadStack {
+    fn drop(&mut self) {
+        stack_guard::unprotect(self);
+    }
+}
+
 /// A possible state of the thread.
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub enum ThreadState {
@@ -62,12 +80,65 @@ pub(crate) struct TtyState {
     input: &'static [u8],
     idx: usize,
     output: String,
+    pub(crate) mode: crate::teletype::TtyMode,
+    // The line being delivered in the canonical mode.
+    line: Vec<u8>,
+    pub(crate) ansi_filter: crate::teletype::AnsiFilter,
+    ansi: crate::teletype::AnsiParser,
+}
+
+impl TtyState {
+    // Edits the next line of the input, echoing the typed characters.
+    fn edit_line(&mut self) {
+        while let Some(&b) = self.input.get(self.idx) {
+            self.idx += 1;
+            match b {
+                0x08 | 0x7f => {
+                    // Erase all the bytes of the last character.
+                    if !self.line.is_empty() {
+                        let start = self.line.iter().rposition(|b| b & 0xc0 != 0x80);
+                        self.line.truncate(start.unwrap_or(0));
+                        self.output.push_str("\x08 \x08");
+                    }
+                }
+                b'\n' => {
+                    self.line.push(b);
+                    self.output.push('\n');
+                    break;
+                }
+                _ => {
+                    self.line.push(b);
+                    self.echo_last();
+                }
+            }
+        }
+    }
+
+    // Echoes the character ending at the last byte of the line as typed, once
+    // all the bytes of a multi-byte UTF-8 character are typed. A byte that
+    // does not form a character is echoed as U+FFFD, as the output holds
+    // only UTF-8 text.
+    fn echo_last(&mut self) {
+        let start = self
+            .line
+            .iter()
+            .rposition(|b| b & 0xc0 != 0x80)
+            .unwrap_or(0);
+        match core::str::from_utf8(&self.line[start..]) {
+            Ok(c) => self.output.push_str(c),
+            Err(e) if e.error_len().is_none() => (),
+            Err(_) => self.output.push(char::REPLACEMENT_CHARACTER),
+        }
+    }
 }
 
 impl crate::teletype::Teletype for TtyState {
     fn write(&mut self, data: &[u8]) -> Result<usize, KernelError> {
-        if let Ok(s) = String::from_utf8(data.to_vec()) {
-            self.output.push_str(&s);
+        if let Ok(s) = core::str::from_utf8(data) {
+            match self.ansi_filter {
+                crate::teletype::AnsiFilter::Strip => self.ansi.filter(s, &mut self.output),
+                crate::teletype::AnsiFilter::Keep => self.output.push_str(s),
+            }
             Ok(data.len())
         } else {
             Err(KernelError::InvalidArgument)
@@ -75,6 +146,16 @@ impl crate::teletype::Teletype for TtyState {
     }
 
     fn read(&mut self, data: &mut [u8]) -> Result<usize, KernelError> {
+        if self.mode == crate::teletype::TtyMode::Canonical {
+            // Deliver the rest of the current line before editing the next one.
+            if self.line.is_empty() {
+                self.edit_line();
+            }
+            let read_bytes = self.line.len().min(data.len());
+            data[..read_bytes].copy_from_slice(&self.line[..read_bytes]);
+            self.line.drain(..read_bytes);
+            return Ok(read_bytes);
+        }
         let read_bytes = self.input.len().wrapping_sub(self.idx).min(data.len());
         data[..read_bytes].copy_from_slice(&self.input[self.idx..self.idx + read_bytes]);
         self.idx += read_bytes;
@@ -85,6 +166,20 @@ impl crate::teletype::Teletype for TtyState {
 static EXIT_CODE_TABLE: SpinLock<BTreeMap<u64, Arc<AtomicU64>>> = SpinLock::new(BTreeMap::new());
 static THREAD_STATE_TABLE: SpinLock<BTreeMap<u64, Arc<SpinLock<ThreadState>>>> =
     SpinLock::new(BTreeMap::new());
+static SCHED_STATS_TABLE: SpinLock<BTreeMap<u64, Arc<SchedCounters>>> =
+    SpinLock::new(BTreeMap::new());
+// Parked threads, which are woken up either by their [`ParkHandle`] or by a
+// kill signal.
+static PARKED_TABLE: SpinLock<BTreeMap<u64, ParkedThread>> = SpinLock::new(BTreeMap::new());
+
+// Robust locks held by each thread, with the functions releasing them when
+// the thread exits. See [`register_robust_lock`].
+type RobustLocks = Vec<(usize, fn(usize, u64))>;
+static ROBUST_LOCK_TABLE: SpinLock<BTreeMap<u64, RobustLocks>> = SpinLock::new(BTreeMap::new());
+
+struct ParkedThread(Box<Thread>);
+
+unsafe impl Send for ParkedThread {}
 
 #[unsafe(no_mangle)]
 #[doc(hidden)]
@@ -99,6 +194,8 @@ pub fn kill_current_thread() -> ! {
 pub unsafe fn __do_exit(exit_code: i32) -> ! {
     let _ = abyss::interrupt::InterruptGuard::new();
     with_current(|th| {
+        release_robust_locks(th.tid);
+
         let mut et = EXIT_CODE_TABLE.lock();
         et.remove(&th.tid);
         et.unlock();
@@ -107,6 +204,12 @@ pub unsafe fn __do_exit(exit_code: i32) -> ! {
         tst.remove(&th.tid);
         tst.unlock();
 
+        let mut sst = SCHED_STATS_TABLE.lock();
+        sst.remove(&th.tid);
+        sst.unlock();
+
+        priority::unregister(th.tid);
+
         th.exit_status
             .store(0x8000_0000_0000_0000 | (exit_code as u64), Ordering::SeqCst);
         let mut state = th.state.lock();
@@ -130,6 +233,11 @@ pub(crate) fn __check_for_signal() {
 }
 
 /// Kill the thread by specified TID (Thread ID).
+///
+/// The thread exits with `exit_code` when it is scheduled next time. A parked
+/// thread is woken up to observe the signal, so that a thread sleeping on a
+/// lock (e.g., a mutex held by another killed thread) also exits. Killing an
+/// already killed thread keeps the first exit code.
 pub fn kill_by_tid(tid: u64, exit_code: i32) -> Result<(), KernelError> {
     let et = EXIT_CODE_TABLE.lock();
     let Some(exit_status) = et.get(&tid) else {
@@ -139,12 +247,26 @@ pub fn kill_by_tid(tid: u64, exit_code: i32) -> Result<(), KernelError> {
     let exit_status = exit_status.clone();
     et.unlock();
 
-    debug_assert_eq!(
-        exit_status.load(Ordering::SeqCst) & 0x4000_0000_FFFF_FFFF,
-        0
-    );
+    if exit_status
+        .compare_exchange(
+            0,
+            0x4000_0000_0000_0000 | exit_code as u32 as u64,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        )
+        .is_err()
+    {
+        return Ok(());
+    }
 
-    exit_status.store(0x4000_0000_0000_0000 | exit_code as u64, Ordering::SeqCst);
+    // The signal must be stored before looking up the parked threads; See
+    // `Scheduler::park_thread`.
+    let mut parked = PARKED_TABLE.lock();
+    let th = parked.remove(&tid);
+    parked.unlock();
+    if let Some(ParkedThread(th)) = th {
+        ParkHandle::wake(th);
+    }
 
     unsafe {
         abyss::dev::x86_64::apic::send_ipi(IPIDest::AllExcludingSelf, Mode::Fixed(0x7f));
@@ -153,6 +275,51 @@ pub fn kill_by_tid(tid: u64, exit_code: i32) -> Result<(), KernelError> {
     Ok(())
 }
 
+/// Register a robust lock held by the current thread.
+///
+/// If the thread exits before calling [`unregister_robust_lock`] with the same
+/// `lock`, e.g., when it is killed by [`kill_by_tid`] or by a fault while
+/// holding the lock, `release` is called with `lock` and the tid of the
+/// thread on its exit path. This allows a lock to hand itself over to its
+/// waiters instead of being held forever by an exited thread.
+///
+/// `lock` is an opaque identifier, usually the address of the lock. The lock
+/// must stay valid until it is unregistered or released.
+pub fn register_robust_lock(lock: usize, release: fn(usize, u64)) {
+    let tid = Current::get_tid();
+    let mut table = ROBUST_LOCK_TABLE.lock();
+    table.entry(tid).or_default().push((lock, release));
+    table.unlock();
+}
+
+/// Unregister a robust lock registered by the current thread with
+/// [`register_robust_lock`].
+pub fn unregister_robust_lock(lock: usize) {
+    let tid = Current::get_tid();
+    let mut table = ROBUST_LOCK_TABLE.lock();
+    if let Some(locks) = table.get_mut(&tid) {
+        if let Some(pos) = locks.iter().rposition(|(l, _)| *l == lock) {
+            locks.remove(pos);
+        }
+        if locks.is_empty() {
+            table.remove(&tid);
+        }
+    }
+    table.unlock();
+}
+
+// Releases the robust locks still held by the exiting thread `tid`.
+fn release_robust_locks(tid: u64) {
+    let mut table = ROBUST_LOCK_TABLE.lock();
+    let locks = table.remove(&tid);
+    table.unlock();
+    // Release the locks outside of the table, as `release` takes the internal
+    // lock of each robust lock.
+    for (lock, release) in locks.into_iter().flatten().rev() {
+        release(lock, tid);
+    }
+}
+
 /// Get specified thread's [`ThreadState`] by TID (Thread ID).
 pub fn get_state_by_tid(tid: u64) -> Result<ThreadState, KernelError> {
     let tst = THREAD_STATE_TABLE.lock();
@@ -171,6 +338,56 @@ pub fn get_state_by_tid(tid: u64) -> Result<ThreadState, KernelError> {
     Ok(result)
 }
 
+/// Get specified thread's [`SchedStats`] by TID (Thread ID).
+pub fn get_sched_stats_by_tid(tid: u64) -> Result<SchedStats, KernelError> {
+    let sst = SCHED_STATS_TABLE.lock();
+    let result = sst.get(&tid).map(|counters| counters.snapshot());
+    sst.unlock();
+    result.ok_or(KernelError::InvalidArgument)
+}
+
+/// Scheduling statistics of a thread.
+///
+/// A context switch is *voluntary* if the thread gives up the CPU by itself,
+/// e.g., by parking on a lock or sleeping, or by yielding. It is
+/// *involuntary* if the thread is preempted by the scheduler on a timer tick.
+///
+/// It is reported to the user program as-is by the `sched_stats` system call,
+/// so the layout must match the `struct sched_stats` of the user library.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct SchedStats {
+    /// Timer ticks on which the thread was running.
+    pub run_ticks: u64,
+    /// The number of times the thread is scheduled.
+    pub scheduled: u64,
+    /// The number of voluntary context switches.
+    pub voluntary_switches: u64,
+    /// The number of involuntary context switches.
+    pub involuntary_switches: u64,
+}
+
+// The counters of [`SchedStats`], updated on the context switch and the timer
+// tick.
+#[derive(Default)]
+pub(crate) struct SchedCounters {
+    run_ticks: AtomicU64,
+    scheduled: AtomicU64,
+    voluntary_switches: AtomicU64,
+    involuntary_switches: AtomicU64,
+}
+
+impl SchedCounters {
+    fn snapshot(&self) -> SchedStats {
+        SchedStats {
+            run_ticks: self.run_ticks.load(Ordering::Relaxed),
+            scheduled: self.scheduled.load(Ordering::Relaxed),
+            voluntary_switches: self.voluntary_switches.load(Ordering::Relaxed),
+            involuntary_switches: self.involuntary_switches.load(Ordering::Relaxed),
+        }
+    }
+}
+
 #[repr(C)]
 /// An thread abstraction.
 pub struct Thread {
@@ -196,8 +413,33 @@ pub struct Thread {
     pub interrupt_frame: SpinLock<*const abyss::interrupt::Registers>,
     #[doc(hidden)]
     pub task: Option<Box<dyn Task>>,
+    /// Page cache accounting of the thread.
+    pub(crate) cache_accounting: crate::fs::CacheAccounting,
+    // The number of held `PreemptGuard`s.
+    pub(crate) preempt_count: AtomicUsize,
+    // Whether a reschedule is deferred while the preemption is disabled.
+    pub(crate) resched_pending: AtomicBool,
+    // The number of critical sections the thread is in.
+    pub(crate) critical_count: AtomicUsize,
+    // The state of the cancellation request; See `JoinHandle::cancel`.
+    pub(crate) cancel: Arc<AtomicU8>,
+    // The end of the time slice extension granted in the critical section, or
+    // zero if none is granted.
+    pub(crate) extension_deadline: AtomicU64,
+    // Whether the thread is handling the timer tick.
+    pub(crate) in_timer_tick: AtomicBool,
+    // Whether the thread is woken up from parking and has not run since.
+    pub(crate) boosted: AtomicBool,
+    // The scheduling statistics of the thread.
+    pub(crate) sched_stats: Arc<SchedCounters>,
+    // The effective priority of the thread; See `priority`.
+    pub(crate) priority: Arc<AtomicU8>,
     // Grading utils.
     pub(crate) tty_hook: SpinLock<Option<Arc<SpinLock<TtyState>>>>,
+    // Whether the system calls of the thread are traced.
+    pub(crate) syscall_trace: AtomicBool,
+    // The symbol table of the user program run by the thread.
+    pub(crate) user_symbols: SpinLock<Option<Arc<crate::task::SymbolTable>>>,
     pub(crate) allocations: SpinLock<Option<BTreeMap<Kva, &'static Location<'static>>>>,
 }
 
@@ -211,6 +453,7 @@ impl Thread {
         let tid = TID.fetch_add(1, Ordering::SeqCst);
         let mut stack: Box<ThreadStack> = unsafe { Box::new_uninit().assume_init() };
         stack.magic = THREAD_MAGIC;
+        stack_guard::protect(&stack);
 
         let exit_status = Arc::new(AtomicU64::new(0));
         let mut et = EXIT_CODE_TABLE.lock();
@@ -222,6 +465,11 @@ impl Thread {
         tst.insert(tid, state.clone());
         tst.unlock();
 
+        let sched_stats = Arc::new(SchedCounters::default());
+        let mut sst = SCHED_STATS_TABLE.lock();
+        sst.insert(tid, sched_stats.clone());
+        sst.unlock();
+
         Box::new(Self {
             sp: 0,
             stack,
@@ -232,6 +480,16 @@ impl Thread {
             interrupt_frame: SpinLock::new(core::ptr::null()),
             running_cpu: Arc::new(AtomicI32::new(-1)),
             task: None,
+            cache_accounting: crate::fs::CacheAccounting::default(),
+            preempt_count: AtomicUsize::new(0),
+            resched_pending: AtomicBool::new(false),
+            critical_count: AtomicUsize::new(0),
+            cancel: Arc::new(AtomicU8::new(0)),
+            extension_deadline: AtomicU64::new(0),
+            in_timer_tick: AtomicBool::new(false),
+            boosted: AtomicBool::new(false),
+            sched_stats,
+            priority: priority::register(tid),
             tty_hook: SpinLock::new(
                 __with_current(|th| {
                     let guard = th.tty_hook.lock();
@@ -241,10 +499,52 @@ impl Thread {
                 })
                 .unwrap_or(None),
             ),
+            syscall_trace: AtomicBool::new(
+                __with_current(|th| th.syscall_trace.load(Ordering::SeqCst)).unwrap_or(false),
+            ),
+            user_symbols: SpinLock::new(
+                __with_current(|th| crate::task::user_symbols_of(&th.user_symbols)).unwrap_or(None),
+            ),
             allocations: SpinLock::new(None),
         })
     }
 
+    /// Returns `true` if the thread is woken up from parking and has not run
+    /// since.
+    ///
+    /// A thread waking up from a condition variable, a pipe, or a sleep is
+    /// typically an interactive one, which runs briefly and parks again. A
+    /// preemptive scheduler honors this tag in [`Scheduler::push_to_queue`] by
+    /// placing the thread ahead of the others, e.g., at the front of the run
+    /// queue, so that it runs on the next reschedule instead of waiting for a
+    /// full round of CPU-bound threads. The tag is cleared when the thread
+    /// starts running, so the boost lasts for a single time slice.
+    ///
+    /// The built-in FIFO and gang schedulers ignore the tag: without a
+    /// preemption on every time slice, threads waking up each other would
+    /// stay ahead of the others indefinitely.
+    ///
+    /// [`Scheduler::push_to_queue`]: scheduler::Scheduler::push_to_queue
+    pub fn is_boosted(&self) -> bool {
+        self.boosted.load(Ordering::SeqCst)
+    }
+
+    /// Returns the effective priority of the thread.
+    ///
+    /// It is the base priority of the thread, raised by the priorities
+    /// donated to it while it holds a resource that higher-priority threads
+    /// wait for. See [`priority`] for details.
+    pub fn priority(&self) -> u8 {
+        self.priority.load(Ordering::SeqCst)
+    }
+
+    // Whether the thread holds neither a `PreemptGuard` nor a sleeping mutex,
+    // so that it can exit by the cancellation when it parks.
+    pub(crate) fn at_safe_point(&self) -> bool {
+        self.preempt_count.load(Ordering::SeqCst) == 0
+            && self.critical_count.load(Ordering::SeqCst) == 0
+    }
+
     #[doc(hidden)]
     pub fn track_alloc(&self) {
         let mut guard = self.allocations.lock();
@@ -314,6 +614,16 @@ impl Thread {
         ThreadPinGuard::new()
     }
 
+    /// Disable the preemption of current thread while leaving interrupts on.
+    ///
+    /// When [`PreemptGuard`] is dropped, the preemption is enabled again.
+    pub fn preempt_disable() -> PreemptGuard {
+        with_current(|th| th.preempt_count.fetch_add(1, Ordering::SeqCst));
+        PreemptGuard {
+            _not_send: PhantomData,
+        }
+    }
+
     #[doc(hidden)]
     pub fn hook_stdin(&self, b: &'static [u8]) {
         let mut guard = self.tty_hook.lock();
@@ -324,6 +634,10 @@ impl Thread {
                 input: b,
                 idx: 0,
                 output: String::new(),
+                mode: crate::teletype::TtyMode::Raw,
+                line: Vec::new(),
+                ansi_filter: crate::teletype::AnsiFilter::Strip,
+                ansi: crate::teletype::AnsiParser::new(),
             })));
             guard.unlock();
         }
@@ -346,6 +660,44 @@ impl Thread {
 /// A RAII implementation of the thread pinning.
 pub type ThreadPinGuard = InterruptGuard;
 
+/// A RAII implementation of the preemption disabling.
+///
+/// [`ThreadPinGuard`] keeps the current thread on the CPU by disabling
+/// interrupts, which also delays the device interrupts. [`PreemptGuard`] is a
+/// lighter alternative: interrupts are still serviced, but a reschedule
+/// requested by the timer interrupt is deferred until the last guard of the
+/// thread is dropped, at which point the thread yields the CPU.
+///
+/// The guards are counted per thread, so they can be nested. A thread must
+/// not park while holding a guard.
+///
+/// This structure is created by [`Thread::preempt_disable`].
+pub struct PreemptGuard {
+    _not_send: PhantomData<*const ()>,
+}
+
+impl PreemptGuard {
+    /// Returns `true` if a reschedule is deferred because the preemption is
+    /// disabled.
+    pub fn is_resched_pending(&self) -> bool {
+        with_current(|th| th.resched_pending.load(Ordering::SeqCst))
+    }
+}
+
+impl Drop for PreemptGuard {
+    fn drop(&mut self) {
+        // A deferred reschedule under a lock is left to the next timer tick.
+        let resched = with_current(|th| {
+            th.preempt_count.fetch_sub(1, Ordering::SeqCst) == 1
+                && !InterruptGuard::is_guarded()
+                && th.resched_pending.swap(false, Ordering::SeqCst)
+        });
+        if resched {
+            scheduler::scheduler().reschedule();
+        }
+    }
+}
+
 /// A handle to join thread.
 pub struct JoinHandle
 where
@@ -355,6 +707,7 @@ where
     pub tid: u64,
     exit_status: Arc<AtomicU64>,
     running_cpu: Arc<AtomicI32>,
+    cancel: Arc<AtomicU8>,
 }
 
 impl JoinHandle {
@@ -364,20 +717,65 @@ impl JoinHandle {
             tid: th.tid,
             exit_status: th.exit_status.clone(),
             running_cpu: th.running_cpu.clone(),
+            cancel: th.cancel.clone(),
         }
     }
 
     /// Join this handle and returns exit code.
+    ///
+    /// The joining thread donates its priority to the thread until it exits
+    /// (see [`priority`]).
     pub fn join(self) -> i32 {
+        priority::donate(self.tid, Arc::as_ptr(&self.exit_status) as usize);
         loop {
             let v = self.exit_status.load(Ordering::SeqCst);
             if v >= 0x8000_0000_0000_0000 {
+                priority::withdraw();
                 return v as i32;
             }
             crate::scheduler().reschedule();
         }
     }
 
+    /// Request the cancellation of the thread.
+    ///
+    /// Unlike [`kill_by_tid`], which terminates the thread on the next
+    /// reschedule wherever it is, the cancellation is cooperative: the thread
+    /// exits with [`CANCELED`] only at a safe point, where it holds no lock, no
+    /// [`PreemptGuard`], and no sleeping mutex (see
+    /// [`Current::enter_critical_section`]). The safe points are the entry of a
+    /// system call, [`Current::test_cancel`], and the blocking points, i.e.,
+    /// [`Current::park_with`] and everything built on it such as waiting for
+    /// a mutex or a condition variable and [`Current::sleep`].
+    ///
+    /// A thread parked at a safe point is woken up to exit immediately.
+    /// Otherwise, the request is kept pending until the thread reaches the
+    /// next safe point. Since the canceled thread never returns from the
+    /// parking, the [`ParkHandle`] given to the primitive it was blocked on is
+    /// left stale; unparking it later does nothing. Requesting the
+    /// cancellation again has no effect.
+    pub fn cancel(&self) {
+        if self
+            .cancel
+            .compare_exchange(0, CANCEL_PENDING, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return;
+        }
+        // The request must be stored before looking up the parked threads;
+        // See `Scheduler::park_thread`.
+        let mut parked = PARKED_TABLE.lock();
+        let th = match parked.get(&self.tid) {
+            Some(ParkedThread(th)) if th.at_safe_point() => parked.remove(&self.tid),
+            _ => None,
+        };
+        parked.unlock();
+        if let Some(ParkedThread(th)) = th {
+            th.cancel.store(CANCEL_WOKEN, Ordering::SeqCst);
+            ParkHandle::wake(th);
+        }
+    }
+
     /// Get scheudled cpu id of the underlying thread.
     ///
     /// If the thread is not runnig, returns None.
@@ -393,25 +791,61 @@ unsafe impl Send for JoinHandle {}
 unsafe impl Sync for JoinHandle {}
 
 /// A handle that represent the parked thread.
+///
+/// The parked thread itself is kept in a global table, so that a kill signal
+/// can wake it up even if the handle is held by others. In that case,
+/// [`ParkHandle::unpark`] does nothing because the thread is exiting.
 pub struct ParkHandle {
-    pub(crate) th: Box<Thread>,
+    tid: u64,
 }
 
 impl ParkHandle {
     pub(crate) fn new_for(th: Box<Thread>) -> Self {
-        Self { th }
+        let tid = th.tid;
+        let mut parked = PARKED_TABLE.lock();
+        parked.insert(tid, ParkedThread(th));
+        parked.unlock();
+        Self { tid }
+    }
+
+    /// Returns the tid of the parked thread.
+    pub fn tid(&self) -> u64 {
+        self.tid
     }
 
     /// Consume the handle and unpark the underlying thread.
     pub fn unpark(self) {
+        self.try_unpark();
+    }
+
+    /// Consume the handle and unpark the underlying thread.
+    ///
+    /// Returns `false` if the thread is not parked anymore, i.e., it is
+    /// already woken up by a kill signal, a cancellation, or a deadline. A
+    /// wait queue handing an event over to one of its waiters can then pass
+    /// it to the next one instead of losing it.
+    pub fn try_unpark(self) -> bool {
+        let mut parked = PARKED_TABLE.lock();
+        let th = parked.remove(&self.tid);
+        parked.unlock();
+        if let Some(ParkedThread(th)) = th {
+            Self::wake(th);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn wake(th: Box<Thread>) {
         // Wait until context switch is finished.
-        while self.th.running_cpu.load(Ordering::SeqCst) != -1 {
+        while th.running_cpu.load(Ordering::SeqCst) != -1 {
             core::hint::spin_loop()
         }
-        let mut state = self.th.state.lock();
+        let mut state = th.state.lock();
         *state = ThreadState::Runnable;
         state.unlock();
-        scheduler::scheduler().push_to_queue(self.th);
+        th.boosted.store(true, Ordering::SeqCst);
+        scheduler::scheduler().push_to_queue(th);
     }
 }
 
@@ -466,6 +900,21 @@ unsafe extern "C" fn finish_context_switch(prev: &'static mut Thread) {
             result
         };
 
+        // A running thread switched out on a timer tick is preempted. The
+        // others park or yield by themselves.
+        let counters = &prev.sched_stats;
+        match prev_state {
+            ThreadState::Running if prev.in_timer_tick.load(Ordering::SeqCst) => {
+                counters
+                    .involuntary_switches
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+            ThreadState::Running | ThreadState::Parked => {
+                counters.voluntary_switches.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => (),
+        }
+
         let mut prev_interrupt_frame = prev.interrupt_frame.lock();
         *prev_interrupt_frame = abyss::x86_64::kernel_gs::current().interrupt_frame;
         prev_interrupt_frame.unlock();
@@ -488,9 +937,11 @@ unsafe extern "C" fn finish_context_switch(prev: &'static mut Thread) {
         with_current(|th| {
             let mut state = th.state.lock();
             if *state != ThreadState::Idle {
-                *state = ThreadState::Running
+                *state = ThreadState::Running;
+                th.sched_stats.scheduled.fetch_add(1, Ordering::Relaxed);
             }
             state.unlock();
+            th.boosted.store(false, Ordering::SeqCst);
 
             __check_for_signal();
 
@@ -501,6 +952,7 @@ unsafe extern "C" fn finish_context_switch(prev: &'static mut Thread) {
             abyss::x86_64::segmentation::SegmentTable::update_tss(
                 th.stack.as_mut() as *mut _ as usize + STACK_SIZE,
             );
+            stack_guard::flush(&th.stack);
             th.running_cpu.store(cpuid() as i32, Ordering::SeqCst);
 
             if let Some(task) = th.task.as_mut() {
@@ -540,6 +992,10 @@ impl Current {
     /// Run a function `f` with [`ParkHandle`] for current thread, and then park
     /// the current thread.
     pub fn park_with(f: impl FnOnce(ParkHandle)) {
+        assert!(
+            with_current(|th| th.preempt_count.load(Ordering::SeqCst)) == 0,
+            "Try to park a thread while the preemption is disabled."
+        );
         let p = abyss::interrupt::InterruptGuard::new();
         with_current(|th| {
             f(unsafe { scheduler::scheduler().park_thread(th).unwrap() });
@@ -552,6 +1008,116 @@ impl Current {
         );
 
         scheduler::scheduler().reschedule();
+
+        // Woken up by the cancellation, which is only delivered while no lock
+        // nor sleeping mutex is held.
+        if with_current(|th| th.cancel.load(Ordering::SeqCst)) == CANCEL_WOKEN {
+            Self::exit(CANCELED);
+        }
+    }
+
+    /// Run a function `f` with [`ParkHandle`] for current thread, and then park
+    /// the current thread until it is unparked or the tick count reaches the
+    /// `deadline` (see [`now_ticks`]).
+    ///
+    /// Returns `false` if the thread is woken up by the deadline. In this case,
+    /// the [`ParkHandle`] given to `f` is stale, and the caller must remove
+    /// it from where `f` stored it.
+    ///
+    /// [`now_ticks`]: crate::time::now_ticks
+    pub fn park_with_deadline(deadline: u64, f: impl FnOnce(ParkHandle)) -> bool {
+        let tid = Self::get_tid();
+        let mut timer = None;
+        Self::park_with(|th| {
+            // Schedule the timer after the thread is marked as parked, so that
+            // the timer never misses it.
+            timer = Some(Timer::at(deadline, move || ParkHandle { tid }.unpark()));
+            f(th);
+        });
+        // The cancellation waits for the callback in flight, so that it never
+        // wakes up the thread parked again for another reason.
+        timer.unwrap().cancel()
+    }
+
+    /// Put the current thread to sleep for `ticks` timer ticks (see
+    /// [`now_ticks`]).
+    ///
+    /// The thread is parked, so it does not consume the CPU while sleeping,
+    /// and is woken up by the timer interrupt on the first tick after the
+    /// interval. Sleeping for zero ticks yields the CPU to another runnable
+    /// thread instead.
+    ///
+    /// [`now_ticks`]: crate::time::now_ticks
+    pub fn sleep(ticks: u64) {
+        if ticks == 0 {
+            scheduler::scheduler().reschedule();
+            return;
+        }
+        let deadline = crate::time::now_ticks().saturating_add(ticks);
+        // Only the timer or a kill signal wakes up the thread, so the handle
+        // is not kept.
+        while crate::time::now_ticks() < deadline {
+            Self::park_with_deadline(deadline, |_| ());
+        }
+    }
+
+    /// Mark that the current thread entered a critical section, such as the
+    /// one protected by a mutex.
+    ///
+    /// Preempting a thread in a critical section stalls every thread waiting
+    /// for it to leave the section. To avoid this, when the time slice of the
+    /// thread expires in a critical section, the reschedule requested by the
+    /// timer tick is deferred once by up to [`CRITICAL_SECTION_EXTENSION`]
+    /// ticks. If the thread is still in a critical section after the
+    /// extension, it is preempted as usual, and no further extension is granted
+    /// until it leaves all of its critical sections.
+    ///
+    /// Critical sections can be nested, and each call must be paired with
+    /// [`Current::exit_critical_section`].
+    ///
+    /// [`CRITICAL_SECTION_EXTENSION`]: scheduler::CRITICAL_SECTION_EXTENSION
+    pub fn enter_critical_section() {
+        with_current(|th| th.critical_count.fetch_add(1, Ordering::SeqCst));
+    }
+
+    /// Mark that the current thread left a critical section.
+    ///
+    /// When the thread leaves its outermost critical section after being
+    /// granted an extension, it yields the CPU, as its time slice is already
+    /// expired.
+    pub fn exit_critical_section() {
+        let resched = with_current(|th| {
+            let count = th.critical_count.load(Ordering::SeqCst);
+            if count == 0 {
+                return false;
+            }
+            th.critical_count.store(count - 1, Ordering::SeqCst);
+            // A deferred reschedule under a lock or with the preemption
+            // disabled is left to them.
+            count == 1
+                && th.extension_deadline.swap(0, Ordering::SeqCst) != 0
+                && th.preempt_count.load(Ordering::SeqCst) == 0
+                && !InterruptGuard::is_guarded()
+                && th.resched_pending.swap(false, Ordering::SeqCst)
+        });
+        if resched {
+            scheduler::scheduler().reschedule();
+        }
+    }
+
+    /// Exit the current thread with [`CANCELED`] if its cancellation is
+    /// requested and it is at a safe point (see [`JoinHandle::cancel`]).
+    ///
+    /// Call this in a long-running loop that never blocks, to make it
+    /// cancelable.
+    pub fn test_cancel() {
+        let cancel = with_current(|th| th.cancel.load(Ordering::SeqCst) != 0 && th.at_safe_point());
+        if cancel
+            && abyss::interrupt::InterruptState::current() == abyss::interrupt::InterruptState::On
+            && !InterruptGuard::is_guarded()
+        {
+            Self::exit(CANCELED);
+        }
     }
 
     /// Exit the current thread with `exit_code`.
@@ -569,6 +1135,24 @@ impl Current {
     pub fn get_tid() -> u64 {
         with_current(|th| th.tid)
     }
+
+    /// Set the base priority of the current thread (see [`priority`]).
+    ///
+    /// The effective priority of the thread stays raised while it holds the
+    /// donations of higher priorities.
+    ///
+    /// # Panics
+    /// Panics if `priority` is greater than [`PRI_MAX`].
+    ///
+    /// [`PRI_MAX`]: priority::PRI_MAX
+    pub fn set_priority(priority: u8) {
+        assert!(
+            priority <= priority::PRI_MAX,
+            "The priority must be at most {}.",
+            priority::PRI_MAX
+        );
+        priority::set_base(priority);
+    }
 }
 
 /// Run a function `f` with current thread as an argument.
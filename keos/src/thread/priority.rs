@@ -0,0 +1,227 @@
+//! Thread priorities and the priority donation.
+//!
+//! Each thread has a *base* priority between [`PRI_MIN`] and [`PRI_MAX`]. A
+//! thread starts with [`PRI_DEFAULT`], and changes its own base priority with
+//! [`Current::set_priority`]. A priority scheduler, such as the
+//! [`PriorityScheduler`], runs the threads with the highest *effective*
+//! priority (see [`Thread::priority`]) first.
+//!
+//! ## Priority Donation
+//!
+//! A thread blocked on a resource held by a lower-priority thread waits as
+//! long as the holder does not run. If threads of a medium priority keep the
+//! holder off the CPU, the high-priority thread waits for all of them, which
+//! is called a **priority inversion**.
+//!
+//! To bound the inversion, a thread about to block on a resource *donates*
+//! its effective priority to the holder with [`donate`]. The effective
+//! priority of a thread is the maximum of its base priority and the
+//! priorities donated to it, so the holder runs ahead of the medium-priority
+//! threads until it releases the resource. If the holder is blocked on
+//! another resource, the donation is passed along to the holder of that one,
+//! up to [`MAX_DONATION_DEPTH`] threads (*nested donation*).
+//!
+//! A donation is made for a `key` identifying the resource, usually its
+//! address. When the holder releases the resource, it drops the donations
+//! made for the resource with [`revoke`], and returns to the priority given
+//! by its base priority and the donations for the other resources it holds.
+//! A donor that stops waiting without the resource, e.g., on a timeout,
+//! takes its donation back with [`withdraw`]. The donations made by or to an
+//! exiting thread are dropped on its exit.
+//!
+//! [`Current::set_priority`]: super::Current::set_priority
+//! [`PriorityScheduler`]: super::scheduler::PriorityScheduler
+
+use super::Current;
+use crate::{KernelError, spinlock::SpinLock};
+use alloc::{collections::btree_map::BTreeMap, sync::Arc};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// The lowest priority of a thread.
+pub const PRI_MIN: u8 = 0;
+/// The priority of a thread when it is created.
+pub const PRI_DEFAULT: u8 = 31;
+/// The highest priority of a thread.
+pub const PRI_MAX: u8 = 63;
+
+/// The maximum number of threads a donation is passed along.
+pub const MAX_DONATION_DEPTH: usize = 8;
+
+struct PriorityState {
+    base: u8,
+    // The effective priority, shared with the `Thread` for the schedulers.
+    effective: Arc<AtomicU8>,
+    // The donations received, by the tid of the donor: the key of the
+    // resource and the donated priority.
+    donations: BTreeMap<u64, (usize, u8)>,
+    // The thread receiving the donation of this thread, and the key.
+    donee: Option<(u64, usize)>,
+}
+
+static PRIORITY_TABLE: SpinLock<BTreeMap<u64, PriorityState>> = SpinLock::new(BTreeMap::new());
+
+// Registers the thread `tid` with the default priority, and returns its
+// effective priority.
+pub(crate) fn register(tid: u64) -> Arc<AtomicU8> {
+    let effective = Arc::new(AtomicU8::new(PRI_DEFAULT));
+    let mut table = PRIORITY_TABLE.lock();
+    table.insert(
+        tid,
+        PriorityState {
+            base: PRI_DEFAULT,
+            effective: effective.clone(),
+            donations: BTreeMap::new(),
+            donee: None,
+        },
+    );
+    table.unlock();
+    effective
+}
+
+// Drops the donations made by and to the exiting thread `tid`.
+pub(crate) fn unregister(tid: u64) {
+    let mut table = PRIORITY_TABLE.lock();
+    if let Some(state) = table.remove(&tid) {
+        if let Some((donee, _)) = state.donee {
+            take_back(&mut table, tid, donee);
+        }
+        for donor in state.donations.keys() {
+            if let Some(donor) = table.get_mut(donor) {
+                donor.donee = None;
+            }
+        }
+    }
+    table.unlock();
+}
+
+// Recomputes the effective priority of `tid`, and passes a change along the
+// chain of the donations.
+fn refresh(table: &mut BTreeMap<u64, PriorityState>, mut tid: u64) {
+    for _ in 0..MAX_DONATION_DEPTH {
+        let Some(state) = table.get(&tid) else {
+            return;
+        };
+        let effective = state
+            .donations
+            .values()
+            .map(|(_, priority)| *priority)
+            .fold(state.base, u8::max);
+        if state.effective.swap(effective, Ordering::SeqCst) == effective {
+            return;
+        }
+        let Some((donee, _)) = state.donee else {
+            return;
+        };
+        match table
+            .get_mut(&donee)
+            .and_then(|state| state.donations.get_mut(&tid))
+        {
+            Some((_, priority)) => *priority = effective,
+            None => return,
+        }
+        tid = donee;
+    }
+}
+
+// Removes the donation of `donor` from `donee`.
+fn take_back(table: &mut BTreeMap<u64, PriorityState>, donor: u64, donee: u64) {
+    if let Some(state) = table.get_mut(&donee)
+        && state.donations.remove(&donor).is_some()
+    {
+        refresh(table, donee);
+    }
+}
+
+/// Donates the effective priority of the current thread to the thread
+/// `holder`, which holds the resource identified by `key`.
+///
+/// Call this before the current thread blocks on the resource. A thread
+/// donates to a single thread at a time; the previous donation of the
+/// current thread, if any, is taken back. Donating to the current thread
+/// itself or to an exited thread does nothing.
+pub fn donate(holder: u64, key: usize) {
+    let tid = Current::get_tid();
+    if holder == tid {
+        return;
+    }
+    let mut table = PRIORITY_TABLE.lock();
+    let Some(state) = table.get_mut(&tid) else {
+        table.unlock();
+        return;
+    };
+    let previous = state.donee.take();
+    let priority = state.effective.load(Ordering::SeqCst);
+    if let Some((donee, _)) = previous {
+        take_back(&mut table, tid, donee);
+    }
+    if let Some(state) = table.get_mut(&holder) {
+        state.donations.insert(tid, (key, priority));
+        refresh(&mut table, holder);
+        if let Some(state) = table.get_mut(&tid) {
+            state.donee = Some((holder, key));
+        }
+    }
+    table.unlock();
+}
+
+/// Takes back the donation of the current thread, if any.
+///
+/// Call this when the current thread stops waiting for the resource it
+/// donated for. It does nothing if the holder already revoked the donation
+/// with [`revoke`].
+pub fn withdraw() {
+    let tid = Current::get_tid();
+    let mut table = PRIORITY_TABLE.lock();
+    if let Some((donee, _)) = table.get_mut(&tid).and_then(|state| state.donee.take()) {
+        take_back(&mut table, tid, donee);
+    }
+    table.unlock();
+}
+
+/// Drops the donations made to the current thread for the resource
+/// identified by `key`.
+///
+/// Call this when the current thread releases the resource. The effective
+/// priority of the thread is lowered to what its base priority and the
+/// remaining donations give.
+pub fn revoke(key: usize) {
+    let tid = Current::get_tid();
+    let mut table = PRIORITY_TABLE.lock();
+    let mut donors = alloc::vec::Vec::new();
+    if let Some(state) = table.get_mut(&tid) {
+        state.donations.retain(|donor, (k, _)| {
+            if *k == key {
+                donors.push(*donor);
+            }
+            *k != key
+        });
+    }
+    for donor in donors {
+        if let Some(state) = table.get_mut(&donor) {
+            state.donee = None;
+        }
+    }
+    refresh(&mut table, tid);
+    table.unlock();
+}
+
+// Sets the base priority of the current thread.
+pub(crate) fn set_base(priority: u8) {
+    let tid = Current::get_tid();
+    let mut table = PRIORITY_TABLE.lock();
+    if let Some(state) = table.get_mut(&tid) {
+        state.base = priority;
+        refresh(&mut table, tid);
+    }
+    table.unlock();
+}
+
+/// Get the effective priority of the thread `tid`.
+pub fn get_priority_by_tid(tid: u64) -> Result<u8, KernelError> {
+    let table = PRIORITY_TABLE.lock();
+    let result = table
+        .get(&tid)
+        .map(|state| state.effective.load(Ordering::SeqCst));
+    table.unlock();
+    result.ok_or(KernelError::InvalidArgument)
+}
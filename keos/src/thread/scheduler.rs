@@ -1,9 +1,15 @@
 //! Thread scheduler
 
-use super::{ParkHandle, STACK_SIZE, THREAD_MAGIC, Thread, ThreadStack, ThreadState};
+use super::{
+    CANCEL_PENDING, CANCEL_WOKEN, PARKED_TABLE, ParkHandle, ParkedThread, STACK_SIZE, THREAD_MAGIC,
+    Thread, ThreadStack, ThreadState,
+};
 use abyss::spinlock::SpinLock;
-use alloc::boxed::Box;
-use core::{arch::asm, sync::atomic::AtomicBool};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicBool, AtomicU8, AtomicU32, AtomicU64, Ordering},
+};
 
 /// A trait for a thread scheduler.
 ///
@@ -34,7 +40,10 @@ pub trait Scheduler {
     /// Push a thread `th` into scheduling queue.
     ///
     /// This method adds the specified thread to the queue of threads waiting to
-    /// be scheduled.
+    /// be scheduled. A preemptive scheduler may place a thread just woken up
+    /// from parking ahead of the others (see [`Thread::is_boosted`]); a
+    /// non-preemptive one must not, as two threads waking up each other would
+    /// then run forever ahead of the rest.
     ///
     /// # Arguments
     ///
@@ -78,6 +87,333 @@ static FIFO: Fifo = Fifo {
     runqueue: SpinLock::new(alloc::collections::VecDeque::new()),
 };
 
+/// A scheduler making pseudo-random decisions from a seed.
+///
+/// [`RandomScheduler`] picks the next thread to run uniformly at random among
+/// the runnable threads, and preempts the running thread at random timer
+/// ticks. Every decision is drawn from a single pseudo-random sequence
+/// determined by the seed, so that the same seed reproduces the same
+/// interleaving of the threads while different seeds shake out different
+/// interleavings. This makes the scheduler useful for reproducing concurrency
+/// bugs.
+///
+/// The decisions are reproducible only if they are made in the same order,
+/// i.e., the kernel runs on a single CPU (`MP=1`) and the workload does not
+/// depend on the wall-clock time.
+///
+/// The scheduler is selected with
+/// [`SystemConfigurationBuilder::set_scheduler`], typically seeded from the
+/// `sched_seed=` option of the kernel command line:
+///
+/// ```ignore
+/// config_builder.set_scheduler(RandomScheduler::from_cmdline());
+/// ```
+///
+/// [`SystemConfigurationBuilder::set_scheduler`]: crate::SystemConfigurationBuilder::set_scheduler
+pub struct RandomScheduler {
+    // Runnable threads and the state of the pseudo-random sequence.
+    inner: SpinLock<(VecDeque<Box<Thread>>, u64)>,
+}
+
+unsafe impl core::marker::Sync for RandomScheduler {}
+
+impl RandomScheduler {
+    /// Create a new [`RandomScheduler`] seeded with `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            inner: SpinLock::new((VecDeque::new(), seed)),
+        }
+    }
+
+    /// Create a new [`RandomScheduler`] seeded with the `sched_seed=` option of
+    /// the kernel command line.
+    ///
+    /// The seed is 0 if the option is not given.
+    ///
+    /// # Panics
+    /// Panics if the option is not a decimal number.
+    pub fn from_cmdline() -> Self {
+        let seed = crate::cmdline::get("sched_seed")
+            .map(|seed| {
+                seed.parse()
+                    .expect("`sched_seed` must be a decimal number.")
+            })
+            .unwrap_or(0);
+        Self::new(seed)
+    }
+
+    // Draws the next number of the sequence (SplitMix64).
+    fn next(state: &mut u64) -> u64 {
+        *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl Scheduler for RandomScheduler {
+    fn next_to_run(&self) -> Option<Box<Thread>> {
+        let mut guard = self.inner.lock();
+        let (runqueue, state) = &mut *guard;
+        let th = if runqueue.is_empty() {
+            None
+        } else {
+            let idx = (Self::next(state) % runqueue.len() as u64) as usize;
+            runqueue.swap_remove_back(idx)
+        };
+        guard.unlock();
+        th
+    }
+
+    fn push_to_queue(&self, th: Box<Thread>) {
+        let mut guard = self.inner.lock();
+        guard.0.push_back(th);
+        guard.unlock();
+    }
+
+    fn timer_tick(&self) {
+        let mut guard = self.inner.lock();
+        let preempt = Self::next(&mut guard.1).is_multiple_of(2);
+        guard.unlock();
+        if preempt {
+            scheduler().reschedule();
+        }
+    }
+}
+
+/// A scheduler dispatching the threads of a thread group together.
+///
+/// The threads of a parallel program often wait for each other, e.g., on a
+/// lock or a barrier. If they run at different times, a thread holding a lock
+/// may be switched out while the others spin or sleep on it. **Gang
+/// scheduling** (or co-scheduling) avoids this by running the threads of a
+/// group at the same time across the CPUs.
+///
+/// [`GangScheduler`] divides the time into **epochs** of a fixed number of
+/// timer ticks. Every CPU reschedules its running thread on an epoch boundary.
+/// The first CPU picking a thread in a new epoch chooses the groups (see
+/// [`Task::thread_group`]) to run in the epoch: taking the groups in the order
+/// of their first thread in the run queue, it chooses each group whose
+/// runnable threads fit in the CPUs left, so that a group runs as a whole.
+/// The CPUs then pick the threads of the chosen groups first.
+///
+/// A thread of a chosen group that is parked is simply left out, and a CPU
+/// with no thread of the chosen groups to run picks the oldest runnable thread
+/// instead of idling. Therefore, a thread blocked individually never stalls
+/// its group nor the other CPUs.
+///
+/// The scheduler is selected with
+/// [`SystemConfigurationBuilder::set_scheduler`]:
+///
+/// ```ignore
+/// config_builder.set_scheduler(GangScheduler::new(10));
+/// ```
+///
+/// [`Task::thread_group`]: crate::task::Task::thread_group
+/// [`SystemConfigurationBuilder::set_scheduler`]: crate::SystemConfigurationBuilder::set_scheduler
+pub struct GangScheduler {
+    // The length of an epoch in timer ticks.
+    epoch: u64,
+    inner: SpinLock<GangState>,
+    // The epoch in which each CPU picked its running thread.
+    dispatched: [AtomicU64; abyss::MAX_CPU],
+}
+
+struct GangState {
+    runqueue: VecDeque<Box<Thread>>,
+    // The epoch for which `gangs` are chosen.
+    epoch: u64,
+    // The groups chosen to run in the epoch.
+    gangs: Vec<u64>,
+}
+
+unsafe impl core::marker::Sync for GangScheduler {}
+
+impl GangScheduler {
+    /// Create a new [`GangScheduler`] whose epoch is `epoch` timer ticks.
+    ///
+    /// # Panics
+    /// Panics if `epoch` is zero.
+    pub fn new(epoch: u64) -> Self {
+        assert!(epoch > 0, "The epoch must be at least a tick.");
+        Self {
+            epoch,
+            inner: SpinLock::new(GangState {
+                runqueue: VecDeque::new(),
+                epoch: u64::MAX,
+                gangs: Vec::new(),
+            }),
+            dispatched: [const { AtomicU64::new(u64::MAX) }; abyss::MAX_CPU],
+        }
+    }
+
+    // The group of `th`. The tid of a thread without a group is used as its
+    // own group, like the process id of a single-threaded process.
+    fn group_of(th: &Thread) -> u64 {
+        th.task
+            .as_ref()
+            .and_then(|task| task.thread_group())
+            .unwrap_or(th.tid)
+    }
+
+    // Chooses the groups that fit in the CPUs, in the order of the run queue.
+    // The first group is always chosen, even if it does not fit.
+    fn choose(runqueue: &VecDeque<Box<Thread>>) -> Vec<u64> {
+        let mut groups: Vec<(u64, usize)> = Vec::new();
+        for th in runqueue {
+            let group = Self::group_of(th);
+            match groups.iter_mut().find(|(g, _)| *g == group) {
+                Some((_, cnt)) => *cnt += 1,
+                None => groups.push((group, 1)),
+            }
+        }
+        let mut free = abyss::MAX_CPU;
+        let mut gangs = Vec::new();
+        for (group, cnt) in groups {
+            if cnt <= free || gangs.is_empty() {
+                free = free.saturating_sub(cnt);
+                gangs.push(group);
+            }
+        }
+        gangs
+    }
+}
+
+impl Scheduler for GangScheduler {
+    fn next_to_run(&self) -> Option<Box<Thread>> {
+        let epoch = crate::time::now_ticks() / self.epoch;
+        let mut guard = self.inner.lock();
+        let state = &mut *guard;
+        if state.epoch != epoch {
+            state.epoch = epoch;
+            state.gangs = Self::choose(&state.runqueue);
+        }
+        let idx = state
+            .runqueue
+            .iter()
+            .position(|th| state.gangs.contains(&Self::group_of(th)))
+            .unwrap_or(0);
+        let th = state.runqueue.remove(idx);
+        guard.unlock();
+        // An idle CPU picks a thread whenever it wakes up, so it does not have
+        // to be rescheduled on the epoch boundary either.
+        self.dispatched[abyss::x86_64::intrinsics::cpuid()].store(epoch, Ordering::Relaxed);
+        th
+    }
+
+    fn push_to_queue(&self, th: Box<Thread>) {
+        let mut guard = self.inner.lock();
+        guard.runqueue.push_back(th);
+        guard.unlock();
+    }
+
+    fn timer_tick(&self) {
+        let epoch = crate::time::now_ticks() / self.epoch;
+        if self.dispatched[abyss::x86_64::intrinsics::cpuid()].load(Ordering::Relaxed) != epoch {
+            scheduler().reschedule();
+        }
+    }
+}
+
+/// A preemptive scheduler running the threads of the highest priority.
+///
+/// [`PriorityScheduler`] always picks the runnable thread with the highest
+/// effective priority (see [`Thread::priority`]), and runs the threads of the
+/// same priority in a round-robin manner with a time slice of `quantum`
+/// ticks. A thread woken up from parking runs ahead of the others of its
+/// priority (see [`Thread::is_boosted`]).
+///
+/// On every timer tick, the running thread is preempted if a runnable thread
+/// has a higher priority, or if its time slice expired and a runnable thread
+/// has the same priority. As the priorities are checked on the tick, a raise
+/// of the priority by a donation (see [`priority`]) takes effect within a
+/// tick. A lower-priority thread never runs while a higher-priority one is
+/// runnable, so it starves unless the higher-priority threads block.
+///
+/// The scheduler is selected with
+/// [`SystemConfigurationBuilder::set_scheduler`]:
+///
+/// ```ignore
+/// config_builder.set_scheduler(PriorityScheduler::new(5));
+/// ```
+///
+/// [`priority`]: super::priority
+/// [`SystemConfigurationBuilder::set_scheduler`]: crate::SystemConfigurationBuilder::set_scheduler
+pub struct PriorityScheduler {
+    // The time slice in timer ticks.
+    quantum: u64,
+    runqueue: SpinLock<VecDeque<Box<Thread>>>,
+    // The remaining time slice of the running thread of each CPU.
+    remain: [AtomicU64; abyss::MAX_CPU],
+}
+
+unsafe impl core::marker::Sync for PriorityScheduler {}
+
+impl PriorityScheduler {
+    /// Create a new [`PriorityScheduler`] whose time slice is `quantum` timer
+    /// ticks.
+    ///
+    /// # Panics
+    /// Panics if `quantum` is zero.
+    pub fn new(quantum: u64) -> Self {
+        assert!(quantum > 0, "The quantum must be at least a tick.");
+        Self {
+            quantum,
+            runqueue: SpinLock::new(VecDeque::new()),
+            remain: [const { AtomicU64::new(0) }; abyss::MAX_CPU],
+        }
+    }
+}
+
+impl Scheduler for PriorityScheduler {
+    fn next_to_run(&self) -> Option<Box<Thread>> {
+        let mut guard = self.runqueue.lock();
+        // The first of the threads with the highest priority, so that the
+        // threads of the same priority run in turn.
+        let idx = guard
+            .iter()
+            .enumerate()
+            .max_by_key(|(idx, th)| (th.priority(), core::cmp::Reverse(*idx)))
+            .map(|(idx, _)| idx);
+        let th = idx.and_then(|idx| guard.remove(idx));
+        guard.unlock();
+        self.remain[abyss::x86_64::intrinsics::cpuid()].store(self.quantum, Ordering::Relaxed);
+        th
+    }
+
+    fn push_to_queue(&self, th: Box<Thread>) {
+        let mut guard = self.runqueue.lock();
+        if th.is_boosted() {
+            guard.push_front(th);
+        } else {
+            guard.push_back(th);
+        }
+        guard.unlock();
+    }
+
+    fn timer_tick(&self) {
+        let remain = &self.remain[abyss::x86_64::intrinsics::cpuid()];
+        let left = remain.load(Ordering::Relaxed);
+        remain.store(left.saturating_sub(1), Ordering::Relaxed);
+        let expired = left <= 1;
+        let Ok(current) = super::__with_current(|th| th.priority()) else {
+            return;
+        };
+        let guard = self.runqueue.lock();
+        let highest = guard.iter().map(|th| th.priority()).max();
+        guard.unlock();
+        match highest {
+            Some(highest) if highest > current || (expired && highest == current) => {
+                scheduler().reschedule()
+            }
+            // Nothing else to run; start a new time slice.
+            _ if expired => remain.store(self.quantum, Ordering::Relaxed),
+            _ => (),
+        }
+    }
+}
+
 /// Set the scheduler of the kernel.
 pub(crate) unsafe fn set_scheduler(t: impl Scheduler + 'static) {
     unsafe {
@@ -101,6 +437,45 @@ impl dyn Scheduler {
             !abyss::interrupt::InterruptGuard::is_guarded(),
             "Try to reschedule a thread while holding a lock."
         );
+        // A running thread with the preemption disabled keeps the CPU, and
+        // yields when the preemption is enabled. A running thread preempted by
+        // the timer in a critical section keeps the CPU until the extension
+        // expires. A thread that parks or exits is always switched out.
+        let deferred = super::__with_current(|th| {
+            let preempt_disabled = th.preempt_count.load(Ordering::SeqCst) != 0;
+            let critical = th.critical_count.load(Ordering::SeqCst) != 0
+                && th.in_timer_tick.load(Ordering::SeqCst);
+            if !preempt_disabled && !critical {
+                return false;
+            }
+            let state = th.state.lock();
+            let running = *state == ThreadState::Running;
+            state.unlock();
+            if !running {
+                return false;
+            }
+            if !preempt_disabled {
+                let now = crate::time::now_ticks();
+                let deadline = match th.extension_deadline.load(Ordering::SeqCst) {
+                    0 => {
+                        let deadline = now + CRITICAL_SECTION_EXTENSION;
+                        th.extension_deadline.store(deadline, Ordering::SeqCst);
+                        deadline
+                    }
+                    deadline => deadline,
+                };
+                if deadline <= now {
+                    th.resched_pending.store(false, Ordering::SeqCst);
+                    return false;
+                }
+            }
+            th.resched_pending.store(true, Ordering::SeqCst);
+            true
+        })
+        .unwrap_or(false);
+        if deferred {
+            return;
+        }
 
         unsafe { abyss::interrupt::InterruptState::disable() };
         match self.next_to_run() {
@@ -117,22 +492,121 @@ impl dyn Scheduler {
         unsafe { abyss::interrupt::InterruptState::enable() };
     }
 
+    /// Returns the utilization of the CPU `cpu` in percent.
+    ///
+    /// The utilization is the ratio of the timer ticks that the CPU spent
+    /// running threads other than the idle thread, over the last complete
+    /// window of [`UTILIZATION_WINDOW`] ticks.
+    ///
+    /// # Panics
+    /// Panics if `cpu` is not less than [`MAX_CPU`].
+    ///
+    /// [`MAX_CPU`]: crate::MAX_CPU
+    pub fn cpu_utilization(&self, cpu: usize) -> u8 {
+        CPU_USAGE[cpu].utilization.load(Ordering::Relaxed)
+    }
+
     /// Park a thread 'th' and return ParkHandle.
+    ///
+    /// A killed thread is not parked, so that it exits on the next
+    /// reschedule. The signal is checked while holding the table of the parked
+    /// threads, which [`kill_by_tid`] looks up after storing the signal.
+    /// Therefore, a thread killed while parking is always woken up.
+    ///
+    /// The same goes for a thread whose cancellation is requested, if it is at
+    /// a safe point; See [`JoinHandle::cancel`].
+    ///
+    /// [`kill_by_tid`]: super::kill_by_tid
+    /// [`JoinHandle::cancel`]: super::JoinHandle::cancel
     pub(crate) unsafe fn park_thread(&self, th: &mut Thread) -> Result<ParkHandle, ()> {
+        let tid = th.tid;
         let mut state = th.state.lock();
         if matches!(*state, ThreadState::Parked) {
+            state.unlock();
             return Err(());
         }
-        *state = ThreadState::Parked;
-        state.unlock();
-        unsafe {
-            Ok(ParkHandle {
-                th: Box::from_raw(th),
-            })
+        let mut parked = PARKED_TABLE.lock();
+        if th.cancel.load(Ordering::SeqCst) == CANCEL_PENDING && th.at_safe_point() {
+            th.cancel.store(CANCEL_WOKEN, Ordering::SeqCst);
+            state.unlock();
+        } else if th.exit_status.load(Ordering::SeqCst) & 0x4000_0000_0000_0000 == 0 {
+            *state = ThreadState::Parked;
+            state.unlock();
+            parked.insert(tid, ParkedThread(unsafe { Box::from_raw(th) }));
+        } else {
+            state.unlock();
         }
+        parked.unlock();
+        Ok(ParkHandle { tid })
+    }
+}
+
+/// The maximum number of timer ticks by which the preemption of a thread in a
+/// critical section is deferred.
+///
+/// See [`Current::enter_critical_section`] for details.
+///
+/// [`Current::enter_critical_section`]: super::Current::enter_critical_section
+pub const CRITICAL_SECTION_EXTENSION: u64 = 5;
+
+/// The number of timer ticks over which the utilization of a CPU is
+/// measured.
+pub const UTILIZATION_WINDOW: u32 = 100;
+
+// The tick accounting of a CPU, updated only by the CPU itself.
+struct CpuUsage {
+    // Ticks in the current window, and the busy ones among them.
+    ticks: AtomicU32,
+    busy: AtomicU32,
+    // The utilization over the last complete window.
+    utilization: AtomicU8,
+}
+
+static CPU_USAGE: [CpuUsage; abyss::MAX_CPU] = [const {
+    CpuUsage {
+        ticks: AtomicU32::new(0),
+        busy: AtomicU32::new(0),
+        utilization: AtomicU8::new(0),
+    }
+}; abyss::MAX_CPU];
+
+// Called on every timer interrupt to account the tick of the current CPU.
+pub(crate) fn account_tick() {
+    let cpu = abyss::x86_64::intrinsics::cpuid();
+    let idle = super::__with_current(|th| {
+        unsafe { IDLE[cpu].as_deref() }.is_some_and(|idle| core::ptr::eq(idle, th))
+    })
+    .unwrap_or(true);
+
+    let usage = &CPU_USAGE[cpu];
+    let busy = usage.busy.load(Ordering::Relaxed) + !idle as u32;
+    let ticks = usage.ticks.load(Ordering::Relaxed) + 1;
+    if ticks == UTILIZATION_WINDOW {
+        usage
+            .utilization
+            .store((busy * 100 / UTILIZATION_WINDOW) as u8, Ordering::Relaxed);
+        usage.busy.store(0, Ordering::Relaxed);
+        usage.ticks.store(0, Ordering::Relaxed);
+    } else {
+        usage.busy.store(busy, Ordering::Relaxed);
+        usage.ticks.store(ticks, Ordering::Relaxed);
     }
 }
 
+// Runs the timer tick handler `f` of the scheduler, marking the reschedules in
+// it as preemptions, and accounts the tick to the running thread. A thread
+// switched out in `f` clears the mark when it is switched back in.
+pub(crate) fn timer_tick(f: impl FnOnce()) {
+    // A tick that arrives before the CPU runs its first thread has no thread
+    // to account to, so the error of `__with_current` is ignored.
+    let _ = super::__with_current(|th| {
+        th.in_timer_tick.store(true, Ordering::SeqCst);
+        th.sched_stats.run_ticks.fetch_add(1, Ordering::Relaxed);
+    });
+    f();
+    let _ = super::__with_current(|th| th.in_timer_tick.store(false, Ordering::SeqCst));
+}
+
 pub(crate) static BOOT_DONE: AtomicBool = AtomicBool::new(false);
 const INIT: Option<Box<Thread>> = None;
 static mut IDLE: [Option<Box<Thread>>; abyss::MAX_CPU] = [INIT; abyss::MAX_CPU];
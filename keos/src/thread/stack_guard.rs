@@ -0,0 +1,189 @@
+//! Guard pages of the kernel thread stacks.
+//!
+//! Each kernel thread stack spans [`STACK_SIZE`] bytes, growing down from its
+//! top. Its first page holds the owning thread and the [`THREAD_MAGIC`], and
+//! the page right above it is left unmapped as a guard page. A thread that
+//! runs out of its stack hits the guard page before it corrupts the thread
+//! pointer, or the memory below the stack.
+//!
+//! The page fault on the guard page can not be handled on the exhausted
+//! stack; The CPU fails to push the exception frame, and raises a double
+//! fault instead. The double fault is handled on a dedicated stack of each
+//! CPU, where [`check_stack_overflow`] finds the thread from the faulting
+//! address and panics with a report of the overflow. The backtrace of the
+//! overflowing thread follows the panic message.
+//!
+//! Stacks live in the direct map of the kernel, which maps the physical
+//! memory with 2 MiB pages. The 2 MiB page covering a stack is split into
+//! 4 KiB pages on the first use, so that only the guard page is unmapped. The
+//! split tables are kept forever, as the other stacks or heap objects in the
+//! same 2 MiB region keep using them.
+
+use super::{STACK_SIZE, THREAD_MAGIC, Thread, ThreadStack};
+use crate::{
+    mm::{
+        ContigPages,
+        page_table::{Pde, PdeFlags, Pml4e, Pte, PteFlags},
+    },
+    spinlock::SpinLock,
+};
+use abyss::{
+    addressing::{Kva, Pa},
+    x86_64::Cr3,
+};
+use core::ops::Range;
+
+/// The offset of the guard page in a [`ThreadStack`].
+const GUARD_OFFSET: usize = 0x1000;
+
+/// Serializes the updates of the kernel page table.
+static KERNEL_PT_LOCK: SpinLock<()> = SpinLock::new(());
+
+/// Returns the page table entries of the table at `pa`.
+///
+/// # Safety
+/// `pa` must point to a page table.
+unsafe fn table<'a, T>(pa: Pa) -> &'a mut [T] {
+    unsafe { core::slice::from_raw_parts_mut(pa.into_kva().into_usize() as *mut T, 512) }
+}
+
+/// Returns the page directory entry of the kernel mapping `kva`.
+fn kernel_pde(root: Pa, kva: Kva) -> Option<&'static mut Pde> {
+    let kva = kva.into_usize();
+    let pml4e = unsafe { table::<Pml4e>(root) }.get_mut((kva >> 39) & 0x1ff)?;
+    let pdpe = pml4e.into_pdp_mut().ok()?.get_mut((kva >> 30) & 0x1ff)?;
+    pdpe.into_pd_mut().ok()?.get_mut((kva >> 21) & 0x1ff)
+}
+
+/// Returns the page table entry of the kernel mapping `kva`, splitting the
+/// 2 MiB page that maps it.
+fn kernel_pte_split(kva: Kva) -> Option<&'static mut Pte> {
+    let root = Pa::new(unsafe {
+        unsafe extern "C" {
+            static mut boot_pml4e: u64;
+        }
+        boot_pml4e as usize
+    })?;
+    let pde = kernel_pde(root, kva)?;
+    if pde.flags().contains(PdeFlags::PS) {
+        let pa = pde.pa()?;
+        let flags = PteFlags::from_bits_truncate((pde.flags() - PdeFlags::PS).bits());
+        let pt = ContigPages::new(0x1000)?;
+        let pt_pa = pt.kva().into_pa();
+        for (i, pte) in unsafe { table::<Pte>(pt_pa) }.iter_mut().enumerate() {
+            *pte = Pte((pa.into_usize() + i * 0x1000) | flags.bits());
+        }
+        // The table maps the same pages as the 2 MiB page does. Therefore,
+        // a stale TLB entry of the 2 MiB page is still valid.
+        *pde = Pde(pt_pa.into_usize() | (PdeFlags::P | PdeFlags::RW).bits());
+        core::mem::forget(pt);
+    }
+    pde.into_pt_mut()
+        .ok()?
+        .get_mut((kva.into_usize() >> 12) & 0x1ff)
+}
+
+/// Returns whether the kernel address `kva` is mapped in the current page
+/// table.
+pub fn is_mapped(kva: Kva) -> bool {
+    let Some(pde) = kernel_pde(Pa::new(Cr3::current().into_usize()).unwrap(), kva) else {
+        return false;
+    };
+    if !pde.flags().contains(PdeFlags::P) {
+        false
+    } else if pde.flags().contains(PdeFlags::PS) {
+        true
+    } else {
+        pde.into_pt()
+            .ok()
+            .and_then(|pt| pt.get((kva.into_usize() >> 12) & 0x1ff))
+            .is_some_and(|pte| pte.flags().contains(PteFlags::P))
+    }
+}
+
+/// Invalidates the TLB entry of the guard page of `stack` on the current CPU.
+///
+/// The guard page is unmapped without the TLB shootdown, as only the owner of
+/// the stack accesses it. Instead, the CPU that runs the thread drops the
+/// stale entry when the thread is switched in.
+#[inline]
+pub(crate) fn flush(stack: &ThreadStack) {
+    unsafe {
+        core::arch::asm!(
+            "invlpg [{0}]",
+            in(reg) stack as *const _ as usize + GUARD_OFFSET,
+            options(nostack)
+        );
+    }
+}
+
+/// Unmaps the guard page of `stack`.
+pub(crate) fn protect(stack: &ThreadStack) {
+    let guard = KERNEL_PT_LOCK.lock();
+    let kva = Kva::new(stack as *const _ as usize + GUARD_OFFSET).unwrap();
+    if let Some(pte) = kernel_pte_split(kva) {
+        unsafe {
+            pte.set_flags(pte.flags() - PteFlags::P);
+        }
+    }
+    guard.unlock();
+    flush(stack);
+}
+
+/// Maps back the guard page of `stack`, before the stack is freed.
+pub(crate) fn unprotect(stack: &ThreadStack) {
+    let guard = KERNEL_PT_LOCK.lock();
+    let kva = Kva::new(stack as *const _ as usize + GUARD_OFFSET).unwrap();
+    let pa = kva.into_pa();
+    // Stacks that are not protected, such as the boot stacks, are left as is.
+    if !is_mapped(kva)
+        && let Some(pte) = kernel_pte_split(kva)
+    {
+        pte.0 = pa.into_usize() | (pte.flags() | PteFlags::P).bits();
+    }
+    guard.unlock();
+}
+
+impl Thread {
+    /// Returns the address range of the guard page of the thread's stack.
+    pub fn stack_guard(&self) -> Range<Kva> {
+        let guard = Kva::new(self.stack.as_ref() as *const _ as usize + GUARD_OFFSET).unwrap();
+        guard..guard + 0x1000
+    }
+}
+
+/// Returns the stack whose guard page contains `addr`.
+fn guarded_stack(addr: usize) -> Option<&'static ThreadStack> {
+    let base = Kva::new(addr & !(STACK_SIZE - 1))?;
+    let guard = base + GUARD_OFFSET;
+    if !(guard..guard + 0x1000).contains(&Kva::new(addr)?) {
+        return None;
+    }
+    // The stack must be protected, and still be owned by a thread.
+    if !is_mapped(base) || is_mapped(guard) {
+        return None;
+    }
+    let stack = unsafe { &*(base.into_usize() as *const ThreadStack) };
+    (stack.magic == THREAD_MAGIC && !stack.thread.is_null()).then_some(stack)
+}
+
+/// Returns the id of the thread whose stack guard page contains `addr`.
+pub fn find_owner(addr: usize) -> Option<u64> {
+    guarded_stack(addr).map(|stack| unsafe { (*stack.thread).tid })
+}
+
+/// Panics if `fault_addr` is in the guard page of a thread stack.
+///
+/// This is called by the double fault handler with the faulting address.
+#[doc(hidden)]
+#[unsafe(no_mangle)]
+pub fn check_stack_overflow(fault_addr: usize) {
+    if let Some(stack) = guarded_stack(fault_addr) {
+        // The overflowing thread is still running, so it is alive.
+        let thread = unsafe { &*stack.thread };
+        panic!(
+            "kernel stack overflow in thread {} (tid {})",
+            thread.name, thread.tid
+        );
+    }
+}
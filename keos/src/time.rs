@@ -0,0 +1,162 @@
+//! Monotonic clock and timers.
+//!
+//! The local APIC timer of each CPU raises the interrupt vector 32 every
+//! millisecond. The timer interrupt on the bootstrap processor advances a
+//! global tick counter, which serves as the monotonic clock of the kernel:
+//! it starts from zero at boot and never goes backward.
+//!
+//! Every timeout in the kernel is expressed as a deadline on this clock (see
+//! [`now_ticks`]).
+//!
+//! ## Timers
+//!
+//! A [`Timer`] runs a callback in the timer interrupt on the first tick at or
+//! after its deadline. The pending timers are kept in a queue sorted by the
+//! deadline, and the timers with the same deadline fire in the order of
+//! creation. A pending timer can be cancelled with [`Timer::cancel`].
+//!
+//! The timeouts of the kernel are built on the timers: [`Poller::wait`]
+//! notifies itself on the deadline, and [`Current::park_with_deadline`] (which
+//! [`Current::sleep`] uses) unparks the thread on the deadline.
+//!
+//! Callbacks run in the interrupt context of the bootstrap processor with
+//! interrupts disabled, which delays every other work on the CPU. Therefore, a
+//! callback must be short and must never block: it may wake up a thread, but
+//! must not park or acquire a sleeping lock. For the same reason, at most
+//! [`MAX_CALLBACKS_PER_TICK`] callbacks run per tick, and the rest are deferred
+//! to the next tick.
+//!
+//! [`Poller::wait`]: crate::poll::Poller::wait
+//! [`Current::park_with_deadline`]: crate::thread::Current::park_with_deadline
+//! [`Current::sleep`]: crate::thread::Current::sleep
+
+use crate::spinlock::SpinLock;
+use abyss::x86_64::intrinsics::cpuid;
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// The number of timer ticks per second.
+pub const TICKS_PER_SEC: u64 = 1000;
+
+/// The length of a timer tick in nanoseconds.
+pub const NS_PER_TICK: u64 = 1_000_000_000 / TICKS_PER_SEC;
+
+/// The maximum number of timer callbacks run in a single tick.
+pub const MAX_CALLBACKS_PER_TICK: usize = 64;
+
+// Timer ticks since boot.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+// The pending timers, keyed by the deadline and the id of the timer.
+static TIMERS: SpinLock<BTreeMap<(u64, u64), Callback>> = SpinLock::new(BTreeMap::new());
+
+// Held while running the expired callbacks.
+static RUNNING: SpinLock<()> = SpinLock::new(());
+
+static NEXT_TIMER_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A reading of the monotonic clock.
+///
+/// It is reported to the user program as-is by the `clock_gettime` system
+/// call, so the layout must match the `struct clock_time` of the user library.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(C)]
+pub struct ClockTime {
+    /// Timer ticks since boot.
+    pub ticks: u64,
+    /// Nanoseconds since boot, estimated from the ticks.
+    pub ns: u64,
+}
+
+/// Returns the number of timer ticks (1ms) since boot.
+pub fn now_ticks() -> u64 {
+    TICKS.load(Ordering::Acquire)
+}
+
+/// Returns the current time of the monotonic clock.
+///
+/// The resolution of the clock is a timer tick, so [`ClockTime::ns`] is a
+/// multiple of [`NS_PER_TICK`].
+pub fn now() -> ClockTime {
+    let ticks = now_ticks();
+    ClockTime {
+        ticks,
+        ns: ticks.saturating_mul(NS_PER_TICK),
+    }
+}
+
+// Called on every timer interrupt.
+pub(crate) fn timer_tick() {
+    if cpuid() != 0 {
+        return;
+    }
+    let now = TICKS.fetch_add(1, Ordering::AcqRel) + 1;
+
+    let running = RUNNING.lock();
+    let mut guard = TIMERS.lock();
+    let mut expired = Vec::new();
+    while expired.len() < MAX_CALLBACKS_PER_TICK
+        && let Some(entry) = guard.first_entry()
+        && entry.key().0 <= now
+    {
+        expired.push(entry.remove());
+    }
+    guard.unlock();
+    // Run the callbacks without holding the queue, so that they can schedule
+    // new timers.
+    for callback in expired {
+        callback();
+    }
+    running.unlock();
+}
+
+/// A handle to a callback scheduled to run on a deadline.
+///
+/// Dropping the handle does not cancel the timer. See the
+/// [module-level documentation](self) for details.
+pub struct Timer {
+    deadline: u64,
+    id: u64,
+}
+
+impl Timer {
+    /// Schedules `callback` to run after `ticks` timer ticks.
+    pub fn after(ticks: u64, callback: impl FnOnce() + Send + 'static) -> Self {
+        Self::at(now_ticks().saturating_add(ticks), callback)
+    }
+
+    /// Schedules `callback` to run when the tick count reaches `deadline`.
+    ///
+    /// A deadline that has already passed fires on the next tick.
+    pub fn at(deadline: u64, callback: impl FnOnce() + Send + 'static) -> Self {
+        let id = NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed);
+        let mut guard = TIMERS.lock();
+        guard.insert((deadline, id), Box::new(callback));
+        guard.unlock();
+        Self { deadline, id }
+    }
+
+    /// Returns the deadline of the timer.
+    pub fn deadline(&self) -> u64 {
+        self.deadline
+    }
+
+    /// Cancels the timer.
+    ///
+    /// Returns `true` if the timer is cancelled before firing. Otherwise, the
+    /// callback has run, and it has finished when this function returns.
+    ///
+    /// This must not be called from a timer callback.
+    pub fn cancel(self) -> bool {
+        let mut guard = TIMERS.lock();
+        let cancelled = guard.remove(&(self.deadline, self.id)).is_some();
+        guard.unlock();
+        if !cancelled {
+            // Wait for the callback in flight.
+            RUNNING.lock().unlock();
+        }
+        cancelled
+    }
+}
@@ -39,9 +39,11 @@
 //! ## Do not assign large variables on stack
 //!
 //! In **KeOS**, each process/thread is assigned a fixed execution stack of
-//! `STACK_SIZE` bytes. While KeOS attempts to detect stack overflows, its
-//! detection is not perfect. **A stack overflow may lead to mysterious kernel
-//! panics.** To avoid this:
+//! `STACK_SIZE` bytes. The page near the bottom of each stack is left
+//! unmapped as a guard page, so a thread that runs out of its stack panics
+//! with `kernel stack overflow in thread <name> (tid <tid>)`, followed by the
+//! backtrace of the overflowing code. **The stack is not grown on overflow;
+//! the kernel panics.** To avoid this:
 //! - **Avoid declaring large data structures on the stack.**
 //! ```rust
 //! let v: [u8; 0x200000]; // ERROR: This may cause a stack overflow
@@ -76,3 +76,61 @@ pub fn cpuid_leaf_1() {
     vm.start_bsp().expect("Failed to start bsp.");
     assert_eq!(vm.join(), 0);
 }
+
+// Check that the vmx feature is hidden while the other features are kept,
+// and exit.
+global_asm!(
+    "cpuid_masking_start:",
+    "mov rax, 0x1",
+    "mov rcx, 0x0",
+    "cpuid",
+    // cpuid.1:ecx.VMX[bit 5]
+    "bt ecx, 5",
+    "jc cpuid_masking_failed",
+    // cpuid.1:edx.FPU[bit 0]
+    "bt edx, 0",
+    "jnc cpuid_masking_failed",
+    // The leaves without an entry are not affected.
+    "mov rax, 0x0",
+    "cpuid",
+    "cmp rbx, 0x756e6547",
+    "jne cpuid_masking_failed",
+    "mov rdi, 0",
+    "mov rax, 0",
+    "vmcall",
+    "cpuid_masking_failed:",
+    "mov rdi, 1",
+    "mov rax, 0",
+    "vmcall",
+    "cpuid_masking_end:"
+);
+pub fn cpuid_masking() {
+    let vm = VmBuilder::new(
+        NoEptVmState::new(unsafe {
+            unsafe extern "C" {
+                static cpuid_masking_start: u8;
+                static cpuid_masking_end: u8;
+            }
+            core::slice::from_raw_parts(
+                &cpuid_masking_start as *const u8,
+                &cpuid_masking_end as *const _ as usize - &cpuid_masking_start as *const _ as usize,
+            )
+        }),
+        1,
+    )
+    .expect("Failed to create vmbuilder.")
+    .finalize()
+    .expect("Failed to create vm.");
+    let host = unsafe { core::arch::x86_64::__cpuid(1) };
+    assert_ne!(host.ecx & (1 << 5), 0);
+    vm.set_cpuid(
+        1,
+        None,
+        host.eax,
+        host.ebx & 0x00ff_ffff,
+        host.ecx & !(1 << 5),
+        host.edx,
+    );
+    vm.start_bsp().expect("Failed to start bsp.");
+    assert_eq!(vm.join(), 0);
+}
@@ -0,0 +1,92 @@
+use alloc::sync::Arc;
+use kev::{
+    VmError,
+    vcpu::{GenericVCpuState, VCpuState, VmexitResult},
+    vm::VmState,
+    vm_control::*,
+    vmcs::ActiveVmcs,
+};
+use kev_project1::no_ept_vm::{Error, NoEptVcpuState, NoEptVmState};
+
+/// A [`NoEptVmState`] whose vmexits are first given to the `hook`.
+///
+/// The `hook` returns `None` to let the vm handle the vmexit as usual.
+pub struct InterceptVmState<H> {
+    vm: NoEptVmState,
+    hook: Arc<H>,
+}
+
+impl<H> InterceptVmState<H>
+where
+    H: Fn(&mut GenericVCpuState) -> Option<Result<VmexitResult, VmError>> + Send + Sync + 'static,
+{
+    pub fn new(code: &'static [u8], hook: H) -> Self {
+        Self {
+            vm: NoEptVmState::new(code),
+            hook: Arc::new(hook),
+        }
+    }
+}
+
+pub struct InterceptVcpuState<H> {
+    vcpu: NoEptVcpuState,
+    hook: Arc<H>,
+}
+
+impl<H> VmState for InterceptVmState<H>
+where
+    H: Fn(&mut GenericVCpuState) -> Option<Result<VmexitResult, VmError>> + Send + Sync + 'static,
+{
+    type VcpuState = InterceptVcpuState<H>;
+    type Error = Error;
+
+    fn vcpu_state(&self) -> Self::VcpuState {
+        InterceptVcpuState {
+            vcpu: self.vm.vcpu_state(),
+            hook: self.hook.clone(),
+        }
+    }
+
+    fn setup_vbsp(
+        &self,
+        vbsp_generic_state: &mut GenericVCpuState,
+        vbsp_vcpu_state: &mut Self::VcpuState,
+    ) -> Result<(), Self::Error> {
+        self.vm
+            .setup_vbsp(vbsp_generic_state, &mut vbsp_vcpu_state.vcpu)
+    }
+}
+
+impl<H> VCpuState for InterceptVcpuState<H>
+where
+    H: Fn(&mut GenericVCpuState) -> Option<Result<VmexitResult, VmError>> + Send + Sync + 'static,
+{
+    fn pinbase_ctls(&self) -> VmcsPinBasedVmexecCtl {
+        self.vcpu.pinbase_ctls()
+    }
+    fn procbase_ctls(&self) -> VmcsProcBasedVmexecCtl {
+        self.vcpu.procbase_ctls()
+    }
+    fn procbase_ctls2(&self) -> VmcsProcBasedSecondaryVmexecCtl {
+        self.vcpu.procbase_ctls2()
+    }
+    fn exit_ctls(&self) -> VmcsExitCtl {
+        self.vcpu.exit_ctls()
+    }
+    fn entry_ctls(&self) -> VmcsEntryCtl {
+        self.vcpu.entry_ctls()
+    }
+    fn init_guest_state(&self, vmcs: &ActiveVmcs) -> Result<(), VmError> {
+        self.vcpu.init_guest_state(vmcs)
+    }
+
+    fn handle_vmexit(
+        &mut self,
+        generic_vcpu_state: &mut GenericVCpuState,
+    ) -> Result<VmexitResult, VmError> {
+        match (self.hook)(generic_vcpu_state) {
+            Some(result) => result,
+            None => self.vcpu.handle_vmexit(generic_vcpu_state),
+        }
+    }
+}
@@ -12,8 +12,11 @@ extern crate grading;
 
 mod cpuid;
 mod hypercall;
+mod intercept;
 mod msr;
 mod pio;
+mod regs;
+mod tsc;
 
 use keos::SystemConfigurationBuilder;
 use keos_project4::round_robin::RoundRobin;
@@ -34,7 +37,11 @@ pub unsafe fn main(config_builder: SystemConfigurationBuilder) {
         &pio::pio_mem,
         &cpuid::cpuid_leaf_0,
         &cpuid::cpuid_leaf_1,
+        &cpuid::cpuid_masking,
         &msr::msr,
+        &msr::msr_bitmap,
+        &regs::regs,
+        &tsc::tsc_offset,
     ]);
 }
 
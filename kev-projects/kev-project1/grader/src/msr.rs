@@ -1,4 +1,14 @@
-use core::arch::global_asm;
+use crate::intercept::InterceptVmState;
+use alloc::sync::Arc;
+use core::{
+    arch::global_asm,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use kev::{
+    vcpu::GenericVCpuState,
+    vm::VmBuilder,
+    vmcs::{BasicExitReason, MsrBitmap},
+};
 
 // Test for msr
 global_asm!(
@@ -36,3 +46,78 @@ pub fn msr() {
         )
     });
 }
+
+// Read the IA32_TSC, which is passed through, and access the trapped msr.
+global_asm!(
+    "msr_bitmap_start:",
+    "mov rcx, 0x10",
+    "rdmsr",
+    "mov rcx, 0xabc",
+    "mov rdx, 0",
+    "mov rax, 0x5678",
+    "wrmsr",
+    "mov rax, 0",
+    "rdmsr",
+    "cmp rax, 0x5678",
+    "jne msr_bitmap_failed",
+    // hcall_exit(0);
+    "mov rdi, 0",
+    "mov rax, 0",
+    "vmcall",
+    "msr_bitmap_failed:",
+    "mov rdi, 1",
+    "mov rax, 0",
+    "vmcall",
+    "msr_bitmap_end:",
+);
+pub fn msr_bitmap() {
+    const IA32_TSC: u32 = 0x10;
+
+    // The number of vmexits on the IA32_TSC and on the trapped msr.
+    let exits = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+    let counter = exits.clone();
+    let count_exits = move |generic_vcpu_state: &mut GenericVCpuState| {
+        let exit_reason = generic_vcpu_state.vmcs.exit_reason().ok()?;
+        if matches!(
+            exit_reason.get_basic_reason(),
+            BasicExitReason::Rdmsr | BasicExitReason::Wrmsr
+        ) {
+            let idx = if generic_vcpu_state.gprs.rcx as u32 == IA32_TSC {
+                0
+            } else {
+                1
+            };
+            counter[idx].fetch_add(1, Ordering::SeqCst);
+        }
+        None
+    };
+
+    let mut bitmap = MsrBitmap::new();
+    bitmap
+        .pass_through_read(IA32_TSC)
+        .expect("Failed to pass through the msr.");
+    let vm = VmBuilder::new(
+        InterceptVmState::new(
+            unsafe {
+                unsafe extern "C" {
+                    static msr_bitmap_start: u8;
+                    static msr_bitmap_end: u8;
+                }
+                core::slice::from_raw_parts(
+                    &msr_bitmap_start as *const u8,
+                    &msr_bitmap_end as *const _ as usize - &msr_bitmap_start as *const _ as usize,
+                )
+            },
+            count_exits,
+        ),
+        1,
+    )
+    .expect("Failed to create vmbuilder.")
+    .msr_bitmap(bitmap)
+    .finalize()
+    .expect("Failed to create vm.");
+    vm.start_bsp().expect("Failed to start bsp.");
+    assert_eq!(vm.join(), 0);
+    assert_eq!(exits[0].load(Ordering::SeqCst), 0);
+    assert_eq!(exits[1].load(Ordering::SeqCst), 2);
+}
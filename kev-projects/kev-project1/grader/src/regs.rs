@@ -0,0 +1,79 @@
+use crate::intercept::InterceptVmState;
+use core::arch::global_asm;
+use kev::{
+    vcpu::{Cr4, GenericVCpuState, VmexitResult},
+    vm::VmBuilder,
+    vmcs::BasicExitReason,
+};
+
+// On hlt, add 0x1000 to rax and set the cr4.TSD.
+fn on_hlt(generic_vcpu_state: &mut GenericVCpuState) -> Option<Result<VmexitResult, kev::VmError>> {
+    let exit_reason = generic_vcpu_state.vmcs.exit_reason().ok()?;
+    if !matches!(exit_reason.get_basic_reason(), BasicExitReason::Hlt) {
+        return None;
+    }
+    Some((|| {
+        let mut regs = generic_vcpu_state.get_regs();
+        regs.rax += 0x1000;
+        generic_vcpu_state.set_regs(regs);
+        let cr4 = generic_vcpu_state.get_cr(4)?;
+        generic_vcpu_state.set_cr(4, cr4 | Cr4::TSD.bits())?;
+        generic_vcpu_state.vmcs.forward_rip()?;
+        Ok(VmexitResult::Ok)
+    })())
+}
+
+// Halt, and exit with the rax and the cr4.TSD observed after the halt.
+global_asm!(
+    "regs_start:",
+    "mov rax, 0x1234",
+    "hlt",
+    "mov rcx, cr4",
+    "and rcx, 0x4",
+    "add rax, rcx",
+    "mov rdi, rax",
+    "mov rax, 0",
+    "vmcall",
+    "regs_end:",
+);
+pub fn regs() {
+    let vm = VmBuilder::new(
+        InterceptVmState::new(
+            unsafe {
+                unsafe extern "C" {
+                    static regs_start: u8;
+                    static regs_end: u8;
+                }
+                core::slice::from_raw_parts(
+                    &regs_start as *const u8,
+                    &regs_end as *const _ as usize - &regs_start as *const _ as usize,
+                )
+            },
+            on_hlt,
+        ),
+        1,
+    )
+    .expect("Failed to create vmbuilder.")
+    .finalize()
+    .expect("Failed to create vm.");
+
+    let mut guard = vm.vcpu(0).unwrap().lock();
+    let cr4 = guard.get_cr(4).expect("Failed to read cr4.");
+    assert_eq!(cr4 & Cr4::TSD.bits(), 0);
+    assert!(guard.get_cr(2).is_err());
+    guard.unlock();
+
+    vm.start_bsp().expect("Failed to start bsp.");
+    assert_eq!(vm.join(), 0x2238);
+
+    // The registers of the exited guest are still readable.
+    let mut guard = vm.vcpu(0).unwrap().lock();
+    let regs = guard.get_regs();
+    let cr4 = guard.get_cr(4);
+    guard.unlock();
+    assert_eq!(regs.rdi, 0x2238);
+    assert_eq!(
+        cr4.expect("Failed to read cr4.") & Cr4::TSD.bits(),
+        Cr4::TSD.bits()
+    );
+}
@@ -0,0 +1,46 @@
+use core::arch::global_asm;
+use kev::vm::VmBuilder;
+use kev_project1::no_ept_vm::NoEptVmState;
+
+// Check that the tsc is shifted by the tsc offset of 1 << 62, and exit.
+global_asm!(
+    "tsc_offset_start:",
+    "rdtsc",
+    "shl rdx, 32",
+    "or rax, rdx",
+    "mov rcx, 0x4000000000000000",
+    "cmp rax, rcx",
+    "jb tsc_offset_failed",
+    // hcall_exit(0);
+    "mov rdi, 0",
+    "mov rax, 0",
+    "vmcall",
+    "tsc_offset_failed:",
+    "mov rdi, 1",
+    "mov rax, 0",
+    "vmcall",
+    "tsc_offset_end:",
+);
+pub fn tsc_offset() {
+    let vm = VmBuilder::new(
+        NoEptVmState::new(unsafe {
+            unsafe extern "C" {
+                static tsc_offset_start: u8;
+                static tsc_offset_end: u8;
+            }
+            core::slice::from_raw_parts(
+                &tsc_offset_start as *const u8,
+                &tsc_offset_end as *const _ as usize - &tsc_offset_start as *const _ as usize,
+            )
+        }),
+        1,
+    )
+    .expect("Failed to create vmbuilder.")
+    .finalize()
+    .expect("Failed to create vm.");
+    // The host tsc does not reach 1 << 62 in decades.
+    vm.set_tsc_offset(1 << 62)
+        .expect("Failed to set the tsc offset.");
+    vm.start_bsp().expect("Failed to start bsp.");
+    assert_eq!(vm.join(), 0);
+}
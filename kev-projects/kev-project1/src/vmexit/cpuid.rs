@@ -20,6 +20,10 @@
 //! EAX = 1, the result contains the executing core's CPU ID, not the VCPU ID.
 //! In this case, the result needs to modified to contain the VCPU ID.
 //!
+//! The VMM may also present a customized CPU to the guest, for example, by
+//! hiding a feature of the host. Such results are configured with
+//! [`Vm::set_cpuid`] and take precedence over the result of the host.
+//!
 //! ## Tasks
 //! Implement cpuid controller's handle method to emulate cpuid instruction.
 //! If the input to the instruction is EAX = 1, you must carefully handle the
@@ -28,6 +32,8 @@
 //! the executing core.](/src/abyss/x86_64/intrinsics.rs.html) In addition, you
 //! **MUST** forward the vCPU instruction pointer (rip) to prevent it from
 //! executing the same instructions indefinitely.
+//!
+//! [`Vm::set_cpuid`]: kev::vm::Vm::set_cpuid
 use kev::{
     Probe, VmError,
     vcpu::{GenericVCpuState, VmexitResult},
@@ -54,7 +60,8 @@ impl kev::vmexits::VmexitController for Controller {
         match reason.get_basic_reason() {
             BasicExitReason::Cpuid => {
                 // HINT:
-                //    - Use `core::arch::x86_64::__cpuid` to execute `cpuid`.
+                //    - Use the entry of `GenericVCpuState::cpuid_entry` as is if exists.
+                //    - Otherwise, use `core::arch::x86_64::__cpuid` to execute `cpuid`.
                 //    - You should advance rip when an instruction is emulated.
                 //    - You must carefully handle the cpuid leaf 1. Because it holds the cpu id,
                 //      you must change the value to the virtual cpu id.
@@ -0,0 +1,62 @@
+use core::arch::global_asm;
+use kev::{
+    vm::{BreakpointHit, Gpa, VmBuilder},
+    vmcs::Field,
+};
+use kev_project2::simple_ept_vm::SimpleEptVmState;
+
+// Sum 2 three times in a loop, and exit with the sum.
+global_asm!(
+    "bp_start:",
+    "xor rdi, rdi",
+    "mov rcx, 3",
+    "bp_target:",
+    "add rdi, 2",
+    "dec rcx",
+    "jnz bp_target",
+    // hcall_exit(6);
+    "mov rax, 0",
+    "vmcall",
+    "bp_end:",
+);
+pub fn breakpoint() {
+    unsafe extern "C" {
+        static bp_start: u8;
+        static bp_target: u8;
+        static bp_end: u8;
+    }
+    let (code, target) = unsafe {
+        (
+            core::slice::from_raw_parts(
+                &bp_start as *const u8,
+                &bp_end as *const _ as usize - &bp_start as *const _ as usize,
+            ),
+            &bp_target as *const _ as usize - &bp_start as *const _ as usize,
+        )
+    };
+    let vm = VmBuilder::new(SimpleEptVmState::new(code), 1)
+        .expect("Failed to create vmbuilder.")
+        .finalize()
+        .expect("Failed to create vm.");
+
+    // The guest starts at the beginning of the code.
+    let entry = vm.snapshot().expect("Failed to take a snapshot.").vcpus[0]
+        .fields
+        .iter()
+        .find(|(field, _)| *field == Field::GuestRip)
+        .unwrap()
+        .1 as usize;
+    let gpa = Gpa::new(entry + target).unwrap();
+    vm.set_breakpoint(gpa).expect("Failed to set a breakpoint.");
+    assert!(vm.set_breakpoint(gpa).is_err());
+
+    vm.start_bsp().expect("Failed to start bsp.");
+    assert_eq!(vm.join(), 6);
+    let hit = BreakpointHit {
+        vcpu: 0,
+        gpa,
+        rip: gpa.into_usize() as u64,
+    };
+    assert_eq!(vm.take_breakpoint_hits(), [hit, hit, hit]);
+    assert!(vm.take_breakpoint_hits().is_empty());
+}
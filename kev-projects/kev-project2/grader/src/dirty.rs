@@ -0,0 +1,69 @@
+use core::arch::global_asm;
+use keos::thread::Current;
+use kev::vm::{Gpa, VmBuilder};
+use kev_project2::simple_ept_vm::SimpleEptVmState;
+
+// Repeatedly write to the second and the fourth page of the code, and exit.
+global_asm!(
+    "dirty_start:",
+    // Clear the cr0.WP to write on the read-only code pages.
+    "mov rax, cr0",
+    "btr rax, 16",
+    "mov cr0, rax",
+    "lea rbx, [rip + dirty_start]",
+    "mov ecx, 0x40000000",
+    "dirty_loop:",
+    "inc qword ptr [rbx + 0x1000]",
+    "inc qword ptr [rbx + 0x3000]",
+    "dec rcx",
+    "jnz dirty_loop",
+    // hcall_exit(0);
+    "mov rdi, 0",
+    "mov rax, 0",
+    "vmcall",
+    ".skip 0x4000 - (. - dirty_start)",
+    "dirty_end:",
+);
+pub fn dirty_pages() {
+    let vm = VmBuilder::new(
+        SimpleEptVmState::new(unsafe {
+            unsafe extern "C" {
+                static dirty_start: u8;
+                static dirty_end: u8;
+            }
+            core::slice::from_raw_parts(
+                &dirty_start as *const u8,
+                &dirty_end as *const _ as usize - &dirty_start as *const _ as usize,
+            )
+        })
+        .dirty_tracking(),
+        1,
+    )
+    .expect("Failed to create vmbuilder.")
+    .finalize()
+    .expect("Failed to create vm.");
+
+    vm.start_bsp().expect("Failed to start bsp.");
+    Current::sleep(10);
+    // The first writes also set the accessed and dirty bits of the guest page
+    // table, which dirties the page table pages.
+    let first = vm
+        .take_dirty_bitmap()
+        .expect("Failed to take the dirty bitmap.");
+    Current::sleep(10);
+    let second = vm
+        .take_dirty_bitmap()
+        .expect("Failed to take the dirty bitmap.");
+    assert_eq!(vm.join(), 0);
+
+    let guard = vm.vcpu(0).unwrap().lock();
+    let base = guard.get_regs().rbx;
+    guard.unlock();
+    let written = [
+        Gpa::new(base + 0x1000).unwrap(),
+        Gpa::new(base + 0x3000).unwrap(),
+    ];
+    assert!(written.iter().all(|gpa| first.contains(gpa)));
+    assert!(!first.contains(&Gpa::new(base).unwrap()));
+    assert_eq!(second.into_iter().collect::<alloc::vec::Vec<_>>(), written);
+}
@@ -9,9 +9,12 @@ extern crate keos;
 extern crate keos_project4;
 extern crate grading;
 
+mod breakpoint;
+mod dirty;
 mod ept;
 mod gkeos;
 mod mmio;
+mod snapshot;
 
 use keos::SystemConfigurationBuilder;
 use keos_project4::round_robin::RoundRobin;
@@ -31,6 +34,9 @@ pub unsafe fn main(config_builder: SystemConfigurationBuilder) {
         &ept::complicate,
         &ept::check_huge_translation,
         &mmio::mmio_print,
+        &snapshot::snapshot_restore,
+        &dirty::dirty_pages,
+        &breakpoint::breakpoint,
         &gkeos::run_keos,
     ]);
 }
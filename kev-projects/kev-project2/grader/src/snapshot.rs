@@ -0,0 +1,67 @@
+use keos::thread::Current;
+use kev::vm::{VmBuilder, VmSnapshot};
+use kev_project2::simple_ept_vm::SimpleEptVmState;
+
+const ITERATIONS: u32 = 0x4000_0000;
+
+// Run an lcg for ITERATIONS times, counting the iterations on rbx, and exit
+// with the result.
+const CODE: &[u8] = &[
+    0x31, 0xDB, // xor    ebx,ebx
+    0x48, 0xC7, 0xC0, 0x01, 0x00, 0x00, 0x00, // mov    rax,0x1
+    0xB9, 0x00, 0x00, 0x00, 0x40, // mov    ecx,0x40000000
+    // loop:
+    0x48, 0xFF, 0xC3, // inc    rbx
+    0x48, 0x69, 0xC0, 0x6D, 0x4E, 0xC6, 0x41, // imul   rax,rax,0x41c64e6d
+    0x48, 0x05, 0x39, 0x30, 0x00, 0x00, // add    rax,0x3039
+    0x48, 0xFF, 0xC9, // dec    rcx
+    0x75, 0xEB, // jne    loop
+    0x48, 0x89, 0xC7, // mov    rdi,rax
+    0x48, 0xC7, 0xC0, 0x00, 0x00, 0x00, 0x00, // mov    rax,0x0
+    0x0F, 0x01, 0xC1, // vmcall
+];
+
+fn expected() -> i32 {
+    let mut rax: u64 = 1;
+    for _ in 0..ITERATIONS {
+        rax = rax.wrapping_mul(0x41c6_4e6d).wrapping_add(0x3039);
+    }
+    rax as i32
+}
+
+fn rbx(snapshot: &VmSnapshot) -> usize {
+    snapshot.vcpus[0].gprs.rbx
+}
+
+/// Snapshot a running vm, and rewind it by restoring the snapshot.
+pub fn snapshot_restore() {
+    let vm = VmBuilder::new(SimpleEptVmState::new(CODE), 1)
+        .expect("Failed to create vmbuilder.")
+        .finalize()
+        .expect("Failed to create vm.");
+
+    vm.start_bsp().expect("Failed to start bsp.");
+    Current::sleep(10);
+    let a = vm.snapshot().expect("Failed to take a snapshot.");
+    assert!(!a.vcpus[0].memory.is_empty());
+    Current::sleep(10);
+    let b = vm.snapshot().expect("Failed to take a snapshot.");
+    assert!(
+        rbx(&a) < rbx(&b),
+        "The vcpu did not make progress between the snapshots."
+    );
+
+    vm.restore(&a).expect("Failed to restore the snapshot.");
+    let c = vm.snapshot().expect("Failed to take a snapshot.");
+    assert!(
+        rbx(&a) <= rbx(&c) && rbx(&c) < rbx(&b),
+        "The vcpu is not rewound to the snapshot."
+    );
+    assert!(
+        a.tsc <= c.tsc && c.tsc < b.tsc,
+        "The guest tsc does not continue from the snapshot."
+    );
+
+    // The guest runs to the same result as it would without the rewind.
+    assert_eq!(vm.join(), expected());
+}
@@ -19,8 +19,9 @@
 //! address to the host physical address, [`simple_ept_vm`] uses the implemented
 //! EPT functionalities in this project. The main concept of this project is
 //! similar to the page table implementations of Project 1. You have to
-//! implement [`ExtendedPageTable::map`], [`ExtendedPageTable::unmap`] and
-//! [`ExtendedPageTable::walk`] to be used for managing extended page table.
+//! implement [`ExtendedPageTable::map`], [`ExtendedPageTable::unmap`],
+//! [`ExtendedPageTable::walk`] and [`ExtendedPageTable::walk_mut`] to be used
+//! for managing extended page table.
 //! In contrast to the page table implementation from Project 1, EPT determines
 //! the presence of an entry by examining the presence of flags in page table
 //! entries. Stated differently, if there are no flags present in an EPT entry,
@@ -31,7 +32,7 @@
 //! initial boot time.
 //!
 //! [`simple_ept_vm`]: crate::simple_ept_vm
-use alloc::boxed::Box;
+use alloc::{boxed::Box, vec::Vec};
 use core::ops::{Deref, DerefMut};
 use keos::{
     addressing::{Kva, PAGE_MASK, PAGE_SHIFT, Pa},
@@ -499,6 +500,42 @@ impl ExtendedPageTable {
     pub fn walk(&self, gpa: Gpa) -> Result<&EptPte, EptMappingError> {
         todo!()
     }
+
+    /// Walk the extended page table and return mutable reference of the
+    /// corresponding eptpte of the `gpa` if exist.
+    pub fn walk_mut(&mut self, gpa: Gpa) -> Result<&mut EptPte, EptMappingError> {
+        // Hint: Same as `walk`, but use `into_ept_*_mut`.
+        todo!()
+    }
+
+    /// Returns the guest physical addresses and the host physical addresses
+    /// of every page mapped in this table, in the order of the guest physical
+    /// address.
+    pub fn mappings(&self) -> Vec<(Gpa, Pa)> {
+        let mut mappings = Vec::new();
+        for (i, pml4e) in self.0.iter().enumerate() {
+            let Ok(pdp) = pml4e.into_ept_pdp() else {
+                continue;
+            };
+            for (j, pdpe) in pdp.iter().enumerate() {
+                let Ok(pd) = pdpe.into_ept_pd() else {
+                    continue;
+                };
+                for (k, pde) in pd.iter().enumerate() {
+                    let Ok(pt) = pde.into_ept_pt() else {
+                        continue;
+                    };
+                    for (l, pte) in pt.iter().enumerate() {
+                        if let Some(hpa) = pte.pa() {
+                            let gpa = (i << 39) | (j << 30) | (k << 21) | (l << 12);
+                            mappings.push((Gpa::new(gpa).unwrap(), hpa));
+                        }
+                    }
+                }
+            }
+        }
+        mappings
+    }
 }
 
 impl kev::Probe for ExtendedPageTable {
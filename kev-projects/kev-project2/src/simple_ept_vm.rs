@@ -1,11 +1,12 @@
 //! Virtual machine configuration of project3-1.
 use crate::{
-    ept::{EptMappingError, ExtendedPageTable, Permission as EptPermission},
+    ept::{EptMappingError, EptPteFlags, ExtendedPageTable, Permission as EptPermission},
     mmio::PrinterDev,
     vmexit::mmio,
 };
+use alloc::{boxed::Box, collections::BTreeSet, vec::Vec};
 use keos::{
-    addressing::{Kva, PAGE_MASK, Pa, Va},
+    addressing::{Kva, PAGE_MASK, PAGE_SIZE, Pa, Va},
     mm::{
         Page,
         page_table::{PageTableMappingError, PageTableRoot, Pde, Pdpe, Permission, Pml4e, Pte},
@@ -13,15 +14,15 @@ use keos::{
 };
 use keos_project2::page_table::PageTable;
 use kev::{
-    VmError,
+    Probe, VmError,
     vcpu::{
         Cr0, Cr4, GenericVCpuState, Rflags, VmexitResult,
         segmentation::{SEGMENT_TABLE, Segment},
         table::SystemTableRegister,
     },
-    vm::Gpa,
+    vm::{Gpa, GuestPage},
     vm_control::*,
-    vmcs::{ActiveVmcs, Field},
+    vmcs::{ActiveVmcs, BasicExitReason, EptViolationQualification, Field},
     vmexits::VmexitController,
 };
 use kev_project1::{hypercall::HypercallCtx, vmexit::hypercall};
@@ -33,10 +34,27 @@ pub struct EptVmBase {}
 /// The Vmstate of EptVmBase.
 pub struct SimpleEptVmState {
     code: &'static [u8],
+    dirty_tracking: bool,
 }
 impl SimpleEptVmState {
     pub fn new(code: &'static [u8]) -> Self {
-        Self { code }
+        Self {
+            code,
+            dirty_tracking: false,
+        }
+    }
+
+    /// Track the guest pages written by the vm.
+    ///
+    /// The guest memory is mapped read-only in the extended page table. On
+    /// the first write to a page, the ept violation records the page as dirty
+    /// and makes the page writable. The dirty pages are taken with
+    /// [`VmHandle::take_dirty_bitmap`], which write-protects them again.
+    ///
+    /// [`VmHandle::take_dirty_bitmap`]: kev::vm::VmHandle::take_dirty_bitmap
+    pub fn dirty_tracking(mut self) -> Self {
+        self.dirty_tracking = true;
+        self
     }
 }
 
@@ -58,6 +76,7 @@ impl kev::vm::VmState for SimpleEptVmState {
         SimpleEptVcpuState {
             ept: ExtendedPageTable::new(),
             page_table: PageTable(PageTableRoot::new_boxed()),
+            dirty_log: None,
             vmexit_controller: (hypercall::Controller::new(HypercallCtx), (mmio_controller)),
         }
     }
@@ -285,6 +304,12 @@ impl kev::vm::VmState for SimpleEptVmState {
             vbsp_vcpu_state.page_table.pa().into_usize() as u64,
         )
         .map_err(Error::VmError)?;
+
+        if self.dirty_tracking {
+            vbsp_vcpu_state
+                .start_dirty_tracking()
+                .map_err(Error::EptError)?;
+        }
         Ok(())
     }
 }
@@ -294,6 +319,16 @@ pub struct SimpleEptVcpuState {
     ept: ExtendedPageTable,
     page_table: PageTable,
     vmexit_controller: (hypercall::Controller<HypercallCtx>, mmio::Controller),
+    dirty_log: Option<DirtyLog>,
+}
+
+/// The state of the dirty page tracking.
+#[derive(Default)]
+struct DirtyLog {
+    /// The pages that are write-protected for the tracking.
+    protected: BTreeSet<Gpa>,
+    /// The pages written since the last take.
+    dirty: BTreeSet<Gpa>,
 }
 
 impl SimpleEptVcpuState {
@@ -309,6 +344,38 @@ impl SimpleEptVcpuState {
                 .map(|_| hpa.into_kva().into_usize() as *const usize)
         }
     }
+
+    /// Write-protect every writable page to start the dirty page tracking.
+    fn start_dirty_tracking(&mut self) -> Result<(), EptMappingError> {
+        let mut dirty_log = DirtyLog::default();
+        for (gpa, _) in self.ept.mappings() {
+            let pte = self.ept.walk_mut(gpa)?;
+            if pte.flags().contains(EptPteFlags::WRITE) {
+                pte.set_perm(pte.flags() - EptPteFlags::WRITE);
+                dirty_log.protected.insert(gpa);
+            }
+        }
+        self.dirty_log = Some(dirty_log);
+        Ok(())
+    }
+
+    /// Record the write to the write-protected page at `gpa`, and make the
+    /// page writable.
+    ///
+    /// Returns false if the page is not write-protected for the tracking.
+    fn log_dirty(&mut self, gpa: Gpa) -> Result<bool, EptMappingError> {
+        let Some(dirty_log) = self.dirty_log.as_mut() else {
+            return Ok(false);
+        };
+        let page = Gpa::new(gpa.into_usize() & !PAGE_MASK).unwrap();
+        if !dirty_log.protected.remove(&page) {
+            return Ok(false);
+        }
+        let pte = self.ept.walk_mut(page)?;
+        pte.set_perm(pte.flags() | EptPteFlags::WRITE);
+        dirty_log.dirty.insert(page);
+        Ok(true)
+    }
 }
 
 impl kev::vcpu::VCpuState for SimpleEptVcpuState {
@@ -342,6 +409,18 @@ impl kev::vcpu::VCpuState for SimpleEptVcpuState {
         generic_vcpu_state: &mut GenericVCpuState,
     ) -> Result<VmexitResult, VmError> {
         let exit_reason = generic_vcpu_state.vmcs.exit_reason()?;
+        if let BasicExitReason::EptViolation {
+            qualification,
+            fault_addr: Some(gpa),
+        } = exit_reason.get_basic_reason()
+            && qualification.contains(EptViolationQualification::BIT1)
+            && self
+                .log_dirty(*gpa)
+                .map_err(|e| VmError::ControllerError(Box::new(e)))?
+        {
+            // Retry the write on the page that is now writable.
+            return Ok(VmexitResult::Ok);
+        }
         let Self {
             ept: mem,
             vmexit_controller,
@@ -349,4 +428,72 @@ impl kev::vcpu::VCpuState for SimpleEptVcpuState {
         } = self;
         vmexit_controller.handle(exit_reason, mem, generic_vcpu_state)
     }
+
+    fn snapshot_memory(&self) -> Result<Vec<GuestPage>, VmError> {
+        Ok(self
+            .ept
+            .mappings()
+            .into_iter()
+            .map(|(gpa, hpa)| GuestPage {
+                gpa,
+                data: unsafe { page_of(hpa) }.into(),
+            })
+            .collect())
+    }
+
+    fn restore_memory(&mut self, pages: &[GuestPage]) -> Result<(), VmError> {
+        let mappings = self.ept.mappings();
+        if mappings.len() != pages.len()
+            || mappings
+                .iter()
+                .zip(pages.iter())
+                .any(|((gpa, _), page)| *gpa != page.gpa || page.data.len() != PAGE_SIZE)
+        {
+            return Err(VmError::VCpuError(Box::new(
+                "Snapshot has a different memory layout.",
+            )));
+        }
+        for ((_, hpa), page) in mappings.into_iter().zip(pages.iter()) {
+            // Some guest pages are identity-mapped host pages, such as the
+            // GDT. Leave the pages that the guest has not changed untouched.
+            let dst = unsafe { page_of(hpa) };
+            if *dst != *page.data {
+                dst.copy_from_slice(&page.data);
+            }
+        }
+        Ok(())
+    }
+
+    fn take_dirty_pages(
+        &mut self,
+        generic_vcpu_state: &mut GenericVCpuState,
+    ) -> Result<Vec<Gpa>, VmError> {
+        let Self { ept, dirty_log, .. } = self;
+        let dirty_log = dirty_log.as_mut().ok_or(VmError::VCpuError(Box::new(
+            "Dirty page tracking is not enabled.",
+        )))?;
+        let dirty = core::mem::take(&mut dirty_log.dirty);
+        for gpa in dirty.iter() {
+            let pte = ept
+                .walk_mut(*gpa)
+                .map_err(|e| VmError::ControllerError(Box::new(e)))?;
+            pte.set_perm(pte.flags() - EptPteFlags::WRITE);
+            dirty_log.protected.insert(*gpa);
+        }
+        // The cpus may have cached the writable translations.
+        generic_vcpu_state.invalidate_ept();
+        Ok(dirty.into_iter().collect())
+    }
+    fn probe(&self) -> Option<&dyn Probe> {
+        Some(&self.ept)
+    }
+}
+
+/// Get the contents of the host page at `hpa`.
+///
+/// # Safety
+/// The `hpa` must point to a page mapped in the extended page table.
+#[allow(clippy::mut_from_ref)]
+unsafe fn page_of<'a>(hpa: Pa) -> &'a mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(hpa.into_kva().into_usize() as *mut u8, PAGE_SIZE) }
 }
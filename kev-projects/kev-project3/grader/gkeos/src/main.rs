@@ -13,6 +13,7 @@ extern crate grading;
 
 mod round_robin;
 mod simple_virtio;
+mod simple_virtio_net;
 mod virtio;
 
 use keos::SystemConfigurationBuilder;
@@ -36,6 +37,9 @@ pub unsafe fn main(config_builder: SystemConfigurationBuilder) {
     keos::TestDriver::<Thread>::start([
         &virtio::check_blockio,
         &virtio::check_blockio_batching,
+        &virtio::check_blockio_batch32,
+        &virtio::check_net_echo,
+        &virtio::check_net_resize,
         &round_robin::functionality,
         &round_robin::balance,
         &round_robin::balance2,
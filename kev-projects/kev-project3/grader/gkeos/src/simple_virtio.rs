@@ -1,5 +1,5 @@
 #[path = "../../../src/virtio/mod.rs"]
-mod virtio;
+pub mod virtio;
 
 use alloc::boxed::Box;
 use alloc::sync::Arc;
@@ -14,6 +14,9 @@ use virtio::{
     VirtIoMmioHeader, VirtIoStatus,
 };
 
+/// The number of the entries of the virtqueue.
+const QUEUE_SIZE: u32 = 0x1000;
+
 pub struct VirtIoBlockDriver {
     header: *mut VirtIoMmioHeader,
     virt_queue: VirtQueue<Box<[VirtQueueEntry]>>,
@@ -30,7 +33,7 @@ impl VirtIoBlockDriver {
 
         let virt_queue = unsafe {
             let virt_queue = if header.status == VirtIoStatus::MAGIC as u32 {
-                VirtQueue::new(0x1000)
+                VirtQueue::new(QUEUE_SIZE as usize)
             } else {
                 write_volatile(&mut header.status, VirtIoStatus::RESET as u32);
                 return None;
@@ -48,7 +51,7 @@ impl VirtIoBlockDriver {
                 .into_usize();
             write_volatile(&mut header.queue_addr_hi, (queue_ptr >> 32) as u32);
             write_volatile(&mut header.queue_addr_lo, (queue_ptr & 0xFFFF_FFFF) as u32);
-            write_volatile(&mut header.queue_size, 0x1000);
+            write_volatile(&mut header.queue_size, QUEUE_SIZE);
 
             info!("VirtIo Block Driver Enabled...");
             write_volatile(&mut header.status, VirtIoStatus::READY as u32);
@@ -92,6 +95,22 @@ impl VirtIoBlockDriver {
         fetcher.push_front(entry).map_err(|_| KernelError::IOError)
     }
 
+    /// Push a request to read `sector` into `buf`.
+    pub fn send_read(
+        fetcher: &mut VirtQueueFetcher<Box<[VirtQueueEntry]>>,
+        buf: &mut [u8],
+        sector: usize,
+    ) -> Result<(), KernelError> {
+        let entry = VirtQueueEntry {
+            addr: Kva::new(buf.as_mut_ptr() as usize).unwrap().into_pa(),
+            size: buf.len(),
+            sector,
+            cmd: VirtQueueEntryCmd::Read,
+        };
+
+        fetcher.push_front(entry).map_err(|_| KernelError::IOError)
+    }
+
     pub fn kick(fetcher: VirtQueueFetcher<Box<[VirtQueueEntry]>>) -> Result<(), KernelError> {
         fetcher.kick()
     }
@@ -154,6 +173,41 @@ impl VirtIoDisk {
         guard.unlock();
         r
     }
+
+    /// Read the sectors of the requests with a single doorbell, and return
+    /// the number of the requests issued.
+    pub fn read_scattered(
+        &self,
+        reqs: &mut [(keos::fs::Sector, [u8; 512])],
+    ) -> Result<usize, KernelError> {
+        let mut guard = self.inner.lock();
+        let mmio = unsafe { &mut *guard.header };
+        let mut fetcher = guard.virt_queue.fetcher(mmio);
+        let mut r = Ok(0);
+        for (sector, buf) in reqs.iter_mut() {
+            r = VirtIoBlockDriver::send_read(&mut fetcher, buf, sector.into_usize())
+                .and_then(|_| r.map(|issued| issued + 1));
+            if r.is_err() {
+                break;
+            }
+        }
+        let r = r.and_then(|issued| VirtIoBlockDriver::kick(fetcher).map(|_| issued));
+        guard.unlock();
+        r
+    }
+
+    /// Get the number of the requests not consumed by the device.
+    pub fn in_flight(&self) -> usize {
+        let guard = self.inner.lock();
+        let (head, tail) = unsafe {
+            (
+                read_volatile(&(*guard.header).queue_head),
+                read_volatile(&(*guard.header).queue_tail),
+            )
+        };
+        guard.unlock();
+        (head.wrapping_sub(tail) % QUEUE_SIZE) as usize
+    }
 }
 
 use abyss::dev::{BlockOps, Sector};
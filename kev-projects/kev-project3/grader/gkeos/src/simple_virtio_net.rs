@@ -0,0 +1,115 @@
+use crate::simple_virtio::virtio::{
+    virt_queue::{VirtQueue, VirtQueueEntry, VirtQueueEntryCmd},
+    VirtIoMmioHeader, VirtIoNetMmioHeader, VirtIoStatus,
+};
+use alloc::boxed::Box;
+use core::ptr::{read_volatile, write_volatile};
+use keos::{
+    addressing::{Kva, Pa},
+    KernelError,
+};
+
+const QUEUE_SIZE: u32 = 64;
+
+pub struct VirtIoNet {
+    header: *mut VirtIoNetMmioHeader,
+    rx: VirtQueue<Box<[VirtQueueEntry]>>,
+    tx: VirtQueue<Box<[VirtQueueEntry]>>,
+}
+
+fn realize_queue(header: &mut VirtIoMmioHeader) -> Option<VirtQueue<Box<[VirtQueueEntry]>>> {
+    unsafe {
+        if read_volatile(&header.status) != VirtIoStatus::MAGIC as u32 {
+            write_volatile(&mut header.status, VirtIoStatus::RESET as u32);
+            return None;
+        }
+        let virt_queue = VirtQueue::new(QUEUE_SIZE as usize);
+        write_volatile(&mut header.status, VirtIoStatus::DRIVEROK as u32);
+        if read_volatile(&header.status) != VirtIoStatus::DRIVEROK as u32 {
+            write_volatile(&mut header.status, VirtIoStatus::RESET as u32);
+            return None;
+        }
+
+        let queue_ptr = Kva::new(virt_queue.virt_queue_ptr())
+            .unwrap()
+            .into_pa()
+            .into_usize();
+        write_volatile(&mut header.queue_addr_hi, (queue_ptr >> 32) as u32);
+        write_volatile(&mut header.queue_addr_lo, (queue_ptr & 0xFFFF_FFFF) as u32);
+        write_volatile(&mut header.queue_size, QUEUE_SIZE);
+        write_volatile(&mut header.status, VirtIoStatus::READY as u32);
+
+        // Now check driver is ready to use
+        if read_volatile(&header.status) != VirtIoStatus::READY as u32 {
+            write_volatile(&mut header.status, VirtIoStatus::RESET as u32);
+            return None;
+        }
+        Some(virt_queue)
+    }
+}
+
+impl VirtIoNet {
+    pub fn new() -> Option<Self> {
+        let header = unsafe {
+            &mut *(Pa::new(0xcafe1000).unwrap().into_kva().into_usize() as *mut VirtIoNetMmioHeader)
+        };
+        info!("VirtIo Net Driver Start.");
+        let rx = realize_queue(&mut header.rx)?;
+        let tx = realize_queue(&mut header.tx)?;
+        info!("VirtIo Net Driver Ready.");
+        Some(Self { header, rx, tx })
+    }
+
+    pub fn finish(&mut self) {
+        unsafe {
+            let header = &mut *self.header;
+            info!("VirtIo Net Driver Finish.");
+            write_volatile(&mut header.rx.status, VirtIoStatus::RESET as u32);
+            write_volatile(&mut header.tx.status, VirtIoStatus::RESET as u32);
+        }
+    }
+
+    fn entry(buf: &[u8]) -> VirtQueueEntry {
+        VirtQueueEntry {
+            addr: Kva::new(buf.as_ptr() as usize).unwrap().into_pa(),
+            size: buf.len(),
+            sector: 0,
+            cmd: VirtQueueEntryCmd::Read,
+        }
+    }
+
+    /// Supply a buffer to receive a frame into.
+    pub fn supply(&mut self, buf: &mut [u8]) -> Result<(), KernelError> {
+        let mmio = unsafe { &mut (*self.header).rx };
+        let mut fetcher = self.rx.fetcher(mmio);
+        fetcher
+            .push_front(Self::entry(buf))
+            .map_err(|_| KernelError::IOError)?;
+        fetcher.kick()
+    }
+
+    /// Transmit a frame.
+    pub fn transmit(&mut self, frame: &[u8]) -> Result<(), KernelError> {
+        let mmio = unsafe { &mut (*self.header).tx };
+        let mut fetcher = self.tx.fetcher(mmio);
+        fetcher
+            .push_front(Self::entry(frame))
+            .map_err(|_| KernelError::IOError)?;
+        fetcher.kick()
+    }
+
+    /// Try to change the size of the ready tx queue to `size`, and return
+    /// the size that the device holds.
+    pub fn resize_tx(&mut self, size: u32) -> u32 {
+        unsafe {
+            let mmio = &mut (*self.header).tx;
+            write_volatile(&mut mmio.queue_size, size);
+            read_volatile(&mmio.queue_size)
+        }
+    }
+
+    /// Get the number of the buffers filled by the device.
+    pub fn received(&self) -> usize {
+        unsafe { read_volatile(&(*self.header).rx.queue_tail) as usize }
+    }
+}
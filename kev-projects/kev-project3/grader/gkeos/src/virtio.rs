@@ -1,4 +1,5 @@
-use crate::simple_virtio::VirtIoDisk;
+use crate::simple_virtio::{virtio::VirtIoNetHdr, VirtIoDisk};
+use crate::simple_virtio_net::VirtIoNet;
 use alloc::vec;
 use core::str::from_utf8;
 use keos::fs::{BlockOps, Sector};
@@ -121,3 +122,67 @@ pub fn check_blockio_batching() {
     assert!(disk.write_many(Sector(0), &mut read_buf).is_ok());
     disk.finish();
 }
+
+pub fn check_net_echo() {
+    let mut net = VirtIoNet::new().unwrap();
+    // A broadcast frame from 02:00:00:00:00:01 with the payload of 0..46.
+    let mut frame = vec![0xff; 6];
+    frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01, 0x88, 0xb5]);
+    frame.extend(0..46);
+    let mut rx_buf = vec![0 as u8; 2048];
+
+    assert!(net.supply(&mut rx_buf).is_ok());
+    assert_eq!(net.received(), 0);
+
+    // The loopback backend echoes the frame into the supplied buffer.
+    assert!(net.transmit(&frame).is_ok());
+    assert_eq!(net.received(), 1);
+    let hdr_len = core::mem::size_of::<VirtIoNetHdr>();
+    let len = unsafe { core::ptr::read_volatile(rx_buf.as_ptr() as *const VirtIoNetHdr) }.len;
+    assert_eq!(len as usize, frame.len());
+    assert_eq!(&rx_buf[hdr_len..hdr_len + frame.len()], &frame[..]);
+    assert!(rx_buf[hdr_len + frame.len()..].iter().all(|b| *b == 0));
+
+    net.finish();
+}
+
+pub fn check_net_resize() {
+    let mut net = VirtIoNet::new().unwrap();
+    let frame = vec![0xff; 60];
+    let mut rx_buf = vec![0 as u8; 2048];
+
+    // The size of the ready queue is kept, so the queue still works.
+    assert_eq!(net.resize_tx(0), 64);
+    assert!(net.supply(&mut rx_buf).is_ok());
+    assert!(net.transmit(&frame).is_ok());
+    assert_eq!(net.received(), 1);
+    let hdr_len = core::mem::size_of::<VirtIoNetHdr>();
+    assert_eq!(&rx_buf[hdr_len..hdr_len + frame.len()], &frame[..]);
+
+    net.finish();
+}
+
+pub fn check_blockio_batch32() {
+    let mut disk = VirtIoDisk::new().unwrap();
+    let sectors = (DISK_CONTENT.len() + 511) / 512;
+    // 32 requests that cycle the sectors of the disk content. The requests
+    // to the adjacent sectors can be served together.
+    let mut reqs = (0..32)
+        .map(|idx| (Sector(idx % sectors), [0 as u8; 512]))
+        .collect::<alloc::vec::Vec<_>>();
+
+    // All requests are issued before the single notification, which serves
+    // all of them.
+    assert_eq!(disk.read_scattered(&mut reqs).ok(), Some(32));
+    assert_eq!(disk.in_flight(), 0);
+    for (sector, buf) in reqs.iter() {
+        let start = sector.into_usize() * 512;
+        let end = (start + 512).min(DISK_CONTENT.len());
+        assert_eq!(
+            &from_utf8(&buf[..end - start]).unwrap(),
+            &&DISK_CONTENT[start..end]
+        );
+    }
+
+    disk.finish();
+}
@@ -1,6 +1,7 @@
 //! Collection of Emulated devices.
 
 pub mod simple_virtio;
+pub mod simple_virtio_net;
 pub mod x2apic;
 
 pub use x2apic::X2Apic;
@@ -146,9 +146,17 @@
 //! [`VirtQueueEntry`] through an struct called [`VirtQueueFetcher`].
 //! You can utilize [`VirtQueueFetcher`] to implement this project.
 //!
+//! A driver may queue many requests before ringing the doorbell once, so the
+//! device must serve all the queued requests in a single notification. Each
+//! access to the backing disk is costly; [`VirtQueueFetcher::drain_runs`]
+//! groups the requests to adjacent sectors into a [`VirtQueueRun`], which can
+//! be served with a single read or write of the disk.
+//!
 //! [`VirtQueue`]: crate::virtio::virt_queue::VirtQueue::new_from_raw_ptr
 //! [`VirtQueueEntry`]: crate::virtio::virt_queue::VirtQueueEntry
 //! [`VirtQueueFetcher`]: crate::virtio::virt_queue::VirtQueueFetcher
+//! [`VirtQueueFetcher::drain_runs`]: crate::virtio::virt_queue::VirtQueueFetcher::drain_runs
+//! [`VirtQueueRun`]: crate::virtio::virt_queue::VirtQueueRun
 use crate::virtio::{
     virt_queue::{VirtQueue, VirtQueueEntry, VirtQueueEntryCmd},
     VirtIoMmioHeader, VirtIoStatus,
@@ -226,6 +234,9 @@ impl mmio::MmioHandler for SimpleVirtIoBlockDev {
         generic_vcpu_state: &mut GenericVCpuState,
     ) -> Result<VmexitResult, VmError> {
         if let mmio::Direction::Write32 { dst, src } = info.direction {
+            // HINT:
+            //    - Serve all queued requests when the driver writes the queue head.
+            //    - Read or write each run of `VirtQueueFetcher::drain_runs` at once.
             todo!()
         }
         Ok(VmexitResult::Ok)
@@ -0,0 +1,385 @@
+//! Simple VirtIO Network
+//!
+//! The simple virtio network device moves ethernet frames between the guest
+//! and a host-side [`NetBackend`]. It follows the specification of the
+//! [`Simple VirtIO Block`] device, with the following differences.
+//!
+//! ### 1. Queues
+//! The device has two ring buffers: the receive queue (rx) and the transmit
+//! queue (tx). Each queue is configured through its own [`VirtIoMmioHeader`],
+//! which are laid out back to back in the mmio area:
+//! ```C
+//! struct svirtn {
+//!     struct svirtb rx;
+//!     struct svirtb tx;
+//! }
+//! ```
+//! The driver initializes each queue by following the device initialization
+//! sequence of the simple virtio block. Writing RESET to the status field of
+//! a queue resets the queue to the initial state. The size of a queue cannot
+//! be changed while the queue is ready, and a queue whose head or tail is out
+//! of the queue is reset.
+//!
+//! For simplicity of the implementation, the mmio region of the simple virtIO
+//! network always located on the 0xcafe1000.
+//!
+//! ### 2. Ring buffer entry
+//! The entries have the same layout as the entries of the simple virtio block,
+//! but the sector and the cmd fields are ignored.
+//! * tx: addr and size describe a frame to transmit. A frame larger than
+//!   [`MAX_FRAME_SIZE`] is invalid, and resets the queue.
+//! * rx: addr and size describe a buffer to receive a frame into. The device
+//!   writes a [`VirtIoNetHdr`] followed by the frame into the buffer. A frame
+//!   that does not fit in the buffer is truncated, and the header holds the
+//!   truncated length.
+//!
+//! ### 3. Notification
+//! Writing the head of the tx queue transmits the frames of the available
+//! entries to the backend. The frames that the backend receives are delivered
+//! into the available entries of the rx queue, whenever the driver writes the
+//! head of either queue. The frames received while no rx entry is available
+//! are kept in the device until the driver supplies one, up to
+//! [`MAX_PENDING_FRAMES`] frames. The frames received beyond it are dropped.
+//!
+//! [`Simple VirtIO Block`]: crate::dev::simple_virtio
+use crate::virtio::{VirtIoMmioHeader, VirtIoNetHdr, VirtIoNetMmioHeader, VirtIoStatus};
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc, vec, vec::Vec};
+use core::mem::{offset_of, size_of};
+use keos::{
+    addressing::{Kva, PAGE_MASK, PAGE_SIZE},
+    mm::Page,
+    sync::SpinLock,
+};
+use kev::{
+    vcpu::{GenericVCpuState, VmexitResult},
+    vm::Gpa,
+    vmcs::ActiveVmcs,
+    Probe, VmError,
+};
+use kev_project2::{
+    ept::EptMappingError,
+    keos_vm::pager::KernelVmPager,
+    vmexit::mmio::{self, MmioInfo, MmioRegion},
+};
+
+/// The guest physical address of the mmio area.
+const MMIO_BASE: usize = 0xcafe1000;
+
+/// The size of an entry of the ring buffer.
+const ENTRY_SIZE: usize = 32;
+
+/// The maximum size of a frame that the guest can transmit.
+pub const MAX_FRAME_SIZE: usize = 0x10000;
+
+/// The maximum number of the received frames kept in the device while no rx
+/// entry is available.
+pub const MAX_PENDING_FRAMES: usize = 256;
+
+/// A host-side backend of the network device.
+pub trait NetBackend
+where
+    Self: Send + Sync,
+{
+    /// Send a frame transmitted by the guest.
+    fn send(&mut self, frame: &[u8]);
+    /// Receive a frame to deliver to the guest, if exists.
+    fn recv(&mut self) -> Option<Vec<u8>>;
+}
+
+/// A backend that echoes the transmitted frames back to the guest.
+#[derive(Default)]
+pub struct Loopback {
+    frames: VecDeque<Vec<u8>>,
+}
+
+impl NetBackend for Loopback {
+    fn send(&mut self, frame: &[u8]) {
+        self.frames.push_back(frame.to_vec());
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        self.frames.pop_front()
+    }
+}
+
+struct SimpleVirtIoNetDevInner {
+    /// The mmio page, until it is mapped to the guest.
+    mmio_page: Option<Page>,
+    /// The kernel virtual address of the mmio page.
+    header: Kva,
+    backend: Box<dyn NetBackend>,
+    /// The frames received while no rx entry is available, up to
+    /// [`MAX_PENDING_FRAMES`].
+    pending: VecDeque<Vec<u8>>,
+}
+
+/// The simple virtio network device.
+///
+/// The device is shared by the vcpus, so every clone refers to the same
+/// device.
+#[derive(Clone)]
+pub struct SimpleVirtIoNetDev {
+    inner: Arc<SpinLock<SimpleVirtIoNetDevInner>>,
+}
+
+impl SimpleVirtIoNetDev {
+    /// Create a new network device connected to the `backend`.
+    pub fn new(backend: impl NetBackend + 'static) -> Self {
+        let mut mmio_page = Page::new();
+        // SAFETY: the header is on the mmio page that the device owns.
+        let header =
+            unsafe { &mut *(mmio_page.inner_mut().as_mut_ptr() as *mut VirtIoNetMmioHeader) };
+        reset(&mut header.rx);
+        reset(&mut header.tx);
+        Self {
+            inner: Arc::new(SpinLock::new(SimpleVirtIoNetDevInner {
+                header: mmio_page.kva(),
+                mmio_page: Some(mmio_page),
+                backend: Box::new(backend),
+                pending: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Attach the device to the vcpu of the `mmio_ctl`.
+    ///
+    /// The mmio page is mapped to the guest on the first attach.
+    pub fn attach(
+        &self,
+        pager: &mut KernelVmPager,
+        mmio_ctl: &mut mmio::Controller,
+    ) -> Result<(), EptMappingError> {
+        let mut inner = self.inner.lock();
+        let result = match inner.mmio_page.take() {
+            Some(page) => pager.map_mmio_page(Gpa::new(MMIO_BASE).unwrap(), page),
+            None => Ok(()),
+        };
+        inner.unlock();
+        result?;
+        mmio_ctl.register(self.clone());
+        Ok(())
+    }
+}
+
+impl SimpleVirtIoNetDevInner {
+    fn header(&mut self) -> &mut VirtIoNetMmioHeader {
+        // SAFETY: the mmio page is never freed while it is mapped to the guest.
+        unsafe { &mut *(self.header.into_usize() as *mut VirtIoNetMmioHeader) }
+    }
+
+    /// Transmit the frames of the available tx entries to the backend.
+    fn transmit(&mut self, p: &dyn Probe, vmcs: &ActiveVmcs) -> Option<()> {
+        while let Some((addr, size)) = pop_entry(&mut self.header().tx, p, vmcs)? {
+            if size > MAX_FRAME_SIZE {
+                return None;
+            }
+            let mut frame = vec![0; size];
+            read_guest(p, vmcs, addr, &mut frame)?;
+            self.backend.send(&frame);
+        }
+        Some(())
+    }
+
+    /// Deliver the received frames into the available rx entries.
+    fn deliver(&mut self, p: &dyn Probe, vmcs: &ActiveVmcs) -> Option<()> {
+        while let Some(frame) = self.backend.recv() {
+            if self.pending.len() < MAX_PENDING_FRAMES {
+                self.pending.push_back(frame);
+            }
+        }
+        if self.header().rx.status != VirtIoStatus::READY as u32 {
+            return Some(());
+        }
+        while !self.pending.is_empty() {
+            let Some((addr, size)) = pop_entry(&mut self.header().rx, p, vmcs)? else {
+                break;
+            };
+            let frame = self.pending.pop_front().unwrap();
+            let len = frame
+                .len()
+                .min(size.saturating_sub(size_of::<VirtIoNetHdr>()));
+            let hdr = VirtIoNetHdr { len: len as u64 };
+            // SAFETY: VirtIoNetHdr is a plain old data.
+            let hdr = unsafe {
+                core::slice::from_raw_parts(
+                    &hdr as *const _ as *const u8,
+                    size_of::<VirtIoNetHdr>(),
+                )
+            };
+            write_guest(p, vmcs, addr, &hdr[..hdr.len().min(size)])?;
+            write_guest(p, vmcs, addr + size_of::<VirtIoNetHdr>(), &frame[..len])?;
+        }
+        Some(())
+    }
+}
+
+impl mmio::MmioHandler for SimpleVirtIoNetDev {
+    fn region(&self) -> MmioRegion {
+        MmioRegion::new(
+            Gpa::new(MMIO_BASE).unwrap(),
+            size_of::<VirtIoNetMmioHeader>(),
+        )
+    }
+
+    fn handle(
+        &mut self,
+        p: &dyn Probe,
+        info: MmioInfo,
+        generic_vcpu_state: &mut GenericVCpuState,
+    ) -> Result<VmexitResult, VmError> {
+        let mmio::Direction::Write32 { dst, src } = info.direction else {
+            return Ok(VmexitResult::Ok);
+        };
+        let vmcs = &generic_vcpu_state.vmcs;
+        let offset = dst.into_usize() - MMIO_BASE;
+        let (is_tx, field) = if offset < offset_of!(VirtIoNetMmioHeader, tx) {
+            (false, offset)
+        } else {
+            (true, offset - offset_of!(VirtIoNetMmioHeader, tx))
+        };
+
+        let mut inner = self.inner.lock();
+        let header = inner.header();
+        let queue = if is_tx {
+            &mut header.tx
+        } else {
+            &mut header.rx
+        };
+        let ok = match field {
+            x if x == offset_of!(VirtIoMmioHeader, status) => {
+                match VirtIoStatus::try_from(src) {
+                    Ok(VirtIoStatus::DRIVEROK) => queue.status = src,
+                    Ok(VirtIoStatus::READY)
+                        if queue.queue_size != 0
+                            && (queue.queue_addr_hi != 0 || queue.queue_addr_lo != 0) =>
+                    {
+                        queue.status = src
+                    }
+                    Ok(VirtIoStatus::READY) => queue.status = VirtIoStatus::RESET as u32,
+                    _ => reset(queue),
+                }
+                Some(())
+            }
+            // The size cannot be changed under the entries in use.
+            x if x == offset_of!(VirtIoMmioHeader, queue_size) => {
+                if queue.status != VirtIoStatus::READY as u32 {
+                    queue.queue_size = src;
+                }
+                Some(())
+            }
+            x if x == offset_of!(VirtIoMmioHeader, queue_addr_hi) => {
+                queue.queue_addr_hi = src;
+                Some(())
+            }
+            x if x == offset_of!(VirtIoMmioHeader, queue_addr_lo) => {
+                queue.queue_addr_lo = src;
+                Some(())
+            }
+            x if x == offset_of!(VirtIoMmioHeader, queue_head) => {
+                queue.queue_head = src;
+                if is_tx {
+                    inner.transmit(p, vmcs)
+                } else {
+                    Some(())
+                }
+                .and_then(|_| inner.deliver(p, vmcs))
+            }
+            // The tail is updated only by the device.
+            _ => Some(()),
+        };
+        if ok.is_none() {
+            // The guest supplied an invalid entry.
+            let header = inner.header();
+            let queue = if is_tx {
+                &mut header.tx
+            } else {
+                &mut header.rx
+            };
+            queue.status = VirtIoStatus::RESET as u32;
+        }
+        inner.unlock();
+        Ok(VmexitResult::Ok)
+    }
+}
+
+/// Reset the queue to the initial state.
+fn reset(queue: &mut VirtIoMmioHeader) {
+    *queue = VirtIoMmioHeader {
+        status: VirtIoStatus::MAGIC as u32,
+        ..VirtIoMmioHeader::new()
+    };
+}
+
+/// Pop an available entry of the queue, and return the address and the size
+/// of its buffer.
+///
+/// Returns `Some(None)` if no entry is available, and `None` if the head or
+/// the tail is out of the queue or the entry is not accessible.
+fn pop_entry(
+    queue: &mut VirtIoMmioHeader,
+    p: &dyn Probe,
+    vmcs: &ActiveVmcs,
+) -> Option<Option<(Gpa, usize)>> {
+    if queue.status != VirtIoStatus::READY as u32 {
+        return Some(None);
+    }
+    if queue.queue_head >= queue.queue_size || queue.queue_tail >= queue.queue_size {
+        return None;
+    }
+    if queue.queue_head == queue.queue_tail {
+        return Some(None);
+    }
+    let base = ((queue.queue_addr_hi as usize) << 32) | queue.queue_addr_lo as usize;
+    let mut entry = [0; ENTRY_SIZE];
+    read_guest(
+        p,
+        vmcs,
+        Gpa::new(base + queue.queue_tail as usize * ENTRY_SIZE)?,
+        &mut entry,
+    )?;
+    queue.queue_tail = (queue.queue_tail + 1) % queue.queue_size;
+    Some(Some((
+        Gpa::new(u64::from_le_bytes(entry[0..8].try_into().unwrap()) as usize)?,
+        u64::from_le_bytes(entry[8..16].try_into().unwrap()) as usize,
+    )))
+}
+
+/// Copy the guest memory at `gpa` into `buf`.
+///
+/// The guest memory may not be contiguous in the host.
+fn read_guest(p: &dyn Probe, vmcs: &ActiveVmcs, mut gpa: Gpa, buf: &mut [u8]) -> Option<()> {
+    let mut off = 0;
+    while off < buf.len() {
+        let len = (PAGE_SIZE - (gpa.into_usize() & PAGE_MASK)).min(buf.len() - off);
+        let src = p.gpa2hva(vmcs, gpa)?;
+        // SAFETY: the range is in the guest page mapped at `src`.
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                src.into_usize() as *const u8,
+                buf[off..].as_mut_ptr(),
+                len,
+            );
+        }
+        off += len;
+        gpa += len;
+    }
+    Some(())
+}
+
+/// Copy `buf` into the guest memory at `gpa`.
+///
+/// The guest memory may not be contiguous in the host.
+fn write_guest(p: &dyn Probe, vmcs: &ActiveVmcs, mut gpa: Gpa, buf: &[u8]) -> Option<()> {
+    let mut off = 0;
+    while off < buf.len() {
+        let len = (PAGE_SIZE - (gpa.into_usize() & PAGE_MASK)).min(buf.len() - off);
+        let dst = p.gpa2hva(vmcs, gpa)?;
+        // SAFETY: the range is in the guest page mapped at `dst`.
+        unsafe {
+            core::ptr::copy_nonoverlapping(buf[off..].as_ptr(), dst.into_usize() as *mut u8, len);
+        }
+        off += len;
+        gpa += len;
+    }
+    Some(())
+}
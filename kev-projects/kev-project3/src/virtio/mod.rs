@@ -1,4 +1,4 @@
-//! Simple Virtio block device
+//! Simple Virtio devices
 pub mod virt_queue;
 
 /// The header of the virtio device.
@@ -36,6 +36,28 @@ impl VirtIoMmioHeader {
     }
 }
 
+/// The header of the simple virtio network device.
+///
+/// The device has two queues, each of which is configured through its own
+/// [`VirtIoMmioHeader`].
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct VirtIoNetMmioHeader {
+    /// Header of the receive queue
+    pub rx: VirtIoMmioHeader,
+    /// Header of the transmit queue
+    pub tx: VirtIoMmioHeader,
+}
+
+/// The header prepended to a received frame of the simple virtio network
+/// device.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct VirtIoNetHdr {
+    /// Length of the frame following the header.
+    pub len: u64,
+}
+
 /// A possible status of sVirtIO device.
 #[derive(Debug, PartialEq)]
 #[repr(u32)]
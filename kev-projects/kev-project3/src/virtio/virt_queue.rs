@@ -30,6 +30,37 @@ pub struct VirtQueueEntry {
     pub cmd: VirtQueueEntryCmd,
 }
 
+/// The size of a sector of the virtual disk.
+pub const SECTOR_SIZE: usize = 512;
+
+/// A run of the entries that access adjacent sectors with the same command.
+///
+/// A run can be served with a single access to the backing disk.
+#[derive(Clone, Debug)]
+pub struct VirtQueueRun {
+    /// The command of the entries.
+    pub cmd: VirtQueueEntryCmd,
+    /// The first sector of the run.
+    pub sector: usize,
+    /// The entries in the order of the sectors.
+    pub entries: Vec<VirtQueueEntry>,
+}
+
+impl VirtQueueRun {
+    /// Get the total size of the entries in bytes.
+    pub fn size(&self) -> usize {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+
+    /// Check whether `entry` continues this run.
+    fn is_followed_by(&self, entry: &VirtQueueEntry) -> bool {
+        let size = self.size();
+        self.cmd == entry.cmd
+            && size.is_multiple_of(SECTOR_SIZE)
+            && self.sector + size / SECTOR_SIZE == entry.sector
+    }
+}
+
 /// A container for holding virtqueue.
 #[repr(C)]
 pub struct VirtQueue<T>
@@ -166,6 +197,28 @@ impl<'a> VirtQueueFetcher<'a, &'static [VirtQueueEntry]> {
             None
         }
     }
+
+    /// Pop all entries of the virtqueue, grouped into runs.
+    ///
+    /// An entry joins the run of the previous entry if it has the same
+    /// command and accesses the sector right after the run. The runs are in
+    /// the order of the requests, so serving the runs one by one never
+    /// reorders a write with another access to the same sector.
+    pub fn drain_runs(&mut self) -> Vec<VirtQueueRun> {
+        let mut runs: Vec<VirtQueueRun> = Vec::new();
+        while let Some(entry) = self.pop_back() {
+            match runs.last_mut() {
+                Some(run) if run.is_followed_by(&entry) => run.entries.push(entry),
+                _ => runs.push(VirtQueueRun {
+                    cmd: entry.cmd,
+                    sector: entry.sector,
+                    entries: alloc::vec![entry],
+                }),
+            }
+        }
+        runs
+    }
+
     /// Acknowledge the consumed request.
     pub fn ack(self) -> Result<(), KernelError> {
         // The sequence of the update in this function
@@ -18,7 +18,11 @@ use kev_project2::{
 };
 use pager::KernelVmPager;
 
-use crate::dev::{simple_virtio::SimpleVirtIoBlockDev, X2Apic};
+use crate::dev::{
+    simple_virtio::SimpleVirtIoBlockDev,
+    simple_virtio_net::{Loopback, SimpleVirtIoNetDev},
+    X2Apic,
+};
 
 pub struct Gs;
 
@@ -52,6 +56,7 @@ impl kev_project1::vmexit::msr::Msr for Gs {
 /// The Vmstate of VmBase.
 pub struct VmState {
     virtio: Arc<SpinLock<SimpleVirtIoBlockDev>>,
+    virtio_net: SimpleVirtIoNetDev,
     pager: Arc<SpinLock<KernelVmPager>>,
     io_bmap: Arc<(Page, Page)>,
 }
@@ -85,9 +90,11 @@ impl VmState {
             ram_in_kib,
         )?));
         let virtio = Arc::new(SpinLock::new(SimpleVirtIoBlockDev::new()));
+        let virtio_net = SimpleVirtIoNetDev::new(Loopback::default());
 
         Some(VmState {
             virtio,
+            virtio_net,
             pager,
             io_bmap,
         })
@@ -113,6 +120,9 @@ impl kev::vm::VmState for VmState {
             &mut pager,
             &mut mmio_ctl,
         );
+        self.virtio_net
+            .attach(&mut pager, &mut mmio_ctl)
+            .expect("Failed to attach the network device.");
         pager.unlock();
         virtio.unlock();
 
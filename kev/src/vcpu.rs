@@ -1,14 +1,16 @@
 //! Virtual CPU implementation.
 use crate::{
     VmError,
-    vm::{Vm, VmOps, VmState},
+    probe::Probe,
+    vm::{BreakpointHit, Gpa, GuestPage, Gva, VCpuSnapshot, Vm, VmOps, VmState},
     vm_control::*,
-    vmcs::{ActiveVmcs, BasicExitReason, ExternalIntInfo, Field, Vmcs},
+    vmcs::{ActiveVmcs, BasicExitReason, ExternalIntInfo, Field, MsrBitmap, Vmcs},
 };
+use abyss::addressing::Pa;
 pub use abyss::{interrupt::GeneralPurposeRegisters, x86_64::*};
-use alloc::sync::Weak;
+use alloc::{boxed::Box, sync::Weak, vec::Vec};
 use core::{
-    arch::naked_asm,
+    arch::{naked_asm, x86_64::CpuidResult},
     sync::atomic::{AtomicBool, AtomicU64, Ordering},
 };
 use interrupt::IDT;
@@ -131,8 +133,106 @@ where
         &mut self,
         genenric_state: &mut GenericVCpuState,
     ) -> Result<VmexitResult, VmError>;
+    /// Capture the guest memory of this vcpu for a snapshot.
+    ///
+    /// The default implementation does not support snapshots.
+    fn snapshot_memory(&self) -> Result<Vec<GuestPage>, VmError> {
+        Err(VmError::VCpuError(Box::new("Snapshot is not supported.")))
+    }
+    /// Restore the guest memory captured by
+    /// [`VCpuState::snapshot_memory`].
+    ///
+    /// The default implementation does not support snapshots.
+    fn restore_memory(&mut self, _pages: &[GuestPage]) -> Result<(), VmError> {
+        Err(VmError::VCpuError(Box::new("Snapshot is not supported.")))
+    }
+    /// Take the guest pages written since the last call, and start tracking
+    /// the writes on them again.
+    ///
+    /// The default implementation does not support dirty page tracking.
+    fn take_dirty_pages(
+        &mut self,
+        _generic_state: &mut GenericVCpuState,
+    ) -> Result<Vec<Gpa>, VmError> {
+        Err(VmError::VCpuError(Box::new(
+            "Dirty page tracking is not supported.",
+        )))
+    }
+    /// Get the probe to access the guest memory of this vcpu.
+    ///
+    /// The breakpoints (see [`Vm::set_breakpoint`]) are placed through the
+    /// probe. The default implementation has no probe, which does not support
+    /// breakpoints.
+    fn probe(&self) -> Option<&dyn Probe> {
+        None
+    }
 }
 
+// The vmcs fields captured in a snapshot of a vcpu.
+//
+// Besides the guest-state area, this includes the event that is about to be
+// injected on the next vm entry and the interrupt-window exiting control, so
+// that an interrupt in flight is neither lost nor injected twice.
+const SNAPSHOT_FIELDS: &[Field] = &[
+    Field::GuestEsSelector,
+    Field::GuestCsSelector,
+    Field::GuestSsSelector,
+    Field::GuestDsSelector,
+    Field::GuestFsSelector,
+    Field::GuestGsSelector,
+    Field::GuestLdtrSelector,
+    Field::GuestTrSelector,
+    Field::GuestEsBase,
+    Field::GuestCsBase,
+    Field::GuestSsBase,
+    Field::GuestDsBase,
+    Field::GuestFsBase,
+    Field::GuestGsBase,
+    Field::GuestLdtrBase,
+    Field::GuestTrBase,
+    Field::GuestEsLimit,
+    Field::GuestCsLimit,
+    Field::GuestSsLimit,
+    Field::GuestDsLimit,
+    Field::GuestFsLimit,
+    Field::GuestGsLimit,
+    Field::GuestLdtrLimit,
+    Field::GuestTrLimit,
+    Field::GuestEsAccessRights,
+    Field::GuestCsAccessRights,
+    Field::GuestSsAccessRights,
+    Field::GuestDsAccessRights,
+    Field::GuestFsAccessRights,
+    Field::GuestGsAccessRights,
+    Field::GuestLdtrAccessRights,
+    Field::GuestTrAccessRights,
+    Field::GuestGdtrBase,
+    Field::GuestGdtrLimit,
+    Field::GuestIdtrBase,
+    Field::GuestIdtrLimit,
+    Field::GuestCr0,
+    Field::GuestCr3,
+    Field::GuestCr4,
+    Field::GuestDr7,
+    Field::GuestRsp,
+    Field::GuestRip,
+    Field::GuestRflags,
+    Field::GuestPendingDbgExceptions,
+    Field::GuestIa32SysenterCsMsr,
+    Field::GuestIa32SysenterEspMsr,
+    Field::GuestIa32SysenterEipMsr,
+    Field::GuestIa32Efer,
+    Field::GuestIa32Debugctl,
+    Field::GuestInterruptibilityState,
+    Field::GuestActivityState,
+    Field::Cr0ReadShadow,
+    Field::Cr4ReadShadow,
+    Field::ProcessorBasedVmexecControls,
+    Field::VmentryInterruptionInfo,
+    Field::VmentryExceptionErrCode,
+    Field::VmentryInstructionLength,
+];
+
 /// A visible state for VCpu.
 pub struct GenericVCpuState<'a> {
     /// The activated vmcs.
@@ -145,6 +245,8 @@ pub struct GenericVCpuState<'a> {
     id: usize,
     // Pending interrupts.
     pending_interrupts: &'a [AtomicU64; 4],
+    // The cpu that has no stale ept translations of this vcpu.
+    ept_cpu: &'a mut Option<usize>,
 }
 
 impl<'a> GenericVCpuState<'a> {
@@ -160,6 +262,73 @@ impl<'a> GenericVCpuState<'a> {
         let (index, ofs) = (vec / 64, vec & 63);
         self.pending_interrupts[index as usize].store(1 << ofs, Ordering::SeqCst);
     }
+
+    /// Get the cpuid entry of the vm for `leaf` and `subleaf`.
+    ///
+    /// Returns None if the vmm has not set an entry for them with
+    /// [`Vm::set_cpuid`], in which case the cpuid is handled as usual.
+    pub fn cpuid_entry(&self, leaf: u32, subleaf: u32) -> Option<CpuidResult> {
+        self.vm.upgrade()?.cpuid_entry(leaf, subleaf)
+    }
+
+    /// Get the general purpose registers of the guest.
+    ///
+    /// The rsp, rip and rflags of the guest are not in the general purpose
+    /// registers but in the vmcs ([`Field::GuestRsp`], [`Field::GuestRip`]
+    /// and [`Field::GuestRflags`]).
+    #[inline]
+    pub fn get_regs(&self) -> GeneralPurposeRegisters {
+        *self.gprs
+    }
+
+    /// Set the general purpose registers of the guest.
+    ///
+    /// The guest observes the registers on the next vm entry.
+    #[inline]
+    pub fn set_regs(&mut self, regs: GeneralPurposeRegisters) {
+        *self.gprs = regs;
+    }
+
+    /// Get the control register `cr{n}` of the guest.
+    ///
+    /// Only cr0, cr3 and cr4 are kept in the vmcs. Returns an error for the
+    /// other control registers.
+    pub fn get_cr(&self, n: usize) -> Result<u64, VmError> {
+        self.vmcs.read(cr_field(n)?)
+    }
+
+    /// Invalidate the cached translations of the extended page table before
+    /// the next vm entry.
+    ///
+    /// This must be called after revoking a permission of the extended page
+    /// table. Unlike [`ActiveVmcs::invept`], this also invalidates the
+    /// translations cached on the cpu that runs the vcpu next.
+    #[inline]
+    pub fn invalidate_ept(&mut self) {
+        *self.ept_cpu = None;
+    }
+
+    /// Set the control register `cr{n}` of the guest.
+    ///
+    /// The read shadows of cr0 and cr4 are left untouched, so the bits owned
+    /// by the host still read as the shadowed value in the guest. Only cr0,
+    /// cr3 and cr4 are kept in the vmcs. Returns an error for the other
+    /// control registers.
+    pub fn set_cr(&self, n: usize, value: u64) -> Result<(), VmError> {
+        self.vmcs.write(cr_field(n)?, value)
+    }
+}
+
+// Get the vmcs field that holds the control register `cr{n}` of the guest.
+fn cr_field(n: usize) -> Result<Field, VmError> {
+    match n {
+        0 => Ok(Field::GuestCr0),
+        3 => Ok(Field::GuestCr3),
+        4 => Ok(Field::GuestCr4),
+        _ => Err(VmError::VCpuError(Box::new(alloc::format!(
+            "cr{n} is not kept in the vmcs."
+        )))),
+    }
 }
 
 /// Virtual cpu.
@@ -179,6 +348,25 @@ pub struct VCpu<S: VmState + 'static> {
     vm: Weak<Vm<S>>,
     /// pending interrupt bitmask
     pending_interrupts: [AtomicU64; 4],
+    /// The cpu that has no stale ept translations of this vcpu.
+    ///
+    /// The cpus cache the ept translations per cpu, so the translations are
+    /// invalidated when the vcpu moves to another cpu.
+    ept_cpu: Option<usize>,
+    /// The breakpoint that this vcpu is stepping over.
+    stepping: Option<Stepping>,
+}
+
+// A breakpoint that a vcpu is stepping over.
+//
+// The int3 of the breakpoint is replaced with the original byte while the
+// vcpu executes the original instruction with the trap flag set. The int3 is
+// put back on the following debug exception.
+struct Stepping {
+    // The guest physical address of the breakpoint.
+    gpa: Gpa,
+    // Whether the guest has set the trap flag by itself.
+    guest_tf: bool,
 }
 
 impl<S: VmState + 'static> VCpu<S> {
@@ -196,9 +384,120 @@ impl<S: VmState + 'static> VCpu<S> {
                 AtomicU64::new(0),
                 AtomicU64::new(0),
             ],
+            ept_cpu: None,
+            stepping: None,
         }
     }
 
+    /// Get the general purpose registers of the guest.
+    ///
+    /// See [`GenericVCpuState::get_regs`] for details.
+    #[inline]
+    pub fn get_regs(&self) -> GeneralPurposeRegisters {
+        self.gprs
+    }
+
+    /// Set the general purpose registers of the guest.
+    ///
+    /// See [`GenericVCpuState::set_regs`] for details.
+    #[inline]
+    pub fn set_regs(&mut self, regs: GeneralPurposeRegisters) {
+        self.gprs = regs;
+    }
+
+    /// Get the control register `cr{n}` of the guest.
+    ///
+    /// See [`GenericVCpuState::get_cr`] for details.
+    pub fn get_cr(&mut self, n: usize) -> Result<u64, VmError> {
+        self.unpack_activate()?.generic_state.get_cr(n)
+    }
+
+    /// Set the control register `cr{n}` of the guest.
+    ///
+    /// See [`GenericVCpuState::set_cr`] for details.
+    pub fn set_cr(&mut self, n: usize, value: u64) -> Result<(), VmError> {
+        self.unpack_activate()?.generic_state.set_cr(n, value)
+    }
+
+    pub(crate) fn set_tsc_offset(&mut self, offset: i64) -> Result<(), VmError> {
+        self.unpack_activate()?
+            .generic_state
+            .vmcs
+            .write(Field::TscOffset, offset as u64)
+    }
+
+    pub(crate) fn snapshot(&mut self) -> Result<VCpuSnapshot, VmError> {
+        let Activated {
+            generic_state,
+            vcpu_state,
+            ..
+        } = &self.unpack_activate()?;
+        Ok(VCpuSnapshot {
+            gprs: *generic_state.gprs,
+            fields: SNAPSHOT_FIELDS
+                .iter()
+                .map(|field| generic_state.vmcs.read(*field).map(|v| (*field, v)))
+                .collect::<Result<_, _>>()?,
+            pending_interrupts: generic_state
+                .pending_interrupts
+                .each_ref()
+                .map(|v| v.load(Ordering::SeqCst)),
+            memory: vcpu_state.snapshot_memory()?,
+        })
+    }
+
+    pub(crate) fn restore(&mut self, snapshot: &VCpuSnapshot) -> Result<(), VmError> {
+        let Activated {
+            generic_state,
+            vcpu_state,
+            ..
+        } = &mut self.unpack_activate()?;
+        vcpu_state.restore_memory(&snapshot.memory)?;
+        for (field, v) in snapshot.fields.iter() {
+            generic_state.vmcs.write(*field, *v)?;
+        }
+        *generic_state.gprs = snapshot.gprs;
+        for (pending, v) in generic_state
+            .pending_interrupts
+            .iter()
+            .zip(snapshot.pending_interrupts)
+        {
+            pending.store(v, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn take_dirty_pages(&mut self) -> Result<Vec<Gpa>, VmError> {
+        let Activated {
+            generic_state,
+            vcpu_state,
+            ..
+        } = &mut self.unpack_activate()?;
+        vcpu_state.take_dirty_pages(generic_state)
+    }
+
+    pub(crate) fn gpa2hpa(&mut self, gpa: Gpa) -> Result<Pa, VmError> {
+        let Activated {
+            generic_state,
+            vcpu_state,
+            ..
+        } = &self.unpack_activate()?;
+        vcpu_state
+            .probe()
+            .ok_or(VmError::VCpuError(Box::new("Breakpoint is not supported.")))?
+            .gpa2hpa(&generic_state.vmcs, gpa)
+            .ok_or(VmError::VCpuError(Box::new("Gpa is not mapped.")))
+    }
+
+    pub(crate) fn intercept_exceptions(&mut self, bitmap: u32) -> Result<(), VmError> {
+        let activated = self.unpack_activate()?;
+        let vmcs = &activated.generic_state.vmcs;
+        vmcs.write(
+            Field::ExceptionBitmap,
+            vmcs.read(Field::ExceptionBitmap)? | bitmap as u64,
+        )
+    }
+
     pub(crate) fn unpack_activate(&mut self) -> Result<Activated<'_, S>, VmError> {
         let Self {
             vmcs,
@@ -208,6 +507,8 @@ impl<S: VmState + 'static> VCpu<S> {
             launched,
             vm,
             pending_interrupts,
+            ept_cpu,
+            stepping,
         } = self;
         Ok(Activated {
             generic_state: GenericVCpuState {
@@ -216,10 +517,13 @@ impl<S: VmState + 'static> VCpu<S> {
                 id: *vcpu_id,
                 vm: vm.clone(),
                 pending_interrupts,
+                ept_cpu,
             },
             vcpu_state: state,
             launched,
             vmcs,
+            vm,
+            stepping,
         })
     }
 }
@@ -247,10 +551,16 @@ pub(crate) struct Activated<'a, S: VmState + 'static> {
     pub(crate) vcpu_state: &'a mut S::VcpuState,
     vmcs: &'a mut Vmcs,
     launched: &'a mut bool,
+    vm: &'a Weak<Vm<S>>,
+    stepping: &'a mut Option<Stepping>,
 }
 
 impl<'a, S: VmState + 'static> Activated<'a, S> {
-    pub(crate) unsafe fn init_vcpu(&mut self, exception_bitmap: u32) -> Result<(), VmError> {
+    pub(crate) unsafe fn init_vcpu(
+        &mut self,
+        exception_bitmap: u32,
+        msr_bitmap: Option<&MsrBitmap>,
+    ) -> Result<(), VmError> {
         unsafe {
             let Self {
                 generic_state: GenericVCpuState { vmcs, .. },
@@ -291,7 +601,16 @@ impl<'a, S: VmState + 'static> Activated<'a, S> {
                     // Make sure there are secondary controls.
                     assert!(supported.contains(VmcsProcBasedVmexecCtl::ACTIVATE_SECONDARY_CTL));
                     enabled |= VmcsProcBasedVmexecCtl::ACTIVATE_SECONDARY_CTL;
+                    // The guest tsc is the host tsc plus the tsc offset of the vm.
+                    enabled |= VmcsProcBasedVmexecCtl::USETSCOFF;
+                    vmcs.write(Field::TscOffset, 0)?;
                     enabled |= vcpu_state.procbase_ctls();
+                    // Without the msr bitmap, every RDMSR and WRMSR causes a vmexit.
+                    if let Some(msr_bitmap) = msr_bitmap {
+                        assert!(supported.contains(VmcsProcBasedVmexecCtl::USEMSRBMP));
+                        enabled |= VmcsProcBasedVmexecCtl::USEMSRBMP;
+                        vmcs.write(Field::MsrBitmaps, msr_bitmap.pa().into_usize() as u64)?;
+                    }
                     vmcs.write(
                         Field::ProcessorBasedVmexecControls,
                         (enabled & supported).bits() as u64,
@@ -426,6 +745,8 @@ impl<'a, S: VmState + 'static> Activated<'a, S> {
             generic_state,
             vcpu_state,
             launched,
+            vm,
+            stepping,
             ..
         } = self;
         unsafe {
@@ -468,9 +789,12 @@ impl<'a, S: VmState + 'static> Activated<'a, S> {
                 // VM-instruction error field. See Chapter 30 for the error numbers.
 
                 // Inject pending interrupt if exists.
+                //
+                // While stepping over a breakpoint, the injection is deferred; otherwise,
+                // the trap flag would be pushed with the interrupt frame.
                 for (index, intr_bitmap) in generic_state.pending_interrupts.iter().enumerate() {
                     let v = intr_bitmap.load(Ordering::SeqCst);
-                    if v != 0 {
+                    if v != 0 && stepping.is_none() {
                         let guest_rflags = Rflags::from_bits_truncate(
                             generic_state
                                 .vmcs
@@ -512,6 +836,18 @@ impl<'a, S: VmState + 'static> Activated<'a, S> {
                     return Ok(VmexitResult::Kicked);
                 }
 
+                // Invalidate the stale ept translations cached on this cpu.
+                let cpu = intrinsics::cpuid();
+                if *generic_state.ept_cpu != Some(cpu) {
+                    let procbase_ctls2 = VmcsProcBasedSecondaryVmexecCtl::from_bits_unchecked(
+                        generic_state.vmcs.read(Field::SecondaryVmexecControls)? as u32,
+                    );
+                    if procbase_ctls2.contains(VmcsProcBasedSecondaryVmexecCtl::ENABLE_EPT) {
+                        generic_state.vmcs.invept()?;
+                    }
+                    *generic_state.ept_cpu = Some(cpu);
+                }
+
                 generic_state.vmcs.write(
                     Field::HostGsBase,
                     &mut kernel_gs::KERNEL_GS_BASES[intrinsics::cpuid()] as *mut kernel_gs::KernelGS
@@ -570,6 +906,16 @@ impl<'a, S: VmState + 'static> Activated<'a, S> {
                                     .expect("Failed to update ProcessorBasedVmexecControls.");
                                 Ok(())
                             }
+                            BasicExitReason::ExceptionOrNmi
+                                if Self::handle_debug_exception(
+                                    vm,
+                                    generic_state,
+                                    &**vcpu_state,
+                                    stepping,
+                                )? =>
+                            {
+                                Ok(())
+                            }
                             _ => match vcpu_state.handle_vmexit(generic_state) {
                                 Ok(VmexitResult::Ok) => Ok(()),
                                 r => return r,
@@ -586,6 +932,75 @@ impl<'a, S: VmState + 'static> Activated<'a, S> {
             }
         }
     }
+
+    // Handle the breakpoint (#BP) and debug (#DB) exceptions of the guest.
+    //
+    // On the int3 of a breakpoint, the original byte is put back and the vcpu
+    // executes the original instruction with the trap flag set. The following
+    // #DB re-arms the breakpoint. Other #BP and #DB are reinjected into the
+    // guest, unless the exception bitmap given to the vm builder intercepts
+    // them.
+    //
+    // Returns false if the exception must be handled by the vcpu state.
+    fn handle_debug_exception(
+        vm: &Weak<Vm<S>>,
+        generic_state: &mut GenericVCpuState,
+        vcpu_state: &S::VcpuState,
+        stepping: &mut Option<Stepping>,
+    ) -> Result<bool, VmError> {
+        let vm = vm
+            .upgrade()
+            .ok_or(VmError::VCpuError(Box::new("Vm is dropped.")))?;
+        let vmcs = &generic_state.vmcs;
+        // 24.9.2 Information for VM Exits Due to Vectored Events
+        let info = vmcs.read(Field::VmexitInterruptionInfo)?;
+        let vector = info as u8;
+        let rflags = Rflags::from_bits_truncate(vmcs.read(Field::GuestRflags)?);
+        match vector {
+            // #BP. The guest rip points to the int3.
+            3 => {
+                let rip = vmcs.read(Field::GuestRip)?;
+                let hpa = vcpu_state
+                    .probe()
+                    .zip(Gva::new(rip as usize))
+                    .and_then(|(probe, gva)| probe.gva2hpa(vmcs, gva));
+                if let Some(gpa) = hpa.and_then(|hpa| vm.restore_original_byte(hpa)) {
+                    vm.record_breakpoint_hit(BreakpointHit {
+                        vcpu: generic_state.id(),
+                        gpa,
+                        rip,
+                    });
+                    *stepping = Some(Stepping {
+                        gpa,
+                        guest_tf: rflags.contains(Rflags::TF),
+                    });
+                    vmcs.write(Field::GuestRflags, (rflags | Rflags::TF).bits())?;
+                    return Ok(true);
+                }
+            }
+            // #DB after executing the original instruction.
+            1 if stepping.is_some() => {
+                let Stepping { gpa, guest_tf } = stepping.take().unwrap();
+                vm.rearm_breakpoint(gpa);
+                if !guest_tf {
+                    vmcs.write(Field::GuestRflags, (rflags - Rflags::TF).bits())?;
+                    return Ok(true);
+                }
+            }
+            1 => (),
+            _ => return Ok(false),
+        }
+        if vm.exception_bitmap & (1 << vector) != 0 {
+            return Ok(false);
+        }
+        // Reinject the exception into the guest.
+        vmcs.write(Field::VmentryInterruptionInfo, (info & 0x7ff) | (1 << 31))?;
+        vmcs.write(
+            Field::VmentryInstructionLength,
+            vmcs.read(Field::VmexitInstructionLength)?,
+        )?;
+        Ok(true)
+    }
 }
 
 impl<'a, S: VmState> Drop for Activated<'a, S> {
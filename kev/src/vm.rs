@@ -1,12 +1,23 @@
 //! Virtual machine interface.
 use crate::{
     VmError,
-    vcpu::{GenericVCpuState, VCpu, VCpuOps, VCpuState},
-    vmcs::Field,
+    vcpu::{GeneralPurposeRegisters, GenericVCpuState, VCpu, VCpuOps, VCpuState},
+    vmcs::{Field, MsrBitmap},
+};
+use abyss::{
+    addressing::Pa,
+    dev::x86_64::apic::{IPIDest, Mode, send_ipi},
+};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, btree_map::Entry},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    arch::x86_64::{_rdtsc, CpuidResult},
+    sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
 };
-use abyss::dev::x86_64::apic::{IPIDest, Mode, send_ipi};
-use alloc::{boxed::Box, sync::Arc, vec::Vec};
-use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use keos::{
     sync::SpinLock,
     syscall::Registers,
@@ -175,6 +186,60 @@ pub struct MsrEntry {
     pub msr_value: u64,
 }
 
+/// A page of the guest memory in a [`VCpuSnapshot`].
+#[derive(Clone, Debug)]
+pub struct GuestPage {
+    /// The guest physical address of the page.
+    pub gpa: Gpa,
+    /// The contents of the page.
+    pub data: Box<[u8]>,
+}
+
+/// The state of a vcpu captured by [`Vm::snapshot`].
+#[derive(Clone, Debug)]
+pub struct VCpuSnapshot {
+    /// The general purpose registers of the vcpu.
+    pub gprs: GeneralPurposeRegisters,
+    /// The values of the guest-state and control fields of the vmcs.
+    pub fields: Vec<(Field, u64)>,
+    /// The bitmask of the interrupts waiting to be injected.
+    pub pending_interrupts: [u64; 4],
+    /// The guest memory of the vcpu, given by
+    /// [`VCpuState::snapshot_memory`].
+    pub memory: Vec<GuestPage>,
+}
+
+/// The state of a virtual machine captured by [`Vm::snapshot`].
+///
+/// A snapshot holds plain values only, so it can be kept in memory or
+/// serialized to be restored later with [`Vm::restore`].
+#[derive(Clone, Debug)]
+pub struct VmSnapshot {
+    /// The states of the vcpus, indexed by the vcpu id.
+    pub vcpus: Vec<VCpuSnapshot>,
+    /// The guest tsc when the snapshot is taken.
+    pub tsc: u64,
+}
+
+/// A hit of a breakpoint set by [`Vm::set_breakpoint`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BreakpointHit {
+    /// The id of the vcpu that hits the breakpoint.
+    pub vcpu: usize,
+    /// The guest physical address of the breakpoint.
+    pub gpa: Gpa,
+    /// The guest rip at the hit.
+    pub rip: u64,
+}
+
+// A breakpoint set by Vm::set_breakpoint.
+struct Breakpoint {
+    // The host physical address of the breakpoint.
+    hpa: Pa,
+    // The original byte replaced with the int3.
+    byte: u8,
+}
+
 #[doc(hidden)]
 pub enum VCpuRunningState {
     Halted,
@@ -188,6 +253,19 @@ pub enum VCpuRunningState {
 /// The virtual machine.
 pub struct Vm<S: VmState + 'static> {
     vcpu: Vec<Arc<SpinLock<VCpu<S>>>>,
+    // The msr bitmap referenced by the vmcs of the vcpus.
+    msr_bitmap: Option<Box<MsrBitmap>>,
+    // The tsc offset of the vcpus.
+    tsc_offset: AtomicI64,
+    // The exception bitmap given to the vm builder.
+    pub(crate) exception_bitmap: u32,
+    // The breakpoints, indexed by the guest physical address.
+    breakpoints: SpinLock<BTreeMap<Gpa, Breakpoint>>,
+    // The breakpoint hits not taken yet.
+    breakpoint_hits: SpinLock<Vec<BreakpointHit>>,
+    // The cpuid entries presented to the guest, indexed by the leaf and the
+    // subleaf.
+    cpuid: SpinLock<BTreeMap<(u32, Option<u32>), CpuidResult>>,
     pub(crate) state: S,
     pub(crate) exit_code: AtomicU64,
     vcpu_states: Vec<Arc<SpinLock<VCpuRunningState>>>,
@@ -204,6 +282,12 @@ impl<S: VmState + 'static> VmHandle<S> {
     pub(crate) fn new(vcpu: usize, state: S) -> Result<Self, S::Error> {
         let vm = Arc::new(Vm {
             vcpu: Vec::new(),
+            msr_bitmap: None,
+            tsc_offset: AtomicI64::new(0),
+            exception_bitmap: 0,
+            breakpoints: SpinLock::new(BTreeMap::new()),
+            breakpoint_hits: SpinLock::new(Vec::new()),
+            cpuid: SpinLock::new(BTreeMap::new()),
             state,
             exit_code: AtomicU64::new(0),
             vcpu_states: (0..vcpu)
@@ -270,6 +354,103 @@ impl<S: VmState + 'static> VmHandle<S> {
     pub fn start_bsp(&self) -> Result<(), VmError> {
         self.vm.start_vcpu(0, |_| {})
     }
+
+    /// Capture a snapshot of this vm.
+    ///
+    /// The running vcpus are kicked before taking the snapshot and resumed
+    /// after it. See [`Vm::snapshot`] for details.
+    pub fn snapshot(&self) -> Result<VmSnapshot, VmError> {
+        self.with_vcpus_kicked(|vm| vm.snapshot())
+    }
+
+    /// Restore this vm to the `snapshot`.
+    ///
+    /// The running vcpus are kicked before restoring the snapshot and resumed
+    /// after it, continuing from the restored state. See [`Vm::restore`] for
+    /// details.
+    pub fn restore(&self, snapshot: &VmSnapshot) -> Result<(), VmError> {
+        self.with_vcpus_kicked(|vm| vm.restore(snapshot))
+    }
+
+    /// Take the guest pages written since the last call.
+    ///
+    /// The running vcpus are kicked before taking the pages and resumed after
+    /// it. See [`Vm::take_dirty_bitmap`] for details.
+    pub fn take_dirty_bitmap(&self) -> Result<BTreeSet<Gpa>, VmError> {
+        self.with_vcpus_kicked(|vm| vm.take_dirty_bitmap())
+    }
+
+    /// Set the tsc offset of this vm.
+    ///
+    /// The running vcpus are kicked before setting the offset and resumed
+    /// after it. See [`Vm::set_tsc_offset`] for details.
+    pub fn set_tsc_offset(&self, offset: i64) -> Result<(), VmError> {
+        self.with_vcpus_kicked(|vm| vm.set_tsc_offset(offset))
+    }
+
+    /// Set a breakpoint at the guest physical address `gpa`.
+    ///
+    /// The running vcpus are kicked before setting the breakpoint and resumed
+    /// after it. See [`Vm::set_breakpoint`] for details.
+    pub fn set_breakpoint(&self, gpa: Gpa) -> Result<(), VmError> {
+        self.with_vcpus_kicked(|vm| vm.set_breakpoint(gpa))
+    }
+
+    /// Clear the breakpoint at the guest physical address `gpa`.
+    ///
+    /// The running vcpus are kicked before clearing the breakpoint and
+    /// resumed after it. See [`Vm::clear_breakpoint`] for details.
+    pub fn clear_breakpoint(&self, gpa: Gpa) -> Result<(), VmError> {
+        self.with_vcpus_kicked(|vm| vm.clear_breakpoint(gpa))
+    }
+
+    /// Take the breakpoint hits reported since the last call.
+    ///
+    /// See [`Vm::take_breakpoint_hits`] for details.
+    #[inline]
+    pub fn take_breakpoint_hits(&self) -> Vec<BreakpointHit> {
+        self.vm.take_breakpoint_hits()
+    }
+
+    /// Set the result of the cpuid instruction for `leaf` and `subleaf`.
+    ///
+    /// See [`Vm::set_cpuid`] for details.
+    #[inline]
+    pub fn set_cpuid(
+        &self,
+        leaf: u32,
+        subleaf: Option<u32>,
+        eax: u32,
+        ebx: u32,
+        ecx: u32,
+        edx: u32,
+    ) {
+        self.vm.set_cpuid(leaf, subleaf, eax, ebx, ecx, edx)
+    }
+
+    fn with_vcpus_kicked<R>(
+        &self,
+        f: impl FnOnce(&Vm<S>) -> Result<R, VmError>,
+    ) -> Result<R, VmError> {
+        if self.vm.exit_code.load(Ordering::SeqCst) >= 0x8000_0000_0000_0000 {
+            return Err(VmError::VCpuError(Box::new("Vm is exited.")));
+        }
+        let mut kicked = Vec::new();
+        for (id, state) in self.vm.vcpu_states.iter().enumerate() {
+            let guard = state.lock();
+            let running = matches!(&*guard, VCpuRunningState::Running { .. });
+            guard.unlock();
+            if running {
+                self.vm.kick_vcpu(id)?;
+                kicked.push(id);
+            }
+        }
+        let result = f(&self.vm);
+        for id in kicked {
+            self.vm.resume_vcpu(id);
+        }
+        result
+    }
 }
 
 impl<S: VmState + 'static> Drop for Vm<S> {
@@ -296,6 +477,253 @@ impl<S: VmState + 'static> Drop for Vm<S> {
 }
 
 impl<S: VmState + 'static> Vm<S> {
+    /// Capture a snapshot of this vm.
+    ///
+    /// The snapshot contains the general purpose registers, the guest-state
+    /// fields of the vmcs, and the guest memory of each vcpu. To be captured
+    /// consistently, no vcpu may be running: a kicked vcpu stops right before
+    /// the vm entry, after the previous vmexit is completely handled. At this
+    /// point, an interrupt may have been moved from the pending interrupts
+    /// into the vm-entry interruption information of the vmcs, or may be
+    /// waiting for an interrupt window. Both are captured, so the interrupt
+    /// is delivered exactly once after the restoration.
+    ///
+    /// Returns an error if a vcpu is running, or if the vcpu state does not
+    /// support snapshots (see [`VCpuState::snapshot_memory`]).
+    pub fn snapshot(&self) -> Result<VmSnapshot, VmError> {
+        self.ensure_not_running()?;
+        let mut vcpus = Vec::new();
+        for vcpu in self.vcpu.iter() {
+            let mut guard = vcpu.lock();
+            let snapshot = guard.snapshot();
+            guard.unlock();
+            vcpus.push(snapshot?);
+        }
+        Ok(VmSnapshot {
+            vcpus,
+            tsc: self.guest_tsc(),
+        })
+    }
+
+    /// Restore this vm to the `snapshot`.
+    ///
+    /// The snapshot must be taken from a vm with the same configuration and
+    /// the same guest memory layout. As with [`Vm::snapshot`], no vcpu may be
+    /// running. The vcpus continue from the restored state when they are
+    /// resumed.
+    ///
+    /// The tsc offset is adjusted so that the guest tsc continues from the
+    /// time of the snapshot, instead of jumping over the time passed since
+    /// the snapshot.
+    pub fn restore(&self, snapshot: &VmSnapshot) -> Result<(), VmError> {
+        self.ensure_not_running()?;
+        if snapshot.vcpus.len() != self.vcpu.len() {
+            return Err(VmError::VCpuError(Box::new(
+                "Snapshot has a different number of vcpus.",
+            )));
+        }
+        for (vcpu, snapshot) in self.vcpu.iter().zip(snapshot.vcpus.iter()) {
+            let mut guard = vcpu.lock();
+            let result = guard.restore(snapshot);
+            guard.unlock();
+            result?;
+        }
+        self.set_tsc_offset(snapshot.tsc.wrapping_sub(unsafe { _rdtsc() }) as i64)
+    }
+
+    /// Take the guest pages written since the last call.
+    ///
+    /// Returns the set of the guest frames that any vcpu has written, and
+    /// clears the set. The writes are tracked again from this point. As with
+    /// [`Vm::snapshot`], no vcpu may be running.
+    ///
+    /// Returns an error if the vcpu state does not support dirty page
+    /// tracking (see [`VCpuState::take_dirty_pages`]).
+    pub fn take_dirty_bitmap(&self) -> Result<BTreeSet<Gpa>, VmError> {
+        self.ensure_not_running()?;
+        let mut dirty = BTreeSet::new();
+        for vcpu in self.vcpu.iter() {
+            let mut guard = vcpu.lock();
+            let pages = guard.take_dirty_pages();
+            guard.unlock();
+            dirty.extend(pages?);
+        }
+        Ok(dirty)
+    }
+
+    /// Set the tsc offset of this vm.
+    ///
+    /// The guest reads the host tsc plus the `offset` with RDTSC, RDTSCP,
+    /// and RDMSR of IA32_TIME_STAMP_COUNTER. The offset is shared by all
+    /// vcpus, so the guest tsc is synchronized across the vcpus. As with
+    /// [`Vm::snapshot`], no vcpu may be running.
+    pub fn set_tsc_offset(&self, offset: i64) -> Result<(), VmError> {
+        self.ensure_not_running()?;
+        for vcpu in self.vcpu.iter() {
+            let mut guard = vcpu.lock();
+            let result = guard.set_tsc_offset(offset);
+            guard.unlock();
+            result?;
+        }
+        self.tsc_offset.store(offset, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Get the tsc offset of this vm.
+    #[inline]
+    pub fn tsc_offset(&self) -> i64 {
+        self.tsc_offset.load(Ordering::SeqCst)
+    }
+
+    /// Set a breakpoint at the guest physical address `gpa`.
+    ///
+    /// The byte at `gpa` is replaced with an int3, and the vcpus intercept
+    /// the breakpoint (#BP) and debug (#DB) exceptions from now on. When a
+    /// vcpu executes the int3, the hit is reported to the vmm (see
+    /// [`Vm::take_breakpoint_hits`]) and the vcpu continues as if there were
+    /// no breakpoint: the original byte is put back while the vcpu executes
+    /// the original instruction in single step, and the int3 is put back
+    /// after it. Meanwhile, the other vcpus may run over the breakpoint
+    /// without hitting it. As with [`Vm::snapshot`], no vcpu may be running.
+    ///
+    /// The guest memory is accessed through the probe of the vcpu state, so
+    /// the vcpu state must provide it (see [`VCpuState::probe`]). Returns an
+    /// error if the probe is missing, if `gpa` is not mapped, or if a
+    /// breakpoint is already set at `gpa`.
+    pub fn set_breakpoint(&self, gpa: Gpa) -> Result<(), VmError> {
+        self.ensure_not_running()?;
+        let mut guard = self.vcpu[0].lock();
+        let hpa = guard.gpa2hpa(gpa);
+        guard.unlock();
+        let hpa = hpa?;
+
+        let mut breakpoints = self.breakpoints.lock();
+        let result = match breakpoints.entry(gpa) {
+            Entry::Occupied(_) => Err(VmError::VCpuError(Box::new("Breakpoint is already set."))),
+            Entry::Vacant(entry) => {
+                // SAFETY: hpa is a guest memory mapped by the probe.
+                let byte = unsafe { core::mem::replace(byte_of(hpa), 0xcc) };
+                entry.insert(Breakpoint { hpa, byte });
+                Ok(())
+            }
+        };
+        breakpoints.unlock();
+        result?;
+
+        for vcpu in self.vcpu.iter() {
+            let mut guard = vcpu.lock();
+            let result = guard.intercept_exceptions((1 << 1) | (1 << 3));
+            guard.unlock();
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Clear the breakpoint at the guest physical address `gpa`.
+    ///
+    /// The original byte at `gpa` is put back. As with [`Vm::snapshot`], no
+    /// vcpu may be running. Returns an error if no breakpoint is set at
+    /// `gpa`.
+    pub fn clear_breakpoint(&self, gpa: Gpa) -> Result<(), VmError> {
+        self.ensure_not_running()?;
+        let mut breakpoints = self.breakpoints.lock();
+        let breakpoint = breakpoints.remove(&gpa);
+        breakpoints.unlock();
+        let Breakpoint { hpa, byte } =
+            breakpoint.ok_or(VmError::VCpuError(Box::new("Breakpoint is not set.")))?;
+        // SAFETY: hpa is a guest memory mapped by the probe.
+        unsafe { *byte_of(hpa) = byte };
+        Ok(())
+    }
+
+    /// Take the breakpoint hits reported since the last call.
+    ///
+    /// The hits are ordered by the time they are reported.
+    pub fn take_breakpoint_hits(&self) -> Vec<BreakpointHit> {
+        let mut hits = self.breakpoint_hits.lock();
+        let result = core::mem::take(&mut *hits);
+        hits.unlock();
+        result
+    }
+
+    /// Set the result of the cpuid instruction for `leaf` and `subleaf`.
+    ///
+    /// The guest reads `eax`, `ebx`, `ecx` and `edx` when it executes the
+    /// cpuid with `leaf` in eax and `subleaf` in ecx, instead of the values
+    /// of the host. This allows to present a customized cpu to the guest,
+    /// for example, by masking off the feature bits of leaf 1. If `subleaf`
+    /// is None, the entry applies to every subleaf of `leaf` that has no
+    /// entry of its own. The entry replaces the whole result, including the
+    /// initial apic id in leaf 1, and is shared by all vcpus. The leaves
+    /// without an entry are handled by the cpuid vmexit controller as usual.
+    ///
+    /// The entry takes effect from the next cpuid of the guest, so it can be
+    /// set while the vcpus are running.
+    pub fn set_cpuid(
+        &self,
+        leaf: u32,
+        subleaf: Option<u32>,
+        eax: u32,
+        ebx: u32,
+        ecx: u32,
+        edx: u32,
+    ) {
+        let mut cpuid = self.cpuid.lock();
+        cpuid.insert((leaf, subleaf), CpuidResult { eax, ebx, ecx, edx });
+        cpuid.unlock();
+    }
+
+    // Put back the original byte of the breakpoint at `hpa`.
+    //
+    // Returns the guest physical address of the breakpoint, or None if no
+    // breakpoint is set at `hpa`.
+    pub(crate) fn restore_original_byte(&self, hpa: Pa) -> Option<Gpa> {
+        let breakpoints = self.breakpoints.lock();
+        let gpa = breakpoints
+            .iter()
+            .find(|(_, breakpoint)| breakpoint.hpa == hpa)
+            .map(|(gpa, breakpoint)| {
+                // SAFETY: hpa is a guest memory mapped by the probe.
+                unsafe { *byte_of(hpa) = breakpoint.byte };
+                *gpa
+            });
+        breakpoints.unlock();
+        gpa
+    }
+
+    // Put back the int3 of the breakpoint at `gpa`, if it is not cleared.
+    pub(crate) fn rearm_breakpoint(&self, gpa: Gpa) {
+        let breakpoints = self.breakpoints.lock();
+        if let Some(breakpoint) = breakpoints.get(&gpa) {
+            // SAFETY: hpa is a guest memory mapped by the probe.
+            unsafe { *byte_of(breakpoint.hpa) = 0xcc };
+        }
+        breakpoints.unlock();
+    }
+
+    pub(crate) fn record_breakpoint_hit(&self, hit: BreakpointHit) {
+        let mut hits = self.breakpoint_hits.lock();
+        hits.push(hit);
+        hits.unlock();
+    }
+
+    // Get the current guest tsc.
+    fn guest_tsc(&self) -> u64 {
+        unsafe { _rdtsc() }.wrapping_add(self.tsc_offset() as u64)
+    }
+
+    fn ensure_not_running(&self) -> Result<(), VmError> {
+        for state in self.vcpu_states.iter() {
+            let guard = state.lock();
+            let running = matches!(&*guard, VCpuRunningState::Running { .. });
+            guard.unlock();
+            if running {
+                return Err(VmError::VCpuError(Box::new("VCpu is running.")));
+            }
+        }
+        Ok(())
+    }
+
     /// The main loop of a VCpu.
     pub fn vcpu_thread_work(
         vcpu: Arc<SpinLock<VCpu<S>>>,
@@ -417,6 +845,9 @@ where
     fn get_vcpu(&self, id: usize) -> Option<&dyn VCpuOps>;
     /// Resum the vcpu.
     fn resume_vcpu(&self, id: usize);
+    /// Get the cpuid entry for `leaf` and `subleaf`, set by
+    /// [`Vm::set_cpuid`].
+    fn cpuid_entry(&self, leaf: u32, subleaf: u32) -> Option<CpuidResult>;
 }
 
 impl<S: VmState + 'static> VmOps for Vm<S> {
@@ -501,6 +932,21 @@ impl<S: VmState + 'static> VmOps for Vm<S> {
     fn get_vcpu(&self, id: usize) -> Option<&dyn VCpuOps> {
         self.vcpu.get(id).map(|cpu| &**cpu as &dyn VCpuOps)
     }
+
+    fn cpuid_entry(&self, leaf: u32, subleaf: u32) -> Option<CpuidResult> {
+        let cpuid = self.cpuid.lock();
+        let entry = cpuid
+            .get(&(leaf, Some(subleaf)))
+            .or_else(|| cpuid.get(&(leaf, None)))
+            .copied();
+        cpuid.unlock();
+        entry
+    }
+}
+
+// Get the byte of the guest memory at `hpa`.
+unsafe fn byte_of<'a>(hpa: Pa) -> &'a mut u8 {
+    unsafe { &mut *(hpa.into_kva().into_usize() as *mut u8) }
 }
 
 impl<S: VmState> core::ops::Deref for Vm<S> {
@@ -514,6 +960,7 @@ impl<S: VmState> core::ops::Deref for Vm<S> {
 pub struct VmBuilder<S: VmState + 'static> {
     pub(crate) vm_handle: VmHandle<S>,
     exception_bitmap: u32,
+    msr_bitmap: Option<Box<MsrBitmap>>,
 }
 
 impl<S: VmState + 'static> VmBuilder<S> {
@@ -526,6 +973,7 @@ impl<S: VmState + 'static> VmBuilder<S> {
         VmHandle::new(vcpu, vmstate).map(|vm| VmBuilder {
             vm_handle: vm,
             exception_bitmap: 0,
+            msr_bitmap: None,
         })
     }
 
@@ -536,20 +984,40 @@ impl<S: VmState + 'static> VmBuilder<S> {
         self
     }
 
+    /// Add a msr bitmap to the builder.
+    ///
+    /// The bitmap is shared by all vcpus of the vm. Without the bitmap, every
+    /// msr access of the guest causes a vmexit.
+    #[inline]
+    pub fn msr_bitmap(mut self, bitmap: MsrBitmap) -> Self {
+        self.msr_bitmap = Some(Box::new(bitmap));
+        self
+    }
+
     /// Finalize this builder.
     #[inline]
     pub fn finalize(self) -> Result<VmHandle<S>, VmError> {
         let Self {
-            vm_handle,
+            mut vm_handle,
             exception_bitmap,
+            msr_bitmap,
         } = self;
         for vcpu in vm_handle.vm.vcpu.iter() {
             unsafe {
                 let mut guard = vcpu.lock();
-                guard.unpack_activate()?.init_vcpu(exception_bitmap)?;
+                guard
+                    .unpack_activate()?
+                    .init_vcpu(exception_bitmap, msr_bitmap.as_deref())?;
                 guard.unlock();
             }
         }
+        // SAFETY:
+        // vcpu is not running.
+        unsafe {
+            let vm = Arc::get_mut_unchecked(&mut vm_handle.vm);
+            vm.exception_bitmap = exception_bitmap;
+            vm.msr_bitmap = msr_bitmap;
+        }
         Ok(vm_handle)
     }
 }
@@ -99,7 +99,7 @@ pub enum InstructionError {
 /// Vmcs field.
 #[allow(missing_docs)]
 #[repr(i32)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Field {
     // 16bit fields
     Vpid = 0x00000000,
@@ -423,6 +423,81 @@ impl Vmcs {
     }
 }
 
+/// MSR bitmap.
+///
+/// When the msr bitmap is used, RDMSR and WRMSR cause vmexits only for the
+/// msrs whose bits are set in the bitmap. The bitmap covers the msrs in
+/// 0x0000_0000..=0x0000_1FFF and 0xC000_0000..=0xC000_1FFF, and accesses to
+/// the other msrs always cause vmexits.
+///
+/// A new bitmap traps every msr, as without the bitmap. Mark the msrs that the
+/// guest may access directly with [`MsrBitmap::pass_through`] or
+/// [`MsrBitmap::pass_through_read`], and install the bitmap with
+/// [`VmBuilder::msr_bitmap`].
+///
+/// ## Details
+/// See Intel® 64 and IA-32 Architectures Software Developer’s Manual, 24.6.9
+/// MSR-Bitmap Address.
+///
+/// [`VmBuilder::msr_bitmap`]: crate::vm::VmBuilder::msr_bitmap
+#[repr(align(4096))]
+pub struct MsrBitmap {
+    /// Read bitmap for low msrs, read bitmap for high msrs, write bitmap for
+    /// low msrs, and write bitmap for high msrs, in order.
+    bitmap: [u8; 0x1000],
+}
+
+impl Default for MsrBitmap {
+    fn default() -> Self {
+        MsrBitmap::new()
+    }
+}
+
+impl MsrBitmap {
+    /// Create a new msr bitmap that traps every msr.
+    pub fn new() -> Self {
+        Self {
+            bitmap: [0xff; 0x1000],
+        }
+    }
+
+    /// Let the guest read and write the `msr` without vmexits.
+    pub fn pass_through(&mut self, msr: u32) -> Result<(), VmError> {
+        let (offset, bit) = Self::locate(msr)?;
+        self.bitmap[offset] &= !bit;
+        self.bitmap[offset + 0x800] &= !bit;
+        Ok(())
+    }
+
+    /// Let the guest read the `msr` without vmexits, while writes to the
+    /// `msr` still cause vmexits.
+    pub fn pass_through_read(&mut self, msr: u32) -> Result<(), VmError> {
+        let (offset, bit) = Self::locate(msr)?;
+        self.bitmap[offset] &= !bit;
+        Ok(())
+    }
+
+    /// Get the physical address of this bitmap.
+    pub fn pa(&self) -> Pa {
+        Kva::new(self as *const _ as usize).unwrap().into_pa()
+    }
+
+    // Get the byte offset in the read bitmap and the bit of the `msr`.
+    fn locate(msr: u32) -> Result<(usize, u8), VmError> {
+        let base = match msr {
+            0..=0x1fff => 0,
+            0xc000_0000..=0xc000_1fff => 0x400,
+            _ => {
+                return Err(VmError::ControllerError(Box::new(format!(
+                    "msr {msr:#x} is not covered by the msr bitmap."
+                ))));
+            }
+        };
+        let index = (msr & 0x1fff) as usize;
+        Ok((base + index / 8, 1 << (index % 8)))
+    }
+}
+
 /// A representation of active vmcs.
 pub struct ActiveVmcs {
     _p: (),
@@ -688,6 +763,32 @@ impl ActiveVmcs {
         }
     }
 
+    /// Invalidate the cached translations derived from the extended page
+    /// table of this vmcs on the current cpu.
+    ///
+    /// This must be called after revoking a permission of the extended page
+    /// table, as the cpu may use the cached translations until invalidated.
+    pub fn invept(&self) -> Result<(), VmError> {
+        unsafe {
+            let err: i8;
+            // Single-context invalidation of the current eptp.
+            let descriptor: [u64; 2] = [self.read(Field::Eptptr)?, 0];
+            asm!(
+                "clc",
+                "invept {}, [{}]",
+                "setna {}",
+                in(reg) 1u64,
+                in(reg) &descriptor,
+                out(reg_byte) err
+            );
+            if err != 0 {
+                Err(VmError::VmxOperationError(Vmcs::instruction_error()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
     /// Forward to the next instruction.
     pub fn forward_rip(&self) -> Result<(), VmError> {
         self.write(